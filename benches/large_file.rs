@@ -0,0 +1,77 @@
+//! A keystroke in a 10MB file, as the editor tracks it
+//!
+//! Measures the work that grows with the file: the editor's copy of the text
+//! and its lazy highlighting caught up, and the change for the language server
+//! worked out. Run with `cargo bench --bench large_file`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use script_editor_plugin::bench::TrackedFile;
+/// About 10MB of Rust-looking source
+fn large_source() -> String {
+    let line = "    let value = compute(index, \"some text\", 42); // ünïcode ✓\n";
+    let mut text = String::with_capacity(10 * 1024 * 1024 + line.len());
+    let mut index = 0;
+    while text.len() < 10 * 1024 * 1024 {
+        if index % 20 == 0 {
+            text.push_str(&format!("fn function_{}() {{\n", index));
+        }
+        text.push_str(line);
+        if index % 20 == 19 {
+            text.push_str("}\n\n");
+        }
+        index += 1;
+    }
+    text
+}
+
+fn large_file(c: &mut Criterion) {
+    let text = large_source();
+
+    c.bench_function("open 10MB file", |b| b.iter(|| TrackedFile::open(black_box(&text))));
+
+    let file = TrackedFile::open(&text);
+    let middle = (0..=text.len() / 2).rev().find(|ix| text.is_char_boundary(*ix)).unwrap();
+    let mut typed = text.clone();
+    typed.insert(middle, 'x');
+    c.bench_function("type a character in 10MB file", |b| {
+        b.iter_batched(
+            || file.clone(),
+            |mut file| {
+                file.edit(black_box(&typed));
+                file
+            },
+            criterion::BatchSize::LargeInput,
+        )
+    });
+
+    // A line pasted in and one deleted, which shift every line below
+    let line_start = text[..middle].rfind('\n').map_or(0, |ix| ix + 1);
+    let mut pasted = text.clone();
+    pasted.insert_str(line_start, "    let pasted = compute(0, \"pasted\", 1);\n");
+    c.bench_function("paste a line in 10MB file", |b| {
+        b.iter_batched(
+            || file.clone(),
+            |mut file| {
+                file.edit(black_box(&pasted));
+                file
+            },
+            criterion::BatchSize::LargeInput,
+        )
+    });
+    let line_end = line_start + text[line_start..].find('\n').unwrap() + 1;
+    let mut deleted = text.clone();
+    deleted.replace_range(line_start..line_end, "");
+    c.bench_function("delete a line in 10MB file", |b| {
+        b.iter_batched(
+            || file.clone(),
+            |mut file| {
+                file.edit(black_box(&deleted));
+                file
+            },
+            criterion::BatchSize::LargeInput,
+        )
+    });
+}
+
+criterion_group!(benches, large_file);
+criterion_main!(benches);
@@ -1,26 +1,34 @@
 //! # Script Editor Plugin
 //!
 //! This plugin provides a professional code editor with LSP support for various programming languages.
-//! It supports standalone script files in multiple languages including Rust, JavaScript, TypeScript, Python, Lua, TOML, and Markdown.
+//! It supports standalone script files in multiple languages including Rust, JavaScript, TypeScript, Python, Lua, Go, Zig, C/C++, TOML, YAML, JSON, and Markdown.
 //!
 //! ## File Types
 //!
 //! - **Rust Script** (.rs)
-//! - **JavaScript** (.js)
-//! - **TypeScript** (.ts)
+//! - **JavaScript** (.js, .jsx)
+//! - **TypeScript** (.ts, .tsx)
 //! - **Python Script** (.py)
 //! - **Lua Script** (.lua)
+//! - **Go** (.go)
+//! - **Zig** (.zig)
+//! - **C** (.c) and **C Header** (.h)
+//! - **C++** (.cpp, .cc, .cxx) and **C++ Header** (.hpp, .hh)
 //! - **TOML Configuration** (.toml)
+//! - **YAML** (.yaml, .yml)
+//! - **JSON** (.json) and **JSON with Comments** (.jsonc)
 //! - **Markdown Document** (.md)
 //!
 //! ## Editors
 //!
 //! - **Script Editor**: Code editor with file explorer, syntax highlighting, and LSP support
 
+use parking_lot::Mutex;
 use plugin_editor_api::*;
 use serde_json::json;
-use std::path::PathBuf;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Once};
 use gpui::*;
 use ui::dock::PanelView;
 
@@ -35,14 +43,335 @@ pub use script_editor::{
     TextEditor,
     ScriptEditorMode,
     DiffFileEntry,
+    SaveAllSummary,
 };
 
+/// For `benches/`, which only see what the crate exports
+#[doc(hidden)]
+pub use script_editor::bench;
+
+/// A file type the plugin registers: the source files, or the headers, of
+/// one of the editor's languages, with that language's extensions.
+///
+/// `FileTypeDefinition` holds a single extension, so a type going by several
+/// is registered once per extension, each time under its one id.
+struct PluginFileType {
+    id: &'static str,
+    language: &'static str,
+    /// For the language's header extensions rather than its sources
+    headers: bool,
+    display_name: &'static str,
+    icon: ui::IconName,
+    color: u32,
+    default_content: &'static str,
+    category: &'static str,
+}
+
+impl PluginFileType {
+    fn extensions(&self) -> Vec<&'static str> {
+        let Some(language) = script_editor::languages::language(self.language) else {
+            return Vec::new();
+        };
+        match self.headers {
+            true => language.headers.to_vec(),
+            false => language.sources().collect(),
+        }
+    }
+
+    fn definitions(&self) -> impl Iterator<Item = FileTypeDefinition> + '_ {
+        self.extensions().into_iter().map(|extension| FileTypeDefinition {
+            id: FileTypeId::new(self.id),
+            extension: extension.to_string(),
+            display_name: self.display_name.to_string(),
+            icon: self.icon.clone(),
+            color: gpui::rgb(self.color).into(),
+            structure: FileStructure::Standalone,
+            default_content: json!(self.default_content),
+            categories: vec![self.category.to_string()],
+        })
+    }
+}
+
+const FILE_TYPES: [PluginFileType; 16] = [
+    PluginFileType {
+        id: "rust_script",
+        language: "rust",
+        headers: false,
+        display_name: "Rust",
+        icon: ui::IconName::RustLang,
+        color: 0xFF5722,
+        default_content: "// New Rust script\n",
+        category: "Scripts",
+    },
+    PluginFileType {
+        id: "javascript",
+        language: "javascript",
+        headers: false,
+        display_name: "JavaScript",
+        icon: ui::IconName::Code,
+        color: 0xF7DF1E,
+        default_content: "// New JavaScript file\n",
+        category: "Scripts",
+    },
+    PluginFileType {
+        id: "typescript",
+        language: "typescript",
+        headers: false,
+        display_name: "TypeScript",
+        icon: ui::IconName::Code,
+        color: 0x3178C6,
+        default_content: "// New TypeScript file\n",
+        category: "Scripts",
+    },
+    PluginFileType {
+        id: "python",
+        language: "python",
+        headers: false,
+        display_name: "Python Script",
+        icon: ui::IconName::Code,
+        color: 0x3776AB,
+        default_content: "# New Python script\n",
+        category: "Scripts",
+    },
+    PluginFileType {
+        id: "lua",
+        language: "lua",
+        headers: false,
+        display_name: "Lua Script",
+        icon: ui::IconName::Code,
+        color: 0x2196F3,
+        default_content: "-- New Lua script\n",
+        category: "Scripts",
+    },
+    PluginFileType {
+        id: "go",
+        language: "go",
+        headers: false,
+        display_name: "Go",
+        icon: ui::IconName::Code,
+        color: 0x00ADD8,
+        default_content: "package main\n\nfunc main() {\n}\n",
+        category: "Scripts",
+    },
+    PluginFileType {
+        id: "zig",
+        language: "zig",
+        headers: false,
+        display_name: "Zig",
+        icon: ui::IconName::Code,
+        color: 0xF7A41D,
+        default_content: "// New Zig file\n\npub fn main() void {}\n",
+        category: "Scripts",
+    },
+    PluginFileType {
+        id: "c",
+        language: "c",
+        headers: false,
+        display_name: "C",
+        icon: ui::IconName::Code,
+        color: 0x5C6BC0,
+        default_content: "// New C file\n\nint main(void) {\n    return 0;\n}\n",
+        category: "Scripts",
+    },
+    PluginFileType {
+        id: "c_header",
+        language: "c",
+        headers: true,
+        display_name: "C Header",
+        icon: ui::IconName::Code,
+        color: 0x5C6BC0,
+        default_content: "#pragma once\n",
+        category: "Scripts",
+    },
+    PluginFileType {
+        id: "cpp",
+        language: "cpp",
+        headers: false,
+        display_name: "C++",
+        icon: ui::IconName::Code,
+        color: 0x00599C,
+        default_content: "// New C++ file\n\nint main() {\n    return 0;\n}\n",
+        category: "Scripts",
+    },
+    PluginFileType {
+        id: "cpp_header",
+        language: "cpp",
+        headers: true,
+        display_name: "C++ Header",
+        icon: ui::IconName::Code,
+        color: 0x00599C,
+        default_content: "#pragma once\n",
+        category: "Scripts",
+    },
+    PluginFileType {
+        id: "toml",
+        language: "toml",
+        headers: false,
+        display_name: "TOML Configuration",
+        icon: ui::IconName::Page,
+        color: 0x9E9E9E,
+        default_content: "# TOML configuration file\n",
+        category: "Data",
+    },
+    PluginFileType {
+        id: "yaml",
+        language: "yaml",
+        headers: false,
+        display_name: "YAML",
+        icon: ui::IconName::Settings,
+        color: 0xCB171E,
+        default_content: "# New YAML file\n",
+        category: "Data",
+    },
+    PluginFileType {
+        id: "json",
+        language: "json",
+        headers: false,
+        display_name: "JSON",
+        icon: ui::IconName::Settings,
+        color: 0xFBC02D,
+        default_content: "{\n}\n",
+        category: "Data",
+    },
+    PluginFileType {
+        id: "jsonc",
+        language: "jsonc",
+        headers: false,
+        display_name: "JSON with Comments",
+        icon: ui::IconName::Settings,
+        color: 0xFBC02D,
+        default_content: "// JSON with comments\n{\n}\n",
+        category: "Data",
+    },
+    PluginFileType {
+        id: "markdown",
+        language: "markdown",
+        headers: false,
+        display_name: "Markdown Document",
+        icon: ui::IconName::Page,
+        color: 0xFF5722,
+        default_content: "# New Document\n",
+        category: "Documents",
+    },
+];
+
+/// Ids earlier versions registered for file types since merged into others,
+/// still opened here
+const RETIRED_FILE_TYPE_IDS: [&str; 1] = ["cpp_cc"];
+
 /// The Script Editor Plugin
-pub struct ScriptEditorPlugin;
+pub struct ScriptEditorPlugin {
+    /// Editors created by this plugin, by the file each was opened for
+    editors: Mutex<HashMap<PathBuf, WeakEntity<ScriptEditorPanel>>>,
+    /// Set once the app has been asked to close every editor when it quits
+    quit_hook: Once,
+}
 
 impl Default for ScriptEditorPlugin {
     fn default() -> Self {
-        Self
+        Self {
+            editors: Mutex::new(HashMap::new()),
+            quit_hook: Once::new(),
+        }
+    }
+}
+
+/// The host drops the plugin when it unloads it, with no app context left to
+/// close editors through; any server still running is stopped here instead.
+impl Drop for ScriptEditorPlugin {
+    fn drop(&mut self) {
+        // Only a plugin that opened editors can have started servers
+        if !self.quit_hook.is_completed() {
+            return;
+        }
+        let count = script_editor::lsp::shut_down_all_servers();
+        log::info!("Script Editor Plugin unloaded, stopped {} language servers", count);
+    }
+}
+
+impl ScriptEditorPlugin {
+    /// The file type of `path`, by extension (ignoring case); the id it's registered under.
+    pub fn file_type_for_path(&self, path: &Path) -> Option<FileTypeId> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        FILE_TYPES
+            .iter()
+            .find(|file_type| file_type.extensions().contains(&extension.as_str()))
+            .map(|file_type| FileTypeId::new(file_type.id))
+    }
+
+    /// Listen to the events of the script editor opened for `path`, as with
+    /// `ScriptEditorPanel::plugin_subscribe`, or `None` when it isn't open.
+    ///
+    /// Drop the subscription to stop listening; closing the editor ends it too.
+    pub fn subscribe(
+        &self,
+        path: &Path,
+        callback: impl FnMut(&TextEditorEvent, &mut App) + 'static,
+        cx: &mut App,
+    ) -> Option<Subscription> {
+        let editor = self.editors.lock().get(path)?.upgrade()?;
+        Some(ScriptEditorPanel::plugin_subscribe(&editor, callback, cx))
+    }
+
+    /// Close the script editor opened for `path`, stopping its language
+    /// servers and file watches; false if none is open for it.
+    ///
+    /// Editors the host drops are shut down the same way when they're released.
+    pub fn close_editor(&self, path: &Path, cx: &mut App) -> bool {
+        let Some(editor) = self.editors.lock().remove(path) else {
+            return false;
+        };
+        match editor.upgrade() {
+            Some(editor) => {
+                editor.update(cx, |editor, cx| editor.shut_down(cx));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Close every script editor, e.g. before the plugin is unloaded, so no
+    /// language server outlives it. Servers get a moment to exit after
+    /// `shutdown` and are killed if they don't; this waits for them.
+    ///
+    /// Runs by itself when the app quits.
+    pub fn close_all_editors(&self, cx: &mut App) {
+        let editors: Vec<Entity<ScriptEditorPanel>> = self
+            .editors
+            .lock()
+            .drain()
+            .filter_map(|(_, editor)| editor.upgrade())
+            .collect();
+        let count = editors.len();
+        for editor in editors {
+            editor.update(cx, |editor, cx| editor.release(cx));
+        }
+        // Every server, so one an editor had handed off to the background goes too
+        let servers = script_editor::lsp::shut_down_all_servers();
+        log::info!("Closed {} script editors and {} language servers", count, servers);
+    }
+
+    /// Save every unsaved file in every open script editor, e.g. before a build.
+    ///
+    /// Files that fail to save are listed in the summary; the others are saved regardless.
+    pub fn save_all(&self, cx: &mut App) -> SaveAllSummary {
+        // Saving emits events whose handlers may reach back into the plugin,
+        // so the lock is not held while editors save
+        let editors: Vec<Entity<ScriptEditorPanel>> = {
+            let mut editors = self.editors.lock();
+            editors.retain(|_, editor| editor.upgrade().is_some());
+            editors.values().filter_map(|editor| editor.upgrade()).collect()
+        };
+
+        let mut summary = SaveAllSummary::default();
+        for editor in editors {
+            summary.extend(editor.update(cx, |editor, cx| editor.save_all(cx)));
+        }
+        for (path, error) in &summary.errors {
+            log::error!("Failed to save {:?}: {}", path, error);
+        }
+        log::info!("Save All: {}", summary.message());
+        summary
     }
 }
 
@@ -58,93 +387,21 @@ impl EditorPlugin for ScriptEditorPlugin {
     }
 
     fn file_types(&self) -> Vec<FileTypeDefinition> {
-        vec![
-            FileTypeDefinition {
-                id: FileTypeId::new("rust_script"),
-                extension: "rs".to_string(),
-                display_name: "Rust".to_string(),
-                icon: ui::IconName::RustLang,
-                color: gpui::rgb(0xFF5722).into(),
-                structure: FileStructure::Standalone,
-                default_content: json!("// New Rust script\n"),
-                categories: vec!["Scripts".to_string()],
-            },
-            FileTypeDefinition {
-                id: FileTypeId::new("javascript"),
-                extension: "js".to_string(),
-                display_name: "JavaScript".to_string(),
-                icon: ui::IconName::Code,
-                color: gpui::rgb(0xF7DF1E).into(),
-                structure: FileStructure::Standalone,
-                default_content: json!("// New JavaScript file\n"),
-                categories: vec!["Scripts".to_string()],
-            },
-            FileTypeDefinition {
-                id: FileTypeId::new("typescript"),
-                extension: "ts".to_string(),
-                display_name: "TypeScript".to_string(),
-                icon: ui::IconName::Code,
-                color: gpui::rgb(0x3178C6).into(),
-                structure: FileStructure::Standalone,
-                default_content: json!("// New TypeScript file\n"),
-                categories: vec!["Scripts".to_string()],
-            },
-            FileTypeDefinition {
-                id: FileTypeId::new("python"),
-                extension: "py".to_string(),
-                display_name: "Python Script".to_string(),
-                icon: ui::IconName::Code,
-                color: gpui::rgb(0x3776AB).into(),
-                structure: FileStructure::Standalone,
-                default_content: json!("# New Python script\n"),
-                categories: vec!["Scripts".to_string()],
-            },
-            FileTypeDefinition {
-                id: FileTypeId::new("lua"),
-                extension: "lua".to_string(),
-                display_name: "Lua Script".to_string(),
-                icon: ui::IconName::Code,
-                color: gpui::rgb(0x2196F3).into(),
-                structure: FileStructure::Standalone,
-                default_content: json!("-- New Lua script\n"),
-                categories: vec!["Scripts".to_string()],
-            },
-            FileTypeDefinition {
-                id: FileTypeId::new("toml"),
-                extension: "toml".to_string(),
-                display_name: "TOML Configuration".to_string(),
-                icon: ui::IconName::Page,
-                color: gpui::rgb(0x9E9E9E).into(),
-                structure: FileStructure::Standalone,
-                default_content: json!("# TOML configuration file\n"),
-                categories: vec!["Data".to_string()],
-            },
-            FileTypeDefinition {
-                id: FileTypeId::new("markdown"),
-                extension: "md".to_string(),
-                display_name: "Markdown Document".to_string(),
-                icon: ui::IconName::Page,
-                color: gpui::rgb(0xFF5722).into(),
-                structure: FileStructure::Standalone,
-                default_content: json!("# New Document\n"),
-                categories: vec!["Documents".to_string()],
-            },
-        ]
+        FILE_TYPES.iter().flat_map(PluginFileType::definitions).collect()
     }
 
     fn editors(&self) -> Vec<EditorMetadata> {
+        let supported_file_types = FILE_TYPES
+            .iter()
+            .map(|file_type| file_type.id)
+            .chain(RETIRED_FILE_TYPE_IDS)
+            .map(FileTypeId::new)
+            .collect();
+
         vec![EditorMetadata {
             id: EditorId::new("script-editor"),
             display_name: "Script Editor".into(),
-            supported_file_types: vec![
-                FileTypeId::new("rust_script"),
-                FileTypeId::new("javascript"),
-                FileTypeId::new("typescript"),
-                FileTypeId::new("python"),
-                FileTypeId::new("lua"),
-                FileTypeId::new("toml"),
-                FileTypeId::new("markdown"),
-            ],
+            supported_file_types,
         }]
     }
 
@@ -155,13 +412,34 @@ impl EditorPlugin for ScriptEditorPlugin {
 
 impl EditorPluginEditor for ScriptEditorPlugin {
     fn register_editors(&'static self, registry: &mut EditorFactoryRegistry) {
-        registry.register_fn(EditorId::new("script-editor"), |file_path, window, cx| {
+        registry.register_fn(EditorId::new("script-editor"), move |file_path, window, cx| {
+            self.quit_hook.call_once(|| {
+                cx.on_app_quit(move |cx| {
+                    self.close_all_editors(cx);
+                    async {}
+                })
+                .detach();
+            });
             let panel = cx.new(|cx| ScriptEditorPanel::new(window, cx));
 
             panel.update(cx, |editor, ecx| {
                 editor.open_file(file_path.clone(), window, ecx);
             });
 
+            self.editors.lock().insert(file_path.clone(), panel.downgrade());
+            // However the host closes the tab, the panel's servers go with it
+            let panel_id = panel.entity_id();
+            let closed_path = file_path.clone();
+            cx.observe_release(&panel, move |panel, cx| {
+                let mut editors = self.editors.lock();
+                if editors.get(&closed_path).is_some_and(|editor| editor.entity_id() == panel_id) {
+                    editors.remove(&closed_path);
+                }
+                drop(editors);
+                panel.shut_down(cx);
+            })
+            .detach();
+
             let panel_arc: Arc<dyn ui::dock::PanelView> = Arc::new(panel.clone());
             log::info!("Created script editor instance for {:?}", file_path);
             Ok(panel_arc)
@@ -183,3 +461,45 @@ impl EditorPluginSubsystems for ScriptEditorPlugin {
 }
 
 export_plugin!(ScriptEditorPlugin);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_type_for_path() {
+        let plugin = ScriptEditorPlugin::default();
+        for (path, id) in [
+            ("main.c", "c"),
+            ("util.h", "c_header"),
+            ("main.cpp", "cpp"),
+            ("lib.cc", "cpp"),
+            ("lib.cxx", "cpp"),
+            ("lib.hpp", "cpp_header"),
+            ("lib.hh", "cpp_header"),
+            ("MAIN.CPP", "cpp"),
+            ("config.yml", "yaml"),
+            ("main.rs", "rust_script"),
+        ] {
+            assert_eq!(plugin.file_type_for_path(Path::new(path)), Some(FileTypeId::new(id)), "{}", path);
+        }
+        assert_eq!(plugin.file_type_for_path(Path::new("notes.unknown")), None);
+        assert_eq!(plugin.file_type_for_path(Path::new("Makefile")), None);
+    }
+
+    #[test]
+    fn test_registered_types_match_lookup() {
+        let plugin = ScriptEditorPlugin::default();
+        let supported = &plugin.editors()[0].supported_file_types;
+        for file_type in &FILE_TYPES {
+            let id = FileTypeId::new(file_type.id);
+            assert!(!file_type.extensions().is_empty(), "{}", file_type.id);
+            for extension in file_type.extensions() {
+                let path = PathBuf::from(format!("file.{}", extension));
+                assert_eq!(plugin.file_type_for_path(&path), Some(FileTypeId::new(file_type.id)), "{}", extension);
+            }
+            assert!(supported.contains(&id), "{}", file_type.id);
+        }
+        assert_eq!(plugin.file_types().len(), FILE_TYPES.iter().map(|file_type| file_type.extensions().len()).sum());
+    }
+}
@@ -0,0 +1,159 @@
+//! ANSI escape sequences in program output: colors and bold are kept as
+//! styles, every other sequence is dropped
+
+/// The 16 basic terminal colors, normal then bright, as `0xRRGGBB`
+const PALETTE: [u32; 16] = [
+    0x000000, 0xcd3131, 0x0dbc79, 0xe5e510, 0x2472c8, 0xbc3fbc, 0x11a8cd, 0xe5e5e5, //
+    0x666666, 0xf14c4c, 0x23d18b, 0xf5f543, 0x3b8eea, 0xd670d6, 0x29b8db, 0xffffff,
+];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AnsiStyle {
+    /// `0xRRGGBB`, `None` for the panel's own text color
+    pub foreground: Option<u32>,
+    pub background: Option<u32>,
+    pub bold: bool,
+    pub dim: bool,
+    pub italic: bool,
+    pub underline: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StyledText {
+    pub text: String,
+    pub style: AnsiStyle,
+}
+
+/// Splits lines into styled runs. The style carries over from one line to
+/// the next, as it does in a terminal.
+#[derive(Debug, Default)]
+pub struct AnsiParser {
+    style: AnsiStyle,
+}
+
+impl AnsiParser {
+    /// Runs of `line`, which has no `\n`. Text a `\r` went back over is
+    /// dropped, so a progress bar shows as its last state.
+    pub fn parse_line(&mut self, line: &str) -> Vec<StyledText> {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let line = line.rsplit('\r').next().unwrap_or(line);
+
+        let mut runs: Vec<StyledText> = Vec::new();
+        let mut push = |text: &str, style: AnsiStyle| {
+            if text.is_empty() {
+                return;
+            }
+            match runs.last_mut() {
+                Some(last) if last.style == style => last.text.push_str(text),
+                _ => runs.push(StyledText { text: text.to_string(), style }),
+            }
+        };
+
+        let mut rest = line;
+        while let Some(escape) = rest.find('\x1b') {
+            push(&rest[..escape], self.style);
+            rest = &rest[escape + 1..];
+            let Some(csi) = rest.strip_prefix('[') else {
+                // A lone escape or another kind of sequence: skip its next character
+                rest = rest.get(rest.chars().next().map_or(0, char::len_utf8)..).unwrap_or("");
+                continue;
+            };
+            // Parameters and intermediates, up to the final byte
+            let Some(end) = csi.find(|ch: char| ('@'..='~').contains(&ch)) else {
+                rest = "";
+                break;
+            };
+            if csi.as_bytes()[end] == b'm' {
+                self.style.apply_sgr(&csi[..end]);
+            }
+            rest = &csi[end + 1..];
+        }
+        push(rest, self.style);
+        runs
+    }
+}
+
+impl AnsiStyle {
+    /// Apply a Select Graphic Rendition sequence's parameters: `1;31` is bold red
+    pub fn apply_sgr(&mut self, params: &str) {
+        let codes: Vec<u32> = params.split([';', ':']).map(|code| code.parse().unwrap_or(0)).collect();
+        let mut codes = codes.into_iter();
+        while let Some(code) = codes.next() {
+            match code {
+                0 => *self = AnsiStyle::default(),
+                1 => self.bold = true,
+                2 => self.dim = true,
+                3 => self.italic = true,
+                4 => self.underline = true,
+                22 => (self.bold, self.dim) = (false, false),
+                23 => self.italic = false,
+                24 => self.underline = false,
+                30..=37 => self.foreground = Some(PALETTE[code as usize - 30]),
+                90..=97 => self.foreground = Some(PALETTE[code as usize - 90 + 8]),
+                39 => self.foreground = None,
+                40..=47 => self.background = Some(PALETTE[code as usize - 40]),
+                100..=107 => self.background = Some(PALETTE[code as usize - 100 + 8]),
+                49 => self.background = None,
+                38 | 48 => {
+                    let color = match codes.next() {
+                        Some(5) => codes.next().map(indexed_color),
+                        Some(2) => {
+                            let (r, g, b) = (codes.next(), codes.next(), codes.next());
+                            Some((r.unwrap_or(0).min(255) << 16) | (g.unwrap_or(0).min(255) << 8) | b.unwrap_or(0).min(255))
+                        }
+                        _ => None,
+                    };
+                    if code == 38 {
+                        self.foreground = color;
+                    } else {
+                        self.background = color;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// A color of the 256-color palette: the basic 16, a 6×6×6 cube, then grays
+fn indexed_color(index: u32) -> u32 {
+    match index {
+        0..=15 => PALETTE[index as usize],
+        16..=231 => {
+            let level = |value: u32| if value == 0 { 0 } else { 55 + value * 40 };
+            let index = index - 16;
+            (level(index / 36) << 16) | (level(index / 6 % 6) << 8) | level(index % 6)
+        }
+        _ => {
+            let gray = 8 + (index.min(255) - 232) * 10;
+            (gray << 16) | (gray << 8) | gray
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_colors_and_bold() {
+        let mut parser = AnsiParser::default();
+        let runs = parser.parse_line("\x1b[1;31merror\x1b[0m: expected `;`\x1b[K");
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].text, "error");
+        assert_eq!(runs[0].style.foreground, Some(0xcd3131));
+        assert!(runs[0].style.bold);
+        assert_eq!(runs[1], StyledText { text: ": expected `;`".into(), style: AnsiStyle::default() });
+
+        // The color is still on for the next line until it's reset
+        parser.parse_line("\x1b[38;5;196mred");
+        assert_eq!(parser.parse_line("still red")[0].style.foreground, Some(0xff0000));
+    }
+
+    #[test]
+    fn test_carriage_return_overwrites() {
+        let mut parser = AnsiParser::default();
+        let runs = parser.parse_line("Downloading 10%\rDownloading 100%\r");
+        assert_eq!(runs[0].text, "Downloading 100%");
+    }
+}
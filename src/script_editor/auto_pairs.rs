@@ -0,0 +1,180 @@
+//! Auto-closing brackets and quotes
+//!
+//! Typing an opening bracket or quote inserts its closing pair, typing the
+//! closing character in front of an identical one steps over it, and
+//! backspace inside an empty pair deletes both halves.
+
+use std::ops::Range;
+
+/// A replacement to make instead of letting the input handle a keystroke
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PairEdit {
+    /// Byte range of the text to replace
+    pub range: Range<usize>,
+    pub text: String,
+    /// Cursor byte offset once the replacement is made
+    pub cursor: usize,
+}
+
+/// Closing character paired with `open`
+fn closing_for(open: char) -> Option<char> {
+    match open {
+        '(' => Some(')'),
+        '[' => Some(']'),
+        '{' => Some('}'),
+        '"' => Some('"'),
+        '\'' => Some('\''),
+        '`' => Some('`'),
+        _ => None,
+    }
+}
+
+fn is_closing(ch: char) -> bool {
+    matches!(ch, ')' | ']' | '}' | '"' | '\'' | '`')
+}
+
+/// Whether typing `open` at `offset` should also insert its closing pair.
+///
+/// Pairs are only inserted in front of whitespace, a closing character or
+/// punctuation, so typing before existing code does not add stray closers.
+/// Quotes are not paired right after a word character (`don't`), and in Rust
+/// `'` is not paired where it likely starts a lifetime (`&'a`, `<'a>`).
+fn should_pair(text: &str, offset: usize, open: char, language: &str) -> bool {
+    let previous = text[..offset].chars().next_back();
+    let next = text[offset..].chars().next();
+
+    if next.is_some_and(|next| !(next.is_whitespace() || is_closing(next) || matches!(next, ';' | ',' | '.' | ':'))) {
+        return false;
+    }
+
+    match open {
+        '"' | '`' | '\'' => {
+            if previous.is_some_and(|previous| previous.is_alphanumeric() || previous == '_' || previous == open) {
+                return false;
+            }
+            match (open, language) {
+                ('\'', "rust") => !matches!(previous, Some('&' | '<')),
+                ('\'', "text" | "markdown") => false,
+                _ => true,
+            }
+        }
+        _ => true,
+    }
+}
+
+/// What to do when `typed` is entered with `selection` (byte offsets, empty for a cursor).
+///
+/// `None` lets the input insert the character as usual.
+pub fn on_char_typed(text: &str, selection: Range<usize>, typed: char, language: &str) -> Option<PairEdit> {
+    let next = text[selection.end..].chars().next();
+
+    if !selection.is_empty() {
+        // Wrap the selection, keeping the cursor just inside the closer
+        let close = closing_for(typed)?;
+        let selected = &text[selection.clone()];
+        return Some(PairEdit {
+            range: selection.clone(),
+            text: format!("{typed}{selected}{close}"),
+            cursor: selection.start + typed.len_utf8() + selected.len(),
+        });
+    }
+
+    let offset = selection.start;
+    if is_closing(typed) && next == Some(typed) {
+        // Step over the closer that is already there
+        return Some(PairEdit {
+            range: offset..offset,
+            text: String::new(),
+            cursor: offset + typed.len_utf8(),
+        });
+    }
+
+    let close = closing_for(typed)?;
+    if !should_pair(text, offset, typed, language) {
+        return None;
+    }
+    Some(PairEdit {
+        range: offset..offset,
+        text: format!("{typed}{close}"),
+        cursor: offset + typed.len_utf8(),
+    })
+}
+
+/// Backspace at `cursor` between an empty pair like `(|)` deletes both characters
+pub fn on_backspace(text: &str, cursor: usize) -> Option<PairEdit> {
+    let open = text[..cursor].chars().next_back()?;
+    let close = closing_for(open)?;
+    if text[cursor..].chars().next() != Some(close) {
+        return None;
+    }
+    let start = cursor - open.len_utf8();
+    Some(PairEdit {
+        range: start..cursor + close.len_utf8(),
+        text: String::new(),
+        cursor: start,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inserts_closing_pair() {
+        assert_eq!(
+            on_char_typed("foo", 3..3, '(', "rust"),
+            Some(PairEdit {
+                range: 3..3,
+                text: "()".into(),
+                cursor: 4,
+            })
+        );
+        // Not in front of existing code
+        assert_eq!(on_char_typed("foo", 0..0, '(', "rust"), None);
+        // Quotes after a word, and lifetimes in Rust
+        assert_eq!(on_char_typed("don", 3..3, '\'', "text"), None);
+        assert_eq!(on_char_typed("&", 1..1, '\'', "rust"), None);
+        assert!(on_char_typed("x = ", 4..4, '"', "python").is_some());
+    }
+
+    #[test]
+    fn test_steps_over_closer() {
+        assert_eq!(
+            on_char_typed("f()", 2..2, ')', "rust"),
+            Some(PairEdit {
+                range: 2..2,
+                text: String::new(),
+                cursor: 3,
+            })
+        );
+        assert_eq!(on_char_typed("f(", 2..2, ')', "rust"), None);
+    }
+
+    #[test]
+    fn test_wraps_selection() {
+        assert_eq!(
+            on_char_typed("a + b", 0..5, '(', "rust"),
+            Some(PairEdit {
+                range: 0..5,
+                text: "(a + b)".into(),
+                cursor: 6,
+            })
+        );
+        // Other characters replace the selection as usual
+        assert_eq!(on_char_typed("a + b", 0..5, 'x', "rust"), None);
+    }
+
+    #[test]
+    fn test_backspace_deletes_empty_pair() {
+        assert_eq!(
+            on_backspace("f()", 2),
+            Some(PairEdit {
+                range: 1..3,
+                text: String::new(),
+                cursor: 1,
+            })
+        );
+        assert_eq!(on_backspace("f(x)", 2), None);
+        assert_eq!(on_backspace("", 0), None);
+    }
+}
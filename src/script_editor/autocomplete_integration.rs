@@ -8,10 +8,15 @@ use ui::input::{
 };
 use std::path::PathBuf;
 use std::rc::Rc;
+use std::sync::Arc;
 
 use engine_backend::services::rust_analyzer_manager::RustAnalyzerManager;
 use engine_backend::services::lsp_completion_provider::GlobalRustAnalyzerCompletionProvider;
 
+use super::lsp::{
+    DocumentVersion, LanguageServer, LspCompletionProvider, LspHoverProvider, SharedInlayHints, SharedCompletionExtras,
+};
+
 fn workspace_from_file(file_path: &PathBuf) -> PathBuf {
     let candidate = if file_path.is_file() {
         file_path
@@ -122,6 +127,27 @@ pub fn setup_text_autocomplete(
     tracing::debug!("✓ Text autocomplete configured for: {:?} (workspace: {:?})", file_path.file_name(), workspace);
 }
 
+/// Helper function to set up completion and hover backed by a plugin-managed language server
+///
+/// Replaces whatever providers were configured before, so call this after the
+/// language-specific setup when a server is available.
+pub fn setup_lsp_providers(
+    input_state: &mut InputState,
+    server: Arc<LanguageServer>,
+    file_path: PathBuf,
+    version: DocumentVersion,
+    inlay_hints: SharedInlayHints,
+    completion_extras: SharedCompletionExtras,
+    _cx: &mut Context<InputState>,
+) {
+    let provider = LspCompletionProvider::new(server.clone(), file_path.clone(), version.clone(), completion_extras);
+    input_state.lsp.completion_provider = Some(Rc::new(provider));
+    let hover = LspHoverProvider::new(server.clone(), file_path.clone(), version, inlay_hints);
+    input_state.lsp.hover_provider = Some(Rc::new(hover));
+
+    tracing::debug!("✓ {} completion and hover configured for: {:?}", server.name(), file_path.file_name());
+}
+
 /// Detect language and set up appropriate autocomplete with global rust-analyzer
 pub fn setup_autocomplete_for_file(
     input_state: &mut InputState,
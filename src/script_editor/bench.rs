@@ -0,0 +1,31 @@
+//! What `benches/large_file.rs` measures, reachable from outside the crate
+//!
+//! The input keeps its own copy of the text; each keystroke, the editor
+//! brings its copy in `OpenFile::synced` and the lazy highlighting up to date
+//! with `track_edit`, as here. Nothing in this module is part of the plugin's API.
+
+use super::lazy_highlight::LazyHighlighter;
+use super::text_buffer::TextBuffer;
+use super::text_editor::track_edit;
+
+/// An open file's text as the editor tracks it, with a server that syncs incrementally
+#[derive(Clone)]
+pub struct TrackedFile {
+    synced: TextBuffer,
+    highlighter: LazyHighlighter,
+}
+
+impl TrackedFile {
+    /// As the editor sets it up when the file is opened
+    pub fn open(text: &str) -> Self {
+        Self {
+            synced: TextBuffer::new(text),
+            highlighter: LazyHighlighter::default(),
+        }
+    }
+
+    /// Catch up with the input now holding `content`; whether anything changed
+    pub fn edit(&mut self, content: &str) -> bool {
+        track_edit(&mut self.synced, Some(&mut self.highlighter), content, true).is_some()
+    }
+}
@@ -0,0 +1,127 @@
+//! Bookmarked lines of open files, kept on their code as it's edited
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+/// Bookmarked lines of one file
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Bookmarks {
+    lines: BTreeSet<u32>,
+}
+
+impl Bookmarks {
+    pub fn lines(&self) -> &BTreeSet<u32> {
+        &self.lines
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// Add a bookmark on `line`, or remove the one there; returns whether it's now bookmarked
+    pub fn toggle(&mut self, line: u32) -> bool {
+        if self.lines.remove(&line) {
+            false
+        } else {
+            self.lines.insert(line);
+            true
+        }
+    }
+
+    pub fn remove(&mut self, line: u32) {
+        self.lines.remove(&line);
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+
+    /// Keep bookmarks on the same code after an edit that changed lines
+    /// `start_line..=old_end_line` into `start_line..=new_end_line`.
+    ///
+    /// `pushes_start_line` is set when whole lines were inserted at the start
+    /// of `start_line`, which moves its text (and bookmark) down. Bookmarks on
+    /// deleted lines move up to the last line left of the edit.
+    pub fn remap(&mut self, start_line: u32, old_end_line: u32, new_end_line: u32, pushes_start_line: bool) {
+        let delta = new_end_line as i64 - old_end_line as i64;
+        self.lines = self
+            .lines
+            .iter()
+            .map(|&line| {
+                if line < start_line || (line == start_line && !pushes_start_line) {
+                    line
+                } else if line > old_end_line || pushes_start_line {
+                    (line as i64 + delta) as u32
+                } else {
+                    line.min(new_end_line)
+                }
+            })
+            .collect();
+    }
+}
+
+/// The bookmark after (or before, going back) `line` of `path`, going on
+/// through the other files in order and wrapping around at the ends
+pub fn step_bookmark<'a>(
+    files: &'a [(PathBuf, Vec<u32>)],
+    path: &Path,
+    line: u32,
+    forward: bool,
+) -> Option<(&'a Path, u32)> {
+    let all: Vec<(usize, u32)> = files
+        .iter()
+        .enumerate()
+        .flat_map(|(file_ix, (_, lines))| lines.iter().map(move |&line| (file_ix, line)))
+        .collect();
+    if all.is_empty() {
+        return None;
+    }
+    let here = files.iter().position(|(file, _)| file == path);
+    let ix = match here {
+        Some(file_ix) if forward => all.partition_point(|&mark| mark <= (file_ix, line)) % all.len(),
+        Some(file_ix) => all.partition_point(|&mark| mark < (file_ix, line)).checked_sub(1).unwrap_or(all.len() - 1),
+        None if forward => 0,
+        None => all.len() - 1,
+    };
+    let (file_ix, line) = all[ix];
+    Some((files[file_ix].0.as_path(), line))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bookmarks_follow_edits() {
+        let mut bookmarks = Bookmarks::default();
+        for line in [2, 5, 9] {
+            bookmarks.toggle(line);
+        }
+        // Two lines typed into line 3
+        bookmarks.remap(3, 3, 5, false);
+        assert_eq!(bookmarks.lines().iter().copied().collect::<Vec<_>>(), [2, 7, 11]);
+        // A line inserted above line 2
+        bookmarks.remap(2, 2, 3, true);
+        assert_eq!(bookmarks.lines().iter().copied().collect::<Vec<_>>(), [3, 8, 12]);
+        // Lines 6..=9 deleted, joining onto line 6
+        bookmarks.remap(6, 9, 6, false);
+        assert_eq!(bookmarks.lines().iter().copied().collect::<Vec<_>>(), [3, 6, 9]);
+        assert!(!bookmarks.toggle(6));
+    }
+
+    #[test]
+    fn test_step_across_files() {
+        let files = vec![
+            (PathBuf::from("/p/a.rs"), vec![4, 10]),
+            (PathBuf::from("/p/b.rs"), vec![]),
+            (PathBuf::from("/p/c.rs"), vec![1]),
+        ];
+        let a = Path::new("/p/a.rs");
+        assert_eq!(step_bookmark(&files, a, 4, true), Some((a, 10)));
+        assert_eq!(step_bookmark(&files, a, 12, true), Some((Path::new("/p/c.rs"), 1)));
+        assert_eq!(step_bookmark(&files, Path::new("/p/c.rs"), 1, true), Some((a, 4)));
+        assert_eq!(step_bookmark(&files, Path::new("/p/b.rs"), 0, false), Some((a, 10)));
+        assert_eq!(step_bookmark(&files, a, 4, false), Some((Path::new("/p/c.rs"), 1)));
+        assert_eq!(step_bookmark(&[], a, 0, true), None);
+    }
+}
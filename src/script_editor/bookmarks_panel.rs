@@ -0,0 +1,235 @@
+//! Bookmarks of every open file, grouped by file
+
+use gpui::{prelude::FluentBuilder, *};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use ui::{
+    button::{Button, ButtonVariants as _},
+    h_flex, v_flex, ActiveTheme as _, IconName, Sizable as _, StyledExt,
+};
+
+/// A bookmarked line of an open file
+#[derive(Clone, Debug, PartialEq)]
+pub struct Bookmark {
+    pub path: PathBuf,
+    /// Zero-based line
+    pub line: u32,
+    /// The line's text, trimmed
+    pub text: String,
+}
+
+/// One visible row of the list: a file header or a bookmark beneath it
+#[derive(Clone, Copy)]
+enum BookmarkRow {
+    File(usize),
+    Entry(usize),
+}
+
+pub enum BookmarksPanelEvent {
+    /// Open a file at a zero-based line
+    OpenLocation { path: PathBuf, line: u32 },
+    Remove { path: PathBuf, line: u32 },
+    ClearAll,
+}
+
+pub struct BookmarksPanel {
+    focus_handle: FocusHandle,
+    /// Sorted by file, then line
+    bookmarks: Vec<Bookmark>,
+    rows: Vec<BookmarkRow>,
+    project_root: Option<PathBuf>,
+    scroll_handle: UniformListScrollHandle,
+}
+
+impl EventEmitter<BookmarksPanelEvent> for BookmarksPanel {}
+
+impl BookmarksPanel {
+    pub fn new(cx: &mut Context<Self>) -> Self {
+        Self {
+            focus_handle: cx.focus_handle(),
+            bookmarks: Vec::new(),
+            rows: Vec::new(),
+            project_root: None,
+            scroll_handle: UniformListScrollHandle::new(),
+        }
+    }
+
+    /// Used to display paths relative to the project
+    pub fn set_project_root(&mut self, root: Option<PathBuf>) {
+        self.project_root = root;
+    }
+
+    pub fn set_bookmarks(&mut self, mut bookmarks: Vec<Bookmark>, cx: &mut Context<Self>) {
+        bookmarks.sort_by(|a, b| a.path.cmp(&b.path).then(a.line.cmp(&b.line)));
+        if bookmarks == self.bookmarks {
+            return;
+        }
+        self.bookmarks = bookmarks;
+        self.rows.clear();
+        for (ix, bookmark) in self.bookmarks.iter().enumerate() {
+            if ix == 0 || self.bookmarks[ix - 1].path != bookmark.path {
+                self.rows.push(BookmarkRow::File(ix));
+            }
+            self.rows.push(BookmarkRow::Entry(ix));
+        }
+        cx.notify();
+    }
+
+    fn display_path<'a>(&self, path: &'a Path) -> std::borrow::Cow<'a, str> {
+        self.project_root
+            .as_ref()
+            .and_then(|root| path.strip_prefix(root).ok())
+            .unwrap_or(path)
+            .to_string_lossy()
+    }
+
+    fn render_rows(&mut self, range: Range<usize>, cx: &mut Context<Self>) -> Vec<AnyElement> {
+        range
+            .filter_map(|row_ix| {
+                let row = *self.rows.get(row_ix)?;
+                Some(match row {
+                    BookmarkRow::File(ix) => self.render_file_row(row_ix, ix, cx),
+                    BookmarkRow::Entry(ix) => self.render_entry_row(row_ix, ix, cx),
+                })
+            })
+            .collect()
+    }
+
+    fn render_file_row(&self, row_ix: usize, ix: usize, cx: &mut Context<Self>) -> AnyElement {
+        let path = &self.bookmarks[ix].path;
+        h_flex()
+            .id(("bookmark-file", row_ix))
+            .h(px(24.))
+            .px_2()
+            .child(
+                div()
+                    .flex_1()
+                    .overflow_hidden()
+                    .text_ellipsis()
+                    .text_xs()
+                    .font_semibold()
+                    .text_color(cx.theme().foreground)
+                    .child(self.display_path(path).to_string()),
+            )
+            .into_any_element()
+    }
+
+    fn render_entry_row(&self, row_ix: usize, ix: usize, cx: &mut Context<Self>) -> AnyElement {
+        let bookmark = &self.bookmarks[ix];
+        let (path, line) = (bookmark.path.clone(), bookmark.line);
+        let remove_path = path.clone();
+
+        h_flex()
+            .id(("bookmark-entry", row_ix))
+            .h(px(24.))
+            .pl_4()
+            .pr_1()
+            .gap_2()
+            .cursor_pointer()
+            .hover(|style| style.bg(cx.theme().accent.opacity(0.1)))
+            .child(
+                div()
+                    .flex_none()
+                    .w(px(32.))
+                    .text_right()
+                    .text_xs()
+                    .text_color(cx.theme().muted_foreground)
+                    .child((line + 1).to_string()),
+            )
+            .child(
+                div()
+                    .flex_1()
+                    .overflow_hidden()
+                    .whitespace_nowrap()
+                    .text_ellipsis()
+                    .text_xs()
+                    .text_color(cx.theme().foreground)
+                    .child(bookmark.text.clone()),
+            )
+            .child(
+                Button::new(("remove_bookmark", row_ix))
+                    .icon(IconName::Close)
+                    .tooltip("Remove Bookmark")
+                    .ghost()
+                    .xsmall()
+                    .on_click(cx.listener(move |_this, _, _window, cx| {
+                        cx.stop_propagation();
+                        cx.emit(BookmarksPanelEvent::Remove {
+                            path: remove_path.clone(),
+                            line,
+                        });
+                    })),
+            )
+            .on_click(cx.listener(move |_this, _, _window, cx| {
+                cx.emit(BookmarksPanelEvent::OpenLocation { path: path.clone(), line });
+            }))
+            .into_any_element()
+    }
+}
+
+impl Focusable for BookmarksPanel {
+    fn focus_handle(&self, _cx: &App) -> FocusHandle {
+        self.focus_handle.clone()
+    }
+}
+
+impl Render for BookmarksPanel {
+    fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        v_flex()
+            .size_full()
+            .track_focus(&self.focus_handle)
+            .child(
+                // Header
+                h_flex()
+                    .w_full()
+                    .px_2()
+                    .py_1()
+                    .gap_1()
+                    .border_b_1()
+                    .border_color(cx.theme().border)
+                    .child(
+                        div()
+                            .flex_1()
+                            .text_xs()
+                            .text_color(cx.theme().muted_foreground)
+                            .child(match self.bookmarks.len() {
+                                1 => "1 bookmark".to_string(),
+                                count => format!("{} bookmarks", count),
+                            }),
+                    )
+                    .child(
+                        Button::new("clear_bookmarks")
+                            .label("Clear All")
+                            .ghost()
+                            .xsmall()
+                            .disabled(self.bookmarks.is_empty())
+                            .on_click(cx.listener(|_this, _, _window, cx| cx.emit(BookmarksPanelEvent::ClearAll))),
+                    ),
+            )
+            .child(
+                div()
+                    .flex_1()
+                    .min_h_0()
+                    .when(self.rows.is_empty(), |content| {
+                        content.child(
+                            div()
+                                .p_4()
+                                .text_xs()
+                                .text_color(cx.theme().muted_foreground)
+                                .child("No bookmarks. Toggle one on the current line with Ctrl+Alt+K."),
+                        )
+                    })
+                    .when(!self.rows.is_empty(), |content| {
+                        content.child(
+                            uniform_list(
+                                "bookmarks-list",
+                                self.rows.len(),
+                                cx.processor(|this, range: Range<usize>, _window, cx| this.render_rows(range, cx)),
+                            )
+                            .track_scroll(self.scroll_handle.clone())
+                            .size_full(),
+                        )
+                    }),
+            )
+    }
+}
@@ -0,0 +1,198 @@
+//! Bracket matching outside strings and comments
+//!
+//! Strings and comments are recognized the same rough way for every language:
+//! `//` and `/* */` comments, `"` and backtick strings and `'x'` character
+//! literals, so a `{` in `"{"` is not matched with real code. A `"` string
+//! ends at the end of its line, so an unclosed quote can't hide the rest of
+//! the file.
+
+/// A `(`, `[`, `{` or its closer in code, as a byte offset
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Bracket {
+    pub offset: usize,
+    pub ch: char,
+}
+
+impl Bracket {
+    pub fn is_open(&self) -> bool {
+        matches!(self.ch, '(' | '[' | '{')
+    }
+
+    fn closes(&self, open: char) -> bool {
+        matches!((open, self.ch), ('(', ')') | ('[', ']') | ('{', '}'))
+    }
+}
+
+/// Every bracket in `text` outside strings and comments, in order
+pub fn code_brackets(text: &str) -> Vec<Bracket> {
+    let mut brackets = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    let mut in_block_comment = false;
+    let mut in_string: Option<char> = None;
+
+    while let Some((offset, ch)) = chars.next() {
+        if in_block_comment {
+            if ch == '*' && chars.peek().map(|(_, next)| *next) == Some('/') {
+                chars.next();
+                in_block_comment = false;
+            }
+            continue;
+        }
+        if let Some(quote) = in_string {
+            if ch == '\\' {
+                chars.next();
+            } else if ch == quote || (ch == '\n' && quote == '"') {
+                in_string = None;
+            }
+            continue;
+        }
+        match ch {
+            '/' if chars.peek().map(|(_, next)| *next) == Some('/') => {
+                // Skip to the end of the line
+                while chars.peek().is_some_and(|(_, next)| *next != '\n') {
+                    chars.next();
+                }
+            }
+            '/' if chars.peek().map(|(_, next)| *next) == Some('*') => {
+                chars.next();
+                in_block_comment = true;
+            }
+            '"' | '`' => in_string = Some(ch),
+            // Only a character literal, not a lifetime or an apostrophe
+            '\'' => {
+                let rest = &text[offset + 1..];
+                let literal_len = if rest.starts_with('\\') {
+                    rest.get(2..).and_then(|tail| tail.find('\'')).map(|end| end + 2)
+                } else {
+                    rest.chars()
+                        .next()
+                        .filter(|_| rest.chars().nth(1) == Some('\''))
+                        .map(|literal| literal.len_utf8())
+                };
+                if let Some(len) = literal_len {
+                    while chars.peek().is_some_and(|(next, _)| *next <= offset + len + 1) {
+                        chars.next();
+                    }
+                }
+            }
+            '(' | '[' | '{' | ')' | ']' | '}' => brackets.push(Bracket { offset, ch }),
+            _ => {}
+        }
+    }
+    brackets
+}
+
+/// Matched bracket pairs of a document and the brackets without a partner
+#[derive(Debug, Default)]
+pub struct BracketPairs {
+    /// `(open, close)` offsets, sorted by the opening offset
+    pairs: Vec<(usize, usize)>,
+    unmatched: Vec<usize>,
+}
+
+impl BracketPairs {
+    pub fn new(text: &str) -> Self {
+        Self::from_brackets(code_brackets(text))
+    }
+
+    /// Pair up brackets found by a language's own scan, in text order
+    pub fn from_brackets(brackets: Vec<Bracket>) -> Self {
+        let mut pairs = Vec::new();
+        let mut unmatched = Vec::new();
+        let mut open: Vec<Bracket> = Vec::new();
+        for bracket in brackets {
+            if bracket.is_open() {
+                open.push(bracket);
+                continue;
+            }
+            match open.last() {
+                Some(last) if bracket.closes(last.ch) => {
+                    pairs.push((last.offset, bracket.offset));
+                    open.pop();
+                }
+                // A stray closer: leave the open brackets for a later match
+                _ => unmatched.push(bracket.offset),
+            }
+        }
+        unmatched.extend(open.into_iter().map(|bracket| bracket.offset));
+        unmatched.sort_unstable();
+        pairs.sort_unstable();
+        Self { pairs, unmatched }
+    }
+
+    pub fn unmatched(&self) -> &[usize] {
+        &self.unmatched
+    }
+
+    /// Offset of the bracket matching the one at `offset`
+    pub fn partner(&self, offset: usize) -> Option<usize> {
+        self.pairs.iter().find_map(|&(open, close)| {
+            if open == offset {
+                Some(close)
+            } else if close == offset {
+                Some(open)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// The matched bracket right after `cursor`, else the one right before it
+    pub fn adjacent(&self, cursor: usize) -> Option<(usize, usize)> {
+        let at = |offset: usize| self.partner(offset).map(|partner| (offset, partner));
+        at(cursor).or_else(|| cursor.checked_sub(1).and_then(at))
+    }
+
+    /// Pairs whose brackets, taken together with what is between them, contain `range`
+    pub fn surrounding(&self, range: std::ops::Range<usize>) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.pairs
+            .iter()
+            .copied()
+            .filter(move |&(open, close)| open <= range.start && range.end <= close + 1)
+    }
+
+    /// Innermost pair whose brackets surround `cursor`
+    pub fn enclosing(&self, cursor: usize) -> Option<(usize, usize)> {
+        self.pairs
+            .iter()
+            .filter(|&&(open, close)| open < cursor && cursor <= close)
+            .max_by_key(|&&(open, _)| open)
+            .copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_skips_strings_and_comments() {
+        let text = "f(\"(\", '{', '\\'', x) // )\n/* ] */ &'a [";
+        let brackets: Vec<char> = code_brackets(text).into_iter().map(|bracket| bracket.ch).collect();
+        assert_eq!(brackets, vec!['(', ')', '[']);
+    }
+
+    #[test]
+    fn test_pairs_and_unmatched() {
+        let text = "{ (a] ) }";
+        let pairs = BracketPairs::new(text);
+        assert_eq!(pairs.partner(0), Some(8));
+        assert_eq!(pairs.partner(2), Some(6));
+        assert_eq!(pairs.unmatched(), &[4]);
+
+        assert_eq!(BracketPairs::new("((x)").unmatched(), &[0]);
+    }
+
+    #[test]
+    fn test_adjacent_and_enclosing() {
+        let text = "f(a, [b])";
+        let pairs = BracketPairs::new(text);
+        // Before `(`, before `)` and right after `)`
+        assert_eq!(pairs.adjacent(1), Some((1, 8)));
+        assert_eq!(pairs.adjacent(8), Some((8, 1)));
+        assert_eq!(pairs.adjacent(9), Some((8, 1)));
+        assert_eq!(pairs.adjacent(3), None);
+        assert_eq!(pairs.enclosing(3), Some((1, 8)));
+        assert_eq!(pairs.enclosing(6), Some((5, 7)));
+    }
+}
@@ -0,0 +1,498 @@
+//! Command palette: every editor command, searchable by name
+//!
+//! Commands live in a `CommandRegistry` owned by the `ScriptEditor`. Built-in
+//! commands dispatch the editor's actions; other features register a
+//! closure. Each comes with default keys, which the keymap can change.
+
+use gpui::{prelude::FluentBuilder, *};
+use schemars::JsonSchema;
+use serde::Deserialize;
+use std::ops::Range;
+use std::rc::Rc;
+use ui::{
+    h_flex,
+    input::{InputEvent, InputState, TextInput},
+    v_flex, ActiveTheme as _, Sizable as _,
+};
+
+use super::keymap::{find_conflicts, KeyConflict, Keymap};
+use super::ScriptEditor;
+
+/// How many recently used commands are remembered
+const MAX_RECENT: usize = 20;
+
+/// Runs the registered command called `name` (what registered keybindings dispatch)
+#[derive(Action, Clone, PartialEq, Deserialize, JsonSchema)]
+#[action(namespace = script_editor)]
+pub struct RunCommand {
+    pub name: String,
+}
+
+pub type CommandHandler = Rc<dyn Fn(&mut ScriptEditor, &mut Window, &mut Context<ScriptEditor>)>;
+
+#[derive(Clone)]
+pub enum CommandTarget {
+    Action(Rc<dyn Action>),
+    Handler(CommandHandler),
+}
+
+struct Command {
+    name: SharedString,
+    /// Keys it comes with, in keymap syntax (`ctrl-k ctrl-m`)
+    default_keys: Vec<String>,
+    /// Keys bound to it now
+    keys: Vec<String>,
+    target: CommandTarget,
+}
+
+/// All commands the palette can run, and which were used most recently
+#[derive(Default)]
+pub struct CommandRegistry {
+    commands: Vec<Command>,
+    /// Most recently run first
+    recent: Vec<SharedString>,
+    keymap: Keymap,
+}
+
+impl CommandRegistry {
+    pub fn new(keymap: Keymap) -> Self {
+        Self {
+            keymap,
+            ..Default::default()
+        }
+    }
+
+    /// Add a command that dispatches `action`, bound to `keys` unless the keymap says otherwise
+    pub fn register_action(
+        &mut self,
+        name: impl Into<SharedString>,
+        keys: &[&str],
+        action: impl Action,
+        cx: &mut App,
+    ) {
+        let keys = keys.iter().map(ToString::to_string).collect();
+        self.add(name.into(), keys, CommandTarget::Action(Rc::new(action)), cx);
+    }
+
+    /// Add a command that runs `handler`, bound to `keybinding` in the editor when given
+    pub fn register(
+        &mut self,
+        name: impl Into<SharedString>,
+        keybinding: Option<&str>,
+        handler: impl Fn(&mut ScriptEditor, &mut Window, &mut Context<ScriptEditor>) + 'static,
+        cx: &mut App,
+    ) {
+        let keys = keybinding.map(ToString::to_string).into_iter().collect();
+        self.add(name.into(), keys, CommandTarget::Handler(Rc::new(handler)), cx);
+    }
+
+    fn add(&mut self, name: SharedString, default_keys: Vec<String>, target: CommandTarget, cx: &mut App) {
+        let keys = self.keymap.keys_for(&name, &default_keys);
+        bind(&name, &keys, cx);
+        self.commands.push(Command {
+            name,
+            default_keys,
+            keys,
+            target,
+        });
+    }
+
+    /// Rebind every command by `keymap`
+    pub fn set_keymap(&mut self, keymap: Keymap, cx: &mut App) {
+        let old_keys: Vec<String> = self.commands.iter().flat_map(|command| command.keys.clone()).collect();
+        for command in &mut self.commands {
+            command.keys = keymap.keys_for(&command.name, &command.default_keys);
+        }
+        // Keys can't be unbound, but bindings to nothing shadow them
+        let stale: Vec<KeyBinding> = old_keys
+            .iter()
+            .filter(|keys| !self.commands.iter().any(|command| command.keys.contains(keys)))
+            .map(|keys| KeyBinding::new(keys, NoAction, Some("ScriptEditor")))
+            .collect();
+        cx.bind_keys(stale);
+        // Bound again so they win over the old bindings of the same keys
+        for command in &self.commands {
+            bind(&command.name, &command.keys, cx);
+        }
+        self.keymap = keymap;
+    }
+
+    /// Keys bound to more than one command
+    pub fn conflicts(&self) -> Vec<KeyConflict> {
+        find_conflicts(self.commands.iter().map(|command| (command.name.as_ref(), command.keys.as_slice())))
+    }
+
+    /// What running the command called `name` does, remembering it as recently used
+    pub fn take_for_run(&mut self, name: &str) -> Option<CommandTarget> {
+        let target = self.commands.iter().find(|command| command.name == name)?.target.clone();
+        self.recent.retain(|recent| recent != name);
+        self.recent.insert(0, SharedString::from(name.to_string()));
+        self.recent.truncate(MAX_RECENT);
+        Some(target)
+    }
+
+    /// Palette entries, the recently used commands first, leaving out `hidden` ones
+    pub fn entries(&self, hidden: &[&str]) -> Vec<PaletteEntry> {
+        let recency = |command: &Command| {
+            self.recent
+                .iter()
+                .position(|recent| *recent == command.name)
+                .unwrap_or(usize::MAX)
+        };
+        let mut commands: Vec<&Command> = self
+            .commands
+            .iter()
+            .filter(|command| !hidden.contains(&command.name.as_ref()))
+            .collect();
+        commands.sort_by_key(|command| recency(command));
+        let conflicts = self.conflicts();
+
+        commands
+            .into_iter()
+            .map(|command| PaletteEntry {
+                name: command.name.clone(),
+                keybinding: command.keys.first().map(|keys| format_keybinding(keys).into()),
+                conflict: conflicts
+                    .iter()
+                    .any(|conflict| conflict.commands.iter().any(|name| name.as_str() == command.name.as_ref())),
+                recent: recency(command) != usize::MAX,
+            })
+            .collect()
+    }
+}
+
+/// A command as listed in the palette
+#[derive(Clone)]
+pub struct PaletteEntry {
+    pub name: SharedString,
+    /// Display form, e.g. `Ctrl+Shift+P`
+    pub keybinding: Option<SharedString>,
+    /// Its keys also run another command
+    pub conflict: bool,
+    pub recent: bool,
+}
+
+/// Bind each of `keys` to running the command called `name` in the editor
+fn bind(name: &str, keys: &[String], cx: &mut App) {
+    cx.bind_keys(keys.iter().map(|keys| {
+        KeyBinding::new(
+            keys,
+            RunCommand {
+                name: name.to_string(),
+            },
+            Some("ScriptEditor"),
+        )
+    }));
+}
+
+/// Keymap syntax (`ctrl-k ctrl-0`) as shown to people (`Ctrl+K Ctrl+0`)
+pub fn format_keybinding(keybinding: &str) -> String {
+    const MODIFIERS: [(&str, &str); 8] = [
+        ("ctrl-", "Ctrl"),
+        ("shift-", "Shift"),
+        ("alt-", "Alt"),
+        ("cmd-", "Cmd"),
+        ("platform-", "Cmd"),
+        ("super-", "Super"),
+        ("win-", "Win"),
+        ("fn-", "Fn"),
+    ];
+    keybinding
+        .split_whitespace()
+        .map(|keystroke| {
+            let mut parts = Vec::new();
+            let mut rest = keystroke;
+            'modifiers: loop {
+                for (prefix, label) in MODIFIERS {
+                    // A lone `-` key is not a modifier separator
+                    if rest.len() > prefix.len() && rest.starts_with(prefix) {
+                        parts.push(label.to_string());
+                        rest = &rest[prefix.len()..];
+                        continue 'modifiers;
+                    }
+                }
+                break;
+            }
+            parts.push(match rest {
+                "escape" => "Esc".to_string(),
+                "enter" | "tab" | "space" | "backspace" | "delete" | "home" | "end" | "up" | "down"
+                | "left" | "right" | "pageup" | "pagedown" => {
+                    let mut chars = rest.chars();
+                    chars.next().map_or(String::new(), |first| {
+                        first.to_uppercase().chain(chars).collect()
+                    })
+                }
+                key => key.to_uppercase(),
+            });
+            parts.join("+")
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// A fuzzy match of a query against a command name
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FuzzyMatch {
+    pub score: i32,
+    /// Byte offsets of the matched characters in the name
+    pub positions: Vec<usize>,
+}
+
+/// Match the characters of `query` in order (ignoring case and spaces) in `candidate`.
+///
+/// Matches at the start of words and runs of consecutive characters score
+/// higher, gaps between matched characters lower.
+pub fn fuzzy_match(query: &str, candidate: &str) -> Option<FuzzyMatch> {
+    let query: Vec<char> = query
+        .chars()
+        .filter(|ch| !ch.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect();
+    let mut positions = Vec::with_capacity(query.len());
+    let mut score = 0;
+    let mut query_ix = 0;
+    let mut previous: Option<char> = None;
+    let mut last_match: Option<usize> = None;
+
+    for (char_ix, (offset, ch)) in candidate.char_indices().enumerate() {
+        if query_ix == query.len() {
+            break;
+        }
+        let word_start = previous.is_none_or(|previous| {
+            !previous.is_alphanumeric() || (previous.is_lowercase() && ch.is_uppercase())
+        });
+        if ch.to_lowercase().eq(std::iter::once(query[query_ix])) {
+            score += 1;
+            if word_start {
+                score += 8;
+            }
+            match last_match {
+                Some(last) if last + 1 == char_ix => score += 5,
+                Some(last) => score -= (char_ix - last - 1).min(5) as i32,
+                None => score -= char_ix.min(5) as i32,
+            }
+            positions.push(offset);
+            last_match = Some(char_ix);
+            query_ix += 1;
+        }
+        previous = Some(ch);
+    }
+
+    (query_ix == query.len()).then_some(FuzzyMatch { score, positions })
+}
+
+pub enum CommandPaletteEvent {
+    /// Run the command with this name
+    Run(SharedString),
+    Dismissed,
+}
+
+pub struct CommandPalette {
+    focus_handle: FocusHandle,
+    query_input: Entity<InputState>,
+    entries: Vec<PaletteEntry>,
+    /// Entry index and match for every entry matching the query, best first
+    matches: Vec<(usize, FuzzyMatch)>,
+    selected: usize,
+    scroll_handle: UniformListScrollHandle,
+    _subscription: Subscription,
+}
+
+impl EventEmitter<CommandPaletteEvent> for CommandPalette {}
+
+impl CommandPalette {
+    pub fn new(entries: Vec<PaletteEntry>, window: &mut Window, cx: &mut Context<Self>) -> Self {
+        let query_input = cx.new(|cx| InputState::new(window, cx).placeholder("Type a command"));
+        let subscription = cx.subscribe_in(&query_input, window, |this, _input, event: &InputEvent, _window, cx| {
+            match event {
+                InputEvent::Change => this.update_matches(cx),
+                InputEvent::PressEnter { .. } => this.confirm(cx),
+                InputEvent::Blur => cx.emit(CommandPaletteEvent::Dismissed),
+                _ => {}
+            }
+        });
+        query_input.update(cx, |input, cx| input.focus(window, cx));
+
+        let mut palette = Self {
+            focus_handle: cx.focus_handle(),
+            query_input,
+            entries,
+            matches: Vec::new(),
+            selected: 0,
+            scroll_handle: UniformListScrollHandle::new(),
+            _subscription: subscription,
+        };
+        palette.update_matches(cx);
+        palette
+    }
+
+    fn update_matches(&mut self, cx: &mut Context<Self>) {
+        let query = self.query_input.read(cx).value();
+        self.matches = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(ix, entry)| Some((ix, fuzzy_match(&query, &entry.name)?)))
+            .collect();
+        // Stable, so equally good matches keep recently used commands first
+        self.matches.sort_by_key(|(_, fuzzy)| std::cmp::Reverse(fuzzy.score));
+        self.selected = 0;
+        self.scroll_handle.scroll_to_item(0, ScrollStrategy::Top);
+        cx.notify();
+    }
+
+    fn move_selection(&mut self, forward: bool, cx: &mut Context<Self>) {
+        if self.matches.is_empty() {
+            return;
+        }
+        let count = self.matches.len();
+        self.selected = if forward {
+            (self.selected + 1) % count
+        } else {
+            (self.selected + count - 1) % count
+        };
+        self.scroll_handle.scroll_to_item(self.selected, ScrollStrategy::Top);
+        cx.notify();
+    }
+
+    fn confirm(&mut self, cx: &mut Context<Self>) {
+        if let Some((entry_ix, _)) = self.matches.get(self.selected) {
+            cx.emit(CommandPaletteEvent::Run(self.entries[*entry_ix].name.clone()));
+        }
+    }
+
+    fn render_rows(&mut self, range: Range<usize>, cx: &mut Context<Self>) -> Vec<AnyElement> {
+        range
+            .filter_map(|row_ix| {
+                let (entry_ix, fuzzy) = self.matches.get(row_ix)?;
+                let entry = &self.entries[*entry_ix];
+                let highlight = HighlightStyle {
+                    color: Some(cx.theme().accent),
+                    font_weight: Some(FontWeight::BOLD),
+                    ..Default::default()
+                };
+                let highlights = fuzzy.positions.iter().filter_map(|&offset| {
+                    let len = entry.name[offset..].chars().next()?.len_utf8();
+                    Some((offset..offset + len, highlight))
+                });
+                let name = StyledText::new(entry.name.clone()).with_highlights(highlights);
+
+                Some(
+                    h_flex()
+                        .id(("command", row_ix))
+                        .h(px(28.))
+                        .px_3()
+                        .gap_2()
+                        .justify_between()
+                        .cursor_pointer()
+                        .rounded(cx.theme().radius)
+                        .when(row_ix == self.selected, |row| row.bg(cx.theme().accent.opacity(0.2)))
+                        .hover(|style| style.bg(cx.theme().accent.opacity(0.1)))
+                        .child(div().text_sm().text_color(cx.theme().foreground).child(name))
+                        .child(
+                            h_flex()
+                                .gap_2()
+                                .text_xs()
+                                .text_color(cx.theme().muted_foreground)
+                                .when(entry.recent, |this| this.child("recently used"))
+                                .children(entry.keybinding.clone().map(|keybinding| {
+                                    div()
+                                        .when(entry.conflict, |this| this.text_color(cx.theme().warning))
+                                        .child(keybinding)
+                                })),
+                        )
+                        .on_click(cx.listener(move |this, _, _window, cx| {
+                            this.selected = row_ix;
+                            this.confirm(cx);
+                        }))
+                        .into_any_element(),
+                )
+            })
+            .collect()
+    }
+}
+
+impl Focusable for CommandPalette {
+    fn focus_handle(&self, _cx: &App) -> FocusHandle {
+        self.focus_handle.clone()
+    }
+}
+
+impl Render for CommandPalette {
+    fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        v_flex()
+            .w(px(520.))
+            .p_1()
+            .gap_1()
+            .bg(cx.theme().popover)
+            .border_1()
+            .border_color(cx.theme().border)
+            .rounded(cx.theme().radius)
+            .shadow_lg()
+            .track_focus(&self.focus_handle)
+            // Arrow keys move through the list instead of the input's cursor
+            .capture_key_down(cx.listener(|this, event: &KeyDownEvent, _window, cx| {
+                match event.keystroke.key.as_str() {
+                    "up" => this.move_selection(false, cx),
+                    "down" => this.move_selection(true, cx),
+                    "escape" => cx.emit(CommandPaletteEvent::Dismissed),
+                    _ => return,
+                }
+                cx.stop_propagation();
+            }))
+            .child(TextInput::new(&self.query_input).small())
+            .child(
+                div()
+                    .h(px(28. * self.matches.len().clamp(1, 12) as f32))
+                    .when(self.matches.is_empty(), |content| {
+                        content.child(
+                            div()
+                                .px_3()
+                                .py_1()
+                                .text_xs()
+                                .text_color(cx.theme().muted_foreground)
+                                .child("No matching commands"),
+                        )
+                    })
+                    .when(!self.matches.is_empty(), |content| {
+                        content.child(
+                            uniform_list(
+                                "command-palette-list",
+                                self.matches.len(),
+                                cx.processor(|this, range: Range<usize>, _window, cx| {
+                                    this.render_rows(range, cx)
+                                }),
+                            )
+                            .track_scroll(self.scroll_handle.clone())
+                            .size_full(),
+                        )
+                    }),
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzzy_match_prefers_word_starts() {
+        let format = fuzzy_match("fd", "Format Document").unwrap();
+        assert_eq!(format.positions, vec![0, 7]);
+        let fold = fuzzy_match("fa", "Fold All").unwrap();
+        let format_a = fuzzy_match("fa", "Format Document").unwrap();
+        assert!(fold.score > format_a.score);
+        assert!(fuzzy_match("xyz", "Format Document").is_none());
+        // Spaces and case in the query are ignored
+        assert!(fuzzy_match("FOLD all", "Fold All").is_some());
+    }
+
+    #[test]
+    fn test_format_keybinding() {
+        assert_eq!(format_keybinding("ctrl-shift-p"), "Ctrl+Shift+P");
+        assert_eq!(format_keybinding("ctrl-k ctrl-0"), "Ctrl+K Ctrl+0");
+        assert_eq!(format_keybinding("shift-f12"), "Shift+F12");
+        assert_eq!(format_keybinding("ctrl--"), "Ctrl+-");
+        assert_eq!(format_keybinding("escape"), "Esc");
+    }
+}
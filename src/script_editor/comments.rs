@@ -0,0 +1,200 @@
+//! Commenting and uncommenting code with each language's comment markers
+
+use std::ops::Range;
+
+use super::selection_expansion::trimmed;
+
+/// How comments are written in a language
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommentSyntax {
+    /// Starts a comment running to the end of the line, e.g. `//`
+    pub line: Option<String>,
+    /// Opens and closes a comment, e.g. `/*` and `*/`
+    pub block: Option<(String, String)>,
+}
+
+impl CommentSyntax {
+    pub fn new(line: Option<&str>, block: Option<(&str, &str)>) -> Self {
+        Self {
+            line: line.map(str::to_string),
+            block: block.map(|(open, close)| (open.to_string(), close.to_string())),
+        }
+    }
+
+    pub fn for_language(language: &str) -> Self {
+        match language {
+            "rust" | "javascript" | "typescript" | "go" | "c" | "cpp" | "java" | "jsonc" => {
+                Self::new(Some("//"), Some(("/*", "*/")))
+            }
+            "zig" => Self::new(Some("//"), None),
+            "python" | "ruby" | "bash" | "toml" | "yaml" => Self::new(Some("#"), None),
+            "sql" => Self::new(Some("--"), Some(("/*", "*/"))),
+            "lua" => Self::new(Some("--"), Some(("--[[", "]]"))),
+            "css" => Self::new(None, Some(("/*", "*/"))),
+            "html" | "xml" | "markdown" => Self::new(None, Some(("<!--", "-->"))),
+            _ => Self::new(None, None),
+        }
+    }
+
+    /// Markers put around each line by Toggle Line Comment: the line comment
+    /// token, or the block markers for languages without one
+    fn line_markers(&self) -> Option<(&str, &str)> {
+        match (&self.line, &self.block) {
+            (Some(line), _) => Some((line, "")),
+            (None, Some((open, close))) => Some((open, close)),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Edits commenting out the non-blank lines in `lines`, or uncommenting them
+/// when all of them are comments already.
+///
+/// Markers go after the indentation the lines share, so indentation is kept
+/// and commented lines stay aligned.
+pub fn toggle_line_comments(text: &str, lines: Range<u32>, syntax: &CommentSyntax) -> Vec<(Range<usize>, String)> {
+    let Some((open, close)) = syntax.line_markers() else {
+        return Vec::new();
+    };
+    let lines: Vec<Range<usize>> = line_ranges(text, lines)
+        .into_iter()
+        .filter(|line| !text[line.clone()].trim().is_empty())
+        .collect();
+    let indent = |line: &Range<usize>| {
+        let content = &text[line.clone()];
+        content.len() - content.trim_start().len()
+    };
+    let commented = |line: &Range<usize>| {
+        let content = text[line.clone()].trim();
+        content.starts_with(open) && content[open.len()..].ends_with(close)
+    };
+
+    let mut edits = Vec::new();
+    if !lines.is_empty() && lines.iter().all(commented) {
+        for line in &lines {
+            let content = &text[line.clone()];
+            let start = line.start + indent(line);
+            let after_open = start + open.len();
+            let open_end = if text[after_open..].starts_with(' ') { after_open + 1 } else { after_open };
+            edits.push((start..open_end, String::new()));
+            if !close.is_empty() {
+                let end = line.start + content.trim_end().len();
+                let close_start = end - close.len();
+                let close_start = if close_start > open_end && text[..close_start].ends_with(' ') {
+                    close_start - 1
+                } else {
+                    close_start
+                };
+                edits.push((close_start.max(open_end)..end, String::new()));
+            }
+        }
+    } else {
+        let column = lines.iter().map(indent).min().unwrap_or(0);
+        for line in &lines {
+            let start = line.start + column;
+            edits.push((start..start, format!("{} ", open)));
+            if !close.is_empty() {
+                let end = line.start + text[line.clone()].trim_end().len();
+                edits.push((end..end, format!(" {}", close)));
+            }
+        }
+    }
+    edits
+}
+
+/// Edits wrapping `selection` (or the cursor's line when nothing is selected)
+/// in block comment markers, or removing the markers when it is one comment
+pub fn toggle_block_comment(text: &str, selection: Range<usize>, syntax: &CommentSyntax) -> Vec<(Range<usize>, String)> {
+    let Some((open, close)) = syntax.block.as_ref().map(|(open, close)| (open.as_str(), close.as_str())) else {
+        return Vec::new();
+    };
+    let range = if selection.is_empty() {
+        let start = text[..selection.start].rfind('\n').map_or(0, |ix| ix + 1);
+        let end = text[selection.start..].find('\n').map_or(text.len(), |ix| selection.start + ix);
+        trimmed(text, start..end)
+    } else {
+        trimmed(text, selection)
+    };
+    let selected = &text[range.clone()];
+
+    if selected.len() >= open.len() + close.len() && selected.starts_with(open) && selected.ends_with(close) {
+        let mut open_end = range.start + open.len();
+        let mut close_start = range.end - close.len();
+        if text[open_end..close_start].starts_with(' ') {
+            open_end += 1;
+        }
+        if close_start > open_end && text[open_end..close_start].ends_with(' ') {
+            close_start -= 1;
+        }
+        return vec![(range.start..open_end, String::new()), (close_start..range.end, String::new())];
+    }
+    vec![
+        (range.start..range.start, format!("{} ", open)),
+        (range.end..range.end, format!(" {}", close)),
+    ]
+}
+
+/// Byte ranges of lines `lines` of `text`, without their line breaks
+fn line_ranges(text: &str, lines: Range<u32>) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    for (line, content) in text.split('\n').enumerate() {
+        if lines.contains(&(line as u32)) {
+            ranges.push(start..start + content.trim_end_matches('\r').len());
+        }
+        start += content.len() + 1;
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::languages::LANGUAGES;
+
+    fn apply(text: &str, mut edits: Vec<(Range<usize>, String)>) -> String {
+        let mut text = text.to_string();
+        edits.sort_by_key(|(range, _)| range.start);
+        for (range, new_text) in edits.into_iter().rev() {
+            text.replace_range(range, &new_text);
+        }
+        text
+    }
+
+    #[test]
+    fn test_every_language_with_comments_has_markers() {
+        // JSON and plain text have no comments to toggle
+        for language in LANGUAGES.iter().filter(|language| !matches!(language.name, "json" | "text")) {
+            assert!(CommentSyntax::for_language(language.name).line_markers().is_some(), "{}", language.name);
+        }
+    }
+
+    #[test]
+    fn test_toggle_line_comments() {
+        let rust = CommentSyntax::for_language("rust");
+        let text = "fn a() {\n    b();\n\n        c();\n}\n";
+        let commented = apply(text, toggle_line_comments(text, 1..4, &rust));
+        assert_eq!(commented, "fn a() {\n    // b();\n\n    //     c();\n}\n");
+        // Toggling again restores the lines
+        assert_eq!(apply(&commented, toggle_line_comments(&commented, 1..4, &rust)), text);
+
+        let html = CommentSyntax::for_language("html");
+        let text = "  <p>x</p>";
+        let commented = apply(text, toggle_line_comments(text, 0..1, &html));
+        assert_eq!(commented, "  <!-- <p>x</p> -->");
+        assert_eq!(apply(&commented, toggle_line_comments(&commented, 0..1, &html)), text);
+    }
+
+    #[test]
+    fn test_toggle_block_comment() {
+        let rust = CommentSyntax::for_language("rust");
+        let text = "let x = a + b;";
+        let commented = apply(text, toggle_block_comment(text, 8..13, &rust));
+        assert_eq!(commented, "let x = /* a + b */;");
+        assert_eq!(apply(&commented, toggle_block_comment(&commented, 8..19, &rust)), text);
+
+        // Without a selection the cursor's line is wrapped
+        let text = "  x();\n";
+        assert_eq!(apply(text, toggle_block_comment(text, 3..3, &rust)), "  /* x(); */\n");
+    }
+}
@@ -0,0 +1,200 @@
+//! Text shown by the diff mode editors: both versions aligned line for line
+//! side by side, or merged into one unified view
+
+use similar::{ChangeTag, TextDiff};
+use std::ops::Range;
+
+use super::git_diff::{diff_hunks, DiffHunk};
+
+/// Changed lines paired up below this similarity are not highlighted character by character
+const MIN_INTRA_LINE_RATIO: f32 = 0.4;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DiffLayout {
+    #[default]
+    SideBySide,
+    Inline,
+}
+
+/// Which text a diff editor shows
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiffSide {
+    /// The old version, padded to line up with the new one
+    Before,
+    /// The new version, padded to line up with the old one
+    After,
+    /// Unchanged lines once, removed lines above the lines added in their place
+    Inline,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiffLine {
+    Unchanged,
+    Added,
+    Removed,
+    /// Blank line keeping the two sides aligned
+    Padding,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DiffView {
+    /// Every line ends with a newline
+    pub text: String,
+    pub lines: Vec<DiffLine>,
+    /// Byte ranges of `text` that changed within a modified line
+    pub changed_ranges: Vec<Range<usize>>,
+    /// Line of `text` where each hunk of `hunks` starts
+    pub hunk_rows: Vec<u32>,
+    pub hunks: Vec<DiffHunk>,
+}
+
+impl DiffView {
+    fn push_line(&mut self, line: &str, kind: DiffLine) -> usize {
+        let start = self.text.len();
+        self.text.push_str(line.strip_suffix('\n').unwrap_or(line));
+        self.text.push('\n');
+        self.lines.push(kind);
+        start
+    }
+
+    fn push_changes(&mut self, start: usize, ranges: Vec<Range<usize>>) {
+        self.changed_ranges
+            .extend(ranges.into_iter().map(|range| start + range.start..start + range.end));
+    }
+}
+
+/// The view of `before` → `after` for one side
+pub fn diff_view(before: &str, after: &str, side: DiffSide) -> DiffView {
+    let old: Vec<&str> = before.split_inclusive('\n').collect();
+    let new: Vec<&str> = after.split_inclusive('\n').collect();
+    let mut view = DiffView {
+        hunks: diff_hunks(before, after),
+        ..Default::default()
+    };
+
+    let mut old_pos = 0;
+    for hunk_ix in 0..view.hunks.len() {
+        let hunk = view.hunks[hunk_ix].clone();
+        for line in &old[old_pos..hunk.head_lines.start as usize] {
+            view.push_line(line, DiffLine::Unchanged);
+        }
+        old_pos = hunk.head_lines.end as usize;
+        view.hunk_rows.push(view.lines.len() as u32);
+
+        let removed = &old[hunk.head_lines.start as usize..hunk.head_lines.end as usize];
+        let added = &new[hunk.lines.start as usize..hunk.lines.end as usize];
+        let rows = removed.len().max(added.len());
+        match side {
+            DiffSide::Before | DiffSide::After => {
+                for row in 0..rows {
+                    let (line, paired) = match side {
+                        DiffSide::Before => (removed.get(row), added.get(row)),
+                        _ => (added.get(row), removed.get(row)),
+                    };
+                    let Some(line) = line else {
+                        view.push_line("", DiffLine::Padding);
+                        continue;
+                    };
+                    let kind = if side == DiffSide::Before { DiffLine::Removed } else { DiffLine::Added };
+                    let start = view.push_line(line, kind);
+                    if let Some(paired) = paired {
+                        let (old_line, new_line) = if side == DiffSide::Before { (line, paired) } else { (paired, line) };
+                        let (old_changes, new_changes) = intra_line_changes(old_line, new_line);
+                        view.push_changes(start, if side == DiffSide::Before { old_changes } else { new_changes });
+                    }
+                }
+            }
+            DiffSide::Inline => {
+                let pairs = removed.len().min(added.len());
+                let changes: Vec<_> = (0..pairs).map(|row| intra_line_changes(removed[row], added[row])).collect();
+                for (row, line) in removed.iter().enumerate() {
+                    let start = view.push_line(line, DiffLine::Removed);
+                    if let Some((old_changes, _)) = changes.get(row) {
+                        view.push_changes(start, old_changes.clone());
+                    }
+                }
+                for (row, line) in added.iter().enumerate() {
+                    let start = view.push_line(line, DiffLine::Added);
+                    if let Some((_, new_changes)) = changes.get(row) {
+                        view.push_changes(start, new_changes.clone());
+                    }
+                }
+            }
+        }
+    }
+    for line in &old[old_pos..] {
+        view.push_line(line, DiffLine::Unchanged);
+    }
+    view
+}
+
+/// Byte ranges that differ between two versions of a line, in each of them.
+///
+/// Empty when the lines have too little in common for the ranges to help.
+fn intra_line_changes(old: &str, new: &str) -> (Vec<Range<usize>>, Vec<Range<usize>>) {
+    let (old, new) = (old.trim_end_matches('\n'), new.trim_end_matches('\n'));
+    let diff = TextDiff::from_chars(old, new);
+    if diff.ratio() < MIN_INTRA_LINE_RATIO {
+        return (Vec::new(), Vec::new());
+    }
+
+    fn extend(ranges: &mut Vec<Range<usize>>, range: Range<usize>) {
+        match ranges.last_mut() {
+            Some(last) if last.end == range.start => last.end = range.end,
+            _ => ranges.push(range),
+        }
+    }
+
+    let (mut old_ranges, mut new_ranges) = (Vec::new(), Vec::new());
+    let (mut old_offset, mut new_offset) = (0, 0);
+    for change in diff.iter_all_changes() {
+        let len = change.value().len();
+        match change.tag() {
+            ChangeTag::Equal => {
+                old_offset += len;
+                new_offset += len;
+            }
+            ChangeTag::Delete => {
+                extend(&mut old_ranges, old_offset..old_offset + len);
+                old_offset += len;
+            }
+            ChangeTag::Insert => {
+                extend(&mut new_ranges, new_offset..new_offset + len);
+                new_offset += len;
+            }
+        }
+    }
+    (old_ranges, new_ranges)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sides_stay_aligned() {
+        let before = "a\nb\nc\n";
+        let after = "a\nB\nx\nc\n";
+        let old = diff_view(before, after, DiffSide::Before);
+        let new = diff_view(before, after, DiffSide::After);
+
+        assert_eq!(old.text, "a\nb\n\nc\n");
+        assert_eq!(new.text, "a\nB\nx\nc\n");
+        assert_eq!(old.lines, [DiffLine::Unchanged, DiffLine::Removed, DiffLine::Padding, DiffLine::Unchanged]);
+        assert_eq!(new.lines[1..3], [DiffLine::Added, DiffLine::Added]);
+        assert_eq!(old.hunk_rows, [1]);
+        assert_eq!(old.hunk_rows, new.hunk_rows);
+    }
+
+    #[test]
+    fn test_inline_and_intra_line_changes() {
+        let before = "let value = 1;\n";
+        let after = "let value = 2;\n";
+        let view = diff_view(before, after, DiffSide::Inline);
+
+        assert_eq!(view.text, "let value = 1;\nlet value = 2;\n");
+        assert_eq!(view.lines, [DiffLine::Removed, DiffLine::Added]);
+        // Only the digit differs, on both lines
+        assert_eq!(view.changed_ranges, [12..13, 27..28]);
+    }
+}
@@ -0,0 +1,405 @@
+//! `.editorconfig` files: a project's indentation, line endings, charset and
+//! whitespace conventions, applied to its files as they're opened
+//!
+//! Every `.editorconfig` from a file's folder up is read, stopping at one
+//! with `root = true`. Sections whose glob matches the file apply in order,
+//! nearer files and later sections over the others.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use super::indentation::IndentStyle;
+use super::line_endings::LineEnding;
+
+/// What the `.editorconfig` files above a file say about it; `None` where
+/// they say nothing, or `unset`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EditorConfig {
+    pub hard_tabs: Option<bool>,
+    pub indent_size: Option<u32>,
+    pub tab_width: Option<u32>,
+    pub end_of_line: Option<LineEnding>,
+    /// As written: `latin1`, `utf-8`, `utf-8-bom`, `utf-16be` or `utf-16le`
+    pub charset: Option<String>,
+    pub trim_trailing_whitespace: Option<bool>,
+    pub insert_final_newline: Option<bool>,
+}
+
+impl EditorConfig {
+    /// Read the `.editorconfig` files that apply to `path`
+    pub fn for_file(path: &Path) -> Self {
+        let mut files = Vec::new();
+        for dir in path.ancestors().skip(1) {
+            let Ok(text) = std::fs::read_to_string(dir.join(".editorconfig")) else {
+                continue;
+            };
+            let file = ConfigFile::parse(&text);
+            let root = file.root;
+            files.push((dir.to_path_buf(), file));
+            if root {
+                break;
+            }
+        }
+        Self::resolve(&files, path)
+    }
+
+    /// The properties `files`, nearest first, give `path`
+    pub fn resolve(files: &[(PathBuf, ConfigFile)], path: &Path) -> Self {
+        let mut properties = BTreeMap::new();
+        for (dir, file) in files.iter().rev() {
+            let Ok(relative) = path.strip_prefix(dir) else {
+                continue;
+            };
+            let relative = relative.to_string_lossy().replace('\\', "/");
+            for (glob, section) in &file.sections {
+                if section_matches(glob, &relative) {
+                    for (key, value) in section {
+                        properties.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        properties.retain(|_, value| value != "unset");
+
+        let size = |key: &str| properties.get(key).and_then(|value| value.parse::<u32>().ok()).filter(|size| *size > 0);
+        let hard_tabs = match properties.get("indent_style").map(String::as_str) {
+            Some("tab") => Some(true),
+            Some("space") => Some(false),
+            _ => None,
+        };
+        let tab_width = size("tab_width");
+        let indent_size = match properties.get("indent_size").map(String::as_str) {
+            Some("tab") => tab_width,
+            _ => size("indent_size"),
+        };
+        // Each defaults to the other; a tab indents by a tab's width
+        let tab_width = tab_width.or(indent_size);
+        let indent_size = indent_size.or(if hard_tabs == Some(true) { tab_width } else { None });
+        let flag = |key: &str| match properties.get(key).map(String::as_str) {
+            Some("true") => Some(true),
+            Some("false") => Some(false),
+            _ => None,
+        };
+        Self {
+            hard_tabs,
+            indent_size,
+            tab_width,
+            end_of_line: match properties.get("end_of_line").map(String::as_str) {
+                Some("lf") => Some(LineEnding::Lf),
+                Some("crlf") => Some(LineEnding::Crlf),
+                _ => None,
+            },
+            charset: properties.get("charset").cloned(),
+            trim_trailing_whitespace: flag("trim_trailing_whitespace"),
+            insert_final_newline: flag("insert_final_newline"),
+        }
+    }
+
+    /// Whether it says how to indent, which then outweighs a file's content
+    pub fn has_indentation(&self) -> bool {
+        self.hard_tabs.is_some() || self.indent_size.is_some()
+    }
+
+    /// `style` with whatever it says about indentation instead
+    pub fn indent_style(&self, style: IndentStyle) -> IndentStyle {
+        let hard_tabs = self.hard_tabs.unwrap_or(style.hard_tabs);
+        let width = if hard_tabs {
+            self.tab_width.or(self.indent_size)
+        } else {
+            self.indent_size.or(self.tab_width)
+        };
+        IndentStyle {
+            hard_tabs,
+            width: width.unwrap_or(style.width),
+        }
+    }
+}
+
+/// One `.editorconfig`: its sections in order, with the keys and values they set
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConfigFile {
+    pub root: bool,
+    pub sections: Vec<(String, Vec<(String, String)>)>,
+}
+
+impl ConfigFile {
+    pub fn parse(text: &str) -> Self {
+        let mut file = ConfigFile::default();
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(['#', ';']) {
+                continue;
+            }
+            if let Some(glob) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+                file.sections.push((glob.to_string(), Vec::new()));
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let key = key.trim().to_lowercase();
+            // Values are case-insensitive, and every one read here is a word or number
+            let value = value.trim().to_lowercase();
+            match file.sections.last_mut() {
+                Some((_, properties)) => properties.push((key, value)),
+                None if key == "root" => file.root = value == "true",
+                None => {}
+            }
+        }
+        file
+    }
+}
+
+/// Whether a section's glob matches a file at `relative`, `/`-separated
+/// from the `.editorconfig`'s folder. A glob without a `/` matches the file
+/// name in any folder.
+fn section_matches(glob: &str, relative: &str) -> bool {
+    if glob.contains('/') {
+        glob_matches(glob.strip_prefix('/').unwrap_or(glob), relative)
+    } else {
+        glob_matches(glob, relative.rsplit('/').next().unwrap_or(relative))
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Token {
+    Char(char),
+    /// `?`
+    Any,
+    /// `*`, which stops at `/`
+    Star,
+    /// `**`
+    DoubleStar,
+    /// `[abc]`, `[a-z]` or `[!abc]`
+    Class { negated: bool, ranges: Vec<(char, char)> },
+    /// `{a,b}`
+    Either(Vec<Vec<Token>>),
+    /// `{1..10}`
+    Number(i64, i64),
+}
+
+fn glob_matches(glob: &str, text: &str) -> bool {
+    let glob: Vec<char> = glob.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    matches(&tokenize(&glob), &text)
+}
+
+fn tokenize(glob: &[char]) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut ix = 0;
+    while ix < glob.len() {
+        match glob[ix] {
+            '\\' if ix + 1 < glob.len() => {
+                tokens.push(Token::Char(glob[ix + 1]));
+                ix += 2;
+                continue;
+            }
+            '?' => tokens.push(Token::Any),
+            '*' if glob.get(ix + 1) == Some(&'*') => {
+                tokens.push(Token::DoubleStar);
+                ix += 1;
+            }
+            '*' => tokens.push(Token::Star),
+            '[' => {
+                if let Some(end) = glob[ix + 1..].iter().position(|c| *c == ']').map(|end| ix + 1 + end) {
+                    tokens.push(class(&glob[ix + 1..end]));
+                    ix = end + 1;
+                    continue;
+                }
+                tokens.push(Token::Char('['));
+            }
+            '{' => {
+                if let Some(end) = closing_brace(glob, ix) {
+                    tokens.push(braces(&glob[ix + 1..end]));
+                    ix = end + 1;
+                    continue;
+                }
+                tokens.push(Token::Char('{'));
+            }
+            c => tokens.push(Token::Char(c)),
+        }
+        ix += 1;
+    }
+    tokens
+}
+
+fn class(inside: &[char]) -> Token {
+    let (negated, inside) = match inside.first() {
+        Some('!') => (true, &inside[1..]),
+        _ => (false, inside),
+    };
+    let mut ranges = Vec::new();
+    let mut ix = 0;
+    while ix < inside.len() {
+        if ix + 2 < inside.len() && inside[ix + 1] == '-' {
+            ranges.push((inside[ix], inside[ix + 2]));
+            ix += 3;
+        } else {
+            ranges.push((inside[ix], inside[ix]));
+            ix += 1;
+        }
+    }
+    Token::Class { negated, ranges }
+}
+
+fn closing_brace(glob: &[char], open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (ix, c) in glob.iter().enumerate().skip(open) {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(ix);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn braces(inside: &[char]) -> Token {
+    let text: String = inside.iter().collect();
+    if let Some((start, end)) = text.split_once("..") {
+        if let (Ok(start), Ok(end)) = (start.parse::<i64>(), end.parse::<i64>()) {
+            return Token::Number(start.min(end), start.max(end));
+        }
+    }
+    // Split at the commas outside nested braces
+    let mut alternatives = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (ix, c) in inside.iter().enumerate() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                alternatives.push(tokenize(&inside[start..ix]));
+                start = ix + 1;
+            }
+            _ => {}
+        }
+    }
+    alternatives.push(tokenize(&inside[start..]));
+    if alternatives.len() == 1 {
+        // `{word}` is just the word in braces
+        let mut literal = vec![Token::Char('{')];
+        literal.extend(alternatives.remove(0));
+        literal.push(Token::Char('}'));
+        return Token::Either(vec![literal]);
+    }
+    Token::Either(alternatives)
+}
+
+fn matches(tokens: &[Token], text: &[char]) -> bool {
+    let Some((token, rest)) = tokens.split_first() else {
+        return text.is_empty();
+    };
+    match token {
+        Token::Char(c) => text.first() == Some(c) && matches(rest, &text[1..]),
+        Token::Any => text.first().is_some_and(|c| *c != '/') && matches(rest, &text[1..]),
+        Token::Star => (0..=text.len())
+            .take_while(|len| *len == 0 || text[len - 1] != '/')
+            .any(|len| matches(rest, &text[len..])),
+        Token::DoubleStar => (0..=text.len()).any(|len| matches(rest, &text[len..])),
+        Token::Class { negated, ranges } => text.first().is_some_and(|c| {
+            *c != '/' && ranges.iter().any(|(low, high)| (low..=high).contains(&c)) != *negated
+        }) && matches(rest, &text[1..]),
+        Token::Either(alternatives) => alternatives.iter().any(|alternative| {
+            let mut tokens = alternative.clone();
+            tokens.extend_from_slice(rest);
+            matches(&tokens, text)
+        }),
+        Token::Number(low, high) => {
+            let sign = usize::from(text.first() == Some(&'-'));
+            let digits = text[sign..].iter().take_while(|c| c.is_ascii_digit()).count();
+            (1..=digits).any(|len| {
+                let number: String = text[..sign + len].iter().collect();
+                number.parse::<i64>().is_ok_and(|number| (*low..=*high).contains(&number))
+                    && matches(rest, &text[sign + len..])
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "\
+# Top of the project
+root = true
+
+[*]
+indent_style = space
+indent_size = 4
+end_of_line = lf
+charset = utf-8
+trim_trailing_whitespace = true
+insert_final_newline = true
+
+[*.{js,json}]
+indent_size = 2
+
+[Makefile]
+indent_style = tab
+
+[*.md]
+trim_trailing_whitespace = false
+
+[lib/**.js]
+indent_size = 8
+
+[{tests,benches}/data_{1..3}.txt]
+end_of_line = crlf
+";
+
+    fn config_for(files: &[(&str, &str)], path: &str) -> EditorConfig {
+        let files: Vec<_> = files
+            .iter()
+            .map(|(dir, text)| (PathBuf::from(dir), ConfigFile::parse(text)))
+            .collect();
+        EditorConfig::resolve(&files, Path::new(path))
+    }
+
+    #[test]
+    fn test_sample_config() {
+        let files = [("/project", SAMPLE)];
+        let rust = config_for(&files, "/project/src/main.rs");
+        assert_eq!(
+            rust,
+            EditorConfig {
+                hard_tabs: Some(false),
+                indent_size: Some(4),
+                tab_width: Some(4),
+                end_of_line: Some(LineEnding::Lf),
+                charset: Some("utf-8".to_string()),
+                trim_trailing_whitespace: Some(true),
+                insert_final_newline: Some(true),
+            }
+        );
+        assert_eq!(config_for(&files, "/project/web/app.js").indent_size, Some(2));
+        assert_eq!(config_for(&files, "/project/lib/deep/util.js").indent_size, Some(8));
+        assert_eq!(config_for(&files, "/project/package.json").indent_size, Some(2));
+        let makefile = config_for(&files, "/project/Makefile");
+        assert_eq!(makefile.indent_style(IndentStyle::spaces(2)), IndentStyle::tabs(4));
+        assert_eq!(config_for(&files, "/project/README.md").trim_trailing_whitespace, Some(false));
+        assert_eq!(config_for(&files, "/project/tests/data_2.txt").end_of_line, Some(LineEnding::Crlf));
+        assert_eq!(config_for(&files, "/project/tests/data_4.txt").end_of_line, Some(LineEnding::Lf));
+        // Outside the project, nothing applies
+        assert_eq!(config_for(&files, "/elsewhere/main.rs"), EditorConfig::default());
+    }
+
+    #[test]
+    fn test_nearer_files_win() {
+        let files = [
+            ("/project/vendor", "[*.c]\nindent_style = tab\nindent_size = tab\ntab_width = 8\ncharset = unset\n"),
+            ("/project", SAMPLE),
+        ];
+        let vendored = config_for(&files, "/project/vendor/zlib/inflate.c");
+        assert_eq!((vendored.hard_tabs, vendored.indent_size, vendored.charset), (Some(true), Some(8), None));
+        assert_eq!(vendored.end_of_line, Some(LineEnding::Lf));
+        assert!(ConfigFile::parse(SAMPLE).root);
+        assert!(!config_for(&[("/project", "[*.py]\nindent_size = 4\n")], "/project/a.rs").has_indentation());
+    }
+}
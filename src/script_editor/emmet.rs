@@ -0,0 +1,492 @@
+//! Emmet abbreviations: `ul>li.item*3` and Tab becomes the markup it describes
+//!
+//! Supported: child `>`, sibling `+`, climb-up `^`, groups `()`,
+//! multiplication `*` with `$` numbering, `#id`, `.class`, `[attr=value]`
+//! and `{text}`. The expansion is a snippet whose tab stops are the empty
+//! attribute values and element contents.
+
+use std::path::Path;
+
+/// Elements without content or closing tag
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source", "track", "wbr",
+];
+
+/// Names that expand on their own; anything else needs an operator to count as an abbreviation
+const KNOWN_TAGS: &[&str] = &[
+    "a", "abbr", "article", "aside", "audio", "b", "blockquote", "body", "br", "button", "canvas", "code",
+    "dd", "details", "div", "dl", "dt", "em", "footer", "form", "h1", "h2", "h3", "h4", "h5", "h6", "head",
+    "header", "hr", "html", "i", "iframe", "img", "input", "label", "li", "link", "main", "meta", "nav", "ol",
+    "option", "p", "pre", "script", "section", "select", "small", "span", "strong", "style", "summary",
+    "table", "tbody", "td", "textarea", "th", "thead", "title", "tr", "ul", "video",
+];
+
+/// What `!` expands to
+const HTML_DOCUMENT: &str = "<!DOCTYPE html>\n<html lang=\"${1:en}\">\n<head>\n\t<meta charset=\"UTF-8\">\n\t\
+    <meta name=\"viewport\" content=\"width=device-width, initial-scale=1.0\">\n\t<title>${2:Document}</title>\n\
+    </head>\n<body>\n\t$0\n</body>\n</html>";
+
+/// Whether abbreviations expand in this file: HTML, and JSX in `.jsx`/`.tsx`
+pub fn supports(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|ext| ext.to_str()),
+        Some("html" | "htm" | "jsx" | "tsx")
+    )
+}
+
+pub fn is_jsx(path: &Path) -> bool {
+    matches!(path.extension().and_then(|ext| ext.to_str()), Some("jsx" | "tsx"))
+}
+
+/// Length of the abbreviation that ends `before_cursor`: back to whitespace
+/// (outside `[]` and `{}`) or anything an abbreviation can't contain
+pub fn abbreviation_len(before_cursor: &str) -> usize {
+    let mut depth = 0;
+    let mut len = 0;
+    for ch in before_cursor.chars().rev() {
+        match ch {
+            ']' | '}' => depth += 1,
+            '[' | '{' if depth == 0 => break,
+            '[' | '{' => depth -= 1,
+            _ if depth > 0 => {}
+            ch if ch.is_alphanumeric() || "-_:.#>+^*$()!".contains(ch) => {}
+            _ => break,
+        }
+        len += ch.len_utf8();
+    }
+    let start = before_cursor.len() - len;
+    // A bracket left open, or the inside of a tag being typed
+    if depth != 0 || before_cursor[..start].ends_with('<') {
+        return 0;
+    }
+    len
+}
+
+/// The snippet body (LSP snippet syntax) `abbreviation` expands to, or `None`
+/// when it isn't one
+pub fn expand(abbreviation: &str, jsx: bool) -> Option<String> {
+    if abbreviation == "!" && !jsx {
+        return Some(HTML_DOCUMENT.to_string());
+    }
+    let mut parser = Parser {
+        chars: abbreviation.chars().collect(),
+        ix: 0,
+    };
+    let items = parser.siblings()?;
+    if parser.ix < parser.chars.len() || items.is_empty() {
+        return None;
+    }
+    let is_plain_word = abbreviation.chars().all(|ch| ch.is_alphanumeric() || ch == '-');
+    if is_plain_word && !KNOWN_TAGS.contains(&abbreviation) {
+        return None;
+    }
+
+    let tags = instantiate(&items, "", 1);
+    let mut body = String::new();
+    let mut output = Output { jsx, next_stop: 1, body: &mut body };
+    output.siblings(&tags, 0);
+    Some(body)
+}
+
+#[derive(Clone, Debug, Default)]
+struct Element {
+    /// Empty for an implicit tag (`.box`), chosen from the parent
+    name: String,
+    id: Option<String>,
+    classes: Vec<String>,
+    attributes: Vec<(String, Option<String>)>,
+    text: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+enum ItemKind {
+    Element(Element),
+    Group(Vec<Item>),
+}
+
+#[derive(Clone, Debug)]
+struct Item {
+    kind: ItemKind,
+    count: usize,
+    children: Vec<Item>,
+}
+
+struct Parser {
+    chars: Vec<char>,
+    ix: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.ix).copied()
+    }
+
+    /// Items joined by `+`, with `>` nesting and `^` climbing back out
+    fn siblings(&mut self) -> Option<Vec<Item>> {
+        let mut items = vec![self.item()?];
+        loop {
+            match self.peek() {
+                Some('+') => {
+                    self.ix += 1;
+                    items.push(self.item()?);
+                }
+                Some('>') => {
+                    self.ix += 1;
+                    let children = self.siblings()?;
+                    let parent = items.last_mut()?;
+                    if matches!(parent.kind, ItemKind::Group(_)) {
+                        return None;
+                    }
+                    parent.children = children;
+                    // Each `^` after the children climbs one level back out
+                    if self.peek() != Some('^') {
+                        return Some(items);
+                    }
+                    self.ix += 1;
+                    if self.peek() == Some('^') {
+                        return Some(items);
+                    }
+                    items.push(self.item()?);
+                }
+                _ => return Some(items),
+            }
+        }
+    }
+
+    fn item(&mut self) -> Option<Item> {
+        let kind = if self.peek() == Some('(') {
+            self.ix += 1;
+            let items = self.siblings()?;
+            if self.peek() != Some(')') {
+                return None;
+            }
+            self.ix += 1;
+            ItemKind::Group(items)
+        } else {
+            ItemKind::Element(self.element()?)
+        };
+        let mut count = 1;
+        if self.peek() == Some('*') {
+            self.ix += 1;
+            count = self.number()?.clamp(1, 1000);
+        }
+        Some(Item {
+            kind,
+            count,
+            children: Vec::new(),
+        })
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        let start = self.ix;
+        while self.peek().is_some_and(|ch| ch.is_ascii_digit()) {
+            self.ix += 1;
+        }
+        self.chars[start..self.ix].iter().collect::<String>().parse().ok()
+    }
+
+    fn name(&mut self) -> String {
+        let start = self.ix;
+        while self.peek().is_some_and(|ch| ch.is_alphanumeric() || "-_:$".contains(ch)) {
+            self.ix += 1;
+        }
+        self.chars[start..self.ix].iter().collect()
+    }
+
+    fn element(&mut self) -> Option<Element> {
+        let mut element = Element {
+            name: self.name(),
+            ..Default::default()
+        };
+        let mut has_parts = !element.name.is_empty();
+        loop {
+            match self.peek() {
+                Some('#') => {
+                    self.ix += 1;
+                    element.id = Some(self.name()).filter(|id| !id.is_empty());
+                }
+                Some('.') => {
+                    self.ix += 1;
+                    let class = self.name();
+                    if class.is_empty() {
+                        return None;
+                    }
+                    element.classes.push(class);
+                }
+                Some('[') => {
+                    self.ix += 1;
+                    self.attributes(&mut element.attributes)?;
+                }
+                Some('{') => {
+                    self.ix += 1;
+                    let start = self.ix;
+                    while self.peek().is_some_and(|ch| ch != '}') {
+                        self.ix += 1;
+                    }
+                    self.peek()?;
+                    element.text = Some(self.chars[start..self.ix].iter().collect());
+                    self.ix += 1;
+                }
+                _ => break,
+            }
+            has_parts = true;
+        }
+        has_parts.then_some(element)
+    }
+
+    /// `name`, `name=value` or `name="value"` pairs up to the closing `]`
+    fn attributes(&mut self, attributes: &mut Vec<(String, Option<String>)>) -> Option<()> {
+        loop {
+            while self.peek() == Some(' ') {
+                self.ix += 1;
+            }
+            if self.peek()? == ']' {
+                self.ix += 1;
+                return Some(());
+            }
+            let name = self.name();
+            if name.is_empty() {
+                return None;
+            }
+            let mut value = None;
+            if self.peek() == Some('=') {
+                self.ix += 1;
+                let mut text = String::new();
+                match self.peek()? {
+                    quote @ ('"' | '\'') => {
+                        self.ix += 1;
+                        while self.peek()? != quote {
+                            text.push(self.chars[self.ix]);
+                            self.ix += 1;
+                        }
+                        self.ix += 1;
+                    }
+                    _ => {
+                        while self.peek().is_some_and(|ch| ch != ' ' && ch != ']') {
+                            text.push(self.chars[self.ix]);
+                            self.ix += 1;
+                        }
+                    }
+                }
+                value = Some(text);
+            }
+            attributes.push((name, value));
+        }
+    }
+}
+
+/// An element with its repeats laid out and `$` numbered
+#[derive(Debug)]
+struct Tag {
+    name: String,
+    attributes: Vec<(String, Option<String>)>,
+    text: Option<String>,
+    children: Vec<Tag>,
+}
+
+fn instantiate(items: &[Item], parent: &str, index: usize) -> Vec<Tag> {
+    let mut tags = Vec::new();
+    for item in items {
+        for repeat in 1..=item.count {
+            let index = if item.count > 1 { repeat } else { index };
+            match &item.kind {
+                ItemKind::Group(group) => tags.extend(instantiate(group, parent, index)),
+                ItemKind::Element(element) => tags.push(instantiate_element(element, &item.children, parent, index)),
+            }
+        }
+    }
+    tags
+}
+
+fn instantiate_element(element: &Element, children: &[Item], parent: &str, index: usize) -> Tag {
+    let name = if element.name.is_empty() {
+        implicit_name(parent).to_string()
+    } else {
+        number(&element.name, index)
+    };
+    let mut attributes = Vec::new();
+    if let Some(id) = &element.id {
+        attributes.push(("id".to_string(), Some(number(id, index))));
+    }
+    if !element.classes.is_empty() {
+        let classes: Vec<String> = element.classes.iter().map(|class| number(class, index)).collect();
+        attributes.push(("class".to_string(), Some(classes.join(" "))));
+    }
+    for (attribute, value) in &element.attributes {
+        attributes.push((number(attribute, index), value.as_ref().map(|value| number(value, index))));
+    }
+    // Attributes an element is rarely written without, left to fill in
+    for default in default_attributes(&name) {
+        if !attributes.iter().any(|(attribute, _)| attribute == default) {
+            attributes.push((default.to_string(), Some(String::new())));
+        }
+    }
+    Tag {
+        children: instantiate(children, &name, index),
+        text: element.text.as_ref().map(|text| number(text, index)),
+        attributes,
+        name,
+    }
+}
+
+fn implicit_name(parent: &str) -> &'static str {
+    match parent {
+        "ul" | "ol" => "li",
+        "table" | "tbody" | "thead" | "tfoot" => "tr",
+        "tr" => "td",
+        "select" | "optgroup" => "option",
+        _ => "div",
+    }
+}
+
+fn default_attributes(name: &str) -> &'static [&'static str] {
+    match name {
+        "a" => &["href"],
+        "img" => &["src", "alt"],
+        "input" => &["type"],
+        "label" => &["for"],
+        "form" => &["action"],
+        "link" => &["rel", "href"],
+        "script" => &["src"],
+        _ => &[],
+    }
+}
+
+/// Replace each run of `$` with `index`, zero-padded to the run's length
+fn number(text: &str, index: usize) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch != '$' {
+            output.push(ch);
+            continue;
+        }
+        let mut width = 1;
+        while chars.next_if_eq(&'$').is_some() {
+            width += 1;
+        }
+        output.push_str(&format!("{:0width$}", index, width = width));
+    }
+    output
+}
+
+/// Text written into the snippet as is
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        if matches!(ch, '$' | '}' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(ch);
+    }
+    escaped
+}
+
+struct Output<'a> {
+    jsx: bool,
+    next_stop: usize,
+    body: &'a mut String,
+}
+
+impl Output<'_> {
+    fn stop(&mut self) {
+        self.body.push_str(&format!("${{{}}}", self.next_stop));
+        self.next_stop += 1;
+    }
+
+    fn siblings(&mut self, tags: &[Tag], depth: usize) {
+        for (ix, tag) in tags.iter().enumerate() {
+            if ix > 0 {
+                self.body.push('\n');
+                self.body.push_str(&"\t".repeat(depth));
+            }
+            self.tag(tag, depth);
+        }
+    }
+
+    fn tag(&mut self, tag: &Tag, depth: usize) {
+        self.body.push('<');
+        self.body.push_str(&escape(&tag.name));
+        for (attribute, value) in &tag.attributes {
+            let attribute = match attribute.as_str() {
+                "class" if self.jsx => "className",
+                "for" if self.jsx => "htmlFor",
+                attribute => attribute,
+            };
+            self.body.push(' ');
+            self.body.push_str(&escape(attribute));
+            match value {
+                Some(value) => {
+                    self.body.push_str("=\"");
+                    if value.is_empty() {
+                        self.stop();
+                    } else {
+                        self.body.push_str(&escape(value));
+                    }
+                    self.body.push('"');
+                }
+                // Boolean attributes are written bare in HTML
+                None if self.jsx => self.body.push_str("={true}"),
+                None => {}
+            }
+        }
+        if VOID_ELEMENTS.contains(&tag.name.as_str()) {
+            self.body.push_str(if self.jsx { " />" } else { ">" });
+            return;
+        }
+        self.body.push('>');
+        if let Some(text) = &tag.text {
+            self.body.push_str(&escape(text));
+        }
+        if !tag.children.is_empty() {
+            self.body.push('\n');
+            self.body.push_str(&"\t".repeat(depth + 1));
+            self.siblings(&tag.children, depth + 1);
+            self.body.push('\n');
+            self.body.push_str(&"\t".repeat(depth));
+        } else if tag.text.is_none() {
+            self.stop();
+        }
+        self.body.push_str("</");
+        self.body.push_str(&escape(&tag.name));
+        self.body.push('>');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand() {
+        assert_eq!(
+            expand("ul>li.item$*3", false).as_deref(),
+            Some("<ul>\n\t<li class=\"item1\">${1}</li>\n\t<li class=\"item2\">${2}</li>\n\t<li class=\"item3\">${3}</li>\n</ul>")
+        );
+        assert_eq!(
+            expand("div#main>p{Hi}+a[title=\"Go home\"]^img", false).as_deref(),
+            Some("<div id=\"main\">\n\t<p>Hi</p>\n\t<a title=\"Go home\" href=\"${1}\">${2}</a>\n</div>\n<img src=\"${3}\" alt=\"${4}\">")
+        );
+        assert_eq!(
+            expand(".card>label+input[disabled]", true).as_deref(),
+            Some("<div className=\"card\">\n\t<label htmlFor=\"${1}\">${2}</label>\n\t<input disabled={true} type=\"${3}\" />\n</div>")
+        );
+        // Words that aren't tags stay words
+        assert_eq!(expand("hello", false), None);
+        assert_eq!(expand("p", false).as_deref(), Some("<p>${1}</p>"));
+        assert_eq!(expand("ul>", false), None);
+        assert_eq!(
+            expand("nav>ul>li^^footer", false).as_deref(),
+            Some("<nav>\n\t<ul>\n\t\t<li>${1}</li>\n\t</ul>\n</nav>\n<footer>${2}</footer>")
+        );
+    }
+
+    #[test]
+    fn test_abbreviation_len() {
+        assert_eq!(abbreviation_len("  return ul>li*2"), "ul>li*2".len());
+        assert_eq!(abbreviation_len("x a[title=\"a b\"]"), "a[title=\"a b\"]".len());
+        assert_eq!(abbreviation_len("<div>{p"), "p".len());
+        assert_eq!(abbreviation_len("<div"), 0);
+        assert_eq!(abbreviation_len("text "), 0);
+    }
+}
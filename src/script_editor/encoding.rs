@@ -0,0 +1,207 @@
+//! Text encodings files are read and written in
+//!
+//! A byte order mark picks UTF-8 or UTF-16; otherwise a file is UTF-8 when
+//! it's valid UTF-8 and Latin-1 when it isn't, which any bytes decode as. A
+//! file keeps its encoding, and its byte order mark, when saved.
+
+use encoding_rs::{Encoding, EUC_KR, GBK, SHIFT_JIS, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1251, WINDOWS_1252};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FileEncoding {
+    pub encoding: &'static Encoding,
+    /// Whether the file starts with a byte order mark
+    pub bom: bool,
+}
+
+impl Default for FileEncoding {
+    fn default() -> Self {
+        Self::without_bom(UTF_8)
+    }
+}
+
+impl FileEncoding {
+    fn without_bom(encoding: &'static Encoding) -> Self {
+        Self { encoding, bom: false }
+    }
+
+    /// Encodings offered for saving; UTF-16 is always written with a byte
+    /// order mark, or it wouldn't be recognised when read again
+    pub fn choices() -> [FileEncoding; 9] {
+        [
+            Self::without_bom(UTF_8),
+            FileEncoding { encoding: UTF_8, bom: true },
+            FileEncoding { encoding: UTF_16LE, bom: true },
+            FileEncoding { encoding: UTF_16BE, bom: true },
+            Self::without_bom(WINDOWS_1252),
+            Self::without_bom(WINDOWS_1251),
+            Self::without_bom(SHIFT_JIS),
+            Self::without_bom(GBK),
+            Self::without_bom(EUC_KR),
+        ]
+    }
+
+    /// An `.editorconfig` charset
+    pub fn for_charset(charset: &str) -> Option<Self> {
+        Some(match charset {
+            "utf-8" => Self::without_bom(UTF_8),
+            "utf-8-bom" => FileEncoding { encoding: UTF_8, bom: true },
+            "utf-16le" => FileEncoding { encoding: UTF_16LE, bom: true },
+            "utf-16be" => FileEncoding { encoding: UTF_16BE, bom: true },
+            "latin1" => Self::without_bom(WINDOWS_1252),
+            _ => return None,
+        })
+    }
+
+    pub fn label(&self) -> String {
+        match (self.encoding.name(), self.bom) {
+            ("UTF-8", true) => "UTF-8 with BOM".to_string(),
+            ("UTF-16LE", _) => "UTF-16 LE".to_string(),
+            ("UTF-16BE", _) => "UTF-16 BE".to_string(),
+            // What `encoding_rs` decodes ISO-8859-1 as, like browsers do
+            ("windows-1252", _) => "Latin-1".to_string(),
+            (name, _) => name.to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Decoded {
+    pub text: String,
+    pub encoding: FileEncoding,
+    /// Whether some bytes weren't valid in the encoding and became `�`
+    pub had_errors: bool,
+}
+
+/// A file's bytes in the encoding its byte order mark or content suggests
+pub fn decode(bytes: &[u8]) -> Decoded {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return decode_as(bytes, encoding);
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Decoded {
+            text: text.to_string(),
+            encoding: FileEncoding::default(),
+            had_errors: false,
+        },
+        Err(_) => decode_as(bytes, WINDOWS_1252),
+    }
+}
+
+/// `decode`, but in the encoding a project asks for where the bytes leave
+/// room: a byte order mark, or UTF-8 with more than ASCII in it, outweighs it
+pub fn decode_preferring(bytes: &[u8], preferred: FileEncoding) -> Decoded {
+    if Encoding::for_bom(bytes).is_some() {
+        return decode(bytes);
+    }
+    let mut decoded = if preferred.encoding == UTF_8 {
+        decode(bytes)
+    } else if preferred.encoding != UTF_16LE
+        && preferred.encoding != UTF_16BE
+        && (bytes.is_ascii() || std::str::from_utf8(bytes).is_err())
+    {
+        decode_as(bytes, preferred.encoding)
+    } else {
+        decode(bytes)
+    };
+    // Saved with the byte order mark the project wants
+    if decoded.encoding.encoding == preferred.encoding {
+        decoded.encoding.bom = preferred.bom;
+    }
+    decoded
+}
+
+/// A file's bytes in `encoding`, after its byte order mark if it has one
+pub fn decode_as(bytes: &[u8], encoding: &'static Encoding) -> Decoded {
+    let bom = bom_bytes(encoding);
+    let has_bom = !bom.is_empty() && bytes.starts_with(bom);
+    let bytes = if has_bom { &bytes[bom.len()..] } else { bytes };
+    let (text, had_errors) = encoding.decode_without_bom_handling(bytes);
+    Decoded {
+        text: text.into_owned(),
+        encoding: FileEncoding { encoding, bom: has_bom },
+        had_errors,
+    }
+}
+
+/// `text` as `encoding` writes it, or `None` when it has characters the
+/// encoding can't write
+pub fn encode(text: &str, encoding: FileEncoding) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len() + 3);
+    if encoding.bom {
+        bytes.extend_from_slice(bom_bytes(encoding.encoding));
+    }
+    // `encoding_rs` only decodes UTF-16
+    if encoding.encoding == UTF_16LE {
+        bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+    } else if encoding.encoding == UTF_16BE {
+        bytes.extend(text.encode_utf16().flat_map(u16::to_be_bytes));
+    } else {
+        let (encoded, _, unmappable) = encoding.encoding.encode(text);
+        if unmappable {
+            return None;
+        }
+        bytes.extend_from_slice(&encoded);
+    }
+    Some(bytes)
+}
+
+fn bom_bytes(encoding: &'static Encoding) -> &'static [u8] {
+    if encoding == UTF_8 {
+        b"\xef\xbb\xbf"
+    } else if encoding == UTF_16LE {
+        b"\xff\xfe"
+    } else if encoding == UTF_16BE {
+        b"\xfe\xff"
+    } else {
+        b""
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        let decoded = decode(b"\xff\xfeh\x00i\x00");
+        assert_eq!(decoded.text, "hi");
+        assert_eq!(decoded.encoding, FileEncoding { encoding: UTF_16LE, bom: true });
+        assert_eq!(decode(b"\xef\xbb\xbfhi").encoding.label(), "UTF-8 with BOM");
+        assert_eq!(decode("héllo".as_bytes()).encoding, FileEncoding::default());
+        // Not UTF-8, so Latin-1
+        let decoded = decode(b"h\xe9llo");
+        assert_eq!((decoded.text.as_str(), decoded.encoding.label()), ("héllo", "Latin-1".to_string()));
+
+        // Bad bytes in a chosen encoding become replacement characters
+        let decoded = decode_as(b"h\xe9llo", UTF_8);
+        assert_eq!(decoded.text, "h\u{fffd}llo");
+        assert!(decoded.had_errors);
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        for encoding in FileEncoding::choices() {
+            let text = if encoding.encoding == SHIFT_JIS { "日本語\n" } else { "abc\n" };
+            let bytes = encode(text, encoding).unwrap();
+            let decoded = decode_as(&bytes, encoding.encoding);
+            assert_eq!((decoded.text.as_str(), decoded.encoding), (text, encoding), "{}", encoding.label());
+        }
+        assert_eq!(decode(&encode("é", FileEncoding { encoding: UTF_16BE, bom: true }).unwrap()).text, "é");
+        // Latin-1 has no Cyrillic
+        assert_eq!(encode("привет", FileEncoding::without_bom(WINDOWS_1252)), None);
+    }
+
+    #[test]
+    fn test_decode_preferring() {
+        let shift_jis = FileEncoding::without_bom(SHIFT_JIS);
+        let bytes = encode("日本語", shift_jis).unwrap();
+        assert_eq!(decode_preferring(&bytes, shift_jis).text, "日本語");
+        assert_eq!(decode_preferring(b"ascii", shift_jis).encoding, shift_jis);
+        // Real UTF-8 stays that, and a byte order mark is believed
+        assert_eq!(decode_preferring("日本語".as_bytes(), shift_jis).encoding, FileEncoding::default());
+        let utf16 = FileEncoding { encoding: UTF_16LE, bom: true };
+        assert_eq!(decode_preferring(b"\xff\xfeh\0", shift_jis).encoding, utf16);
+        let with_bom = FileEncoding::for_charset("utf-8-bom").unwrap();
+        assert_eq!(decode_preferring(b"plain", with_bom).encoding, with_bom);
+    }
+}
@@ -0,0 +1,137 @@
+//! Filtering the file explorer by file name, or by what files contain
+//!
+//! The project is walked and matched off the main thread, a chunk of
+//! entries at a time, so results show up while a large tree is still being
+//! searched.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::command_palette::fuzzy_match;
+
+/// Entries matched between results being shown
+pub const FILTER_CHUNK: usize = 2000;
+
+/// Files larger than this are skipped when searching contents
+const MAX_CONTENT_SEARCH_BYTES: u64 = 1024 * 1024;
+
+/// Same limit the tree itself stops expanding at
+const MAX_DEPTH: usize = 10;
+
+/// Hidden files and build output the explorer doesn't list
+pub fn is_ignored(name: &str) -> bool {
+    name.starts_with('.') || name == "target" || name == "node_modules"
+}
+
+#[derive(Clone, Debug)]
+pub struct IndexedEntry {
+    pub path: PathBuf,
+    pub is_directory: bool,
+}
+
+/// Every entry the explorer could show under `root`, expanded or not
+pub fn index_tree(root: &Path) -> Vec<IndexedEntry> {
+    fn walk(dir: &Path, depth: usize, entries: &mut Vec<IndexedEntry>) {
+        if depth > MAX_DEPTH {
+            return;
+        }
+        let Ok(read_dir) = fs::read_dir(dir) else {
+            return;
+        };
+        for entry in read_dir.flatten() {
+            if is_ignored(&entry.file_name().to_string_lossy()) {
+                continue;
+            }
+            let path = entry.path();
+            let is_directory = path.is_dir();
+            entries.push(IndexedEntry {
+                path: path.clone(),
+                is_directory,
+            });
+            if is_directory {
+                walk(&path, depth + 1, entries);
+            }
+        }
+    }
+
+    let mut entries = Vec::new();
+    walk(root, 0, &mut entries);
+    entries
+}
+
+/// Whether `entry` matches `query`: its name fuzzily, or with `search_contents`
+/// a file containing `query` (ignoring case)
+pub fn entry_matches(entry: &IndexedEntry, query: &str, search_contents: bool) -> bool {
+    if !search_contents {
+        let name = entry.path.file_name().unwrap_or_default().to_string_lossy();
+        return fuzzy_match(query, &name).is_some();
+    }
+    if entry.is_directory {
+        return false;
+    }
+    let small_enough = fs::metadata(&entry.path).is_ok_and(|metadata| metadata.len() <= MAX_CONTENT_SEARCH_BYTES);
+    // Binary files fail to read as UTF-8 and are skipped
+    small_enough
+        && fs::read_to_string(&entry.path)
+            .is_ok_and(|text| text.to_lowercase().contains(&query.to_lowercase()))
+}
+
+/// Entries matching the filter and the folders leading to them
+#[derive(Clone, Debug, Default)]
+pub struct FilterMatches {
+    pub matched: HashSet<PathBuf>,
+    /// Folders (below the root) containing a match, shown expanded
+    pub ancestors: HashSet<PathBuf>,
+}
+
+impl FilterMatches {
+    pub fn add(&mut self, path: &Path, root: &Path) {
+        self.matched.insert(path.to_path_buf());
+        for ancestor in path.ancestors().skip(1) {
+            if ancestor == root || !ancestor.starts_with(root) {
+                break;
+            }
+            // Everything above was added along with an earlier match
+            if !self.ancestors.insert(ancestor.to_path_buf()) {
+                break;
+            }
+        }
+    }
+
+    /// Entries neither matching nor leading to a match are shown dimmed
+    pub fn is_dimmed(&self, path: &Path) -> bool {
+        !self.matched.contains(path) && !self.ancestors.contains(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_expand_ancestors() {
+        let root = Path::new("/project");
+        let mut matches = FilterMatches::default();
+        matches.add(Path::new("/project/src/editor/main.rs"), root);
+        matches.add(Path::new("/project/src/lib.rs"), root);
+
+        assert!(matches.ancestors.contains(Path::new("/project/src")));
+        assert!(matches.ancestors.contains(Path::new("/project/src/editor")));
+        assert!(!matches.ancestors.contains(root));
+        assert!(!matches.is_dimmed(Path::new("/project/src/lib.rs")));
+        assert!(!matches.is_dimmed(Path::new("/project/src/editor")));
+        assert!(matches.is_dimmed(Path::new("/project/tests")));
+    }
+
+    #[test]
+    fn test_name_matching_is_fuzzy() {
+        let entry = |path: &str| IndexedEntry {
+            path: PathBuf::from(path),
+            is_directory: false,
+        };
+        assert!(entry_matches(&entry("/project/src/text_editor.rs"), "txed", false));
+        // Only the name counts, not the folders above it
+        assert!(!entry_matches(&entry("/project/src/main.rs"), "src", false));
+    }
+}
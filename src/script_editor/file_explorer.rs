@@ -1,18 +1,31 @@
 use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 use gpui::{*, prelude::FluentBuilder, actions};
 use ui::{
     button::{Button, ButtonVariants as _},
     context_menu::ContextMenuExt,
     h_flex,
+    input::{InputEvent, InputState, TextInput},
     scroll::Scrollbar,
+    v_flex,
     ActiveTheme as _, StyledExt, Sizable as _,
     IconName, Icon,
 };
 use serde::Deserialize;
 use schemars::JsonSchema;
 
+use super::git_status::{load_git_status, GitFileStatus, GitStatusMap};
+use super::languages::language_for_path;
+use super::explorer_filter::{entry_matches, index_tree, is_ignored, FilterMatches, IndexedEntry, FILTER_CHUNK};
+
+/// How long typing in the filter must pause before the tree is searched
+const FILTER_DELAY: Duration = Duration::from_millis(150);
+
+/// How often git status is re-read to catch changes made outside the editor
+const GIT_STATUS_INTERVAL: Duration = Duration::from_secs(5);
+
 // Define actions for context menu
 actions!(
     file_explorer,
@@ -48,6 +61,165 @@ pub enum ClipboardOperation {
     Paste,
 }
 
+pub enum FileExplorerEvent {
+    /// A file or folder was renamed on disk
+    Renamed { from: PathBuf, to: PathBuf },
+}
+
+/// What the name being typed in the explorer is for
+#[derive(Clone, Debug, PartialEq)]
+enum NameEditKind {
+    Rename(PathBuf),
+    /// New file in this directory
+    NewFile(PathBuf),
+    /// New folder in this directory
+    NewFolder(PathBuf),
+}
+
+/// Name input shown above the tree while creating or renaming an entry
+struct NameEdit {
+    kind: NameEditKind,
+    input: Entity<InputState>,
+    error: Option<String>,
+    _subscription: Subscription,
+}
+
+/// Why `name` can't be used for a file or folder, if it can't.
+///
+/// Checks the rules of every platform so projects stay portable.
+pub fn invalid_name_reason(name: &str) -> Option<&'static str> {
+    const RESERVED: [&str; 4] = ["CON", "PRN", "AUX", "NUL"];
+
+    if name.trim().is_empty() {
+        return Some("Name can't be empty");
+    }
+    if name == "." || name == ".." {
+        return Some("Name can't be . or ..");
+    }
+    if name.contains(['/', '\\']) {
+        return Some("Name can't contain slashes");
+    }
+    if name.contains(['<', '>', ':', '"', '|', '?', '*']) || name.chars().any(char::is_control) {
+        return Some("Name can't contain < > : \" | ? * or control characters");
+    }
+    if name.ends_with([' ', '.']) {
+        return Some("Name can't end with a space or a dot");
+    }
+    let stem = name.split('.').next().unwrap_or(name).to_ascii_uppercase();
+    let numbered_device = (stem.starts_with("COM") || stem.starts_with("LPT"))
+        && stem.len() == 4
+        && stem[3..].chars().all(|ch| ch.is_ascii_digit() && ch != '0');
+    if RESERVED.contains(&stem.as_str()) || numbered_device {
+        return Some("Name is reserved by Windows");
+    }
+    None
+}
+
+/// Files and folders being dragged from the explorer, within it or onto an editor
+#[derive(Clone)]
+pub struct DraggedEntries {
+    pub paths: Vec<PathBuf>,
+}
+
+impl Render for DraggedEntries {
+    fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let label = match self.paths.as_slice() {
+            [path] => path.file_name().unwrap_or_default().to_string_lossy().to_string(),
+            paths => format!("{} items", paths.len()),
+        };
+        div()
+            .px_2()
+            .py_1()
+            .rounded_md()
+            .bg(cx.theme().popover)
+            .border_1()
+            .border_color(cx.theme().border)
+            .shadow_md()
+            .text_sm()
+            .text_color(cx.theme().foreground)
+            .child(label)
+    }
+}
+
+/// Where `source` ends up when moved into `target_dir`.
+///
+/// `Ok(None)` when it is already there; an error for a folder moved into
+/// itself or one of its own descendants.
+pub fn move_destination(source: &Path, target_dir: &Path) -> Result<Option<PathBuf>, &'static str> {
+    if target_dir.starts_with(source) {
+        return Err("Can't move a folder into itself");
+    }
+    if source.parent() == Some(target_dir) {
+        return Ok(None);
+    }
+    let name = source.file_name().ok_or("Can't move the project root")?;
+    Ok(Some(target_dir.join(name)))
+}
+
+/// Move `path` to the system trash, returning `false` if it was deleted for good instead
+fn move_to_trash(path: &Path) -> std::io::Result<bool> {
+    #[cfg(target_os = "windows")]
+    let trashed = {
+        let method = if path.is_dir() { "DeleteDirectory" } else { "DeleteFile" };
+        let script = format!(
+            "Add-Type -AssemblyName Microsoft.VisualBasic; [Microsoft.VisualBasic.FileIO.FileSystem]::{}('{}', 'OnlyErrorDialogs', 'SendToRecycleBin')",
+            method,
+            path.to_string_lossy().replace('\'', "''")
+        );
+        std::process::Command::new("powershell")
+            .args(["-NoProfile", "-Command", &script])
+            .status()
+    };
+    #[cfg(target_os = "macos")]
+    let trashed = {
+        let script = format!(
+            "tell application \"Finder\" to delete POSIX file \"{}\"",
+            path.to_string_lossy().replace('\\', "\\\\").replace('"', "\\\"")
+        );
+        std::process::Command::new("osascript").args(["-e", &script]).status()
+    };
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let trashed = std::process::Command::new("gio").arg("trash").arg(path).status();
+
+    if trashed.is_ok_and(|status| status.success()) && !path.exists() {
+        return Ok(true);
+    }
+    if path.is_dir() {
+        fs::remove_dir_all(path)?;
+    } else {
+        fs::remove_file(path)?;
+    }
+    Ok(false)
+}
+
+/// Show `path` in the system's file manager, selected where it can be, in
+/// its folder otherwise
+pub fn reveal_in_file_manager(path: &Path) {
+    #[cfg(target_os = "windows")]
+    {
+        let _ = std::process::Command::new("explorer")
+            .arg("/select,")
+            .arg(path)
+            .spawn();
+    }
+    #[cfg(target_os = "macos")]
+    {
+        let _ = std::process::Command::new("open")
+            .arg("-R")
+            .arg(path)
+            .spawn();
+    }
+    #[cfg(target_os = "linux")]
+    {
+        if let Some(parent) = path.parent() {
+            let _ = std::process::Command::new("xdg-open")
+                .arg(parent)
+                .spawn();
+        }
+    }
+    tracing::debug!("📂 Revealed in file manager: {:?}", path);
+}
+
 #[derive(Clone)]
 pub struct FileEntry {
     pub name: String,
@@ -80,14 +252,47 @@ pub struct FileExplorer {
     /// Clipboard state for cut/copy/paste
     clipboard_path: Option<PathBuf>,
     clipboard_operation: Option<ClipboardOperation>,
-    /// Path being renamed (for inline rename)
-    renaming_path: Option<PathBuf>,
+    /// Name being typed for a new or renamed entry
+    name_edit: Option<NameEdit>,
+    /// Entry waiting for the delete to be confirmed
+    pending_delete: Option<PathBuf>,
+    /// Entries Ctrl/Cmd+clicked along with the selected one, dragged together
+    multi_selected: Vec<PathBuf>,
+    filter_input: Entity<InputState>,
+    /// Match file contents instead of names
+    filter_contents: bool,
+    /// Matches of the current filter, `None` while the filter is empty
+    filter: Option<FilterMatches>,
+    filter_task: Option<Task<()>>,
+    _filter_subscription: Subscription,
+    /// Last git status read for the project; empty outside a repository
+    git_status: GitStatusMap,
+    git_status_task: Option<Task<()>>,
+    _git_status_poll: Task<()>,
     /// Files that have diffs (for diff mode highlighting)
     diff_files: HashSet<PathBuf>,
 }
 
 impl FileExplorer {
     pub fn new(window: &mut Window, cx: &mut Context<Self>) -> Self {
+        cx.bind_keys([
+            KeyBinding::new("f2", RenameFile, Some("FileExplorer")),
+            KeyBinding::new("delete", DeleteFile, Some("FileExplorer")),
+        ]);
+
+        let filter_input = cx.new(|cx| InputState::new(window, cx).placeholder("Filter files"));
+        let filter_subscription = cx.subscribe_in(&filter_input, window, |this, _input, event: &InputEvent, _window, cx| {
+            if let InputEvent::Change = event {
+                this.schedule_filter(cx);
+            }
+        });
+        let git_status_poll = cx.spawn(async move |this, cx| loop {
+            cx.background_executor().timer(GIT_STATUS_INTERVAL).await;
+            if this.update(cx, |this, cx| this.refresh_git_status(cx)).is_err() {
+                break;
+            }
+        });
+
         Self {
             focus_handle: cx.focus_handle(),
             project_root: None,
@@ -104,7 +309,17 @@ impl FileExplorer {
             needs_scroll_update: false,
             clipboard_path: None,
             clipboard_operation: None,
-            renaming_path: None,
+            name_edit: None,
+            pending_delete: None,
+            multi_selected: Vec::new(),
+            filter_input,
+            filter_contents: false,
+            filter: None,
+            filter_task: None,
+            _filter_subscription: filter_subscription,
+            git_status: GitStatusMap::default(),
+            git_status_task: None,
+            _git_status_poll: git_status_poll,
             diff_files: HashSet::new(),
         }
     }
@@ -133,9 +348,10 @@ impl FileExplorer {
         }
     }
 
-    fn on_delete_file(&mut self, _: &DeleteFile, window: &mut Window, cx: &mut Context<Self>) {
+    fn on_delete_file(&mut self, _: &DeleteFile, _window: &mut Window, cx: &mut Context<Self>) {
         if let Some(path) = self.selected_file.clone() {
-            self.delete_file(path, window, cx);
+            self.pending_delete = Some(path);
+            cx.notify();
         }
     }
 
@@ -159,7 +375,7 @@ impl FileExplorer {
 
     fn on_reveal_in_file_manager(&mut self, _: &RevealInFileManager, _window: &mut Window, _cx: &mut Context<Self>) {
         if let Some(path) = &self.selected_file {
-            self.reveal_in_file_manager(path);
+            reveal_in_file_manager(path);
         }
     }
 
@@ -176,7 +392,9 @@ impl FileExplorer {
     pub fn open_project(&mut self, path: PathBuf, _window: &mut Window, cx: &mut Context<Self>) {
         if path.is_dir() {
             self.project_root = Some(path.clone());
+            self.git_status = GitStatusMap::default();
             self.refresh_file_tree(cx);
+            self.refresh_git_status(cx);
             cx.notify();
         }
     }
@@ -247,15 +465,18 @@ impl FileExplorer {
                 let name = entry.file_name().to_string_lossy().to_string();
 
                 // Skip hidden files and common ignore patterns
-                if name.starts_with('.') || name == "target" || name == "node_modules" {
+                if is_ignored(&name) {
                     continue;
                 }
 
+                // Folders leading to filter matches open up while filtering
+                let is_expanded = self.expanded_folders.get(&path).copied().unwrap_or(false)
+                    || self.filter.as_ref().is_some_and(|filter| filter.ancestors.contains(&path));
                 let file_entry = FileEntry {
                     name,
                     path: path.clone(),
                     is_directory: path.is_dir(),
-                    is_expanded: self.expanded_folders.get(&path).copied().unwrap_or(false),
+                    is_expanded,
                     depth,
                 };
 
@@ -310,6 +531,214 @@ impl FileExplorer {
         cx.notify();
     }
 
+    /// Re-read git status in the background; renders use the cached result
+    pub fn refresh_git_status(&mut self, cx: &mut Context<Self>) {
+        // Still waiting on the previous read
+        if self.git_status_task.is_some() {
+            return;
+        }
+        let Some(root) = self.project_root.clone() else {
+            return;
+        };
+        self.git_status_task = Some(cx.spawn(async move |this, cx| {
+            let status = cx
+                .background_executor()
+                .spawn(async move { load_git_status(&root) })
+                .await;
+            this.update(cx, |this, cx| {
+                this.git_status_task = None;
+                this.git_status = status.unwrap_or_default();
+                cx.notify();
+            })
+            .ok();
+        }));
+    }
+
+    /// Search the project for the filter text once typing pauses.
+    ///
+    /// Matches are published a chunk at a time; the previous matches stay up
+    /// until the first chunk of the new search is done.
+    fn schedule_filter(&mut self, cx: &mut Context<Self>) {
+        let query = self.filter_input.read(cx).value().trim().to_string();
+        let Some(root) = self.project_root.clone().filter(|_| !query.is_empty()) else {
+            self.filter_task = None;
+            if self.filter.take().is_some() {
+                self.refresh_file_tree(cx);
+            }
+            cx.notify();
+            return;
+        };
+        let search_contents = self.filter_contents;
+
+        self.filter_task = Some(cx.spawn(async move |this, cx| {
+            cx.background_executor().timer(FILTER_DELAY).await;
+            let index = cx
+                .background_executor()
+                .spawn({
+                    let root = root.clone();
+                    async move { index_tree(&root) }
+                })
+                .await;
+
+            let mut chunks: Vec<Vec<IndexedEntry>> = index.chunks(FILTER_CHUNK).map(<[_]>::to_vec).collect();
+            if chunks.is_empty() {
+                // An empty project still replaces the previous matches
+                chunks.push(Vec::new());
+            }
+            let chunk_count = chunks.len();
+            let mut matches = FilterMatches::default();
+            for (chunk_ix, chunk) in chunks.into_iter().enumerate() {
+                let query = query.clone();
+                let found: Vec<PathBuf> = cx
+                    .background_executor()
+                    .spawn(async move {
+                        chunk
+                            .into_iter()
+                            .filter(|entry| entry_matches(entry, &query, search_contents))
+                            .map(|entry| entry.path)
+                            .collect()
+                    })
+                    .await;
+                // The first chunk replaces the previous matches, the last marks the end
+                if found.is_empty() && chunk_ix != 0 && chunk_ix + 1 != chunk_count {
+                    continue;
+                }
+                for path in &found {
+                    matches.add(path, &root);
+                }
+                let snapshot = matches.clone();
+                let updated = this.update(cx, |this, cx| {
+                    this.filter = Some(snapshot);
+                    this.refresh_file_tree(cx);
+                    cx.notify();
+                });
+                if updated.is_err() {
+                    return;
+                }
+            }
+        }));
+    }
+
+    fn clear_filter(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.filter_input.update(cx, |state, cx| state.set_value("", window, cx));
+        self.schedule_filter(cx);
+    }
+
+    fn render_filter_bar(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let has_query = !self.filter_input.read(cx).value().is_empty();
+        h_flex()
+            .w_full()
+            .px_3()
+            .py_2()
+            .gap_1()
+            .border_b_1()
+            .border_color(cx.theme().border)
+            .capture_key_down(cx.listener(|this, event: &KeyDownEvent, window, cx| {
+                if event.keystroke.key == "escape" {
+                    this.clear_filter(window, cx);
+                    cx.stop_propagation();
+                }
+            }))
+            .child(div().flex_1().child(TextInput::new(&self.filter_input).small()))
+            .child(
+                Button::new("filter_contents")
+                    .icon(IconName::Search)
+                    .tooltip("Search File Contents")
+                    .ghost()
+                    .xsmall()
+                    .selected(self.filter_contents)
+                    .on_click(cx.listener(|this, _, _window, cx| {
+                        this.filter_contents = !this.filter_contents;
+                        this.schedule_filter(cx);
+                    })),
+            )
+            .when(has_query, |bar| {
+                bar.child(
+                    Button::new("clear_filter")
+                        .icon(IconName::Close)
+                        .tooltip("Clear Filter")
+                        .ghost()
+                        .xsmall()
+                        .on_click(cx.listener(|this, _, window, cx| {
+                            this.clear_filter(window, cx);
+                        })),
+                )
+            })
+    }
+
+    /// Add `path` to (or drop it from) the entries dragged together
+    fn toggle_multi_select(&mut self, path: PathBuf, cx: &mut Context<Self>) {
+        if let Some(selected) = self.selected_file.take() {
+            if !self.multi_selected.contains(&selected) {
+                self.multi_selected.push(selected);
+            }
+        }
+        if let Some(ix) = self.multi_selected.iter().position(|selected| *selected == path) {
+            self.multi_selected.remove(ix);
+        } else {
+            self.multi_selected.push(path.clone());
+            self.selected_file = Some(path);
+        }
+        cx.notify();
+    }
+
+    /// What dragging `path` drags: the whole multi-selection when it is part of it
+    fn drag_paths(&self, path: &Path) -> Vec<PathBuf> {
+        if self.multi_selected.iter().any(|selected| selected == path) {
+            self.multi_selected.clone()
+        } else {
+            vec![path.to_path_buf()]
+        }
+    }
+
+    /// Whether every dragged entry can move into `target_dir`
+    fn can_move_into(paths: &[PathBuf], target_dir: &Path) -> bool {
+        paths
+            .iter()
+            .all(|path| matches!(move_destination(path, target_dir), Ok(Some(_))))
+    }
+
+    /// Move dragged entries into `target_dir`, telling open editors where they went
+    fn move_entries(&mut self, paths: &[PathBuf], target_dir: PathBuf, cx: &mut Context<Self>) {
+        // Entries inside a dragged folder move along with it
+        let roots: Vec<&PathBuf> = paths
+            .iter()
+            .filter(|path| !paths.iter().any(|other| other != *path && path.starts_with(other)))
+            .collect();
+
+        for from in roots {
+            let to = match move_destination(from, &target_dir) {
+                Ok(Some(to)) => to,
+                Ok(None) => continue,
+                Err(reason) => {
+                    tracing::error!("Can't move {:?}: {}", from, reason);
+                    continue;
+                }
+            };
+            if to.exists() {
+                tracing::error!("Can't move {:?}: {:?} already exists", from, to);
+                continue;
+            }
+            if let Err(e) = fs::rename(from, &to) {
+                tracing::error!("Failed to move {:?} to {:?}: {}", from, to, e);
+                continue;
+            }
+            tracing::debug!("✓ Moved: {:?} -> {:?}", from, to);
+            if let Some(expanded) = self.expanded_folders.remove(from) {
+                self.expanded_folders.insert(to.clone(), expanded);
+            }
+            if self.selected_file.as_ref() == Some(from) {
+                self.selected_file = Some(to.clone());
+            }
+            cx.emit(FileExplorerEvent::Renamed { from: from.clone(), to });
+        }
+
+        self.multi_selected.clear();
+        self.expanded_folders.insert(target_dir, true);
+        self.refresh_file_tree(cx);
+        cx.notify();
+    }
+
     fn open_file_in_editor(&mut self, path: PathBuf, _window: &mut Window, cx: &mut Context<Self>) {
         tracing::debug!("FileExplorer: open_file_in_editor called with path: {:?}", path);
         self.selected_file = Some(path.clone());
@@ -350,28 +779,24 @@ impl FileExplorer {
         })
     }
 
-    fn create_new_file(&mut self, _window: &mut Window, cx: &mut Context<Self>) {
-        if let Some(root) = &self.project_root {
-            let new_path = root.join("new_file.rs");
-
-            // Create the file
-            if let Ok(_) = fs::write(&new_path, "") {
-                self.refresh_file_tree(cx);
-                self.selected_file = Some(new_path);
-                cx.notify();
-            }
+    /// Folder new entries go in: the selected folder, the selected file's folder, or the root
+    fn target_directory(&self) -> Option<PathBuf> {
+        match &self.selected_file {
+            Some(selected) if selected.is_dir() => Some(selected.clone()),
+            Some(selected) => selected.parent().map(Path::to_path_buf),
+            None => self.project_root.clone(),
         }
     }
 
-    fn create_new_folder(&mut self, _window: &mut Window, cx: &mut Context<Self>) {
-        if let Some(root) = &self.project_root {
-            let new_path = root.join("new_folder");
+    fn create_new_file(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        if let Some(dir) = self.target_directory() {
+            self.create_file_in_directory(dir, window, cx);
+        }
+    }
 
-            // Create the directory
-            if let Ok(_) = fs::create_dir(&new_path) {
-                self.refresh_file_tree(cx);
-                cx.notify();
-            }
+    fn create_new_folder(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        if let Some(dir) = self.target_directory() {
+            self.create_folder_in_directory(dir, window, cx);
         }
     }
 
@@ -383,15 +808,10 @@ impl FileExplorer {
                 IconName::Folder
             }
         } else {
+            if let Some(language) = language_for_path(&entry.path) {
+                return language.icon.clone();
+            }
             match entry.path.extension().and_then(|ext| ext.to_str()) {
-                Some("rs") => IconName::Code,
-                Some("js") | Some("ts") => IconName::Code,
-                Some("py") => IconName::Code,
-                Some("toml") | Some("json") => IconName::Settings,
-                Some("md") => IconName::Notes,
-                Some("txt") => IconName::Page,
-                Some("html") => IconName::Html5,
-                Some("css") => IconName::Css3,
                 Some("png") | Some("jpg") | Some("jpeg") | Some("gif") => IconName::MediaImage,
                 _ => IconName::Page,
             }
@@ -471,15 +891,13 @@ impl FileExplorer {
     }
 
     fn delete_file(&mut self, path: PathBuf, _window: &mut Window, cx: &mut Context<Self>) {
-        let result = if path.is_dir() {
-            fs::remove_dir_all(&path)
-        } else {
-            fs::remove_file(&path)
-        };
-
-        match result {
-            Ok(_) => {
-                tracing::debug!("✓ Deleted: {:?}", path);
+        self.pending_delete = None;
+        match move_to_trash(&path) {
+            Ok(trashed) => {
+                tracing::debug!("✓ {}: {:?}", if trashed { "Moved to trash" } else { "Deleted" }, path);
+                if self.selected_file.as_ref().is_some_and(|selected| selected.starts_with(&path)) {
+                    self.selected_file = None;
+                }
                 self.refresh_file_tree(cx);
             }
             Err(e) => {
@@ -489,11 +907,191 @@ impl FileExplorer {
         cx.notify();
     }
 
-    fn start_rename(&mut self, path: PathBuf, _window: &mut Window, cx: &mut Context<Self>) {
-        self.renaming_path = Some(path);
+    fn start_rename(&mut self, path: PathBuf, window: &mut Window, cx: &mut Context<Self>) {
+        let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        self.start_name_edit(NameEditKind::Rename(path), name, window, cx);
+    }
+
+    /// Show the name input for `kind`, prefilled with `name`
+    fn start_name_edit(&mut self, kind: NameEditKind, name: String, window: &mut Window, cx: &mut Context<Self>) {
+        self.pending_delete = None;
+        let input = cx.new(|cx| InputState::new(window, cx).default_value(name));
+        let subscription = cx.subscribe_in(&input, window, |this, _input, event: &InputEvent, window, cx| {
+            match event {
+                InputEvent::Change => {
+                    if let Some(edit) = this.name_edit.as_mut() {
+                        edit.error = None;
+                        cx.notify();
+                    }
+                }
+                InputEvent::PressEnter { .. } => this.confirm_name_edit(window, cx),
+                InputEvent::Blur => this.cancel_name_edit(cx),
+                _ => {}
+            }
+        });
+        input.update(cx, |state, cx| state.focus(window, cx));
+        self.name_edit = Some(NameEdit {
+            kind,
+            input,
+            error: None,
+            _subscription: subscription,
+        });
+        cx.notify();
+    }
+
+    fn cancel_name_edit(&mut self, cx: &mut Context<Self>) {
+        if self.name_edit.take().is_some() {
+            cx.notify();
+        }
+    }
+
+    /// Create or rename the entry; errors keep the input open to be corrected
+    fn confirm_name_edit(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(edit) = self.name_edit.as_mut() else {
+            return;
+        };
+        let name = edit.input.read(cx).value().trim().to_string();
+        if let Some(reason) = invalid_name_reason(&name) {
+            edit.error = Some(reason.to_string());
+            cx.notify();
+            return;
+        }
+
+        let kind = edit.kind.clone();
+        let target = match &kind {
+            NameEditKind::Rename(from) => from.with_file_name(&name),
+            NameEditKind::NewFile(dir) | NameEditKind::NewFolder(dir) => dir.join(&name),
+        };
+        // Renaming `a.rs` to `A.rs` finds itself on case-insensitive file systems
+        let case_only_rename = matches!(&kind, NameEditKind::Rename(from)
+            if from.to_string_lossy().to_lowercase() == target.to_string_lossy().to_lowercase());
+        if matches!(&kind, NameEditKind::Rename(from) if *from == target) {
+            self.cancel_name_edit(cx);
+            return;
+        }
+        if target.exists() && !case_only_rename {
+            edit.error = Some(format!("A file or folder named {} already exists here", name));
+            cx.notify();
+            return;
+        }
+
+        let result = match &kind {
+            NameEditKind::Rename(from) => fs::rename(from, &target),
+            NameEditKind::NewFile(_) => fs::OpenOptions::new().write(true).create_new(true).open(&target).map(drop),
+            NameEditKind::NewFolder(_) => fs::create_dir(&target),
+        };
+        if let Err(e) = result {
+            edit.error = Some(format!("Couldn't save {}: {}", name, e));
+            cx.notify();
+            return;
+        }
+
+        self.name_edit = None;
+        match kind {
+            NameEditKind::Rename(from) => {
+                tracing::debug!("✓ Renamed: {:?} -> {:?}", from, target);
+                if let Some(expanded) = self.expanded_folders.remove(&from) {
+                    self.expanded_folders.insert(target.clone(), expanded);
+                }
+                self.selected_file = Some(target.clone());
+                cx.emit(FileExplorerEvent::Renamed { from, to: target.clone() });
+            }
+            NameEditKind::NewFile(dir) => {
+                self.expanded_folders.insert(dir, true);
+                self.open_file_in_editor(target.clone(), window, cx);
+            }
+            NameEditKind::NewFolder(dir) => {
+                self.expanded_folders.insert(dir, true);
+                self.selected_file = Some(target.clone());
+            }
+        }
+        self.refresh_file_tree(cx);
+        self.scroll_to_entry(&target, cx);
         cx.notify();
     }
 
+    fn render_name_edit(&self, cx: &mut Context<Self>) -> Option<impl IntoElement> {
+        let edit = self.name_edit.as_ref()?;
+        let relative = |path: &Path| {
+            self.project_root
+                .as_ref()
+                .and_then(|root| path.strip_prefix(root).ok())
+                .filter(|relative| !relative.as_os_str().is_empty())
+                .map(|relative| relative.to_string_lossy().to_string())
+                .unwrap_or_else(|| "project root".to_string())
+        };
+        let title = match &edit.kind {
+            NameEditKind::Rename(path) => format!("Rename {}", relative(path)),
+            NameEditKind::NewFile(dir) => format!("New file in {}", relative(dir)),
+            NameEditKind::NewFolder(dir) => format!("New folder in {}", relative(dir)),
+        };
+        Some(
+            v_flex()
+                .w_full()
+                .px_3()
+                .py_2()
+                .gap_1()
+                .border_b_1()
+                .border_color(cx.theme().border)
+                .capture_key_down(cx.listener(|this, event: &KeyDownEvent, _window, cx| {
+                    if event.keystroke.key == "escape" {
+                        this.cancel_name_edit(cx);
+                        cx.stop_propagation();
+                    }
+                }))
+                .child(div().text_xs().text_color(cx.theme().muted_foreground).child(title))
+                .child(TextInput::new(&edit.input).small())
+                .when_some(edit.error.clone(), |bar, error| {
+                    bar.child(div().text_xs().text_color(cx.theme().danger).child(error))
+                }),
+        )
+    }
+
+    fn render_delete_confirmation(&self, cx: &mut Context<Self>) -> Option<impl IntoElement> {
+        let path = self.pending_delete.clone()?;
+        let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let what = if path.is_dir() { "folder" } else { "file" };
+        Some(
+            v_flex()
+                .w_full()
+                .px_3()
+                .py_2()
+                .gap_2()
+                .border_b_1()
+                .border_color(cx.theme().border)
+                .child(
+                    div()
+                        .text_xs()
+                        .text_color(cx.theme().foreground)
+                        .child(format!("Delete {} {}? It goes to the trash when possible.", what, name)),
+                )
+                .child(
+                    h_flex()
+                        .gap_1()
+                        .justify_end()
+                        .child(
+                            Button::new("cancel_delete")
+                                .label("Cancel")
+                                .ghost()
+                                .xsmall()
+                                .on_click(cx.listener(|this, _, _window, cx| {
+                                    this.pending_delete = None;
+                                    cx.notify();
+                                })),
+                        )
+                        .child(
+                            Button::new("confirm_delete")
+                                .label("Delete")
+                                .danger()
+                                .xsmall()
+                                .on_click(cx.listener(move |this, _, window, cx| {
+                                    this.delete_file(path.clone(), window, cx);
+                                })),
+                        ),
+                ),
+        )
+    }
+
     fn copy_path_to_clipboard(&self, path: &Path, cx: &mut App) {
         let path_str = path.to_string_lossy().to_string();
         cx.write_to_clipboard(ClipboardItem::new_string(path_str.clone()));
@@ -513,52 +1111,12 @@ impl FileExplorer {
         self.copy_path_to_clipboard(path, cx);
     }
 
-    fn reveal_in_file_manager(&self, path: &Path) {
-        #[cfg(target_os = "windows")]
-        {
-            let _ = std::process::Command::new("explorer")
-                .arg("/select,")
-                .arg(path)
-                .spawn();
-        }
-        #[cfg(target_os = "macos")]
-        {
-            let _ = std::process::Command::new("open")
-                .arg("-R")
-                .arg(path)
-                .spawn();
-        }
-        #[cfg(target_os = "linux")]
-        {
-            if let Some(parent) = path.parent() {
-                let _ = std::process::Command::new("xdg-open")
-                    .arg(parent)
-                    .spawn();
-            }
-        }
-        tracing::debug!("📂 Revealed in file manager: {:?}", path);
+    fn create_file_in_directory(&mut self, dir_path: PathBuf, window: &mut Window, cx: &mut Context<Self>) {
+        self.start_name_edit(NameEditKind::NewFile(dir_path), String::new(), window, cx);
     }
 
-    fn create_file_in_directory(&mut self, dir_path: PathBuf, _window: &mut Window, cx: &mut Context<Self>) {
-        let new_path = dir_path.join("new_file.txt");
-        
-        // Create the file
-        if let Ok(_) = fs::write(&new_path, "") {
-            self.refresh_file_tree(cx);
-            self.selected_file = Some(new_path.clone());
-            self.start_rename(new_path, _window, cx);
-        }
-    }
-
-    fn create_folder_in_directory(&mut self, dir_path: PathBuf, _window: &mut Window, cx: &mut Context<Self>) {
-        let new_path = dir_path.join("new_folder");
-        
-        // Create the directory
-        if let Ok(_) = fs::create_dir(&new_path) {
-            self.refresh_file_tree(cx);
-            self.selected_file = Some(new_path.clone());
-            self.start_rename(new_path, _window, cx);
-        }
+    fn create_folder_in_directory(&mut self, dir_path: PathBuf, window: &mut Window, cx: &mut Context<Self>) {
+        self.start_name_edit(NameEditKind::NewFolder(dir_path), String::new(), window, cx);
     }
     
     /// Calculate which entries are visible in the viewport (virtualization)
@@ -647,11 +1205,18 @@ impl FileExplorer {
         // Offset for virtualization
         let offset_y = self.item_height * start_idx as f32;
         
+        let project_root = self.project_root.clone();
         div()
             .id("file_tree_viewport")
             .relative()
             .size_full()
             .overflow_hidden()
+            // Space below the rows drops into the project root
+            .when_some(project_root, |viewport, root| {
+                viewport.on_drop(cx.listener(move |this, dragged: &DraggedEntries, _window, cx| {
+                    this.move_entries(&dragged.paths, root.clone(), cx);
+                }))
+            })
             .font_family("JetBrains Mono")
             .font(gpui::Font {
                 family: "JetBrains Mono".to_string().into(),
@@ -713,6 +1278,26 @@ impl FileExplorer {
 
     fn render_file_item(&self, entry: &FileEntry, cx: &mut Context<Self>) -> impl IntoElement {
         let is_selected = self.selected_file.as_ref() == Some(&entry.path);
+        let is_multi_selected = self.multi_selected.contains(&entry.path);
+        let git_status = self.git_status.status(&entry.path);
+        let git_color = match git_status {
+            Some(GitFileStatus::Modified) => Some(cx.theme().warning),
+            Some(GitFileStatus::Untracked) => Some(cx.theme().success),
+            Some(GitFileStatus::Staged) => Some(cx.theme().info),
+            Some(GitFileStatus::Conflicted) => Some(cx.theme().danger),
+            Some(GitFileStatus::Ignored) | None => None,
+        };
+        let is_ignored = git_status == Some(GitFileStatus::Ignored);
+        let is_filtered_out = self.filter.as_ref().is_some_and(|filter| filter.is_dimmed(&entry.path));
+        let drop_dir = if is_directory {
+            entry.path.clone()
+        } else {
+            entry.path.parent().map(Path::to_path_buf).unwrap_or_default()
+        };
+        let drop_bg = cx.theme().accent.opacity(0.2);
+        let drop_border = cx.theme().accent;
+        let is_being_changed = self.pending_delete.as_ref() == Some(&entry.path)
+            || self.name_edit.as_ref().is_some_and(|edit| edit.kind == NameEditKind::Rename(entry.path.clone()));
         let path = entry.path.clone();
         let is_directory = entry.is_directory;
         let icon = self.get_file_icon(entry);
@@ -752,6 +1337,9 @@ impl FileExplorer {
             .pr_3()
             .rounded_md()
             .when(is_selected, |style| style.bg(cx.theme().accent))
+            .when(is_multi_selected && !is_selected, |style| style.bg(cx.theme().accent.opacity(0.3)))
+            .when(is_being_changed, |style| style.border_1().border_color(cx.theme().ring))
+            .when(is_filtered_out || is_ignored, |style| style.opacity(0.4))
             .when(!is_selected && is_clickable, |style| {
                 style.hover(|style| style.bg(cx.theme().accent.opacity(0.1)))
             })
@@ -763,15 +1351,36 @@ impl FileExplorer {
                     .text_sm()
                     .when(should_grey, |style| style.italic())
                     .when(is_selected, |style| style.text_color(cx.theme().accent_foreground))
-                    .when(!is_selected && !should_grey, |style| style.text_color(cx.theme().foreground))
+                    .when(!is_selected && !should_grey, |style| {
+                        style.text_color(git_color.unwrap_or(cx.theme().foreground))
+                    })
                     .when(!is_selected && should_grey, |style| style.text_color(cx.theme().muted_foreground))
                     .child(entry.name.clone())
             )
+            .when_some(git_color, |row, color| {
+                // Folders get a dot summarizing their files, files a status letter
+                let badge = if is_directory { Some("•") } else { git_status.and_then(GitFileStatus::badge) };
+                row.child(
+                    div()
+                        .ml_auto()
+                        .text_xs()
+                        .font_semibold()
+                        .text_color(if is_selected { cx.theme().accent_foreground } else { color })
+                        .children(badge),
+                )
+            })
             .when(is_clickable, |div| {
-                div.on_mouse_down(gpui::MouseButton::Left, {
+                // On click rather than mouse down, so starting a drag doesn't open or toggle
+                div.on_click({
                     let path = path.clone();
                     let is_dir = is_directory;
-                    cx.listener(move |this, _, window, cx| {
+                    cx.listener(move |this, event: &ClickEvent, window, cx| {
+                        window.focus(&this.focus_handle);
+                        if event.modifiers().secondary() {
+                            this.toggle_multi_select(path.clone(), cx);
+                            return;
+                        }
+                        this.multi_selected.clear();
                         tracing::debug!("FileExplorer: Click on {:?}, is_directory: {}", path, is_dir);
                         if is_dir {
                             this.toggle_folder(&path, window, cx);
@@ -783,10 +1392,31 @@ impl FileExplorer {
                     })
                 })
             })
+            .when(!in_diff_mode, |div| {
+                div.on_drag(DraggedEntries { paths: self.drag_paths(&entry.path) }, |dragged, _offset, _window, cx| {
+                    cx.new(|_| dragged.clone())
+                })
+                // Rows take every drop (invalid moves are skipped) so it doesn't fall
+                // through to the root; only valid targets light up
+                .drag_over::<DraggedEntries>({
+                    let drop_dir = drop_dir.clone();
+                    move |style, dragged, _window, _cx| {
+                        if Self::can_move_into(&dragged.paths, &drop_dir) {
+                            style.bg(drop_bg).border_1().border_color(drop_border)
+                        } else {
+                            style
+                        }
+                    }
+                })
+                .on_drop(cx.listener(move |this, dragged: &DraggedEntries, _window, cx| {
+                    this.move_entries(&dragged.paths, drop_dir.clone(), cx);
+                }))
+            })
             // Select the item on right-click to ensure context menu actions work on it
             .on_mouse_down(gpui::MouseButton::Right, {
                 let path = path.clone();
                 cx.listener(move |this, _, window, cx| {
+                    window.focus(&this.focus_handle);
                     this.select_file(path.clone(), window, cx);
                 })
             })
@@ -859,6 +1489,25 @@ impl FileExplorer {
         self.scroll_handle.set_offset(clamped_offset);
     }
     
+    /// Expand the folders down to `path`, then select and scroll to it;
+    /// false when it isn't in the project
+    pub fn reveal(&mut self, path: &Path, cx: &mut Context<Self>) -> bool {
+        let Some(root) = self.project_root.clone() else {
+            return false;
+        };
+        if !path.starts_with(&root) {
+            return false;
+        }
+        for folder in path.ancestors().skip(1).take_while(|folder| *folder != root) {
+            self.expanded_folders.insert(folder.to_path_buf(), true);
+        }
+        self.selected_file = Some(path.to_path_buf());
+        self.refresh_file_tree(cx);
+        self.scroll_to_entry(path, cx);
+        cx.notify();
+        true
+    }
+
     /// Scroll to ensure a specific entry is visible
     pub fn scroll_to_entry(&mut self, entry_path: &Path, cx: &mut Context<Self>) {
         // Find the entry index in visible_entries
@@ -893,6 +1542,8 @@ impl FileExplorer {
     }
 }
 
+impl EventEmitter<FileExplorerEvent> for FileExplorer {}
+
 impl Focusable for FileExplorer {
     fn focus_handle(&self, _: &App) -> FocusHandle {
         self.focus_handle.clone()
@@ -903,6 +1554,9 @@ impl Render for FileExplorer {
     fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
         let file_tree_empty = self.file_tree.is_empty();
         let focus_handle = self.focus_handle.clone();
+        let filter_bar = self.project_root.is_some().then(|| self.render_filter_bar(cx));
+        let name_edit = self.render_name_edit(cx);
+        let delete_confirmation = self.render_delete_confirmation(cx);
         
         div()
             .key_context("FileExplorer")
@@ -989,6 +1643,9 @@ impl Render for FileExplorer {
                             )
                     )
             )
+            .children(filter_bar)
+            .children(name_edit)
+            .children(delete_confirmation)
             .child(
                 // Scrollable content area with virtualization
                 div()
@@ -1053,4 +1710,38 @@ impl Render for FileExplorer {
                 )
             })
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invalid_name_reason() {
+        assert_eq!(invalid_name_reason("main.rs"), None);
+        assert_eq!(invalid_name_reason(".gitignore"), None);
+        assert_eq!(invalid_name_reason("COM10.txt"), None);
+        assert!(invalid_name_reason("  ").is_some());
+        assert!(invalid_name_reason("..").is_some());
+        assert!(invalid_name_reason("src/main.rs").is_some());
+        assert!(invalid_name_reason("what?.rs").is_some());
+        assert!(invalid_name_reason("trailing.").is_some());
+        assert!(invalid_name_reason("nul.rs").is_some());
+        assert!(invalid_name_reason("LPT1").is_some());
+    }
+
+    #[test]
+    fn test_move_destination() {
+        let src = Path::new("/project/src");
+        assert_eq!(
+            move_destination(Path::new("/project/src/main.rs"), Path::new("/project/tests")),
+            Ok(Some(PathBuf::from("/project/tests/main.rs")))
+        );
+        // Already in that folder
+        assert_eq!(move_destination(Path::new("/project/src/main.rs"), src), Ok(None));
+        // Into itself or a descendant
+        assert!(move_destination(src, src).is_err());
+        assert!(move_destination(src, Path::new("/project/src/nested")).is_err());
+        // A sibling whose name merely starts the same is fine
+        assert!(move_destination(src, Path::new("/project/src2")).is_ok());
+    }
+}
@@ -0,0 +1,157 @@
+//! Remembered cursor and scroll positions, restored when a file is reopened
+//!
+//! Stored as JSON in the user's cache directory, keyed by absolute path.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Files remembered at most; the least recently used are forgotten first
+const MAX_ENTRIES: usize = 500;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FilePosition {
+    /// Cursor as a byte offset into the file
+    pub cursor: usize,
+    /// Vertical scroll offset in pixels
+    pub scroll_top: f32,
+    /// Incremented on every update, for least-recently-used eviction
+    #[serde(default)]
+    last_used: u64,
+}
+
+impl FilePosition {
+    pub fn new(cursor: usize, scroll_top: f32) -> Self {
+        Self {
+            cursor,
+            scroll_top,
+            last_used: 0,
+        }
+    }
+
+    /// Cursor offset that is valid in `text`, which may have changed on disk since
+    pub fn clamped_cursor(&self, text: &str) -> usize {
+        let mut cursor = self.cursor.min(text.len());
+        while !text.is_char_boundary(cursor) {
+            cursor -= 1;
+        }
+        cursor
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct FilePositions {
+    /// Where the cache is saved; `None` keeps it in memory only
+    cache_path: Option<PathBuf>,
+    entries: HashMap<PathBuf, FilePosition>,
+    clock: u64,
+}
+
+impl FilePositions {
+    /// Load the cache from the user's cache directory (empty if missing or unreadable)
+    pub fn load() -> Self {
+        let Some(cache_path) = default_cache_path() else {
+            return Self::default();
+        };
+        let entries: HashMap<PathBuf, FilePosition> = std::fs::read_to_string(&cache_path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        let clock = entries.values().map(|entry| entry.last_used).max().unwrap_or(0);
+        Self {
+            cache_path: Some(cache_path),
+            entries,
+            clock,
+        }
+    }
+
+    pub fn get(&self, path: &Path) -> Option<FilePosition> {
+        self.entries.get(&absolute(path)).copied()
+    }
+
+    /// Remember a file's position and write the cache to disk
+    pub fn set(&mut self, path: &Path, mut position: FilePosition) {
+        self.clock += 1;
+        position.last_used = self.clock;
+        self.entries.insert(absolute(path), position);
+
+        if self.entries.len() > MAX_ENTRIES {
+            let mut by_age: Vec<(u64, PathBuf)> = self
+                .entries
+                .iter()
+                .map(|(path, entry)| (entry.last_used, path.clone()))
+                .collect();
+            by_age.sort();
+            for (_, path) in by_age.into_iter().take(self.entries.len() - MAX_ENTRIES) {
+                self.entries.remove(&path);
+            }
+        }
+        self.save();
+    }
+
+    fn save(&self) {
+        let Some(cache_path) = &self.cache_path else {
+            return;
+        };
+        let result = serde_json::to_string(&self.entries)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| {
+                if let Some(parent) = cache_path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(cache_path, json)?;
+                Ok(())
+            });
+        if let Err(e) = result {
+            tracing::error!("⚠️  Failed to save file positions to {:?}: {}", cache_path, e);
+        }
+    }
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path)
+        .or_else(|_| std::path::absolute(path))
+        .unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// `<cache dir>/pulsar/script_editor/file_positions.json`
+fn default_cache_path() -> Option<PathBuf> {
+    let base = if cfg!(target_os = "windows") {
+        std::env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        std::env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Caches"))
+    } else {
+        std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+    }?;
+    Some(base.join("pulsar").join("script_editor").join("file_positions.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clamped_cursor() {
+        assert_eq!(FilePosition::new(3, 0.0).clamped_cursor("hello"), 3);
+        // File shrank on disk
+        assert_eq!(FilePosition::new(40, 0.0).clamped_cursor("hello"), 5);
+        // Never inside a multi-byte character
+        assert_eq!(FilePosition::new(2, 0.0).clamped_cursor("aé"), 1);
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let mut positions = FilePositions::default();
+        for ix in 0..=MAX_ENTRIES {
+            positions.set(Path::new(&format!("/tmp/file-{ix}.rs")), FilePosition::new(ix, 0.0));
+        }
+        assert_eq!(positions.entries.len(), MAX_ENTRIES);
+        assert!(positions.get(Path::new("/tmp/file-0.rs")).is_none());
+        assert_eq!(
+            positions.get(Path::new(&format!("/tmp/file-{MAX_ENTRIES}.rs"))).map(|p| p.cursor),
+            Some(MAX_ENTRIES)
+        );
+    }
+}
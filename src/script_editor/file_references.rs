@@ -0,0 +1,190 @@
+//! What dropping files from the explorer onto an editor inserts: an import
+//! of each for code, a link or image for Markdown and HTML, the path itself
+//! otherwise
+//!
+//! A template set for the language overrides that, with `{path}` (from the
+//! file dropped onto, with `/`), `{name}`, `{stem}` and `{module}` (the
+//! language's module path for it) filled in.
+
+use std::path::{Component, Path, PathBuf};
+
+const IMAGE_EXTENSIONS: [&str; 7] = ["png", "jpg", "jpeg", "gif", "svg", "webp", "bmp"];
+
+/// Script extensions JavaScript and TypeScript imports leave off
+const SCRIPT_EXTENSIONS: [&str; 6] = ["js", "ts", "jsx", "tsx", "mjs", "cjs"];
+
+/// The text for `targets` dropped onto `file`, a file in `language` inside the project at `root`
+pub fn file_references(language: &str, file: &Path, targets: &[PathBuf], root: &Path, template: Option<&str>) -> String {
+    let dir = file.parent().unwrap_or(Path::new(""));
+    let references: Vec<String> = targets
+        .iter()
+        .map(|target| {
+            let reference = Reference {
+                path: slashed(&relative_path(dir, target)),
+                name: file_name(target),
+                stem: target.file_stem().unwrap_or_default().to_string_lossy().into_owned(),
+                extension: target.extension().unwrap_or_default().to_string_lossy().to_lowercase(),
+                module: module_path(language, target, root),
+            };
+            match template {
+                Some(template) => reference.fill(template),
+                None => reference.default_for(language),
+            }
+        })
+        .collect();
+    references.join("\n")
+}
+
+/// `target` as reached from the folder `dir`, through `..` where it has to
+pub fn relative_path(dir: &Path, target: &Path) -> PathBuf {
+    let dir: Vec<Component> = dir.components().collect();
+    let target: Vec<Component> = target.components().collect();
+    let common = dir.iter().zip(&target).take_while(|(a, b)| a == b).count();
+    let mut path = PathBuf::new();
+    for _ in common..dir.len() {
+        path.push("..");
+    }
+    path.extend(&target[common..]);
+    path
+}
+
+struct Reference {
+    path: String,
+    name: String,
+    stem: String,
+    extension: String,
+    module: Option<String>,
+}
+
+impl Reference {
+    fn fill(&self, template: &str) -> String {
+        template
+            .replace("{path}", &self.path)
+            .replace("{name}", &self.name)
+            .replace("{stem}", &self.stem)
+            .replace("{module}", self.module.as_deref().unwrap_or(&self.path))
+    }
+
+    fn default_for(&self, language: &str) -> String {
+        let is_image = IMAGE_EXTENSIONS.contains(&self.extension.as_str());
+        match (language, &self.module) {
+            ("markdown", _) if is_image => format!("![{}]({})", self.stem, self.path.replace(' ', "%20")),
+            ("markdown", _) => format!("[{}]({})", self.name, self.path.replace(' ', "%20")),
+            ("rust", Some(module)) => format!("use crate::{};", module),
+            ("rust", None) => format!("#[path = \"{}\"]\nmod {};", self.path, identifier(&self.stem)),
+            ("python", Some(module)) => format!("import {}", module),
+            ("lua", Some(module)) => format!("require(\"{}\")", module),
+            ("javascript" | "typescript", _) if self.extension == "css" => format!("import \"{}\";", dotted(&self.path)),
+            ("javascript" | "typescript", _) => {
+                let path = match SCRIPT_EXTENSIONS.contains(&self.extension.as_str()) {
+                    true => self.path.strip_suffix(&format!(".{}", self.extension)).unwrap_or(&self.path),
+                    false => &self.path,
+                };
+                format!("import {} from \"{}\";", identifier(&self.stem), dotted(path))
+            }
+            ("c" | "cpp", _) => format!("#include \"{}\"", self.path),
+            ("css", _) if is_image => format!("url(\"{}\")", self.path),
+            ("css", _) => format!("@import \"{}\";", self.path),
+            ("html", _) if is_image => format!("<img src=\"{}\" alt=\"{}\">", self.path, self.stem),
+            ("html", _) if self.extension == "css" => format!("<link rel=\"stylesheet\" href=\"{}\">", self.path),
+            ("html", _) if self.extension == "js" => format!("<script src=\"{}\"></script>", self.path),
+            ("html", _) => format!("<a href=\"{}\">{}</a>", self.path, self.name),
+            _ => self.path.clone(),
+        }
+    }
+}
+
+/// Module path of `target` for the imports of `language`, where it has them
+fn module_path(language: &str, target: &Path, root: &Path) -> Option<String> {
+    match language {
+        // From the crate's `src`, which `mod.rs`, `lib.rs` and `main.rs` stand for
+        "rust" if target.extension().is_some_and(|ext| ext == "rs") => {
+            let src = target.ancestors().skip(1).find(|dir| dir.file_name().is_some_and(|name| name == "src"))?;
+            let mut parts = module_parts(target.strip_prefix(src).ok()?);
+            if parts.last().is_some_and(|last| matches!(last.as_str(), "mod" | "lib" | "main")) {
+                parts.pop();
+            }
+            (!parts.is_empty()).then(|| parts.join("::"))
+        }
+        "python" if target.extension().is_some_and(|ext| ext == "py") => {
+            let mut parts = module_parts(target.strip_prefix(root).ok()?);
+            if parts.last().is_some_and(|last| last == "__init__") {
+                parts.pop();
+            }
+            (!parts.is_empty()).then(|| parts.join("."))
+        }
+        "lua" if target.extension().is_some_and(|ext| ext == "lua") => {
+            Some(module_parts(target.strip_prefix(root).ok()?).join("."))
+        }
+        _ => None,
+    }
+}
+
+/// Components of a relative path, the last without its extension
+fn module_parts(path: &Path) -> Vec<String> {
+    let mut parts: Vec<String> = path.components().map(|part| part.as_os_str().to_string_lossy().into_owned()).collect();
+    if let (Some(last), Some(stem)) = (parts.last_mut(), path.file_stem()) {
+        *last = stem.to_string_lossy().into_owned();
+    }
+    parts
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().unwrap_or_default().to_string_lossy().into_owned()
+}
+
+fn slashed(path: &Path) -> String {
+    let parts: Vec<_> = path.components().map(|part| part.as_os_str().to_string_lossy()).collect();
+    parts.join("/")
+}
+
+/// A relative import, which JavaScript wants to start with `./` or `../`
+fn dotted(path: &str) -> String {
+    match path.starts_with("../") {
+        true => path.to_string(),
+        false => format!("./{}", path),
+    }
+}
+
+/// `stem` made a valid identifier: `user-card` becomes `user_card`
+fn identifier(stem: &str) -> String {
+    let ident: String = stem
+        .chars()
+        .map(|ch| if ch.is_alphanumeric() || ch == '_' { ch } else { '_' })
+        .collect();
+    match ident.starts_with(|ch: char| ch.is_ascii_digit()) {
+        true => format!("_{}", ident),
+        false => ident,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_references() {
+        let root = Path::new("/game");
+        let reference = |language: &str, file: &str, target: &str| {
+            file_references(language, &root.join(file), &[root.join(target)], root, None)
+        };
+        assert_eq!(reference("rust", "src/main.rs", "src/enemy/ai.rs"), "use crate::enemy::ai;");
+        assert_eq!(reference("rust", "src/lib.rs", "src/enemy/mod.rs"), "use crate::enemy;");
+        assert_eq!(reference("python", "tools/run.py", "tools/level/__init__.py"), "import tools.level");
+        assert_eq!(reference("lua", "scripts/main.lua", "scripts/ui/menu.lua"), "require(\"scripts.ui.menu\")");
+        assert_eq!(reference("typescript", "web/src/app.ts", "web/lib/user-card.tsx"), "import user_card from \"../lib/user-card\";");
+        assert_eq!(reference("javascript", "web/app.js", "web/style.css"), "import \"./style.css\";");
+        assert_eq!(reference("markdown", "docs/guide.md", "assets/hero shot.png"), "![hero shot](../assets/hero%20shot.png)");
+        assert_eq!(reference("markdown", "README.md", "docs/guide.md"), "[guide.md](docs/guide.md)");
+        assert_eq!(reference("cpp", "src/main.cpp", "include/math.hpp"), "#include \"../include/math.hpp\"");
+        assert_eq!(reference("toml", "Cargo.toml", "assets/icon.png"), "assets/icon.png");
+    }
+
+    #[test]
+    fn test_template_and_several_files() {
+        let root = Path::new("/game");
+        let targets = [root.join("src/physics.rs"), root.join("src/render/mesh.rs")];
+        let text = file_references("rust", &root.join("src/main.rs"), &targets, root, Some("mod {stem}; // {path} {module}"));
+        assert_eq!(text, "mod physics; // physics.rs physics\nmod mesh; // render/mesh.rs render::mesh");
+    }
+}
@@ -0,0 +1,72 @@
+//! Noticing when open files are changed or deleted outside the editor
+//!
+//! Each open file gets a watcher on its folder rather than on the file itself:
+//! many tools save by writing a new file and renaming it over the old one,
+//! which a watch on the old file would lose track of.
+
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How long to wait for more events after one arrives, so a burst of them
+/// (a save, a checkout) is handled once
+pub const WATCH_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// One folder watcher per open file, reporting the files that changed on a channel
+pub struct FileWatcher {
+    watchers: HashMap<PathBuf, RecommendedWatcher>,
+    changes: UnboundedSender<PathBuf>,
+}
+
+impl FileWatcher {
+    pub fn new() -> (Self, UnboundedReceiver<PathBuf>) {
+        let (changes, receiver) = unbounded();
+        let watcher = Self {
+            watchers: HashMap::new(),
+            changes,
+        };
+        (watcher, receiver)
+    }
+
+    /// Start reporting changes to `path`; files not on disk are skipped
+    pub fn watch(&mut self, path: &Path) {
+        if self.watchers.contains_key(path) || !path.is_file() {
+            return;
+        }
+        let (Some(folder), Some(file_name)) = (path.parent(), path.file_name()) else {
+            return;
+        };
+
+        let changes = self.changes.clone();
+        let watched = path.to_path_buf();
+        let file_name = file_name.to_os_string();
+        let watcher = notify::recommended_watcher(move |result: notify::Result<Event>| {
+            let Ok(event) = result else {
+                return;
+            };
+            if matches!(event.kind, EventKind::Access(_)) {
+                return;
+            }
+            if event.paths.iter().any(|changed| changed.file_name() == Some(file_name.as_os_str())) {
+                changes.unbounded_send(watched.clone()).ok();
+            }
+        })
+        .and_then(|mut watcher| {
+            watcher.watch(folder, RecursiveMode::NonRecursive)?;
+            Ok(watcher)
+        });
+
+        match watcher {
+            Ok(watcher) => {
+                self.watchers.insert(path.to_path_buf(), watcher);
+            }
+            Err(e) => tracing::warn!("⚠️  Not watching {:?} for changes: {}", path, e),
+        }
+    }
+
+    pub fn unwatch(&mut self, path: &Path) {
+        self.watchers.remove(path);
+    }
+}
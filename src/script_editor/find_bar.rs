@@ -0,0 +1,451 @@
+//! Find and replace bar for the text editor
+
+use gpui::{prelude::FluentBuilder, *};
+use std::ops::Range;
+use ui::{
+    button::{Button, ButtonVariants as _},
+    h_flex,
+    input::{InputEvent, InputState, TextInput},
+    v_flex, ActiveTheme as _, IconName, Selectable as _, Sizable as _,
+};
+
+use super::lsp::{offset_to_position, offsets_to_range};
+use super::search::{find_matches, match_at_or_after, SearchQuery, MAX_MATCHES};
+
+actions!(find_bar, [SelectNextMatch, SelectPreviousMatch, DismissFindBar]);
+
+pub enum FindBarEvent {
+    Dismissed,
+}
+
+pub struct FindBar {
+    focus_handle: FocusHandle,
+    query_input: Entity<InputState>,
+    replace_input: Entity<InputState>,
+    show_replace: bool,
+    case_sensitive: bool,
+    whole_word: bool,
+    use_regex: bool,
+    /// Buffer being searched
+    target: Option<Entity<InputState>>,
+    matches: Vec<Range<usize>>,
+    active_match: Option<usize>,
+    /// Invalid regex message
+    error: Option<String>,
+    /// Running search; replacing it cancels the previous one
+    search_task: Option<Task<()>>,
+    _target_subscription: Option<Subscription>,
+    _subscriptions: Vec<Subscription>,
+}
+
+impl EventEmitter<FindBarEvent> for FindBar {}
+
+impl FindBar {
+    pub fn new(window: &mut Window, cx: &mut Context<Self>) -> Self {
+        cx.bind_keys([
+            KeyBinding::new("enter", SelectNextMatch, Some("FindBar")),
+            KeyBinding::new("shift-enter", SelectPreviousMatch, Some("FindBar")),
+            KeyBinding::new("escape", DismissFindBar, Some("FindBar")),
+        ]);
+
+        let query_input = cx.new(|cx| InputState::new(window, cx).placeholder("Find"));
+        let replace_input = cx.new(|cx| InputState::new(window, cx).placeholder("Replace"));
+
+        let subscriptions = vec![
+            cx.subscribe_in(&query_input, window, |this, _input, event: &InputEvent, window, cx| {
+                match event {
+                    InputEvent::Change => this.search(cx),
+                    InputEvent::PressEnter { .. } => this.select_next_match(window, cx),
+                    _ => {}
+                }
+            }),
+            cx.subscribe_in(&replace_input, window, |this, _input, event: &InputEvent, window, cx| {
+                if let InputEvent::PressEnter { .. } = event {
+                    this.replace_current(window, cx);
+                }
+            }),
+        ];
+
+        Self {
+            focus_handle: cx.focus_handle(),
+            query_input,
+            replace_input,
+            show_replace: false,
+            case_sensitive: false,
+            whole_word: false,
+            use_regex: false,
+            target: None,
+            matches: Vec::new(),
+            active_match: None,
+            error: None,
+            search_task: None,
+            _target_subscription: None,
+            _subscriptions: subscriptions,
+        }
+    }
+
+    /// Focus the search field, optionally expanding the replace row
+    pub fn show(&mut self, show_replace: bool, window: &mut Window, cx: &mut Context<Self>) {
+        self.show_replace = show_replace;
+        self.query_input.update(cx, |input, cx| input.focus(window, cx));
+        self.search(cx);
+    }
+
+    /// Search a different buffer, re-running the search whenever it changes
+    pub fn set_target(&mut self, target: Option<Entity<InputState>>, cx: &mut Context<Self>) {
+        if self.target == target {
+            return;
+        }
+        self.clear_highlights(cx);
+        self._target_subscription = target.as_ref().map(|target| {
+            cx.subscribe(target, |this, _target, event: &InputEvent, cx| {
+                if let InputEvent::Change = event {
+                    this.search(cx);
+                }
+            })
+        });
+        self.target = target;
+        self.search(cx);
+    }
+
+    /// Remove match highlights from the buffer, e.g. when the bar closes
+    pub fn clear_highlights(&mut self, cx: &mut Context<Self>) {
+        self.search_task = None;
+        self.matches.clear();
+        self.active_match = None;
+        if let Some(target) = &self.target {
+            target.update(cx, |state, cx| {
+                state.set_search_highlights(Vec::new(), None);
+                cx.notify();
+            });
+        }
+    }
+
+    fn query(&self, cx: &App) -> SearchQuery {
+        SearchQuery {
+            pattern: self.query_input.read(cx).value().to_string(),
+            case_sensitive: self.case_sensitive,
+            whole_word: self.whole_word,
+            regex: self.use_regex,
+        }
+    }
+
+    /// Recompute matches in the background so large buffers don't block typing
+    fn search(&mut self, cx: &mut Context<Self>) {
+        let Some(target) = self.target.clone() else {
+            return;
+        };
+        let query = self.query(cx);
+        if query.pattern.is_empty() {
+            self.error = None;
+            self.clear_highlights(cx);
+            cx.notify();
+            return;
+        }
+
+        let text = target.read(cx).value().to_string();
+        let cursor = target.read(cx).cursor();
+        self.search_task = Some(cx.spawn(async move |this, cx| {
+            let result = cx
+                .background_spawn(async move {
+                    query
+                        .build()
+                        .map(|regex| find_matches(&regex, &text, MAX_MATCHES))
+                })
+                .await;
+
+            this.update(cx, |this, cx| {
+                match result {
+                    Ok(matches) => {
+                        this.error = None;
+                        this.active_match = match_at_or_after(&matches, cursor);
+                        this.matches = matches;
+                    }
+                    Err(e) => {
+                        this.error = Some(e.to_string());
+                        this.matches.clear();
+                        this.active_match = None;
+                    }
+                }
+                this.update_highlights(cx);
+                cx.notify();
+            })
+            .ok();
+        }));
+    }
+
+    fn update_highlights(&self, cx: &mut Context<Self>) {
+        if let Some(target) = &self.target {
+            let matches = self.matches.clone();
+            let active = self.active_match;
+            target.update(cx, |state, cx| {
+                state.set_search_highlights(matches, active);
+                cx.notify();
+            });
+        }
+    }
+
+    fn select_match(&mut self, index: usize, window: &mut Window, cx: &mut Context<Self>) {
+        let (Some(target), Some(range)) = (self.target.clone(), self.matches.get(index).cloned()) else {
+            return;
+        };
+        self.active_match = Some(index);
+        target.update(cx, |state, cx| {
+            let position = offset_to_position(&state.value(), range.start);
+            state.set_cursor_position(
+                ui::input::Position::new(position.line, position.character),
+                window,
+                cx,
+            );
+        });
+        self.update_highlights(cx);
+        cx.notify();
+    }
+
+    fn select_next_match(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        if self.matches.is_empty() {
+            return;
+        }
+        let next = self.active_match.map_or(0, |ix| (ix + 1) % self.matches.len());
+        self.select_match(next, window, cx);
+    }
+
+    fn select_previous_match(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        if self.matches.is_empty() {
+            return;
+        }
+        let len = self.matches.len();
+        let previous = self.active_match.map_or(len - 1, |ix| (ix + len - 1) % len);
+        self.select_match(previous, window, cx);
+    }
+
+    fn replace_current(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let (Some(target), Some(index)) = (self.target.clone(), self.active_match) else {
+            return;
+        };
+        let Some(range) = self.matches.get(index).cloned() else {
+            return;
+        };
+        let query = self.query(cx);
+        let Ok(regex) = query.build() else {
+            return;
+        };
+
+        let text = target.read(cx).value().to_string();
+        let replace = self.replace_input.read(cx).value().to_string();
+        let new_text = query.replacement(&regex, &text, range.clone(), &replace);
+        let edit = lsp_types::TextEdit {
+            range: offsets_to_range(&text, range),
+            new_text,
+        };
+        super::text_editor::apply_edits_to_input(&target, vec![edit], window, cx);
+        // The change event re-runs the search, which moves on to the next match
+    }
+
+    fn replace_all(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(target) = self.target.clone() else {
+            return;
+        };
+        let query = self.query(cx);
+        if query.pattern.is_empty() {
+            return;
+        }
+        let Ok(regex) = query.build() else {
+            return;
+        };
+
+        let text = target.read(cx).value().to_string();
+        let replace = self.replace_input.read(cx).value().to_string();
+        let (new_text, count) = query.replace_all(&regex, &text, &replace);
+        if count == 0 {
+            return;
+        }
+        // One edit covering the whole buffer keeps Replace All a single undo step
+        let edit = lsp_types::TextEdit {
+            range: offsets_to_range(&text, 0..text.len()),
+            new_text,
+        };
+        super::text_editor::apply_edits_to_input(&target, vec![edit], window, cx);
+        tracing::debug!("✓ Replaced {} matches", count);
+    }
+
+    fn toggle_option(&mut self, update: impl FnOnce(&mut Self), cx: &mut Context<Self>) {
+        update(self);
+        self.search(cx);
+        cx.notify();
+    }
+
+    fn on_select_next_match(&mut self, _: &SelectNextMatch, window: &mut Window, cx: &mut Context<Self>) {
+        self.select_next_match(window, cx);
+    }
+
+    fn on_select_previous_match(
+        &mut self,
+        _: &SelectPreviousMatch,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.select_previous_match(window, cx);
+    }
+
+    fn on_dismiss(&mut self, _: &DismissFindBar, _window: &mut Window, cx: &mut Context<Self>) {
+        self.clear_highlights(cx);
+        cx.emit(FindBarEvent::Dismissed);
+    }
+
+    fn match_summary(&self) -> String {
+        if let Some(error) = &self.error {
+            return error.lines().last().unwrap_or("Invalid pattern").to_string();
+        }
+        match (self.active_match, self.matches.len()) {
+            (_, 0) => "No results".to_string(),
+            (Some(active), total) if total >= MAX_MATCHES => format!("{} of {}+", active + 1, total),
+            (Some(active), total) => format!("{} of {}", active + 1, total),
+            (None, total) => format!("{} results", total),
+        }
+    }
+}
+
+impl Focusable for FindBar {
+    fn focus_handle(&self, _cx: &App) -> FocusHandle {
+        self.focus_handle.clone()
+    }
+}
+
+impl Render for FindBar {
+    fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        v_flex()
+            .key_context("FindBar")
+            .track_focus(&self.focus_handle)
+            .on_action(cx.listener(Self::on_select_next_match))
+            .on_action(cx.listener(Self::on_select_previous_match))
+            .on_action(cx.listener(Self::on_dismiss))
+            .w_full()
+            .px_2()
+            .py_1()
+            .gap_1()
+            .bg(cx.theme().secondary)
+            .border_b_1()
+            .border_color(cx.theme().border)
+            .child(
+                h_flex()
+                    .gap_1()
+                    .child(
+                        Button::new("toggle_replace")
+                            .icon(if self.show_replace {
+                                IconName::ChevronDown
+                            } else {
+                                IconName::ChevronRight
+                            })
+                            .ghost()
+                            .xsmall()
+                            .on_click(cx.listener(|this, _, _window, cx| {
+                                this.show_replace = !this.show_replace;
+                                cx.notify();
+                            })),
+                    )
+                    .child(div().flex_1().child(TextInput::new(&self.query_input).small()))
+                    .child(
+                        Button::new("case_sensitive")
+                            .label("Aa")
+                            .tooltip("Match Case")
+                            .ghost()
+                            .xsmall()
+                            .selected(self.case_sensitive)
+                            .on_click(cx.listener(|this, _, _window, cx| {
+                                this.toggle_option(|this| this.case_sensitive = !this.case_sensitive, cx);
+                            })),
+                    )
+                    .child(
+                        Button::new("whole_word")
+                            .label("ab")
+                            .tooltip("Match Whole Word")
+                            .ghost()
+                            .xsmall()
+                            .selected(self.whole_word)
+                            .on_click(cx.listener(|this, _, _window, cx| {
+                                this.toggle_option(|this| this.whole_word = !this.whole_word, cx);
+                            })),
+                    )
+                    .child(
+                        Button::new("use_regex")
+                            .label(".*")
+                            .tooltip("Use Regular Expression")
+                            .ghost()
+                            .xsmall()
+                            .selected(self.use_regex)
+                            .on_click(cx.listener(|this, _, _window, cx| {
+                                this.toggle_option(|this| this.use_regex = !this.use_regex, cx);
+                            })),
+                    )
+                    .child(
+                        div()
+                            .min_w(px(80.))
+                            .text_xs()
+                            .text_color(if self.error.is_some() {
+                                cx.theme().danger
+                            } else {
+                                cx.theme().muted_foreground
+                            })
+                            .child(self.match_summary()),
+                    )
+                    .child(
+                        Button::new("previous_match")
+                            .icon(IconName::ArrowUp)
+                            .tooltip("Previous Match (Shift+Enter)")
+                            .ghost()
+                            .xsmall()
+                            .on_click(cx.listener(|this, _, window, cx| {
+                                this.select_previous_match(window, cx);
+                            })),
+                    )
+                    .child(
+                        Button::new("next_match")
+                            .icon(IconName::ArrowDown)
+                            .tooltip("Next Match (Enter)")
+                            .ghost()
+                            .xsmall()
+                            .on_click(cx.listener(|this, _, window, cx| {
+                                this.select_next_match(window, cx);
+                            })),
+                    )
+                    .child(
+                        Button::new("close_find")
+                            .icon(IconName::Close)
+                            .tooltip("Close (Escape)")
+                            .ghost()
+                            .xsmall()
+                            .on_click(cx.listener(|this, _, _window, cx| {
+                                this.clear_highlights(cx);
+                                cx.emit(FindBarEvent::Dismissed);
+                            })),
+                    ),
+            )
+            .when(self.show_replace, |bar| {
+                bar.child(
+                    h_flex()
+                        .gap_1()
+                        .pl_6()
+                        .child(div().flex_1().child(TextInput::new(&self.replace_input).small()))
+                        .child(
+                            Button::new("replace")
+                                .label("Replace")
+                                .ghost()
+                                .xsmall()
+                                .on_click(cx.listener(|this, _, window, cx| {
+                                    this.replace_current(window, cx);
+                                })),
+                        )
+                        .child(
+                            Button::new("replace_all")
+                                .label("Replace All")
+                                .ghost()
+                                .xsmall()
+                                .on_click(cx.listener(|this, _, window, cx| {
+                                    this.replace_all(window, cx);
+                                })),
+                        ),
+                )
+            })
+    }
+}
@@ -0,0 +1,335 @@
+//! Code folding: foldable ranges and which of them are collapsed
+//!
+//! Ranges come from the language server (`textDocument/foldingRange`) when it
+//! supports them, otherwise from braces, `// region` markers and indentation,
+//! or a TOML file's `[table]` sections.
+
+use super::brackets::{code_brackets, Bracket};
+use super::toml_validation::{section_fold_ranges, toml_brackets};
+
+/// Shown after the first line of a folded range
+pub const FOLD_PLACEHOLDER: &str = "⋯";
+
+/// A foldable range of lines. `start` stays visible; `start + 1..=end` hide when folded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FoldRange {
+    pub start: u32,
+    pub end: u32,
+}
+
+impl FoldRange {
+    pub fn hides(&self, line: u32) -> bool {
+        line > self.start && line <= self.end
+    }
+}
+
+/// Foldable ranges reported by the language server, in the same shape as the heuristics
+pub fn fold_ranges_from_lsp(ranges: Vec<lsp_types::FoldingRange>) -> Vec<FoldRange> {
+    normalize(
+        ranges
+            .into_iter()
+            .map(|range| FoldRange {
+                start: range.start_line,
+                end: range.end_line,
+            })
+            .collect(),
+    )
+}
+
+/// Foldable ranges found without a language server.
+///
+/// Brace blocks and `region`/`endregion` comments are used when the text has
+/// any; otherwise (Python, YAML, ...) ranges follow the indentation.
+pub fn heuristic_fold_ranges(text: &str) -> Vec<FoldRange> {
+    let mut ranges = brace_fold_ranges(text, code_brackets(text));
+    ranges.extend(region_fold_ranges(text));
+    if ranges.is_empty() {
+        ranges = indent_fold_ranges(text);
+    }
+    normalize(ranges)
+}
+
+/// Foldable ranges of a TOML file: `[table]` sections and multi-line arrays
+pub fn toml_fold_ranges(text: &str) -> Vec<FoldRange> {
+    let mut ranges = section_fold_ranges(text);
+    ranges.extend(brace_fold_ranges(text, toml_brackets(text)));
+    ranges.extend(region_fold_ranges(text));
+    normalize(ranges)
+}
+
+/// Multi-line `{}`/`[]`/`()` blocks; the closing line stays visible
+fn brace_fold_ranges(text: &str, brackets: Vec<Bracket>) -> Vec<FoldRange> {
+    let mut ranges = Vec::new();
+    let mut open: Vec<u32> = Vec::new();
+    let mut line = 0;
+    let mut scanned = 0;
+
+    for bracket in brackets {
+        line += text[scanned..bracket.offset].matches('\n').count() as u32;
+        scanned = bracket.offset;
+        if bracket.is_open() {
+            open.push(line);
+        } else if let Some(start) = open.pop() {
+            if line > start + 1 {
+                ranges.push(FoldRange {
+                    start,
+                    end: line - 1,
+                });
+            }
+        }
+    }
+    ranges
+}
+
+/// `// region` ... `// endregion` (also `#region`, `// #region`) spans, markers included
+fn region_fold_ranges(text: &str) -> Vec<FoldRange> {
+    let mut ranges = Vec::new();
+    let mut open: Vec<u32> = Vec::new();
+    for (line_ix, line) in text.lines().enumerate() {
+        let trimmed = line.trim_start();
+        let marker = trimmed
+            .trim_start_matches("//")
+            .trim_start_matches('#')
+            .trim_start()
+            .trim_start_matches('#');
+        if marker.len() == trimmed.len() {
+            // Not a comment
+            continue;
+        }
+        if marker.starts_with("endregion") {
+            if let Some(start) = open.pop() {
+                ranges.push(FoldRange {
+                    start,
+                    end: line_ix as u32,
+                });
+            }
+        } else if marker.starts_with("region") {
+            open.push(line_ix as u32);
+        }
+    }
+    ranges
+}
+
+/// Runs of lines indented deeper than the line that starts them
+fn indent_fold_ranges(text: &str) -> Vec<FoldRange> {
+    let indents: Vec<Option<usize>> = text
+        .lines()
+        .map(|line| {
+            let trimmed = line.trim_start();
+            (!trimmed.is_empty()).then(|| line.len() - trimmed.len())
+        })
+        .collect();
+
+    let mut ranges = Vec::new();
+    for (start, indent) in indents.iter().enumerate() {
+        let Some(indent) = *indent else {
+            continue;
+        };
+        let mut end = None;
+        for (line, other) in indents.iter().enumerate().skip(start + 1) {
+            match other {
+                Some(other) if *other > indent => end = Some(line),
+                Some(_) => break,
+                None => {}
+            }
+        }
+        if let Some(end) = end {
+            ranges.push(FoldRange {
+                start: start as u32,
+                end: end as u32,
+            });
+        }
+    }
+    ranges
+}
+
+/// Sort, drop single-line ranges and keep one range per start line (the largest)
+fn normalize(mut ranges: Vec<FoldRange>) -> Vec<FoldRange> {
+    ranges.retain(|range| range.end > range.start);
+    ranges.sort_by(|a, b| a.start.cmp(&b.start).then(b.end.cmp(&a.end)));
+    ranges.dedup_by_key(|range| range.start);
+    ranges
+}
+
+/// Foldable ranges of a document and the ones currently folded
+#[derive(Clone, Debug, Default)]
+pub struct FoldState {
+    foldable: Vec<FoldRange>,
+    folded: Vec<FoldRange>,
+}
+
+impl FoldState {
+    pub fn foldable(&self) -> &[FoldRange] {
+        &self.foldable
+    }
+
+    pub fn folded(&self) -> &[FoldRange] {
+        &self.folded
+    }
+
+    /// Replace the foldable ranges, e.g. after the server recomputed them.
+    ///
+    /// Folds that still start on a foldable line stay folded (taking the new
+    /// end); the others are dropped.
+    pub fn set_foldable(&mut self, foldable: Vec<FoldRange>) {
+        self.folded = self
+            .folded
+            .iter()
+            .filter_map(|folded| foldable.iter().find(|range| range.start == folded.start).copied())
+            .collect();
+        self.foldable = foldable;
+    }
+
+    pub fn is_folded(&self, line: u32) -> bool {
+        self.folded.iter().any(|range| range.start == line)
+    }
+
+    /// Fold or unfold the range starting at `line`; returns false if nothing starts there
+    pub fn toggle(&mut self, line: u32) -> bool {
+        if let Some(ix) = self.folded.iter().position(|range| range.start == line) {
+            self.folded.remove(ix);
+            return true;
+        }
+        match self.foldable.iter().find(|range| range.start == line) {
+            Some(range) => {
+                self.folded.push(*range);
+                self.folded.sort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn fold_all(&mut self) {
+        self.folded = self.foldable.clone();
+    }
+
+    pub fn unfold_all(&mut self) {
+        self.folded.clear();
+    }
+
+    /// Whether `line` is inside a folded range (and so not rendered)
+    pub fn is_hidden(&self, line: u32) -> bool {
+        self.folded.iter().any(|range| range.hides(line))
+    }
+
+    /// Folded ranges without the ones nested inside other folds
+    pub fn outermost_folds(&self) -> Vec<FoldRange> {
+        let mut outermost: Vec<FoldRange> = Vec::new();
+        for range in &self.folded {
+            match outermost.last() {
+                Some(last) if range.start <= last.end => {}
+                _ => outermost.push(*range),
+            }
+        }
+        outermost
+    }
+
+    /// Where a cursor moving from line `from` to line `to` should land so that
+    /// it never rests on a hidden line: moving down skips past the fold,
+    /// moving up (or jumping in) stops on the fold's first line.
+    pub fn visible_line(&self, from: u32, to: u32) -> u32 {
+        let Some(fold) = self.outermost_folds().into_iter().find(|range| range.hides(to)) else {
+            return to;
+        };
+        if to > from && from <= fold.start {
+            fold.end + 1
+        } else {
+            fold.start
+        }
+    }
+
+    /// Keep folds on the same code after an edit that changed lines
+    /// `start_line..=old_end_line` into `start_line..=new_end_line`.
+    ///
+    /// Foldable ranges are shifted the same way until they are recomputed;
+    /// ranges the edit touched are dropped.
+    pub fn remap(&mut self, start_line: u32, old_end_line: u32, new_end_line: u32) {
+        let delta = new_end_line as i64 - old_end_line as i64;
+        let remap = |ranges: &mut Vec<FoldRange>| {
+            ranges.retain_mut(|range| {
+                if range.end < start_line {
+                    true
+                } else if range.start > old_end_line {
+                    range.start = (range.start as i64 + delta) as u32;
+                    range.end = (range.end as i64 + delta) as u32;
+                    true
+                } else if range.start < start_line && range.end > old_end_line {
+                    // Edit inside the body (e.g. a replace-all): keep the range, resized
+                    range.end = (range.end as i64 + delta) as u32;
+                    true
+                } else {
+                    false
+                }
+            });
+        };
+        remap(&mut self.foldable);
+        remap(&mut self.folded);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_brace_and_region_ranges() {
+        let text = "\
+// region setup
+fn main() {
+    let s = \"{\";
+    call(
+        1,
+    );
+}
+// endregion
+";
+        assert_eq!(
+            heuristic_fold_ranges(text),
+            vec![
+                FoldRange { start: 0, end: 7 },
+                FoldRange { start: 1, end: 5 },
+                FoldRange { start: 3, end: 4 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_indent_ranges() {
+        let text = "def f():\n    a = 1\n\n    b = 2\nx = 3\n";
+        assert_eq!(heuristic_fold_ranges(text), vec![FoldRange { start: 0, end: 3 }]);
+    }
+
+    #[test]
+    fn test_toggle_and_visible_line() {
+        let mut folds = FoldState::default();
+        folds.set_foldable(vec![FoldRange { start: 2, end: 6 }, FoldRange { start: 3, end: 4 }]);
+        assert!(folds.toggle(2));
+        assert!(!folds.toggle(1));
+        assert!(folds.is_hidden(4));
+        assert!(!folds.is_hidden(2));
+
+        // Down from the header skips the body, up into it stops on the header
+        assert_eq!(folds.visible_line(2, 3), 7);
+        assert_eq!(folds.visible_line(7, 6), 2);
+        assert_eq!(folds.visible_line(0, 1), 1);
+    }
+
+    #[test]
+    fn test_remap_keeps_untouched_folds() {
+        let mut folds = FoldState::default();
+        folds.set_foldable(vec![FoldRange { start: 2, end: 4 }, FoldRange { start: 10, end: 12 }]);
+        folds.fold_all();
+
+        // Two lines inserted at line 6
+        folds.remap(6, 6, 8);
+        assert_eq!(
+            folds.folded(),
+            &[FoldRange { start: 2, end: 4 }, FoldRange { start: 12, end: 14 }]
+        );
+
+        // Editing the header line drops that fold
+        folds.remap(2, 2, 2);
+        assert_eq!(folds.folded(), &[FoldRange { start: 12, end: 14 }]);
+    }
+}
@@ -0,0 +1,200 @@
+//! Git blame for the lines of an open file, read with `git blame --porcelain`
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+
+/// Hash git reports for lines that are not committed yet
+const UNCOMMITTED: &str = "0000000000000000000000000000000000000000";
+
+/// The commit that last changed a line
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BlameCommit {
+    pub sha: String,
+    pub author: String,
+    pub author_mail: String,
+    /// Unix time the commit was authored
+    pub author_time: i64,
+    pub summary: String,
+}
+
+impl BlameCommit {
+    pub fn is_uncommitted(&self) -> bool {
+        self.sha == UNCOMMITTED
+    }
+
+    pub fn short_sha(&self) -> &str {
+        &self.sha[..self.sha.len().min(8)]
+    }
+}
+
+/// Parse `git blame --porcelain` output into zero-based line numbers and their commits.
+///
+/// Commit details are only sent the first time a commit appears; later
+/// lines from the same commit share it.
+pub fn parse_blame_porcelain(output: &str) -> Vec<(u32, Arc<BlameCommit>)> {
+    let mut commits: HashMap<String, BlameCommit> = HashMap::new();
+    let mut lines: Vec<(u32, String)> = Vec::new();
+    let mut current: Option<String> = None;
+
+    for line in output.lines() {
+        if line.starts_with('\t') {
+            // The line's content ends its entry
+            current = None;
+            continue;
+        }
+        let Some(sha) = current.clone() else {
+            // Header: `<sha> <original line> <final line> [<lines in group>]`
+            let mut fields = line.split(' ');
+            let (Some(sha), Some(_), Some(final_line)) = (fields.next(), fields.next(), fields.next()) else {
+                continue;
+            };
+            let Ok(final_line) = final_line.parse::<u32>() else {
+                continue;
+            };
+            commits.entry(sha.to_string()).or_insert_with(|| BlameCommit {
+                sha: sha.to_string(),
+                ..Default::default()
+            });
+            lines.push((final_line.saturating_sub(1), sha.to_string()));
+            current = Some(sha.to_string());
+            continue;
+        };
+        let commit = commits.get_mut(&sha).expect("header inserted the commit");
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+        match key {
+            "author" => commit.author = value.to_string(),
+            "author-mail" => commit.author_mail = value.trim_matches(['<', '>']).to_string(),
+            "author-time" => commit.author_time = value.parse().unwrap_or_default(),
+            "summary" => commit.summary = value.to_string(),
+            _ => {}
+        }
+    }
+
+    let commits: HashMap<String, Arc<BlameCommit>> = commits
+        .into_iter()
+        .map(|(sha, commit)| (sha, Arc::new(commit)))
+        .collect();
+    lines
+        .into_iter()
+        .filter_map(|(line, sha)| Some((line, commits.get(&sha)?.clone())))
+        .collect()
+}
+
+/// How long ago `then` was, as in `3 days ago`
+pub fn relative_time(then: i64, now: i64) -> String {
+    const MINUTE: i64 = 60;
+    const HOUR: i64 = 60 * MINUTE;
+    const DAY: i64 = 24 * HOUR;
+    const MONTH: i64 = 30 * DAY;
+    const YEAR: i64 = 365 * DAY;
+
+    let elapsed = (now - then).max(0);
+    let (count, unit) = match elapsed {
+        elapsed if elapsed < MINUTE => return "just now".to_string(),
+        elapsed if elapsed < HOUR => (elapsed / MINUTE, "minute"),
+        elapsed if elapsed < DAY => (elapsed / HOUR, "hour"),
+        elapsed if elapsed < MONTH => (elapsed / DAY, "day"),
+        elapsed if elapsed < YEAR => (elapsed / MONTH, "month"),
+        elapsed => (elapsed / YEAR, "year"),
+    };
+    format!("{} {}{} ago", count, unit, if count == 1 { "" } else { "s" })
+}
+
+/// Commit `HEAD` points at in the repository containing `path`, or `None`
+/// when it is not under version control
+pub fn head_commit(path: &Path) -> Option<String> {
+    let output = git_in(path)?.args(["rev-parse", "HEAD"]).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Blame the zero-based `lines` of `path` as they are in `text` (the buffer, saved or not)
+pub fn blame_lines(path: &Path, text: &str, lines: std::ops::Range<u32>) -> Option<Vec<(u32, Arc<BlameCommit>)>> {
+    let file_name = path.file_name()?;
+    let mut child = git_in(path)?
+        .arg("blame")
+        .arg("--porcelain")
+        .arg("-L")
+        .arg(format!("{},{}", lines.start + 1, lines.end))
+        .args(["--contents", "-", "--"])
+        .arg(file_name)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    child.stdin.take()?.write_all(text.as_bytes()).ok()?;
+    let output = child.wait_with_output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| parse_blame_porcelain(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Full message of commit `sha`
+pub fn commit_message(path: &Path, sha: &str) -> Option<String> {
+    let output = git_in(path)?.args(["show", "-s", "--format=%B", sha]).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim_end().to_string())
+}
+
+/// `git` run from the folder holding `path`
+fn git_in(path: &Path) -> Option<Command> {
+    let mut command = Command::new("git");
+    command.current_dir(path.parent()?);
+    Some(command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_blame_porcelain() {
+        let output = "\
+1111111111111111111111111111111111111111 1 1 2
+author Ada
+author-mail <ada@example.com>
+author-time 1700000000
+summary Add parser
+filename src/lib.rs
+\tfn parse() {
+1111111111111111111111111111111111111111 2 2
+\t}
+0000000000000000000000000000000000000000 3 3 1
+author Not Committed Yet
+author-mail <not.committed.yet>
+author-time 1700000500
+summary Version of src/lib.rs from -
+filename src/lib.rs
+\t// new
+";
+        let lines = parse_blame_porcelain(output);
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].0, 0);
+        assert_eq!(lines[0].1.author, "Ada");
+        assert_eq!(lines[0].1.author_mail, "ada@example.com");
+        assert!(Arc::ptr_eq(&lines[0].1, &lines[1].1));
+        assert_eq!(lines[2].0, 2);
+        assert!(lines[2].1.is_uncommitted());
+    }
+
+    #[test]
+    fn test_relative_time() {
+        let now = 1_700_000_000;
+        assert_eq!(relative_time(now - 30, now), "just now");
+        assert_eq!(relative_time(now - 60, now), "1 minute ago");
+        assert_eq!(relative_time(now - 3 * 3600, now), "3 hours ago");
+        assert_eq!(relative_time(now - 3 * 86400, now), "3 days ago");
+        assert_eq!(relative_time(now - 400 * 86400, now), "1 year ago");
+        // Clock skew never reads as the future
+        assert_eq!(relative_time(now + 100, now), "just now");
+    }
+}
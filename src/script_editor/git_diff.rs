@@ -0,0 +1,199 @@
+//! Lines of an open file changed since `HEAD`, and reverting or staging them
+
+use similar::{DiffOp, TextDiff};
+use std::io::Write;
+use std::ops::Range;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HunkKind {
+    Added,
+    Modified,
+    Deleted,
+}
+
+/// A run of lines that differ from `HEAD`
+#[derive(Clone, Debug, PartialEq)]
+pub struct DiffHunk {
+    pub kind: HunkKind,
+    /// Zero-based lines of the current text; empty for deletions, placed at
+    /// the line that followed the deleted ones
+    pub lines: Range<u32>,
+    /// Zero-based lines as they are in `HEAD`
+    pub head_lines: Range<u32>,
+    /// The lines in `HEAD`, with line endings
+    pub head_text: String,
+    /// The lines now, with line endings
+    pub text: String,
+}
+
+/// Hunks turning `head` into `current`, in order
+pub fn diff_hunks(head: &str, current: &str) -> Vec<DiffHunk> {
+    let diff = TextDiff::from_lines(head, current);
+    let (old, new) = (diff.old_slices(), diff.new_slices());
+
+    // A deletion right before an insertion is one modification
+    let mut spans: Vec<(Range<usize>, Range<usize>)> = Vec::new();
+    for op in diff.ops() {
+        let (old_range, new_range) = match *op {
+            DiffOp::Equal { .. } => continue,
+            DiffOp::Delete { old_index, old_len, new_index } => (old_index..old_index + old_len, new_index..new_index),
+            DiffOp::Insert { old_index, new_index, new_len } => (old_index..old_index, new_index..new_index + new_len),
+            DiffOp::Replace { old_index, old_len, new_index, new_len } => {
+                (old_index..old_index + old_len, new_index..new_index + new_len)
+            }
+        };
+        match spans.last_mut() {
+            Some((last_old, last_new)) if last_old.end == old_range.start && last_new.end == new_range.start => {
+                last_old.end = old_range.end;
+                last_new.end = new_range.end;
+            }
+            _ => spans.push((old_range, new_range)),
+        }
+    }
+
+    spans
+        .into_iter()
+        .map(|(old_range, new_range)| {
+            let kind = if old_range.is_empty() {
+                HunkKind::Added
+            } else if new_range.is_empty() {
+                HunkKind::Deleted
+            } else {
+                HunkKind::Modified
+            };
+            DiffHunk {
+                kind,
+                head_text: old[old_range.clone()].concat(),
+                text: new[new_range.clone()].concat(),
+                lines: new_range.start as u32..new_range.end as u32,
+                head_lines: old_range.start as u32..old_range.end as u32,
+            }
+        })
+        .collect()
+}
+
+/// `current` with the hunk's lines put back the way they are in `HEAD`.
+///
+/// `None` when those lines have been edited since the hunk was computed.
+pub fn revert_hunk(current: &str, hunk: &DiffHunk) -> Option<String> {
+    let lines: Vec<&str> = current.split_inclusive('\n').collect();
+    let range = hunk.lines.start as usize..hunk.lines.end as usize;
+    if range.end > lines.len() || lines[range.clone()].concat() != hunk.text {
+        return None;
+    }
+    let mut reverted: String = lines[..range.start].concat();
+    reverted.push_str(&hunk.head_text);
+    reverted.push_str(&lines[range.end..].concat());
+    Some(reverted)
+}
+
+/// Zero-context patch applying just `hunk` to `file_name` (for `git apply --unidiff-zero`)
+pub fn hunk_patch(file_name: &str, hunk: &DiffHunk) -> String {
+    // Empty ranges name the line before them; others their first line (one-based)
+    let start = |range: &Range<u32>| if range.is_empty() { range.start } else { range.start + 1 };
+    let mut patch = format!(
+        "--- a/{name}\n+++ b/{name}\n@@ -{},{} +{},{} @@\n",
+        start(&hunk.head_lines),
+        hunk.head_lines.len(),
+        start(&hunk.lines),
+        hunk.lines.len(),
+        name = file_name,
+    );
+    for (prefix, text) in [('-', &hunk.head_text), ('+', &hunk.text)] {
+        for line in text.split_inclusive('\n') {
+            patch.push(prefix);
+            patch.push_str(line);
+            if !line.ends_with('\n') {
+                patch.push_str("\n\\ No newline at end of file\n");
+            }
+        }
+    }
+    patch
+}
+
+/// Text of `path` as committed in `HEAD`; `None` when it isn't in a commit yet
+pub fn head_text(path: &Path) -> Option<String> {
+    let file_name = path.file_name()?.to_str()?;
+    let output = Command::new("git")
+        .current_dir(path.parent()?)
+        .arg("show")
+        .arg(format!("HEAD:./{}", file_name))
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Stage just `hunk` of `path`, leaving the rest of its changes unstaged
+pub fn stage_hunk(path: &Path, hunk: &DiffHunk) -> Result<(), String> {
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| "Not a file".to_string())?;
+    let parent = path.parent().ok_or_else(|| "Not a file".to_string())?;
+    let mut child = Command::new("git")
+        .current_dir(parent)
+        .args(["apply", "--cached", "--unidiff-zero", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+    child
+        .stdin
+        .take()
+        .ok_or_else(|| "git closed its input".to_string())?
+        .write_all(hunk_patch(file_name, hunk).as_bytes())
+        .map_err(|e| e.to_string())?;
+    let output = child.wait_with_output().map_err(|e| e.to_string())?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hunk_kinds() {
+        let head = "one\ntwo\nthree\nfour\n";
+        let current = "zero\none\n2\nthree\n";
+        let hunks = diff_hunks(head, current);
+
+        assert_eq!(hunks.len(), 3);
+        assert_eq!(hunks[0].kind, HunkKind::Added);
+        assert_eq!(hunks[0].lines, 0..1);
+        assert_eq!(hunks[1].kind, HunkKind::Modified);
+        assert_eq!(hunks[1].lines, 2..3);
+        assert_eq!(hunks[1].head_text, "two\n");
+        assert_eq!(hunks[2].kind, HunkKind::Deleted);
+        assert_eq!(hunks[2].lines, 4..4);
+        assert_eq!(hunks[2].head_lines, 3..4);
+    }
+
+    #[test]
+    fn test_revert_and_patch() {
+        let head = "a\nb\nc\n";
+        let current = "a\nB\nc\n";
+        let hunk = &diff_hunks(head, current)[0];
+
+        assert_eq!(revert_hunk(current, hunk).as_deref(), Some(head));
+        // Lines edited since the diff was taken are left alone
+        assert_eq!(revert_hunk("a\nBB\nc\n", hunk), None);
+        assert_eq!(hunk_patch("f.txt", hunk), "--- a/f.txt\n+++ b/f.txt\n@@ -2,1 +2,1 @@\n-b\n+B\n");
+
+        let added = &diff_hunks("a\n", "a\nb")[0];
+        assert_eq!(
+            hunk_patch("f.txt", added),
+            "--- a/f.txt\n+++ b/f.txt\n@@ -1,0 +2,1 @@\n+b\n\\ No newline at end of file\n"
+        );
+    }
+}
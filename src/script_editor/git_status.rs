@@ -0,0 +1,157 @@
+//! Git status of the project's files, read with `git status --porcelain`
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Status of a file, or the most pressing status among a folder's files.
+///
+/// Ordered so that the greater status wins when summarizing a folder.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum GitFileStatus {
+    Ignored,
+    Untracked,
+    /// Changes are in the index and the worktree matches it
+    Staged,
+    Modified,
+    Conflicted,
+}
+
+impl GitFileStatus {
+    /// Letter shown next to the file name
+    pub fn badge(self) -> Option<&'static str> {
+        match self {
+            GitFileStatus::Ignored => None,
+            GitFileStatus::Untracked => Some("U"),
+            GitFileStatus::Staged => Some("S"),
+            GitFileStatus::Modified => Some("M"),
+            GitFileStatus::Conflicted => Some("C"),
+        }
+    }
+
+    /// Status from the two-letter `XY` code of a porcelain line
+    fn from_code(code: &str) -> Option<Self> {
+        let mut chars = code.chars();
+        let (index, worktree) = (chars.next()?, chars.next()?);
+        Some(match (index, worktree) {
+            ('?', '?') => GitFileStatus::Untracked,
+            ('!', '!') => GitFileStatus::Ignored,
+            ('U', _) | (_, 'U') | ('A', 'A') | ('D', 'D') => GitFileStatus::Conflicted,
+            (_, ' ') => GitFileStatus::Staged,
+            _ => GitFileStatus::Modified,
+        })
+    }
+}
+
+/// Statuses of files and summarized statuses of the folders holding them
+#[derive(Clone, Debug, Default)]
+pub struct GitStatusMap {
+    files: HashMap<PathBuf, GitFileStatus>,
+    folders: HashMap<PathBuf, GitFileStatus>,
+    /// Ignored folders, everything below which is ignored too
+    ignored_dirs: Vec<PathBuf>,
+}
+
+impl GitStatusMap {
+    /// Status shown for `path`; `None` for clean files and folders
+    pub fn status(&self, path: &Path) -> Option<GitFileStatus> {
+        if let Some(status) = self.files.get(path).or_else(|| self.folders.get(path)) {
+            return Some(*status);
+        }
+        self.ignored_dirs
+            .iter()
+            .any(|dir| path.starts_with(dir))
+            .then_some(GitFileStatus::Ignored)
+    }
+}
+
+/// Parse `git status --porcelain=v1 -z --ignored` output, whose paths are relative to `repo_root`
+pub fn parse_porcelain(output: &str, repo_root: &Path) -> GitStatusMap {
+    let mut map = GitStatusMap::default();
+    let mut records = output.split('\0');
+    while let Some(record) = records.next() {
+        if record.len() < 4 {
+            continue;
+        }
+        let (code, relative) = (&record[..2], &record[3..]);
+        // Renames and copies are followed by the original path
+        if code.starts_with(['R', 'C']) {
+            records.next();
+        }
+        let Some(status) = GitFileStatus::from_code(code) else {
+            continue;
+        };
+
+        if status == GitFileStatus::Ignored && relative.ends_with('/') {
+            map.ignored_dirs.push(repo_root.join(relative.trim_end_matches('/')));
+            continue;
+        }
+        // Untracked folders are listed as a whole (`?? new_dir/`)
+        let path = repo_root.join(relative.trim_end_matches('/'));
+        if status != GitFileStatus::Ignored {
+            for folder in path.ancestors().skip(1) {
+                if !folder.starts_with(repo_root) {
+                    break;
+                }
+                let summary = map.folders.entry(folder.to_path_buf()).or_insert(status);
+                *summary = (*summary).max(status);
+            }
+        }
+        map.files.insert(path, status);
+    }
+    map
+}
+
+/// Read the status of the repository containing `project_root`.
+///
+/// `None` when the project is not in a git repository or git is not installed.
+pub fn load_git_status(project_root: &Path) -> Option<GitStatusMap> {
+    let toplevel = Command::new("git")
+        .arg("-C")
+        .arg(project_root)
+        .args(["rev-parse", "--show-toplevel"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    let repo_root = PathBuf::from(String::from_utf8_lossy(&toplevel.stdout).trim());
+
+    let status = Command::new("git")
+        .arg("-C")
+        .arg(&repo_root)
+        .args(["status", "--porcelain=v1", "-z", "--ignored=matching", "--untracked-files=normal"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    Some(parse_porcelain(&String::from_utf8_lossy(&status.stdout), &repo_root))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_porcelain() {
+        let root = Path::new("/repo");
+        let output = " M src/main.rs\0M  src/lib.rs\0?? notes.txt\0R  src/new.rs\0src/old.rs\0UU src/conflict.rs\0!! build/\0";
+        let map = parse_porcelain(output, root);
+
+        assert_eq!(map.status(Path::new("/repo/src/main.rs")), Some(GitFileStatus::Modified));
+        assert_eq!(map.status(Path::new("/repo/src/lib.rs")), Some(GitFileStatus::Staged));
+        assert_eq!(map.status(Path::new("/repo/notes.txt")), Some(GitFileStatus::Untracked));
+        assert_eq!(map.status(Path::new("/repo/src/new.rs")), Some(GitFileStatus::Staged));
+        assert_eq!(map.status(Path::new("/repo/src/old.rs")), None);
+        assert_eq!(map.status(Path::new("/repo/build/out.o")), Some(GitFileStatus::Ignored));
+        assert_eq!(map.status(Path::new("/repo/README.md")), None);
+    }
+
+    #[test]
+    fn test_folders_summarize_children() {
+        let root = Path::new("/repo");
+        let map = parse_porcelain("?? src/a/new.rs\0 M src/b.rs\0!! src/cache.tmp\0", root);
+        assert_eq!(map.status(Path::new("/repo/src")), Some(GitFileStatus::Modified));
+        assert_eq!(map.status(Path::new("/repo/src/a")), Some(GitFileStatus::Untracked));
+        // Ignored files don't color their folder
+        let map = parse_porcelain("!! docs/draft.md\0", root);
+        assert_eq!(map.status(Path::new("/repo/docs")), None);
+    }
+}
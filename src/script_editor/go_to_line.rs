@@ -0,0 +1,86 @@
+//! Parsing "Go to Line" input (`line` or `line:column`)
+
+/// A location as typed: 1-based line and optional 1-based column
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LineTarget {
+    pub line: u32,
+    pub column: Option<u32>,
+}
+
+/// Parse `line` or `line:column`; `Ok(None)` while the input is empty
+pub fn parse_line_target(input: &str) -> Result<Option<LineTarget>, &'static str> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Ok(None);
+    }
+    let (line, column) = match input.split_once(':') {
+        Some((line, column)) => (line.trim(), Some(column.trim())),
+        None => (input, None),
+    };
+    let line = parse_number(line)?;
+    let column = match column {
+        // `12:` while the column is still being typed
+        Some("") | None => None,
+        Some(column) => Some(parse_number(column)?),
+    };
+    Ok(Some(LineTarget { line, column }))
+}
+
+fn parse_number(text: &str) -> Result<u32, &'static str> {
+    if text.starts_with('-') {
+        return Err("Line and column numbers can't be negative");
+    }
+    if text.is_empty() || !text.chars().all(|ch| ch.is_ascii_digit()) {
+        return Err("Type a line number, or line:column");
+    }
+    // Only digits, so the parse can only fail by overflowing; that clamps later anyway
+    Ok(text.parse().unwrap_or(u32::MAX))
+}
+
+/// Zero-based line and UTF-16 column of `target` in `text`, clamped to the
+/// lines that exist and to the end of the target line
+pub fn clamp_to_text(target: LineTarget, text: &str) -> (u32, u32) {
+    let line_count = text.split('\n').count() as u32;
+    let line = target.line.clamp(1, line_count) - 1;
+    let line_text = text.split('\n').nth(line as usize).unwrap_or_default();
+    let line_text = line_text.strip_suffix('\r').unwrap_or(line_text);
+    let line_len = line_text.encode_utf16().count() as u32;
+    let column = target.column.unwrap_or(1).clamp(1, line_len + 1) - 1;
+    (line, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_line_target() {
+        assert_eq!(parse_line_target(""), Ok(None));
+        assert_eq!(
+            parse_line_target(" 12 "),
+            Ok(Some(LineTarget { line: 12, column: None }))
+        );
+        assert_eq!(
+            parse_line_target("12:5"),
+            Ok(Some(LineTarget { line: 12, column: Some(5) }))
+        );
+        assert_eq!(
+            parse_line_target("12:"),
+            Ok(Some(LineTarget { line: 12, column: None }))
+        );
+        assert!(parse_line_target("-3").is_err());
+        assert!(parse_line_target("4:-1").is_err());
+        assert!(parse_line_target("abc").is_err());
+    }
+
+    #[test]
+    fn test_clamp_to_text() {
+        let text = "first\nsecond line\r\nthird";
+        let target = |line, column| LineTarget { line, column };
+        assert_eq!(clamp_to_text(target(2, Some(3)), text), (1, 2));
+        // Past the end of the file and of the line
+        assert_eq!(clamp_to_text(target(99, Some(99)), text), (2, 5));
+        assert_eq!(clamp_to_text(target(0, None), text), (0, 0));
+        assert_eq!(clamp_to_text(target(2, Some(50)), text), (1, 11));
+    }
+}
@@ -0,0 +1,244 @@
+//! Files that aren't text, shown read-only as their bytes: an offset, hex
+//! and ASCII column for every 16 of them
+
+use gpui::{prelude::FluentBuilder, *};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use ui::{
+    button::{Button, ButtonVariants as _},
+    h_flex, v_flex, ActiveTheme as _, IconName, Sizable as _,
+};
+
+pub const BYTES_PER_ROW: usize = 16;
+
+/// Rows Page Up and Page Down move by
+const PAGE_ROWS: usize = 32;
+
+/// Offset of a row's first byte, as the offset column shows it
+pub fn format_offset(offset: usize) -> String {
+    format!("{:08x}", offset)
+}
+
+/// How a byte shows in the ASCII column: printable ASCII as itself, anything else as `.`
+pub fn ascii_char(byte: u8) -> char {
+    if byte.is_ascii_graphic() || byte == b' ' {
+        byte as char
+    } else {
+        '.'
+    }
+}
+
+/// Bytes of row `row`, which runs short at the end of the file
+pub fn row_bytes(bytes: &[u8], row: usize) -> &[u8] {
+    let start = (row * BYTES_PER_ROW).min(bytes.len());
+    &bytes[start..(start + BYTES_PER_ROW).min(bytes.len())]
+}
+
+pub fn row_count(len: usize) -> usize {
+    len.div_ceil(BYTES_PER_ROW).max(1)
+}
+
+/// Where a key moves the selected byte from `offset`, in a file of `len` bytes
+pub fn move_offset(offset: usize, len: usize, key: &str, to_edge: bool) -> Option<usize> {
+    let last = len.saturating_sub(1);
+    let row_start = offset - offset % BYTES_PER_ROW;
+    let target = match key {
+        "left" => offset.saturating_sub(1),
+        "right" => offset + 1,
+        "up" => offset.saturating_sub(BYTES_PER_ROW),
+        "down" => offset + BYTES_PER_ROW,
+        "pageup" => offset.saturating_sub(BYTES_PER_ROW * PAGE_ROWS),
+        "pagedown" => offset + BYTES_PER_ROW * PAGE_ROWS,
+        "home" if to_edge => 0,
+        "end" if to_edge => last,
+        "home" => row_start,
+        "end" => row_start + BYTES_PER_ROW - 1,
+        _ => return None,
+    };
+    Some(target.min(last))
+}
+
+pub enum HexViewEvent {
+    Close,
+}
+
+pub struct HexView {
+    focus_handle: FocusHandle,
+    path: PathBuf,
+    bytes: Vec<u8>,
+    /// Offset of the selected byte
+    selected: usize,
+    scroll_handle: UniformListScrollHandle,
+}
+
+impl EventEmitter<HexViewEvent> for HexView {}
+
+impl HexView {
+    pub fn new(path: PathBuf, bytes: Vec<u8>, cx: &mut Context<Self>) -> Self {
+        Self {
+            focus_handle: cx.focus_handle(),
+            path,
+            bytes,
+            selected: 0,
+            scroll_handle: UniformListScrollHandle::new(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn select(&mut self, offset: usize, cx: &mut Context<Self>) {
+        self.selected = offset;
+        self.scroll_handle.scroll_to_item(offset / BYTES_PER_ROW, ScrollStrategy::Top);
+        cx.notify();
+    }
+
+    fn render_rows(&mut self, range: Range<usize>, cx: &mut Context<Self>) -> Vec<AnyElement> {
+        range
+            .map(|row| {
+                let start = row * BYTES_PER_ROW;
+                let bytes = row_bytes(&self.bytes, row);
+                let cell = |offset: usize, text: String, cx: &mut Context<Self>| {
+                    div()
+                        .id(("hex-byte", offset))
+                        .cursor_pointer()
+                        .when(offset == self.selected, |cell| {
+                            cell.bg(cx.theme().accent.opacity(0.3)).text_color(cx.theme().foreground)
+                        })
+                        .child(text)
+                        .on_click(cx.listener(move |this, _, _window, cx| this.select(offset, cx)))
+                };
+                let hex: Vec<_> = bytes
+                    .iter()
+                    .enumerate()
+                    // A gap between the two halves of the row
+                    .map(|(ix, byte)| cell(start + ix, format!("{:02x}", byte), cx).when(ix == BYTES_PER_ROW / 2, |cell| cell.ml_2()))
+                    .collect();
+                let ascii: Vec<_> = bytes
+                    .iter()
+                    .enumerate()
+                    .map(|(ix, &byte)| cell(start + ix, ascii_char(byte).to_string(), cx))
+                    .collect();
+                h_flex()
+                    .id(("hex-row", row))
+                    .h(px(20.))
+                    .px_3()
+                    .gap_4()
+                    .font_family("JetBrains Mono")
+                    .text_sm()
+                    .text_color(cx.theme().muted_foreground)
+                    .child(div().w(px(72.)).child(format_offset(start)))
+                    .child(h_flex().w(px(420.)).gap_1().children(hex))
+                    .child(h_flex().children(ascii))
+                    .into_any_element()
+            })
+            .collect()
+    }
+}
+
+impl Focusable for HexView {
+    fn focus_handle(&self, _cx: &App) -> FocusHandle {
+        self.focus_handle.clone()
+    }
+}
+
+impl Render for HexView {
+    fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let len = self.bytes.len();
+        let selected = if len == 0 {
+            "Empty file".to_string()
+        } else {
+            format!("Offset 0x{:x} ({}) = 0x{:02x}", self.selected, self.selected, self.bytes[self.selected])
+        };
+        v_flex()
+            .size_full()
+            .bg(cx.theme().background)
+            .track_focus(&self.focus_handle)
+            .on_key_down(cx.listener(|this, event: &KeyDownEvent, _window, cx| {
+                let keystroke = &event.keystroke;
+                if keystroke.key == "escape" {
+                    cx.emit(HexViewEvent::Close);
+                    return;
+                }
+                if let Some(offset) =
+                    move_offset(this.selected, this.bytes.len(), &keystroke.key, keystroke.modifiers.secondary())
+                {
+                    this.select(offset, cx);
+                    cx.stop_propagation();
+                }
+            }))
+            .child(
+                h_flex()
+                    .w_full()
+                    .px_3()
+                    .py_1()
+                    .gap_4()
+                    .border_b_1()
+                    .border_color(cx.theme().border)
+                    .text_xs()
+                    .text_color(cx.theme().muted_foreground)
+                    .child(
+                        div()
+                            .flex_1()
+                            .overflow_hidden()
+                            .text_ellipsis()
+                            .text_color(cx.theme().foreground)
+                            .child(format!("{} (binary, read-only)", self.path.display())),
+                    )
+                    .child(selected)
+                    .child(match len {
+                        1 => "1 byte".to_string(),
+                        len => format!("{} bytes", len),
+                    })
+                    .child(
+                        Button::new("close_hex_view")
+                            .icon(IconName::Close)
+                            .tooltip("Close")
+                            .ghost()
+                            .xsmall()
+                            .on_click(cx.listener(|_this, _, _window, cx| cx.emit(HexViewEvent::Close))),
+                    ),
+            )
+            .child(
+                div().flex_1().min_h_0().py_1().child(
+                    uniform_list(
+                        "hex-rows",
+                        row_count(len),
+                        cx.processor(|this, range: Range<usize>, _window, cx| this.render_rows(range, cx)),
+                    )
+                    .track_scroll(self.scroll_handle.clone())
+                    .size_full(),
+                ),
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rows() {
+        let bytes: Vec<u8> = (0..40).collect();
+        assert_eq!(row_count(bytes.len()), 3);
+        assert_eq!(row_count(0), 1);
+        assert_eq!(row_bytes(&bytes, 2), &bytes[32..40]);
+        assert!(row_bytes(&bytes, 3).is_empty());
+        assert_eq!(format_offset(32), "00000020");
+        let ascii: String = b"Hi\0\x7f\xff ~".iter().map(|&byte| ascii_char(byte)).collect();
+        assert_eq!(ascii, "Hi... ~");
+    }
+
+    #[test]
+    fn test_move_offset() {
+        assert_eq!(move_offset(20, 40, "down", false), Some(36));
+        assert_eq!(move_offset(36, 40, "down", false), Some(39));
+        assert_eq!(move_offset(5, 40, "up", false), Some(0));
+        assert_eq!(move_offset(20, 40, "home", false), Some(16));
+        assert_eq!(move_offset(20, 40, "end", false), Some(31));
+        assert_eq!(move_offset(20, 40, "end", true), Some(39));
+        assert_eq!(move_offset(0, 0, "right", false), Some(0));
+        assert_eq!(move_offset(3, 40, "a", false), None);
+    }
+}
@@ -0,0 +1,633 @@
+//! Call and type hierarchies: who calls a function or what it calls, a
+//! type's supertypes or subtypes, as a tree expanded one level at a time
+//!
+//! Each item's children are asked for once and kept, so it expands at once
+//! wherever else it turns up. An item already above itself, like a function
+//! calling itself, is shown but not expanded again.
+
+use gpui::{prelude::FluentBuilder, *};
+use lsp_types::{CallHierarchyItem, Range as LspRange, SymbolKind, TypeHierarchyItem, Uri};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::ops::Range;
+use ui::{
+    button::{Button, ButtonVariants as _},
+    h_flex, v_flex, ActiveTheme as _, Icon, IconName, Sizable as _, StyledExt,
+};
+
+use super::lsp::uri_to_path;
+use super::outline_panel::kind_glyph;
+use super::location::Location;
+
+/// What a hierarchy is made of
+pub trait HierarchyItem: Clone + 'static {
+    /// Panel title
+    const TITLE: &'static str;
+    /// Shown while there's nothing in the panel
+    const HINT: &'static str;
+
+    fn name(&self) -> &str;
+    fn kind(&self) -> SymbolKind;
+    fn detail(&self) -> Option<&str>;
+    fn uri(&self) -> &Uri;
+    fn selection_range(&self) -> LspRange;
+}
+
+impl HierarchyItem for CallHierarchyItem {
+    const TITLE: &'static str = "Calls";
+    const HINT: &'static str = "Press Shift+Alt+H on a function to see its calls";
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn kind(&self) -> SymbolKind {
+        self.kind
+    }
+    fn detail(&self) -> Option<&str> {
+        self.detail.as_deref()
+    }
+    fn uri(&self) -> &Uri {
+        &self.uri
+    }
+    fn selection_range(&self) -> LspRange {
+        self.selection_range
+    }
+}
+
+impl HierarchyItem for TypeHierarchyItem {
+    const TITLE: &'static str = "Types";
+    const HINT: &'static str = "Press Shift+Alt+T on a type to see its supertypes and subtypes";
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn kind(&self) -> SymbolKind {
+        self.kind
+    }
+    fn detail(&self) -> Option<&str> {
+        self.detail.as_deref()
+    }
+    fn uri(&self) -> &Uri {
+        &self.uri
+    }
+    fn selection_range(&self) -> LspRange {
+        self.selection_range
+    }
+}
+
+/// Which way the tree grows from its roots
+pub trait HierarchyDirection: Copy + Eq + Hash + 'static {
+    /// Both ways, the first shown by default
+    const ALL: [Self; 2];
+
+    fn label(self) -> &'static str;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CallDirection {
+    /// Functions calling the one above them
+    Incoming,
+    /// Functions the one above them calls
+    Outgoing,
+}
+
+impl HierarchyDirection for CallDirection {
+    const ALL: [Self; 2] = [Self::Incoming, Self::Outgoing];
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Incoming => "Incoming",
+            Self::Outgoing => "Outgoing",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum TypeDirection {
+    /// Types, or traits, the one above them extends or implements
+    Supertypes,
+    /// Types extending or implementing the one above them
+    Subtypes,
+}
+
+impl HierarchyDirection for TypeDirection {
+    const ALL: [Self; 2] = [Self::Supertypes, Self::Subtypes];
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Supertypes => "Supertypes",
+            Self::Subtypes => "Subtypes",
+        }
+    }
+}
+
+/// A child of its parent in the tree
+#[derive(Clone, Debug)]
+pub struct HierarchyEntry<I> {
+    pub item: I,
+    /// What ties it to its parent, like the calls between them, and the file
+    /// those are in
+    pub sites: Option<(Uri, Vec<LspRange>)>,
+}
+
+impl<I> HierarchyEntry<I> {
+    pub fn new(item: I) -> Self {
+        Self { item, sites: None }
+    }
+}
+
+pub type CallEntry = HierarchyEntry<CallHierarchyItem>;
+
+/// What identifies an item across requests
+type ItemKey = (String, u32, u32);
+
+fn item_key<I: HierarchyItem>(item: &I) -> ItemKey {
+    let start = item.selection_range().start;
+    (item.uri().as_str().to_string(), start.line, start.character)
+}
+
+#[derive(Clone, Debug)]
+struct HierarchyNode<I> {
+    entry: HierarchyEntry<I>,
+    depth: usize,
+    parent: Option<usize>,
+    /// `None` until its children are in
+    children: Option<Vec<usize>>,
+    expanded: bool,
+    /// Already above itself in the tree
+    recursive: bool,
+}
+
+/// The tree, apart from drawing it
+#[derive(Clone, Debug)]
+pub struct HierarchyTree<I, D> {
+    direction: D,
+    roots: Vec<I>,
+    nodes: Vec<HierarchyNode<I>>,
+    /// Children asked for so far, by direction and item
+    cache: HashMap<(D, ItemKey), Vec<HierarchyEntry<I>>>,
+}
+
+impl<I: HierarchyItem, D: HierarchyDirection> HierarchyTree<I, D> {
+    pub fn new(direction: D) -> Self {
+        Self {
+            direction,
+            roots: Vec::new(),
+            nodes: Vec::new(),
+            cache: HashMap::new(),
+        }
+    }
+
+    pub fn direction(&self) -> D {
+        self.direction
+    }
+
+    /// Start over from `roots`, forgetting children found for other ones,
+    /// which may have changed since
+    pub fn set_roots(&mut self, roots: Vec<I>) {
+        self.roots = roots;
+        self.cache.clear();
+        self.rebuild();
+    }
+
+    /// Grow the tree the other way, from the same roots
+    pub fn set_direction(&mut self, direction: D) {
+        self.direction = direction;
+        self.rebuild();
+    }
+
+    fn rebuild(&mut self) {
+        self.nodes = self
+            .roots
+            .iter()
+            .map(|item| HierarchyNode {
+                entry: HierarchyEntry::new(item.clone()),
+                depth: 0,
+                parent: None,
+                children: None,
+                expanded: false,
+                recursive: false,
+            })
+            .collect();
+    }
+
+    /// Expand or collapse `node`; the item it needs the children of, when
+    /// they have to be asked for
+    pub fn toggle(&mut self, node: usize) -> Option<I> {
+        let tree_node = self.nodes.get_mut(node).filter(|node| !node.recursive)?;
+        tree_node.expanded = !tree_node.expanded;
+        if !tree_node.expanded || tree_node.children.is_some() {
+            return None;
+        }
+        let item = tree_node.entry.item.clone();
+        match self.cache.get(&(self.direction, item_key(&item))).cloned() {
+            Some(children) => {
+                self.add_children(node, children);
+                None
+            }
+            None => Some(item),
+        }
+    }
+
+    /// The children of the item at `node`, as the server found them
+    pub fn set_children(&mut self, node: usize, children: Vec<HierarchyEntry<I>>) {
+        let Some(tree_node) = self.nodes.get(node) else {
+            return;
+        };
+        self.cache
+            .insert((self.direction, item_key(&tree_node.entry.item)), children.clone());
+        self.add_children(node, children);
+    }
+
+    fn add_children(&mut self, node: usize, entries: Vec<HierarchyEntry<I>>) {
+        let mut above = Vec::new();
+        let mut ancestor = Some(node);
+        while let Some(ix) = ancestor {
+            above.push(item_key(&self.nodes[ix].entry.item));
+            ancestor = self.nodes[ix].parent;
+        }
+        let depth = self.nodes[node].depth + 1;
+        let mut children = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let recursive = above.contains(&item_key(&entry.item));
+            children.push(self.nodes.len());
+            self.nodes.push(HierarchyNode {
+                entry,
+                depth,
+                parent: Some(node),
+                children: None,
+                expanded: false,
+                recursive,
+            });
+        }
+        self.nodes[node].children = Some(children);
+    }
+
+    /// Nodes shown, in order: the roots and the children of expanded ones
+    pub fn rows(&self) -> Vec<usize> {
+        let mut rows = Vec::new();
+        let mut stack: Vec<usize> = (0..self.roots.len()).rev().collect();
+        while let Some(node) = stack.pop() {
+            rows.push(node);
+            let tree_node = &self.nodes[node];
+            if let (true, Some(children)) = (tree_node.expanded, &tree_node.children) {
+                stack.extend(children.iter().rev());
+            }
+        }
+        rows
+    }
+
+    /// Where clicking `node` goes: its first site, or the item itself without one
+    pub fn location(&self, node: usize) -> Option<Location> {
+        let entry = &self.nodes.get(node)?.entry;
+        let (uri, position) = match &entry.sites {
+            Some((uri, sites)) if !sites.is_empty() => (uri, sites[0].start),
+            _ => (entry.item.uri(), entry.item.selection_range().start),
+        };
+        Some(Location {
+            path: uri_to_path(uri)?,
+            line: position.line,
+            character: position.character,
+        })
+    }
+}
+
+pub enum HierarchyPanelEvent<I, D> {
+    OpenLocation(Location),
+    /// The children of `item` are wanted, in `direction`, to expand `node`;
+    /// answered with `set_children` for the same `generation`
+    FetchChildren {
+        generation: usize,
+        node: usize,
+        item: I,
+        direction: D,
+    },
+}
+
+pub struct HierarchyPanel<I, D> {
+    focus_handle: FocusHandle,
+    tree: HierarchyTree<I, D>,
+    /// Counts trees shown, so children asked for by an earlier one are dropped
+    generation: usize,
+    /// Nodes whose children are on their way
+    loading: Vec<usize>,
+    is_loading: bool,
+    scroll_handle: UniformListScrollHandle,
+}
+
+pub type CallHierarchyPanel = HierarchyPanel<CallHierarchyItem, CallDirection>;
+pub type CallHierarchyPanelEvent = HierarchyPanelEvent<CallHierarchyItem, CallDirection>;
+pub type TypeHierarchyPanel = HierarchyPanel<TypeHierarchyItem, TypeDirection>;
+pub type TypeHierarchyPanelEvent = HierarchyPanelEvent<TypeHierarchyItem, TypeDirection>;
+
+impl<I: HierarchyItem, D: HierarchyDirection> EventEmitter<HierarchyPanelEvent<I, D>> for HierarchyPanel<I, D> {}
+
+impl<I: HierarchyItem, D: HierarchyDirection> HierarchyPanel<I, D> {
+    pub fn new(cx: &mut Context<Self>) -> Self {
+        Self {
+            focus_handle: cx.focus_handle(),
+            tree: HierarchyTree::new(D::ALL[0]),
+            generation: 0,
+            loading: Vec::new(),
+            is_loading: false,
+            scroll_handle: UniformListScrollHandle::new(),
+        }
+    }
+
+    /// Clear the tree while the item under the cursor is looked up
+    pub fn begin(&mut self, cx: &mut Context<Self>) {
+        self.generation += 1;
+        self.tree.set_roots(Vec::new());
+        self.loading.clear();
+        self.is_loading = true;
+        cx.notify();
+    }
+
+    /// Show the hierarchy around `roots`, with the first expanded
+    pub fn set_roots(&mut self, roots: Vec<I>, cx: &mut Context<Self>) {
+        self.is_loading = false;
+        self.tree.set_roots(roots);
+        self.scroll_handle.scroll_to_item(0, ScrollStrategy::Top);
+        if !self.tree.roots.is_empty() {
+            self.toggle(0, cx);
+        }
+        cx.notify();
+    }
+
+    pub fn set_children(
+        &mut self,
+        generation: usize,
+        node: usize,
+        children: Vec<HierarchyEntry<I>>,
+        cx: &mut Context<Self>,
+    ) {
+        if generation != self.generation {
+            return;
+        }
+        self.loading.retain(|loading| *loading != node);
+        self.tree.set_children(node, children);
+        cx.notify();
+    }
+
+    fn set_direction(&mut self, direction: D, cx: &mut Context<Self>) {
+        if self.tree.direction() == direction {
+            return;
+        }
+        self.generation += 1;
+        self.loading.clear();
+        self.tree.set_direction(direction);
+        if !self.tree.roots.is_empty() {
+            self.toggle(0, cx);
+        }
+        cx.notify();
+    }
+
+    fn toggle(&mut self, node: usize, cx: &mut Context<Self>) {
+        if let Some(item) = self.tree.toggle(node) {
+            self.loading.push(node);
+            cx.emit(HierarchyPanelEvent::FetchChildren {
+                generation: self.generation,
+                node,
+                item,
+                direction: self.tree.direction(),
+            });
+        }
+        cx.notify();
+    }
+
+    fn render_rows(&mut self, range: Range<usize>, cx: &mut Context<Self>) -> Vec<AnyElement> {
+        let rows = self.tree.rows();
+        range
+            .filter_map(|row_ix| {
+                let node = *rows.get(row_ix)?;
+                Some(self.render_row(row_ix, node, cx))
+            })
+            .collect()
+    }
+
+    fn render_row(&self, row_ix: usize, node: usize, cx: &mut Context<Self>) -> AnyElement {
+        let tree_node = &self.tree.nodes[node];
+        let item = &tree_node.entry.item;
+        let (glyph, _) = kind_glyph(item.kind());
+        // An item known to have no children has nothing to open
+        let leaf = tree_node.recursive || tree_node.children.as_ref().is_some_and(Vec::is_empty);
+        let loading = self.loading.contains(&node);
+        let file = uri_to_path(item.uri())
+            .and_then(|path| path.file_name().map(|name| name.to_string_lossy().into_owned()))
+            .unwrap_or_default();
+        let sites = tree_node.entry.sites.as_ref().map_or(0, |(_, sites)| sites.len());
+
+        h_flex()
+            .id(("hierarchy-row", row_ix))
+            .h(px(24.))
+            .pl(px(8. + tree_node.depth as f32 * 14.))
+            .pr_2()
+            .gap_1()
+            .cursor_pointer()
+            .hover(|style| style.bg(cx.theme().accent.opacity(0.1)))
+            .child(
+                div()
+                    .id(("hierarchy-toggle", row_ix))
+                    .w(px(14.))
+                    .when(!leaf, |toggle| {
+                        toggle
+                            .child(
+                                Icon::new(if tree_node.expanded { IconName::ChevronDown } else { IconName::ChevronRight })
+                                    .size_3()
+                                    .text_color(cx.theme().muted_foreground),
+                            )
+                            .on_click(cx.listener(move |this, _, _window, cx| {
+                                cx.stop_propagation();
+                                this.toggle(node, cx);
+                            }))
+                    }),
+            )
+            .child(div().text_xs().text_color(cx.theme().muted_foreground).child(glyph))
+            .child(
+                div()
+                    .overflow_hidden()
+                    .whitespace_nowrap()
+                    .text_ellipsis()
+                    .text_xs()
+                    .font_family("JetBrains Mono")
+                    .text_color(cx.theme().foreground)
+                    .child(item.name().to_string()),
+            )
+            .when(tree_node.recursive, |row| {
+                row.child(div().text_xs().text_color(cx.theme().muted_foreground).child("↻ recursive"))
+            })
+            .child(
+                div()
+                    .flex_1()
+                    .overflow_hidden()
+                    .whitespace_nowrap()
+                    .text_ellipsis()
+                    .text_xs()
+                    .text_color(cx.theme().muted_foreground)
+                    .child(format!("{} {}", file, item.detail().unwrap_or_default())),
+            )
+            .when(sites > 1, |row| {
+                row.child(div().text_xs().text_color(cx.theme().muted_foreground).child(format!("×{}", sites)))
+            })
+            .when(loading, |row| {
+                row.child(div().text_xs().text_color(cx.theme().muted_foreground).child("…"))
+            })
+            .on_click(cx.listener(move |this, _, _window, cx| {
+                if let Some(location) = this.tree.location(node) {
+                    cx.emit(HierarchyPanelEvent::OpenLocation(location));
+                }
+            }))
+            .into_any_element()
+    }
+
+    fn render_direction(&self, ix: usize, direction: D, cx: &mut Context<Self>) -> impl IntoElement {
+        Button::new(("hierarchy-direction", ix))
+            .label(direction.label())
+            .ghost()
+            .xsmall()
+            .selected(self.tree.direction() == direction)
+            .on_click(cx.listener(move |this, _, _window, cx| this.set_direction(direction, cx)))
+    }
+}
+
+impl<I: HierarchyItem, D: HierarchyDirection> Focusable for HierarchyPanel<I, D> {
+    fn focus_handle(&self, _cx: &App) -> FocusHandle {
+        self.focus_handle.clone()
+    }
+}
+
+impl<I: HierarchyItem, D: HierarchyDirection> Render for HierarchyPanel<I, D> {
+    fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let row_count = self.tree.rows().len();
+        let title = match self.tree.roots.first() {
+            Some(root) => format!("{}: {}", I::TITLE, root.name()),
+            None => I::TITLE.to_string(),
+        };
+
+        v_flex()
+            .size_full()
+            .track_focus(&self.focus_handle)
+            .child(
+                // Header
+                v_flex()
+                    .w_full()
+                    .px_4()
+                    .py_3()
+                    .gap_1()
+                    .border_b_1()
+                    .border_color(cx.theme().border)
+                    .child(div().text_sm().font_semibold().text_color(cx.theme().foreground).child(title))
+                    .child(
+                        h_flex()
+                            .gap_1()
+                            .children(D::ALL.into_iter().enumerate().map(|(ix, direction)| self.render_direction(ix, direction, cx))),
+                    ),
+            )
+            .child(
+                div()
+                    .flex_1()
+                    .min_h_0()
+                    .when(row_count == 0, |content| {
+                        content.child(
+                            div()
+                                .p_4()
+                                .text_xs()
+                                .text_color(cx.theme().muted_foreground)
+                                .child(if self.is_loading { "Looking it up..." } else { I::HINT }),
+                        )
+                    })
+                    .when(row_count > 0, |content| {
+                        content.child(
+                            uniform_list(
+                                "hierarchy-list",
+                                row_count,
+                                cx.processor(|this, range: Range<usize>, _window, cx| this.render_rows(range, cx)),
+                            )
+                            .track_scroll(self.scroll_handle.clone())
+                            .size_full(),
+                        )
+                    }),
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::Position;
+    use std::path::PathBuf;
+    use std::str::FromStr;
+
+    fn item(name: &str, line: u32) -> CallHierarchyItem {
+        let range = LspRange::new(Position::new(line, 3), Position::new(line, 3 + name.len() as u32));
+        CallHierarchyItem {
+            name: name.to_string(),
+            kind: SymbolKind::FUNCTION,
+            tags: None,
+            detail: None,
+            uri: Uri::from_str("file:///project/src/lib.rs").unwrap(),
+            range,
+            selection_range: range,
+            data: None,
+        }
+    }
+
+    fn calls(items: &[CallHierarchyItem]) -> Vec<CallEntry> {
+        items
+            .iter()
+            .map(|item| {
+                let line = item.range.start.line + 1;
+                CallEntry {
+                    item: item.clone(),
+                    sites: Some((item.uri.clone(), vec![LspRange::new(Position::new(line, 4), Position::new(line, 8))])),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_recursion_stops_expanding() {
+        let (parse, expr, atom) = (item("parse", 0), item("expr", 10), item("atom", 20));
+        let mut tree = HierarchyTree::new(CallDirection::Incoming);
+        tree.set_roots(vec![expr.clone()]);
+        assert_eq!(tree.toggle(0).map(|item| item.name), Some("expr".to_string()));
+        // `expr` is called by `atom`, which `expr` calls in turn
+        tree.set_children(0, calls(&[parse, atom.clone()]));
+        assert_eq!(tree.rows(), vec![0, 1, 2]);
+        assert_eq!(tree.toggle(2).map(|item| item.name), Some("atom".to_string()));
+        tree.set_children(2, calls(&[expr]));
+        assert_eq!(tree.rows(), vec![0, 1, 2, 3]);
+        assert!(tree.nodes[3].recursive);
+        assert_eq!(tree.toggle(3), None);
+        assert_eq!(tree.rows().len(), 4);
+        // The call site, in the caller
+        let location = |line, character| Location {
+            path: PathBuf::from("/project/src/lib.rs"),
+            line,
+            character,
+        };
+        assert_eq!(tree.location(2), Some(location(21, 4)));
+        assert_eq!(tree.location(0), Some(location(10, 3)));
+    }
+
+    #[test]
+    fn test_children_are_asked_for_once() {
+        let (main, run) = (item("main", 0), item("run", 10));
+        let mut tree = HierarchyTree::new(CallDirection::Outgoing);
+        tree.set_roots(vec![main]);
+        assert!(tree.toggle(0).is_some());
+        tree.set_children(0, calls(&[run]));
+        // Collapsing and expanding again keeps what was found
+        assert_eq!(tree.toggle(0), None);
+        assert_eq!(tree.rows(), vec![0]);
+        assert_eq!(tree.toggle(0), None);
+        assert_eq!(tree.rows(), vec![0, 1]);
+
+        // Back from the other way, they're still known
+        tree.set_direction(CallDirection::Incoming);
+        assert!(tree.toggle(0).is_some());
+        tree.set_direction(CallDirection::Outgoing);
+        assert_eq!(tree.toggle(0), None);
+        assert_eq!(tree.rows(), vec![0, 1]);
+    }
+}
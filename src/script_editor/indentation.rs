@@ -0,0 +1,267 @@
+//! Indentation: per-language indent style, detection from file content,
+//! indenting and outdenting lines, and indent guides
+
+use std::ops::Range;
+
+/// How one level of indentation is written
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IndentStyle {
+    /// Indent with tab characters instead of spaces
+    pub hard_tabs: bool,
+    /// Columns per indent level (and per tab character)
+    pub width: u32,
+}
+
+impl IndentStyle {
+    pub const fn spaces(width: u32) -> Self {
+        Self {
+            hard_tabs: false,
+            width,
+        }
+    }
+
+    pub const fn tabs(width: u32) -> Self {
+        Self {
+            hard_tabs: true,
+            width,
+        }
+    }
+
+    /// Default style for a language id as returned by `get_language_from_extension`
+    pub fn for_language(language: &str) -> Self {
+        match language {
+            "go" => Self::tabs(4),
+            "javascript" | "typescript" | "json" | "jsonc" | "yaml" | "html" | "css" | "xml" | "ruby" | "markdown" => {
+                Self::spaces(2)
+            }
+            _ => Self::spaces(4),
+        }
+    }
+
+    /// Text inserted for one level of indentation
+    pub fn unit(&self) -> String {
+        if self.hard_tabs {
+            "\t".to_string()
+        } else {
+            " ".repeat(self.width as usize)
+        }
+    }
+
+    /// Text inserted by Tab at `column`: one tab, or spaces up to the next tab stop
+    pub fn unit_at(&self, column: u32) -> String {
+        if self.hard_tabs {
+            "\t".to_string()
+        } else {
+            " ".repeat((self.width - column % self.width) as usize)
+        }
+    }
+
+    /// Display width of a line's leading whitespace
+    fn indent_columns(&self, line: &str) -> u32 {
+        let mut columns = 0;
+        for ch in line.chars() {
+            match ch {
+                ' ' => columns += 1,
+                '\t' => columns += self.width - columns % self.width,
+                _ => break,
+            }
+        }
+        columns
+    }
+}
+
+/// Indent style used by `text`, or `None` if none of its lines are indented.
+///
+/// Tabs win if more lines start with a tab than with spaces. For spaces the
+/// width is the most common change in indentation between consecutive lines,
+/// so a few oddly indented lines (doc comment continuations, alignment) in
+/// a mostly consistent file don't change the result. `tab_width` is kept as
+/// the display width of tabs.
+pub fn detect(text: &str, tab_width: u32) -> Option<IndentStyle> {
+    let mut tab_lines = 0;
+    let mut space_lines = 0;
+    // How often each indentation change of 2..=8 spaces occurs
+    let mut deltas = [0usize; 9];
+    let mut previous = 0usize;
+
+    for line in text.lines() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if line.starts_with('\t') {
+            tab_lines += 1;
+            continue;
+        }
+        let indent = line.len() - line.trim_start_matches(' ').len();
+        if indent > 0 {
+            space_lines += 1;
+        }
+        let delta = indent.abs_diff(previous);
+        if (2..deltas.len()).contains(&delta) {
+            deltas[delta] += 1;
+        }
+        previous = indent;
+    }
+
+    if tab_lines == 0 && space_lines == 0 {
+        return None;
+    }
+    if tab_lines > space_lines {
+        return Some(IndentStyle::tabs(tab_width));
+    }
+    let (width, count) = deltas
+        .iter()
+        .enumerate()
+        .rev()
+        .max_by_key(|(_, count)| **count)
+        .map(|(width, count)| (width as u32, *count))?;
+    (count > 0).then_some(IndentStyle::spaces(width))
+}
+
+/// Byte offset where each line of `text` starts
+fn line_starts(text: &str) -> Vec<usize> {
+    std::iter::once(0)
+        .chain(text.match_indices('\n').map(|(offset, _)| offset + 1))
+        .collect()
+}
+
+/// Edits adding one indent level to every non-blank line in `lines`
+pub fn indent_lines(text: &str, lines: Range<u32>, style: IndentStyle) -> Vec<(Range<usize>, String)> {
+    let starts = line_starts(text);
+    let unit = style.unit();
+    lines
+        .filter_map(|line| {
+            let start = *starts.get(line as usize)?;
+            let end = starts.get(line as usize + 1).map_or(text.len(), |next| next - 1);
+            (!text[start..end].trim().is_empty()).then(|| (start..start, unit.clone()))
+        })
+        .collect()
+}
+
+/// Edits removing up to one indent level from every line in `lines`
+pub fn outdent_lines(text: &str, lines: Range<u32>, style: IndentStyle) -> Vec<(Range<usize>, String)> {
+    let starts = line_starts(text);
+    lines
+        .filter_map(|line| {
+            let start = *starts.get(line as usize)?;
+            let rest = &text[start..];
+            let len = if rest.starts_with('\t') {
+                1
+            } else {
+                rest.chars()
+                    .take(style.width as usize)
+                    .take_while(|ch| *ch == ' ')
+                    .count()
+            };
+            (len > 0).then(|| (start..start + len, String::new()))
+        })
+        .collect()
+}
+
+/// A vertical guide at `column` beside lines `lines` (end exclusive)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IndentGuide {
+    pub column: u32,
+    pub lines: Range<u32>,
+}
+
+/// Guides for every indent level, one per run of lines indented past it.
+///
+/// Blank lines take the smaller indentation of the lines around them so a
+/// guide continues through an empty line inside a block.
+pub fn indent_guides(text: &str, style: IndentStyle) -> Vec<IndentGuide> {
+    let indents: Vec<Option<u32>> = text
+        .lines()
+        .map(|line| (!line.trim().is_empty()).then(|| style.indent_columns(line)))
+        .collect();
+
+    // Levels indented past, with blank lines bridged
+    let mut levels = vec![0u32; indents.len()];
+    let mut next_indent = 0;
+    for (ix, indent) in indents.iter().enumerate().rev() {
+        if let Some(indent) = indent {
+            next_indent = *indent;
+        }
+        levels[ix] = indent.unwrap_or(next_indent);
+    }
+    let mut previous_indent = 0;
+    for (ix, indent) in indents.iter().enumerate() {
+        match indent {
+            Some(indent) => previous_indent = *indent,
+            None => levels[ix] = levels[ix].min(previous_indent),
+        }
+    }
+    for level in &mut levels {
+        *level /= style.width.max(1);
+    }
+
+    let mut guides = Vec::new();
+    let depth = levels.iter().copied().max().unwrap_or(0);
+    for level in 0..depth {
+        let mut run_start = None;
+        for (line, indent_level) in levels.iter().copied().chain([0]).enumerate() {
+            match (indent_level > level, run_start) {
+                (true, None) => run_start = Some(line as u32),
+                (false, Some(start)) => {
+                    guides.push(IndentGuide {
+                        column: level * style.width,
+                        lines: start..line as u32,
+                    });
+                    run_start = None;
+                }
+                _ => {}
+            }
+        }
+    }
+    guides
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_dominant_style() {
+        assert_eq!(detect("fn a() {\n    b();\n}\n", 4), Some(IndentStyle::spaces(4)));
+        assert_eq!(detect("a:\n  b:\n    c: 1\n", 4), Some(IndentStyle::spaces(2)));
+        // Mostly tabs, with one space-indented line
+        assert_eq!(
+            detect("f {\n\ta\n\tb\n\tc\n  d\n}\n", 8),
+            Some(IndentStyle::tabs(8))
+        );
+        assert_eq!(detect("a\nb\n", 4), None);
+    }
+
+    #[test]
+    fn test_indent_and_outdent_lines() {
+        let text = "a\n\n  b\n\tc\n";
+        assert_eq!(
+            indent_lines(text, 0..3, IndentStyle::spaces(2)),
+            vec![(0..0, "  ".to_string()), (3..3, "  ".to_string())]
+        );
+        assert_eq!(
+            outdent_lines(text, 0..4, IndentStyle::spaces(4)),
+            vec![(3..5, String::new()), (7..8, String::new())]
+        );
+    }
+
+    #[test]
+    fn test_unit_at_next_tab_stop() {
+        assert_eq!(IndentStyle::spaces(4).unit_at(1), "   ");
+        assert_eq!(IndentStyle::spaces(4).unit_at(4), "    ");
+        assert_eq!(IndentStyle::tabs(4).unit_at(2), "\t");
+    }
+
+    #[test]
+    fn test_indent_guides() {
+        // The blank line continues the outer guide but not the inner one
+        let text = "fn a() {\n    if x {\n        y();\n\n    }\n}\n";
+        assert_eq!(
+            indent_guides(text, IndentStyle::spaces(4)),
+            vec![
+                IndentGuide { column: 0, lines: 1..5 },
+                IndentGuide { column: 4, lines: 2..3 },
+            ]
+        );
+    }
+}
@@ -0,0 +1,216 @@
+//! Built-in checking of JSON and JSONC files, used when no JSON language
+//! server is installed, and the settings sent to one that is
+//!
+//! The server validates and completes against schemas named by a file's
+//! `$schema` field or associated with its path in the editor settings; the
+//! built-in check only reports syntax errors.
+
+use lsp_types::DiagnosticSeverity;
+use serde_json::json;
+use std::ops::Range;
+use std::path::Path;
+
+use super::lsp::FileDiagnostic;
+use super::settings::JsonSchemaAssociation;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JsonDialect {
+    /// Plain JSON: comments and trailing commas are errors
+    Strict,
+    /// JSON with comments, as in `tsconfig.json`: comments and trailing commas are fine
+    WithComments,
+}
+
+impl JsonDialect {
+    pub fn for_path(path: &Path) -> Option<Self> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Some(Self::Strict),
+            Some("jsonc") => Some(Self::WithComments),
+            _ => None,
+        }
+    }
+}
+
+/// Something strict JSON doesn't allow
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Extension {
+    Comment,
+    TrailingComma,
+}
+
+/// Syntax errors in `text`
+pub fn validate_json(text: &str, dialect: JsonDialect) -> Vec<FileDiagnostic> {
+    if text.trim().is_empty() {
+        return Vec::new();
+    }
+    let (stripped, extensions) = strip_extensions(text);
+
+    let mut diagnostics = Vec::new();
+    if dialect == JsonDialect::Strict {
+        diagnostics.extend(extensions.into_iter().map(|(range, extension)| {
+            let message = match extension {
+                Extension::Comment => "Comments are not permitted in JSON",
+                Extension::TrailingComma => "Trailing comma",
+            };
+            diagnostic(range, message)
+        }));
+    }
+    // Blanked-out extensions keep every offset where it was, so the parser's
+    // position is also a position in `text`
+    if let Err(e) = serde_json::from_str::<serde::de::IgnoredAny>(&stripped) {
+        let message = e.to_string();
+        let message = message.split(" at line ").next().unwrap_or(&message);
+        diagnostics.push(diagnostic(error_range(text, e.line(), e.column()), message));
+    }
+    diagnostics.sort_by_key(|diagnostic| diagnostic.range.start);
+    diagnostics
+}
+
+/// `text` without its comments and trailing commas, for parsers that only take strict JSON
+pub fn strip_comments(text: &str) -> String {
+    strip_extensions(text).0
+}
+
+/// `workspace/didChangeConfiguration` settings for the JSON language server
+pub fn json_language_settings(schemas: &[JsonSchemaAssociation]) -> serde_json::Value {
+    json!({
+        "json": {
+            "validate": { "enable": true },
+            "schemas": schemas,
+        }
+    })
+}
+
+fn diagnostic(range: Range<usize>, message: &str) -> FileDiagnostic {
+    FileDiagnostic {
+        range,
+        severity: DiagnosticSeverity::ERROR,
+        message: message.to_string(),
+        source: Some("json".to_string()),
+        code: None,
+    }
+}
+
+/// The character at a one-based line and byte column, or the last one past the end
+fn error_range(text: &str, line: usize, column: usize) -> Range<usize> {
+    let line_start: usize = text
+        .split_inclusive('\n')
+        .take(line.saturating_sub(1))
+        .map(str::len)
+        .sum();
+    let mut start = (line_start + column.saturating_sub(1)).min(text.len());
+    while !text.is_char_boundary(start) {
+        start -= 1;
+    }
+    match text[start..].chars().next() {
+        Some(ch) => start..start + ch.len_utf8(),
+        None => {
+            let last = text.char_indices().last().map_or(0, |(ix, _)| ix);
+            last..text.len()
+        }
+    }
+}
+
+/// `text` with its comments and trailing commas replaced by spaces (newlines
+/// kept), and where they were
+fn strip_extensions(text: &str) -> (String, Vec<(Range<usize>, Extension)>) {
+    let bytes = text.as_bytes();
+    let mut stripped = bytes.to_vec();
+    let mut extensions = Vec::new();
+    let mut ix = 0;
+    while ix < bytes.len() {
+        match bytes[ix] {
+            b'"' => ix = string_end(bytes, ix),
+            b'/' if matches!(bytes.get(ix + 1), Some(b'/' | b'*')) => {
+                let end = comment_end(bytes, ix);
+                for byte in &mut stripped[ix..end] {
+                    if *byte != b'\n' {
+                        *byte = b' ';
+                    }
+                }
+                extensions.push((ix..end, Extension::Comment));
+                ix = end;
+            }
+            b',' => {
+                if matches!(bytes.get(next_significant(bytes, ix + 1)), Some(b'}' | b']')) {
+                    stripped[ix] = b' ';
+                    extensions.push((ix..ix + 1, Extension::TrailingComma));
+                }
+                ix += 1;
+            }
+            _ => ix += 1,
+        }
+    }
+    // Only whole comments (made of whole characters) and commas were replaced
+    let stripped = String::from_utf8(stripped).expect("replaced whole characters with spaces");
+    (stripped, extensions)
+}
+
+/// Index just past the string starting with the quote at `start`
+fn string_end(bytes: &[u8], start: usize) -> usize {
+    let mut ix = start + 1;
+    while ix < bytes.len() {
+        match bytes[ix] {
+            b'\\' => ix += 2,
+            b'"' => return ix + 1,
+            _ => ix += 1,
+        }
+    }
+    bytes.len()
+}
+
+/// Index just past the comment starting at `start`; a line comment stops
+/// before its newline, an unterminated block comment at the end of the text
+fn comment_end(bytes: &[u8], start: usize) -> usize {
+    if bytes[start + 1] == b'/' {
+        bytes[start..]
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .map_or(bytes.len(), |len| start + len)
+    } else {
+        bytes[start + 2..]
+            .windows(2)
+            .position(|pair| pair == b"*/")
+            .map_or(bytes.len(), |len| start + 2 + len + 2)
+    }
+}
+
+/// Index of the first byte from `ix` that isn't whitespace or in a comment
+fn next_significant(bytes: &[u8], mut ix: usize) -> usize {
+    while ix < bytes.len() {
+        match bytes[ix] {
+            b' ' | b'\t' | b'\r' | b'\n' => ix += 1,
+            b'/' if matches!(bytes.get(ix + 1), Some(b'/' | b'*')) => ix = comment_end(bytes, ix),
+            _ => break,
+        }
+    }
+    ix
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_comments_and_trailing_commas() {
+        let text = "{\n  // c\n  \"a\": \"//\",\n}";
+        assert!(validate_json(text, JsonDialect::WithComments).is_empty());
+
+        let diagnostics = validate_json(text, JsonDialect::Strict);
+        let ranges: Vec<_> = diagnostics.iter().map(|d| d.range.clone()).collect();
+        // The `//` inside the string is not a comment
+        assert_eq!(ranges, [4..8, 20..21]);
+        assert_eq!(diagnostics[0].message, "Comments are not permitted in JSON");
+        assert_eq!(diagnostics[1].message, "Trailing comma");
+    }
+
+    #[test]
+    fn test_syntax_error_position() {
+        let diagnostics = validate_json("{\"a\": }", JsonDialect::Strict);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range, 6..7);
+        assert_eq!(diagnostics[0].message, "expected value");
+
+        assert!(validate_json("", JsonDialect::Strict).is_empty());
+    }
+}
@@ -0,0 +1,263 @@
+//! Which keys run which commands
+//!
+//! Every command comes with its default keys. A preset (Vim-lite, Sublime)
+//! changes some of them, and the user's `keymap.json` has the last word:
+//!
+//! ```json
+//! [
+//!     { "command": "Close File", "keys": ["ctrl-k w"] },
+//!     { "command": "Go to Definition", "keys": ["cmd-b"], "os": "macos" },
+//!     { "command": "Toggle Minimap", "keys": [] }
+//! ]
+//! ```
+//!
+//! An empty `keys` list unbinds the command. Entries with an `os` (`linux`,
+//! `macos` or `windows`) only apply there.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use super::settings::config_file;
+
+/// A set of keybindings laid over the default ones
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum KeymapPreset {
+    #[default]
+    Default,
+    /// A few of Vim's control keys, e.g. `ctrl-]` and `ctrl-w v`
+    VimLite,
+    Sublime,
+}
+
+impl KeymapPreset {
+    pub const ALL: [KeymapPreset; 3] = [KeymapPreset::Default, KeymapPreset::VimLite, KeymapPreset::Sublime];
+
+    pub fn is_default(&self) -> bool {
+        *self == KeymapPreset::Default
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            KeymapPreset::Default => "Default",
+            KeymapPreset::VimLite => "Vim-lite",
+            KeymapPreset::Sublime => "Sublime",
+        }
+    }
+
+    /// Commands whose keys this preset replaces
+    fn bindings(self) -> &'static [(&'static str, &'static [&'static str])] {
+        match self {
+            KeymapPreset::Default => &[],
+            KeymapPreset::VimLite => &[
+                ("Go to Definition", &["f12", "ctrl-]"]),
+                ("Go Back", &["alt-left", "ctrl-o"]),
+                ("Go Forward", &["alt-right", "ctrl-i"]),
+                // `ctrl-w` starts window commands, as in Vim
+                ("Close File", &["ctrl-w q"]),
+                ("Split Right", &["ctrl-w v"]),
+                ("Split Down", &["ctrl-w s"]),
+                ("Close Pane", &["ctrl-w c"]),
+                ("Next Tab", &["ctrl-tab", "ctrl-pagedown"]),
+                ("Previous Tab", &["ctrl-shift-tab", "ctrl-pageup"]),
+            ],
+            KeymapPreset::Sublime => &[
+                ("Move Line Up", &["ctrl-shift-up"]),
+                ("Move Line Down", &["ctrl-shift-down"]),
+                ("Duplicate Line Down", &["ctrl-shift-d"]),
+                ("Column Select Up", &["ctrl-alt-up"]),
+                ("Column Select Down", &["ctrl-alt-down"]),
+                ("Expand Selection", &["ctrl-shift-space"]),
+                ("Jump to Matching Bracket", &["ctrl-m"]),
+                ("Go to Symbol in Workspace", &["ctrl-shift-r"]),
+                ("Toggle Outline", &["ctrl-r"]),
+                ("Split Right", &["alt-shift-2"]),
+                ("Split Down", &["alt-shift-8"]),
+                ("Close Pane", &["alt-shift-1"]),
+            ],
+        }
+    }
+}
+
+/// One entry of `keymap.json`
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct KeymapEntry {
+    pub command: String,
+    #[serde(default)]
+    pub keys: Vec<String>,
+    /// Only on this OS, as named by `std::env::consts::OS`
+    #[serde(default)]
+    pub os: Option<String>,
+}
+
+/// Keys of the commands that don't keep their defaults
+#[derive(Clone, Debug, Default)]
+pub struct Keymap {
+    bindings: HashMap<String, Vec<String>>,
+}
+
+impl Keymap {
+    /// `preset`'s keys, then `entries` that apply on `os`, later ones winning
+    pub fn new(preset: KeymapPreset, entries: &[KeymapEntry], os: &str) -> Self {
+        let mut bindings: HashMap<String, Vec<String>> = preset
+            .bindings()
+            .iter()
+            .map(|(command, keys)| (command.to_string(), keys.iter().map(ToString::to_string).collect()))
+            .collect();
+        for entry in entries {
+            if entry.os.as_deref().is_none_or(|entry_os| entry_os.eq_ignore_ascii_case(os)) {
+                bindings.insert(entry.command.clone(), entry.keys.clone());
+            }
+        }
+        Self { bindings }
+    }
+
+    /// `preset` with the user's `keymap.json` on top, for this OS
+    pub fn load(preset: KeymapPreset) -> Self {
+        let entries = keymap_path()
+            .filter(|path| path.exists())
+            .map(|path| {
+                std::fs::read_to_string(&path)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|json| Ok(serde_json::from_str::<Vec<KeymapEntry>>(&json)?))
+                    .unwrap_or_else(|e| {
+                        tracing::error!("⚠️  Failed to load keymap {:?}: {}", path, e);
+                        Vec::new()
+                    })
+            })
+            .unwrap_or_default();
+        Self::new(preset, &entries, std::env::consts::OS)
+    }
+
+    /// The keys that run `command`, `defaults` unless the keymap says otherwise
+    pub fn keys_for(&self, command: &str, defaults: &[String]) -> Vec<String> {
+        self.bindings.get(command).cloned().unwrap_or_else(|| defaults.to_vec())
+    }
+}
+
+/// Keys that run more than one command: bound to several, or a prefix of
+/// another command's keys (`ctrl-k` before `ctrl-k ctrl-0`)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyConflict {
+    pub keys: String,
+    pub commands: Vec<String>,
+}
+
+/// Every conflict between the commands' keys, in the order the keys were first bound
+pub fn find_conflicts<'a>(bindings: impl IntoIterator<Item = (&'a str, &'a [String])>) -> Vec<KeyConflict> {
+    let bound: Vec<(Vec<String>, &str)> = bindings
+        .into_iter()
+        .flat_map(|(command, keys)| keys.iter().map(move |keys| (normalize_keys(keys), command)))
+        .collect();
+    let mut conflicts: Vec<KeyConflict> = Vec::new();
+    for (ix, (keys, command)) in bound.iter().enumerate() {
+        let text = keys.join(" ");
+        if conflicts.iter().any(|conflict| conflict.keys == text) {
+            continue;
+        }
+        let mut commands = vec![command.to_string()];
+        for (other_keys, other) in &bound[ix + 1..] {
+            let shadows = other_keys.starts_with(keys) || keys.starts_with(other_keys);
+            if shadows && !commands.iter().any(|known| known == other) {
+                commands.push(other.to_string());
+            }
+        }
+        if commands.len() > 1 {
+            conflicts.push(KeyConflict { keys: text, commands });
+        }
+    }
+    conflicts
+}
+
+/// Keystrokes with their modifiers in one order, so `shift-alt-up` is `alt-shift-up`
+fn normalize_keys(keys: &str) -> Vec<String> {
+    keys.split_whitespace()
+        .map(|keystroke| {
+            let mut parts: Vec<&str> = keystroke.split('-').collect();
+            // A trailing `-` is the minus key, not a separator
+            let key = if keystroke.ends_with("--") || keystroke == "-" {
+                parts.truncate(parts.len().saturating_sub(2));
+                "-"
+            } else {
+                parts.pop().unwrap_or_default()
+            };
+            parts.sort_unstable();
+            parts.push(key);
+            parts.join("-").to_lowercase()
+        })
+        .collect()
+}
+
+/// `<config dir>/pulsar/script_editor/keymap.json`
+pub fn keymap_path() -> Option<PathBuf> {
+    config_file("keymap.json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(keys: &[&str]) -> Vec<String> {
+        keys.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn test_preset_and_user_entries() {
+        let entries = [
+            KeymapEntry {
+                command: "Split Right".into(),
+                keys: keys(&["ctrl-shift-\\"]),
+                os: None,
+            },
+            KeymapEntry {
+                command: "Go to Definition".into(),
+                keys: keys(&["cmd-b"]),
+                os: Some("macos".into()),
+            },
+            KeymapEntry {
+                command: "Toggle Minimap".into(),
+                keys: Vec::new(),
+                os: None,
+            },
+        ];
+        let keymap = Keymap::new(KeymapPreset::VimLite, &entries, "linux");
+        assert_eq!(keymap.keys_for("Split Right", &keys(&["ctrl-\\"])), keys(&["ctrl-shift-\\"]));
+        assert_eq!(keymap.keys_for("Split Down", &keys(&["ctrl-k ctrl-\\"])), keys(&["ctrl-w s"]));
+        // The macOS entry doesn't apply, so the preset's keys stay
+        assert_eq!(keymap.keys_for("Go to Definition", &keys(&["f12"])), keys(&["f12", "ctrl-]"]));
+        assert_eq!(keymap.keys_for("Toggle Minimap", &keys(&["ctrl-k m"])), Vec::<String>::new());
+        assert_eq!(keymap.keys_for("Save File", &keys(&["ctrl-s"])), keys(&["ctrl-s"]));
+
+        let keymap = Keymap::new(KeymapPreset::Default, &entries, "macos");
+        assert_eq!(keymap.keys_for("Go to Definition", &keys(&["f12"])), keys(&["cmd-b"]));
+    }
+
+    #[test]
+    fn test_find_conflicts() {
+        let bindings = [
+            ("Close File", keys(&["ctrl-w"])),
+            ("Split Right", keys(&["ctrl-w v"])),
+            ("Duplicate Line Up", keys(&["shift-alt-up"])),
+            ("Expand Selection", keys(&["alt-shift-up"])),
+            ("Fold All", keys(&["ctrl-k ctrl-0"])),
+            ("Unfold All", keys(&["ctrl-k ctrl-j"])),
+            ("Zoom Out", keys(&["ctrl--"])),
+        ];
+        let conflicts = find_conflicts(bindings.iter().map(|(command, keys)| (*command, keys.as_slice())));
+        assert_eq!(
+            conflicts,
+            [
+                KeyConflict {
+                    keys: "ctrl-w".into(),
+                    commands: vec!["Close File".into(), "Split Right".into()],
+                },
+                KeyConflict {
+                    keys: "alt-shift-up".into(),
+                    commands: vec!["Duplicate Line Up".into(), "Expand Selection".into()],
+                },
+            ]
+        );
+        assert_eq!(normalize_keys("ctrl--"), ["ctrl--"]);
+    }
+}
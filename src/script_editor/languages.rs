@@ -0,0 +1,105 @@
+//! The languages the editor knows by file extension
+//!
+//! The plugin's file types, the editor's language and grammar for a file and
+//! the explorer's icons are all looked up here, so each extension is listed once.
+
+use std::path::Path;
+use ui::IconName;
+
+pub struct Language {
+    /// What settings, language servers and comment markers know it by
+    pub name: &'static str,
+    /// Lowercase and without the dot, the main one first
+    pub extensions: &'static [&'static str],
+    /// Extensions of its header files, which get a file type of their own
+    pub headers: &'static [&'static str],
+    /// Tree-sitter grammar that highlights it
+    pub grammar: &'static str,
+    pub icon: IconName,
+}
+
+impl Language {
+    const fn new(name: &'static str, extensions: &'static [&'static str], grammar: &'static str, icon: IconName) -> Self {
+        Self {
+            name,
+            extensions,
+            headers: &[],
+            grammar,
+            icon,
+        }
+    }
+
+    const fn with_headers(mut self, headers: &'static [&'static str]) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    /// Extensions of its source files, leaving out the headers
+    pub fn sources(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.extensions.iter().copied().filter(|extension| !self.headers.contains(extension))
+    }
+}
+
+pub const LANGUAGES: &[Language] = &[
+    Language::new("rust", &["rs"], "rust", IconName::Code),
+    Language::new("javascript", &["js", "jsx"], "javascript", IconName::Code),
+    Language::new("typescript", &["ts", "tsx"], "typescript", IconName::Code),
+    Language::new("python", &["py"], "python", IconName::Code),
+    Language::new("lua", &["lua"], "lua", IconName::Code),
+    Language::new("go", &["go"], "go", IconName::Code),
+    Language::new("zig", &["zig"], "zig", IconName::Code),
+    Language::new("c", &["c", "h"], "c", IconName::Code).with_headers(&["h"]),
+    Language::new("cpp", &["cpp", "cc", "cxx", "hpp", "hh"], "cpp", IconName::Code).with_headers(&["hpp", "hh"]),
+    Language::new("java", &["java"], "java", IconName::Code),
+    Language::new("ruby", &["rb"], "plaintext", IconName::Code),
+    Language::new("sql", &["sql"], "plaintext", IconName::Code),
+    Language::new("bash", &["sh", "bash"], "bash", IconName::Code),
+    Language::new("json", &["json"], "json", IconName::Settings),
+    // Highlighted as JSON, but with comments allowed
+    Language::new("jsonc", &["jsonc"], "json", IconName::Settings),
+    Language::new("toml", &["toml"], "toml", IconName::Settings),
+    Language::new("yaml", &["yaml", "yml"], "yaml", IconName::Settings),
+    Language::new("markdown", &["md"], "markdown", IconName::Notes),
+    Language::new("html", &["html", "htm"], "html", IconName::Html5),
+    Language::new("css", &["css"], "css", IconName::Css3),
+    Language::new("xml", &["xml"], "xml", IconName::Code),
+    Language::new("text", &["txt", "log"], "plaintext", IconName::Page),
+];
+
+/// The language called `name`
+pub fn language(name: &str) -> Option<&'static Language> {
+    LANGUAGES.iter().find(|language| language.name == name)
+}
+
+/// The language of `path`, by its extension (ignoring case)
+pub fn language_for_path(path: &Path) -> Option<&'static Language> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    LANGUAGES.iter().find(|language| language.extensions.contains(&extension.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_each_extension_has_one_language() {
+        let mut extensions: Vec<&str> = LANGUAGES.iter().flat_map(|language| language.extensions.iter().copied()).collect();
+        let count = extensions.len();
+        extensions.sort_unstable();
+        extensions.dedup();
+        assert_eq!(extensions.len(), count);
+        for language in LANGUAGES {
+            assert!(language.headers.iter().all(|header| language.extensions.contains(header)), "{}", language.name);
+        }
+    }
+
+    #[test]
+    fn test_language_for_path() {
+        let name = |path: &str| language_for_path(Path::new(path)).map(|language| language.name);
+        assert_eq!(name("src/main.rs"), Some("rust"));
+        assert_eq!(name("include/vec.HPP"), Some("cpp"));
+        assert_eq!(name("config.yml"), Some("yaml"));
+        assert_eq!(name("Makefile"), None);
+        assert_eq!(language("cpp").unwrap().sources().collect::<Vec<_>>(), ["cpp", "cc", "cxx"]);
+    }
+}
@@ -0,0 +1,75 @@
+//! Files too large to open as usual, and files that aren't text
+//!
+//! A file over the size threshold is opened only after asking, either fully
+//! or read-only: without a language server, highlighting or folding, so it
+//! costs little more than its text.
+
+use std::io::Read;
+use std::path::Path;
+
+/// Size threshold in megabytes unless the settings change it
+pub const DEFAULT_LARGE_FILE_THRESHOLD_MB: u64 = 50;
+
+/// Bytes at the start of a file looked at to tell a binary one
+const BINARY_SNIFF_BYTES: usize = 8 * 1024;
+
+/// Whether a file of `size` bytes needs asking about first
+pub fn is_large(size: u64, threshold_mb: u64) -> bool {
+    size > threshold_mb.saturating_mul(1024 * 1024)
+}
+
+/// Whether the first bytes of a file aren't text: they have a NUL, or
+/// aren't UTF-8 and have control characters, which text in a legacy encoding
+/// doesn't. UTF-16 text, which has NULs, starts with a byte order mark.
+pub fn looks_binary(first_bytes: &[u8]) -> bool {
+    if first_bytes.starts_with(b"\xff\xfe") || first_bytes.starts_with(b"\xfe\xff") {
+        return false;
+    }
+    let sample = &first_bytes[..first_bytes.len().min(BINARY_SNIFF_BYTES)];
+    if sample.contains(&0) {
+        return true;
+    }
+    match std::str::from_utf8(sample) {
+        Ok(_) => false,
+        // Cut off inside the last character
+        Err(e) if e.error_len().is_none() => false,
+        Err(_) => sample.iter().any(|&byte| byte < 0x20 && !matches!(byte, b'\t' | b'\n' | b'\r' | 0x0c | 0x1b)),
+    }
+}
+
+/// `looks_binary` for the file at `path`, reading only its start
+pub fn is_binary_file(path: &Path) -> std::io::Result<bool> {
+    let mut first_bytes = Vec::with_capacity(BINARY_SNIFF_BYTES);
+    std::fs::File::open(path)?.take(BINARY_SNIFF_BYTES as u64).read_to_end(&mut first_bytes)?;
+    Ok(looks_binary(&first_bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_threshold_boundary() {
+        let mb = 1024 * 1024;
+        assert!(!is_large(50 * mb - 1, 50));
+        assert!(!is_large(50 * mb, 50));
+        assert!(is_large(50 * mb + 1, 50));
+        assert!(is_large(1, 0));
+        assert!(!is_large(u64::MAX, u64::MAX));
+    }
+
+    #[test]
+    fn test_looks_binary() {
+        assert!(looks_binary(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"));
+        assert!(!looks_binary(b"fn main() {}\n"));
+        assert!(!looks_binary(b"\xff\xfeh\0i\0"));
+        assert!(looks_binary(b"\x7fELF\x02\x01\x01\xe8\x03"));
+        // Latin-1 text, and UTF-8 cut off inside a character
+        assert!(!looks_binary(b"caf\xe9\r\n"));
+        assert!(!looks_binary(&"né".as_bytes()[..2]));
+        // Past the bytes looked at
+        let mut late = vec![b'a'; BINARY_SNIFF_BYTES];
+        late.push(0);
+        assert!(!looks_binary(&late));
+    }
+}
@@ -0,0 +1,270 @@
+//! Highlighting for files too large to parse whole
+//!
+//! Lines are tokenized with the minimap's rough lexer. Those in view are
+//! lexed on the spot, so they're colored the frame they appear; the rest of
+//! the file is lexed a chunk at a time on the background executor, top down,
+//! and merged as each chunk finishes. The only state carried from one line to
+//! the next is whether it starts inside a block comment, so a line's tokens
+//! are cached by its text and that state: an edit relexes the lines it
+//! touched, and the lines below only if it opened or closed a comment.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+use std::sync::Arc;
+
+use super::minimap::{tokenize_line, TokenKind};
+use super::text_buffer::TextBuffer;
+
+/// Edits spanning more lines than this aren't relexed on the spot; the
+/// background pass picks them up instead
+const MAX_RELEXED_LINES: u32 = 1_000;
+
+/// A byte range and its theme kind
+pub type Token = (Range<usize>, &'static str);
+
+/// A line's text and the state it starts in, which together decide its tokens
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct LineKey {
+    text_hash: u64,
+    in_block_comment: bool,
+}
+
+impl LineKey {
+    fn new(text: &str, in_block_comment: bool) -> Self {
+        let mut hasher = DefaultHasher::new();
+        text.hash(&mut hasher);
+        Self {
+            text_hash: hasher.finish(),
+            in_block_comment,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct LexedLine {
+    /// Byte ranges within the line
+    tokens: Arc<[Token]>,
+    ends_in_comment: bool,
+}
+
+/// Lines lexed off the UI thread, for `LazyHighlighter::merge`
+#[derive(Debug)]
+pub struct LexedChunk {
+    start: u32,
+    starts_in_comment: bool,
+    lines: Vec<(LineKey, LexedLine)>,
+}
+
+#[derive(Clone, Debug)]
+pub struct LazyHighlighter {
+    /// Whether each line starts inside a block comment, for the lines from
+    /// the top down to the first one not lexed yet
+    line_starts_in_comment: Vec<bool>,
+    cache: HashMap<LineKey, LexedLine>,
+}
+
+impl Default for LazyHighlighter {
+    fn default() -> Self {
+        Self {
+            line_starts_in_comment: vec![false],
+            cache: HashMap::new(),
+        }
+    }
+}
+
+impl LazyHighlighter {
+    /// Tokens of `lines` as byte ranges of `buffer`. Lines below the ones
+    /// lexed from the top are taken to start outside a comment until the
+    /// background pass reaches them.
+    pub fn highlights(&mut self, buffer: &TextBuffer, lines: Range<u32>) -> Vec<Token> {
+        let end = lines.end.min(buffer.line_count() as u32);
+        let mut in_block_comment = false;
+        let mut highlights = Vec::new();
+        for line in lines.start.min(end)..end {
+            if let Some(&known) = self.line_starts_in_comment.get(line as usize) {
+                in_block_comment = known;
+            }
+            let line_range = buffer.line_range(line);
+            let lexed = self.lex(&buffer.slice(line_range.clone()), in_block_comment);
+            if self.line_starts_in_comment.len() == line as usize + 1 {
+                self.line_starts_in_comment.push(lexed.ends_in_comment);
+            }
+            in_block_comment = lexed.ends_in_comment;
+            highlights.extend(
+                lexed
+                    .tokens
+                    .iter()
+                    .map(|(range, kind)| (line_range.start + range.start..line_range.start + range.end, *kind)),
+            );
+        }
+        highlights
+    }
+
+    /// Whether every line of `buffer` has been lexed from the top
+    pub fn is_complete(&self, buffer: &TextBuffer) -> bool {
+        self.line_starts_in_comment.len() > buffer.line_count()
+    }
+
+    /// The first line not lexed from the top, and whether it starts in a comment
+    pub fn resume_point(&self) -> (u32, bool) {
+        let line = self.line_starts_in_comment.len() - 1;
+        (line as u32, self.line_starts_in_comment[line])
+    }
+
+    /// Take in lines lexed in the background from the text this highlighter
+    /// has now, returning those that were new. A chunk that no longer
+    /// continues from what's known, because an edit changed the lines above
+    /// it, is dropped.
+    pub fn merge(&mut self, chunk: LexedChunk) -> Range<u32> {
+        let known = self.line_starts_in_comment.len();
+        if chunk.start as usize >= known
+            || self.line_starts_in_comment[chunk.start as usize] != chunk.starts_in_comment
+        {
+            return 0..0;
+        }
+        let merged_from = known as u32 - 1;
+        for (offset, (key, lexed)) in chunk.lines.into_iter().enumerate() {
+            if chunk.start as usize + offset + 1 >= self.line_starts_in_comment.len() {
+                self.line_starts_in_comment.push(lexed.ends_in_comment);
+            }
+            self.cache.insert(key, lexed);
+        }
+        merged_from..self.line_starts_in_comment.len() as u32 - 1
+    }
+
+    /// Account for an edit of `buffer` that replaced lines `first..=old_last`
+    /// with `first..=new_last`. The edited lines are relexed; what's known of
+    /// the lines below stands if the edit left the first of them starting in
+    /// the same state, and is otherwise left to the background pass.
+    pub fn edit(&mut self, buffer: &TextBuffer, first: u32, old_last: u32, new_last: u32) {
+        // Tokens of lines no longer in the file go once they outnumber its lines
+        if self.cache.len() > 2 * buffer.line_count() + 1_024 {
+            self.cache.clear();
+        }
+        let first = first as usize;
+        if first + 1 >= self.line_starts_in_comment.len() || new_last - first as u32 > MAX_RELEXED_LINES {
+            self.line_starts_in_comment.truncate(first + 1);
+            return;
+        }
+        let below = self.line_starts_in_comment.split_off(first + 1);
+        for line in first as u32..=new_last.min(buffer.line_count() as u32 - 1) {
+            let text = buffer.slice(buffer.line_range(line));
+            let lexed = self.lex(&text, self.line_starts_in_comment[line as usize]);
+            self.line_starts_in_comment.push(lexed.ends_in_comment);
+        }
+        // `below[0]` was the state of the line after `first`, before the edit
+        let unchanged_from = old_last as usize - first;
+        if below.get(unchanged_from) == self.line_starts_in_comment.last() {
+            self.line_starts_in_comment.extend_from_slice(&below[unchanged_from + 1..]);
+        }
+    }
+
+    fn lex(&mut self, text: &str, in_block_comment: bool) -> LexedLine {
+        self.cache
+            .entry(LineKey::new(text, in_block_comment))
+            .or_insert_with(|| lex_line(text, in_block_comment))
+            .clone()
+    }
+}
+
+/// Lex up to `count` lines of `buffer` from `start`, which starts in the
+/// given state: the part of highlighting that runs in the background
+pub fn lex_chunk(buffer: &TextBuffer, start: u32, starts_in_comment: bool, count: u32) -> LexedChunk {
+    let end = (start + count).min(buffer.line_count() as u32);
+    let mut in_block_comment = starts_in_comment;
+    let lines = (start.min(end)..end)
+        .map(|line| {
+            let text = buffer.slice(buffer.line_range(line));
+            let key = LineKey::new(&text, in_block_comment);
+            let lexed = lex_line(&text, in_block_comment);
+            in_block_comment = lexed.ends_in_comment;
+            (key, lexed)
+        })
+        .collect();
+    LexedChunk {
+        start,
+        starts_in_comment,
+        lines,
+    }
+}
+
+/// Tokens of one line as byte ranges, leaving plain text to the default color
+fn lex_line(text: &str, in_block_comment: bool) -> LexedLine {
+    let byte_of = |column: usize| text.char_indices().nth(column).map_or(text.len(), |(ix, _)| ix);
+    let mut ends_in_comment = in_block_comment;
+    let tokens = tokenize_line(text, usize::MAX, &mut ends_in_comment)
+        .into_iter()
+        .filter_map(|(columns, kind)| {
+            let kind = match kind {
+                TokenKind::Keyword => "keyword",
+                TokenKind::String => "string",
+                TokenKind::Comment => "comment",
+                TokenKind::Number => "number",
+                TokenKind::Text => return None,
+            };
+            Some((byte_of(columns.start)..byte_of(columns.end), kind))
+        })
+        .collect();
+    LexedLine {
+        tokens,
+        ends_in_comment,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds<'a>(text: &'a str, highlights: &[Token]) -> Vec<(&'a str, &'static str)> {
+        highlights.iter().map(|(range, kind)| (&text[range.clone()], *kind)).collect()
+    }
+
+    fn lex_to_end(highlighter: &mut LazyHighlighter, buffer: &TextBuffer) {
+        while !highlighter.is_complete(buffer) {
+            let (start, in_block_comment) = highlighter.resume_point();
+            assert!(!highlighter.merge(lex_chunk(buffer, start, in_block_comment, 100)).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_background_pass_corrects_lines_in_view() {
+        let mut text = "/* start\n".to_string();
+        text.push_str(&"still a comment\n".repeat(1_000));
+        text.push_str("end */ let x = \"é\";\n");
+        let buffer = TextBuffer::new(&text);
+        let mut highlighter = LazyHighlighter::default();
+
+        // The end of the file is in view before the comment above it is known...
+        let last = kinds(&text, &highlighter.highlights(&buffer, 1_001..1_003));
+        assert_eq!(last, [("let", "keyword"), ("\"é\"", "string")]);
+
+        // ...and right once the background pass gets there
+        lex_to_end(&mut highlighter, &buffer);
+        let last = kinds(&text, &highlighter.highlights(&buffer, 1_001..1_003));
+        assert_eq!(last, [("end */", "comment"), ("let", "keyword"), ("\"é\"", "string")]);
+    }
+
+    #[test]
+    fn test_edits_relex_their_dependents() {
+        let text = format!("/* start\n{}end */ let x = 1;\n", "still a comment\n".repeat(1_000));
+        let mut highlighter = LazyHighlighter::default();
+        lex_to_end(&mut highlighter, &TextBuffer::new(&text));
+
+        // Inside the comment, only the edited line changes
+        let edited = text.replacen("still", "yet", 1);
+        let buffer = TextBuffer::new(&edited);
+        highlighter.edit(&buffer, 1, 1, 1);
+        assert!(highlighter.is_complete(&buffer));
+
+        // Closing the comment early changes every line below
+        let edited = edited.replacen("/*", "//", 1);
+        let buffer = TextBuffer::new(&edited);
+        highlighter.edit(&buffer, 0, 0, 0);
+        assert!(!highlighter.is_complete(&buffer));
+        lex_to_end(&mut highlighter, &buffer);
+        let last = kinds(&edited, &highlighter.highlights(&buffer, 1_001..1_002));
+        assert_eq!(last, [("let", "keyword"), ("1", "number")]);
+    }
+}
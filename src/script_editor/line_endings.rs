@@ -0,0 +1,81 @@
+//! A file's line endings
+//!
+//! Buffers only ever hold `\n`, so offsets, LSP positions and search mean the
+//! same in every file. The ending a file was opened with comes back when it
+//! is saved.
+
+use std::borrow::Cow;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,
+}
+
+impl LineEnding {
+    pub fn label(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "LF",
+            LineEnding::Crlf => "CRLF",
+        }
+    }
+
+    /// The ending most of `text`'s lines have (`\n` when no line has one),
+    /// and whether some have the other
+    pub fn detect(text: &str) -> (LineEnding, bool) {
+        let crlf = text.matches("\r\n").count();
+        let lf = text.matches('\n').count() - crlf;
+        let ending = if crlf > lf { LineEnding::Crlf } else { LineEnding::Lf };
+        (ending, crlf > 0 && lf > 0)
+    }
+
+    /// `text`, with only `\n` line breaks, written out with this ending
+    pub fn apply(self, text: &str) -> Cow<'_, str> {
+        match self {
+            LineEnding::Lf => Cow::Borrowed(text),
+            LineEnding::Crlf => Cow::Owned(text.replace('\n', "\r\n")),
+        }
+    }
+}
+
+/// `text` with every `\r\n` turned into `\n`, as buffers hold it
+pub fn normalize(text: &str) -> Cow<'_, str> {
+    if text.contains("\r\n") {
+        Cow::Owned(text.replace("\r\n", "\n"))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+/// Like `normalize`, without copying text that has no `\r\n`
+pub fn into_normalized(text: String) -> String {
+    match normalize(&text) {
+        Cow::Owned(normalized) => normalized,
+        Cow::Borrowed(_) => text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(LineEnding::detect("a\r\nb\r\n"), (LineEnding::Crlf, false));
+        assert_eq!(LineEnding::detect("a\nb\n"), (LineEnding::Lf, false));
+        assert_eq!(LineEnding::detect("a"), (LineEnding::Lf, false));
+        assert_eq!(LineEnding::detect("a\r\nb\r\nc\n"), (LineEnding::Crlf, true));
+        // A lone `\r` isn't a line ending
+        assert_eq!(LineEnding::detect("a\rb\n"), (LineEnding::Lf, false));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let text = "fn main() {\r\n    print!(\"\\r\");\r\n}\r\n";
+        let normalized = normalize(text);
+        assert_eq!(normalized, "fn main() {\n    print!(\"\\r\");\n}\n");
+        assert_eq!(LineEnding::Crlf.apply(&normalized), text);
+        assert_eq!(LineEnding::Lf.apply(&normalized), normalized);
+    }
+}
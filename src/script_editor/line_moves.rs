@@ -0,0 +1,101 @@
+//! Moving and duplicating whole lines
+//!
+//! Each operation is one replacement, so it undoes in one step, plus how far
+//! the selection moves to stay on the lines it was on.
+
+use std::ops::Range;
+
+/// A replacement of `range` with `new_text`, after which the selection is
+/// `shift` bytes from where it was
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LineEdit {
+    pub range: Range<usize>,
+    pub new_text: String,
+    pub shift: isize,
+}
+
+/// Swap lines `lines` with the line above (or below), or `None` when they
+/// are already the first (or last) lines
+pub fn move_lines(text: &str, lines: Range<u32>, up: bool) -> Option<LineEdit> {
+    let block = line_span(text, lines.clone())?;
+    let block_text = &text[block.clone()];
+    if up {
+        let above = line_span(text, lines.start.checked_sub(1)?..lines.start)?;
+        Some(LineEdit {
+            range: above.start..block.end,
+            new_text: format!("{}\n{}", block_text, &text[above.clone()]),
+            shift: -(above.len() as isize + 1),
+        })
+    } else {
+        let below = line_span(text, lines.end..lines.end + 1)?;
+        Some(LineEdit {
+            range: block.start..below.end,
+            new_text: format!("{}\n{}", &text[below.clone()], block_text),
+            shift: below.len() as isize + 1,
+        })
+    }
+}
+
+/// Copy lines `lines` right below themselves; the selection moves onto the
+/// copy when `down`, and stays on the lines above otherwise
+pub fn duplicate_lines(text: &str, lines: Range<u32>, down: bool) -> Option<LineEdit> {
+    let block = line_span(text, lines)?;
+    Some(LineEdit {
+        range: block.end..block.end,
+        new_text: format!("\n{}", &text[block.clone()]),
+        shift: if down { block.len() as isize + 1 } else { 0 },
+    })
+}
+
+/// Bytes of lines `lines` without the last one's line break, or `None` if
+/// the text doesn't have them all
+fn line_span(text: &str, lines: Range<u32>) -> Option<Range<usize>> {
+    if lines.is_empty() {
+        return None;
+    }
+    let mut line_start = 0;
+    let mut start = None;
+    for (line, content) in text.split('\n').enumerate() {
+        let line = line as u32;
+        if line == lines.start {
+            start = Some(line_start);
+        }
+        if line == lines.end - 1 {
+            return Some(start?..line_start + content.len());
+        }
+        line_start += content.len() + 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(text: &str, edit: LineEdit) -> String {
+        let mut text = text.to_string();
+        text.replace_range(edit.range, &edit.new_text);
+        text
+    }
+
+    #[test]
+    fn test_move_lines() {
+        let text = "a\n    b\n    c\nd";
+        let up = move_lines(text, 1..3, true).unwrap();
+        assert_eq!(up.shift, -2);
+        assert_eq!(apply(text, up), "    b\n    c\na\nd");
+        // The last line has no line break of its own
+        assert_eq!(apply(text, move_lines(text, 1..3, false).unwrap()), "a\nd\n    b\n    c");
+        assert_eq!(move_lines(text, 0..1, true), None);
+        assert_eq!(move_lines(text, 3..4, false), None);
+    }
+
+    #[test]
+    fn test_duplicate_lines() {
+        let text = "a\nb\n";
+        let down = duplicate_lines(text, 0..1, true).unwrap();
+        assert_eq!(down.shift, 2);
+        assert_eq!(apply(text, down), "a\na\nb\n");
+        assert_eq!(duplicate_lines(text, 2..3, false).map(|edit| apply(text, edit)), Some("a\nb\n\n".to_string()));
+    }
+}
@@ -0,0 +1,11 @@
+//! A place in a file for the editor to open, as the panels that list them send it
+
+use std::path::PathBuf;
+
+/// A zero-based line and UTF-16 column of a file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Location {
+    pub path: PathBuf,
+    pub line: u32,
+    pub character: u32,
+}
@@ -0,0 +1,807 @@
+//! JSON-RPC transport for a single language server process
+//!
+//! The server is spawned as a child process speaking LSP over stdio. A reader
+//! thread parses incoming frames, resolves pending requests and fans
+//! notifications out to subscribers. Requests return a [`PendingRequest`]
+//! whose response can be awaited from a gpui task. Subscriptions end when
+//! the process does, which is how the editor learns of a crash.
+
+use anyhow::{anyhow, Context as _, Result};
+use futures::channel::{mpsc, oneshot};
+use lsp_types::OneOf;
+use parking_lot::Mutex;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+
+use super::convert::path_to_file_url;
+use super::document_sync::{sync_kind, wants_changes};
+use super::semantic_tokens::{TOKEN_MODIFIERS, TOKEN_TYPES};
+use super::servers::ServerCommand;
+
+/// A notification pushed by the server, e.g. `textDocument/publishDiagnostics`,
+/// or a request forwarded to the editor to answer once it's dealt with it:
+/// `workspace/applyEdit` and `window/showMessageRequest`
+#[derive(Clone, Debug)]
+pub struct ServerNotification {
+    pub method: String,
+    pub params: Value,
+    /// Id of a request the editor answers itself with `LanguageServer::respond`
+    pub id: Option<Value>,
+}
+
+/// Error object returned by the server in place of a result
+#[derive(Clone, Debug)]
+pub struct ResponseError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl std::fmt::Display for ResponseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "language server error {}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for ResponseError {}
+
+type ResponseSender = oneshot::Sender<std::result::Result<Value, ResponseError>>;
+
+/// A request that has been sent and is waiting for its response
+pub struct PendingRequest<T> {
+    pub id: i64,
+    receiver: oneshot::Receiver<std::result::Result<Value, ResponseError>>,
+    _result: PhantomData<T>,
+}
+
+impl<T: DeserializeOwned> PendingRequest<T> {
+    /// Wait for the response and decode it
+    pub async fn response(self) -> Result<T> {
+        let value = self
+            .receiver
+            .await
+            .map_err(|_| anyhow!("request {} was cancelled or the server exited", self.id))??;
+        serde_json::from_value(value).context("failed to decode language server response")
+    }
+}
+
+/// State shared between the owning handle and the reader thread
+struct Shared {
+    stdin: Mutex<Option<ChildStdin>>,
+    pending: Mutex<HashMap<i64, ResponseSender>>,
+    subscribers: Mutex<Vec<mpsc::UnboundedSender<ServerNotification>>>,
+    /// Frames written before `initialize` completed, flushed afterwards
+    queued: Mutex<Vec<Vec<u8>>>,
+    initialized: AtomicBool,
+    running: AtomicBool,
+    capabilities: Mutex<Option<lsp_types::ServerCapabilities>>,
+    /// Settings given with `set_configuration`, which `workspace/configuration`
+    /// requests are answered from
+    configuration: Mutex<Value>,
+}
+
+impl Shared {
+    fn write_frame(&self, frame: &[u8]) {
+        let mut stdin = self.stdin.lock();
+        if let Some(stdin) = stdin.as_mut() {
+            if let Err(e) = stdin.write_all(frame).and_then(|_| stdin.flush()) {
+                tracing::error!("⚠️  Failed to write to language server: {}", e);
+            }
+        }
+    }
+
+    /// Write a frame now, or queue it until the server is initialized
+    fn send_frame(&self, frame: Vec<u8>) {
+        let mut queued = self.queued.lock();
+        if !self.initialized.load(Ordering::SeqCst) {
+            queued.push(frame);
+            return;
+        }
+        drop(queued);
+        self.write_frame(&frame);
+    }
+
+    fn broadcast(&self, notification: ServerNotification) {
+        let mut subscribers = self.subscribers.lock();
+        subscribers.retain(|subscriber| !subscriber.is_closed());
+        // Editors sharing the server would each answer a request; only the
+        // one listening longest hears it
+        let listeners = match notification.id {
+            Some(_) => &subscribers[..subscribers.len().min(1)],
+            None => &subscribers[..],
+        };
+        for subscriber in listeners {
+            let _ = subscriber.unbounded_send(notification.clone());
+        }
+    }
+
+    /// Fail every outstanding request, used when the process goes away
+    fn fail_pending(&self) {
+        for (_, sender) in self.pending.lock().drain() {
+            let _ = sender.send(Err(ResponseError {
+                code: -32099,
+                message: "language server exited".into(),
+            }));
+        }
+    }
+}
+
+fn workspace_folder(path: &Path) -> Value {
+    let name = path
+        .file_name()
+        .map_or_else(|| "workspace".to_string(), |name| name.to_string_lossy().to_string());
+    json!({ "uri": path_to_file_url(path), "name": name })
+}
+
+fn encode_frame(message: &Value) -> Vec<u8> {
+    let body = message.to_string();
+    let mut frame = format!("Content-Length: {}\r\n\r\n", body.len()).into_bytes();
+    frame.extend_from_slice(body.as_bytes());
+    frame
+}
+
+/// Read one `Content-Length` framed message, `Ok(None)` on end of stream
+fn read_frame(reader: &mut impl BufRead) -> std::io::Result<Option<Value>> {
+    let mut content_length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            content_length = value.trim().parse::<usize>().ok();
+        }
+    }
+
+    let length = content_length.ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, "missing Content-Length header")
+    })?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+/// How often a shutting down server is checked on
+const SHUTDOWN_POLL: Duration = Duration::from_millis(10);
+
+/// Every server this process started, for stopping whatever is left when
+/// the plugin unloads, with no editor around to ask
+static STARTED: Mutex<Vec<Weak<LanguageServer>>> = parking_lot::const_mutex(Vec::new());
+
+/// The servers started here whose process hasn't exited, including any
+/// being shut down in the background
+pub fn running_servers() -> Vec<Arc<LanguageServer>> {
+    let mut started = STARTED.lock();
+    started.retain(|server| server.strong_count() > 0);
+    started
+        .iter()
+        .filter_map(Weak::upgrade)
+        .filter(|server| server.exit_status().is_none())
+        .collect()
+}
+
+/// A running language server process
+pub struct LanguageServer {
+    name: String,
+    language_id: String,
+    root: PathBuf,
+    next_id: AtomicI64,
+    shared: Arc<Shared>,
+    child: Mutex<Child>,
+    started_at: Instant,
+    /// Sent with `initialize`, from the user's settings for the language
+    initialization_options: Option<Value>,
+    /// Set when the editor stopped the process, so its exit isn't a crash
+    stopped: AtomicBool,
+    /// Folders the server works on: its root, then any added since
+    folders: Mutex<Vec<PathBuf>>,
+}
+
+impl LanguageServer {
+    /// Spawn the server and begin the `initialize` handshake in the background.
+    ///
+    /// Messages sent before the handshake finishes are queued and flushed once
+    /// the server has answered `initialize`.
+    pub fn start(command: &ServerCommand, language_id: &str, root: &Path) -> Result<Arc<Self>> {
+        let mut cmd = Command::new(&command.command);
+        cmd.args(&command.args)
+            .envs(command.env.iter().map(|(name, value)| (name, value)))
+            .current_dir(command.cwd.as_deref().unwrap_or(root))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        #[cfg(target_os = "windows")]
+        {
+            use std::os::windows::process::CommandExt;
+            const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+            cmd.creation_flags(CREATE_NO_WINDOW);
+        }
+
+        let mut child = cmd
+            .spawn()
+            .with_context(|| format!("failed to spawn language server `{}`", command.command))?;
+
+        let stdin = child.stdin.take().context("language server has no stdin")?;
+        let stdout = child.stdout.take().context("language server has no stdout")?;
+        let stderr = child.stderr.take();
+
+        let shared = Arc::new(Shared {
+            stdin: Mutex::new(Some(stdin)),
+            pending: Mutex::new(HashMap::new()),
+            subscribers: Mutex::new(Vec::new()),
+            queued: Mutex::new(Vec::new()),
+            initialized: AtomicBool::new(false),
+            running: AtomicBool::new(true),
+            capabilities: Mutex::new(None),
+            configuration: Mutex::new(Value::Null),
+        });
+
+        let server = Arc::new(Self {
+            name: command.name.clone(),
+            language_id: language_id.to_string(),
+            root: root.to_path_buf(),
+            next_id: AtomicI64::new(1),
+            shared: shared.clone(),
+            child: Mutex::new(child),
+            started_at: Instant::now(),
+            initialization_options: command.initialization_options.clone(),
+            stopped: AtomicBool::new(false),
+            folders: Mutex::new(vec![root.to_path_buf()]),
+        });
+
+        let reader_shared = shared.clone();
+        let reader_name = command.name.clone();
+        std::thread::Builder::new()
+            .name(format!("lsp-reader-{}", command.name))
+            .spawn(move || Self::reader_loop(reader_name, stdout, reader_shared))
+            .context("failed to spawn language server reader thread")?;
+
+        if let Some(stderr) = stderr {
+            let stderr_name = command.name.clone();
+            let _ = std::thread::Builder::new()
+                .name(format!("lsp-stderr-{}", command.name))
+                .spawn(move || {
+                    for line in BufReader::new(stderr).lines().map_while(|l| l.ok()) {
+                        tracing::debug!("[{}] {}", stderr_name, line);
+                    }
+                });
+        }
+
+        server.initialize();
+        STARTED.lock().push(Arc::downgrade(&server));
+        tracing::debug!(
+            "🚀 Started language server {} for {} (root: {:?})",
+            server.name,
+            server.language_id,
+            server.root
+        );
+        Ok(server)
+    }
+
+    fn reader_loop(name: String, stdout: impl Read, shared: Arc<Shared>) {
+        let mut reader = BufReader::new(stdout);
+        loop {
+            let message = match read_frame(&mut reader) {
+                Ok(Some(message)) => message,
+                Ok(None) => break,
+                Err(e) => {
+                    tracing::error!("⚠️  [{}] Malformed message from language server: {}", name, e);
+                    continue;
+                }
+            };
+
+            let id = message.get("id").cloned();
+            let method = message.get("method").and_then(|m| m.as_str()).map(str::to_string);
+
+            match (id, method) {
+                // Response to one of our requests
+                (Some(id), None) => {
+                    let Some(id) = id.as_i64() else { continue };
+                    let Some(sender) = shared.pending.lock().remove(&id) else {
+                        continue;
+                    };
+                    let result = if let Some(error) = message.get("error") {
+                        Err(ResponseError {
+                            code: error.get("code").and_then(|c| c.as_i64()).unwrap_or(0),
+                            message: error
+                                .get("message")
+                                .and_then(|m| m.as_str())
+                                .unwrap_or_default()
+                                .to_string(),
+                        })
+                    } else {
+                        Ok(message.get("result").cloned().unwrap_or(Value::Null))
+                    };
+                    let _ = sender.send(result);
+                }
+                // Request from the server that expects an answer
+                (Some(id), Some(method)) => {
+                    let params = message.get("params").cloned().unwrap_or(Value::Null);
+                    // Waits for the user to pick an action, or for the editor to
+                    // apply the edit, unless nobody could
+                    let forwarded = matches!(method.as_str(), "window/showMessageRequest" | "workspace/applyEdit");
+                    if forwarded && !shared.subscribers.lock().is_empty() {
+                        shared.broadcast(ServerNotification { method, params, id: Some(id) });
+                        continue;
+                    }
+                    let result = Self::default_server_request_result(&method, &params, &shared.configuration.lock());
+                    shared.write_frame(&encode_frame(&json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "result": result,
+                    })));
+                }
+                // Notification
+                (None, Some(method)) => {
+                    let params = message.get("params").cloned().unwrap_or(Value::Null);
+                    shared.broadcast(ServerNotification { method, params, id: None });
+                }
+                (None, None) => {}
+            }
+        }
+
+        tracing::debug!("[{}] Language server output closed", name);
+        shared.running.store(false, Ordering::SeqCst);
+        shared.fail_pending();
+        shared.subscribers.lock().clear();
+    }
+
+    /// Answer server-to-client requests the editor has no UI for yet
+    fn default_server_request_result(method: &str, params: &Value, configuration: &Value) -> Value {
+        match method {
+            // The requested section of each item, `null` for ones never set
+            "workspace/configuration" => {
+                let items = params.get("items").and_then(|items| items.as_array());
+                Value::Array(
+                    items
+                        .into_iter()
+                        .flatten()
+                        .map(|item| {
+                            let section = item.get("section").and_then(|section| section.as_str());
+                            configuration_section(configuration, section)
+                        })
+                        .collect(),
+                )
+            }
+            // Only reached with no editor listening to apply it
+            "workspace/applyEdit" => json!({ "applied": false, "failureReason": "No editor is open to apply it" }),
+            _ => Value::Null,
+        }
+    }
+
+    fn initialize(self: &Arc<Self>) {
+        let mut params = json!({
+            "processId": std::process::id(),
+            "clientInfo": { "name": "Pulsar Script Editor" },
+            "rootUri": path_to_file_url(&self.root),
+            "workspaceFolders": [workspace_folder(&self.root)],
+            "capabilities": Self::client_capabilities(),
+        });
+        if let Some(options) = &self.initialization_options {
+            params["initializationOptions"] = options.clone();
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let (sender, receiver) = oneshot::channel();
+        self.shared.pending.lock().insert(id, sender);
+        // `initialize` bypasses the queue, everything else waits for its answer
+        self.shared.write_frame(&encode_frame(&json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "initialize",
+            "params": params,
+        })));
+
+        let shared = self.shared.clone();
+        let name = self.name.clone();
+        let _ = std::thread::Builder::new()
+            .name(format!("lsp-init-{}", self.name))
+            .spawn(move || match futures::executor::block_on(receiver) {
+                Ok(Ok(result)) => {
+                    let capabilities = result
+                        .get("capabilities")
+                        .cloned()
+                        .and_then(|c| serde_json::from_value(c).ok());
+                    *shared.capabilities.lock() = capabilities;
+
+                    shared.write_frame(&encode_frame(&json!({
+                        "jsonrpc": "2.0",
+                        "method": "initialized",
+                        "params": {},
+                    })));
+
+                    let mut queued = shared.queued.lock();
+                    shared.initialized.store(true, Ordering::SeqCst);
+                    for frame in queued.drain(..) {
+                        shared.write_frame(&frame);
+                    }
+                    tracing::debug!("✓ [{}] Language server initialized", name);
+                    shared.broadcast(ServerNotification {
+                        method: "initialized".to_string(),
+                        params: Value::Null,
+                        id: None,
+                    });
+                }
+                Ok(Err(e)) => tracing::error!("⚠️  [{}] initialize failed: {}", name, e),
+                Err(_) => tracing::error!("⚠️  [{}] exited before initializing", name),
+            });
+    }
+
+    /// Capabilities this client advertises during `initialize`
+    fn client_capabilities() -> Value {
+        json!({
+            "general": { "positionEncodings": ["utf-16"] },
+            "window": {
+                "workDoneProgress": true,
+                "showMessage": { "messageActionItem": { "additionalPropertiesSupport": false } },
+            },
+            "workspace": {
+                "workspaceEdit": {
+                    "documentChanges": true,
+                    "resourceOperations": ["create", "rename", "delete"],
+                    // Checked in full up front, and stopped at the first change the disk refuses
+                    "failureHandling": "abort",
+                },
+                "symbol": { "dynamicRegistration": false },
+                "applyEdit": true,
+                "executeCommand": { "dynamicRegistration": false },
+                "configuration": true,
+                "didChangeConfiguration": { "dynamicRegistration": false },
+                "workspaceFolders": true,
+            },
+            "textDocument": {
+                "synchronization": { "didSave": true, "dynamicRegistration": false },
+                "completion": {
+                    "completionItem": {
+                        "snippetSupport": true,
+                        "insertReplaceSupport": false,
+                        "documentationFormat": ["markdown", "plaintext"],
+                    },
+                    "contextSupport": true,
+                },
+                "hover": { "contentFormat": ["markdown", "plaintext"] },
+                "definition": { "linkSupport": true },
+                "documentHighlight": { "dynamicRegistration": false },
+                "documentSymbol": { "hierarchicalDocumentSymbolSupport": true },
+                "signatureHelp": {
+                    "signatureInformation": {
+                        "documentationFormat": ["markdown", "plaintext"],
+                        "parameterInformation": { "labelOffsetSupport": true },
+                        "activeParameterSupport": true,
+                    },
+                    "contextSupport": false,
+                },
+                "formatting": { "dynamicRegistration": false },
+                "rangeFormatting": { "dynamicRegistration": false },
+                "rename": { "prepareSupport": true },
+                "callHierarchy": { "dynamicRegistration": false },
+                "typeHierarchy": { "dynamicRegistration": false },
+                "codeAction": {
+                    "codeActionLiteralSupport": {
+                        "codeActionKind": {
+                            "valueSet": [
+                                "", "quickfix", "refactor", "refactor.extract", "refactor.inline",
+                                "refactor.rewrite", "source", "source.organizeImports", "source.fixAll",
+                            ],
+                        },
+                    },
+                    "isPreferredSupport": true,
+                    "disabledSupport": true,
+                    "dataSupport": true,
+                    "resolveSupport": { "properties": ["edit"] },
+                },
+                "foldingRange": { "lineFoldingOnly": true },
+                "inlayHint": { "resolveSupport": { "properties": ["tooltip", "label.tooltip"] } },
+                "semanticTokens": {
+                    "requests": { "full": { "delta": true } },
+                    "tokenTypes": TOKEN_TYPES,
+                    "tokenModifiers": TOKEN_MODIFIERS,
+                    "formats": ["relative"],
+                    "overlappingTokenSupport": false,
+                    "multilineTokenSupport": false,
+                },
+            },
+        })
+    }
+
+    /// Human-readable server name (e.g. `rust-analyzer`)
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// LSP language id this server was started for
+    pub fn language_id(&self) -> &str {
+        &self.language_id
+    }
+
+    /// Workspace root the server was initialized with
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Whether the server process is still producing output
+    pub fn is_running(&self) -> bool {
+        self.shared.running.load(Ordering::SeqCst)
+    }
+
+    /// How long ago the process was started
+    pub fn uptime(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+
+    /// The exit status of a process that has exited
+    pub fn exit_status(&self) -> Option<ExitStatus> {
+        self.child.lock().try_wait().ok().flatten()
+    }
+
+    /// End the process on purpose, e.g. to restart it
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        self.shared.stdin.lock().take();
+        let _ = self.child.lock().kill();
+    }
+
+    /// Send `shutdown`, the first half of ending the process the way the
+    /// protocol has it, and hand back its answer for `finish_shutdown` to wait
+    /// for. `None` for a server that never answered `initialize`, which would
+    /// only queue it.
+    pub fn begin_shutdown(&self) -> Option<PendingRequest<Value>> {
+        self.stopped.store(true, Ordering::SeqCst);
+        if !self.is_running() || !self.shared.initialized.load(Ordering::SeqCst) {
+            return None;
+        }
+        Some(self.request_raw("shutdown", Value::Null))
+    }
+
+    /// Send `exit` once `shutdown` is answered and wait for the process to
+    /// end, killing it if it's still there at `deadline`.
+    ///
+    /// Returns whether it exited by itself.
+    pub fn finish_shutdown(&self, pending: Option<PendingRequest<Value>>, deadline: Instant) -> bool {
+        if let Some(mut pending) = pending {
+            while Instant::now() < deadline && matches!(pending.receiver.try_recv(), Ok(None)) {
+                std::thread::sleep(SHUTDOWN_POLL);
+            }
+            self.notify_raw("exit", Value::Null);
+            self.shared.stdin.lock().take();
+            while Instant::now() < deadline {
+                if self.exit_status().is_some() {
+                    return true;
+                }
+                std::thread::sleep(SHUTDOWN_POLL);
+            }
+        }
+        self.shared.stdin.lock().take();
+        let mut child = self.child.lock();
+        let _ = child.kill();
+        let _ = child.wait();
+        false
+    }
+
+    /// Whether the server takes more folders after starting, through
+    /// `add_workspace_folder`
+    pub fn supports_workspace_folders(&self) -> bool {
+        let folders = self
+            .capabilities()
+            .and_then(|capabilities| capabilities.workspace?.workspace_folders);
+        folders.is_some_and(|folders| {
+            folders.supported == Some(true)
+                && matches!(folders.change_notifications, Some(OneOf::Left(true) | OneOf::Right(_)))
+        })
+    }
+
+    pub fn workspace_folders(&self) -> Vec<PathBuf> {
+        self.folders.lock().clone()
+    }
+
+    /// Give the server another project to work on, alongside its root
+    pub fn add_workspace_folder(&self, folder: &Path) {
+        let mut folders = self.folders.lock();
+        if folders.iter().any(|known| known == folder) {
+            return;
+        }
+        folders.push(folder.to_path_buf());
+        self.notify_raw(
+            "workspace/didChangeWorkspaceFolders",
+            json!({ "event": { "added": [workspace_folder(folder)], "removed": [] } }),
+        );
+    }
+
+    /// Take back a folder given with `add_workspace_folder`
+    pub fn remove_workspace_folder(&self, folder: &Path) {
+        let mut folders = self.folders.lock();
+        let Some(ix) = folders.iter().position(|known| known == folder) else {
+            return;
+        };
+        folders.remove(ix);
+        self.notify_raw(
+            "workspace/didChangeWorkspaceFolders",
+            json!({ "event": { "added": [], "removed": [workspace_folder(folder)] } }),
+        );
+    }
+
+    /// Whether the process was ended by `stop` rather than exiting on its own
+    pub fn was_stopped(&self) -> bool {
+        self.stopped.load(Ordering::SeqCst)
+    }
+
+    /// Capabilities reported by the server, `None` until `initialize` completes
+    pub fn capabilities(&self) -> Option<lsp_types::ServerCapabilities> {
+        self.shared.capabilities.lock().clone()
+    }
+
+    /// Subscribe to notifications pushed by the server, plus an `initialized`
+    /// one of the client's own once the server has answered `initialize`
+    pub fn subscribe(&self) -> mpsc::UnboundedReceiver<ServerNotification> {
+        let (sender, receiver) = mpsc::unbounded();
+        self.shared.subscribers.lock().push(sender);
+        receiver
+    }
+
+    /// Answer a request the server sent, forwarded with its `id`
+    pub fn respond(&self, id: Value, result: Value) {
+        self.shared.write_frame(&encode_frame(&json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "result": result,
+        })));
+    }
+
+    /// Send a typed request
+    pub fn request<R>(&self, params: R::Params) -> PendingRequest<R::Result>
+    where
+        R: lsp_types::request::Request,
+    {
+        let params = serde_json::to_value(params).unwrap_or(Value::Null);
+        self.request_raw(R::METHOD, params)
+    }
+
+    /// Send a request with untyped params
+    pub fn request_raw<T>(&self, method: &str, params: Value) -> PendingRequest<T> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let (sender, receiver) = oneshot::channel();
+
+        if self.is_running() {
+            self.shared.pending.lock().insert(id, sender);
+            self.shared.send_frame(encode_frame(&json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": method,
+                "params": params,
+            })));
+        } else {
+            let _ = sender.send(Err(ResponseError {
+                code: -32099,
+                message: "language server is not running".into(),
+            }));
+        }
+
+        PendingRequest {
+            id,
+            receiver,
+            _result: PhantomData,
+        }
+    }
+
+    /// Cancel an in-flight request; its pending response resolves as an error
+    pub fn cancel(&self, id: i64) {
+        if self.shared.pending.lock().remove(&id).is_some() {
+            self.notify_raw("$/cancelRequest", json!({ "id": id }));
+        }
+    }
+
+    /// Send a typed notification
+    pub fn notify<N>(&self, params: N::Params)
+    where
+        N: lsp_types::notification::Notification,
+    {
+        let params = serde_json::to_value(params).unwrap_or(Value::Null);
+        self.notify_raw(N::METHOD, params);
+    }
+
+    /// Replace the settings the server is told about, now and whenever it
+    /// asks for them again
+    pub fn set_configuration(&self, settings: Value) {
+        *self.shared.configuration.lock() = settings.clone();
+        self.notify_raw("workspace/didChangeConfiguration", json!({ "settings": settings }));
+    }
+
+    /// Send a notification with untyped params
+    pub fn notify_raw(&self, method: &str, params: Value) {
+        if !self.is_running() {
+            return;
+        }
+        self.shared.send_frame(encode_frame(&json!({
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": params,
+        })));
+    }
+
+    pub fn did_open(&self, path: &Path, version: i32, text: &str) {
+        self.notify_raw(
+            "textDocument/didOpen",
+            json!({
+                "textDocument": {
+                    "uri": path_to_file_url(path),
+                    "languageId": self.language_id,
+                    "version": version,
+                    "text": text,
+                }
+            }),
+        );
+    }
+
+    /// Whether the server asked for only the changed ranges of documents
+    pub fn syncs_incrementally(&self) -> bool {
+        self.capabilities()
+            .is_some_and(|caps| sync_kind(&caps) == lsp_types::TextDocumentSyncKind::INCREMENTAL)
+    }
+
+    /// Report changes to a document: one replaced range each when the server
+    /// syncs incrementally (see `incremental_change`), or the whole new text.
+    /// Servers whose sync kind is `NONE` aren't told.
+    pub fn did_change(&self, path: &Path, version: i32, changes: Vec<lsp_types::TextDocumentContentChangeEvent>) {
+        if self.capabilities().is_some_and(|caps| !wants_changes(&caps)) {
+            return;
+        }
+        self.notify_raw(
+            "textDocument/didChange",
+            json!({
+                "textDocument": { "uri": path_to_file_url(path), "version": version },
+                "contentChanges": changes,
+            }),
+        );
+    }
+
+    pub fn did_save(&self, path: &Path, text: &str) {
+        self.notify_raw(
+            "textDocument/didSave",
+            json!({
+                "textDocument": { "uri": path_to_file_url(path) },
+                "text": text,
+            }),
+        );
+    }
+
+    pub fn did_close(&self, path: &Path) {
+        self.notify_raw(
+            "textDocument/didClose",
+            json!({ "textDocument": { "uri": path_to_file_url(path) } }),
+        );
+    }
+}
+
+impl Drop for LanguageServer {
+    fn drop(&mut self) {
+        self.shared.stdin.lock().take();
+        let _ = self.child.lock().kill();
+    }
+}
+
+/// The part of `settings` a `workspace/configuration` item asks for: all of
+/// it without a section, or the value at a dotted path like `python.analysis`
+fn configuration_section(settings: &Value, section: Option<&str>) -> Value {
+    let Some(section) = section.filter(|section| !section.is_empty()) else {
+        return settings.clone();
+    };
+    section
+        .split('.')
+        .try_fold(settings, |value, key| value.get(key))
+        .cloned()
+        .unwrap_or(Value::Null)
+}
@@ -0,0 +1,198 @@
+//! Quick fixes, refactors and source actions from `textDocument/codeAction`
+
+use lsp_types::{
+    CodeAction, CodeActionContext, CodeActionKind, CodeActionOrCommand, CodeActionParams,
+    CodeActionProviderCapability, CodeActionResponse, Diagnostic, ServerCapabilities,
+    TextDocumentIdentifier,
+};
+use std::ops::Range;
+use std::path::Path;
+
+use super::convert::{offsets_to_range, path_to_uri};
+
+/// An entry of the code action menu
+#[derive(Clone, Debug)]
+pub struct CodeActionItem {
+    pub title: String,
+    /// Section of the menu it is listed under
+    pub group: &'static str,
+    pub preferred: bool,
+    /// Why the server says the action can't be applied here
+    pub disabled: Option<String>,
+    pub action: CodeActionOrCommand,
+}
+
+/// Menu sections, in the order they are listed
+const GROUPS: [&str; 4] = ["Quick Fix", "Refactor", "Source Action", "Other"];
+
+/// Request the actions for `range` of a document, passing the diagnostics it
+/// overlaps so the server can offer fixes for them
+pub fn code_action_params(
+    path: &Path,
+    text: &str,
+    range: Range<usize>,
+    diagnostics: Vec<Diagnostic>,
+) -> Option<CodeActionParams> {
+    Some(CodeActionParams {
+        text_document: TextDocumentIdentifier { uri: path_to_uri(path)? },
+        range: offsets_to_range(text, range),
+        context: CodeActionContext {
+            diagnostics,
+            only: None,
+            trigger_kind: None,
+        },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    })
+}
+
+/// Request the server's organize imports action for a whole document
+pub fn organize_imports_params(path: &Path, text: &str) -> Option<CodeActionParams> {
+    let mut params = code_action_params(path, text, 0..text.len(), Vec::new())?;
+    params.context.only = Some(vec![CodeActionKind::SOURCE_ORGANIZE_IMPORTS]);
+    Some(params)
+}
+
+/// Whether the server lists organizing imports among its code action kinds;
+/// one that doesn't list any isn't asked
+pub fn offers_organize_imports(capabilities: Option<&ServerCapabilities>) -> bool {
+    let kinds = capabilities
+        .and_then(|caps| caps.code_action_provider.as_ref())
+        .and_then(|provider| match provider {
+            CodeActionProviderCapability::Options(options) => options.code_action_kinds.as_ref(),
+            CodeActionProviderCapability::Simple(_) => None,
+        });
+    kinds.is_some_and(|kinds| {
+        kinds.iter().any(|kind| {
+            // A kind covers the ones under it
+            let organize = CodeActionKind::SOURCE_ORGANIZE_IMPORTS;
+            organize.as_str() == kind.as_str() || organize.as_str().starts_with(&format!("{}.", kind.as_str()))
+        })
+    })
+}
+
+/// The organize imports action of a response, unless the server disabled it
+pub fn organize_imports_action(response: CodeActionResponse) -> Option<CodeAction> {
+    response.into_iter().find_map(|action| match action {
+        CodeActionOrCommand::CodeAction(action)
+            if action.disabled.is_none()
+                && action.kind.as_ref().is_some_and(|kind| {
+                    kind.as_str().starts_with(CodeActionKind::SOURCE_ORGANIZE_IMPORTS.as_str())
+                }) =>
+        {
+            Some(action)
+        }
+        _ => None,
+    })
+}
+
+/// Menu entries for a response, grouped by kind with preferred fixes first
+pub fn code_action_items(response: CodeActionResponse) -> Vec<CodeActionItem> {
+    let mut items: Vec<CodeActionItem> = response
+        .into_iter()
+        .map(|action| match &action {
+            CodeActionOrCommand::Command(command) => CodeActionItem {
+                title: command.title.clone(),
+                group: group_label(None),
+                preferred: false,
+                disabled: None,
+                action,
+            },
+            CodeActionOrCommand::CodeAction(code_action) => CodeActionItem {
+                title: code_action.title.clone(),
+                group: group_label(code_action.kind.as_ref()),
+                preferred: code_action.is_preferred == Some(true),
+                disabled: code_action.disabled.as_ref().map(|disabled| disabled.reason.clone()),
+                action,
+            },
+        })
+        .collect();
+    items.sort_by_key(|item| {
+        let group = GROUPS.iter().position(|group| *group == item.group);
+        (group, !item.preferred, item.disabled.is_some())
+    });
+    items
+}
+
+/// Menu section for an action's kind
+pub fn group_label(kind: Option<&CodeActionKind>) -> &'static str {
+    let kind = kind.map(|kind| kind.as_str()).unwrap_or_default();
+    let family = kind.split('.').next().unwrap_or_default();
+    match family {
+        "quickfix" => GROUPS[0],
+        "refactor" => GROUPS[1],
+        "source" => GROUPS[2],
+        _ => GROUPS[3],
+    }
+}
+
+/// Whether the server asked for `codeAction/resolve` to fill in the action's edit
+pub fn needs_resolve(action: &CodeAction, capabilities: Option<&ServerCapabilities>) -> bool {
+    let resolves = capabilities
+        .and_then(|caps| caps.code_action_provider.as_ref())
+        .is_some_and(|provider| match provider {
+            CodeActionProviderCapability::Options(options) => options.resolve_provider == Some(true),
+            CodeActionProviderCapability::Simple(_) => false,
+        });
+    resolves && action.edit.is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::Command;
+
+    fn action(title: &str, kind: CodeActionKind, preferred: bool) -> CodeActionOrCommand {
+        CodeActionOrCommand::CodeAction(CodeAction {
+            title: title.to_string(),
+            kind: Some(kind),
+            is_preferred: Some(preferred),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_code_action_items_are_grouped() {
+        let items = code_action_items(vec![
+            action("Organize imports", CodeActionKind::SOURCE_ORGANIZE_IMPORTS, false),
+            CodeActionOrCommand::Command(Command::new("Run test".to_string(), "runTest".to_string(), None)),
+            action("Extract function", CodeActionKind::REFACTOR_EXTRACT, false),
+            action("Import `HashMap`", CodeActionKind::QUICKFIX, false),
+            action("Add missing `;`", CodeActionKind::QUICKFIX, true),
+        ]);
+        let listed: Vec<(&str, &str)> = items.iter().map(|item| (item.group, item.title.as_str())).collect();
+        assert_eq!(
+            listed,
+            [
+                ("Quick Fix", "Add missing `;`"),
+                ("Quick Fix", "Import `HashMap`"),
+                ("Refactor", "Extract function"),
+                ("Source Action", "Organize imports"),
+                ("Other", "Run test"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_organize_imports() {
+        let capabilities = |kinds: Vec<CodeActionKind>| ServerCapabilities {
+            code_action_provider: Some(CodeActionProviderCapability::Options(lsp_types::CodeActionOptions {
+                code_action_kinds: Some(kinds),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        assert!(offers_organize_imports(Some(&capabilities(vec![CodeActionKind::SOURCE]))));
+        assert!(!offers_organize_imports(Some(&capabilities(vec![CodeActionKind::QUICKFIX]))));
+        assert!(!offers_organize_imports(Some(&ServerCapabilities {
+            code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+            ..Default::default()
+        })));
+
+        let action = organize_imports_action(vec![
+            action("Add missing `;`", CodeActionKind::QUICKFIX, true),
+            action("Organize Imports", CodeActionKind::new("source.organizeImports.ts"), false),
+        ]);
+        assert_eq!(action.map(|action| action.title), Some("Organize Imports".to_string()));
+    }
+}
@@ -0,0 +1,257 @@
+//! Completion provider backed by a language server
+
+use anyhow::{anyhow, Result};
+use gpui::{Context, Task, Window};
+use lsp_types::{
+    CompletionContext, CompletionItem, CompletionParams, CompletionResponse, CompletionTextEdit,
+    InsertTextFormat, TextEdit,
+};
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use ui::input::{CompletionProvider, InputState, Rope};
+
+use super::client::LanguageServer;
+use super::convert::{position_to_offset, text_document_position};
+use super::diagnostics::remap_range;
+use super::request_tracker::{DocumentVersion, RequestTracker, TrackedRequest};
+use super::super::snippets::{parse_snippet, ParsedSnippet};
+
+/// How long typing must pause before completions are requested. Waiting also
+/// lets the keystroke's `didChange` reach the server first.
+const COMPLETION_DEBOUNCE: Duration = Duration::from_millis(50);
+
+/// What accepting one of the latest completions brings along, by the plain
+/// text the menu inserts for it
+#[derive(Default)]
+pub struct CompletionExtras {
+    /// Snippets, so accepting one can start filling in its tab stops
+    pub snippets: HashMap<String, ParsedSnippet>,
+    /// Edits besides the insertion, mostly an import of what's inserted
+    pub additional_edits: HashMap<String, Vec<TextEdit>>,
+}
+
+impl CompletionExtras {
+    fn clear(&mut self) {
+        self.snippets.clear();
+        self.additional_edits.clear();
+    }
+}
+
+pub type SharedCompletionExtras = Arc<Mutex<CompletionExtras>>;
+
+/// Sends `textDocument/completion` for one document.
+///
+/// Only the latest request matters: issuing a new one cancels the previous
+/// request, and responses computed against an older buffer never reach the
+/// menu.
+pub struct LspCompletionProvider {
+    server: Arc<LanguageServer>,
+    path: PathBuf,
+    version: DocumentVersion,
+    requests: RequestTracker,
+    extras: SharedCompletionExtras,
+}
+
+impl LspCompletionProvider {
+    pub fn new(
+        server: Arc<LanguageServer>,
+        path: PathBuf,
+        version: DocumentVersion,
+        extras: SharedCompletionExtras,
+    ) -> Self {
+        Self {
+            server,
+            path,
+            version,
+            requests: RequestTracker::default(),
+            extras,
+        }
+    }
+
+    fn trigger_characters(&self) -> Vec<String> {
+        self.server
+            .capabilities()
+            .and_then(|caps| caps.completion_provider)
+            .and_then(|provider| provider.trigger_characters)
+            .unwrap_or_default()
+    }
+}
+
+impl CompletionProvider for LspCompletionProvider {
+    fn completions(
+        &self,
+        text: &Rope,
+        offset: usize,
+        trigger: CompletionContext,
+        _window: &mut Window,
+        cx: &mut Context<InputState>,
+    ) -> Task<Result<CompletionResponse>> {
+        if let Some(previous) = self.requests.take() {
+            self.server.cancel(previous);
+        }
+
+        let Some(position) = text_document_position(&self.path, &text.to_string(), offset) else {
+            return Task::ready(Ok(CompletionResponse::Array(Vec::new())));
+        };
+
+        let params = CompletionParams {
+            text_document_position: position,
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            context: Some(trigger),
+        };
+
+        let server = self.server.clone();
+        let version = self.version.clone();
+        let requests = self.requests.clone();
+        let extras = self.extras.clone();
+        let timer = cx.background_executor().timer(COMPLETION_DEBOUNCE);
+
+        cx.background_spawn(async move {
+            timer.await;
+
+            let request = server.request::<lsp_types::request::Completion>(params);
+            let sent = TrackedRequest {
+                id: request.id,
+                version: version.get(),
+            };
+            if let Some(previous) = requests.start(sent) {
+                server.cancel(previous);
+            }
+            let response = request
+                .response()
+                .await?
+                .unwrap_or(CompletionResponse::Array(Vec::new()));
+            if !requests.finish(sent, version.get()) {
+                return Err(anyhow!("completion request {} was superseded", sent.id));
+            }
+            Ok(normalize_completion_response(response, &mut extras.lock()))
+        })
+    }
+
+    fn is_completion_trigger(
+        &self,
+        _offset: usize,
+        new_text: &str,
+        _cx: &mut Context<InputState>,
+    ) -> bool {
+        let Some(last) = new_text.chars().last() else {
+            return false;
+        };
+        if last.is_alphanumeric() || last == '_' {
+            return true;
+        }
+        self.trigger_characters()
+            .iter()
+            .any(|trigger| new_text.ends_with(trigger.as_str()))
+    }
+}
+
+/// Rewrite snippet items as plain text so placeholders are not inserted
+/// literally, remembering their tab stops and every item's additional
+/// edits in `extras`
+fn normalize_completion_response(response: CompletionResponse, extras: &mut CompletionExtras) -> CompletionResponse {
+    extras.clear();
+    let mut normalize = |item| {
+        let mut item = normalize_completion_item(item, &mut extras.snippets);
+        if let Some(edits) = item.additional_text_edits.take().filter(|edits| !edits.is_empty()) {
+            extras.additional_edits.insert(inserted_text(&item).to_string(), edits);
+        }
+        item
+    };
+    match response {
+        CompletionResponse::Array(items) => CompletionResponse::Array(items.into_iter().map(&mut normalize).collect()),
+        CompletionResponse::List(mut list) => {
+            list.items = list.items.into_iter().map(&mut normalize).collect();
+            CompletionResponse::List(list)
+        }
+    }
+}
+
+fn normalize_completion_item(mut item: CompletionItem, snippets: &mut HashMap<String, ParsedSnippet>) -> CompletionItem {
+    if item.insert_text_format != Some(InsertTextFormat::SNIPPET) {
+        return item;
+    }
+
+    let mut expand = |snippet: &mut String| {
+        let parsed = parse_snippet(snippet, "", "\t");
+        *snippet = parsed.text.clone();
+        // Only worth a session with somewhere to go besides the end
+        let end = parsed.text.len();
+        if parsed.stops.len() > 1 || parsed.stops[0] != [end..end] {
+            snippets.insert(parsed.text.clone(), parsed);
+        }
+    };
+    if let Some(insert_text) = item.insert_text.as_mut() {
+        expand(insert_text);
+    }
+    match item.text_edit.as_mut() {
+        Some(CompletionTextEdit::Edit(edit)) => expand(&mut edit.new_text),
+        Some(CompletionTextEdit::InsertAndReplace(edit)) => expand(&mut edit.new_text),
+        None => {}
+    }
+    item.insert_text_format = Some(InsertTextFormat::PLAIN_TEXT);
+    item
+}
+
+/// What the menu puts in the text for `item`
+fn inserted_text(item: &CompletionItem) -> &str {
+    match &item.text_edit {
+        Some(CompletionTextEdit::Edit(edit)) => &edit.new_text,
+        Some(CompletionTextEdit::InsertAndReplace(edit)) => &edit.new_text,
+        None => item.insert_text.as_deref().unwrap_or(&item.label),
+    }
+}
+
+/// The additional edits of a completion just accepted, moved into the text
+/// after it: `old_text` is the text before, in which the server placed
+/// them, and `accepted` the insertion as `(start, old_end, new_end)`.
+/// Edits overlapping the insertion are dropped.
+pub fn accepted_additional_edits(
+    old_text: &str,
+    edits: &[TextEdit],
+    accepted: (usize, usize, usize),
+) -> Vec<(Range<usize>, String)> {
+    let mut moved: Vec<(Range<usize>, String)> = edits
+        .iter()
+        .filter_map(|edit| {
+            let range = position_to_offset(old_text, edit.range.start)..position_to_offset(old_text, edit.range.end);
+            Some((remap_range(range, accepted)?, edit.new_text.clone()))
+        })
+        .collect();
+    moved.sort_by_key(|(range, _)| range.start);
+    moved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::{Position, Range as LspRange};
+
+    #[test]
+    fn test_import_far_above_the_completion() {
+        let old_text = "use std::io;\n\nfn main() {\n    let map = Hash\n}\n";
+        let completed = old_text.find("Hash").unwrap();
+        // `Hash` completed to `HashMap::new()`
+        let accepted = (completed, completed + 4, completed + "HashMap::new()".len());
+        let import = TextEdit {
+            range: LspRange::new(Position::new(0, 12), Position::new(0, 12)),
+            new_text: "\nuse std::collections::HashMap;".to_string(),
+        };
+        let edits = accepted_additional_edits(old_text, &[import], accepted);
+        assert_eq!(edits, vec![(12..12, "\nuse std::collections::HashMap;".to_string())]);
+
+        // One placed after the completion moves along with its length
+        let after = TextEdit {
+            range: LspRange::new(Position::new(4, 0), Position::new(4, 1)),
+            new_text: "};".to_string(),
+        };
+        let end = old_text.rfind('}').unwrap();
+        let shift = "HashMap::new()".len() - 4;
+        assert_eq!(accepted_additional_edits(old_text, &[after], accepted), vec![(end + shift..end + shift + 1, "};".to_string())]);
+    }
+}
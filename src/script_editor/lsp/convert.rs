@@ -0,0 +1,221 @@
+//! Conversions between editor buffer coordinates and LSP coordinates
+//!
+//! The editor addresses text by UTF-8 byte offset while LSP positions use a
+//! zero-based line and a UTF-16 code unit column, so every request and
+//! response crosses this boundary.
+
+use lsp_types::{Position, Range, TextDocumentIdentifier, TextDocumentPositionParams, Uri};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Convert a UTF-8 byte offset into an LSP position (UTF-16 columns).
+///
+/// Offsets past the end of the text clamp to the end; offsets inside a
+/// multi-byte character resolve to the start of that character.
+pub fn offset_to_position(text: &str, offset: usize) -> Position {
+    let offset = offset.min(text.len());
+    let mut line = 0u32;
+    let mut line_start = 0usize;
+
+    for (ix, ch) in text.char_indices() {
+        if ix >= offset {
+            break;
+        }
+        if ch == '\n' {
+            line += 1;
+            line_start = ix + 1;
+        }
+    }
+
+    let character = text[line_start..]
+        .char_indices()
+        .take_while(|(ix, _)| line_start + ix < offset)
+        .map(|(_, ch)| ch.len_utf16() as u32)
+        .sum();
+
+    Position { line, character }
+}
+
+/// Convert an LSP position into a UTF-8 byte offset.
+///
+/// Lines past the end clamp to the end of the text and columns past the end
+/// of a line clamp to the line's end (before its newline).
+pub fn position_to_offset(text: &str, position: Position) -> usize {
+    let mut line_start = 0usize;
+    for _ in 0..position.line {
+        match text[line_start..].find('\n') {
+            Some(newline) => line_start += newline + 1,
+            None => return text.len(),
+        }
+    }
+
+    let line_end = text[line_start..]
+        .find('\n')
+        .map(|newline| line_start + newline)
+        .unwrap_or(text.len());
+    let line_text = &text[line_start..line_end];
+
+    let mut utf16_column = 0u32;
+    for (ix, ch) in line_text.char_indices() {
+        if utf16_column >= position.character {
+            return line_start + ix;
+        }
+        utf16_column += ch.len_utf16() as u32;
+    }
+    line_end
+}
+
+/// Convert an LSP range into a UTF-8 byte range
+pub fn range_to_offsets(text: &str, range: Range) -> std::ops::Range<usize> {
+    let start = position_to_offset(text, range.start);
+    let end = position_to_offset(text, range.end).max(start);
+    start..end
+}
+
+/// Convert a UTF-8 byte range into an LSP range
+pub fn offsets_to_range(text: &str, range: std::ops::Range<usize>) -> Range {
+    Range {
+        start: offset_to_position(text, range.start),
+        end: offset_to_position(text, range.end),
+    }
+}
+
+/// Convert a UTF-8 byte offset into a UTF-16 code unit offset
+pub fn offset_to_utf16(text: &str, offset: usize) -> usize {
+    let offset = offset.min(text.len());
+    text.char_indices()
+        .take_while(|(ix, _)| *ix < offset)
+        .map(|(_, ch)| ch.len_utf16())
+        .sum()
+}
+
+/// Convert a UTF-16 code unit offset into a UTF-8 byte offset
+pub fn utf16_to_offset(text: &str, utf16_offset: usize) -> usize {
+    let mut utf16 = 0usize;
+    for (ix, ch) in text.char_indices() {
+        if utf16 >= utf16_offset {
+            return ix;
+        }
+        utf16 += ch.len_utf16();
+    }
+    text.len()
+}
+
+/// Build a `file://` URL for an absolute path, percent-encoding as needed
+pub fn path_to_file_url(path: &Path) -> String {
+    let raw = path.to_string_lossy().replace('\\', "/");
+    let mut url = String::from("file://");
+    // Windows drive paths (C:/...) need a leading slash to form `file:///C:/...`
+    if !raw.starts_with('/') {
+        url.push('/');
+    }
+    for byte in raw.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => {
+                url.push(byte as char)
+            }
+            _ => url.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    url
+}
+
+/// Parse a `file://` URL back into a filesystem path
+pub fn file_url_to_path(url: &str) -> Option<PathBuf> {
+    let encoded = url.strip_prefix("file://")?;
+    // Skip an optional authority (usually empty or `localhost`)
+    let encoded = &encoded[encoded.find('/')?..];
+
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut ix = 0;
+    while ix < bytes.len() {
+        if bytes[ix] == b'%' && ix + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[ix + 1..ix + 3]).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            ix += 3;
+        } else {
+            decoded.push(bytes[ix]);
+            ix += 1;
+        }
+    }
+    let decoded = String::from_utf8(decoded).ok()?;
+
+    // `/C:/foo` -> `C:/foo` on Windows-style drive paths
+    let is_drive_path = decoded.len() >= 3
+        && decoded.as_bytes()[0] == b'/'
+        && decoded.as_bytes()[1].is_ascii_alphabetic()
+        && decoded.as_bytes()[2] == b':';
+    if is_drive_path {
+        Some(PathBuf::from(&decoded[1..]))
+    } else {
+        Some(PathBuf::from(decoded))
+    }
+}
+
+/// Build an LSP document URI for a path
+pub fn path_to_uri(path: &Path) -> Option<Uri> {
+    Uri::from_str(&path_to_file_url(path)).ok()
+}
+
+/// Resolve an LSP document URI to a filesystem path
+pub fn uri_to_path(uri: &Uri) -> Option<PathBuf> {
+    file_url_to_path(uri.as_str())
+}
+
+/// Build `TextDocumentPositionParams` for a byte offset in a document
+pub fn text_document_position(
+    path: &Path,
+    text: &str,
+    offset: usize,
+) -> Option<TextDocumentPositionParams> {
+    Some(TextDocumentPositionParams {
+        text_document: TextDocumentIdentifier {
+            uri: path_to_uri(path)?,
+        },
+        position: offset_to_position(text, offset),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offset_position_round_trip() {
+        let text = "fn main() {\n    let é = \"😀\";\n}\n";
+        for (offset, _) in text.char_indices() {
+            let position = offset_to_position(text, offset);
+            assert_eq!(position_to_offset(text, position), offset);
+        }
+        // "    let é = \"" is 13 UTF-16 units, the emoji counts as two
+        let emoji = text.find('😀').unwrap();
+        assert_eq!(offset_to_position(text, emoji), Position::new(1, 13));
+        assert_eq!(
+            offset_to_position(text, emoji + '😀'.len_utf8()),
+            Position::new(1, 15)
+        );
+    }
+
+    #[test]
+    fn test_position_clamping() {
+        let text = "ab\ncd";
+        assert_eq!(position_to_offset(text, Position::new(0, 99)), 2);
+        assert_eq!(position_to_offset(text, Position::new(9, 0)), text.len());
+        assert_eq!(offset_to_position(text, 999), Position::new(1, 2));
+    }
+
+    #[test]
+    fn test_file_url_round_trip() {
+        let path = PathBuf::from("/home/user/my project/src/main.rs");
+        let url = path_to_file_url(&path);
+        assert_eq!(url, "file:///home/user/my%20project/src/main.rs");
+        assert_eq!(file_url_to_path(&url), Some(path));
+
+        assert_eq!(
+            file_url_to_path("file:///C:/Users/dev/lib.rs"),
+            Some(PathBuf::from("C:/Users/dev/lib.rs"))
+        );
+        assert_eq!(file_url_to_path("https://example.com"), None);
+    }
+}
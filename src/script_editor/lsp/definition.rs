@@ -0,0 +1,61 @@
+//! Go-to-definition results from a language server
+
+use lsp_types::{GotoDefinitionResponse, Location, LocationLink, Position};
+use std::path::PathBuf;
+
+use super::convert::uri_to_path;
+
+/// A place a definition request resolved to
+#[derive(Clone, Debug, PartialEq)]
+pub struct DefinitionTarget {
+    pub path: PathBuf,
+    /// Start of the symbol's name (zero-based, UTF-16 column)
+    pub position: Position,
+}
+
+impl DefinitionTarget {
+    fn from_location(location: Location) -> Option<Self> {
+        Some(Self {
+            path: uri_to_path(&location.uri)?,
+            position: location.range.start,
+        })
+    }
+
+    fn from_link(link: LocationLink) -> Option<Self> {
+        // Land on the symbol name rather than the start of the whole item
+        Some(Self {
+            path: uri_to_path(&link.target_uri)?,
+            position: link.target_selection_range.start,
+        })
+    }
+}
+
+/// Flatten any of the three response shapes into a de-duplicated target list
+pub fn definition_targets(response: GotoDefinitionResponse) -> Vec<DefinitionTarget> {
+    let targets: Vec<DefinitionTarget> = match response {
+        GotoDefinitionResponse::Scalar(location) => {
+            DefinitionTarget::from_location(location).into_iter().collect()
+        }
+        GotoDefinitionResponse::Array(locations) => locations
+            .into_iter()
+            .filter_map(DefinitionTarget::from_location)
+            .collect(),
+        GotoDefinitionResponse::Link(links) => links
+            .into_iter()
+            .filter_map(DefinitionTarget::from_link)
+            .collect(),
+    };
+
+    let mut unique = Vec::with_capacity(targets.len());
+    for target in targets {
+        if !unique.contains(&target) {
+            unique.push(target);
+        }
+    }
+    unique
+}
+
+/// References as targets, e.g. to peek at them one by one
+pub fn location_targets(locations: Vec<Location>) -> Vec<DefinitionTarget> {
+    definition_targets(GotoDefinitionResponse::Array(locations))
+}
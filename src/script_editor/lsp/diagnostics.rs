@@ -0,0 +1,176 @@
+//! Diagnostics published by language servers
+//!
+//! Ranges are kept as byte offsets into the buffer so they can be shifted
+//! locally while the user types, before the server publishes fresh results.
+
+use lsp_types::{DiagnosticSeverity, NumberOrString};
+use std::ops::Range;
+
+use super::convert::{offset_to_position, offsets_to_range, range_to_offsets};
+
+/// A diagnostic resolved against a file's current text
+#[derive(Clone, Debug)]
+pub struct FileDiagnostic {
+    /// Byte range in the buffer
+    pub range: Range<usize>,
+    pub severity: DiagnosticSeverity,
+    pub message: String,
+    pub source: Option<String>,
+    pub code: Option<String>,
+}
+
+impl FileDiagnostic {
+    pub fn from_lsp(text: &str, diagnostic: lsp_types::Diagnostic) -> Self {
+        Self {
+            range: range_to_offsets(text, diagnostic.range),
+            // Servers may omit the severity; the spec leaves it to the client, treat as an error
+            severity: diagnostic.severity.unwrap_or(DiagnosticSeverity::ERROR),
+            message: diagnostic.message,
+            source: diagnostic.source,
+            code: diagnostic.code.map(|code| match code {
+                NumberOrString::Number(number) => number.to_string(),
+                NumberOrString::String(string) => string,
+            }),
+        }
+    }
+
+    /// The diagnostic as the server sent it, for requests that refer back to it
+    pub fn to_lsp(&self, text: &str) -> lsp_types::Diagnostic {
+        lsp_types::Diagnostic {
+            range: offsets_to_range(text, self.range.clone()),
+            severity: Some(self.severity),
+            code: self.code.as_ref().map(|code| match code.parse() {
+                Ok(number) => NumberOrString::Number(number),
+                Err(_) => NumberOrString::String(code.clone()),
+            }),
+            source: self.source.clone(),
+            message: self.message.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Build the input's diagnostic, which draws the underline and hover message
+pub fn to_input_diagnostic(text: &str, diagnostic: &FileDiagnostic) -> ui::highlighter::Diagnostic {
+    use ui::highlighter::DiagnosticSeverity as Severity;
+    use ui::input::Position;
+
+    let start = offset_to_position(text, diagnostic.range.start);
+    let end = offset_to_position(text, diagnostic.range.end);
+    let severity = match diagnostic.severity {
+        DiagnosticSeverity::ERROR => Severity::Error,
+        DiagnosticSeverity::WARNING => Severity::Warning,
+        DiagnosticSeverity::INFORMATION => Severity::Info,
+        _ => Severity::Hint,
+    };
+
+    let mut input_diagnostic = ui::highlighter::Diagnostic::new(
+        Position::new(start.line, start.character)..Position::new(end.line, end.character),
+        diagnostic.message.clone(),
+    )
+    .with_severity(severity);
+    if let Some(source) = &diagnostic.source {
+        input_diagnostic = input_diagnostic.with_source(source.clone());
+    }
+    if let Some(code) = &diagnostic.code {
+        input_diagnostic = input_diagnostic.with_code(code.clone());
+    }
+    input_diagnostic
+}
+
+/// Worst severity per zero-based line, for gutter markers
+pub fn line_severities(
+    line_of: impl Fn(usize) -> u32,
+    diagnostics: &[FileDiagnostic],
+) -> std::collections::BTreeMap<u32, DiagnosticSeverity> {
+    let mut lines = std::collections::BTreeMap::new();
+    for diagnostic in diagnostics {
+        let line = line_of(diagnostic.range.start);
+        lines
+            .entry(line)
+            .and_modify(|worst: &mut DiagnosticSeverity| {
+                if severity_rank(diagnostic.severity) < severity_rank(*worst) {
+                    *worst = diagnostic.severity;
+                }
+            })
+            .or_insert(diagnostic.severity);
+    }
+    lines
+}
+
+/// Lower is more severe, matching the LSP numbering
+pub fn severity_rank(severity: DiagnosticSeverity) -> u8 {
+    match severity {
+        DiagnosticSeverity::ERROR => 1,
+        DiagnosticSeverity::WARNING => 2,
+        DiagnosticSeverity::INFORMATION => 3,
+        _ => 4,
+    }
+}
+
+/// The single edited region between two versions of a text.
+///
+/// Returns `(start, old_end, new_end)`: bytes before `start` are unchanged,
+/// `old[old_end..]` equals `new[new_end..]`.
+pub fn edit_bounds(old: &str, new: &str) -> (usize, usize, usize) {
+    let mut start = old
+        .bytes()
+        .zip(new.bytes())
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !old.is_char_boundary(start) || !new.is_char_boundary(start) {
+        start -= 1;
+    }
+
+    let max_suffix = (old.len() - start).min(new.len() - start);
+    let mut suffix = old
+        .bytes()
+        .rev()
+        .zip(new.bytes().rev())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !old.is_char_boundary(old.len() - suffix) || !new.is_char_boundary(new.len() - suffix) {
+        suffix -= 1;
+    }
+
+    (start, old.len() - suffix, new.len() - suffix)
+}
+
+/// Move a range across an edit, or `None` if the edit touched it
+pub fn remap_range(range: Range<usize>, edit: (usize, usize, usize)) -> Option<Range<usize>> {
+    let (start, old_end, new_end) = edit;
+    if range.end <= start {
+        Some(range)
+    } else if range.start >= old_end {
+        Some(range.start - old_end + new_end..range.end - old_end + new_end)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_bounds() {
+        assert_eq!(edit_bounds("let x = 1;", "let xy = 1;"), (5, 5, 6));
+        assert_eq!(edit_bounds("abc", "abc"), (3, 3, 3));
+        assert_eq!(edit_bounds("aaa", "aa"), (2, 3, 2));
+        // Never split a multi-byte character
+        assert_eq!(edit_bounds("é", "è"), (0, 2, 2));
+    }
+
+    #[test]
+    fn test_remap_range() {
+        // Typing after a diagnostic leaves it alone
+        assert_eq!(remap_range(0..3, (5, 5, 6)), Some(0..3));
+        // Typing before a diagnostic shifts it
+        assert_eq!(remap_range(10..12, (5, 5, 6)), Some(11..13));
+        // Deleting text before it shifts it back
+        assert_eq!(remap_range(10..12, (2, 5, 2)), Some(7..9));
+        // Editing inside it clears it
+        assert_eq!(remap_range(4..8, (5, 6, 6)), None);
+    }
+}
@@ -0,0 +1,86 @@
+//! Occurrences of the symbol under the cursor, from
+//! `textDocument/documentHighlight` or, without a server, by matching words
+
+use lsp_types::{DocumentHighlight, DocumentHighlightKind};
+use std::ops::Range;
+
+use super::convert::range_to_offsets;
+
+/// Byte ranges to highlight, reads apart from writes
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Occurrences {
+    pub reads: Vec<Range<usize>>,
+    pub writes: Vec<Range<usize>>,
+}
+
+impl Occurrences {
+    pub fn is_empty(&self) -> bool {
+        self.reads.is_empty() && self.writes.is_empty()
+    }
+}
+
+/// Sort a response's highlights by kind. Textual matches, and highlights
+/// without a kind, are styled as reads.
+pub fn occurrences(text: &str, highlights: &[DocumentHighlight]) -> Occurrences {
+    let mut occurrences = Occurrences::default();
+    for highlight in highlights {
+        let range = range_to_offsets(text, highlight.range);
+        if highlight.kind == Some(DocumentHighlightKind::WRITE) {
+            occurrences.writes.push(range);
+        } else {
+            occurrences.reads.push(range);
+        }
+    }
+    occurrences
+}
+
+/// Whole-word matches of `word`, for files no server knows about
+pub fn word_occurrences(text: &str, word: &str) -> Occurrences {
+    let is_word = |ch: char| ch.is_alphanumeric() || ch == '_';
+    let reads = text
+        .match_indices(word)
+        .map(|(start, _)| start..start + word.len())
+        .filter(|range| {
+            !text[..range.start].chars().next_back().is_some_and(is_word)
+                && !text[range.end..].chars().next().is_some_and(is_word)
+        })
+        .collect();
+    Occurrences {
+        reads,
+        writes: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::{Position, Range as LspRange};
+
+    #[test]
+    fn test_occurrences_by_kind() {
+        let text = "let mut n = 1;\nn += n;";
+        let highlight = |line, start, end, kind| DocumentHighlight {
+            range: LspRange::new(Position::new(line, start), Position::new(line, end)),
+            kind,
+        };
+        let highlights = [
+            highlight(0, 8, 9, Some(DocumentHighlightKind::WRITE)),
+            highlight(1, 0, 1, Some(DocumentHighlightKind::WRITE)),
+            highlight(1, 5, 6, Some(DocumentHighlightKind::READ)),
+            highlight(1, 5, 6, None),
+        ];
+        assert_eq!(
+            occurrences(text, &highlights),
+            Occurrences {
+                reads: vec![20..21, 20..21],
+                writes: vec![8..9, 15..16],
+            }
+        );
+    }
+
+    #[test]
+    fn test_word_occurrences() {
+        let text = "count = count_all(count) + recount";
+        assert_eq!(word_occurrences(text, "count").reads, [0..5, 18..23]);
+    }
+}
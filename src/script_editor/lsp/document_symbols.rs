@@ -0,0 +1,173 @@
+//! Document symbols as a tree, for breadcrumbs and the outline
+
+use lsp_types::{DocumentSymbol, DocumentSymbolResponse, Position, Range, SymbolKind};
+
+/// A symbol in a file and the symbols nested inside it
+#[derive(Clone, Debug, PartialEq)]
+pub struct SymbolNode {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// Whole extent of the symbol, including its body
+    pub range: Range,
+    /// Where the cursor goes when the symbol is picked (usually its name)
+    pub selection_start: Position,
+    pub children: Vec<SymbolNode>,
+}
+
+impl SymbolNode {
+    fn from_document_symbol(symbol: DocumentSymbol) -> Self {
+        let mut children: Vec<SymbolNode> = symbol
+            .children
+            .unwrap_or_default()
+            .into_iter()
+            .map(Self::from_document_symbol)
+            .collect();
+        children.sort_by_key(|child| child.range.start);
+        Self {
+            name: symbol.name,
+            kind: symbol.kind,
+            range: symbol.range,
+            selection_start: symbol.selection_range.start,
+            children,
+        }
+    }
+
+    fn contains(&self, position: Position) -> bool {
+        self.range.start <= position && position <= self.range.end
+    }
+}
+
+/// Symbols of a `textDocument/documentSymbol` response, sorted by position.
+///
+/// Flat `SymbolInformation` lists are nested by range containment.
+pub fn symbol_tree(response: DocumentSymbolResponse) -> Vec<SymbolNode> {
+    match response {
+        DocumentSymbolResponse::Nested(symbols) => {
+            let mut nodes: Vec<SymbolNode> = symbols.into_iter().map(SymbolNode::from_document_symbol).collect();
+            nodes.sort_by_key(|node| node.range.start);
+            nodes
+        }
+        DocumentSymbolResponse::Flat(symbols) => nest(
+            symbols
+                .into_iter()
+                .map(|symbol| SymbolNode {
+                    name: symbol.name,
+                    kind: symbol.kind,
+                    range: symbol.location.range,
+                    selection_start: symbol.location.range.start,
+                    children: Vec::new(),
+                })
+                .collect(),
+        ),
+    }
+}
+
+/// Nest each symbol under the smallest other symbol whose range contains it
+fn nest(mut flat: Vec<SymbolNode>) -> Vec<SymbolNode> {
+    // Outer symbols first where two start at the same place
+    flat.sort_by(|a, b| a.range.start.cmp(&b.range.start).then(b.range.end.cmp(&a.range.end)));
+
+    let mut roots = Vec::new();
+    let mut stack: Vec<SymbolNode> = Vec::new();
+    for node in flat {
+        while stack.last().is_some_and(|open| node.range.end > open.range.end) {
+            let done = stack.pop().unwrap();
+            push_child(&mut stack, &mut roots, done);
+        }
+        stack.push(node);
+    }
+    while let Some(done) = stack.pop() {
+        push_child(&mut stack, &mut roots, done);
+    }
+    roots
+}
+
+fn push_child(stack: &mut [SymbolNode], roots: &mut Vec<SymbolNode>, node: SymbolNode) {
+    match stack.last_mut() {
+        Some(parent) => parent.children.push(node),
+        None => roots.push(node),
+    }
+}
+
+/// Index path (into `symbols`, then each node's children) of the innermost
+/// symbols containing `position`, outermost first
+pub fn symbol_path(symbols: &[SymbolNode], position: Position) -> Vec<usize> {
+    let mut path = Vec::new();
+    let mut level = symbols;
+    while let Some(ix) = level.iter().position(|node| node.contains(position)) {
+        path.push(ix);
+        level = &level[ix].children;
+    }
+    path
+}
+
+/// The symbols at depth `path.len()` below `path`: the top level for an empty
+/// path, else the children of the node it points to
+pub fn symbols_at<'a>(symbols: &'a [SymbolNode], path: &[usize]) -> &'a [SymbolNode] {
+    let mut level = symbols;
+    for &ix in path {
+        match level.get(ix) {
+            Some(node) => level = &node.children,
+            None => return &[],
+        }
+    }
+    level
+}
+
+/// Short label shown before a symbol's name
+pub fn symbol_kind_label(kind: SymbolKind) -> &'static str {
+    match kind {
+        SymbolKind::MODULE | SymbolKind::NAMESPACE | SymbolKind::PACKAGE => "mod",
+        SymbolKind::CLASS => "class",
+        SymbolKind::STRUCT => "struct",
+        SymbolKind::ENUM => "enum",
+        SymbolKind::INTERFACE => "trait",
+        SymbolKind::FUNCTION | SymbolKind::METHOD | SymbolKind::CONSTRUCTOR => "fn",
+        SymbolKind::FIELD | SymbolKind::PROPERTY => "field",
+        SymbolKind::CONSTANT => "const",
+        SymbolKind::VARIABLE => "let",
+        SymbolKind::ENUM_MEMBER => "variant",
+        SymbolKind::TYPE_PARAMETER => "type",
+        SymbolKind::OBJECT => "impl",
+        SymbolKind::KEY => "key",
+        SymbolKind::ARRAY => "array",
+        _ => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(name: &str, start: u32, end: u32) -> SymbolNode {
+        SymbolNode {
+            name: name.to_string(),
+            kind: SymbolKind::FUNCTION,
+            range: Range::new(Position::new(start, 0), Position::new(end, 0)),
+            selection_start: Position::new(start, 0),
+            children: Vec::new(),
+        }
+    }
+
+    fn names(nodes: &[SymbolNode]) -> Vec<&str> {
+        nodes.iter().map(|node| node.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_nest_flat_symbols() {
+        let tree = nest(vec![node("b", 2, 3), node("module", 0, 10), node("a", 1, 5), node("c", 6, 7)]);
+        assert_eq!(names(&tree), vec!["module"]);
+        assert_eq!(names(&tree[0].children), vec!["a", "c"]);
+        assert_eq!(names(&tree[0].children[0].children), vec!["b"]);
+    }
+
+    #[test]
+    fn test_symbol_path_and_siblings() {
+        let tree = nest(vec![node("impl", 0, 10), node("new", 1, 3), node("run", 4, 8), node("free", 12, 14)]);
+        let path = symbol_path(&tree, Position::new(5, 4));
+        assert_eq!(path, vec![0, 1]);
+        assert_eq!(names(symbols_at(&tree, &path[..1])), vec!["new", "run"]);
+        assert_eq!(names(symbols_at(&tree, &[])), vec!["impl", "free"]);
+        assert!(symbol_path(&tree, Position::new(11, 0)).is_empty());
+    }
+}
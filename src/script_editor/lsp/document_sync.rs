@@ -0,0 +1,119 @@
+//! Keeping a server's copy of a document in step with the buffer
+//!
+//! Servers that accept incremental sync get only the replaced range of each
+//! change; others get the whole text again, and ones that asked for no sync
+//! hear nothing of changes.
+
+use lsp_types::{ServerCapabilities, TextDocumentContentChangeEvent, TextDocumentSyncCapability, TextDocumentSyncKind};
+
+use super::super::text_buffer::TextBuffer;
+
+/// How the server asked for document changes in its `initialize` result
+pub fn sync_kind(capabilities: &ServerCapabilities) -> TextDocumentSyncKind {
+    match &capabilities.text_document_sync {
+        Some(TextDocumentSyncCapability::Kind(kind)) => *kind,
+        Some(TextDocumentSyncCapability::Options(options)) => options.change.unwrap_or(TextDocumentSyncKind::NONE),
+        None => TextDocumentSyncKind::NONE,
+    }
+}
+
+/// Whether the server wants `didChange` at all, which it doesn't with `NONE`
+pub fn wants_changes(capabilities: &ServerCapabilities) -> bool {
+    sync_kind(capabilities) != TextDocumentSyncKind::NONE
+}
+
+/// The change `edit` (as from `TextBuffer::diff`) makes to `old`, leaving
+/// `new`, as one replaced range of `old`
+pub fn incremental_change(old: &TextBuffer, edit: (usize, usize, usize), new: &str) -> TextDocumentContentChangeEvent {
+    let (mut start, mut old_end, mut new_end) = edit;
+    // A position between `\r` and `\n` is the end of the line to the server,
+    // so the range never starts or ends inside a line break
+    if start > 0 && old.byte(start - 1) == b'\r' {
+        start -= 1;
+    }
+    if old_end > 0 && old_end < old.len() && old.byte(old_end - 1) == b'\r' && old.byte(old_end) == b'\n' {
+        old_end += 1;
+        new_end += 1;
+    }
+    TextDocumentContentChangeEvent {
+        range: Some(old.offsets_to_range(start..old_end)),
+        range_length: None,
+        text: new[start..new_end].to_string(),
+    }
+}
+
+pub fn full_change(text: &str) -> Vec<TextDocumentContentChangeEvent> {
+    vec![TextDocumentContentChangeEvent {
+        range: None,
+        range_length: None,
+        text: text.to_string(),
+    }]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::convert::range_to_offsets;
+
+    /// What a server does with the changes it receives
+    fn apply(text: &str, changes: &[TextDocumentContentChangeEvent]) -> String {
+        let mut text = text.to_string();
+        for change in changes {
+            match change.range {
+                Some(range) => {
+                    let range = range_to_offsets(&text, range);
+                    text.replace_range(range, &change.text);
+                }
+                None => text = change.text.clone(),
+            }
+        }
+        text
+    }
+
+    #[test]
+    fn test_no_changes_for_sync_kind_none() {
+        let capabilities = |sync| ServerCapabilities {
+            text_document_sync: sync,
+            ..Default::default()
+        };
+        let none = capabilities(Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::NONE)));
+        assert_eq!(sync_kind(&none), TextDocumentSyncKind::NONE);
+        assert!(!wants_changes(&none));
+        // Leaving it out means the same
+        assert!(!wants_changes(&capabilities(None)));
+        let options = lsp_types::TextDocumentSyncOptions {
+            open_close: Some(true),
+            ..Default::default()
+        };
+        assert!(!wants_changes(&capabilities(Some(TextDocumentSyncCapability::Options(options)))));
+
+        assert!(wants_changes(&capabilities(Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)))));
+        let incremental = capabilities(Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::INCREMENTAL)));
+        assert!(wants_changes(&incremental));
+    }
+
+    #[test]
+    fn test_incremental_changes_rebuild_the_buffer() {
+        let mut buffer = "fn main() {\r\n    let π = 3.14;\r\n}\r\n".to_string();
+        let mut server = buffer.clone();
+        let edits: [(&str, &str); 7] = [
+            ("π", "τ"),
+            ("3.14", "6.28"),
+            ("{\r\n", "{\r\n    // 😀 emoji\r\n"),
+            ("\r\n}", "\r\n    println!(\"{}\", τ);\r\n}"),
+            ("    let", "let"),
+            ("\r\n", "\n"),
+            ("}\r\n", ""),
+        ];
+        for (find, replace) in edits {
+            let old = TextBuffer::new(&buffer);
+            buffer = buffer.replacen(find, replace, 1);
+            server = apply(&server, &[incremental_change(&old, old.diff(&buffer), &buffer)]);
+            assert_eq!(server, buffer);
+        }
+        // A line break typed between `\r` and `\n` still lands in the right place
+        let old = TextBuffer::new(&buffer);
+        buffer.insert(buffer.find("\r\n").unwrap() + 1, 'x');
+        assert_eq!(apply(&server, &[incremental_change(&old, old.diff(&buffer), &buffer)]), buffer);
+    }
+}
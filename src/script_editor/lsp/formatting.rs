@@ -0,0 +1,59 @@
+//! Applying server-provided text edits
+
+use lsp_types::TextEdit;
+
+use super::convert::range_to_offsets;
+
+/// Apply LSP text edits to `text`.
+///
+/// All ranges refer to the original text, as the protocol requires. Edits
+/// that start at the same position are inserted in the order they were sent.
+pub fn apply_text_edits(text: &str, edits: Vec<TextEdit>) -> String {
+    let mut resolved: Vec<(std::ops::Range<usize>, String)> = edits
+        .into_iter()
+        .map(|edit| (range_to_offsets(text, edit.range), edit.new_text))
+        .collect();
+    // Stable sort keeps same-position edits in their original order
+    resolved.sort_by_key(|(range, _)| range.start);
+
+    let mut output = String::with_capacity(text.len());
+    let mut cursor = 0;
+    for (range, new_text) in resolved {
+        // Overlapping edits are invalid; skip the part already replaced
+        let start = range.start.max(cursor);
+        let end = range.end.max(start);
+        output.push_str(&text[cursor..start]);
+        output.push_str(&new_text);
+        cursor = end;
+    }
+    output.push_str(&text[cursor..]);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::{Position, Range};
+
+    fn edit(start: (u32, u32), end: (u32, u32), new_text: &str) -> TextEdit {
+        TextEdit {
+            range: Range::new(Position::new(start.0, start.1), Position::new(end.0, end.1)),
+            new_text: new_text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_apply_text_edits() {
+        let text = "fn main(){\nlet x=1;\n}\n";
+        let edits = vec![
+            edit((1, 0), (1, 0), "    "),
+            edit((0, 9), (0, 9), " "),
+            edit((1, 5), (1, 6), " = "),
+        ];
+        assert_eq!(apply_text_edits(text, edits), "fn main() {\n    let x = 1;\n}\n");
+
+        // Insertions at the same position keep their order
+        let edits = vec![edit((0, 0), (0, 0), "a"), edit((0, 0), (0, 0), "b")];
+        assert_eq!(apply_text_edits("c", edits), "abc");
+    }
+}
@@ -0,0 +1,187 @@
+//! Hover provider backed by a language server
+
+use anyhow::Result;
+use gpui::{App, Task, Window};
+use lsp_types::{Hover, HoverContents, HoverParams, MarkedString, MarkupContent, MarkupKind};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use ui::input::{HoverProvider, Rope};
+
+use super::client::LanguageServer;
+use super::convert::text_document_position;
+use super::inlay_hints::{hint_tooltip, resolves_inlay_hints, SharedInlayHints};
+use super::request_tracker::{DocumentVersion, RequestTracker, TrackedRequest};
+
+/// How long the mouse has to rest on a symbol before the server is asked
+const HOVER_DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// Sends `textDocument/hover` for one document.
+///
+/// The input drops the previous hover task whenever the mouse moves or the
+/// buffer changes, which also drops its debounce timer, so only a resting
+/// mouse reaches the server. A request that was already sent is cancelled
+/// when the next one starts, and its answer dropped if the text has changed
+/// since.
+///
+/// Hovering an inlay hint shows its tooltip instead, resolving it first when
+/// the server sends tooltips only on request.
+pub struct LspHoverProvider {
+    server: Arc<LanguageServer>,
+    path: PathBuf,
+    version: DocumentVersion,
+    requests: RequestTracker,
+    inlay_hints: SharedInlayHints,
+}
+
+impl LspHoverProvider {
+    pub fn new(
+        server: Arc<LanguageServer>,
+        path: PathBuf,
+        version: DocumentVersion,
+        inlay_hints: SharedInlayHints,
+    ) -> Self {
+        Self {
+            server,
+            path,
+            version,
+            requests: RequestTracker::default(),
+            inlay_hints,
+        }
+    }
+
+    /// The tooltip of the inlay hint at `offset`, if there is one there
+    fn inlay_hint_hover(&self, offset: usize, cx: &mut App) -> Option<Task<Result<Option<Hover>>>> {
+        let hint = self.inlay_hints.lock().iter().find(|item| item.offset == offset).cloned()?;
+        if let Some(tooltip) = hint.tooltip {
+            return Some(Task::ready(Ok(Some(markdown_hover(tooltip)))));
+        }
+        if !self.server.capabilities().is_some_and(|caps| resolves_inlay_hints(&caps)) {
+            return None;
+        }
+
+        let request = self.server.request::<lsp_types::request::InlayHintResolveRequest>(hint.hint);
+        let inlay_hints = self.inlay_hints.clone();
+        Some(cx.background_spawn(async move {
+            let resolved = request.response().await?;
+            let tooltip = hint_tooltip(&resolved);
+            // Keep the tooltip so hovering again doesn't ask again
+            if let Some(item) = inlay_hints.lock().iter_mut().find(|item| item.offset == offset) {
+                item.tooltip = tooltip.clone();
+                item.hint = resolved;
+            }
+            Ok(tooltip.map(markdown_hover))
+        }))
+    }
+}
+
+fn markdown_hover(markdown: String) -> Hover {
+    Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: markdown,
+        }),
+        range: None,
+    }
+}
+
+impl HoverProvider for LspHoverProvider {
+    fn hover(
+        &self,
+        text: &Rope,
+        offset: usize,
+        _window: &mut Window,
+        cx: &mut App,
+    ) -> Task<Result<Option<Hover>>> {
+        if let Some(previous) = self.requests.take() {
+            self.server.cancel(previous);
+        }
+        if let Some(task) = self.inlay_hint_hover(offset, cx) {
+            return task;
+        }
+
+        let has_hover = self
+            .server
+            .capabilities()
+            .is_some_and(|caps| caps.hover_provider.is_some());
+        if !has_hover {
+            return Task::ready(Ok(None));
+        }
+
+        let Some(position) = text_document_position(&self.path, &text.to_string(), offset) else {
+            return Task::ready(Ok(None));
+        };
+
+        let server = self.server.clone();
+        let version = self.version.clone();
+        let requests = self.requests.clone();
+        let timer = cx.background_executor().timer(HOVER_DEBOUNCE);
+
+        cx.background_spawn(async move {
+            timer.await;
+
+            let request = server.request::<lsp_types::request::HoverRequest>(HoverParams {
+                text_document_position_params: position,
+                work_done_progress_params: Default::default(),
+            });
+            let sent = TrackedRequest {
+                id: request.id,
+                version: version.get(),
+            };
+            if let Some(previous) = requests.start(sent) {
+                server.cancel(previous);
+            }
+
+            // Servers answer `null` when there is nothing under the cursor
+            let hover = request.response().await?;
+            if !requests.finish(sent, version.get()) {
+                return Ok(None);
+            }
+            Ok(hover.and_then(normalize_hover))
+        })
+    }
+}
+
+/// Flatten legacy `MarkedString` contents into a single markdown block and
+/// drop hovers that carry no visible text.
+fn normalize_hover(hover: Hover) -> Option<Hover> {
+    let markdown = match hover.contents {
+        HoverContents::Markup(markup) => {
+            if markup.value.trim().is_empty() {
+                return None;
+            }
+            return Some(Hover {
+                contents: HoverContents::Markup(markup),
+                range: hover.range,
+            });
+        }
+        HoverContents::Scalar(marked) => marked_string_to_markdown(marked),
+        HoverContents::Array(items) => items
+            .into_iter()
+            .map(marked_string_to_markdown)
+            .filter(|section| !section.trim().is_empty())
+            .collect::<Vec<_>>()
+            .join("\n\n---\n\n"),
+    };
+
+    if markdown.trim().is_empty() {
+        return None;
+    }
+
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: markdown,
+        }),
+        range: hover.range,
+    })
+}
+
+fn marked_string_to_markdown(marked: MarkedString) -> String {
+    match marked {
+        MarkedString::String(text) => text,
+        MarkedString::LanguageString(code) => {
+            format!("```{}\n{}\n```", code.language, code.value)
+        }
+    }
+}
@@ -0,0 +1,147 @@
+//! Inlay hints from `textDocument/inlayHint`: types after bindings and
+//! parameter names at call sites, drawn between characters of the buffer
+
+use lsp_types::{
+    InlayHint, InlayHintLabel, InlayHintParams, InlayHintServerCapabilities, InlayHintTooltip,
+    OneOf, ServerCapabilities, TextDocumentIdentifier,
+};
+use parking_lot::Mutex;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+
+use super::convert::{offsets_to_range, path_to_uri, position_to_offset};
+
+/// A hint placed at a byte offset of the text it was requested for
+#[derive(Clone, Debug)]
+pub struct InlayHintItem {
+    pub offset: usize,
+    /// Label with the padding the server asked for
+    pub label: String,
+    /// Markdown shown when hovering the hint, once known
+    pub tooltip: Option<String>,
+    /// The hint as the server sent it, to resolve its tooltip
+    pub hint: InlayHint,
+}
+
+/// A file's hints, shared with its hover provider for their tooltips
+pub type SharedInlayHints = Arc<Mutex<Vec<InlayHintItem>>>;
+
+/// Request the hints for the byte range `range` of a document
+pub fn inlay_hint_params(path: &Path, text: &str, range: Range<usize>) -> Option<InlayHintParams> {
+    Some(InlayHintParams {
+        text_document: TextDocumentIdentifier { uri: path_to_uri(path)? },
+        range: offsets_to_range(text, range),
+        work_done_progress_params: Default::default(),
+    })
+}
+
+/// Hints placed in `text`, in order
+pub fn inlay_hint_items(text: &str, hints: Vec<InlayHint>) -> Vec<InlayHintItem> {
+    let mut items: Vec<InlayHintItem> = hints
+        .into_iter()
+        .map(|hint| {
+            let label = match &hint.label {
+                InlayHintLabel::String(label) => label.clone(),
+                InlayHintLabel::LabelParts(parts) => parts.iter().map(|part| part.value.as_str()).collect(),
+            };
+            let label = format!(
+                "{}{}{}",
+                if hint.padding_left == Some(true) { " " } else { "" },
+                label,
+                if hint.padding_right == Some(true) { " " } else { "" },
+            );
+            InlayHintItem {
+                offset: position_to_offset(text, hint.position),
+                label,
+                tooltip: hint_tooltip(&hint),
+                hint,
+            }
+        })
+        .collect();
+    items.sort_by_key(|item| item.offset);
+    items
+}
+
+/// The hint's tooltip and those of its label parts, as markdown
+pub fn hint_tooltip(hint: &InlayHint) -> Option<String> {
+    let tooltip_text = |tooltip: &InlayHintTooltip| match tooltip {
+        InlayHintTooltip::String(text) => text.clone(),
+        InlayHintTooltip::MarkupContent(markup) => markup.value.clone(),
+    };
+    let mut sections: Vec<String> = hint.tooltip.iter().map(tooltip_text).collect();
+    if let InlayHintLabel::LabelParts(parts) = &hint.label {
+        sections.extend(parts.iter().filter_map(|part| match &part.tooltip {
+            Some(lsp_types::InlayHintLabelPartTooltip::String(text)) => Some(text.clone()),
+            Some(lsp_types::InlayHintLabelPartTooltip::MarkupContent(markup)) => Some(markup.value.clone()),
+            None => None,
+        }));
+    }
+    sections.retain(|section| !section.trim().is_empty());
+    (!sections.is_empty()).then(|| sections.join("\n\n---\n\n"))
+}
+
+/// Whether a server answers `textDocument/inlayHint`
+pub fn provides_inlay_hints(capabilities: &ServerCapabilities) -> bool {
+    match &capabilities.inlay_hint_provider {
+        Some(OneOf::Left(enabled)) => *enabled,
+        Some(OneOf::Right(_)) => true,
+        None => false,
+    }
+}
+
+/// Whether a server fills in hint tooltips with `inlayHint/resolve`
+pub fn resolves_inlay_hints(capabilities: &ServerCapabilities) -> bool {
+    match &capabilities.inlay_hint_provider {
+        Some(OneOf::Right(InlayHintServerCapabilities::Options(options))) => options.resolve_provider == Some(true),
+        Some(OneOf::Right(InlayHintServerCapabilities::RegistrationOptions(options))) => {
+            options.inlay_hint_options.resolve_provider == Some(true)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::{InlayHintKind, InlayHintLabelPart, Position};
+
+    fn hint(line: u32, character: u32, label: InlayHintLabel, kind: InlayHintKind) -> InlayHint {
+        InlayHint {
+            position: Position::new(line, character),
+            label,
+            kind: Some(kind),
+            text_edits: None,
+            tooltip: None,
+            padding_left: None,
+            padding_right: None,
+            data: None,
+        }
+    }
+
+    #[test]
+    fn test_inlay_hint_items() {
+        let text = "let x = f(1);\n";
+        let mut parameter = hint(0, 10, InlayHintLabel::String("count:".to_string()), InlayHintKind::PARAMETER);
+        parameter.padding_right = Some(true);
+        let binding = hint(
+            0,
+            5,
+            InlayHintLabel::LabelParts(vec![
+                InlayHintLabelPart { value: ": ".to_string(), ..Default::default() },
+                InlayHintLabelPart {
+                    value: "usize".to_string(),
+                    tooltip: Some(lsp_types::InlayHintLabelPartTooltip::String("primitive usize".to_string())),
+                    ..Default::default()
+                },
+            ]),
+            InlayHintKind::TYPE,
+        );
+
+        let items = inlay_hint_items(text, vec![parameter, binding]);
+        let placed: Vec<(usize, &str)> = items.iter().map(|item| (item.offset, item.label.as_str())).collect();
+        assert_eq!(placed, [(5, ": usize"), (10, "count: ")]);
+        assert_eq!(items[0].tooltip.as_deref(), Some("primitive usize"));
+        assert_eq!(items[1].tooltip, None);
+    }
+}
@@ -0,0 +1,161 @@
+//! Messages servers address to the user
+//!
+//! `window/showMessage` becomes a toast that goes away by itself, and
+//! `window/showMessageRequest` a prompt offering the server's actions, one at
+//! a time, whose choice is sent back as the response. `window/logMessage`
+//! only goes to the log.
+
+use lsp_types::{LogMessageParams, MessageType, ShowMessageParams, ShowMessageRequestParams};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::client::LanguageServer;
+
+/// Toasts shown at once; the oldest makes way for a new one
+pub const MAX_TOASTS: usize = 3;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Toast {
+    pub id: usize,
+    pub server: String,
+    pub kind: MessageType,
+    pub text: String,
+    /// Times the server sent this text while it was showing
+    pub count: usize,
+}
+
+impl Toast {
+    /// How long it stays up after it was last sent
+    pub fn duration(&self) -> Duration {
+        match self.kind {
+            MessageType::ERROR | MessageType::WARNING => Duration::from_secs(10),
+            _ => Duration::from_secs(5),
+        }
+    }
+}
+
+/// A `showMessageRequest` waiting for the user
+pub struct MessageRequest {
+    pub server: Arc<LanguageServer>,
+    pub id: Value,
+    pub params: ShowMessageRequestParams,
+}
+
+#[derive(Default)]
+pub struct ServerMessages {
+    toasts: Vec<Toast>,
+    requests: VecDeque<MessageRequest>,
+    next_id: usize,
+}
+
+impl ServerMessages {
+    /// Show a message, returning its toast's id and count for `expire`. A
+    /// text already showing is counted again instead of stacking up.
+    pub fn show(&mut self, server: &str, params: ShowMessageParams) -> (usize, usize) {
+        if let Some(toast) = self
+            .toasts
+            .iter_mut()
+            .find(|toast| toast.server == server && toast.text == params.message)
+        {
+            toast.count += 1;
+            toast.kind = params.typ;
+            return (toast.id, toast.count);
+        }
+        if self.toasts.len() == MAX_TOASTS {
+            self.toasts.remove(0);
+        }
+        self.next_id += 1;
+        self.toasts.push(Toast {
+            id: self.next_id,
+            server: server.to_string(),
+            kind: params.typ,
+            text: params.message,
+            count: 1,
+        });
+        (self.next_id, 1)
+    }
+
+    /// Take down a toast once its time is up, unless it was sent again since
+    pub fn expire(&mut self, id: usize, count: usize) -> bool {
+        let before = self.toasts.len();
+        self.toasts.retain(|toast| toast.id != id || toast.count != count);
+        self.toasts.len() != before
+    }
+
+    pub fn dismiss(&mut self, id: usize) {
+        self.toasts.retain(|toast| toast.id != id);
+    }
+
+    pub fn toasts(&self) -> &[Toast] {
+        &self.toasts
+    }
+
+    pub fn push_request(&mut self, request: MessageRequest) {
+        self.requests.push_back(request);
+    }
+
+    /// The prompt to show, the oldest one unanswered
+    pub fn request(&self) -> Option<&MessageRequest> {
+        self.requests.front()
+    }
+
+    /// Send the shown prompt's chosen action, `None` if dismissed, and move
+    /// on to the next
+    pub fn answer(&mut self, action: Option<usize>) {
+        let Some(request) = self.requests.pop_front() else {
+            return;
+        };
+        let chosen = action.and_then(|ix| request.params.actions.as_ref()?.get(ix).cloned());
+        let result = serde_json::to_value(chosen).unwrap_or(Value::Null);
+        request.server.respond(request.id, result);
+    }
+
+    /// Drop the prompts of a server that exited; there's no one left to answer
+    pub fn server_exited(&mut self, server: &Arc<LanguageServer>) {
+        self.requests.retain(|request| !Arc::ptr_eq(&request.server, server));
+    }
+}
+
+/// Log a `window/logMessage` at its level
+pub fn log_message(server: &str, params: &LogMessageParams) {
+    match params.typ {
+        MessageType::ERROR => tracing::error!("[{}] {}", server, params.message),
+        MessageType::WARNING => tracing::warn!("[{}] {}", server, params.message),
+        MessageType::INFO => tracing::info!("[{}] {}", server, params.message),
+        _ => tracing::debug!("[{}] {}", server, params.message),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(text: &str) -> ShowMessageParams {
+        ShowMessageParams {
+            typ: MessageType::INFO,
+            message: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_repeated_messages_share_a_toast() {
+        let mut messages = ServerMessages::default();
+        let first = messages.show("rust-analyzer", message("Failed to load workspace"));
+        let again = messages.show("rust-analyzer", message("Failed to load workspace"));
+        assert_eq!(messages.toasts().len(), 1);
+        assert_eq!(again, (first.0, 2));
+
+        // The first showing's timer doesn't cut the second one short
+        assert!(!messages.expire(first.0, first.1));
+        assert!(messages.expire(again.0, again.1));
+        assert!(messages.toasts().is_empty());
+
+        for ix in 0..=MAX_TOASTS {
+            messages.show("pyright", message(&ix.to_string()));
+        }
+        let texts: Vec<&str> = messages.toasts().iter().map(|toast| toast.text.as_str()).collect();
+        assert_eq!(texts, ["1", "2", "3"]);
+    }
+}
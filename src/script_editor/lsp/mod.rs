@@ -0,0 +1,61 @@
+//! Language server support for the Script Editor
+//!
+//! Servers are spawned per language and workspace root and speak LSP over
+//! stdio. The host-provided rust-analyzer keeps serving Rust completions when
+//! present; these servers cover everything else the editor asks for.
+
+mod client;
+mod code_actions;
+mod completion;
+mod convert;
+mod definition;
+mod diagnostics;
+mod document_highlight;
+mod document_symbols;
+mod document_sync;
+mod formatting;
+mod hover;
+mod inlay_hints;
+mod messages;
+mod progress;
+mod registry;
+mod request_tracker;
+mod selection_range;
+mod semantic_tokens;
+mod servers;
+mod signature_help;
+mod workspace_edit;
+mod workspace_symbols;
+
+pub use client::{LanguageServer, PendingRequest, ResponseError, ServerNotification};
+pub use code_actions::{
+    code_action_items, code_action_params, needs_resolve, offers_organize_imports, organize_imports_action,
+    organize_imports_params, CodeActionItem,
+};
+pub use completion::{accepted_additional_edits, LspCompletionProvider, SharedCompletionExtras};
+pub use convert::*;
+pub use definition::{definition_targets, location_targets, DefinitionTarget};
+pub use document_highlight::{occurrences, word_occurrences, Occurrences};
+pub use document_symbols::{symbol_kind_label, symbol_path, symbol_tree, symbols_at, SymbolNode};
+pub use diagnostics::{
+    edit_bounds, line_severities, remap_range, severity_rank, to_input_diagnostic, FileDiagnostic,
+};
+pub use document_sync::{full_change, incremental_change};
+pub use formatting::apply_text_edits;
+pub use hover::LspHoverProvider;
+pub use inlay_hints::{
+    inlay_hint_items, inlay_hint_params, provides_inlay_hints, InlayHintItem, SharedInlayHints,
+};
+pub use messages::{log_message, MessageRequest, ServerMessages, Toast};
+pub use progress::ServerProgress;
+pub use registry::{shut_down_all_servers, shut_down_servers, LanguageServerPool, ServerStatus, MAX_RESTARTS};
+pub use request_tracker::DocumentVersion;
+pub use selection_range::selection_range_chain;
+pub use semantic_tokens::{
+    apply_semantic_token_edits, semantic_highlights, semantic_tokens_delta_params, semantic_tokens_params,
+    semantic_tokens_support, SemanticTokenState,
+};
+pub use servers::{default_server_command, find_in_path, ServerCommand};
+pub use signature_help::{Signature, Signatures};
+pub use workspace_edit::{apply_edit_response, workspace_changes, EditFailure, WorkspaceChange};
+pub use workspace_symbols::{merge_workspace_symbols, workspace_symbol_items, WorkspaceSymbolItem};
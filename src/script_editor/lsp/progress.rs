@@ -0,0 +1,115 @@
+//! Work done progress a server reports through `$/progress`, e.g. while
+//! indexing a workspace
+//!
+//! Each operation has a token and goes through `begin`, any number of
+//! `report`s and an `end`, after which it's forgotten.
+
+use lsp_types::{NumberOrString, ProgressParams, ProgressParamsValue, WorkDoneProgress};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProgressItem {
+    pub title: String,
+    pub message: Option<String>,
+    pub percentage: Option<u32>,
+}
+
+/// A server's operations in progress, in the order they began
+#[derive(Clone, Debug, Default)]
+pub struct ServerProgress {
+    items: Vec<(NumberOrString, ProgressItem)>,
+}
+
+impl ServerProgress {
+    pub fn apply(&mut self, params: ProgressParams) {
+        let ProgressParamsValue::WorkDone(progress) = params.value;
+        match progress {
+            WorkDoneProgress::Begin(begin) => {
+                self.items.retain(|(token, _)| *token != params.token);
+                self.items.push((
+                    params.token,
+                    ProgressItem {
+                        title: begin.title,
+                        message: begin.message,
+                        percentage: begin.percentage,
+                    },
+                ));
+            }
+            WorkDoneProgress::Report(report) => {
+                let Some((_, item)) = self.items.iter_mut().find(|(token, _)| *token == params.token) else {
+                    return;
+                };
+                // Either may be left out to keep the last one
+                if report.message.is_some() {
+                    item.message = report.message;
+                }
+                if report.percentage.is_some() {
+                    item.percentage = report.percentage;
+                }
+            }
+            WorkDoneProgress::End(_) => self.items.retain(|(token, _)| *token != params.token),
+        }
+    }
+
+    /// The operation begun last, e.g. `Indexing 12/40 (core) 30%`, and how
+    /// many others are under way
+    pub fn label(&self) -> Option<String> {
+        let (_, item) = self.items.last()?;
+        let mut label = item.title.clone();
+        if let Some(message) = &item.message {
+            label.push(' ');
+            label.push_str(message);
+        }
+        if let Some(percentage) = item.percentage {
+            label.push_str(&format!(" {}%", percentage));
+        }
+        if self.items.len() > 1 {
+            label.push_str(&format!(" (+{} more)", self.items.len() - 1));
+        }
+        Some(label)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::{WorkDoneProgressBegin, WorkDoneProgressEnd, WorkDoneProgressReport};
+
+    fn params(token: &str, progress: WorkDoneProgress) -> ProgressParams {
+        ProgressParams {
+            token: NumberOrString::String(token.to_string()),
+            value: ProgressParamsValue::WorkDone(progress),
+        }
+    }
+
+    #[test]
+    fn test_progress_begins_reports_and_ends() {
+        let mut progress = ServerProgress::default();
+        progress.apply(params("index", WorkDoneProgress::Begin(WorkDoneProgressBegin {
+            title: "Indexing".into(),
+            percentage: Some(0),
+            ..Default::default()
+        })));
+        progress.apply(params("index", WorkDoneProgress::Report(WorkDoneProgressReport {
+            message: Some("12/40".into()),
+            percentage: Some(30),
+            ..Default::default()
+        })));
+        assert_eq!(progress.label().as_deref(), Some("Indexing 12/40 30%"));
+
+        progress.apply(params("check", WorkDoneProgress::Begin(WorkDoneProgressBegin {
+            title: "cargo check".into(),
+            ..Default::default()
+        })));
+        // A report leaving out the message keeps the last one
+        progress.apply(params("index", WorkDoneProgress::Report(WorkDoneProgressReport {
+            percentage: Some(43),
+            ..Default::default()
+        })));
+        assert_eq!(progress.label().as_deref(), Some("cargo check (+1 more)"));
+
+        progress.apply(params("check", WorkDoneProgress::End(WorkDoneProgressEnd::default())));
+        assert_eq!(progress.label().as_deref(), Some("Indexing 12/40 43%"));
+        progress.apply(params("index", WorkDoneProgress::End(WorkDoneProgressEnd::default())));
+        assert_eq!(progress.label(), None);
+    }
+}
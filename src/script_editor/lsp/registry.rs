@@ -0,0 +1,502 @@
+//! Lazily started language servers keyed by language and workspace root
+//!
+//! A server that crashes is restarted after a delay that doubles with each
+//! crash in a row, until it has crashed `MAX_RESTARTS` times; one that ran
+//! for a while before crashing starts counting again.
+//!
+//! Every editor shares one registry, so all the Rust files of a project talk
+//! to one rust-analyzer however many editors show them. Each document is
+//! opened with its server once, and synced by the first editor that opened
+//! it; a server is shut down when the last of its documents closes.
+//!
+//! A server that can work on several workspace folders also takes on the
+//! other projects of its language as they're opened, rather than another
+//! process being started for each.
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::super::settings::LanguageServerSettings;
+use super::client::{running_servers, LanguageServer};
+use super::servers::{find_in_path, server_command};
+
+/// Crashes in a row after which a server is left stopped
+pub const MAX_RESTARTS: u32 = 5;
+
+/// Delay before the first restart, doubled for each one after
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(30);
+
+/// A server that ran this long before crashing isn't crashing in a loop
+const STABLE_UPTIME: Duration = Duration::from_secs(120);
+
+/// How long servers get to exit after `shutdown` before they're killed
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How a language/root pair's server is doing, for the status bar
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ServerStatus {
+    /// Spawned, but hasn't answered `initialize` yet
+    Starting,
+    Ready,
+    /// Crashed, with a restart scheduled
+    Restarting,
+    /// Crashed too often, or failed to start again
+    Stopped,
+}
+
+/// A language id and workspace root, which get one server between them
+type ServerKey = (String, PathBuf);
+
+fn server_key(server: &LanguageServer) -> ServerKey {
+    (server.language_id().to_string(), server.root().to_path_buf())
+}
+
+/// A document open with a server, as the server knows it
+struct OpenDocument {
+    /// Editors with the document open, by the ids they gave; the first one
+    /// sends its changes
+    editors: Vec<u64>,
+    /// The version the server last heard of, counted here rather than by
+    /// the editors, whose own counts start wherever they opened the file
+    version: i32,
+    /// Whether the editor syncing it took over from another, whose text the
+    /// server has, and sends all of its own next
+    taken_over: bool,
+    /// The server's workspace folder the document is in
+    folder: PathBuf,
+}
+
+/// The registry every editor shares, a global of the app
+#[derive(Clone, Default)]
+pub struct LanguageServerPool(Rc<RefCell<LanguageServerRegistry>>);
+
+impl gpui::Global for LanguageServerPool {}
+
+impl LanguageServerPool {
+    pub fn global(cx: &mut gpui::App) -> Self {
+        cx.default_global::<Self>().clone()
+    }
+
+    pub fn borrow(&self) -> std::cell::Ref<'_, LanguageServerRegistry> {
+        self.0.borrow()
+    }
+
+    pub fn borrow_mut(&self) -> std::cell::RefMut<'_, LanguageServerRegistry> {
+        self.0.borrow_mut()
+    }
+}
+
+#[derive(Default)]
+pub struct LanguageServerRegistry {
+    /// Servers by the language and root they were started for, and by each
+    /// folder they took on since
+    servers: HashMap<ServerKey, Arc<LanguageServer>>,
+    /// Documents open with each running server
+    documents: HashMap<ServerKey, HashMap<PathBuf, OpenDocument>>,
+    /// The last crash counted per language/root pair, with what it returned:
+    /// every editor using the server hears of the same crash
+    last_crash: HashMap<ServerKey, (usize, Option<Duration>)>,
+    /// Languages whose server is not installed or failed to spawn
+    unavailable: HashSet<String>,
+    /// Messages about servers found missing, not yet shown to the user
+    notices: Vec<String>,
+    /// Crashes in a row per language/root pair
+    crashes: HashMap<(String, PathBuf), u32>,
+    /// The user's choice of server per language id, over the built-in ones
+    settings: BTreeMap<String, LanguageServerSettings>,
+}
+
+impl LanguageServerRegistry {
+    /// Get the running server for a language/root pair, starting it on first use
+    pub fn get_or_start(&mut self, language_id: &str, root: &Path) -> Option<Arc<LanguageServer>> {
+        let key = (language_id.to_string(), root.to_path_buf());
+        if let Some(server) = self.servers.get(&key).cloned() {
+            if server.is_running() {
+                return Some(server);
+            }
+            self.forget(&server);
+        }
+
+        if self.unavailable.contains(language_id) {
+            return None;
+        }
+
+        let shared = self
+            .servers
+            .iter()
+            .find(|((language, _), server)| {
+                language == language_id && server.is_running() && server.supports_workspace_folders()
+            })
+            .map(|(_, server)| server.clone());
+        if let Some(server) = shared {
+            self.add_folder(&server, root);
+            return Some(server);
+        }
+
+        let command = server_command(language_id, root, self.settings.get(language_id))?;
+        if find_in_path(&command.command).is_none() {
+            tracing::debug!(
+                "ℹ️  {} not found on PATH, {} files will use basic editing only",
+                command.command,
+                language_id
+            );
+            self.unavailable.insert(language_id.to_string());
+            self.notices.push(format!(
+                "{} not found on PATH; {} files get syntax highlighting only",
+                command.command, language_id
+            ));
+            return None;
+        }
+
+        match LanguageServer::start(&command, language_id, root) {
+            Ok(server) => {
+                // Whatever was open belonged to the previous process
+                self.documents.remove(&key);
+                self.servers.insert(key, server.clone());
+                Some(server)
+            }
+            Err(e) => {
+                tracing::error!("⚠️  Failed to start {}: {:#}", command.name, e);
+                self.unavailable.insert(language_id.to_string());
+                None
+            }
+        }
+    }
+
+    /// Launch servers as `settings` say from now on; running ones are kept
+    pub fn set_settings(&mut self, settings: BTreeMap<String, LanguageServerSettings>) {
+        if settings != self.settings {
+            // A server not found before may be configured now
+            self.unavailable.clear();
+            self.settings = settings;
+        }
+    }
+
+    /// Messages about missing servers since the last call; each is reported once
+    pub fn take_notices(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.notices)
+    }
+
+    /// Count a crash of `server`, returning how long to wait before starting
+    /// it again, or `None` if it has crashed too often to try
+    pub fn record_crash(&mut self, server: &Arc<LanguageServer>) -> Option<Duration> {
+        let key = server_key(server);
+        let id = Arc::as_ptr(server) as usize;
+        if let Some((counted, delay)) = self.last_crash.get(&key) {
+            if *counted == id {
+                return *delay;
+            }
+        }
+        let crashes = self.crashes.entry(key.clone()).or_default();
+        let delay = next_restart(crashes, server.uptime());
+        self.last_crash.insert(key, (id, delay));
+        delay
+    }
+
+    /// Stop the server for a language/root pair, if one is running, and start
+    /// it afresh with a clean crash count
+    pub fn restart(&mut self, language_id: &str, root: &Path) -> Option<Arc<LanguageServer>> {
+        let key = (language_id.to_string(), root.to_path_buf());
+        if let Some(server) = self.servers.get(&key).cloned() {
+            self.forget(&server);
+            server.stop();
+        }
+        self.crashes.remove(&key);
+        self.unavailable.remove(language_id);
+        self.get_or_start(language_id, root)
+    }
+
+    pub fn status(&self, language_id: &str, root: &Path) -> ServerStatus {
+        let key = (language_id.to_string(), root.to_path_buf());
+        match self.servers.get(&key) {
+            Some(server) if server.is_running() && server.capabilities().is_none() => ServerStatus::Starting,
+            Some(server) if server.is_running() => ServerStatus::Ready,
+            _ if self.crashes.get(&key).is_some_and(|crashes| *crashes <= MAX_RESTARTS) => {
+                ServerStatus::Restarting
+            }
+            _ => ServerStatus::Stopped,
+        }
+    }
+
+    /// Have `server` take on `folder` as a workspace folder, which it's then
+    /// found by; false when it can't, and must be given the documents of
+    /// another project anyway
+    pub fn add_folder(&mut self, server: &Arc<LanguageServer>, folder: &Path) -> bool {
+        if !server.supports_workspace_folders() {
+            return false;
+        }
+        if !server.workspace_folders().iter().any(|known| known == folder) {
+            tracing::debug!("📂 {} takes on {:?} as another workspace folder", server.name(), folder);
+            server.add_workspace_folder(folder);
+        }
+        self.servers
+            .insert((server.language_id().to_string(), folder.to_path_buf()), server.clone());
+        true
+    }
+
+    /// Drop `server` under every folder it has, with its documents
+    fn forget(&mut self, server: &Arc<LanguageServer>) {
+        self.servers.retain(|_, known| !Arc::ptr_eq(known, server));
+        self.documents.remove(&server_key(server));
+    }
+
+    /// All servers started through this registry, each once
+    pub fn servers(&self) -> impl Iterator<Item = &Arc<LanguageServer>> {
+        self.servers
+            .iter()
+            .filter(|(key, server)| **key == server_key(server))
+            .map(|(_, server)| server)
+    }
+
+    /// Note that `editor` has `path` open with `server`; the server hears
+    /// `didOpen` only from the first editor to open it
+    pub fn open_document(&mut self, server: &LanguageServer, path: &Path, text: &str, editor: u64) {
+        let documents = self.documents.entry(server_key(server)).or_default();
+        let document = documents.entry(path.to_path_buf()).or_insert_with(|| {
+            server.did_open(path, 1, text);
+            // The innermost, when one folder is inside another
+            let folder = server
+                .workspace_folders()
+                .into_iter()
+                .filter(|folder| path.starts_with(folder))
+                .max_by_key(|folder| folder.components().count())
+                .unwrap_or_else(|| server.root().to_path_buf());
+            OpenDocument {
+                editors: Vec::new(),
+                version: 1,
+                taken_over: false,
+                folder,
+            }
+        });
+        if !document.editors.contains(&editor) {
+            document.editors.push(editor);
+        }
+    }
+
+    /// Whether `editor` is the one that sends `path`'s changes to `server`
+    pub fn syncs_document(&self, server: &LanguageServer, path: &Path, editor: u64) -> bool {
+        self.document(server, path)
+            .is_none_or(|document| document.editors.first() == Some(&editor))
+    }
+
+    /// The version of `path` the server last heard of, which its diagnostics
+    /// are published for
+    pub fn document_version(&self, server: &LanguageServer, path: &Path) -> Option<i32> {
+        self.document(server, path).map(|document| document.version)
+    }
+
+    fn document(&self, server: &LanguageServer, path: &Path) -> Option<&OpenDocument> {
+        self.documents.get(&server_key(server))?.get(path)
+    }
+
+    /// Send `editor`'s changes of `path`, which leave it as `text`, unless
+    /// another editor syncs it
+    pub fn did_change(
+        &mut self,
+        server: &LanguageServer,
+        path: &Path,
+        editor: u64,
+        changes: Vec<lsp_types::TextDocumentContentChangeEvent>,
+        text: &str,
+    ) {
+        let Some(document) = self
+            .documents
+            .get_mut(&server_key(server))
+            .and_then(|documents| documents.get_mut(path))
+            .filter(|document| document.editors.first() == Some(&editor))
+        else {
+            return;
+        };
+        document.version += 1;
+        let changes = if std::mem::take(&mut document.taken_over) {
+            super::document_sync::full_change(text)
+        } else {
+            changes
+        };
+        server.did_change(path, document.version, changes);
+    }
+
+    /// Note that `editor` closed `path`. The server hears `didClose` once no
+    /// editor has it open; when the one that synced it closes, the next one
+    /// takes over.
+    ///
+    /// Returns the server if that closed its last document, for the caller to
+    /// shut down with `shut_down_servers`.
+    pub fn close_document(
+        &mut self,
+        server: &Arc<LanguageServer>,
+        path: &Path,
+        editor: u64,
+    ) -> Option<Arc<LanguageServer>> {
+        let key = server_key(server);
+        let documents = self.documents.get_mut(&key)?;
+        let document = documents.get_mut(path)?;
+        let position = document.editors.iter().position(|id| *id == editor)?;
+        document.editors.remove(position);
+        if !document.editors.is_empty() {
+            document.taken_over |= position == 0;
+            return None;
+        }
+        let folder = documents.remove(path)?.folder;
+        server.did_close(path);
+        if !documents.is_empty() {
+            // The last document of a folder it took on
+            if folder != server.root() && documents.values().all(|document| document.folder != folder) {
+                server.remove_workspace_folder(&folder);
+                let alias = (key.0.clone(), folder);
+                if self.servers.get(&alias).is_some_and(|known| Arc::ptr_eq(known, server)) {
+                    self.servers.remove(&alias);
+                }
+            }
+            return None;
+        }
+        self.documents.remove(&key);
+        // A server since replaced by a restart is already on its way out
+        if !self.servers.values().any(|known| Arc::ptr_eq(known, server)) {
+            return None;
+        }
+        self.forget(server);
+        Some(server.clone())
+    }
+}
+
+/// Shut down `servers` as the protocol has it, returning how many there
+/// were. All are asked at once and share `SHUTDOWN_TIMEOUT` to exit, after
+/// which those left are killed.
+pub fn shut_down_servers(servers: Vec<Arc<LanguageServer>>) -> usize {
+    let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+    let shutting_down: Vec<_> = servers
+        .into_iter()
+        .map(|server| {
+            let pending = server.begin_shutdown();
+            (server, pending)
+        })
+        .collect();
+    let count = shutting_down.len();
+    let mut killed = 0;
+    for (server, pending) in shutting_down {
+        if !server.finish_shutdown(pending, deadline) {
+            killed += 1;
+        }
+    }
+    if count > 0 {
+        tracing::info!("🛑 Shut down {} language servers ({} killed)", count, killed);
+    }
+    count
+}
+
+/// Shut down every server still running, whichever editor started it, and
+/// wait for them: for when the plugin is unloading
+pub fn shut_down_all_servers() -> usize {
+    shut_down_servers(running_servers())
+}
+
+/// Count a crash after `uptime` in `crashes`, the number in a row so far,
+/// returning the delay before the next restart unless that's one too many
+fn next_restart(crashes: &mut u32, uptime: Duration) -> Option<Duration> {
+    if uptime >= STABLE_UPTIME {
+        *crashes = 0;
+    }
+    *crashes += 1;
+    if *crashes > MAX_RESTARTS {
+        return None;
+    }
+    Some((RESTART_DELAY * 2u32.pow(*crashes - 1)).min(MAX_RESTART_DELAY))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restarts_back_off_then_give_up() {
+        let mut crashes = 0;
+        let quick = Duration::from_secs(1);
+        let delays: Vec<Option<Duration>> = (0..6).map(|_| next_restart(&mut crashes, quick)).collect();
+        let secs = |secs| Some(Duration::from_secs(secs));
+        assert_eq!(delays, [secs(1), secs(2), secs(4), secs(8), secs(16), None]);
+
+        // After running a good while, a crash is the first of a new run
+        assert_eq!(next_restart(&mut crashes, STABLE_UPTIME), secs(1));
+    }
+
+    /// Shutting down every server reaches those of other tests too
+    #[cfg(unix)]
+    static SERVER_TESTS: parking_lot::Mutex<()> = parking_lot::const_mutex(());
+
+    /// A registry whose Rust server is any process that keeps running
+    #[cfg(unix)]
+    fn sleeping_registry() -> LanguageServerRegistry {
+        let mut registry = LanguageServerRegistry::default();
+        registry.set_settings(BTreeMap::from([(
+            "rust".to_string(),
+            LanguageServerSettings {
+                command: Some("sleep".to_string()),
+                args: Some(vec!["30".to_string()]),
+                ..Default::default()
+            },
+        )]));
+        registry
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unloading_shuts_down_every_server() {
+        let _serial = SERVER_TESTS.lock();
+        let mut registry = sleeping_registry();
+        let root = std::env::temp_dir().join("registry-unload");
+        std::fs::create_dir_all(&root).unwrap();
+        let server = registry.get_or_start("rust", &root).unwrap();
+        registry.open_document(&server, &root.join("main.rs"), "", 1);
+        assert!(running_servers().iter().any(|running| Arc::ptr_eq(running, &server)));
+
+        // Still in use by an editor, as when the plugin unloads with files open
+        assert!(shut_down_all_servers() >= 1);
+        assert!(server.was_stopped() && server.exit_status().is_some());
+        assert!(!running_servers().iter().any(|running| Arc::ptr_eq(running, &server)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_editors_share_a_server_until_the_last_document_closes() {
+        let _serial = SERVER_TESTS.lock();
+        let mut registry = sleeping_registry();
+        let root = std::env::temp_dir().join("registry-shared-server");
+        std::fs::create_dir_all(&root).unwrap();
+        let (main, lib) = (root.join("main.rs"), root.join("lib.rs"));
+        for _ in 0..3 {
+            let editors: Vec<u64> = (1..=4).collect();
+            let server = registry.get_or_start("rust", &root).unwrap();
+            for &editor in &editors {
+                assert!(Arc::ptr_eq(&registry.get_or_start("rust", &root).unwrap(), &server));
+                registry.open_document(&server, &main, "", editor);
+            }
+            registry.open_document(&server, &lib, "", 4);
+            assert_eq!(registry.servers().count(), 1);
+
+            // Only the first editor's changes reach the server
+            registry.did_change(&server, &main, 2, Vec::new(), "");
+            assert_eq!(registry.document_version(&server, &main), Some(1));
+            registry.did_change(&server, &main, 1, Vec::new(), "");
+            assert_eq!(registry.document_version(&server, &main), Some(2));
+            assert!(registry.close_document(&server, &main, 1).is_none());
+            assert!(registry.syncs_document(&server, &main, 2));
+
+            for &editor in &editors[1..] {
+                assert!(registry.close_document(&server, &main, editor).is_none());
+            }
+            let closed = registry.close_document(&server, &lib, 4);
+            assert!(closed.is_some_and(|closed| Arc::ptr_eq(&closed, &server)));
+            assert_eq!(registry.servers().count(), 0);
+            // Never initialized, so killed rather than waited for
+            assert_eq!(shut_down_servers(vec![server.clone()]), 1);
+            assert!(server.was_stopped() && server.exit_status().is_some());
+        }
+    }
+}
@@ -0,0 +1,102 @@
+//! Requests a feature keeps resending as the user types
+//!
+//! Each completion or hover request supersedes the one before it, which is
+//! cancelled with `$/cancelRequest`; should its response arrive anyway, it's
+//! dropped. So is the response to a request made against an older version of
+//! the document than the buffer holds by the time it arrives.
+
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::Arc;
+
+/// A document's version, bumped by the editor on every change and read by
+/// the providers sending requests about it
+#[derive(Clone, Debug, Default)]
+pub struct DocumentVersion(Arc<AtomicI32>);
+
+impl DocumentVersion {
+    pub fn get(&self) -> i32 {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    pub fn set(&self, version: i32) {
+        self.0.store(version, Ordering::SeqCst);
+    }
+}
+
+/// A request and the document version it was made against
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TrackedRequest {
+    pub id: i64,
+    pub version: i32,
+}
+
+/// The latest request of one kind about one document
+#[derive(Clone, Debug, Default)]
+pub struct RequestTracker {
+    latest: Arc<Mutex<Option<TrackedRequest>>>,
+}
+
+impl RequestTracker {
+    /// Track a request just sent, returning the id of the one it supersedes
+    /// so it can be cancelled
+    pub fn start(&self, request: TrackedRequest) -> Option<i64> {
+        self.latest
+            .lock()
+            .replace(request)
+            .map(|previous| previous.id)
+            .filter(|previous| *previous != request.id)
+    }
+
+    /// Stop tracking the latest request, returning its id to cancel
+    pub fn take(&self) -> Option<i64> {
+        self.latest.lock().take().map(|request| request.id)
+    }
+
+    /// Whether the response to `request`, just arrived, should be applied to
+    /// a document now at `current_version`. The request stops being tracked
+    /// either way.
+    pub fn finish(&self, request: TrackedRequest, current_version: i32) -> bool {
+        let mut latest = self.latest.lock();
+        if *latest != Some(request) {
+            return false;
+        }
+        *latest = None;
+        request.version >= current_version
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_the_latest_completion_is_applied() {
+        let requests = RequestTracker::default();
+        let version = DocumentVersion::default();
+
+        // Typing five characters quickly sends a request after each; every
+        // one supersedes the request before it
+        let mut sent = Vec::new();
+        for (id, typed) in (1..=5).zip(2..) {
+            version.set(typed);
+            let request = TrackedRequest { id, version: version.get() };
+            let superseded = requests.start(request);
+            assert_eq!(superseded, sent.last().map(|request: &TrackedRequest| request.id));
+            sent.push(request);
+        }
+
+        // Responses arrive out of order; the superseded ones are dropped
+        let applied: Vec<i64> = [3, 5, 1, 4, 2]
+            .into_iter()
+            .filter(|id| requests.finish(sent[*id as usize - 1], version.get()))
+            .collect();
+        assert_eq!(applied, [5]);
+
+        // A response made stale by a later edit is dropped too
+        let request = TrackedRequest { id: 6, version: version.get() };
+        requests.start(request);
+        version.set(request.version + 1);
+        assert!(!requests.finish(request, version.get()));
+    }
+}
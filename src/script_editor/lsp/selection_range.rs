@@ -0,0 +1,35 @@
+//! Reading `textDocument/selectionRange` responses
+
+use lsp_types::SelectionRange;
+
+use super::convert::range_to_offsets;
+
+/// The ranges of a selection range and its parents, innermost first
+pub fn selection_range_chain(text: &str, selection_range: &SelectionRange) -> Vec<std::ops::Range<usize>> {
+    let mut chain = Vec::new();
+    let mut next = Some(selection_range);
+    while let Some(selection_range) = next {
+        chain.push(range_to_offsets(text, selection_range.range));
+        next = selection_range.parent.as_deref();
+    }
+    chain
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::{Position, Range};
+
+    #[test]
+    fn test_selection_range_chain() {
+        let line = |start, end| Range::new(Position::new(1, start), Position::new(1, end));
+        let selection_range = SelectionRange {
+            range: line(4, 7),
+            parent: Some(Box::new(SelectionRange {
+                range: line(0, 10),
+                parent: None,
+            })),
+        };
+        assert_eq!(selection_range_chain("x\nlet foo = 1;", &selection_range), [6..9, 2..12]);
+    }
+}
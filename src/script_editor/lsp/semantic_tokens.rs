@@ -0,0 +1,260 @@
+//! Semantic tokens from `textDocument/semanticTokens/full` and its delta
+//! variant: identifiers colored by what the server resolved them to, drawn
+//! over the lexer's highlighting
+//!
+//! Tokens arrive as runs of five numbers, each relative to the token before
+//! it: line delta, start column delta (from the previous token's start when
+//! on the same line), length, type index and modifier bits, the last two
+//! indexing the server's legend. Columns and lengths count UTF-16 units.
+
+use lsp_types::{
+    SemanticToken, SemanticTokensDeltaParams, SemanticTokensEdit, SemanticTokensFullOptions,
+    SemanticTokensLegend, SemanticTokensParams, SemanticTokensServerCapabilities, ServerCapabilities,
+    TextDocumentIdentifier,
+};
+use std::ops::Range;
+use std::path::Path;
+
+use super::convert::path_to_uri;
+
+/// Token types the client asks servers to use, in the order it lists them
+pub const TOKEN_TYPES: &[&str] = &[
+    "namespace", "type", "class", "enum", "interface", "struct", "typeParameter", "parameter",
+    "variable", "property", "enumMember", "event", "function", "method", "macro", "keyword",
+    "modifier", "comment", "string", "number", "regexp", "operator", "decorator",
+];
+
+pub const TOKEN_MODIFIERS: &[&str] = &[
+    "declaration", "definition", "readonly", "static", "deprecated", "abstract", "async",
+    "modification", "documentation", "defaultLibrary", "mutable",
+];
+
+/// How a server serves semantic tokens for whole documents
+#[derive(Clone, Debug)]
+pub struct SemanticTokensSupport {
+    pub legend: SemanticTokensLegend,
+    /// Whether it answers `semanticTokens/full/delta` with edits to the last result
+    pub delta: bool,
+}
+
+/// Tokens last received for a document
+#[derive(Clone, Debug, Default)]
+pub struct SemanticTokenState {
+    /// Id to ask the next delta against
+    pub result_id: Option<String>,
+    pub data: Vec<SemanticToken>,
+    /// Token ranges with their theme kinds, moved along with edits until new
+    /// tokens arrive
+    pub highlights: Vec<(Range<usize>, &'static str)>,
+}
+
+/// The legend and delta support of a server that gives full-document tokens,
+/// or `None` when only the lexer highlights its files
+pub fn semantic_tokens_support(capabilities: &ServerCapabilities) -> Option<SemanticTokensSupport> {
+    let options = match capabilities.semantic_tokens_provider.as_ref()? {
+        SemanticTokensServerCapabilities::SemanticTokensOptions(options) => options,
+        SemanticTokensServerCapabilities::SemanticTokensRegistrationOptions(options) => {
+            &options.semantic_tokens_options
+        }
+    };
+    let delta = match options.full.as_ref()? {
+        SemanticTokensFullOptions::Bool(false) => return None,
+        SemanticTokensFullOptions::Bool(true) => false,
+        SemanticTokensFullOptions::Delta { delta } => *delta == Some(true),
+    };
+    Some(SemanticTokensSupport {
+        legend: options.legend.clone(),
+        delta,
+    })
+}
+
+pub fn semantic_tokens_params(path: &Path) -> Option<SemanticTokensParams> {
+    Some(SemanticTokensParams {
+        text_document: TextDocumentIdentifier { uri: path_to_uri(path)? },
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    })
+}
+
+pub fn semantic_tokens_delta_params(path: &Path, previous_result_id: String) -> Option<SemanticTokensDeltaParams> {
+    Some(SemanticTokensDeltaParams {
+        text_document: TextDocumentIdentifier { uri: path_to_uri(path)? },
+        previous_result_id,
+        work_done_progress_params: Default::default(),
+        partial_result_params: Default::default(),
+    })
+}
+
+/// The tokens after a delta response. Edits index the flat array of numbers
+/// and all refer to the previous result, so they apply from the last one back.
+pub fn apply_semantic_token_edits(data: &[SemanticToken], mut edits: Vec<SemanticTokensEdit>) -> Vec<SemanticToken> {
+    let mut numbers: Vec<u32> = data
+        .iter()
+        .flat_map(|token| {
+            [token.delta_line, token.delta_start, token.length, token.token_type, token.token_modifiers_bitset]
+        })
+        .collect();
+    edits.sort_by_key(|edit| std::cmp::Reverse(edit.start));
+    for edit in edits {
+        let start = (edit.start as usize).min(numbers.len());
+        let end = (start + edit.delete_count as usize).min(numbers.len());
+        let inserted = edit.data.unwrap_or_default().into_iter().flat_map(|token| {
+            [token.delta_line, token.delta_start, token.length, token.token_type, token.token_modifiers_bitset]
+        });
+        numbers.splice(start..end, inserted);
+    }
+    numbers
+        .chunks_exact(5)
+        .map(|token| SemanticToken {
+            delta_line: token[0],
+            delta_start: token[1],
+            length: token[2],
+            token_type: token[3],
+            token_modifiers_bitset: token[4],
+        })
+        .collect()
+}
+
+/// Byte ranges of `text` colored by the tokens, with the theme kind of each;
+/// tokens of types the theme has no kind for are left to the lexer
+pub fn semantic_highlights(
+    text: &str,
+    data: &[SemanticToken],
+    legend: &SemanticTokensLegend,
+) -> Vec<(Range<usize>, &'static str)> {
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(text.match_indices('\n').map(|(ix, _)| ix + 1))
+        .collect();
+    let mut highlights = Vec::new();
+    let (mut line, mut column) = (0u32, 0u32);
+    for token in data {
+        if token.delta_line > 0 {
+            line += token.delta_line;
+            column = token.delta_start;
+        } else {
+            column += token.delta_start;
+        }
+        let Some(token_type) = legend.token_types.get(token.token_type as usize) else {
+            continue;
+        };
+        let modifiers: Vec<&str> = legend
+            .token_modifiers
+            .iter()
+            .enumerate()
+            .filter(|(bit, _)| *bit < 32 && token.token_modifiers_bitset & (1 << bit) != 0)
+            .map(|(_, modifier)| modifier.as_str())
+            .collect();
+        let Some(kind) = token_kind(token_type.as_str(), &modifiers) else {
+            continue;
+        };
+        let Some(&line_start) = line_starts.get(line as usize) else {
+            break;
+        };
+        let line_end = line_starts.get(line as usize + 1).map_or(text.len(), |next| next - 1);
+        let line_text = &text[line_start..line_end];
+        let start = line_start + utf16_column_to_byte(line_text, column);
+        let end = line_start + utf16_column_to_byte(line_text, column + token.length);
+        if start < end {
+            highlights.push((start..end, kind));
+        }
+    }
+    highlights
+}
+
+/// Theme kind for a token type and its modifiers: the role a name resolved to
+/// picks the same colors the lexer uses for that kind of token
+pub fn token_kind(token_type: &str, modifiers: &[&str]) -> Option<&'static str> {
+    let has = |modifier: &str| modifiers.contains(&modifier);
+    let kind = match token_type {
+        "namespace" | "type" | "class" | "enum" | "interface" | "struct" | "typeParameter" | "typeAlias"
+        | "builtinType" | "selfTypeKeyword" => "type",
+        "variable" | "parameter" if has("readonly") && has("static") => "constant",
+        "variable" | "parameter" if has("mutable") => "variable.special",
+        "variable" | "parameter" | "selfKeyword" => "variable",
+        "property" => "property",
+        "enumMember" | "const" | "static" => "constant",
+        "function" | "method" => "function",
+        "macro" | "derive" => "preproc",
+        "decorator" | "attribute" | "attributeBracket" => "attribute",
+        "keyword" | "modifier" => "keyword",
+        "comment" => "comment",
+        "string" => "string",
+        "number" => "number",
+        "boolean" => "boolean",
+        "operator" => "operator",
+        "lifetime" | "label" => "label",
+        _ => return None,
+    };
+    Some(kind)
+}
+
+/// Byte offset of a UTF-16 column in a line, clamped to its end
+fn utf16_column_to_byte(line: &str, column: u32) -> usize {
+    let mut units = 0;
+    for (ix, ch) in line.char_indices() {
+        if units >= column {
+            return ix;
+        }
+        units += ch.len_utf16() as u32;
+    }
+    line.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::{SemanticTokenModifier, SemanticTokenType};
+
+    fn token(delta_line: u32, delta_start: u32, length: u32, token_type: u32, modifiers: u32) -> SemanticToken {
+        SemanticToken {
+            delta_line,
+            delta_start,
+            length,
+            token_type,
+            token_modifiers_bitset: modifiers,
+        }
+    }
+
+    fn legend() -> SemanticTokensLegend {
+        SemanticTokensLegend {
+            token_types: ["variable", "function", "struct", "macro", "unknownThing"]
+                .into_iter()
+                .map(SemanticTokenType::new)
+                .collect(),
+            token_modifiers: ["declaration", "mutable"].into_iter().map(SemanticTokenModifier::new).collect(),
+        }
+    }
+
+    #[test]
+    fn test_semantic_highlights() {
+        let text = "let mut é = Foo;\nprintln!(\"{}\", é);\nx();";
+        let data = [
+            // `é` on line 0, declared mutable (2 bytes, 1 UTF-16 unit)
+            token(0, 8, 1, 0, 0b11),
+            // `Foo` three units after the start of `é`
+            token(0, 4, 3, 2, 0),
+            token(1, 0, 7, 3, 0),
+            // `é` on line 1 at column 15
+            token(0, 15, 1, 0, 0),
+            // Types the theme doesn't know are left to the lexer
+            token(1, 0, 1, 4, 0),
+        ];
+        let highlights = semantic_highlights(text, &data, &legend());
+        let colored: Vec<(&str, &str)> = highlights.iter().map(|(range, kind)| (&text[range.clone()], *kind)).collect();
+        assert_eq!(
+            colored,
+            [("é", "variable.special"), ("Foo", "type"), ("println", "preproc"), ("é", "variable")]
+        );
+    }
+
+    #[test]
+    fn test_apply_semantic_token_edits() {
+        let data = [token(0, 0, 3, 1, 0), token(1, 4, 2, 0, 0), token(2, 0, 5, 2, 0)];
+        let edits = vec![
+            // Drop the last token, and replace the second with a longer one
+            SemanticTokensEdit { start: 10, delete_count: 5, data: None },
+            SemanticTokensEdit { start: 5, delete_count: 5, data: Some(vec![token(1, 4, 6, 0, 2)]) },
+        ];
+        assert_eq!(apply_semantic_token_edits(&data, edits), [token(0, 0, 3, 1, 0), token(1, 4, 6, 0, 2)]);
+    }
+}
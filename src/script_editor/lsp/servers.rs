@@ -0,0 +1,229 @@
+//! Known language servers and how to launch them, or the ones the user
+//! configured instead
+
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+use super::super::settings::LanguageServerSettings;
+
+/// How to launch a language server process
+#[derive(Clone, Debug, PartialEq)]
+pub struct ServerCommand {
+    /// Display name, e.g. `rust-analyzer`
+    pub name: String,
+    /// Executable name or path
+    pub command: String,
+    pub args: Vec<String>,
+    /// Variables added to the editor's environment
+    pub env: Vec<(String, String)>,
+    /// Folder to run in, when not the workspace root
+    pub cwd: Option<PathBuf>,
+    pub initialization_options: Option<Value>,
+}
+
+impl ServerCommand {
+    pub fn new(name: &str, command: &str, args: &[&str]) -> Self {
+        Self {
+            name: name.to_string(),
+            command: command.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            env: Vec::new(),
+            cwd: None,
+            initialization_options: None,
+        }
+    }
+}
+
+/// The server for an LSP language id started in `root`: the built-in one
+/// with the user's `settings` for the language applied, if any
+pub fn server_command(
+    language_id: &str,
+    root: &Path,
+    settings: Option<&LanguageServerSettings>,
+) -> Option<ServerCommand> {
+    let Some(settings) = settings else {
+        return default_server_command(language_id, root);
+    };
+    let expand = |value: &str| expand_env(value, |name| std::env::var(name).ok());
+    let default = default_server_command(language_id, root);
+    let mut command = match (&settings.command, default) {
+        (Some(command), default) => {
+            let command = expand(command);
+            match default {
+                // The same server keeps the arguments it's launched with by default
+                Some(default) if default.command == command => default,
+                _ => {
+                    let name = Path::new(&command)
+                        .file_stem()
+                        .map_or_else(|| command.clone(), |stem| stem.to_string_lossy().to_string());
+                    ServerCommand::new(&name, &command, &[])
+                }
+            }
+        }
+        (None, Some(default)) => default,
+        (None, None) => return None,
+    };
+    if let Some(args) = &settings.args {
+        command.args = args.iter().map(|arg| expand(arg)).collect();
+    }
+    command.env = settings.env.iter().map(|(name, value)| (name.clone(), expand(value))).collect();
+    command.cwd = settings.cwd.as_ref().map(|cwd| root.join(expand(&cwd.to_string_lossy())));
+    if settings.initialization_options.is_some() {
+        command.initialization_options = settings.initialization_options.clone();
+    }
+    Some(command)
+}
+
+/// `value` with each `${NAME}` replaced by what `lookup` gives for `NAME`,
+/// or nothing when that's unset
+fn expand_env(value: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    let mut expanded = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        let Some(end) = rest[start..].find('}') else {
+            break;
+        };
+        expanded.push_str(&rest[..start]);
+        expanded.push_str(&lookup(&rest[start + 2..start + end]).unwrap_or_default());
+        rest = &rest[start + end + 1..];
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+/// Default server for an LSP language id started in `root`, if one is known
+pub fn default_server_command(language_id: &str, root: &Path) -> Option<ServerCommand> {
+    match language_id {
+        "rust" => Some(ServerCommand::new("rust-analyzer", "rust-analyzer", &[])),
+        "javascript" | "typescript" => Some(ServerCommand::new(
+            "typescript-language-server",
+            "typescript-language-server",
+            &["--stdio"],
+        )),
+        "python" => Some(ServerCommand::new("pylsp", "pylsp", &[])),
+        "json" | "jsonc" => Some(ServerCommand::new(
+            "vscode-json-language-server",
+            "vscode-json-language-server",
+            &["--stdio"],
+        )),
+        "yaml" => Some(ServerCommand::new(
+            "yaml-language-server",
+            "yaml-language-server",
+            &["--stdio"],
+        )),
+        "go" => Some(ServerCommand::new("gopls", "gopls", &[])),
+        "zig" => Some(ServerCommand::new("zls", "zls", &[])),
+        "lua" => Some(lua_language_server()),
+        // Without a compilation database clangd guesses flags and reports
+        // errors for every include, so those files are only highlighted
+        "c" | "cpp" => {
+            let Some(folder) = find_compile_commands(root) else {
+                tracing::debug!("ℹ️  No compile_commands.json above {:?}, not starting clangd", root);
+                return None;
+            };
+            Some(ServerCommand::new(
+                "clangd",
+                "clangd",
+                &[&format!("--compile-commands-dir={}", folder.display())],
+            ))
+        }
+        _ => None,
+    }
+}
+
+/// lua-language-server, which takes its client options from `initializationOptions`
+fn lua_language_server() -> ServerCommand {
+    let mut command = ServerCommand::new("lua-language-server", "lua-language-server", &[]);
+    command.initialization_options = Some(json!({
+        // Settings stay in `.luarc.json`; the server doesn't get to rewrite them
+        "changeConfiguration": false,
+        // Neither has a place in the editor: a status bar item and virtual documents
+        "statusBar": false,
+        "viewDocument": false,
+    }));
+    command
+}
+
+/// Folder holding the `compile_commands.json` for files under `root`: in
+/// `root` or a folder above it, or in the `build` folder of one of those
+pub fn find_compile_commands(root: &Path) -> Option<PathBuf> {
+    root.ancestors().find_map(|folder| {
+        [folder.to_path_buf(), folder.join("build")]
+            .into_iter()
+            .find(|candidate| candidate.join("compile_commands.json").is_file())
+    })
+}
+
+/// Locate an executable on `PATH`
+pub fn find_in_path(command: &str) -> Option<PathBuf> {
+    let candidate = PathBuf::from(command);
+    if candidate.components().count() > 1 {
+        return candidate.is_file().then_some(candidate);
+    }
+
+    let extensions: &[&str] = if cfg!(target_os = "windows") {
+        &["exe", "cmd", "bat"]
+    } else {
+        &[]
+    };
+
+    std::env::split_paths(&std::env::var_os("PATH")?).find_map(|dir| {
+        let path = dir.join(command);
+        if path.is_file() {
+            return Some(path);
+        }
+        extensions
+            .iter()
+            .map(|ext| path.with_extension(ext))
+            .find(|path| path.is_file())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_configured_server_command() {
+        let root = Path::new("/work/project");
+        // Custom flags for the built-in Rust server
+        let settings = LanguageServerSettings {
+            args: Some(vec!["--log-file".into(), "/tmp/ra.log".into()]),
+            env: BTreeMap::from([("RA_LOG".to_string(), "info".to_string())]),
+            cwd: Some(PathBuf::from("crates/core")),
+            ..Default::default()
+        };
+        let command = server_command("rust", root, Some(&settings)).unwrap();
+        assert_eq!(command.name, "rust-analyzer");
+        assert_eq!(command.args, ["--log-file", "/tmp/ra.log"]);
+        assert_eq!(command.env, [("RA_LOG".to_string(), "info".to_string())]);
+        assert_eq!(command.cwd, Some(root.join("crates/core")));
+
+        // Another Python server, which doesn't inherit pylsp's arguments
+        let settings = LanguageServerSettings {
+            command: Some("pyright-langserver".into()),
+            args: Some(vec!["--stdio".into()]),
+            initialization_options: Some(serde_json::json!({ "python": { "analysis": {} } })),
+            ..Default::default()
+        };
+        let command = server_command("python", root, Some(&settings)).unwrap();
+        assert_eq!((command.name.as_str(), command.args.as_slice()), ("pyright-langserver", &["--stdio".to_string()][..]));
+        assert!(command.initialization_options.is_some());
+
+        // Languages without a built-in server need a command
+        assert!(server_command("ruby", root, Some(&LanguageServerSettings::default())).is_none());
+        // The built-in server's own options stay unless replaced
+        let command = server_command("lua", root, Some(&LanguageServerSettings::default())).unwrap();
+        assert_eq!(command.initialization_options.unwrap()["changeConfiguration"], false);
+        assert_eq!(server_command("go", root, None), default_server_command("go", root));
+    }
+
+    #[test]
+    fn test_expand_env() {
+        let lookup = |name: &str| (name == "HOME").then(|| "/home/me".to_string());
+        assert_eq!(expand_env("${HOME}/bin/zls", lookup), "/home/me/bin/zls");
+        assert_eq!(expand_env("--x=${UNSET}!", lookup), "--x=!");
+        assert_eq!(expand_env("${unterminated", lookup), "${unterminated");
+    }
+}
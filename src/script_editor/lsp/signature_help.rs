@@ -0,0 +1,160 @@
+//! Signature help results from a language server
+
+use lsp_types::{Documentation, ParameterLabel, SignatureHelp};
+use std::ops::Range;
+
+use super::convert::utf16_to_offset;
+
+/// One signature of the function being called
+#[derive(Clone, Debug, PartialEq)]
+pub struct Signature {
+    pub label: String,
+    /// Byte range of each parameter within `label`
+    pub parameters: Vec<Range<usize>>,
+    pub documentation: Option<String>,
+    /// Parameter the cursor is on, if this signature says so itself
+    active_parameter: Option<usize>,
+}
+
+/// Signatures (more than one for overloads) with the cursor's signature and parameter
+#[derive(Clone, Debug, PartialEq)]
+pub struct Signatures {
+    pub signatures: Vec<Signature>,
+    pub active_signature: usize,
+    active_parameter: Option<usize>,
+}
+
+impl Signatures {
+    /// `None` when the server sent no signatures
+    pub fn from_lsp(help: SignatureHelp) -> Option<Self> {
+        let signatures: Vec<Signature> = help
+            .signatures
+            .into_iter()
+            .map(|signature| {
+                let parameters = signature
+                    .parameters
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|parameter| parameter_range(&signature.label, parameter.label))
+                    .collect();
+                Signature {
+                    documentation: signature.documentation.map(documentation_text),
+                    active_parameter: signature.active_parameter.map(|ix| ix as usize),
+                    parameters,
+                    label: signature.label,
+                }
+            })
+            .collect();
+        if signatures.is_empty() {
+            return None;
+        }
+        let active_signature = (help.active_signature.unwrap_or(0) as usize).min(signatures.len() - 1);
+        Some(Self {
+            signatures,
+            active_signature,
+            active_parameter: help.active_parameter.map(|ix| ix as usize),
+        })
+    }
+
+    pub fn active(&self) -> &Signature {
+        &self.signatures[self.active_signature]
+    }
+
+    /// Byte range in the active signature's label of the parameter being typed
+    pub fn active_parameter_range(&self) -> Option<Range<usize>> {
+        let signature = self.active();
+        let ix = signature.active_parameter.or(self.active_parameter).unwrap_or(0);
+        signature.parameters.get(ix).cloned()
+    }
+
+    /// Show the next (or previous) overload, wrapping around
+    pub fn cycle(&mut self, forward: bool) {
+        let count = self.signatures.len();
+        self.active_signature = if forward {
+            (self.active_signature + 1) % count
+        } else {
+            (self.active_signature + count - 1) % count
+        };
+    }
+}
+
+/// Locate a parameter in its signature's label.
+///
+/// Labels given as a string are found by the first match after the opening
+/// parenthesis, so a parameter named like the function isn't found in its name.
+fn parameter_range(label: &str, parameter: ParameterLabel) -> Option<Range<usize>> {
+    match parameter {
+        ParameterLabel::LabelOffsets([start, end]) => {
+            Some(utf16_to_offset(label, start as usize)..utf16_to_offset(label, end as usize))
+        }
+        ParameterLabel::Simple(name) => {
+            let search_from = label.find('(').map_or(0, |ix| ix + 1);
+            let start = search_from + label[search_from..].find(&name)?;
+            Some(start..start + name.len())
+        }
+    }
+}
+
+fn documentation_text(documentation: Documentation) -> String {
+    match documentation {
+        Documentation::String(text) => text,
+        Documentation::MarkupContent(markup) => markup.value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::{ParameterInformation, SignatureInformation};
+
+    fn signature(label: &str, parameters: Vec<ParameterLabel>) -> SignatureInformation {
+        SignatureInformation {
+            label: label.to_string(),
+            documentation: None,
+            parameters: Some(
+                parameters
+                    .into_iter()
+                    .map(|label| ParameterInformation {
+                        label,
+                        documentation: None,
+                    })
+                    .collect(),
+            ),
+            active_parameter: None,
+        }
+    }
+
+    #[test]
+    fn test_parameter_ranges() {
+        let help = SignatureHelp {
+            signatures: vec![signature(
+                "fn add(a: i32, b: i32) -> i32",
+                vec![
+                    ParameterLabel::Simple("a: i32".into()),
+                    ParameterLabel::LabelOffsets([15, 21]),
+                ],
+            )],
+            active_signature: None,
+            active_parameter: Some(1),
+        };
+        let signatures = Signatures::from_lsp(help).unwrap();
+        assert_eq!(signatures.active().parameters, vec![7..13, 15..21]);
+        assert_eq!(signatures.active_parameter_range(), Some(15..21));
+    }
+
+    #[test]
+    fn test_cycle_overloads() {
+        let help = SignatureHelp {
+            signatures: vec![signature("f()", vec![]), signature("f(x)", vec![])],
+            active_signature: Some(5),
+            active_parameter: None,
+        };
+        let mut signatures = Signatures::from_lsp(help).unwrap();
+        // Out-of-range indices from the server are clamped
+        assert_eq!(signatures.active_signature, 1);
+        signatures.cycle(true);
+        assert_eq!(signatures.active_signature, 0);
+        signatures.cycle(false);
+        assert_eq!(signatures.active_signature, 1);
+    }
+}
@@ -0,0 +1,244 @@
+//! Flattening `WorkspaceEdit`s into per-file changes
+
+use lsp_types::{
+    ApplyWorkspaceEditResponse, DocumentChangeOperation, DocumentChanges, OneOf, ResourceOp, TextEdit,
+    WorkspaceEdit,
+};
+use std::path::{Path, PathBuf};
+
+use super::convert::uri_to_path;
+
+/// One step of a workspace edit, in the order it must be applied
+#[derive(Clone, Debug)]
+pub enum WorkspaceChange {
+    Edit(PathBuf, Vec<TextEdit>),
+    Create(PathBuf, FileOptions),
+    Rename(PathBuf, PathBuf, FileOptions),
+    Delete(PathBuf, DeleteOptions),
+}
+
+/// What a create or rename does when its target exists; with neither set, it fails
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FileOptions {
+    /// Replace the file there, winning over `ignore_if_exists`
+    pub overwrite: bool,
+    /// Leave the file there and skip the change
+    pub ignore_if_exists: bool,
+}
+
+/// Whether a delete may take a folder's contents, and skip a missing path
+/// rather than fail
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DeleteOptions {
+    pub recursive: bool,
+    pub ignore_if_not_exists: bool,
+}
+
+impl WorkspaceChange {
+    /// Whether the change goes ahead (`true`) or is skipped as its options
+    /// ask, or why it can't be made. `exists` tells whether a path is there
+    /// once the changes before this one are made, and `has_entries` whether
+    /// it's a folder with anything in it.
+    pub fn check(&self, exists: impl Fn(&Path) -> bool, has_entries: impl Fn(&Path) -> bool) -> Result<bool, String> {
+        match self {
+            WorkspaceChange::Edit(..) => Ok(true),
+            WorkspaceChange::Create(path, options) => existing_target(path, *options, &exists),
+            WorkspaceChange::Rename(from, to, options) => {
+                if !exists(from) {
+                    return Err(format!("{} doesn't exist", from.display()));
+                }
+                existing_target(to, *options, &exists)
+            }
+            WorkspaceChange::Delete(path, options) => {
+                if !exists(path) {
+                    return match options.ignore_if_not_exists {
+                        true => Ok(false),
+                        false => Err(format!("{} doesn't exist", path.display())),
+                    };
+                }
+                if !options.recursive && has_entries(path) {
+                    return Err(format!("{} isn't empty", path.display()));
+                }
+                Ok(true)
+            }
+        }
+    }
+}
+
+fn existing_target(path: &Path, options: FileOptions, exists: impl Fn(&Path) -> bool) -> Result<bool, String> {
+    if !exists(path) || options.overwrite {
+        Ok(true)
+    } else if options.ignore_if_exists {
+        Ok(false)
+    } else {
+        Err(format!("{} already exists", path.display()))
+    }
+}
+
+/// Why a workspace edit wasn't applied, or was only applied in part
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EditFailure {
+    pub reason: String,
+    /// Index of the change that failed once the ones before it were made;
+    /// `None` when nothing was changed
+    pub failed_change: Option<usize>,
+}
+
+impl std::fmt::Display for EditFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.failed_change {
+            Some(index) => write!(f, "stopped at change {} of the edit: {}", index + 1, self.reason),
+            None => f.write_str(&self.reason),
+        }
+    }
+}
+
+/// The answer to a server's `workspace/applyEdit`, from how applying it went
+pub fn apply_edit_response<T>(result: &Result<T, EditFailure>) -> ApplyWorkspaceEditResponse {
+    match result {
+        Ok(_) => ApplyWorkspaceEditResponse {
+            applied: true,
+            failure_reason: None,
+            failed_change: None,
+        },
+        Err(failure) => ApplyWorkspaceEditResponse {
+            applied: false,
+            failure_reason: Some(failure.to_string()),
+            failed_change: failure.failed_change.map(|index| index as u32),
+        },
+    }
+}
+
+/// Resolve a workspace edit into ordered changes.
+///
+/// `documentChanges` wins over `changes` when a server sends both, as the
+/// spec asks. URIs that are not `file://` paths are skipped.
+pub fn workspace_changes(edit: WorkspaceEdit) -> Vec<WorkspaceChange> {
+    if let Some(document_changes) = edit.document_changes {
+        let operations = match document_changes {
+            DocumentChanges::Edits(edits) => edits
+                .into_iter()
+                .map(DocumentChangeOperation::Edit)
+                .collect(),
+            DocumentChanges::Operations(operations) => operations,
+        };
+        return operations
+            .into_iter()
+            .filter_map(|operation| match operation {
+                DocumentChangeOperation::Edit(edit) => {
+                    let path = uri_to_path(&edit.text_document.uri)?;
+                    let edits = edit
+                        .edits
+                        .into_iter()
+                        .map(|edit| match edit {
+                            OneOf::Left(edit) => edit,
+                            OneOf::Right(annotated) => annotated.text_edit,
+                        })
+                        .collect();
+                    Some(WorkspaceChange::Edit(path, edits))
+                }
+                DocumentChangeOperation::Op(ResourceOp::Create(create)) => {
+                    let options = create.options.map_or_else(FileOptions::default, |options| FileOptions {
+                        overwrite: options.overwrite.unwrap_or(false),
+                        ignore_if_exists: options.ignore_if_exists.unwrap_or(false),
+                    });
+                    Some(WorkspaceChange::Create(uri_to_path(&create.uri)?, options))
+                }
+                DocumentChangeOperation::Op(ResourceOp::Rename(rename)) => {
+                    let options = rename.options.map_or_else(FileOptions::default, |options| FileOptions {
+                        overwrite: options.overwrite.unwrap_or(false),
+                        ignore_if_exists: options.ignore_if_exists.unwrap_or(false),
+                    });
+                    Some(WorkspaceChange::Rename(
+                        uri_to_path(&rename.old_uri)?,
+                        uri_to_path(&rename.new_uri)?,
+                        options,
+                    ))
+                }
+                DocumentChangeOperation::Op(ResourceOp::Delete(delete)) => {
+                    let options = delete.options.map_or_else(DeleteOptions::default, |options| DeleteOptions {
+                        recursive: options.recursive.unwrap_or(false),
+                        ignore_if_not_exists: options.ignore_if_not_exists.unwrap_or(false),
+                    });
+                    Some(WorkspaceChange::Delete(uri_to_path(&delete.uri)?, options))
+                }
+            })
+            .collect();
+    }
+
+    let mut changes: Vec<WorkspaceChange> = edit
+        .changes
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|(uri, edits)| Some(WorkspaceChange::Edit(uri_to_path(&uri)?, edits)))
+        .collect();
+    // `changes` is an unordered map; sort so the result is deterministic
+    changes.sort_by(|a, b| match (a, b) {
+        (WorkspaceChange::Edit(a, _), WorkspaceChange::Edit(b, _)) => a.cmp(b),
+        _ => std::cmp::Ordering::Equal,
+    });
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `check` against a disk holding only `/p/a.rs` and the folder `/p/dir` with a file in it
+    fn check(change: WorkspaceChange) -> Result<bool, String> {
+        let exists = |path: &Path| path == Path::new("/p/a.rs") || path == Path::new("/p/dir");
+        change.check(exists, |path| path == Path::new("/p/dir"))
+    }
+
+    #[test]
+    fn test_create_and_rename_options() {
+        let (a, b) = (PathBuf::from("/p/a.rs"), PathBuf::from("/p/b.rs"));
+        let overwrite = FileOptions { overwrite: true, ignore_if_exists: true };
+        let ignore = FileOptions { ignore_if_exists: true, ..Default::default() };
+
+        assert_eq!(check(WorkspaceChange::Create(b.clone(), FileOptions::default())), Ok(true));
+        assert!(check(WorkspaceChange::Create(a.clone(), FileOptions::default())).is_err());
+        assert_eq!(check(WorkspaceChange::Create(a.clone(), ignore)), Ok(false));
+        assert_eq!(check(WorkspaceChange::Create(a.clone(), overwrite)), Ok(true));
+
+        assert_eq!(check(WorkspaceChange::Rename(a.clone(), b.clone(), FileOptions::default())), Ok(true));
+        assert!(check(WorkspaceChange::Rename(b.clone(), a.clone(), overwrite)).is_err());
+        let onto_existing = |options| check(WorkspaceChange::Rename(a.clone(), PathBuf::from("/p/dir"), options));
+        assert!(onto_existing(FileOptions::default()).is_err());
+        assert_eq!(onto_existing(ignore), Ok(false));
+        assert_eq!(onto_existing(overwrite), Ok(true));
+    }
+
+    #[test]
+    fn test_delete_options() {
+        let delete = |path: &str, recursive, ignore_if_not_exists| {
+            check(WorkspaceChange::Delete(PathBuf::from(path), DeleteOptions { recursive, ignore_if_not_exists }))
+        };
+        assert_eq!(delete("/p/a.rs", false, false), Ok(true));
+        assert!(delete("/p/missing.rs", false, false).is_err());
+        assert_eq!(delete("/p/missing.rs", false, true), Ok(false));
+        assert!(delete("/p/dir", false, false).is_err());
+        assert_eq!(delete("/p/dir", true, false), Ok(true));
+    }
+
+    #[test]
+    fn test_apply_edit_response() {
+        assert!(apply_edit_response(&Ok(2)).applied);
+
+        let rejected = apply_edit_response::<usize>(&Err(EditFailure {
+            reason: "/p/a.rs already exists".into(),
+            failed_change: None,
+        }));
+        assert!(!rejected.applied);
+        assert_eq!(rejected.failure_reason.as_deref(), Some("/p/a.rs already exists"));
+        assert_eq!(rejected.failed_change, None);
+
+        let partial = apply_edit_response::<usize>(&Err(EditFailure {
+            reason: "permission denied".into(),
+            failed_change: Some(2),
+        }));
+        assert!(!partial.applied);
+        assert_eq!(partial.failed_change, Some(2));
+        assert_eq!(partial.failure_reason.as_deref(), Some("stopped at change 3 of the edit: permission denied"));
+    }
+}
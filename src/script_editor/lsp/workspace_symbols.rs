@@ -0,0 +1,120 @@
+//! Workspace symbol search results from language servers
+
+use lsp_types::{OneOf, Position, SymbolKind, WorkspaceSymbolResponse};
+use std::path::{Path, PathBuf};
+
+use super::convert::uri_to_path;
+
+/// A symbol somewhere in the workspace
+#[derive(Clone, Debug, PartialEq)]
+pub struct WorkspaceSymbolItem {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// Enclosing symbol as the server reports it (e.g. the `impl` or module)
+    pub container: Option<String>,
+    pub path: PathBuf,
+    /// Where the symbol starts; the top of the file when the server only sent a file
+    pub position: Position,
+}
+
+/// Flatten either response shape, keeping only symbols in files under `root`
+pub fn workspace_symbol_items(response: WorkspaceSymbolResponse, root: &Path) -> Vec<WorkspaceSymbolItem> {
+    let items: Vec<WorkspaceSymbolItem> = match response {
+        WorkspaceSymbolResponse::Flat(symbols) => symbols
+            .into_iter()
+            .filter_map(|symbol| {
+                Some(WorkspaceSymbolItem {
+                    path: uri_to_path(&symbol.location.uri)?,
+                    position: symbol.location.range.start,
+                    name: symbol.name,
+                    kind: symbol.kind,
+                    container: symbol.container_name,
+                })
+            })
+            .collect(),
+        WorkspaceSymbolResponse::Nested(symbols) => symbols
+            .into_iter()
+            .filter_map(|symbol| {
+                let (path, position) = match symbol.location {
+                    OneOf::Left(location) => (uri_to_path(&location.uri)?, location.range.start),
+                    OneOf::Right(location) => (uri_to_path(&location.uri)?, Position::default()),
+                };
+                Some(WorkspaceSymbolItem {
+                    path,
+                    position,
+                    name: symbol.name,
+                    kind: symbol.kind,
+                    container: symbol.container_name,
+                })
+            })
+            .collect(),
+    };
+    items.into_iter().filter(|item| item.path.starts_with(root)).collect()
+}
+
+/// Combine results from several servers, dropping symbols reported twice
+pub fn merge_workspace_symbols(results: impl IntoIterator<Item = Vec<WorkspaceSymbolItem>>) -> Vec<WorkspaceSymbolItem> {
+    let mut merged: Vec<WorkspaceSymbolItem> = Vec::new();
+    for item in results.into_iter().flatten() {
+        let duplicate = merged
+            .iter()
+            .any(|seen| seen.name == item.name && seen.path == item.path && seen.position == item.position);
+        if !duplicate {
+            merged.push(item);
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::{Location, Range, SymbolInformation, Uri};
+    use std::str::FromStr;
+
+    #[allow(deprecated)]
+    fn symbol(name: &str, uri: &str, line: u32) -> SymbolInformation {
+        SymbolInformation {
+            name: name.to_string(),
+            kind: SymbolKind::FUNCTION,
+            tags: None,
+            deprecated: None,
+            location: Location {
+                uri: Uri::from_str(uri).unwrap(),
+                range: Range::new(Position::new(line, 4), Position::new(line, 8)),
+            },
+            container_name: None,
+        }
+    }
+
+    #[test]
+    fn test_items_outside_root_are_dropped() {
+        let response = WorkspaceSymbolResponse::Flat(vec![
+            symbol("run", "file:///project/src/main.rs", 3),
+            symbol("spawn", "file:///home/.cargo/registry/tokio/src/lib.rs", 10),
+        ]);
+        let items = workspace_symbol_items(response, Path::new("/project"));
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].name, "run");
+        assert_eq!(items[0].position, Position::new(3, 4));
+    }
+
+    #[test]
+    fn test_merge_drops_duplicates() {
+        let root = Path::new("/project");
+        let first = workspace_symbol_items(
+            WorkspaceSymbolResponse::Flat(vec![symbol("run", "file:///project/a.rs", 1)]),
+            root,
+        );
+        let second = workspace_symbol_items(
+            WorkspaceSymbolResponse::Flat(vec![
+                symbol("run", "file:///project/a.rs", 1),
+                symbol("run", "file:///project/b.rs", 1),
+            ]),
+            root,
+        );
+        let merged = merge_workspace_symbols([first, second]);
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[1].path, PathBuf::from("/project/b.rs"));
+    }
+}
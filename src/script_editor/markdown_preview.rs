@@ -0,0 +1,503 @@
+//! Live preview of Markdown files beside the editor
+//!
+//! The source is parsed with pulldown-cmark into a flat list of blocks, each
+//! knowing the line it starts on: the preview follows the editor by scrolling
+//! to the block at its top line, and clicking a heading moves the editor to
+//! it. Code fences naming a language are colored with the minimap's lexer.
+
+use gpui::*;
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
+use std::rc::Rc;
+use ui::{h_flex, v_flex, ActiveTheme as _};
+
+use super::minimap::{tokenize_line, TokenKind};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SpanStyle {
+    pub bold: bool,
+    pub italic: bool,
+    pub strikethrough: bool,
+    pub code: bool,
+    pub link: bool,
+    /// A footnote reference
+    pub footnote: bool,
+    /// Lexer token inside a code fence
+    pub token: Option<TokenKind>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Span {
+    pub text: String,
+    pub style: SpanStyle,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BlockKind {
+    Heading(u8),
+    Paragraph,
+    /// `marker` is the bullet, or the item's number
+    ListItem { marker: String, checked: Option<bool> },
+    /// Lines of a code block
+    Code(Vec<Vec<Span>>),
+    TableRow { header: bool, cells: Vec<Vec<Span>> },
+    Rule,
+    Footnote(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PreviewBlock {
+    pub kind: BlockKind,
+    /// Source line the block starts on
+    pub line: u32,
+    /// Lists the block is nested in
+    pub list_depth: usize,
+    /// Block quotes the block is nested in
+    pub quote_depth: usize,
+    pub spans: Vec<Span>,
+}
+
+/// Parse Markdown with tables, task lists, footnotes and strikethrough
+pub fn parse(markdown: &str) -> Vec<PreviewBlock> {
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(markdown.match_indices('\n').map(|(ix, _)| ix + 1))
+        .collect();
+    let line_of = |offset: usize| (line_starts.partition_point(|&start| start <= offset) - 1) as u32;
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH;
+
+    let mut builder = Builder::default();
+    for (event, range) in Parser::new_ext(markdown, options).into_offset_iter() {
+        builder.event(event, line_of(range.start));
+    }
+    builder.blocks
+}
+
+/// Index of the block shown for a source line: the last one starting at or above it
+pub fn block_at_line(blocks: &[PreviewBlock], line: u32) -> usize {
+    blocks.partition_point(|block| block.line <= line).saturating_sub(1)
+}
+
+#[derive(Default)]
+struct Builder {
+    blocks: Vec<PreviewBlock>,
+    /// Whether inline content goes into the last block
+    open: bool,
+    strong: usize,
+    emphasis: usize,
+    strikethrough: usize,
+    links: usize,
+    quote_depth: usize,
+    /// The next number of each list open, `None` for bulleted ones
+    lists: Vec<Option<u64>>,
+    /// Text of the code block being read, and whether its fence names a language
+    code: Option<(String, bool)>,
+    /// Cells of the table row being read
+    table_row: Option<Vec<Vec<Span>>>,
+}
+
+impl Builder {
+    fn style(&self) -> SpanStyle {
+        SpanStyle {
+            bold: self.strong > 0,
+            italic: self.emphasis > 0,
+            strikethrough: self.strikethrough > 0,
+            link: self.links > 0,
+            ..SpanStyle::default()
+        }
+    }
+
+    fn push_block(&mut self, kind: BlockKind, line: u32) {
+        self.blocks.push(PreviewBlock {
+            kind,
+            line,
+            list_depth: self.lists.len(),
+            quote_depth: self.quote_depth,
+            spans: Vec::new(),
+        });
+        self.open = true;
+    }
+
+    fn push_span(&mut self, text: &str, style: SpanStyle, line: u32) {
+        if let Some(row) = &mut self.table_row {
+            if let Some(cell) = row.last_mut() {
+                push_merged(cell, text, style);
+            }
+            return;
+        }
+        if !self.open {
+            self.push_block(BlockKind::Paragraph, line);
+        }
+        if let Some(block) = self.blocks.last_mut() {
+            push_merged(&mut block.spans, text, style);
+        }
+    }
+
+    fn event(&mut self, event: Event, line: u32) {
+        match event {
+            Event::Start(tag) => self.start(tag, line),
+            Event::End(tag) => self.end(tag, line),
+            Event::Text(text) => match &mut self.code {
+                Some((code, _)) => code.push_str(&text),
+                None => self.push_span(&text, self.style(), line),
+            },
+            Event::Code(text) => {
+                let style = SpanStyle {
+                    code: true,
+                    ..self.style()
+                };
+                self.push_span(&text, style, line);
+            }
+            Event::Html(html) | Event::InlineHtml(html) => self.push_span(&html, self.style(), line),
+            Event::SoftBreak => self.push_span(" ", self.style(), line),
+            Event::HardBreak => self.push_span("\n", self.style(), line),
+            Event::Rule => {
+                self.push_block(BlockKind::Rule, line);
+                self.open = false;
+            }
+            Event::TaskListMarker(done) => {
+                if let Some(PreviewBlock {
+                    kind: BlockKind::ListItem { checked, .. },
+                    ..
+                }) = self.blocks.last_mut()
+                {
+                    *checked = Some(done);
+                }
+            }
+            Event::FootnoteReference(label) => {
+                let style = SpanStyle {
+                    footnote: true,
+                    ..self.style()
+                };
+                self.push_span(&format!("[{}]", label), style, line);
+            }
+            _ => {}
+        }
+    }
+
+    fn start(&mut self, tag: Tag, line: u32) {
+        match tag {
+            Tag::Paragraph => {
+                // The first paragraph of an item or footnote is its own text
+                let continues = self.open
+                    && self.blocks.last().is_some_and(|block| {
+                        block.spans.is_empty()
+                            && matches!(block.kind, BlockKind::ListItem { .. } | BlockKind::Footnote(_))
+                    });
+                if !continues {
+                    self.push_block(BlockKind::Paragraph, line);
+                }
+            }
+            Tag::Heading { level, .. } => self.push_block(BlockKind::Heading(level as u8), line),
+            Tag::BlockQuote(_) => {
+                self.quote_depth += 1;
+                self.open = false;
+            }
+            Tag::CodeBlock(kind) => {
+                let names_language = matches!(&kind, CodeBlockKind::Fenced(language) if !language.is_empty());
+                self.push_block(BlockKind::Code(Vec::new()), line);
+                self.code = Some((String::new(), names_language));
+            }
+            Tag::List(start) => {
+                self.lists.push(start);
+                self.open = false;
+            }
+            Tag::Item => {
+                let marker = match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        format!("{}.", *number - 1)
+                    }
+                    _ => "•".to_string(),
+                };
+                self.push_block(BlockKind::ListItem { marker, checked: None }, line);
+            }
+            Tag::FootnoteDefinition(label) => self.push_block(BlockKind::Footnote(label.to_string()), line),
+            Tag::Table(_) => self.open = false,
+            Tag::TableHead | Tag::TableRow => self.table_row = Some(Vec::new()),
+            Tag::TableCell => {
+                if let Some(row) = &mut self.table_row {
+                    row.push(Vec::new());
+                }
+            }
+            Tag::Emphasis => self.emphasis += 1,
+            Tag::Strong => self.strong += 1,
+            Tag::Strikethrough => self.strikethrough += 1,
+            Tag::Link { .. } | Tag::Image { .. } => self.links += 1,
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd, line: u32) {
+        match tag {
+            TagEnd::Paragraph | TagEnd::Heading(_) | TagEnd::Item | TagEnd::FootnoteDefinition => {
+                self.open = false
+            }
+            TagEnd::BlockQuote(_) => {
+                self.quote_depth = self.quote_depth.saturating_sub(1);
+                self.open = false;
+            }
+            TagEnd::CodeBlock => {
+                if let Some((code, names_language)) = self.code.take() {
+                    if let Some(block) = self.blocks.last_mut() {
+                        block.kind = BlockKind::Code(code_lines(&code, names_language));
+                    }
+                }
+                self.open = false;
+            }
+            TagEnd::List(_) => {
+                self.lists.pop();
+                self.open = false;
+            }
+            TagEnd::TableHead | TagEnd::TableRow => {
+                let cells = self.table_row.take().unwrap_or_default();
+                let header = tag == TagEnd::TableHead;
+                self.push_block(BlockKind::TableRow { header, cells }, line);
+                self.open = false;
+            }
+            TagEnd::Emphasis => self.emphasis = self.emphasis.saturating_sub(1),
+            TagEnd::Strong => self.strong = self.strong.saturating_sub(1),
+            TagEnd::Strikethrough => self.strikethrough = self.strikethrough.saturating_sub(1),
+            TagEnd::Link | TagEnd::Image => self.links = self.links.saturating_sub(1),
+            _ => {}
+        }
+    }
+}
+
+/// Append text, extending the last span if it's styled the same
+fn push_merged(spans: &mut Vec<Span>, text: &str, style: SpanStyle) {
+    match spans.last_mut() {
+        Some(last) if last.style == style => last.text.push_str(text),
+        _ => spans.push(Span {
+            text: text.to_string(),
+            style,
+        }),
+    }
+}
+
+/// A code block's lines, split into lexer tokens when `colored`
+fn code_lines(code: &str, colored: bool) -> Vec<Vec<Span>> {
+    let code_style = SpanStyle {
+        code: true,
+        ..SpanStyle::default()
+    };
+    let mut in_block_comment = false;
+    code.strip_suffix('\n')
+        .unwrap_or(code)
+        .split('\n')
+        .map(|line| {
+            if !colored {
+                return vec![Span {
+                    text: line.to_string(),
+                    style: code_style,
+                }];
+            }
+            let chars: Vec<char> = line.chars().collect();
+            let mut spans = Vec::new();
+            let mut column = 0;
+            for (range, kind) in tokenize_line(line, usize::MAX, &mut in_block_comment) {
+                if range.start > column {
+                    push_merged(&mut spans, &chars[column..range.start].iter().collect::<String>(), code_style);
+                }
+                let style = SpanStyle {
+                    token: (kind != TokenKind::Text).then_some(kind),
+                    ..code_style
+                };
+                push_merged(&mut spans, &chars[range.clone()].iter().collect::<String>(), style);
+                column = range.end;
+            }
+            if column < chars.len() {
+                push_merged(&mut spans, &chars[column..].iter().collect::<String>(), code_style);
+            }
+            spans
+        })
+        .collect()
+}
+
+/// Spans as one styled text
+fn styled_text(spans: &[Span], cx: &App) -> StyledText {
+    let mut text = String::new();
+    let mut highlights = Vec::new();
+    for span in spans {
+        let start = text.len();
+        text.push_str(&span.text);
+        let style = span.style;
+        let color = match style.token {
+            Some(TokenKind::Keyword) => Some(cx.theme().info),
+            Some(TokenKind::String) => Some(cx.theme().success),
+            Some(TokenKind::Comment) => Some(cx.theme().muted_foreground),
+            Some(TokenKind::Number) => Some(cx.theme().warning),
+            _ if style.link || style.footnote => Some(cx.theme().link),
+            _ => None,
+        };
+        highlights.push((
+            start..text.len(),
+            HighlightStyle {
+                color,
+                font_weight: style.bold.then_some(FontWeight::BOLD),
+                font_style: style.italic.then_some(FontStyle::Italic),
+                background_color: (style.code && style.token.is_none() && !style.link)
+                    .then(|| cx.theme().muted),
+                strikethrough: style.strikethrough.then(|| StrikethroughStyle {
+                    thickness: px(1.),
+                    color: None,
+                }),
+                underline: style.link.then(|| UnderlineStyle {
+                    thickness: px(1.),
+                    color: None,
+                    wavy: false,
+                }),
+                ..Default::default()
+            },
+        ));
+    }
+    StyledText::new(text).with_highlights(highlights)
+}
+
+/// The preview's column of blocks, one child per block so `block_at_line`
+/// indexes its children. Clicking a heading calls `on_heading` with its line.
+pub fn render_preview(
+    blocks: &[PreviewBlock],
+    scroll_handle: &ScrollHandle,
+    on_heading: Rc<dyn Fn(u32, &mut Window, &mut App)>,
+    cx: &App,
+) -> impl IntoElement {
+    div()
+        .id("markdown-preview")
+        .size_full()
+        .overflow_y_scroll()
+        .track_scroll(scroll_handle)
+        .p_5()
+        .bg(cx.theme().background)
+        .text_color(cx.theme().foreground)
+        .text_sm()
+        .children(blocks.iter().enumerate().map(|(ix, block)| {
+            let content = match &block.kind {
+                BlockKind::Heading(level) => {
+                    let line = block.line;
+                    let on_heading = on_heading.clone();
+                    let size = match level {
+                        1 => rems(1.75),
+                        2 => rems(1.5),
+                        3 => rems(1.25),
+                        _ => rems(1.),
+                    };
+                    div()
+                        .id(("markdown-heading", ix))
+                        .pt_3()
+                        .pb_1()
+                        .text_size(size)
+                        .font_weight(FontWeight::BOLD)
+                        .cursor_pointer()
+                        .when(*level <= 2, |heading| heading.border_b_1().border_color(cx.theme().border))
+                        .on_click(move |_, window, cx| on_heading(line, window, cx))
+                        .child(styled_text(&block.spans, cx))
+                        .into_any_element()
+                }
+                BlockKind::Paragraph => div().py_1().child(styled_text(&block.spans, cx)).into_any_element(),
+                BlockKind::ListItem { marker, checked } => {
+                    let marker = match checked {
+                        Some(true) => "☑".to_string(),
+                        Some(false) => "☐".to_string(),
+                        None => marker.clone(),
+                    };
+                    h_flex()
+                        .items_start()
+                        .gap_2()
+                        .child(div().flex_none().text_color(cx.theme().muted_foreground).child(marker))
+                        .child(div().flex_1().child(styled_text(&block.spans, cx)))
+                        .into_any_element()
+                }
+                BlockKind::Code(lines) => v_flex()
+                    .my_1()
+                    .p_2()
+                    .rounded(cx.theme().radius)
+                    .bg(cx.theme().secondary)
+                    .font_family("JetBrains Mono")
+                    .text_xs()
+                    .children(lines.iter().map(|line| div().min_h_4().child(styled_text(line, cx))))
+                    .into_any_element(),
+                BlockKind::TableRow { header, cells } => h_flex()
+                    .border_b_1()
+                    .border_color(cx.theme().border)
+                    .when(*header, |row| row.font_weight(FontWeight::BOLD).bg(cx.theme().secondary))
+                    .children(cells.iter().map(|cell| div().flex_1().px_2().py_1().child(styled_text(cell, cx))))
+                    .into_any_element(),
+                BlockKind::Rule => div().my_2().h(px(1.)).bg(cx.theme().border).into_any_element(),
+                BlockKind::Footnote(label) => h_flex()
+                    .items_start()
+                    .gap_2()
+                    .text_xs()
+                    .child(div().flex_none().text_color(cx.theme().link).child(format!("[{}]", label)))
+                    .child(div().flex_1().child(styled_text(&block.spans, cx)))
+                    .into_any_element(),
+            };
+            div()
+                .pl(px(16. * block.list_depth.saturating_sub(1) as f32 + 12. * block.quote_depth as f32))
+                .when(block.quote_depth > 0, |quoted| {
+                    quoted
+                        .border_l_2()
+                        .border_color(cx.theme().border)
+                        .text_color(cx.theme().muted_foreground)
+                })
+                .child(content)
+        }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(spans: &[Span]) -> String {
+        spans.iter().map(|span| span.text.as_str()).collect()
+    }
+
+    #[test]
+    fn test_blocks_and_their_lines() {
+        let markdown = "# Title\n\nSome *emphasis*[^1].\n\n- [x] done\n- [ ] todo\n\n| a | b |\n|---|---|\n| 1 | 2 |\n\n```rust\nlet x = 1;\n```\n\n[^1]: A note.\n";
+        let blocks = parse(markdown);
+        let summary: Vec<(u32, String)> = blocks
+            .iter()
+            .map(|block| {
+                let kind = match &block.kind {
+                    BlockKind::Heading(level) => format!("h{}", level),
+                    BlockKind::Paragraph => "p".to_string(),
+                    BlockKind::ListItem { checked, .. } => format!("item {:?}", checked),
+                    BlockKind::Code(lines) => format!("code {}", lines.len()),
+                    BlockKind::TableRow { header, cells } => format!("row {} {}", header, cells.len()),
+                    BlockKind::Rule => "hr".to_string(),
+                    BlockKind::Footnote(label) => format!("note {}", label),
+                };
+                (block.line, format!("{} {}", kind, text(&block.spans)).trim_end().to_string())
+            })
+            .collect();
+        let expected: Vec<(u32, String)> = [
+            (0, "h1 Title"),
+            (2, "p Some emphasis[1]."),
+            (4, "item Some(true) done"),
+            (5, "item Some(false) todo"),
+            (7, "row true 2"),
+            (9, "row false 2"),
+            (11, "code 1"),
+            (15, "note 1 A note."),
+        ]
+        .into_iter()
+        .map(|(line, summary)| (line, summary.to_string()))
+        .collect();
+        assert_eq!(summary, expected);
+
+        let BlockKind::Code(lines) = &blocks[6].kind else {
+            unreachable!()
+        };
+        let tokens: Vec<(&str, Option<TokenKind>)> =
+            lines[0].iter().map(|span| (span.text.as_str(), span.style.token)).collect();
+        assert_eq!(
+            tokens,
+            [("let", Some(TokenKind::Keyword)), (" x = ", None), ("1", Some(TokenKind::Number)), (";", None)]
+        );
+
+        assert_eq!(block_at_line(&blocks, 3), 1);
+        assert_eq!(block_at_line(&blocks, 100), blocks.len() - 1);
+    }
+}
@@ -0,0 +1,285 @@
+//! Minimap: a scaled-down overview of the document beside the editor
+//!
+//! Each document line becomes a row of colored runs, one per token. Long
+//! files are sampled so the whole document always fits the minimap's height.
+
+use gpui::*;
+use std::cell::Cell;
+use std::ops::Range;
+use std::rc::Rc;
+
+/// Height of one minimap row
+pub const ROW_HEIGHT: Pixels = px(2.0);
+/// Width of one character in a minimap row
+const CHAR_WIDTH: Pixels = px(1.0);
+/// Columns past this are not drawn
+const MAX_COLUMNS: usize = 160;
+/// Lines kept for painting; longer documents are sampled down to this many
+const MAX_ROWS: usize = 1_500;
+
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "class", "const", "continue", "def", "else", "enum", "export",
+    "false", "fn", "for", "from", "function", "if", "impl", "import", "in", "let", "loop", "match",
+    "mod", "mut", "pub", "return", "self", "Self", "static", "struct", "trait", "true", "type",
+    "use", "var", "where", "while",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenKind {
+    Text,
+    Keyword,
+    String,
+    Comment,
+    Number,
+}
+
+/// Sampled document lines as colored column runs
+#[derive(Debug, Default)]
+pub struct MinimapRows {
+    /// Lines in the whole document
+    pub line_count: usize,
+    rows: Vec<Vec<(Range<usize>, TokenKind)>>,
+}
+
+impl MinimapRows {
+    pub fn new(text: &str) -> Self {
+        let lines: Vec<&str> = text.lines().collect();
+        let line_count = lines.len().max(1);
+        let mut in_block_comment = false;
+        let rows = sample(lines.len(), MAX_ROWS)
+            .map(|line| tokenize_line(lines[line], MAX_COLUMNS, &mut in_block_comment))
+            .collect();
+        Self { line_count, rows }
+    }
+
+    /// Rows shown in a minimap of the given height
+    fn visible_rows(&self, height: Pixels) -> usize {
+        let fit = (height / ROW_HEIGHT).floor() as usize;
+        self.rows.len().min(fit).max(1)
+    }
+
+    /// Height taken by the rows in a minimap of the given height
+    pub fn content_height(&self, height: Pixels) -> Pixels {
+        ROW_HEIGHT * self.visible_rows(height) as f32
+    }
+
+    /// Document line under a point `y` pixels below the minimap's top
+    pub fn line_at(&self, y: Pixels, height: Pixels) -> usize {
+        let fraction = (y / self.content_height(height)).clamp(0.0, 1.0);
+        ((fraction * self.line_count as f32) as usize).min(self.line_count - 1)
+    }
+}
+
+/// `count` evenly spread indices into `0..len` (all of them when `len <= count`)
+fn sample(len: usize, count: usize) -> impl Iterator<Item = usize> {
+    let count = len.min(count);
+    (0..count).map(move |ix| ix * len / count)
+}
+
+/// Split a line into column runs by rough token kind.
+///
+/// This is a language-agnostic approximation of the editor's highlighting:
+/// it only needs to look right at two pixels per line (or to stand in for it
+/// in files too large to parse). Runs are in characters, up to `max_columns`.
+pub fn tokenize_line(line: &str, max_columns: usize, in_block_comment: &mut bool) -> Vec<(Range<usize>, TokenKind)> {
+    let chars: Vec<char> = line.chars().take(max_columns).collect();
+    let mut runs: Vec<(Range<usize>, TokenKind)> = Vec::new();
+    let mut push = |range: Range<usize>, kind: TokenKind| match runs.last_mut() {
+        Some((last, last_kind)) if *last_kind == kind && last.end == range.start => last.end = range.end,
+        _ => runs.push((range, kind)),
+    };
+
+    let mut ix = 0;
+    while ix < chars.len() {
+        let start = ix;
+        let ch = chars[ix];
+        if *in_block_comment {
+            ix = skip_block_comment(&chars, ix, in_block_comment);
+            push(start..ix, TokenKind::Comment);
+        } else if ch.is_whitespace() {
+            ix += 1;
+        } else if (ch == '/' && chars.get(ix + 1) == Some(&'/')) || (ch == '#' && ix == first_non_blank(&chars)) {
+            push(start..chars.len(), TokenKind::Comment);
+            break;
+        } else if ch == '/' && chars.get(ix + 1) == Some(&'*') {
+            *in_block_comment = true;
+            ix = skip_block_comment(&chars, ix + 2, in_block_comment);
+            push(start..ix, TokenKind::Comment);
+        } else if ch == '"' || ch == '\'' || ch == '`' {
+            ix += 1;
+            while ix < chars.len() && chars[ix] != ch {
+                ix += if chars[ix] == '\\' { 2 } else { 1 };
+            }
+            ix = (ix + 1).min(chars.len());
+            push(start..ix, TokenKind::String);
+        } else if ch.is_ascii_digit() {
+            while ix < chars.len() && (chars[ix].is_ascii_alphanumeric() || chars[ix] == '.' || chars[ix] == '_') {
+                ix += 1;
+            }
+            push(start..ix, TokenKind::Number);
+        } else if ch.is_alphanumeric() || ch == '_' {
+            while ix < chars.len() && (chars[ix].is_alphanumeric() || chars[ix] == '_') {
+                ix += 1;
+            }
+            let word: String = chars[start..ix].iter().collect();
+            let kind = if KEYWORDS.contains(&word.as_str()) {
+                TokenKind::Keyword
+            } else {
+                TokenKind::Text
+            };
+            push(start..ix, kind);
+        } else {
+            ix += 1;
+            push(start..ix, TokenKind::Text);
+        }
+    }
+    runs
+}
+
+/// Index just past the `*/` ending a block comment, or the line's end if it continues
+fn skip_block_comment(chars: &[char], mut ix: usize, in_block_comment: &mut bool) -> usize {
+    while ix < chars.len() && !(chars[ix] == '*' && chars.get(ix + 1) == Some(&'/')) {
+        ix += 1;
+    }
+    if ix < chars.len() {
+        *in_block_comment = false;
+        ix += 2;
+    }
+    ix.min(chars.len())
+}
+
+fn first_non_blank(chars: &[char]) -> usize {
+    chars.iter().position(|ch| !ch.is_whitespace()).unwrap_or(0)
+}
+
+/// Where the minimap was last painted, for turning clicks into lines
+#[derive(Clone, Copy, Default)]
+pub struct MinimapGeometry {
+    pub bounds: Bounds<Pixels>,
+    /// Line height of the editor text next to the minimap
+    pub line_height: Pixels,
+}
+
+pub struct MinimapColors {
+    pub text: Hsla,
+    pub keyword: Hsla,
+    pub string: Hsla,
+    pub comment: Hsla,
+    pub number: Hsla,
+    pub viewport: Hsla,
+}
+
+impl MinimapColors {
+    fn for_kind(&self, kind: TokenKind) -> Hsla {
+        match kind {
+            TokenKind::Text => self.text,
+            TokenKind::Keyword => self.keyword,
+            TokenKind::String => self.string,
+            TokenKind::Comment => self.comment,
+            TokenKind::Number => self.number,
+        }
+    }
+}
+
+/// Paint `rows` with the editor viewport (scrolled `scroll_top` down) highlighted.
+///
+/// Must be placed where the text style matches the editor's font size, so the
+/// line height used for the viewport matches the editor's.
+pub fn minimap(
+    rows: Rc<MinimapRows>,
+    scroll_top: Pixels,
+    colors: MinimapColors,
+    geometry: Rc<Cell<MinimapGeometry>>,
+) -> impl IntoElement {
+    canvas(
+        move |bounds, window, _cx| {
+            geometry.set(MinimapGeometry {
+                bounds,
+                line_height: window.line_height(),
+            });
+            window.line_height()
+        },
+        move |bounds, line_height, window, _cx| {
+            let visible_rows = rows.visible_rows(bounds.size.height);
+            for (row, sampled) in sample(rows.rows.len(), visible_rows).enumerate() {
+                let top = bounds.top() + ROW_HEIGHT * row as f32;
+                for (columns, kind) in &rows.rows[sampled] {
+                    let left = bounds.left() + CHAR_WIDTH * columns.start as f32;
+                    if left >= bounds.right() {
+                        break;
+                    }
+                    let width = (CHAR_WIDTH * columns.len() as f32).min(bounds.right() - left);
+                    window.paint_quad(fill(
+                        Bounds::new(point(left, top), size(width, ROW_HEIGHT * 0.75)),
+                        colors.for_kind(*kind),
+                    ));
+                }
+            }
+
+            // Viewport: the editor shows about as many lines as fit in the minimap's height
+            let content_height = rows.content_height(bounds.size.height);
+            let line_count = rows.line_count as f32;
+            let first_line = scroll_top / line_height;
+            let visible_lines = bounds.size.height / line_height;
+            let top = content_height * (first_line / line_count).clamp(0.0, 1.0);
+            let height = (content_height * (visible_lines / line_count)).clamp(ROW_HEIGHT * 4.0, content_height);
+            window.paint_quad(fill(
+                Bounds::new(
+                    point(bounds.left(), bounds.top() + top.min(content_height - height)),
+                    size(bounds.size.width, height),
+                ),
+                colors.viewport,
+            ));
+        },
+    )
+    .size_full()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample() {
+        assert_eq!(sample(3, 10).collect::<Vec<_>>(), vec![0, 1, 2]);
+        assert_eq!(sample(10, 4).collect::<Vec<_>>(), vec![0, 2, 5, 7]);
+        assert_eq!(sample(0, 4).count(), 0);
+    }
+
+    #[test]
+    fn test_tokenize_line() {
+        let mut in_block_comment = false;
+        assert_eq!(
+            tokenize_line("let s = \"a\"; // 1", MAX_COLUMNS, &mut in_block_comment),
+            vec![
+                (0..3, TokenKind::Keyword),
+                (4..5, TokenKind::Text),
+                (6..7, TokenKind::Text),
+                (8..11, TokenKind::String),
+                (11..12, TokenKind::Text),
+                (13..17, TokenKind::Comment),
+            ]
+        );
+
+        assert_eq!(
+            tokenize_line("x /* a", MAX_COLUMNS, &mut in_block_comment),
+            vec![(0..1, TokenKind::Text), (2..6, TokenKind::Comment)]
+        );
+        assert!(in_block_comment);
+        assert_eq!(
+            tokenize_line("b */ 42", MAX_COLUMNS, &mut in_block_comment),
+            vec![(0..4, TokenKind::Comment), (5..7, TokenKind::Number)]
+        );
+        assert!(!in_block_comment);
+    }
+
+    #[test]
+    fn test_line_at() {
+        let text = "a\n".repeat(10);
+        let rows = MinimapRows::new(&text);
+        // 10 rows of 2px in a 100px minimap: the rows end at 20px
+        assert_eq!(rows.line_at(px(0.0), px(100.0)), 0);
+        assert_eq!(rows.line_at(px(11.0), px(100.0)), 5);
+        assert_eq!(rows.line_at(px(90.0), px(100.0)), 9);
+    }
+}
@@ -1,26 +1,170 @@
+mod auto_pairs;
+#[doc(hidden)]
+pub mod bench;
+mod bookmarks;
+mod bookmarks_panel;
 mod autocomplete_integration;
+mod ansi;
+mod brackets;
+mod command_palette;
+mod comments;
+mod diff_view;
+mod editorconfig;
+mod emmet;
+mod encoding;
+mod explorer_filter;
 mod file_explorer;
+mod file_positions;
+mod file_references;
+mod file_watcher;
+mod find_bar;
+mod folding;
+mod git_blame;
+mod git_diff;
+mod git_status;
+mod go_to_line;
+mod hex_view;
+mod hierarchy_panel;
+mod indentation;
+mod keymap;
+pub(crate) mod languages;
+mod location;
+mod large_files;
+mod line_moves;
+mod json_validation;
+mod lazy_highlight;
+mod line_endings;
+pub mod lsp;
+mod markdown_preview;
+mod minimap;
+mod multi_cursor;
+mod navigation_history;
+mod outline_panel;
+mod problems_panel;
+mod project_search;
+mod python_env;
+mod quick_open;
+mod references_panel;
+mod run_output;
+mod search;
+mod selection_expansion;
+mod settings;
+mod snippets;
+mod sticky_scroll;
+mod symbol_picker;
+mod syntax_theme;
+mod terminal_panel;
+mod terminal_screen;
 pub mod text_editor;
+mod text_buffer;
+mod toml_validation;
+mod undo_history;
+mod viewport;
+mod vim;
+mod vscode_theme;
+mod whitespace;
 mod workspace_panels;
+mod workspace_root;
+mod yaml;
 
 pub use autocomplete_integration::*;
-pub use file_explorer::FileExplorer;
-pub use text_editor::{TextEditor, TextEditorEvent};
+pub use command_palette::RunCommand;
+pub use comments::CommentSyntax;
+pub use file_explorer::{FileExplorer, FileExplorerEvent};
+pub use hierarchy_panel::{
+    CallDirection, CallEntry, CallHierarchyPanel, CallHierarchyPanelEvent, HierarchyEntry, HierarchyPanelEvent,
+    TypeDirection, TypeHierarchyPanel, TypeHierarchyPanelEvent,
+};
+pub use indentation::IndentStyle;
+pub use keymap::KeymapPreset;
+pub use location::Location;
+pub use outline_panel::{OutlinePanel, OutlinePanelEvent};
+pub use bookmarks_panel::{BookmarksPanel, BookmarksPanelEvent};
+pub use problems_panel::{ProblemsPanel, ProblemsPanelEvent};
+pub use project_search::{ProjectSearchPanel, ProjectSearchPanelEvent};
+pub use references_panel::{ReferencesPanel, ReferencesPanelEvent};
+pub use run_output::{OutputPanel, OutputPanelEvent};
+pub use terminal_panel::{TerminalPanel, TerminalPanelEvent};
+pub use text_editor::{SaveAllSummary, TextEditor, TextEditorEvent};
 pub use workspace_panels::*;
 
 use gpui::*;
 use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 use ui::{
     dock::{Panel, PanelEvent},
     h_flex,
-    resizable::{h_resizable, resizable_panel, ResizableState},
-    v_flex, ActiveTheme, StyledExt,
+    resizable::{h_resizable, resizable_panel, v_resizable, ResizableState},
+    tab::{Tab, TabBar},
+    button::{Button, ButtonVariants as _},
+    v_flex, ActiveTheme, IconName, Sizable as _, StyledExt,
 };
 
 use engine_backend::services::rust_analyzer_manager::RustAnalyzerManager;
 
-actions!(script_editor, [SaveCurrentFile, CloseCurrentFile]);
+use command_palette::{format_keybinding, CommandPalette, CommandPaletteEvent, CommandRegistry, CommandTarget};
+use diff_view::{diff_view, DiffLayout, DiffSide};
+use encoding::FileEncoding;
+use git_diff::DiffHunk;
+use hex_view::{HexView, HexViewEvent};
+use keymap::{keymap_path, KeyConflict, Keymap};
+use line_endings::LineEnding;
+pub use settings::{AutoSaveMode, EditorSettings, LanguageServerSettings, RenderWhitespace, WrapMode};
+pub use syntax_theme::SyntaxTheme;
+use problems_panel::Problem;
+use quick_open::{FileIndex, QuickOpen, QuickOpenEvent, MAX_RECENT};
+use snippets::{user_snippets_path, SnippetLibrary};
+use symbol_picker::{SymbolPicker, SymbolPickerEvent};
+
+actions!(
+    script_editor,
+    [
+        SaveCurrentFile,
+        CloseCurrentFile,
+        GoToDefinition,
+        FindAllReferences,
+        ShowCallHierarchy,
+        ShowTypeHierarchy,
+        RevealInFileExplorer,
+        CopyAbsolutePath,
+        CopyRelativePath,
+        OpenContainingFolder,
+        FormatDocument,
+        RenameSymbol,
+        ShowCodeActions,
+        ToggleFind,
+        ToggleReplace,
+        SelectNextOccurrence,
+        CollapseCursors,
+        ColumnSelectUp,
+        ColumnSelectDown,
+        ColumnSelectLeft,
+        ColumnSelectRight,
+        ExpandSelection,
+        ShrinkSelection,
+        ToggleLineComment,
+        ToggleBlockComment,
+        MoveLineUp,
+        MoveLineDown,
+        DuplicateLineUp,
+        DuplicateLineDown,
+        FoldAll,
+        UnfoldAll,
+        NextTab,
+        PreviousTab,
+        SplitRight,
+        SplitDown,
+        ClosePane,
+        JumpToMatchingBracket,
+        ToggleOutline,
+        ToggleCommandPalette,
+        GoToLine,
+        GoToWorkspaceSymbol,
+        ToggleQuickOpen
+    ]
+);
 
 /// Mode for the script editor
 #[derive(Clone, Copy, PartialEq, Debug)]
@@ -29,6 +173,78 @@ pub enum ScriptEditorMode {
     Normal,
     /// Diff mode with side-by-side editors
     Diff,
+    /// A binary file's bytes, read-only
+    Hex,
+}
+
+/// Which view the left sidebar shows
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SidebarView {
+    Explorer,
+    References,
+    Outline,
+    Problems,
+    Search,
+    Bookmarks,
+    CallHierarchy,
+    TypeHierarchy,
+}
+
+/// What the panel below the editor shows
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum BottomPanel {
+    Output,
+    Terminal,
+}
+
+/// Which editor pane has focus when the editor is split
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Pane {
+    Primary,
+    Secondary,
+}
+
+/// A second editor pane beside or below the main one.
+///
+/// Files open in both panes share one buffer: every edit is copied to the
+/// other pane, which keeps its own cursor and scroll position.
+struct EditorSplit {
+    /// `Horizontal` puts the panes side by side, `Vertical` stacks them
+    axis: Axis,
+    editor: Entity<TextEditor>,
+    resizable_state: Entity<ResizableState>,
+    _subscription: Subscription,
+    _observation: Subscription,
+}
+
+/// The hex view of a binary file while it is open
+struct HexViewSession {
+    view: Entity<HexView>,
+    _subscription: Subscription,
+}
+
+/// The command palette while it is open
+struct PaletteSession {
+    palette: Entity<CommandPalette>,
+    /// Focus to give back when the palette closes
+    previous_focus: Option<FocusHandle>,
+    _subscription: Subscription,
+}
+
+/// The workspace symbol picker while it is open
+struct SymbolPickerSession {
+    picker: Entity<SymbolPicker>,
+    /// Focus to give back when the picker is dismissed
+    previous_focus: Option<FocusHandle>,
+    _subscription: Subscription,
+}
+
+/// The "Go to File" picker while it is open
+struct QuickOpenSession {
+    quick_open: Entity<QuickOpen>,
+    /// Focus to give back when the picker is dismissed
+    previous_focus: Option<FocusHandle>,
+    _subscription: Subscription,
 }
 
 /// Entry for diff mode file list
@@ -42,7 +258,38 @@ pub struct DiffFileEntry {
 pub struct ScriptEditor {
     focus_handle: FocusHandle,
     file_explorer: Entity<FileExplorer>,
+    /// Find All References results, shown in place of the explorer
+    references_panel: Entity<ReferencesPanel>,
+    /// Symbols of the focused pane's current file
+    outline_panel: Entity<OutlinePanel>,
+    /// Diagnostics of the files open in either pane
+    problems_panel: Entity<ProblemsPanel>,
+    /// Search and replace across the project's files
+    project_search_panel: Entity<ProjectSearchPanel>,
+    /// Bookmarks of the files open in either pane
+    bookmarks_panel: Entity<BookmarksPanel>,
+    /// Callers or callees of a function, expanded as a tree
+    call_hierarchy_panel: Entity<CallHierarchyPanel>,
+    /// The server the call hierarchy's function came from, asked for its calls
+    call_hierarchy_server: Option<Arc<lsp::LanguageServer>>,
+    /// Supertypes or subtypes of a type, expanded as a tree
+    type_hierarchy_panel: Entity<TypeHierarchyPanel>,
+    type_hierarchy_server: Option<Arc<lsp::LanguageServer>>,
+    /// Output of the script last run
+    output_panel: Entity<OutputPanel>,
+    terminal_panel: Entity<TerminalPanel>,
+    /// Shown below the editor, if either is
+    bottom_panel: Option<BottomPanel>,
+    bottom_resizable_state: Entity<ResizableState>,
+    sidebar_view: SidebarView,
     text_editor: Entity<TextEditor>,
+    /// Forwards the main editor's events and keeps split panes in sync
+    _text_editor_subscription: Subscription,
+    /// Keeps the outline in step with the main editor
+    _text_editor_observation: Subscription,
+    /// Second editor pane in normal mode, if split
+    split: Option<EditorSplit>,
+    active_pane: Pane,
     /// Second editor for diff mode
     diff_editor: Entity<TextEditor>,
     /// Resizable state for diff mode split
@@ -50,270 +297,2216 @@ pub struct ScriptEditor {
     horizontal_resizable_state: Entity<ResizableState>,
     /// Global rust analyzer for LSP support
     rust_analyzer: Option<Entity<RustAnalyzerManager>>,
-    /// Current mode (normal or diff)
+    /// Current mode (normal, diff or hex)
     mode: ScriptEditorMode,
+    /// The binary file shown in hex mode
+    hex_view: Option<HexViewSession>,
     /// Diff mode file list with before/after content
     diff_files: Vec<DiffFileEntry>,
     /// Currently selected diff file index
     selected_diff_index: Option<usize>,
+    diff_layout: DiffLayout,
+    /// Changes in the selected diff file, and the editor line each starts at
+    diff_hunks: Vec<(DiffHunk, u32)>,
+    /// Change the hunk buttons act on
+    selected_diff_hunk: usize,
     /// Last known scroll offset for left editor (for sync)
     last_left_scroll: Option<Point<Pixels>>,
     /// Last known scroll offset for right editor (for sync)
     last_right_scroll: Option<Point<Pixels>>,
-    /// Keeps the analyzer event bridge subscription alive and replaceable.
-    _analyzer_sub: Option<Subscription>,
     /// Last workspace root used to start rust-analyzer.
     analyzer_workspace_root: Option<PathBuf>,
+    /// Commands listed in the command palette
+    commands: CommandRegistry,
+    command_palette: Option<PaletteSession>,
+    symbol_picker: Option<SymbolPickerSession>,
+    quick_open: Option<QuickOpenSession>,
+    /// The project's files for "Go to File", listed once a project is open
+    file_index: Option<Entity<FileIndex>>,
+    /// Files opened lately, most recent first, which "Go to File" ranks higher
+    recent_files: Vec<PathBuf>,
+    settings: EditorSettings,
+    snippets: Arc<SnippetLibrary>,
+    /// Save waiting for typing to pause, with `AutoSaveMode::AfterDelay`
+    auto_save_task: Option<Task<()>>,
+    _auto_save_subscriptions: Vec<Subscription>,
 }
 
 impl ScriptEditor {
     pub fn new(window: &mut Window, cx: &mut Context<Self>) -> Self {
         cx.bind_keys([
-            KeyBinding::new("ctrl-s", SaveCurrentFile, Some("ScriptEditor")),
-            KeyBinding::new("ctrl-w", CloseCurrentFile, Some("ScriptEditor")),
+            KeyBinding::new("escape", CollapseCursors, Some("ScriptEditor")),
+            KeyBinding::new("ctrl-shift-p", ToggleCommandPalette, Some("ScriptEditor")),
         ]);
 
         let horizontal_resizable_state = ResizableState::new(cx);
         let diff_resizable_state = ResizableState::new(cx);
 
         let file_explorer = cx.new(|cx| FileExplorer::new(window, cx));
-        let text_editor = cx.new(|cx| TextEditor::new(window, cx));
+        let references_panel = cx.new(ReferencesPanel::new);
+        let outline_panel = cx.new(OutlinePanel::new);
+        let problems_panel = cx.new(|cx| ProblemsPanel::new(window, cx));
+        let project_search_panel = cx.new(|cx| ProjectSearchPanel::new(window, cx));
+        let bookmarks_panel = cx.new(BookmarksPanel::new);
+        let call_hierarchy_panel = cx.new(CallHierarchyPanel::new);
+        let type_hierarchy_panel = cx.new(TypeHierarchyPanel::new);
+        let output_panel = cx.new(OutputPanel::new);
+        let terminal_panel = cx.new(TerminalPanel::new);
+        let bottom_resizable_state = ResizableState::new(cx);
+        let settings = EditorSettings::load();
+        let snippets = Arc::new(SnippetLibrary::load());
+        if let Some(name) = &settings.syntax_theme {
+            if let Err(e) = SyntaxTheme::resolve(name).and_then(|theme| theme.apply(cx)) {
+                tracing::error!("⚠️  Failed to apply syntax theme {:?}: {:#}", name, e);
+            }
+        }
+        let text_editor = cx.new(|cx| {
+            let mut editor = TextEditor::new(window, cx);
+            editor.set_json_schemas(settings.json_schemas.clone());
+            editor.set_run_commands(settings.run_commands.clone());
+            editor.set_drop_templates(settings.drop_templates.clone());
+            editor.set_language_server_settings(settings.language_servers.clone());
+            editor.set_wrap_mode(settings.word_wrap, window, cx);
+            editor.set_render_whitespace(settings.render_whitespace, cx);
+            editor.set_trim_trailing_whitespace(settings.trim_trailing_whitespace);
+            editor.set_final_newlines(settings.insert_final_newline, settings.trim_final_newlines);
+            editor.set_language_save_cleanup(settings.save_cleanup.clone());
+            editor.set_save_when_formatting_fails(settings.save_when_formatting_fails);
+            editor.set_highlight_current_line(settings.highlight_current_line, window, cx);
+            editor.set_inlay_hints_enabled(settings.inlay_hints, cx);
+            editor.set_vim_mode(settings.vim_mode, cx);
+            editor.set_large_file_threshold(settings.large_file_threshold_mb);
+            editor.set_sticky_scroll_depth(settings.sticky_scroll_max_depth);
+            editor.set_snippets(snippets.clone());
+            editor
+        });
         let diff_editor = cx.new(|cx| TextEditor::new(window, cx));
 
-        // Forward text editor events
+        let text_editor_subscription = cx.subscribe_in(&text_editor, window, Self::on_editor_event);
+        let text_editor_observation = cx.observe(&text_editor, |this, _editor, cx| {
+            this.refresh_outline(cx);
+            this.refresh_problems(cx);
+            this.refresh_bookmarks(cx);
+        });
+
+        // Jump to references picked in the references panel
+        cx.subscribe(
+            &references_panel,
+            |this: &mut Self, _panel, event: &ReferencesPanelEvent, cx| match event {
+                ReferencesPanelEvent::OpenLocation(location) => this.open_location(location, cx),
+            },
+        )
+        .detach();
+
+        cx.subscribe(
+            &call_hierarchy_panel,
+            |this: &mut Self, _panel, event: &CallHierarchyPanelEvent, cx| match event {
+                HierarchyPanelEvent::OpenLocation(location) => this.open_location(location, cx),
+                HierarchyPanelEvent::FetchChildren {
+                    generation,
+                    node,
+                    item,
+                    direction,
+                } => this.fetch_calls(*generation, *node, item.clone(), *direction, cx),
+            },
+        )
+        .detach();
+
+        cx.subscribe(
+            &type_hierarchy_panel,
+            |this: &mut Self, _panel, event: &TypeHierarchyPanelEvent, cx| match event {
+                HierarchyPanelEvent::OpenLocation(location) => this.open_location(location, cx),
+                HierarchyPanelEvent::FetchChildren {
+                    generation,
+                    node,
+                    item,
+                    direction,
+                } => this.fetch_types(*generation, *node, item.clone(), *direction, cx),
+            },
+        )
+        .detach();
+
+        let mut commands = CommandRegistry::new(Keymap::load(settings.keymap));
+        Self::register_builtin_commands(&mut commands, cx);
+        for conflict in commands.conflicts() {
+            tracing::warn!("⚠️  Keys {:?} run more than one command: {}", conflict.keys, conflict.commands.join(", "));
+        }
+
+        // Keep open files pointing at files moved in the explorer
+        cx.subscribe(
+            &file_explorer,
+            |this: &mut Self, _explorer, event: &FileExplorerEvent, cx| match event {
+                FileExplorerEvent::Renamed { from, to } => {
+                    let panes = std::iter::once(this.text_editor.clone())
+                        .chain(this.split.as_ref().map(|split| split.editor.clone()));
+                    for editor in panes {
+                        editor.update(cx, |editor, cx| editor.rename_path(from, to, cx));
+                    }
+                }
+            },
+        )
+        .detach();
+
+        cx.subscribe(&output_panel, |this: &mut Self, _panel, event: &OutputPanelEvent, cx| match event {
+            OutputPanelEvent::Close => this.hide_bottom_panel(BottomPanel::Output, cx),
+        })
+        .detach();
+        cx.subscribe(&terminal_panel, |this: &mut Self, _panel, event: &TerminalPanelEvent, cx| match event {
+            TerminalPanelEvent::Close => this.hide_bottom_panel(BottomPanel::Terminal, cx),
+        })
+        .detach();
+
+        // Jump to problems picked in the problems panel
+        cx.subscribe(
+            &problems_panel,
+            |this: &mut Self, _panel, event: &ProblemsPanelEvent, cx| match event {
+                ProblemsPanelEvent::OpenLocation(location) => this.open_location(location, cx),
+            },
+        )
+        .detach();
+
+        // Search as the query changes, jump to matches and apply replacements
+        cx.subscribe_in(
+            &project_search_panel,
+            window,
+            |this: &mut Self, _panel, event: &ProjectSearchPanelEvent, window, cx| match event {
+                ProjectSearchPanelEvent::SearchRequested => this.search_project(cx),
+                ProjectSearchPanelEvent::OpenLocation(location) => this.open_location(location, cx),
+                ProjectSearchPanelEvent::Replace(edit) => {
+                    let edit = edit.clone();
+                    let files = this
+                        .active_editor()
+                        .update(cx, |editor, cx| editor.apply_workspace_edit(edit, window, cx));
+                    if files.is_ok() {
+                        this.search_project(cx);
+                    }
+                }
+            },
+        )
+        .detach();
+
+        cx.subscribe(
+            &bookmarks_panel,
+            |this: &mut Self, _panel, event: &BookmarksPanelEvent, cx| match event {
+                BookmarksPanelEvent::OpenLocation { path, line } => {
+                    this.active_editor().update(cx, |editor, cx| {
+                        editor.navigate_to(path.clone(), *line, 0, cx);
+                    });
+                }
+                BookmarksPanelEvent::Remove { path, line } => {
+                    for editor in this.editors() {
+                        editor.update(cx, |editor, cx| editor.remove_bookmark(path, *line, cx));
+                    }
+                }
+                BookmarksPanelEvent::ClearAll => this.clear_bookmarks(cx),
+            },
+        )
+        .detach();
+
+        // Jump to symbols picked in the outline
         cx.subscribe(
-            &text_editor,
-            |this: &mut Self, _editor, event: &TextEditorEvent, cx| {
-                cx.emit(event.clone());
+            &outline_panel,
+            |this: &mut Self, _panel, event: &OutlinePanelEvent, cx| match event {
+                OutlinePanelEvent::OpenLocation(location) => this.open_location(location, cx),
             },
         )
         .detach();
 
+        let focus_handle = cx.focus_handle();
+        let auto_save_subscriptions = vec![
+            cx.on_focus_out(&focus_handle, window, |this, _event, window, cx| {
+                if this.settings.auto_save == AutoSaveMode::OnFocusChange {
+                    this.auto_save(window, cx);
+                }
+            }),
+            cx.observe_window_activation(window, |this, window, cx| {
+                if !window.is_window_active() && this.settings.auto_save == AutoSaveMode::OnWindowBlur {
+                    this.auto_save(window, cx);
+                }
+            }),
+        ];
+
         Self {
-            focus_handle: cx.focus_handle(),
+            focus_handle,
             file_explorer,
+            references_panel,
+            outline_panel,
+            problems_panel,
+            project_search_panel,
+            bookmarks_panel,
+            call_hierarchy_panel,
+            call_hierarchy_server: None,
+            type_hierarchy_panel,
+            type_hierarchy_server: None,
+            output_panel,
+            terminal_panel,
+            bottom_panel: None,
+            bottom_resizable_state,
+            sidebar_view: SidebarView::Explorer,
             text_editor,
+            _text_editor_subscription: text_editor_subscription,
+            _text_editor_observation: text_editor_observation,
+            split: None,
+            active_pane: Pane::Primary,
             diff_editor,
             diff_resizable_state,
             horizontal_resizable_state,
             rust_analyzer: None,
             mode: ScriptEditorMode::Normal,
+            hex_view: None,
             diff_files: Vec::new(),
             selected_diff_index: None,
+            diff_layout: DiffLayout::default(),
+            diff_hunks: Vec::new(),
+            selected_diff_hunk: 0,
             last_left_scroll: None,
             last_right_scroll: None,
-            _analyzer_sub: None,
             analyzer_workspace_root: None,
+            commands,
+            command_palette: None,
+            symbol_picker: None,
+            quick_open: None,
+            file_index: None,
+            recent_files: Vec::new(),
+            settings,
+            snippets,
+            auto_save_task: None,
+            _auto_save_subscriptions: auto_save_subscriptions,
         }
     }
 
-    fn resolve_workspace_root_for_file(path: &PathBuf) -> PathBuf {
-        let candidate = if path.is_file() {
-            path.parent()
-                .map(|p| p.to_path_buf())
-                .unwrap_or_else(|| path.clone())
-        } else {
-            path.clone()
+    pub fn settings(&self) -> &EditorSettings {
+        &self.settings
+    }
+
+    /// Change when files are saved automatically, remembering it for next time
+    pub fn set_auto_save_mode(&mut self, mode: AutoSaveMode, cx: &mut Context<Self>) {
+        self.settings.auto_save = mode;
+        self.settings.save();
+        self.auto_save_task = None;
+        self.text_editor.update(cx, |editor, cx| {
+            editor.set_status_message(format!("Auto save: {}", mode.label()), cx);
+        });
+        cx.notify();
+    }
+
+    /// Change how long lines wrap in both panes, remembering it for next time
+    pub fn set_wrap_mode(&mut self, mode: WrapMode, window: &mut Window, cx: &mut Context<Self>) {
+        self.settings.word_wrap = mode;
+        self.settings.save();
+        for editor in self.editors() {
+            editor.update(cx, |editor, cx| editor.set_wrap_mode(mode, window, cx));
+        }
+        self.text_editor.update(cx, |editor, cx| {
+            editor.set_status_message(format!("Word wrap: {}", mode.label()), cx);
+        });
+        cx.notify();
+    }
+
+    /// Change which whitespace is drawn in both panes, remembering it for next time
+    pub fn set_render_whitespace(&mut self, mode: RenderWhitespace, cx: &mut Context<Self>) {
+        self.settings.render_whitespace = mode;
+        self.settings.save();
+        for editor in self.editors() {
+            editor.update(cx, |editor, cx| editor.set_render_whitespace(mode, cx));
+        }
+        self.text_editor.update(cx, |editor, cx| {
+            editor.set_status_message(format!("Render whitespace: {}", mode.label()), cx);
+        });
+    }
+
+    /// Turn trimming trailing whitespace on save on or off, remembering it for next time
+    pub fn set_trim_trailing_whitespace(&mut self, trim: bool, cx: &mut Context<Self>) {
+        self.settings.trim_trailing_whitespace = trim;
+        self.settings.save();
+        for editor in self.editors() {
+            editor.update(cx, |editor, _cx| editor.set_trim_trailing_whitespace(trim));
+        }
+        let message = if trim { "Trim trailing whitespace on save: On" } else { "Trim trailing whitespace on save: Off" };
+        self.text_editor.update(cx, |editor, cx| editor.set_status_message(message, cx));
+    }
+
+    /// Choose how saved files end, remembering it for next time
+    pub fn set_final_newlines(&mut self, insert: bool, trim: bool, cx: &mut Context<Self>) {
+        self.settings.insert_final_newline = insert;
+        self.settings.trim_final_newlines = trim;
+        self.settings.save();
+        for editor in self.editors() {
+            editor.update(cx, |editor, _cx| editor.set_final_newlines(insert, trim));
+        }
+        let on_off = |on: bool| if on { "On" } else { "Off" };
+        let message = format!("Insert final newline: {} | Trim final newlines: {}", on_off(insert), on_off(trim));
+        self.text_editor.update(cx, |editor, cx| editor.set_status_message(message, cx));
+    }
+
+    /// Turn formatting on save on or off for the current file's language,
+    /// remembering it for next time
+    pub fn toggle_format_on_save(&mut self, cx: &mut Context<Self>) {
+        let Some(path) = self.active_editor().read(cx).current_file_path() else {
+            return;
+        };
+        let language = self.active_editor().read(cx).get_language_from_extension(&path);
+        let format = !self.active_editor().read(cx).formats_on_save(&path);
+        self.settings.save_cleanup.entry(language.clone()).or_default().format_on_save = Some(format);
+        self.settings.save();
+        let save_cleanup = self.settings.save_cleanup.clone();
+        for editor in self.editors() {
+            editor.update(cx, |editor, _cx| editor.set_language_save_cleanup(save_cleanup.clone()));
+        }
+        let message = format!("Format on save for {}: {}", language, if format { "On" } else { "Off" });
+        self.text_editor.update(cx, |editor, cx| editor.set_status_message(message, cx));
+    }
+
+    /// Turn organizing imports on save on or off for the current file's
+    /// language, remembering it for next time
+    pub fn toggle_organize_imports_on_save(&mut self, cx: &mut Context<Self>) {
+        let Some(path) = self.active_editor().read(cx).current_file_path() else {
+            return;
         };
+        let language = self.active_editor().read(cx).get_language_from_extension(&path);
+        let organize = !self.active_editor().read(cx).organizes_imports_on_save(&path);
+        self.settings.save_cleanup.entry(language.clone()).or_default().organize_imports_on_save = Some(organize);
+        self.settings.save();
+        let save_cleanup = self.settings.save_cleanup.clone();
+        for editor in self.editors() {
+            editor.update(cx, |editor, _cx| editor.set_language_save_cleanup(save_cleanup.clone()));
+        }
+        let message = format!("Organize imports on save for {}: {}", language, if organize { "On" } else { "Off" });
+        self.text_editor.update(cx, |editor, cx| editor.set_status_message(message, cx));
+    }
+
+    /// Turn the current line highlight on or off in both panes, remembering it for next time
+    pub fn set_highlight_current_line(&mut self, enabled: bool, window: &mut Window, cx: &mut Context<Self>) {
+        self.settings.highlight_current_line = enabled;
+        self.settings.save();
+        for editor in self.editors() {
+            editor.update(cx, |editor, cx| editor.set_highlight_current_line(enabled, window, cx));
+        }
+    }
+
+    /// Show or hide inlay hints in both panes, remembering it for next time
+    pub fn set_inlay_hints(&mut self, enabled: bool, cx: &mut Context<Self>) {
+        self.settings.inlay_hints = enabled;
+        self.settings.save();
+        for editor in self.editors() {
+            editor.update(cx, |editor, cx| editor.set_inlay_hints_enabled(enabled, cx));
+        }
+        let message = if enabled { "Inlay hints: On" } else { "Inlay hints: Off" };
+        self.text_editor.update(cx, |editor, cx| editor.set_status_message(message, cx));
+    }
+
+    /// Turn Vim mode on or off in both panes, remembering it for next time
+    pub fn set_vim_mode(&mut self, enabled: bool, cx: &mut Context<Self>) {
+        self.settings.vim_mode = enabled;
+        self.settings.save();
+        for editor in self.editors() {
+            editor.update(cx, |editor, cx| editor.set_vim_mode(enabled, cx));
+        }
+        let message = if enabled { "Vim mode: On" } else { "Vim mode: Off" };
+        self.text_editor.update(cx, |editor, cx| editor.set_status_message(message, cx));
+    }
+
+    /// The main pane's editor, and the split pane's if there is one
+    fn editors(&self) -> impl Iterator<Item = Entity<TextEditor>> {
+        let split_editor = self.split.as_ref().map(|split| split.editor.clone());
+        std::iter::once(self.text_editor.clone()).chain(split_editor)
+    }
 
-        let mut current = candidate.as_path();
-        loop {
-            if current.join("Cargo.toml").exists() {
-                return current.to_path_buf();
+    /// Switch every editor to `theme`, remembering `name_or_path` for next time
+    pub fn set_syntax_theme(&mut self, theme: SyntaxTheme, name_or_path: String, cx: &mut Context<Self>) {
+        let message = match theme.apply(cx) {
+            Ok(()) => {
+                self.settings.syntax_theme = Some(name_or_path);
+                self.settings.save();
+                format!("Syntax theme: {}", theme.name)
             }
-            if let Some(parent) = current.parent() {
-                current = parent;
-            } else {
-                break;
+            Err(e) => format!("Failed to apply syntax theme {}: {:#}", theme.name, e),
+        };
+        self.text_editor.update(cx, |editor, cx| editor.set_status_message(message, cx));
+    }
+
+    /// Open the user's `snippets.json`, creating it when there isn't one yet
+    fn open_user_snippets(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        if let Some(path) = user_snippets_path() {
+            self.open_config_file(path, "{\n}\n", window, cx);
+        }
+    }
+
+    /// Open the user's `keymap.json`, creating it when there isn't one yet
+    fn open_keymap_file(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        if let Some(path) = keymap_path() {
+            self.open_config_file(path, "[\n]\n", window, cx);
+        }
+    }
+
+    /// Open a file of the config directory, starting it with `empty` if it doesn't exist
+    fn open_config_file(&mut self, path: PathBuf, empty: &str, window: &mut Window, cx: &mut Context<Self>) {
+        if !path.exists() {
+            let created = path
+                .parent()
+                .map_or(Ok(()), std::fs::create_dir_all)
+                .and_then(|()| std::fs::write(&path, empty));
+            if let Err(e) = created {
+                self.text_editor.update(cx, |editor, cx| {
+                    editor.set_status_message(format!("Failed to create {}: {}", path.display(), e), cx)
+                });
+                return;
             }
         }
+        self.open_file(path, window, cx);
+    }
+
+    /// Read the user's snippets again, e.g. after `snippets.json` was saved
+    fn reload_snippets(&mut self, cx: &mut Context<Self>) {
+        self.snippets = Arc::new(SnippetLibrary::load());
+        for editor in self.editors().collect::<Vec<_>>() {
+            let snippets = self.snippets.clone();
+            editor.update(cx, |editor, _cx| editor.set_snippets(snippets));
+        }
+    }
 
-        candidate
+    /// Switch to a keymap preset, remembering it for next time
+    pub fn set_keymap_preset(&mut self, preset: KeymapPreset, cx: &mut Context<Self>) {
+        self.settings.keymap = preset;
+        self.settings.save();
+        self.reload_keymap(format!("Keymap: {}", preset.label()), cx);
     }
 
-    fn ensure_analyzer_workspace(
+    /// Rebind the commands by the keymap preset and `keymap.json`, showing
+    /// `message` and any keys that now run more than one command
+    fn reload_keymap(&mut self, message: String, cx: &mut Context<Self>) {
+        self.commands.set_keymap(Keymap::load(self.settings.keymap), cx);
+        let message = match self.commands.conflicts().as_slice() {
+            [] => message,
+            [KeyConflict { keys, commands }] => {
+                format!("{} (conflict: {} runs {})", message, format_keybinding(keys), commands.join(", "))
+            }
+            conflicts => format!("{} ({} keybinding conflicts, see the command palette)", message, conflicts.len()),
+        };
+        self.active_editor().clone().update(cx, |editor, cx| editor.set_status_message(message, cx));
+    }
+
+    /// Ask for a `.json` or `.toml` theme file and switch to it
+    fn load_syntax_theme_file(&mut self, cx: &mut Context<Self>) {
+        let paths = cx.prompt_for_paths(PathPromptOptions {
+            files: true,
+            directories: false,
+            multiple: false,
+            prompt: Some("Load Syntax Theme".into()),
+        });
+        cx.spawn(async move |this, cx| {
+            let Ok(Ok(Some(paths))) = paths.await else {
+                return;
+            };
+            let Some(path) = paths.into_iter().next() else {
+                return;
+            };
+            this.update(cx, |this, cx| match SyntaxTheme::load(&path) {
+                Ok(theme) => this.set_syntax_theme(theme, path.to_string_lossy().to_string(), cx),
+                Err(e) => this.text_editor.update(cx, |editor, cx| {
+                    editor.set_status_message(format!("Failed to load syntax theme: {:#}", e), cx)
+                }),
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    /// Save the focused pane's file the way Ctrl+S does, once typing pauses
+    fn schedule_auto_save(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let delay = Duration::from_millis(self.settings.auto_save_delay);
+        self.auto_save_task = Some(cx.spawn_in(window, async move |this, cx| {
+            cx.background_executor().timer(delay).await;
+            this.update_in(cx, |this, window, cx| this.auto_save(window, cx)).ok();
+        }));
+    }
+
+    /// Save the focused pane's file unless it can't or shouldn't be saved right now
+    fn auto_save(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let editor = self.active_editor().clone();
+        let Some(path) = editor.read(cx).current_file_path() else {
+            return;
+        };
+        // Untitled buffers have never been given a real path, and files
+        // deleted on disk are left for the user to decide about
+        if self.mode == ScriptEditorMode::Diff || !path.exists() || !editor.read(cx).is_file_modified(&path) {
+            return;
+        }
+        // A format or rename is about to change the buffer; save once it has
+        if editor.read(cx).is_applying_lsp_edits() {
+            if self.settings.auto_save == AutoSaveMode::AfterDelay {
+                self.schedule_auto_save(window, cx);
+            }
+            return;
+        }
+        if self.plugin_save(window, cx).is_ok() && !editor.read(cx).is_file_modified(&path) {
+            cx.emit(TextEditorEvent::AutoSaved { path });
+        }
+    }
+
+    /// Editor commands available from the command palette
+    fn register_builtin_commands(commands: &mut CommandRegistry, cx: &mut App) {
+        commands.register_action("Save File", &["ctrl-s"], SaveCurrentFile, cx);
+        commands.register_action("Close File", &["ctrl-w"], CloseCurrentFile, cx);
+        commands.register_action("Go to Definition", &["f12"], GoToDefinition, cx);
+        commands.register_action("Find All References", &["shift-f12"], FindAllReferences, cx);
+        commands.register_action("Show Call Hierarchy", &["shift-alt-h"], ShowCallHierarchy, cx);
+        commands.register_action("Show Type Hierarchy", &["shift-alt-t"], ShowTypeHierarchy, cx);
+        commands.register_action("Reveal in File Explorer", &[], RevealInFileExplorer, cx);
+        commands.register_action("Copy Absolute Path", &[], CopyAbsolutePath, cx);
+        commands.register_action("Copy Relative Path", &[], CopyRelativePath, cx);
+        commands.register_action("Open Containing Folder", &[], OpenContainingFolder, cx);
+        commands.register(
+            "Peek Definition",
+            Some("alt-f12"),
+            |this, _window, cx| {
+                this.active_editor().clone().update(cx, |editor, cx| editor.peek_definition(cx));
+            },
+            cx,
+        );
+        commands.register(
+            "Peek References",
+            Some("alt-shift-f12"),
+            |this, _window, cx| {
+                this.active_editor().clone().update(cx, |editor, cx| editor.peek_references(cx));
+            },
+            cx,
+        );
+        commands.register_action("Rename Symbol", &["f2"], RenameSymbol, cx);
+        commands.register_action("Show Code Actions", &["ctrl-."], ShowCodeActions, cx);
+        commands.register_action("Format Document", &["shift-alt-f"], FormatDocument, cx);
+        commands.register_action("Find", &["ctrl-f"], ToggleFind, cx);
+        commands.register_action("Find and Replace", &["ctrl-h"], ToggleReplace, cx);
+        commands.register_action("Select Next Occurrence", &["ctrl-d"], SelectNextOccurrence, cx);
+        commands.register_action("Column Select Up", &["ctrl-shift-alt-up"], ColumnSelectUp, cx);
+        commands.register_action("Column Select Down", &["ctrl-shift-alt-down"], ColumnSelectDown, cx);
+        commands.register_action("Column Select Left", &["ctrl-shift-alt-left"], ColumnSelectLeft, cx);
+        commands.register_action("Column Select Right", &["ctrl-shift-alt-right"], ColumnSelectRight, cx);
+        commands.register_action("Expand Selection", &["alt-shift-right"], ExpandSelection, cx);
+        commands.register_action("Shrink Selection", &["alt-shift-left"], ShrinkSelection, cx);
+        commands.register_action("Toggle Line Comment", &["ctrl-/"], ToggleLineComment, cx);
+        commands.register_action("Toggle Block Comment", &["ctrl-shift-/"], ToggleBlockComment, cx);
+        commands.register_action("Move Line Up", &["alt-up"], MoveLineUp, cx);
+        commands.register_action("Move Line Down", &["alt-down"], MoveLineDown, cx);
+        commands.register_action("Duplicate Line Up", &["shift-alt-up"], DuplicateLineUp, cx);
+        commands.register_action("Duplicate Line Down", &["shift-alt-down", "ctrl-shift-d"], DuplicateLineDown, cx);
+        commands.register_action("Fold All", &["ctrl-k ctrl-0"], FoldAll, cx);
+        commands.register_action("Unfold All", &["ctrl-k ctrl-j"], UnfoldAll, cx);
+        commands.register_action("Go to Line", &["ctrl-g"], GoToLine, cx);
+        commands.register_action("Go to Symbol in Workspace", &["ctrl-t"], GoToWorkspaceSymbol, cx);
+        commands.register_action("Go to File", &["ctrl-p"], ToggleQuickOpen, cx);
+        commands.register_action("Jump to Matching Bracket", &["ctrl-shift-\\"], JumpToMatchingBracket, cx);
+        commands.register_action("Next Tab", &["ctrl-tab"], NextTab, cx);
+        commands.register_action("Previous Tab", &["ctrl-shift-tab"], PreviousTab, cx);
+        commands.register_action("Split Right", &["ctrl-\\"], SplitRight, cx);
+        commands.register_action("Split Down", &["ctrl-k ctrl-\\"], SplitDown, cx);
+        commands.register_action("Close Pane", &["ctrl-k ctrl-w"], ClosePane, cx);
+        commands.register_action("Toggle Outline", &["ctrl-shift-o"], ToggleOutline, cx);
+        commands.register(
+            "Show Problems",
+            Some("ctrl-shift-m"),
+            |this, _window, cx| this.set_sidebar_view(SidebarView::Problems, cx),
+            cx,
+        );
+        commands.register(
+            "Go Back",
+            Some("alt-left"),
+            |this, _window, cx| {
+                this.active_editor().clone().update(cx, |editor, cx| editor.go_back(cx));
+            },
+            cx,
+        );
+        commands.register(
+            "Go Forward",
+            Some("alt-right"),
+            |this, _window, cx| {
+                this.active_editor().clone().update(cx, |editor, cx| editor.go_forward(cx));
+            },
+            cx,
+        );
+        commands.register(
+            "Toggle Bookmark",
+            Some("ctrl-alt-k"),
+            |this, _window, cx| {
+                this.active_editor().clone().update(cx, |editor, cx| editor.toggle_bookmark(cx));
+            },
+            cx,
+        );
+        commands.register(
+            "Next Bookmark",
+            Some("ctrl-alt-j"),
+            |this, _window, cx| {
+                this.active_editor().clone().update(cx, |editor, cx| editor.step_bookmark(true, cx));
+            },
+            cx,
+        );
+        commands.register(
+            "Previous Bookmark",
+            Some("ctrl-alt-l"),
+            |this, _window, cx| {
+                this.active_editor().clone().update(cx, |editor, cx| editor.step_bookmark(false, cx));
+            },
+            cx,
+        );
+        commands.register("Clear All Bookmarks", None, |this, _window, cx| this.clear_bookmarks(cx), cx);
+        commands.register(
+            "Show Bookmarks",
+            None,
+            |this, _window, cx| this.set_sidebar_view(SidebarView::Bookmarks, cx),
+            cx,
+        );
+        commands.register(
+            "Search in Project",
+            Some("ctrl-shift-f"),
+            |this, window, cx| this.show_project_search(window, cx),
+            cx,
+        );
+        commands.register(
+            "Open User Snippets",
+            None,
+            |this, window, cx| this.open_user_snippets(window, cx),
+            cx,
+        );
+        commands.register(
+            "Open Keymap File",
+            Some("ctrl-k ctrl-s"),
+            |this, window, cx| this.open_keymap_file(window, cx),
+            cx,
+        );
+        for preset in KeymapPreset::ALL {
+            commands.register(
+                format!("Keymap: {}", preset.label()),
+                None,
+                move |this, _window, cx| this.set_keymap_preset(preset, cx),
+                cx,
+            );
+        }
+        commands.register(
+            "Toggle Minimap",
+            None,
+            |this, _window, cx| {
+                this.active_editor().clone().update(cx, |editor, cx| editor.toggle_minimap(cx));
+            },
+            cx,
+        );
+        for theme in SyntaxTheme::presets() {
+            let name = theme.name.clone();
+            commands.register(
+                format!("Syntax Theme: {}", name),
+                None,
+                move |this, _window, cx| this.set_syntax_theme(theme.clone(), name.clone(), cx),
+                cx,
+            );
+        }
+        commands.register(
+            "Syntax Theme: Load From File...",
+            None,
+            |this, _window, cx| this.load_syntax_theme_file(cx),
+            cx,
+        );
+        commands.register(
+            "Toggle Word Wrap",
+            Some("alt-z"),
+            |this, window, cx| {
+                let mode = if this.settings.word_wrap.is_off() { WrapMode::Viewport } else { WrapMode::Off };
+                this.set_wrap_mode(mode, window, cx);
+            },
+            cx,
+        );
+        for mode in [WrapMode::Off, WrapMode::Viewport, WrapMode::Column(80), WrapMode::Column(100), WrapMode::Column(120)] {
+            commands.register(
+                format!("Word Wrap: {}", mode.label()),
+                None,
+                move |this, window, cx| this.set_wrap_mode(mode, window, cx),
+                cx,
+            );
+        }
+        commands.register(
+            "Toggle Inlay Hints",
+            None,
+            |this, _window, cx| {
+                let enabled = !this.settings.inlay_hints;
+                this.set_inlay_hints(enabled, cx);
+            },
+            cx,
+        );
+        commands.register(
+            "Toggle Vim Mode",
+            None,
+            |this, _window, cx| {
+                let enabled = !this.settings.vim_mode;
+                this.set_vim_mode(enabled, cx);
+            },
+            cx,
+        );
+        commands.register(
+            "Toggle Current Line Highlight",
+            None,
+            |this, window, cx| {
+                let enabled = !this.settings.highlight_current_line;
+                this.set_highlight_current_line(enabled, window, cx);
+            },
+            cx,
+        );
+        commands.register(
+            "Toggle Render Whitespace",
+            None,
+            |this, _window, cx| {
+                let mode = if this.settings.render_whitespace.is_none() { RenderWhitespace::All } else { RenderWhitespace::None };
+                this.set_render_whitespace(mode, cx);
+            },
+            cx,
+        );
+        for mode in RenderWhitespace::ALL {
+            commands.register(
+                format!("Render Whitespace: {}", mode.label()),
+                None,
+                move |this, _window, cx| this.set_render_whitespace(mode, cx),
+                cx,
+            );
+        }
+        commands.register(
+            "Toggle Trim Trailing Whitespace on Save",
+            None,
+            |this, _window, cx| {
+                let trim = !this.settings.trim_trailing_whitespace;
+                this.set_trim_trailing_whitespace(trim, cx);
+            },
+            cx,
+        );
+        for encoding in FileEncoding::choices() {
+            commands.register(
+                format!("Save with Encoding: {}", encoding.label()),
+                None,
+                move |this, window, cx| {
+                    this.active_editor().update(cx, |editor, cx| editor.save_with_encoding(encoding, window, cx));
+                },
+                cx,
+            );
+            // Reading keeps a byte order mark that is there, so UTF-8 with one is just UTF-8
+            if encoding != (FileEncoding { bom: true, ..FileEncoding::default() }) {
+                commands.register(
+                    format!("Reopen with Encoding: {}", encoding.label()),
+                    None,
+                    move |this, window, cx| {
+                        this.active_editor().update(cx, |editor, cx| editor.reopen_with_encoding(encoding, window, cx));
+                    },
+                    cx,
+                );
+            }
+        }
+        for ending in [LineEnding::Lf, LineEnding::Crlf] {
+            commands.register(
+                format!("Convert Line Endings to {}", ending.label()),
+                None,
+                move |this, _window, cx| this.text_editor.update(cx, |editor, cx| editor.set_line_ending(ending, cx)),
+                cx,
+            );
+        }
+        commands.register(
+            "Toggle Insert Final Newline on Save",
+            None,
+            |this, _window, cx| {
+                let insert = !this.settings.insert_final_newline;
+                this.set_final_newlines(insert, this.settings.trim_final_newlines, cx);
+            },
+            cx,
+        );
+        commands.register(
+            "Toggle Trim Final Newlines on Save",
+            None,
+            |this, _window, cx| {
+                let trim = !this.settings.trim_final_newlines;
+                this.set_final_newlines(this.settings.insert_final_newline, trim, cx);
+            },
+            cx,
+        );
+        commands.register(
+            "Toggle Format on Save",
+            None,
+            |this, _window, cx| this.toggle_format_on_save(cx),
+            cx,
+        );
+        commands.register(
+            "Toggle Organize Imports on Save",
+            None,
+            |this, _window, cx| this.toggle_organize_imports_on_save(cx),
+            cx,
+        );
+        commands.register(
+            "Trim Trailing Whitespace",
+            Some("ctrl-k ctrl-x"),
+            |this, window, cx| this.text_editor.update(cx, |editor, cx| editor.trim_trailing_whitespace(window, cx)),
+            cx,
+        );
+        for mode in AutoSaveMode::ALL {
+            commands.register(
+                format!("Auto Save: {}", mode.label()),
+                None,
+                move |this, _window, cx| this.set_auto_save_mode(mode, cx),
+                cx,
+            );
+        }
+        commands.register(
+            "Toggle Inline Diff",
+            None,
+            |this, window, cx| {
+                if this.mode == ScriptEditorMode::Diff {
+                    this.toggle_diff_layout(window, cx);
+                }
+            },
+            cx,
+        );
+        commands.register(
+            "Save All",
+            Some("ctrl-k s"),
+            |this, _window, cx| {
+                let summary = this.save_all(cx);
+                this.active_editor().clone().update(cx, |editor, cx| editor.set_status_message(summary.message(), cx));
+            },
+            cx,
+        );
+        commands.register(
+            "Restart Language Server",
+            None,
+            |this, _window, cx| {
+                this.active_editor().clone().update(cx, |editor, cx| editor.restart_language_server(cx));
+            },
+            cx,
+        );
+        commands.register(
+            "Run Script",
+            Some("f5"),
+            |this, window, cx| {
+                this.active_editor().clone().update(cx, |editor, cx| editor.run_current_file(window, cx));
+            },
+            cx,
+        );
+        commands.register(
+            "Stop Running Script",
+            Some("shift-f5"),
+            |this, _window, cx| this.output_panel.update(cx, |panel, cx| panel.stop(cx)),
+            cx,
+        );
+        commands.register(
+            "Toggle Output",
+            None,
+            |this, _window, cx| {
+                if this.bottom_panel == Some(BottomPanel::Output) {
+                    this.hide_bottom_panel(BottomPanel::Output, cx);
+                } else {
+                    this.bottom_panel = Some(BottomPanel::Output);
+                    cx.notify();
+                }
+            },
+            cx,
+        );
+        commands.register("Toggle Terminal", Some("ctrl-`"), |this, window, cx| this.toggle_terminal(window, cx), cx);
+        commands.register(
+            "New Terminal",
+            Some("ctrl-shift-`"),
+            |this, window, cx| {
+                this.show_terminal(window, cx);
+                this.terminal_panel.update(cx, |terminal, cx| terminal.new_session(cx));
+            },
+            cx,
+        );
+        commands.register(
+            "Run Selection in Terminal",
+            None,
+            |this, window, cx| this.run_selection_in_terminal(window, cx),
+            cx,
+        );
+        commands.register(
+            "Python: Select Interpreter",
+            None,
+            |this, _window, cx| {
+                this.active_editor().clone().update(cx, |editor, cx| editor.select_python_interpreter(cx));
+            },
+            cx,
+        );
+        commands.register(
+            "Toggle Git Blame",
+            Some("ctrl-k ctrl-b"),
+            |this, _window, cx| {
+                this.active_editor().clone().update(cx, |editor, cx| editor.cycle_blame_mode(cx));
+            },
+            cx,
+        );
+    }
+
+    /// Add a command to the command palette, bound to `keybinding` (keymap
+    /// syntax, e.g. `"ctrl-k ctrl-m"`) when given
+    pub fn register_command(
         &mut self,
-        workspace_root: PathBuf,
+        name: impl Into<SharedString>,
+        keybinding: Option<&str>,
+        handler: impl Fn(&mut ScriptEditor, &mut Window, &mut Context<ScriptEditor>) + 'static,
+        cx: &mut Context<Self>,
+    ) {
+        self.commands.register(name, keybinding, handler, cx);
+    }
+
+    fn toggle_command_palette(
+        &mut self,
+        _action: &ToggleCommandPalette,
         window: &mut Window,
         cx: &mut Context<Self>,
     ) {
-        if self.analyzer_workspace_root.as_ref() == Some(&workspace_root) {
+        if self.command_palette.is_some() {
+            self.close_command_palette(window, cx);
             return;
         }
-
-        if let Some(analyzer) = self.rust_analyzer.clone() {
-            analyzer.update(cx, |analyzer, cx| {
-                analyzer.start(workspace_root.clone(), window, cx);
-            });
-            self.analyzer_workspace_root = Some(workspace_root);
+        let previous_focus = window.focused(cx);
+        // Commands the focused pane's language server can't answer
+        let mut hidden = Vec::new();
+        if !self.active_editor().read(cx).provides_type_hierarchy() {
+            hidden.push("Show Type Hierarchy");
         }
+        let entries = self.commands.entries(&hidden);
+        let palette = cx.new(|cx| CommandPalette::new(entries, window, cx));
+        let subscription = cx.subscribe_in(&palette, window, |this, _palette, event: &CommandPaletteEvent, window, cx| {
+            match event {
+                CommandPaletteEvent::Run(name) => {
+                    this.close_command_palette(window, cx);
+                    this.run_command(name, window, cx);
+                }
+                CommandPaletteEvent::Dismissed => this.close_command_palette(window, cx),
+            }
+        });
+        self.command_palette = Some(PaletteSession {
+            palette,
+            previous_focus,
+            _subscription: subscription,
+        });
+        cx.notify();
+    }
+
+    fn close_command_palette(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(session) = self.command_palette.take() else {
+            return;
+        };
+        if let Some(focus) = session.previous_focus {
+            window.focus(&focus);
+        }
+        cx.notify();
+    }
+
+    fn go_to_workspace_symbol(
+        &mut self,
+        _action: &GoToWorkspaceSymbol,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if self.symbol_picker.is_some() {
+            self.close_symbol_picker(window, cx);
+            return;
+        }
+        // Every pane starts its own servers, so ask all of them
+        let mut servers = self.text_editor.read(cx).workspace_symbol_servers();
+        if let Some(split) = &self.split {
+            for server in split.editor.read(cx).workspace_symbol_servers() {
+                if !servers.iter().any(|known| Arc::ptr_eq(known, &server)) {
+                    servers.push(server);
+                }
+            }
+        }
+        let Some(root) = self
+            .get_project_root(cx)
+            .or_else(|| servers.first().map(|server| server.root().to_path_buf()))
+        else {
+            return;
+        };
+
+        let previous_focus = window.focused(cx);
+        let picker = cx.new(|cx| SymbolPicker::new(servers, root, window, cx));
+        let subscription = cx.subscribe_in(&picker, window, |this, _picker, event: &SymbolPickerEvent, window, cx| {
+            match event {
+                SymbolPickerEvent::OpenLocation(location) => {
+                    this.close_symbol_picker(window, cx);
+                    this.open_location(location, cx);
+                }
+                SymbolPickerEvent::Dismissed => this.close_symbol_picker(window, cx),
+            }
+        });
+        self.symbol_picker = Some(SymbolPickerSession {
+            picker,
+            previous_focus,
+            _subscription: subscription,
+        });
+        cx.notify();
+    }
+
+    fn close_symbol_picker(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(session) = self.symbol_picker.take() else {
+            return;
+        };
+        if let Some(focus) = session.previous_focus {
+            window.focus(&focus);
+        }
+        cx.notify();
+    }
+
+    fn toggle_quick_open(&mut self, _action: &ToggleQuickOpen, window: &mut Window, cx: &mut Context<Self>) {
+        if self.quick_open.is_some() {
+            self.close_quick_open(window, cx);
+            return;
+        }
+        let Some(root) = self.get_project_root(cx) else {
+            self.active_editor().update(cx, |editor, cx| editor.set_status_message("Open a project to search its files", cx));
+            return;
+        };
+        let index = self.ensure_file_index(root.clone(), cx);
+        let recent = self
+            .recent_files
+            .iter()
+            .filter_map(|path| path.strip_prefix(&root).ok())
+            .map(|path| {
+                let parts: Vec<_> = path.components().map(|part| part.as_os_str().to_string_lossy()).collect();
+                parts.join("/")
+            })
+            .collect();
+
+        let previous_focus = window.focused(cx);
+        let quick_open = cx.new(|cx| QuickOpen::new(index, recent, window, cx));
+        let subscription = cx.subscribe_in(&quick_open, window, |this, _quick_open, event: &QuickOpenEvent, window, cx| {
+            match event {
+                QuickOpenEvent::Open { path, target } => {
+                    this.close_quick_open(window, cx);
+                    match target {
+                        Some(target) => {
+                            this.record_recent_file(path);
+                            let line = target.line.saturating_sub(1);
+                            let character = target.column.unwrap_or(1).saturating_sub(1);
+                            this.active_editor().update(cx, |editor, cx| {
+                                editor.navigate_to(path.clone(), line, character, cx);
+                            });
+                        }
+                        None => this.open_file(path.clone(), window, cx),
+                    }
+                }
+                QuickOpenEvent::Dismissed => this.close_quick_open(window, cx),
+            }
+        });
+        self.quick_open = Some(QuickOpenSession {
+            quick_open,
+            previous_focus,
+            _subscription: subscription,
+        });
+        cx.notify();
+    }
+
+    fn close_quick_open(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(session) = self.quick_open.take() else {
+            return;
+        };
+        if let Some(focus) = session.previous_focus {
+            window.focus(&focus);
+        }
+        cx.notify();
+    }
+
+    fn run_command_action(&mut self, action: &RunCommand, window: &mut Window, cx: &mut Context<Self>) {
+        self.run_command(&action.name, window, cx);
+    }
+
+    /// Run a registered command in the focused pane
+    pub fn run_command(&mut self, name: &str, window: &mut Window, cx: &mut Context<Self>) {
+        match self.commands.take_for_run(name) {
+            // Dispatched from the restored focus so it reaches the editor's handlers
+            Some(CommandTarget::Action(action)) => window.dispatch_action(action.boxed_clone(), cx),
+            Some(CommandTarget::Handler(handler)) => handler(self, window, cx),
+            None => tracing::debug!("ℹ️  No command named {:?}", name),
+        }
+    }
+
+    fn resolve_workspace_root_for_file(path: &PathBuf) -> PathBuf {
+        workspace_root::workspace_root(path)
+    }
+
+    fn ensure_analyzer_workspace(
+        &mut self,
+        workspace_root: PathBuf,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if self.analyzer_workspace_root.as_ref() == Some(&workspace_root) {
+            return;
+        }
+
+        if let Some(analyzer) = self.rust_analyzer.clone() {
+            analyzer.update(cx, |analyzer, cx| {
+                analyzer.start(workspace_root.clone(), window, cx);
+            });
+            self.analyzer_workspace_root = Some(workspace_root);
+        }
+    }
+
+    /// Set the global rust analyzer manager
+    pub fn set_rust_analyzer(
+        &mut self,
+        analyzer: Entity<RustAnalyzerManager>,
+        cx: &mut Context<Self>,
+    ) {
+        tracing::debug!("🔧 ScriptEditor::set_rust_analyzer called");
+        self.rust_analyzer = Some(analyzer.clone());
+
+        // Pass it to the text editor
+        self.text_editor.update(cx, |editor, cx| {
+            tracing::debug!("🔧 Passing rust-analyzer to TextEditor");
+            editor.set_rust_analyzer(analyzer.clone(), cx);
+        });
+
+        if let Some(split) = &self.split {
+            split.editor.update(cx, |editor, cx| {
+                editor.set_rust_analyzer(analyzer.clone(), cx);
+            });
+        }
+
+        tracing::debug!("✓ ScriptEditor rust-analyzer setup complete");
+    }
+
+    /// Tell rust-analyzer about a file opened, saved or closed in one of the panes
+    fn notify_analyzer(&self, event: &TextEditorEvent, cx: &mut Context<Self>) {
+        tracing::debug!(
+            "📨 ScriptEditor received TextEditorEvent: {:?}",
+            std::mem::discriminant(event)
+        );
+        if let Some(ref analyzer) = self.rust_analyzer {
+            match event {
+                TextEditorEvent::FileOpened { path, content } => {
+                    tracing::debug!("📂 ScriptEditor handling FileOpened: {:?}", path);
+                    // Notify rust-analyzer that a file was opened
+                    analyzer.update(cx, |analyzer, _cx| {
+                        let language_id =
+                            if path.extension().and_then(|e| e.to_str()) == Some("rs") {
+                                "rust"
+                            } else {
+                                "text"
+                            };
+
+                        tracing::debug!(
+                            "🚀 Calling did_open_file for {:?} (language: {})",
+                            path.file_name(),
+                            language_id
+                        );
+                        if let Err(e) = analyzer.did_open_file(path, content, language_id) {
+                            tracing::error!(
+                                "⚠️  Failed to notify rust-analyzer of file open: {}",
+                                e
+                            );
+                        } else {
+                            tracing::debug!(
+                                "✓ Notified rust-analyzer: file opened {:?}",
+                                path.file_name()
+                            );
+                        }
+                    });
+                }
+                TextEditorEvent::FileSaved { path, content } => {
+                    tracing::debug!("💾 ScriptEditor handling FileSaved: {:?}", path);
+                    // Notify rust-analyzer that a file was saved
+                    analyzer.update(cx, |analyzer, _cx| {
+                        if let Err(e) = analyzer.did_save_file(path, content) {
+                            tracing::error!(
+                                "⚠️  Failed to notify rust-analyzer of file save: {}",
+                                e
+                            );
+                        } else {
+                            tracing::debug!(
+                                "✓ Notified rust-analyzer: file saved {:?}",
+                                path.file_name()
+                            );
+                        }
+                    });
+                }
+                TextEditorEvent::FileClosed { path } => {
+                    tracing::debug!("❌ ScriptEditor handling FileClosed: {:?}", path);
+                    // Notify rust-analyzer that a file was closed
+                    analyzer.update(cx, |analyzer, _cx| {
+                        if let Err(e) = analyzer.did_close_file(path) {
+                            tracing::error!(
+                                "⚠️  Failed to notify rust-analyzer of file close: {}",
+                                e
+                            );
+                        } else {
+                            tracing::debug!(
+                                "✓ Notified rust-analyzer: file closed {:?}",
+                                path.file_name()
+                            );
+                        }
+                    });
+                }
+                _ => {}
+            }
+        } else {
+            tracing::debug!("⚠️  ScriptEditor: rust_analyzer is None!");
+        }
+    }
+
+    /// Editor that actions and explorer clicks go to: the focused pane
+    fn active_editor(&self) -> &Entity<TextEditor> {
+        match (&self.split, self.active_pane) {
+            (Some(split), Pane::Secondary) => &split.editor,
+            _ => &self.text_editor,
+        }
+    }
+
+    /// Open a file a panel pointed to in the focused pane, at its line and column
+    fn open_location(&self, location: &Location, cx: &mut Context<Self>) {
+        self.active_editor().update(cx, |editor, cx| {
+            editor.navigate_to(location.path.clone(), location.line, location.character, cx);
+        });
+    }
+
+    /// The pane next to `editor`, if the editor is split
+    fn other_pane(&self, editor: &Entity<TextEditor>) -> Option<Entity<TextEditor>> {
+        let split = self.split.as_ref()?;
+        if editor == &self.text_editor {
+            Some(split.editor.clone())
+        } else {
+            Some(self.text_editor.clone())
+        }
+    }
+
+    /// Keep panes showing the same file in sync and forward events to the host
+    fn on_editor_event(
+        &mut self,
+        editor: &Entity<TextEditor>,
+        event: &TextEditorEvent,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let other = self.other_pane(editor);
+        let open_in_other = |path: &PathBuf, cx: &App| {
+            other
+                .as_ref()
+                .is_some_and(|other| other.read(cx).buffer_text(path, cx).is_some())
+        };
+
+        if matches!(event, TextEditorEvent::BufferChanged { .. }) && self.settings.auto_save == AutoSaveMode::AfterDelay {
+            self.schedule_auto_save(window, cx);
+        }
+
+        match event {
+            // A file already open in the other pane may have unsaved edits
+            TextEditorEvent::FileOpened { path, .. } | TextEditorEvent::BufferChanged { path } => {
+                let (from, to) = match event {
+                    TextEditorEvent::FileOpened { .. } => (other.clone(), Some(editor.clone())),
+                    _ => (Some(editor.clone()), other.clone()),
+                };
+                if let (Some(from), Some(to)) = (from, to) {
+                    if let Some(text) = from.read(cx).buffer_text(path, cx) {
+                        to.update(cx, |editor, cx| editor.sync_buffer(path, &text, window, cx));
+                    }
+                }
+            }
+            TextEditorEvent::FileSaved { path, content } => {
+                if let Some(other) = &other {
+                    other.update(cx, |editor, cx| editor.mark_saved(path, content, cx));
+                }
+            }
+            _ => {}
+        }
+
+        match event {
+            // rust-analyzer tracks each file once, however many panes show it
+            TextEditorEvent::FileOpened { path, .. } | TextEditorEvent::FileClosed { path } => {
+                if !open_in_other(path, cx) {
+                    self.notify_analyzer(event, cx);
+                }
+            }
+            TextEditorEvent::FileSaved { path, .. } => {
+                self.notify_analyzer(event, cx);
+                self.file_explorer.update(cx, |explorer, cx| explorer.refresh_git_status(cx));
+                if user_snippets_path().as_ref() == Some(path) {
+                    self.reload_snippets(cx);
+                }
+                if keymap_path().as_ref() == Some(path) {
+                    self.reload_keymap("Keymap reloaded".to_string(), cx);
+                }
+            }
+            TextEditorEvent::RunScriptRequested(path, command) => {
+                let cwd = path.parent().map(PathBuf::from).unwrap_or_default();
+                self.output_panel.update(cx, |panel, cx| panel.run(command.clone(), &cwd, cx));
+                self.bottom_panel = Some(BottomPanel::Output);
+                cx.notify();
+            }
+            TextEditorEvent::ShowProblemsRequested => self.set_sidebar_view(SidebarView::Problems, cx),
+            TextEditorEvent::GitIndexChanged => {
+                self.file_explorer.update(cx, |explorer, cx| explorer.refresh_git_status(cx));
+            }
+            // Report the state of all panes, not just the one that changed
+            TextEditorEvent::DirtyChanged(_) => {
+                cx.emit(TextEditorEvent::DirtyChanged(self.is_dirty(cx)));
+                return;
+            }
+            _ => {}
+        }
+        cx.emit(event.clone());
+    }
+
+    /// Split the editor along `axis`, showing the current file in the new pane.
+    ///
+    /// There is at most one split; splitting again only changes its direction.
+    fn split_editor(&mut self, axis: Axis, window: &mut Window, cx: &mut Context<Self>) {
+        if let Some(split) = &mut self.split {
+            split.axis = axis;
+            cx.notify();
+            return;
+        }
+
+        let current_path = self.text_editor.read(cx).current_file_path();
+        let json_schemas = self.settings.json_schemas.clone();
+        let run_commands = self.settings.run_commands.clone();
+        let drop_templates = self.settings.drop_templates.clone();
+        let language_servers = self.settings.language_servers.clone();
+        let wrap_mode = self.settings.word_wrap;
+        let render_whitespace = self.settings.render_whitespace;
+        let trim_trailing_whitespace = self.settings.trim_trailing_whitespace;
+        let insert_final_newline = self.settings.insert_final_newline;
+        let trim_final_newlines = self.settings.trim_final_newlines;
+        let save_cleanup = self.settings.save_cleanup.clone();
+        let save_when_formatting_fails = self.settings.save_when_formatting_fails;
+        let highlight_current_line = self.settings.highlight_current_line;
+        let inlay_hints = self.settings.inlay_hints;
+        let vim_mode = self.settings.vim_mode;
+        let large_file_threshold = self.settings.large_file_threshold_mb;
+        let sticky_scroll_depth = self.settings.sticky_scroll_max_depth;
+        let snippets = self.snippets.clone();
+        let editor = cx.new(|cx| {
+            let mut editor = TextEditor::new(window, cx);
+            editor.set_json_schemas(json_schemas);
+            editor.set_run_commands(run_commands);
+            editor.set_drop_templates(drop_templates);
+            editor.set_language_server_settings(language_servers);
+            editor.set_wrap_mode(wrap_mode, window, cx);
+            editor.set_render_whitespace(render_whitespace, cx);
+            editor.set_trim_trailing_whitespace(trim_trailing_whitespace);
+            editor.set_final_newlines(insert_final_newline, trim_final_newlines);
+            editor.set_language_save_cleanup(save_cleanup);
+            editor.set_save_when_formatting_fails(save_when_formatting_fails);
+            editor.set_highlight_current_line(highlight_current_line, window, cx);
+            editor.set_inlay_hints_enabled(inlay_hints, cx);
+            editor.set_vim_mode(vim_mode, cx);
+            editor.set_large_file_threshold(large_file_threshold);
+            editor.set_sticky_scroll_depth(sticky_scroll_depth);
+            editor.set_snippets(snippets);
+            editor
+        });
+        if let Some(analyzer) = self.rust_analyzer.clone() {
+            editor.update(cx, |editor, cx| editor.set_rust_analyzer(analyzer, cx));
+        }
+        let subscription = cx.subscribe_in(&editor, window, Self::on_editor_event);
+        let observation = cx.observe(&editor, |this, _editor, cx| {
+            this.refresh_outline(cx);
+            this.refresh_problems(cx);
+            this.refresh_bookmarks(cx);
+        });
+        self.split = Some(EditorSplit {
+            axis,
+            editor: editor.clone(),
+            resizable_state: ResizableState::new(cx),
+            _subscription: subscription,
+            _observation: observation,
+        });
+        self.active_pane = Pane::Secondary;
+
+        // The opened file picks up the main pane's unsaved edits in `on_editor_event`
+        if let Some(path) = current_path {
+            editor.update(cx, |editor, cx| editor.open_file(path, window, cx));
+        }
+        cx.notify();
+    }
+
+    /// Close the focused pane; the other one takes the whole editor area.
+    ///
+    /// Files with unsaved changes that only the closed pane had open are
+    /// reopened in the remaining pane so no edits are lost.
+    fn close_focused_pane(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(split) = self.split.take() else {
+            return;
+        };
+        let closed = if self.active_pane == Pane::Primary {
+            self._text_editor_subscription = split._subscription;
+            self._text_editor_observation = split._observation;
+            std::mem::replace(&mut self.text_editor, split.editor)
+        } else {
+            split.editor
+        };
+        self.active_pane = Pane::Primary;
+
+        let remaining = self.text_editor.clone();
+        for (path, text) in closed.read(cx).open_buffers(cx) {
+            if remaining.read(cx).buffer_text(&path, cx).is_some() {
+                continue;
+            }
+            if closed.read(cx).is_file_modified(&path) {
+                remaining.update(cx, |editor, cx| {
+                    editor.open_file(path.clone(), window, cx);
+                    editor.sync_buffer(&path, &text, window, cx);
+                });
+            } else {
+                self.notify_analyzer(&TextEditorEvent::FileClosed { path }, cx);
+            }
+        }
+        self.refresh_outline(cx);
+        self.refresh_problems(cx);
+        self.refresh_bookmarks(cx);
+        cx.notify();
+    }
+
+    /// Set the project path and load it in the file explorer
+    pub fn set_project_path(
+        &mut self,
+        project_path: PathBuf,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        tracing::debug!(
+            "📁 ScriptEditor::set_project_path called with: {:?}",
+            project_path
+        );
+        self.ensure_analyzer_workspace(project_path.clone(), window, cx);
+        self.terminal_panel.update(cx, |terminal, _cx| terminal.set_working_directory(project_path.clone()));
+        self.ensure_file_index(project_path.clone(), cx);
+        self.file_explorer.update(cx, |explorer, cx| {
+            explorer.open_project(project_path, window, cx);
+        });
+    }
+
+    /// Start listing the files under `root` for "Go to File", unless they already are
+    fn ensure_file_index(&mut self, root: PathBuf, cx: &mut Context<Self>) -> Entity<FileIndex> {
+        if let Some(index) = self.file_index.as_ref().filter(|index| index.read(cx).root() == root) {
+            return index.clone();
+        }
+        let index = cx.new(|cx| FileIndex::new(root, cx));
+        self.file_index = Some(index.clone());
+        index
+    }
+
+    fn record_recent_file(&mut self, path: &std::path::Path) {
+        self.recent_files.retain(|recent| recent != path);
+        self.recent_files.insert(0, path.to_path_buf());
+        self.recent_files.truncate(MAX_RECENT);
+    }
+
+    pub fn open_file(&mut self, path: PathBuf, window: &mut Window, cx: &mut Context<Self>) {
+        self.record_recent_file(&path);
+        if self.mode != ScriptEditorMode::Diff && large_files::is_binary_file(&path).unwrap_or(false) {
+            self.open_hex_view(path, window, cx);
+            return;
+        }
+        self.close_hex_view(window, cx);
+        let workspace_root = Self::resolve_workspace_root_for_file(&path);
+        self.ensure_analyzer_workspace(workspace_root, window, cx);
+        self.active_editor().update(cx, |editor, cx| {
+            editor.open_file(path, window, cx);
+        });
+    }
+
+    /// Show a binary file's bytes in place of the editor
+    fn open_hex_view(&mut self, path: PathBuf, window: &mut Window, cx: &mut Context<Self>) {
+        if self.hex_view.as_ref().is_some_and(|session| session.view.read(cx).path() == path) {
+            return;
+        }
+        let bytes = match std::fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                tracing::error!("✗ Failed to read file: {:?}, error: {}", path, e);
+                return;
+            }
+        };
+        let view = cx.new(|cx| HexView::new(path, bytes, cx));
+        let subscription = cx.subscribe_in(&view, window, |this, _view, event: &HexViewEvent, window, cx| match event {
+            HexViewEvent::Close => this.close_hex_view(window, cx),
+        });
+        window.focus(&view.focus_handle(cx));
+        self.hex_view = Some(HexViewSession {
+            view,
+            _subscription: subscription,
+        });
+        self.mode = ScriptEditorMode::Hex;
+        cx.notify();
+    }
+
+    fn close_hex_view(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        if self.mode != ScriptEditorMode::Hex {
+            return;
+        }
+        self.mode = ScriptEditorMode::Normal;
+        self.hex_view = None;
+        window.focus(&self.active_editor().focus_handle(cx));
+        cx.notify();
+    }
+
+    pub fn get_file_explorer(&self) -> &Entity<FileExplorer> {
+        &self.file_explorer
+    }
+
+    pub fn get_text_editor(&self) -> &Entity<TextEditor> {
+        &self.text_editor
+    }
+
+    /// Save every file with unsaved changes in both panes.
+    ///
+    /// A file that fails to save is reported in the summary and the rest are still saved.
+    pub fn save_all(&mut self, cx: &mut Context<Self>) -> SaveAllSummary {
+        let mut summary = SaveAllSummary::default();
+        // The diff editors show padded text that must never reach the files
+        if self.mode == ScriptEditorMode::Diff {
+            return summary;
+        }
+        let split_editor = self.split.as_ref().map(|split| split.editor.clone());
+        for editor in std::iter::once(self.text_editor.clone()).chain(split_editor) {
+            // A file open in both panes is saved once; the other pane hears about it
+            let pane = editor.update(cx, |editor, cx| {
+                for path in &summary.saved {
+                    if let Some(text) = editor.buffer_text(path, cx) {
+                        editor.mark_saved(path, &text, cx);
+                    }
+                }
+                editor.save_all_files(cx)
+            });
+            summary.extend(pane);
+        }
+        summary
+    }
+
+    /// Whether the editor has unsaved changes
+    pub fn is_dirty(&self, cx: &App) -> bool {
+        self.text_editor.read(cx).is_dirty()
+            || self
+                .split
+                .as_ref()
+                .is_some_and(|split| split.editor.read(cx).is_dirty())
+    }
+
+    /// Navigate to a specific line and column in the currently open file
+    pub fn go_to_line(
+        &mut self,
+        line: usize,
+        column: usize,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.active_editor().update(cx, |editor, cx| {
+            editor.go_to_line(line, column, window, cx);
+        });
+    }
+
+    fn save_current_file(
+        &mut self,
+        _action: &SaveCurrentFile,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.active_editor().update(cx, |editor, cx| {
+            editor.format_and_save_current_file(window, cx);
+        });
+    }
+
+    fn close_current_file(
+        &mut self,
+        _action: &CloseCurrentFile,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.active_editor().update(cx, |editor, cx| {
+            editor.close_current_file(window, cx);
+        });
+    }
+
+    fn go_to_definition(
+        &mut self,
+        _action: &GoToDefinition,
+        _window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.active_editor().update(cx, |editor, cx| {
+            editor.go_to_definition(cx);
+        });
+    }
+
+    fn find_all_references(
+        &mut self,
+        _action: &FindAllReferences,
+        _window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let Some((request, symbol)) = self.active_editor().read(cx).find_references(cx) else {
+            tracing::debug!("ℹ️  No language server available for Find All References");
+            return;
+        };
+        let buffers = self.active_editor().read(cx).open_buffers(cx);
+        let project_root = self
+            .active_editor()
+            .read(cx)
+            .current_file_path()
+            .map(|path| Self::resolve_workspace_root_for_file(&path));
+
+        self.references_panel.update(cx, |panel, cx| {
+            panel.set_project_root(project_root);
+            panel.begin_search(symbol, cx);
+        });
+        self.set_sidebar_view(SidebarView::References, cx);
+
+        let panel = self.references_panel.downgrade();
+        cx.spawn(async move |_this, cx| {
+            let locations = match request.response().await {
+                Ok(locations) => locations.unwrap_or_default(),
+                Err(e) => {
+                    tracing::error!("⚠️  References request failed: {:#}", e);
+                    Vec::new()
+                }
+            };
+
+            // Reading snippets can touch many files, keep it off the UI thread
+            let groups = cx
+                .background_spawn(async move {
+                    let locations = locations
+                        .into_iter()
+                        .filter_map(|location| {
+                            let path = lsp::uri_to_path(&location.uri)?;
+                            Some((path, location.range.start.line, location.range.start.character))
+                        })
+                        .collect();
+                    references_panel::group_references(locations, &buffers)
+                })
+                .await;
+
+            panel
+                .update(cx, |panel, cx| panel.set_results(groups, cx))
+                .ok();
+        })
+        .detach();
+    }
+
+    fn show_call_hierarchy(
+        &mut self,
+        _action: &ShowCallHierarchy,
+        _window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let Some((request, server)) = self.active_editor().read(cx).prepare_call_hierarchy(cx) else {
+            tracing::debug!("ℹ️  No language server available for Show Call Hierarchy");
+            return;
+        };
+        self.call_hierarchy_server = Some(server);
+        self.call_hierarchy_panel.update(cx, |panel, cx| panel.begin(cx));
+        self.set_sidebar_view(SidebarView::CallHierarchy, cx);
+
+        let panel = self.call_hierarchy_panel.downgrade();
+        cx.spawn(async move |_this, cx| {
+            let items = match request.response().await {
+                Ok(items) => items.unwrap_or_default(),
+                Err(e) => {
+                    tracing::error!("⚠️  Call hierarchy request failed: {:#}", e);
+                    Vec::new()
+                }
+            };
+            panel.update(cx, |panel, cx| panel.set_roots(items, cx)).ok();
+        })
+        .detach();
+    }
+
+    /// Ask for the calls of a function expanded in the call hierarchy
+    fn fetch_calls(
+        &mut self,
+        generation: usize,
+        node: usize,
+        item: lsp_types::CallHierarchyItem,
+        direction: CallDirection,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(server) = self.call_hierarchy_server.clone() else {
+            return;
+        };
+        let panel = self.call_hierarchy_panel.downgrade();
+        // Outgoing calls are made from the function asked about
+        let caller = item.uri.clone();
+        cx.spawn(async move |_this, cx| {
+            let calls = match direction {
+                CallDirection::Incoming => server
+                    .request::<lsp_types::request::CallHierarchyIncomingCalls>(lsp_types::CallHierarchyIncomingCallsParams {
+                        item,
+                        work_done_progress_params: Default::default(),
+                        partial_result_params: Default::default(),
+                    })
+                    .response()
+                    .await
+                    .map(|calls| {
+                        calls
+                            .unwrap_or_default()
+                            .into_iter()
+                            .map(|call| CallEntry {
+                                sites: Some((call.from.uri.clone(), call.from_ranges)),
+                                item: call.from,
+                            })
+                            .collect::<Vec<_>>()
+                    }),
+                CallDirection::Outgoing => server
+                    .request::<lsp_types::request::CallHierarchyOutgoingCalls>(lsp_types::CallHierarchyOutgoingCallsParams {
+                        item,
+                        work_done_progress_params: Default::default(),
+                        partial_result_params: Default::default(),
+                    })
+                    .response()
+                    .await
+                    .map(|calls| {
+                        calls
+                            .unwrap_or_default()
+                            .into_iter()
+                            .map(|call| CallEntry {
+                                item: call.to,
+                                sites: Some((caller.clone(), call.from_ranges)),
+                            })
+                            .collect::<Vec<_>>()
+                    }),
+            };
+            let calls = calls.unwrap_or_else(|e| {
+                tracing::error!("⚠️  Call hierarchy request failed: {:#}", e);
+                Vec::new()
+            });
+            panel
+                .update(cx, |panel, cx| panel.set_children(generation, node, calls, cx))
+                .ok();
+        })
+        .detach();
+    }
+
+    fn show_type_hierarchy(
+        &mut self,
+        _action: &ShowTypeHierarchy,
+        _window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let Some((request, server)) = self.active_editor().read(cx).prepare_type_hierarchy(cx) else {
+            tracing::debug!("ℹ️  No language server with type hierarchies for Show Type Hierarchy");
+            return;
+        };
+        self.type_hierarchy_server = Some(server);
+        self.type_hierarchy_panel.update(cx, |panel, cx| panel.begin(cx));
+        self.set_sidebar_view(SidebarView::TypeHierarchy, cx);
+
+        let panel = self.type_hierarchy_panel.downgrade();
+        cx.spawn(async move |_this, cx| {
+            let items = match request.response().await {
+                Ok(items) => items.unwrap_or_default(),
+                Err(e) => {
+                    tracing::error!("⚠️  Type hierarchy request failed: {:#}", e);
+                    Vec::new()
+                }
+            };
+            panel.update(cx, |panel, cx| panel.set_roots(items, cx)).ok();
+        })
+        .detach();
+    }
+
+    /// Ask for the supertypes or subtypes of a type expanded in the type hierarchy
+    fn fetch_types(
+        &mut self,
+        generation: usize,
+        node: usize,
+        item: lsp_types::TypeHierarchyItem,
+        direction: TypeDirection,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(server) = self.type_hierarchy_server.clone() else {
+            return;
+        };
+        let panel = self.type_hierarchy_panel.downgrade();
+        cx.spawn(async move |_this, cx| {
+            let types = match direction {
+                TypeDirection::Supertypes => server
+                    .request::<lsp_types::request::TypeHierarchySupertypes>(lsp_types::TypeHierarchySupertypesParams {
+                        item,
+                        work_done_progress_params: Default::default(),
+                        partial_result_params: Default::default(),
+                    })
+                    .response()
+                    .await,
+                TypeDirection::Subtypes => server
+                    .request::<lsp_types::request::TypeHierarchySubtypes>(lsp_types::TypeHierarchySubtypesParams {
+                        item,
+                        work_done_progress_params: Default::default(),
+                        partial_result_params: Default::default(),
+                    })
+                    .response()
+                    .await,
+            };
+            let types = match types {
+                Ok(types) => types.unwrap_or_default().into_iter().map(HierarchyEntry::new).collect(),
+                Err(e) => {
+                    tracing::error!("⚠️  Type hierarchy request failed: {:#}", e);
+                    Vec::new()
+                }
+            };
+            panel
+                .update(cx, |panel, cx| panel.set_children(generation, node, types, cx))
+                .ok();
+        })
+        .detach();
+    }
+
+    fn reveal_in_file_explorer(
+        &mut self,
+        _action: &RevealInFileExplorer,
+        _window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(path) = self.active_editor().read(cx).current_file_path() else {
+            return;
+        };
+        if self.file_explorer.update(cx, |explorer, cx| explorer.reveal(&path, cx)) {
+            self.set_sidebar_view(SidebarView::Explorer, cx);
+        } else {
+            self.active_editor().update(cx, |editor, cx| {
+                editor.set_status_message("The file isn't in the open project", cx);
+            });
+        }
+    }
+
+    fn copy_absolute_path(
+        &mut self,
+        _action: &CopyAbsolutePath,
+        _window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if let Some(path) = self.active_editor().read(cx).current_file_path() {
+            cx.write_to_clipboard(ClipboardItem::new_string(path.to_string_lossy().to_string()));
+        }
+    }
+
+    /// Copy the current file's path from the root of the project it's in
+    fn copy_relative_path(
+        &mut self,
+        _action: &CopyRelativePath,
+        _window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(path) = self.active_editor().read(cx).current_file_path() else {
+            return;
+        };
+        let root = Self::resolve_workspace_root_for_file(&path);
+        let relative = path.strip_prefix(&root).unwrap_or(&path);
+        cx.write_to_clipboard(ClipboardItem::new_string(relative.to_string_lossy().to_string()));
+    }
+
+    fn open_containing_folder(
+        &mut self,
+        _action: &OpenContainingFolder,
+        _window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if let Some(path) = self.active_editor().read(cx).current_file_path() {
+            file_explorer::reveal_in_file_manager(&path);
+        }
+    }
+
+    fn format_document(
+        &mut self,
+        _action: &FormatDocument,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.active_editor().update(cx, |editor, cx| {
+            editor.format_document(window, cx);
+        });
+    }
+
+    fn rename_symbol(
+        &mut self,
+        _action: &RenameSymbol,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.active_editor().update(cx, |editor, cx| {
+            editor.start_rename(window, cx);
+        });
+    }
+
+    fn show_code_actions(&mut self, _action: &ShowCodeActions, window: &mut Window, cx: &mut Context<Self>) {
+        self.active_editor().update(cx, |editor, cx| {
+            editor.show_code_actions(window, cx);
+        });
+    }
+
+    fn toggle_find(&mut self, _action: &ToggleFind, window: &mut Window, cx: &mut Context<Self>) {
+        self.active_editor().update(cx, |editor, cx| {
+            editor.show_find_bar(false, window, cx);
+        });
+    }
+
+    fn toggle_replace(
+        &mut self,
+        _action: &ToggleReplace,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.active_editor().update(cx, |editor, cx| {
+            editor.show_find_bar(true, window, cx);
+        });
+    }
+
+    fn select_next_occurrence(
+        &mut self,
+        _action: &SelectNextOccurrence,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        self.active_editor().update(cx, |editor, cx| {
+            editor.select_next_occurrence(window, cx);
+        });
+    }
+
+    fn expand_selection(&mut self, _action: &ExpandSelection, window: &mut Window, cx: &mut Context<Self>) {
+        self.active_editor().update(cx, |editor, cx| {
+            editor.expand_selection(window, cx);
+        });
+    }
+
+    fn shrink_selection(&mut self, _action: &ShrinkSelection, window: &mut Window, cx: &mut Context<Self>) {
+        self.active_editor().update(cx, |editor, cx| {
+            editor.shrink_selection(window, cx);
+        });
+    }
+
+    fn toggle_line_comment(&mut self, _action: &ToggleLineComment, window: &mut Window, cx: &mut Context<Self>) {
+        self.active_editor().update(cx, |editor, cx| {
+            editor.toggle_line_comment(window, cx);
+        });
+    }
+
+    fn toggle_block_comment(&mut self, _action: &ToggleBlockComment, window: &mut Window, cx: &mut Context<Self>) {
+        self.active_editor().update(cx, |editor, cx| {
+            editor.toggle_block_comment(window, cx);
+        });
+    }
+
+    fn move_lines(&mut self, up: bool, window: &mut Window, cx: &mut Context<Self>) {
+        self.active_editor().update(cx, |editor, cx| {
+            editor.move_lines(up, window, cx);
+        });
+    }
+
+    fn duplicate_lines(&mut self, down: bool, window: &mut Window, cx: &mut Context<Self>) {
+        self.active_editor().update(cx, |editor, cx| {
+            editor.duplicate_lines(down, window, cx);
+        });
     }
 
-    /// Set the global rust analyzer manager
-    pub fn set_rust_analyzer(
+    fn extend_box_selection(&mut self, lines: i32, columns: i32, window: &mut Window, cx: &mut Context<Self>) {
+        self.active_editor().update(cx, |editor, cx| {
+            editor.extend_box_selection(lines, columns, window, cx);
+        });
+    }
+
+    fn collapse_cursors(
         &mut self,
-        analyzer: Entity<RustAnalyzerManager>,
+        _action: &CollapseCursors,
+        _window: &mut Window,
         cx: &mut Context<Self>,
     ) {
-        tracing::debug!("🔧 ScriptEditor::set_rust_analyzer called");
-        self.rust_analyzer = Some(analyzer.clone());
+        self.active_editor().update(cx, |editor, cx| {
+            editor.collapse_cursors(cx);
+        });
+    }
 
-        // Pass it to the text editor
-        self.text_editor.update(cx, |editor, cx| {
-            tracing::debug!("🔧 Passing rust-analyzer to TextEditor");
-            editor.set_rust_analyzer(analyzer.clone(), cx);
+    fn fold_all(&mut self, _action: &FoldAll, _window: &mut Window, cx: &mut Context<Self>) {
+        self.active_editor().update(cx, |editor, cx| {
+            editor.set_all_folded(true, cx);
         });
+    }
 
-        // Subscribe to text editor events to forward to rust-analyzer.
-        // Store the subscription so repeated calls replace rather than stack.
-        self._analyzer_sub = Some(cx.subscribe(
-            &self.text_editor,
-            move |this: &mut Self, _editor, event: &TextEditorEvent, cx| {
-                tracing::debug!(
-                    "📨 ScriptEditor received TextEditorEvent: {:?}",
-                    std::mem::discriminant(event)
-                );
-                if let Some(ref analyzer) = this.rust_analyzer {
-                    match event {
-                        TextEditorEvent::FileOpened { path, content } => {
-                            tracing::debug!("📂 ScriptEditor handling FileOpened: {:?}", path);
-                            // Notify rust-analyzer that a file was opened
-                            analyzer.update(cx, |analyzer, _cx| {
-                                let language_id =
-                                    if path.extension().and_then(|e| e.to_str()) == Some("rs") {
-                                        "rust"
-                                    } else {
-                                        "text"
-                                    };
-
-                                tracing::debug!(
-                                    "🚀 Calling did_open_file for {:?} (language: {})",
-                                    path.file_name(),
-                                    language_id
-                                );
-                                if let Err(e) = analyzer.did_open_file(path, content, language_id) {
-                                    tracing::error!(
-                                        "⚠️  Failed to notify rust-analyzer of file open: {}",
-                                        e
-                                    );
-                                } else {
-                                    tracing::debug!(
-                                        "✓ Notified rust-analyzer: file opened {:?}",
-                                        path.file_name()
-                                    );
-                                }
-                            });
-                        }
-                        TextEditorEvent::FileSaved { path, content } => {
-                            tracing::debug!("💾 ScriptEditor handling FileSaved: {:?}", path);
-                            // Notify rust-analyzer that a file was saved
-                            analyzer.update(cx, |analyzer, _cx| {
-                                if let Err(e) = analyzer.did_save_file(path, content) {
-                                    tracing::error!(
-                                        "⚠️  Failed to notify rust-analyzer of file save: {}",
-                                        e
-                                    );
-                                } else {
-                                    tracing::debug!(
-                                        "✓ Notified rust-analyzer: file saved {:?}",
-                                        path.file_name()
-                                    );
-                                }
-                            });
-                        }
-                        TextEditorEvent::FileClosed { path } => {
-                            tracing::debug!("❌ ScriptEditor handling FileClosed: {:?}", path);
-                            // Notify rust-analyzer that a file was closed
-                            analyzer.update(cx, |analyzer, _cx| {
-                                if let Err(e) = analyzer.did_close_file(path) {
-                                    tracing::error!(
-                                        "⚠️  Failed to notify rust-analyzer of file close: {}",
-                                        e
-                                    );
-                                } else {
-                                    tracing::debug!(
-                                        "✓ Notified rust-analyzer: file closed {:?}",
-                                        path.file_name()
-                                    );
-                                }
-                            });
-                        }
-                        _ => {}
-                    }
-                } else {
-                    tracing::debug!("⚠️  ScriptEditor: rust_analyzer is None!");
-                }
-            },
-        ));
+    fn unfold_all(&mut self, _action: &UnfoldAll, _window: &mut Window, cx: &mut Context<Self>) {
+        self.active_editor().update(cx, |editor, cx| {
+            editor.set_all_folded(false, cx);
+        });
+    }
 
-        tracing::debug!("✓ ScriptEditor rust-analyzer setup complete");
+    fn next_tab(&mut self, _action: &NextTab, window: &mut Window, cx: &mut Context<Self>) {
+        self.active_editor().update(cx, |editor, cx| {
+            editor.cycle_tabs(true, window, cx);
+        });
     }
 
-    /// Set the project path and load it in the file explorer
-    pub fn set_project_path(
+    fn previous_tab(&mut self, _action: &PreviousTab, window: &mut Window, cx: &mut Context<Self>) {
+        self.active_editor().update(cx, |editor, cx| {
+            editor.cycle_tabs(false, window, cx);
+        });
+    }
+
+    fn jump_to_matching_bracket(
         &mut self,
-        project_path: PathBuf,
+        _action: &JumpToMatchingBracket,
         window: &mut Window,
         cx: &mut Context<Self>,
     ) {
-        tracing::debug!(
-            "📁 ScriptEditor::set_project_path called with: {:?}",
-            project_path
-        );
-        self.ensure_analyzer_workspace(project_path.clone(), window, cx);
-        self.file_explorer.update(cx, |explorer, cx| {
-            explorer.open_project(project_path, window, cx);
+        self.active_editor().update(cx, |editor, cx| {
+            editor.jump_to_matching_bracket(window, cx);
         });
     }
 
-    pub fn open_file(&mut self, path: PathBuf, window: &mut Window, cx: &mut Context<Self>) {
-        let workspace_root = Self::resolve_workspace_root_for_file(&path);
-        self.ensure_analyzer_workspace(workspace_root, window, cx);
-        self.text_editor.update(cx, |editor, cx| {
-            editor.open_file(path, window, cx);
+    fn show_go_to_line(&mut self, _action: &GoToLine, window: &mut Window, cx: &mut Context<Self>) {
+        self.active_editor().update(cx, |editor, cx| {
+            editor.show_go_to_line(window, cx);
         });
     }
 
-    pub fn get_file_explorer(&self) -> &Entity<FileExplorer> {
-        &self.file_explorer
+    fn split_right(&mut self, _action: &SplitRight, window: &mut Window, cx: &mut Context<Self>) {
+        self.split_editor(Axis::Horizontal, window, cx);
     }
 
-    pub fn get_text_editor(&self) -> &Entity<TextEditor> {
-        &self.text_editor
+    fn split_down(&mut self, _action: &SplitDown, window: &mut Window, cx: &mut Context<Self>) {
+        self.split_editor(Axis::Vertical, window, cx);
     }
 
-    /// Navigate to a specific line and column in the currently open file
-    pub fn go_to_line(
-        &mut self,
-        line: usize,
-        column: usize,
-        window: &mut Window,
-        cx: &mut Context<Self>,
-    ) {
-        self.text_editor.update(cx, |editor, cx| {
-            editor.go_to_line(line, column, window, cx);
+    fn close_pane(&mut self, _action: &ClosePane, window: &mut Window, cx: &mut Context<Self>) {
+        self.close_focused_pane(window, cx);
+    }
+
+    fn toggle_outline(&mut self, _action: &ToggleOutline, _window: &mut Window, cx: &mut Context<Self>) {
+        let view = if self.sidebar_view == SidebarView::Outline {
+            SidebarView::Explorer
+        } else {
+            SidebarView::Outline
+        };
+        self.set_sidebar_view(view, cx);
+    }
+
+    fn hide_bottom_panel(&mut self, panel: BottomPanel, cx: &mut Context<Self>) {
+        if self.bottom_panel == Some(panel) {
+            self.bottom_panel = None;
+            cx.notify();
+        }
+    }
+
+    /// Show the terminal, with a shell started in it if none is running
+    fn show_terminal(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.bottom_panel = Some(BottomPanel::Terminal);
+        self.terminal_panel.update(cx, |terminal, cx| {
+            if !terminal.has_sessions() {
+                terminal.new_session(cx);
+            }
+            terminal.focus(window);
         });
+        cx.notify();
     }
 
-    fn save_current_file(
-        &mut self,
-        _action: &SaveCurrentFile,
-        window: &mut Window,
-        cx: &mut Context<Self>,
-    ) {
-        self.text_editor.update(cx, |editor, cx| {
-            editor.save_current_file(window, cx);
+    fn toggle_terminal(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        if self.bottom_panel == Some(BottomPanel::Terminal) {
+            self.hide_bottom_panel(BottomPanel::Terminal, cx);
+            self.active_editor().clone().update(cx, |editor, cx| editor.focus_current_file(window, cx));
+        } else {
+            self.show_terminal(window, cx);
+        }
+    }
+
+    /// Type the selection, or the cursor's line, into the terminal and run it
+    fn run_selection_in_terminal(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(text) = self.active_editor().update(cx, |editor, cx| editor.selection_or_line(window, cx)) else {
+            return;
+        };
+        self.show_terminal(window, cx);
+        self.active_editor().clone().update(cx, |editor, cx| editor.focus_current_file(window, cx));
+        self.terminal_panel.update(cx, |terminal, cx| {
+            // Lines end in `\r`, as if typed
+            let text = text.trim_end_matches(['\r', '\n']).replace("\r\n", "\r").replace('\n', "\r");
+            terminal.send_text(&format!("{}\r", text), cx);
         });
     }
 
-    fn close_current_file(
-        &mut self,
-        _action: &CloseCurrentFile,
-        window: &mut Window,
-        cx: &mut Context<Self>,
-    ) {
-        self.text_editor.update(cx, |editor, cx| {
-            editor.close_current_file(window, cx);
+    /// Switch the sidebar between the file explorer, references and outline
+    pub fn set_sidebar_view(&mut self, view: SidebarView, cx: &mut Context<Self>) {
+        if self.sidebar_view != view {
+            self.sidebar_view = view;
+            self.refresh_outline(cx);
+            self.refresh_problems(cx);
+            self.refresh_bookmarks(cx);
+            cx.notify();
+        }
+    }
+
+    /// Show the search panel with the focused pane's selection as the query, if there is one
+    pub fn show_project_search(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let selection = self.active_editor().update(cx, |editor, cx| editor.selected_text(window, cx));
+        self.set_sidebar_view(SidebarView::Search, cx);
+        let project_root = self.get_project_root(cx);
+        self.project_search_panel.update(cx, |panel, cx| {
+            panel.set_project_root(project_root);
+            panel.focus_search(selection, window, cx);
+        });
+    }
+
+    /// List the bookmarks of both panes' files, while the bookmarks panel is visible
+    fn refresh_bookmarks(&mut self, cx: &mut Context<Self>) {
+        if self.sidebar_view != SidebarView::Bookmarks {
+            return;
+        }
+        let mut bookmarks = Vec::new();
+        for editor in self.editors() {
+            // A file open in both panes has bookmarks in each; the first pane's are listed
+            let listed: std::collections::HashSet<PathBuf> =
+                bookmarks.iter().map(|bookmark: &bookmarks_panel::Bookmark| bookmark.path.clone()).collect();
+            bookmarks.extend(editor.read(cx).bookmarks().into_iter().filter(|bookmark| !listed.contains(&bookmark.path)));
+        }
+        let project_root = self.get_project_root(cx);
+        self.bookmarks_panel.update(cx, |panel, cx| {
+            panel.set_project_root(project_root);
+            panel.set_bookmarks(bookmarks, cx);
+        });
+    }
+
+    fn clear_bookmarks(&mut self, cx: &mut Context<Self>) {
+        for editor in self.editors() {
+            editor.update(cx, |editor, cx| editor.clear_bookmarks(cx));
+        }
+    }
+
+    /// Search the project for the search panel's query, with open files as
+    /// they are in either pane
+    fn search_project(&mut self, cx: &mut Context<Self>) {
+        let mut buffers = HashMap::new();
+        for editor in self.editors() {
+            buffers.extend(editor.read(cx).open_buffers(cx));
+        }
+        let project_root = self.get_project_root(cx);
+        self.project_search_panel.update(cx, |panel, cx| {
+            panel.set_project_root(project_root);
+            panel.search(buffers, cx);
+        });
+    }
+
+    /// List the diagnostics of both panes' files, while the problems panel is visible
+    fn refresh_problems(&mut self, cx: &mut Context<Self>) {
+        if self.sidebar_view != SidebarView::Problems {
+            return;
+        }
+        let mut problems = Vec::new();
+        for editor in self.editors() {
+            // A file open in both panes has the same diagnostics in each
+            let listed: std::collections::HashSet<PathBuf> = problems.iter().map(|problem: &Problem| problem.path.clone()).collect();
+            problems.extend(editor.read(cx).problems().into_iter().filter(|problem| !listed.contains(&problem.path)));
+        }
+        let project_root = self.get_project_root(cx);
+        self.problems_panel.update(cx, |panel, cx| {
+            panel.set_project_root(project_root);
+            panel.set_problems(problems, cx);
+        });
+    }
+
+    /// Show the focused pane's current file in the outline, while it is visible
+    fn refresh_outline(&mut self, cx: &mut Context<Self>) {
+        if self.sidebar_view != SidebarView::Outline {
+            return;
+        }
+        let (file, symbols, cursor_path) = match self.active_editor().read(cx).current_outline() {
+            Some((file, symbols, cursor_path)) => (Some(file.clone()), symbols.to_vec(), cursor_path.to_vec()),
+            None => (None, Vec::new(), Vec::new()),
+        };
+        self.outline_panel.update(cx, |panel, cx| {
+            panel.set_outline(file, symbols, cursor_path, cx);
         });
     }
 
@@ -372,6 +2565,7 @@ impl ScriptEditor {
         self.mode = ScriptEditorMode::Normal;
         self.diff_files.clear();
         self.selected_diff_index = None;
+        self.diff_hunks.clear();
 
         // Clear diff files in explorer
         self.file_explorer.update(cx, |explorer, cx| {
@@ -394,27 +2588,40 @@ impl ScriptEditor {
             let after_content = file.after_content.clone();
             let path = PathBuf::from(&file.path);
 
-            // Load "before" content into main editor with diff highlighting
+            let main_side = match self.diff_layout {
+                DiffLayout::SideBySide => DiffSide::Before,
+                DiffLayout::Inline => DiffSide::Inline,
+            };
+            let view = diff_view(&before_content, &after_content, main_side);
+            if self.selected_diff_index != Some(index) {
+                self.selected_diff_hunk = 0;
+            }
+            self.selected_diff_hunk = self.selected_diff_hunk.min(view.hunks.len().saturating_sub(1));
+            self.diff_hunks = view.hunks.into_iter().zip(view.hunk_rows).collect();
+
+            // Load "before" content (or the unified view) into main editor with diff highlighting
             self.text_editor.update(cx, |editor, cx| {
                 editor.load_content_with_diff_highlight(
                     path.clone(),
                     before_content.clone(),
-                    Some((after_content.clone(), true)), // Compare with after, this is before
+                    Some((after_content.clone(), main_side)),
                     window,
                     cx,
                 );
             });
 
             // Load "after" content into diff editor with diff highlighting
-            self.diff_editor.update(cx, |editor, cx| {
-                editor.load_content_with_diff_highlight(
-                    path,
-                    after_content,
-                    Some((before_content, false)), // Compare with before, this is after
-                    window,
-                    cx,
-                );
-            });
+            if self.diff_layout == DiffLayout::SideBySide {
+                self.diff_editor.update(cx, |editor, cx| {
+                    editor.load_content_with_diff_highlight(
+                        path,
+                        after_content,
+                        Some((before_content, DiffSide::After)),
+                        window,
+                        cx,
+                    );
+                });
+            }
 
             self.selected_diff_index = Some(index);
             cx.notify();
@@ -427,6 +2634,181 @@ impl ScriptEditor {
         }
     }
 
+    /// Switch diff mode between side-by-side columns and one unified view
+    fn toggle_diff_layout(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.diff_layout = match self.diff_layout {
+            DiffLayout::SideBySide => DiffLayout::Inline,
+            DiffLayout::Inline => DiffLayout::SideBySide,
+        };
+        self.last_left_scroll = None;
+        self.last_right_scroll = None;
+        if let Some(index) = self.selected_diff_index {
+            self.load_diff_file(index, window, cx);
+        }
+        cx.notify();
+    }
+
+    /// Select the next (or previous) change and scroll to it
+    fn select_diff_hunk(&mut self, forward: bool, window: &mut Window, cx: &mut Context<Self>) {
+        let count = self.diff_hunks.len();
+        if count == 0 {
+            return;
+        }
+        self.selected_diff_hunk = if forward {
+            (self.selected_diff_hunk + 1) % count
+        } else {
+            (self.selected_diff_hunk + count - 1) % count
+        };
+        let row = self.diff_hunks[self.selected_diff_hunk].1 as usize;
+        self.text_editor.update(cx, |editor, cx| editor.go_to_line(row + 1, 1, window, cx));
+        if self.diff_layout == DiffLayout::SideBySide {
+            self.diff_editor.update(cx, |editor, cx| editor.go_to_line(row + 1, 1, window, cx));
+        }
+        cx.notify();
+    }
+
+    /// Copy the original lines of the selected change
+    fn copy_diff_hunk(&mut self, cx: &mut Context<Self>) {
+        if let Some((hunk, _)) = self.diff_hunks.get(self.selected_diff_hunk) {
+            cx.write_to_clipboard(ClipboardItem::new_string(hunk.head_text.clone()));
+            self.text_editor
+                .update(cx, |editor, cx| editor.set_status_message("Copied original lines", cx));
+        }
+    }
+
+    /// Undo the selected change in the new version, and on disk when the
+    /// project file is that version
+    fn revert_diff_hunk(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let (Some(index), Some((hunk, _))) = (self.selected_diff_index, self.diff_hunks.get(self.selected_diff_hunk))
+        else {
+            return;
+        };
+        let root = self.get_project_root(cx);
+        let Some(file) = self.diff_files.get_mut(index) else {
+            return;
+        };
+        let Some(reverted) = git_diff::revert_hunk(&file.after_content, hunk) else {
+            return;
+        };
+        if let Some(disk_path) = root.map(|root| root.join(&file.path)) {
+            let on_disk = std::fs::read_to_string(&disk_path).ok();
+            if on_disk.as_deref() == Some(file.after_content.as_str()) {
+                if let Err(e) = std::fs::write(&disk_path, &reverted) {
+                    tracing::error!("⚠️  Failed to revert change in {:?}: {}", disk_path, e);
+                    return;
+                }
+            }
+        }
+        file.after_content = reverted;
+        self.load_diff_file(index, window, cx);
+    }
+
+    /// Stage just the selected change of the project file
+    fn stage_diff_hunk(&mut self, cx: &mut Context<Self>) {
+        let (Some(index), Some((hunk, _))) = (self.selected_diff_index, self.diff_hunks.get(self.selected_diff_hunk))
+        else {
+            return;
+        };
+        let (Some(root), Some(file)) = (self.get_project_root(cx), self.diff_files.get(index)) else {
+            return;
+        };
+        let path = root.join(&file.path);
+        let hunk = hunk.clone();
+        let text_editor = self.text_editor.clone();
+        cx.spawn(async move |this, cx| {
+            let result = cx.background_executor().spawn(async move { git_diff::stage_hunk(&path, &hunk) }).await;
+            let message = match result {
+                Ok(()) => "Staged change".to_string(),
+                Err(e) => format!("Failed to stage change: {}", e),
+            };
+            text_editor.update(cx, |editor, cx| editor.set_status_message(message, cx)).ok();
+            this.update(cx, |this, cx| {
+                this.file_explorer.update(cx, |explorer, cx| explorer.refresh_git_status(cx));
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    /// Layout toggle and the buttons for the selected change, above the diff editors
+    fn render_diff_toolbar(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let has_hunks = !self.diff_hunks.is_empty();
+        let position = if has_hunks {
+            format!("Change {} of {}", self.selected_diff_hunk + 1, self.diff_hunks.len())
+        } else {
+            "No changes".to_string()
+        };
+
+        h_flex()
+            .w_full()
+            .px_2()
+            .py_1()
+            .gap_1()
+            .bg(cx.theme().secondary)
+            .border_b_1()
+            .border_color(cx.theme().border)
+            .child(
+                Button::new("diff_layout")
+                    .label(match self.diff_layout {
+                        DiffLayout::SideBySide => "Inline",
+                        DiffLayout::Inline => "Side by Side",
+                    })
+                    .ghost()
+                    .xsmall()
+                    .on_click(cx.listener(|this, _, window, cx| this.toggle_diff_layout(window, cx))),
+            )
+            .child(div().flex_1())
+            .child(
+                div()
+                    .text_xs()
+                    .text_color(cx.theme().muted_foreground)
+                    .child(position),
+            )
+            .child(
+                Button::new("previous_hunk")
+                    .icon(IconName::ChevronUp)
+                    .tooltip("Previous Change")
+                    .ghost()
+                    .xsmall()
+                    .disabled(!has_hunks)
+                    .on_click(cx.listener(|this, _, window, cx| this.select_diff_hunk(false, window, cx))),
+            )
+            .child(
+                Button::new("next_hunk")
+                    .icon(IconName::ChevronDown)
+                    .tooltip("Next Change")
+                    .ghost()
+                    .xsmall()
+                    .disabled(!has_hunks)
+                    .on_click(cx.listener(|this, _, window, cx| this.select_diff_hunk(true, window, cx))),
+            )
+            .child(
+                Button::new("copy_hunk")
+                    .label("Copy")
+                    .tooltip("Copy Original Lines")
+                    .ghost()
+                    .xsmall()
+                    .disabled(!has_hunks)
+                    .on_click(cx.listener(|this, _, _window, cx| this.copy_diff_hunk(cx))),
+            )
+            .child(
+                Button::new("revert_hunk")
+                    .label("Revert")
+                    .ghost()
+                    .xsmall()
+                    .disabled(!has_hunks)
+                    .on_click(cx.listener(|this, _, window, cx| this.revert_diff_hunk(window, cx))),
+            )
+            .child(
+                Button::new("stage_hunk")
+                    .label("Stage")
+                    .ghost()
+                    .xsmall()
+                    .disabled(!has_hunks)
+                    .on_click(cx.listener(|this, _, _window, cx| this.stage_diff_hunk(cx))),
+            )
+    }
+
     /// Get the project root from the file explorer
     pub fn get_project_root(&self, cx: &mut Context<Self>) -> Option<PathBuf> {
         self.file_explorer
@@ -535,7 +2917,7 @@ impl Panel for ScriptEditor {
     }
 
     fn panel_file_path(&self, cx: &App) -> Option<std::path::PathBuf> {
-        self.text_editor.read(cx).current_file_path()
+        self.active_editor().read(cx).current_file_path()
     }
 
     fn title(&self, _window: &Window, _cx: &App) -> AnyElement {
@@ -557,9 +2939,9 @@ impl ScriptEditor {
         window: &mut Window,
         cx: &mut Context<Self>,
     ) -> Result<(), plugin_editor_api::PluginError> {
-        // Delegate save to the text editor
-        self.text_editor.update(cx, |editor, cx| {
-            editor.save_current_file(window, cx);
+        // Delegate save to the text editor, which may format the file first
+        self.active_editor().update(cx, |editor, cx| {
+            editor.format_and_save_current_file(window, cx);
         });
         Ok(())
     }
@@ -569,15 +2951,82 @@ impl ScriptEditor {
         window: &mut Window,
         cx: &mut Context<Self>,
     ) -> Result<(), plugin_editor_api::PluginError> {
-        // Get current file path from text editor and reload it
-        let current_file = self
-            .text_editor
-            .update(cx, |editor, _cx| editor.current_file_path());
+        // Re-read the current file from disk, which also resets the dirty flag
+        let reloaded = self
+            .active_editor()
+            .update(cx, |editor, cx| editor.reload_current_file(window, cx));
 
-        if let Some(file_path) = current_file {
-            self.text_editor.update(cx, |editor, cx| {
-                editor.open_file(file_path, window, cx);
+        if reloaded {
+            Ok(())
+        } else {
+            Err(plugin_editor_api::PluginError::Other {
+                message: "No file currently open".into(),
+            })
+        }
+    }
+
+    /// Release what the editor holds outside itself before it goes away: its
+    /// language servers and file watches, and rust-analyzer's copies of its
+    /// files. Unsaved changes are dropped, so ask about them first.
+    ///
+    /// Servers it was the last to use are stopped in the background.
+    pub fn shut_down(&mut self, cx: &mut App) {
+        let unused = self.release(cx);
+        cx.background_spawn(async move { lsp::shut_down_servers(unused) }).detach();
+    }
+
+    /// Close every file as `shut_down` does, returning the servers it was the
+    /// last to use for the caller to stop
+    pub fn release(&mut self, cx: &mut App) -> Vec<Arc<lsp::LanguageServer>> {
+        let mut closed = Vec::new();
+        let mut unused = Vec::new();
+        let editors: Vec<_> = self.editors().chain(std::iter::once(self.diff_editor.clone())).collect();
+        for editor in editors {
+            let (files, servers) = editor.update(cx, |editor, cx| editor.shut_down(cx));
+            closed.extend(files);
+            unused.extend(servers);
+        }
+        closed.sort();
+        closed.dedup();
+        if let Some(analyzer) = &self.rust_analyzer {
+            analyzer.update(cx, |analyzer, _cx| {
+                for path in &closed {
+                    if let Err(e) = analyzer.did_close_file(path) {
+                        tracing::error!("⚠️  Failed to notify rust-analyzer of file close: {}", e);
+                    }
+                }
             });
+        }
+        tracing::debug!("🔌 Script editor shut down, closed {} files", closed.len());
+        unused
+    }
+
+    /// Call `callback` with each event the editor reports: opened, saved and
+    /// closed files, the dirty state, edits and cursor moves
+    ///
+    /// Events stop once the returned subscription is dropped, or when the
+    /// editor itself is, so a listener doesn't outlive what it listens to.
+    pub fn plugin_subscribe(
+        this: &Entity<Self>,
+        mut callback: impl FnMut(&TextEditorEvent, &mut App) + 'static,
+        cx: &mut App,
+    ) -> Subscription {
+        cx.subscribe(this, move |_, event: &TextEditorEvent, cx| callback(event, cx))
+    }
+
+    /// Move the cursor to `line` and `column` (both 1-based), clamped to the file
+    pub fn plugin_goto_position(
+        &mut self,
+        line: usize,
+        column: usize,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Result<(), plugin_editor_api::PluginError> {
+        let moved = self
+            .active_editor()
+            .update(cx, |editor, cx| editor.goto_position(line, column, window, cx));
+
+        if moved {
             Ok(())
         } else {
             Err(plugin_editor_api::PluginError::Other {
@@ -585,6 +3034,47 @@ impl ScriptEditor {
             })
         }
     }
+
+    /// The cursor's 1-based line and column, or (1, 1) with no file open
+    pub fn plugin_cursor_position(&self, cx: &App) -> (usize, usize) {
+        self.active_editor().read(cx).cursor_position(cx).unwrap_or((1, 1))
+    }
+
+    /// The active file's text as it is in the editor, saved or not, or an
+    /// empty string with no file open
+    ///
+    /// Like every method here it runs on the main thread; a background task
+    /// has to get back there first, e.g. through `AsyncApp::update`.
+    pub fn plugin_text(&self, cx: &App) -> String {
+        self.active_editor().read(cx).current_text(cx).unwrap_or_default()
+    }
+
+    /// The active file's selected text, or `None` when nothing is selected
+    ///
+    /// Reading the selection needs the window the editor is in, so this takes
+    /// it along with the editor's own context, as from `Entity::update_in`.
+    pub fn plugin_selected_text(&mut self, window: &mut Window, cx: &mut Context<Self>) -> Option<String> {
+        self.active_editor()
+            .update(cx, |editor, cx| editor.selected_text(window, cx))
+    }
+
+    /// Replace the bytes `range` of the active file's text with `text`
+    ///
+    /// The edit is a single undo step and leaves the file unsaved, as if typed.
+    /// Offsets are UTF-8 bytes of the text `plugin_text` returns and have to
+    /// fall on character boundaries; read-only files can't be changed. Needs
+    /// the window like `plugin_selected_text`.
+    pub fn plugin_replace_range(
+        &mut self,
+        range: std::ops::Range<usize>,
+        text: &str,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Result<(), plugin_editor_api::PluginError> {
+        self.active_editor()
+            .update(cx, |editor, cx| editor.replace_range(range, text, window, cx))
+            .map_err(|message| plugin_editor_api::PluginError::Other { message })
+    }
 }
 
 impl Focusable for ScriptEditor {
@@ -599,7 +3089,7 @@ impl EventEmitter<text_editor::TextEditorEvent> for ScriptEditor {}
 impl ScriptEditor {
     /// Synchronize scroll positions between left and right editors in diff mode
     fn sync_diff_editor_scrolls(&mut self, cx: &mut Context<Self>) {
-        if self.mode != ScriptEditorMode::Diff {
+        if self.mode != ScriptEditorMode::Diff || self.diff_layout == DiffLayout::Inline {
             return;
         }
 
@@ -637,6 +3127,72 @@ impl ScriptEditor {
     }
 }
 
+impl ScriptEditor {
+    fn render_sidebar_tabs(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        TabBar::new("sidebar-tabs")
+            .w_full()
+            .border_b_1()
+            .border_color(cx.theme().border)
+            .selected_index(match self.sidebar_view {
+                SidebarView::Explorer => 0,
+                SidebarView::References => 1,
+                SidebarView::Outline => 2,
+                SidebarView::Problems => 3,
+                SidebarView::Search => 4,
+                SidebarView::Bookmarks => 5,
+                SidebarView::CallHierarchy => 6,
+                SidebarView::TypeHierarchy => 7,
+            })
+            .on_click(cx.listener(|this, ix: &usize, _window, cx| {
+                let view = match *ix {
+                    0 => SidebarView::Explorer,
+                    1 => SidebarView::References,
+                    2 => SidebarView::Outline,
+                    3 => SidebarView::Problems,
+                    4 => SidebarView::Search,
+                    5 => SidebarView::Bookmarks,
+                    6 => SidebarView::CallHierarchy,
+                    _ => SidebarView::TypeHierarchy,
+                };
+                this.set_sidebar_view(view, cx);
+            }))
+            .child(Tab::new("Explorer"))
+            .child(Tab::new("References"))
+            .child(Tab::new("Outline"))
+            .child(Tab::new("Problems"))
+            .child(Tab::new("Search"))
+            .child(Tab::new("Bookmarks"))
+            .child(Tab::new("Calls"))
+            .child(Tab::new("Types"))
+    }
+
+    /// One split pane; clicking anywhere in it makes it the focused pane
+    fn render_pane(
+        &self,
+        pane: Pane,
+        editor: Entity<TextEditor>,
+        cx: &mut Context<Self>,
+    ) -> impl IntoElement {
+        let border_color = if pane == self.active_pane {
+            cx.theme().accent
+        } else {
+            cx.theme().border
+        };
+        div()
+            .size_full()
+            .border_1()
+            .border_color(border_color)
+            .capture_any_mouse_down(cx.listener(move |this, _event: &MouseDownEvent, _window, cx| {
+                if this.active_pane != pane {
+                    this.active_pane = pane;
+                    this.refresh_outline(cx);
+                    cx.notify();
+                }
+            }))
+            .child(editor)
+    }
+}
+
 impl Render for ScriptEditor {
     fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
         // Synchronize scroll positions in diff mode
@@ -659,17 +3215,57 @@ impl Render for ScriptEditor {
         }
 
         let editor_panel = match self.mode {
-            ScriptEditorMode::Normal => {
-                // Single editor
-                div()
+            ScriptEditorMode::Normal => match &self.split {
+                None => div()
                     .size_full()
                     .child(self.text_editor.clone())
-                    .into_any_element()
-            }
+                    .into_any_element(),
+                Some(split) => {
+                    let primary = self.render_pane(Pane::Primary, self.text_editor.clone(), cx);
+                    let secondary = self.render_pane(Pane::Secondary, split.editor.clone(), cx);
+                    match split.axis {
+                        Axis::Horizontal => h_resizable("editor-split")
+                            .state(split.resizable_state.clone())
+                            .child(resizable_panel().child(primary))
+                            .child(resizable_panel().child(secondary))
+                            .into_any_element(),
+                        Axis::Vertical => v_resizable("editor-split")
+                            .state(split.resizable_state.clone())
+                            .child(resizable_panel().child(primary))
+                            .child(resizable_panel().child(secondary))
+                            .into_any_element(),
+                    }
+                }
+            },
+            ScriptEditorMode::Hex => div()
+                .size_full()
+                .children(self.hex_view.as_ref().map(|session| session.view.clone()))
+                .into_any_element(),
+            ScriptEditorMode::Diff if self.diff_layout == DiffLayout::Inline => v_flex()
+                .size_full()
+                .child(self.render_diff_toolbar(cx))
+                .child(
+                    div().px_3().py_2().border_b_1().border_color(cx.theme().border).child(
+                        div()
+                            .text_xs()
+                            .font_semibold()
+                            .text_color(cx.theme().muted_foreground)
+                            .child("BEFORE → AFTER (Unified)"),
+                    ),
+                )
+                .child(
+                    div()
+                        .flex_1()
+                        .size_full()
+                        .bg(cx.theme().background)
+                        .child(self.text_editor.clone()),
+                )
+                .into_any_element(),
             ScriptEditorMode::Diff => {
                 // Side-by-side editors with headers
                 v_flex()
                     .size_full()
+                    .child(self.render_diff_toolbar(cx))
                     .child(
                         // Headers: BEFORE | AFTER
                         h_flex()
@@ -730,13 +3326,100 @@ impl Render for ScriptEditor {
                     .into_any_element()
             }
         };
+        let bottom_panel = match self.bottom_panel.filter(|_| self.mode == ScriptEditorMode::Normal) {
+            Some(BottomPanel::Output) => Some(self.output_panel.clone().into_any_element()),
+            Some(BottomPanel::Terminal) => Some(self.terminal_panel.clone().into_any_element()),
+            None => None,
+        };
+        let editor_panel = match bottom_panel {
+            Some(bottom_panel) => v_resizable("editor-bottom-split")
+                .state(self.bottom_resizable_state.clone())
+                .child(resizable_panel().child(editor_panel))
+                .child(resizable_panel().size(px(240.)).size_range(px(80.)..px(600.)).child(bottom_panel))
+                .into_any_element(),
+            None => editor_panel,
+        };
+
+        let command_palette = self.command_palette.as_ref().map(|session| {
+            div()
+                .absolute()
+                .top_10()
+                .left_0()
+                .right_0()
+                .flex()
+                .justify_center()
+                .child(session.palette.clone())
+        });
+        let symbol_picker = self.symbol_picker.as_ref().map(|session| {
+            div()
+                .absolute()
+                .top_10()
+                .left_0()
+                .right_0()
+                .flex()
+                .justify_center()
+                .child(session.picker.clone())
+        });
+        let quick_open = self.quick_open.as_ref().map(|session| {
+            div()
+                .absolute()
+                .top_10()
+                .left_0()
+                .right_0()
+                .flex()
+                .justify_center()
+                .child(session.quick_open.clone())
+        });
 
         h_flex()
+            .relative()
             .size_full()
             .bg(cx.theme().background)
             .key_context("ScriptEditor")
+            .track_focus(&self.focus_handle)
             .on_action(cx.listener(Self::save_current_file))
             .on_action(cx.listener(Self::close_current_file))
+            .on_action(cx.listener(Self::go_to_definition))
+            .on_action(cx.listener(Self::find_all_references))
+            .on_action(cx.listener(Self::show_call_hierarchy))
+            .on_action(cx.listener(Self::show_type_hierarchy))
+            .on_action(cx.listener(Self::reveal_in_file_explorer))
+            .on_action(cx.listener(Self::copy_absolute_path))
+            .on_action(cx.listener(Self::copy_relative_path))
+            .on_action(cx.listener(Self::open_containing_folder))
+            .on_action(cx.listener(Self::format_document))
+            .on_action(cx.listener(Self::rename_symbol))
+            .on_action(cx.listener(Self::show_code_actions))
+            .on_action(cx.listener(Self::toggle_find))
+            .on_action(cx.listener(Self::toggle_replace))
+            .on_action(cx.listener(Self::select_next_occurrence))
+            .on_action(cx.listener(Self::collapse_cursors))
+            .on_action(cx.listener(Self::expand_selection))
+            .on_action(cx.listener(Self::shrink_selection))
+            .on_action(cx.listener(Self::toggle_line_comment))
+            .on_action(cx.listener(Self::toggle_block_comment))
+            .on_action(cx.listener(|this, _: &MoveLineUp, window, cx| this.move_lines(true, window, cx)))
+            .on_action(cx.listener(|this, _: &MoveLineDown, window, cx| this.move_lines(false, window, cx)))
+            .on_action(cx.listener(|this, _: &DuplicateLineUp, window, cx| this.duplicate_lines(false, window, cx)))
+            .on_action(cx.listener(|this, _: &DuplicateLineDown, window, cx| this.duplicate_lines(true, window, cx)))
+            .on_action(cx.listener(|this, _: &ColumnSelectUp, window, cx| this.extend_box_selection(-1, 0, window, cx)))
+            .on_action(cx.listener(|this, _: &ColumnSelectDown, window, cx| this.extend_box_selection(1, 0, window, cx)))
+            .on_action(cx.listener(|this, _: &ColumnSelectLeft, window, cx| this.extend_box_selection(0, -1, window, cx)))
+            .on_action(cx.listener(|this, _: &ColumnSelectRight, window, cx| this.extend_box_selection(0, 1, window, cx)))
+            .on_action(cx.listener(Self::fold_all))
+            .on_action(cx.listener(Self::unfold_all))
+            .on_action(cx.listener(Self::next_tab))
+            .on_action(cx.listener(Self::previous_tab))
+            .on_action(cx.listener(Self::split_right))
+            .on_action(cx.listener(Self::split_down))
+            .on_action(cx.listener(Self::close_pane))
+            .on_action(cx.listener(Self::jump_to_matching_bracket))
+            .on_action(cx.listener(Self::toggle_outline))
+            .on_action(cx.listener(Self::show_go_to_line))
+            .on_action(cx.listener(Self::toggle_command_palette))
+            .on_action(cx.listener(Self::go_to_workspace_symbol))
+            .on_action(cx.listener(Self::toggle_quick_open))
+            .on_action(cx.listener(Self::run_command_action))
             .child(
                 h_resizable("script-editor-horizontal")
                     .state(self.horizontal_resizable_state.clone())
@@ -745,15 +3428,28 @@ impl Render for ScriptEditor {
                             .size(px(300.))
                             .size_range(px(200.)..px(500.))
                             .child(
-                                div()
+                                v_flex()
                                     .size_full()
                                     .bg(cx.theme().sidebar)
                                     .border_r_1()
                                     .border_color(cx.theme().border)
-                                    .child(self.file_explorer.clone()),
+                                    .child(self.render_sidebar_tabs(cx))
+                                    .child(div().flex_1().min_h_0().child(match self.sidebar_view {
+                                        SidebarView::Explorer => self.file_explorer.clone().into_any_element(),
+                                        SidebarView::References => self.references_panel.clone().into_any_element(),
+                                        SidebarView::Outline => self.outline_panel.clone().into_any_element(),
+                                        SidebarView::Problems => self.problems_panel.clone().into_any_element(),
+                                        SidebarView::Search => self.project_search_panel.clone().into_any_element(),
+                                        SidebarView::Bookmarks => self.bookmarks_panel.clone().into_any_element(),
+                                        SidebarView::CallHierarchy => self.call_hierarchy_panel.clone().into_any_element(),
+                                        SidebarView::TypeHierarchy => self.type_hierarchy_panel.clone().into_any_element(),
+                                    })),
                             ),
                     )
                     .child(resizable_panel().child(div().size_full().child(editor_panel))),
             )
+            .children(command_palette)
+            .children(symbol_picker)
+            .children(quick_open)
     }
 }
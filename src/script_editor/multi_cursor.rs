@@ -0,0 +1,261 @@
+//! Secondary cursors layered on top of the input's own cursor
+//!
+//! The input owns the primary cursor and applies keystrokes to it. Every edit
+//! and movement it makes is replayed here at the secondary cursors, so all
+//! cursors type, delete and move together.
+//!
+//! A column (box) selection is the same thing: one selection per line of the
+//! box, the primary one on the line the box was dragged to.
+
+use lsp_types::Position;
+use std::ops::Range;
+
+use super::lsp::{edit_bounds, offset_to_position, position_to_offset, remap_range};
+
+/// A cursor with an optional selection, as byte offsets
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Selection {
+    pub anchor: usize,
+    pub head: usize,
+}
+
+impl Selection {
+    pub fn cursor(offset: usize) -> Self {
+        Self {
+            anchor: offset,
+            head: offset,
+        }
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.anchor.min(self.head)..self.anchor.max(self.head)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.anchor == self.head
+    }
+}
+
+/// Edits to replay at the secondary cursors after the primary cursor edited the text
+pub struct ReplayedEdit {
+    /// Byte ranges in the text *after* the primary edit, with their replacement
+    pub edits: Vec<(Range<usize>, String)>,
+    /// Secondary selections once `edits` are applied
+    pub selections: Vec<Selection>,
+}
+
+#[derive(Clone, Default)]
+pub struct MultiCursor {
+    /// Secondary selections, sorted and non-overlapping
+    selections: Vec<Selection>,
+}
+
+impl MultiCursor {
+    pub fn is_active(&self) -> bool {
+        !self.selections.is_empty()
+    }
+
+    pub fn selections(&self) -> &[Selection] {
+        &self.selections
+    }
+
+    pub fn clear(&mut self) {
+        self.selections.clear();
+    }
+
+    pub fn add(&mut self, selection: Selection) {
+        self.selections.push(selection);
+        self.merge_overlapping();
+    }
+
+    pub fn set(&mut self, selections: Vec<Selection>) {
+        self.selections = selections;
+        self.merge_overlapping();
+    }
+
+    /// Drop the secondary cursor that coincides with the primary one
+    pub fn remove_at(&mut self, offset: usize) {
+        self.selections
+            .retain(|selection| !(selection.range().contains(&offset) || selection.head == offset));
+    }
+
+    /// Sort selections and merge the ones that touch or overlap
+    fn merge_overlapping(&mut self) {
+        self.selections.sort_by_key(|selection| selection.range().start);
+        let mut merged: Vec<Selection> = Vec::with_capacity(self.selections.len());
+        for selection in self.selections.drain(..) {
+            match merged.last_mut() {
+                Some(last) if selection.range().start <= last.range().end => {
+                    let start = last.range().start;
+                    let end = last.range().end.max(selection.range().end);
+                    *last = Selection { anchor: start, head: end };
+                }
+                _ => merged.push(selection),
+            }
+        }
+        self.selections = merged;
+    }
+
+    /// Work out how the secondary cursors repeat an edit the primary cursor just made.
+    ///
+    /// `cursor` is the primary cursor in `old_text` before the edit. A selection
+    /// is replaced by the typed text; a bare cursor repeats the same number of
+    /// bytes deleted before/after it, clamped to character boundaries.
+    pub fn replay_edit(&self, old_text: &str, new_text: &str, cursor: usize) -> ReplayedEdit {
+        let edit = edit_bounds(old_text, new_text);
+        let (start, old_end, new_end) = edit;
+        let inserted = &new_text[start..new_end];
+        let deleted_before = cursor.saturating_sub(start);
+        let deleted_after = old_end.saturating_sub(cursor);
+
+        let mut edits = Vec::new();
+        for selection in &self.selections {
+            let range = if selection.is_empty() {
+                let mut from = selection.head.saturating_sub(deleted_before);
+                while !old_text.is_char_boundary(from) {
+                    from -= 1;
+                }
+                let mut to = (selection.head + deleted_after).min(old_text.len());
+                while !old_text.is_char_boundary(to) {
+                    to += 1;
+                }
+                from..to
+            } else {
+                selection.range()
+            };
+            // Cursors inside the primary edit were swallowed by it
+            if let Some(mapped) = remap_range(range, edit) {
+                edits.push((mapped, inserted.to_string()));
+            }
+        }
+
+        let mut selections = Vec::with_capacity(edits.len());
+        let mut shift: isize = 0;
+        for (range, text) in &edits {
+            let start = (range.start as isize + shift) as usize;
+            selections.push(Selection::cursor(start + text.len()));
+            shift += text.len() as isize - range.len() as isize;
+        }
+
+        ReplayedEdit { edits, selections }
+    }
+
+    /// Move every secondary cursor the way the primary cursor moved from `from` to `to`
+    ///
+    /// Horizontal moves shift by the same number of columns; vertical moves
+    /// keep each cursor's column (clamped to the target line).
+    pub fn replay_movement(&mut self, text: &str, from: usize, to: usize) {
+        let from = offset_to_position(text, from);
+        let to = offset_to_position(text, to);
+        let line_delta = to.line as i64 - from.line as i64;
+        let column_delta = to.character as i64 - from.character as i64;
+
+        for selection in &mut self.selections {
+            let mut position = offset_to_position(text, selection.head);
+            if line_delta == 0 {
+                position.character = (position.character as i64 + column_delta).max(0) as u32;
+            } else {
+                position.line = (position.line as i64 + line_delta).max(0) as u32;
+            }
+            *selection = Selection::cursor(position_to_offset(text, position));
+        }
+        self.merge_overlapping();
+    }
+}
+
+/// One selection per line of the box spanned by `anchor` and `head`, top to bottom.
+///
+/// Columns count UTF-16 units like every other position. Lines that end
+/// before a column of the box are clamped to their end, so a line shorter
+/// than the whole box gets a bare cursor after its last character.
+pub fn box_selections(text: &str, anchor: Position, head: Position) -> Vec<Selection> {
+    let last_line = text.matches('\n').count() as u32;
+    let lines = anchor.line.min(head.line)..=anchor.line.max(head.line).min(last_line);
+    lines
+        .map(|line| Selection {
+            anchor: position_to_offset(text, Position::new(line, anchor.character)),
+            head: position_to_offset(text, Position::new(line, head.character)),
+        })
+        .collect()
+}
+
+/// Range of the next occurrence of `needle` after `after`, wrapping around,
+/// that is not already covered by `taken`
+pub fn next_occurrence(
+    text: &str,
+    needle: &str,
+    after: usize,
+    taken: &[Range<usize>],
+) -> Option<Range<usize>> {
+    if needle.is_empty() {
+        return None;
+    }
+    let after = after.min(text.len());
+    let later = text[after..].match_indices(needle).map(|(ix, _)| after + ix);
+    let earlier = text[..after].match_indices(needle).map(|(ix, _)| ix);
+    later
+        .chain(earlier)
+        .map(|start| start..start + needle.len())
+        .find(|candidate| !taken.iter().any(|range| range.start < candidate.end && candidate.start < range.end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_overlapping() {
+        let mut cursors = MultiCursor::default();
+        cursors.add(Selection { anchor: 4, head: 8 });
+        cursors.add(Selection::cursor(1));
+        cursors.add(Selection { anchor: 10, head: 6 });
+        assert_eq!(
+            cursors.selections(),
+            &[Selection::cursor(1), Selection { anchor: 4, head: 10 }]
+        );
+    }
+
+    #[test]
+    fn test_replay_typing_and_backspace() {
+        let mut cursors = MultiCursor::default();
+        cursors.set(vec![Selection::cursor(4), Selection::cursor(8)]);
+
+        // Primary types "x" at 0
+        let replay = cursors.replay_edit("abc\nabc\nabc", "xabc\nabc\nabc", 0);
+        assert_eq!(replay.edits, vec![(5..5, "x".to_string()), (9..9, "x".to_string())]);
+        assert_eq!(replay.selections, vec![Selection::cursor(6), Selection::cursor(11)]);
+
+        // Primary backspaces at 1
+        cursors.set(vec![Selection::cursor(5), Selection::cursor(9)]);
+        let replay = cursors.replay_edit("abc\nabc\nabc", "bc\nabc\nabc", 1);
+        assert_eq!(replay.edits, vec![(3..4, String::new()), (7..8, String::new())]);
+        assert_eq!(replay.selections, vec![Selection::cursor(3), Selection::cursor(6)]);
+    }
+
+    #[test]
+    fn test_box_selections() {
+        let text = "abcdef\nab\nabcdef";
+        assert_eq!(
+            box_selections(text, Position::new(0, 3), Position::new(2, 5)),
+            vec![
+                Selection { anchor: 3, head: 5 },
+                // Too short for the box: a cursor at its end
+                Selection { anchor: 9, head: 9 },
+                Selection { anchor: 13, head: 15 },
+            ]
+        );
+        // Dragged leftwards and past the last line
+        assert_eq!(
+            box_selections(text, Position::new(1, 2), Position::new(5, 0)),
+            vec![Selection { anchor: 9, head: 7 }, Selection { anchor: 12, head: 10 }]
+        );
+    }
+
+    #[test]
+    fn test_next_occurrence() {
+        let text = "foo bar foo baz foo";
+        assert_eq!(next_occurrence(text, "foo", 3, &[0..3]), Some(8..11));
+        assert_eq!(next_occurrence(text, "foo", 19, &[8..11, 16..19]), Some(0..3));
+        assert_eq!(next_occurrence(text, "foo", 0, &[0..3, 8..11, 16..19]), None);
+    }
+}
@@ -0,0 +1,115 @@
+//! Where the cursor was before each jump, for Go Back and Go Forward
+//!
+//! Only jumps count: going to a definition, a search result or a problem,
+//! or moving the cursor more than a few lines at once. Moving around within
+//! a few lines of a place doesn't add another one.
+
+use std::path::PathBuf;
+
+/// Places remembered at most in each direction
+const MAX_ENTRIES: usize = 100;
+
+/// Cursor moves shorter than this many lines aren't jumps, and places this
+/// close to each other count as one
+pub const NEAR_LINES: u32 = 8;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NavLocation {
+    pub path: PathBuf,
+    /// Zero-based line and UTF-16 column
+    pub line: u32,
+    pub character: u32,
+}
+
+impl NavLocation {
+    pub fn is_near(&self, other: &NavLocation) -> bool {
+        self.path == other.path && self.line.abs_diff(other.line) < NEAR_LINES
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct NavigationHistory {
+    back: Vec<NavLocation>,
+    forward: Vec<NavLocation>,
+}
+
+impl NavigationHistory {
+    /// Remember `from` as the place a jump left. Going forward is no longer
+    /// possible after a new jump.
+    pub fn record(&mut self, from: NavLocation) {
+        self.forward.clear();
+        match self.back.last_mut() {
+            // Still around the last place: keep it current
+            Some(last) if last.is_near(&from) => *last = from,
+            _ => {
+                if self.back.len() == MAX_ENTRIES {
+                    self.back.remove(0);
+                }
+                self.back.push(from);
+            }
+        }
+    }
+
+    /// The place to go back to from `current`, which can then be gone forward to
+    pub fn go_back(&mut self, current: NavLocation) -> Option<NavLocation> {
+        Self::step(&mut self.back, &mut self.forward, current)
+    }
+
+    pub fn go_forward(&mut self, current: NavLocation) -> Option<NavLocation> {
+        Self::step(&mut self.forward, &mut self.back, current)
+    }
+
+    fn step(from: &mut Vec<NavLocation>, to: &mut Vec<NavLocation>, current: NavLocation) -> Option<NavLocation> {
+        // Places next to the cursor aren't worth a step
+        while from.last().is_some_and(|last| last.is_near(&current)) {
+            from.pop();
+        }
+        let target = from.pop()?;
+        if to.len() == MAX_ENTRIES {
+            to.remove(0);
+        }
+        to.push(current);
+        Some(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(path: &str, line: u32) -> NavLocation {
+        NavLocation {
+            path: PathBuf::from(path),
+            line,
+            character: 0,
+        }
+    }
+
+    #[test]
+    fn test_back_and_forward() {
+        let mut history = NavigationHistory::default();
+        history.record(at("/p/a.rs", 10));
+        // Near the last place: replaces it
+        history.record(at("/p/a.rs", 12));
+        history.record(at("/p/b.rs", 40));
+
+        assert_eq!(history.go_back(at("/p/c.rs", 5)), Some(at("/p/b.rs", 40)));
+        assert_eq!(history.go_back(at("/p/b.rs", 40)), Some(at("/p/a.rs", 12)));
+        assert_eq!(history.go_back(at("/p/a.rs", 12)), None);
+        assert_eq!(history.go_forward(at("/p/a.rs", 12)), Some(at("/p/b.rs", 40)));
+
+        // A new jump drops the way forward
+        history.record(at("/p/b.rs", 100));
+        assert_eq!(history.go_forward(at("/p/d.rs", 0)), None);
+    }
+
+    #[test]
+    fn test_capped() {
+        let mut history = NavigationHistory::default();
+        for line in 0..MAX_ENTRIES as u32 + 5 {
+            history.record(at("/p/a.rs", line * NEAR_LINES));
+        }
+        assert_eq!(history.back.len(), MAX_ENTRIES);
+        assert_eq!(history.back[0], at("/p/a.rs", 5 * NEAR_LINES));
+    }
+}
@@ -0,0 +1,276 @@
+//! Outline of the current file's symbols as a collapsible tree
+
+use gpui::{prelude::FluentBuilder, *};
+use lsp_types::SymbolKind;
+use std::collections::HashSet;
+use std::ops::Range;
+use std::path::PathBuf;
+use ui::{h_flex, v_flex, ActiveTheme as _, Icon, IconName, StyledExt};
+
+use super::lsp::SymbolNode;
+use super::location::Location;
+
+/// One visible row: a symbol at an index path into the tree
+struct OutlineRow {
+    path: Vec<usize>,
+    has_children: bool,
+}
+
+pub enum OutlinePanelEvent {
+    OpenLocation(Location),
+}
+
+pub struct OutlinePanel {
+    focus_handle: FocusHandle,
+    file: Option<PathBuf>,
+    symbols: Vec<SymbolNode>,
+    /// Index path of the symbols around the cursor
+    cursor_path: Vec<usize>,
+    rows: Vec<OutlineRow>,
+    collapsed: HashSet<Vec<usize>>,
+    scroll_handle: UniformListScrollHandle,
+}
+
+impl EventEmitter<OutlinePanelEvent> for OutlinePanel {}
+
+/// Glyph shown for a symbol kind and whether it is a type-like symbol
+pub fn kind_glyph(kind: SymbolKind) -> (&'static str, bool) {
+    match kind {
+        SymbolKind::MODULE | SymbolKind::NAMESPACE | SymbolKind::PACKAGE => ("M", false),
+        SymbolKind::CLASS | SymbolKind::STRUCT | SymbolKind::OBJECT => ("S", true),
+        SymbolKind::ENUM => ("E", true),
+        SymbolKind::INTERFACE => ("T", true),
+        SymbolKind::TYPE_PARAMETER => ("t", true),
+        SymbolKind::FUNCTION | SymbolKind::METHOD | SymbolKind::CONSTRUCTOR => ("ƒ", false),
+        SymbolKind::FIELD | SymbolKind::PROPERTY | SymbolKind::ENUM_MEMBER | SymbolKind::KEY => ("·", false),
+        SymbolKind::ARRAY => ("[]", false),
+        SymbolKind::CONSTANT => ("C", false),
+        SymbolKind::VARIABLE => ("v", false),
+        _ => ("◦", false),
+    }
+}
+
+impl OutlinePanel {
+    pub fn new(cx: &mut Context<Self>) -> Self {
+        Self {
+            focus_handle: cx.focus_handle(),
+            file: None,
+            symbols: Vec::new(),
+            cursor_path: Vec::new(),
+            rows: Vec::new(),
+            collapsed: HashSet::new(),
+            scroll_handle: UniformListScrollHandle::new(),
+        }
+    }
+
+    /// Show `symbols` of `file`, highlighting the ones at `cursor_path`
+    pub fn set_outline(
+        &mut self,
+        file: Option<PathBuf>,
+        symbols: Vec<SymbolNode>,
+        cursor_path: Vec<usize>,
+        cx: &mut Context<Self>,
+    ) {
+        if self.file == file && self.symbols == symbols && self.cursor_path == cursor_path {
+            return;
+        }
+        if self.file != file {
+            self.file = file;
+            self.collapsed.clear();
+            self.scroll_handle.scroll_to_item(0, ScrollStrategy::Top);
+        }
+        if self.symbols != symbols {
+            self.symbols = symbols;
+            self.rebuild_rows();
+        }
+        if self.cursor_path != cursor_path {
+            self.cursor_path = cursor_path;
+            if let Some(row_ix) = self.rows.iter().position(|row| row.path == self.cursor_path) {
+                self.scroll_handle.scroll_to_item(row_ix, ScrollStrategy::Center);
+            }
+        }
+        cx.notify();
+    }
+
+    fn rebuild_rows(&mut self) {
+        fn push_rows(
+            rows: &mut Vec<OutlineRow>,
+            collapsed: &HashSet<Vec<usize>>,
+            nodes: &[SymbolNode],
+            parent: &[usize],
+        ) {
+            for (ix, node) in nodes.iter().enumerate() {
+                let mut path = parent.to_vec();
+                path.push(ix);
+                let expanded = !collapsed.contains(&path);
+                rows.push(OutlineRow {
+                    path: path.clone(),
+                    has_children: !node.children.is_empty(),
+                });
+                if expanded {
+                    push_rows(rows, collapsed, &node.children, &path);
+                }
+            }
+        }
+        self.rows.clear();
+        push_rows(&mut self.rows, &self.collapsed, &self.symbols, &[]);
+    }
+
+    fn node(&self, path: &[usize]) -> Option<&SymbolNode> {
+        let (&first, rest) = path.split_first()?;
+        let mut node = self.symbols.get(first)?;
+        for &ix in rest {
+            node = node.children.get(ix)?;
+        }
+        Some(node)
+    }
+
+    fn toggle_row(&mut self, path: Vec<usize>, cx: &mut Context<Self>) {
+        if !self.collapsed.remove(&path) {
+            self.collapsed.insert(path);
+        }
+        self.rebuild_rows();
+        cx.notify();
+    }
+
+    fn render_rows(&mut self, range: Range<usize>, cx: &mut Context<Self>) -> Vec<AnyElement> {
+        range
+            .filter_map(|row_ix| {
+                let row = self.rows.get(row_ix)?;
+                let node = self.node(&row.path)?;
+                Some(self.render_row(row_ix, row, node, cx))
+            })
+            .collect()
+    }
+
+    fn render_row(&self, row_ix: usize, row: &OutlineRow, node: &SymbolNode, cx: &mut Context<Self>) -> AnyElement {
+        let is_cursor_symbol = row.path == self.cursor_path;
+        let collapsed = self.collapsed.contains(&row.path);
+        let (glyph, is_type) = kind_glyph(node.kind);
+        let depth = row.path.len() - 1;
+        let toggle_path = row.path.clone();
+        let file = self.file.clone();
+        let position = node.selection_start;
+
+        h_flex()
+            .id(("outline-row", row_ix))
+            .h(px(24.))
+            .pl(px(8. + depth as f32 * 12.))
+            .pr_2()
+            .gap_1()
+            .cursor_pointer()
+            .when(is_cursor_symbol, |row| row.bg(cx.theme().accent.opacity(0.2)))
+            .hover(|style| style.bg(cx.theme().accent.opacity(0.1)))
+            .child(
+                div()
+                    .id(("outline-toggle", row_ix))
+                    .w(px(12.))
+                    .when(row.has_children, |toggle| {
+                        toggle
+                            .child(
+                                Icon::new(if collapsed { IconName::ChevronRight } else { IconName::ChevronDown })
+                                    .size_3()
+                                    .text_color(cx.theme().muted_foreground),
+                            )
+                            .on_click(cx.listener(move |this, _, _window, cx| {
+                                cx.stop_propagation();
+                                this.toggle_row(toggle_path.clone(), cx);
+                            }))
+                    }),
+            )
+            .child(
+                div()
+                    .w(px(14.))
+                    .text_xs()
+                    .font_semibold()
+                    .text_color(if is_type { cx.theme().warning } else { cx.theme().info })
+                    .child(glyph),
+            )
+            .child(
+                div()
+                    .flex_1()
+                    .overflow_hidden()
+                    .whitespace_nowrap()
+                    .text_ellipsis()
+                    .text_xs()
+                    .font_family("JetBrains Mono")
+                    .text_color(cx.theme().foreground)
+                    .child(node.name.clone()),
+            )
+            .on_click(cx.listener(move |_this, _, _window, cx| {
+                if let Some(path) = file.clone() {
+                    cx.emit(OutlinePanelEvent::OpenLocation(Location {
+                        path,
+                        line: position.line,
+                        character: position.character,
+                    }));
+                }
+            }))
+            .into_any_element()
+    }
+}
+
+impl Focusable for OutlinePanel {
+    fn focus_handle(&self, _cx: &App) -> FocusHandle {
+        self.focus_handle.clone()
+    }
+}
+
+impl Render for OutlinePanel {
+    fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let title = self
+            .file
+            .as_ref()
+            .and_then(|path| path.file_name())
+            .map(|name| format!("Outline: {}", name.to_string_lossy()))
+            .unwrap_or_else(|| "Outline".to_string());
+
+        v_flex()
+            .size_full()
+            .track_focus(&self.focus_handle)
+            .child(
+                // Header
+                div()
+                    .w_full()
+                    .px_4()
+                    .py_3()
+                    .border_b_1()
+                    .border_color(cx.theme().border)
+                    .text_sm()
+                    .font_semibold()
+                    .text_color(cx.theme().foreground)
+                    .child(title),
+            )
+            .child(
+                div()
+                    .flex_1()
+                    .min_h_0()
+                    .when(self.rows.is_empty(), |content| {
+                        content.child(
+                            div()
+                                .p_4()
+                                .text_xs()
+                                .text_color(cx.theme().muted_foreground)
+                                .child(if self.file.is_some() {
+                                    "No symbols found in this file"
+                                } else {
+                                    "Open a file to see its outline"
+                                }),
+                        )
+                    })
+                    .when(!self.rows.is_empty(), |content| {
+                        content.child(
+                            uniform_list(
+                                "outline-list",
+                                self.rows.len(),
+                                cx.processor(|this, range: Range<usize>, _window, cx| {
+                                    this.render_rows(range, cx)
+                                }),
+                            )
+                            .track_scroll(self.scroll_handle.clone())
+                            .size_full(),
+                        )
+                    }),
+            )
+    }
+}
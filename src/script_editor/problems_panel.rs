@@ -0,0 +1,473 @@
+//! Diagnostics of every open file, grouped by file
+
+use gpui::{prelude::FluentBuilder, *};
+use lsp_types::DiagnosticSeverity;
+use std::collections::HashSet;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use ui::{
+    button::{Button, ButtonVariants as _},
+    h_flex,
+    input::{InputEvent, InputState, TextInput},
+    v_flex, ActiveTheme as _, Icon, IconName, Sizable as _, StyledExt,
+};
+use super::location::Location;
+
+/// A diagnostic of an open file
+#[derive(Clone, Debug, PartialEq)]
+pub struct Problem {
+    pub path: PathBuf,
+    /// Zero-based line
+    pub line: u32,
+    /// Zero-based UTF-16 column
+    pub character: u32,
+    pub severity: DiagnosticSeverity,
+    pub message: String,
+    /// The server or check that reported it, e.g. `rustc`
+    pub source: Option<String>,
+    pub code: Option<String>,
+}
+
+/// Which problems the list shows
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProblemFilter {
+    pub errors: bool,
+    pub warnings: bool,
+    /// Information and hints
+    pub infos: bool,
+    /// Shown only when the message, source or code contains it, ignoring case
+    pub query: String,
+}
+
+impl Default for ProblemFilter {
+    fn default() -> Self {
+        Self {
+            errors: true,
+            warnings: true,
+            infos: true,
+            query: String::new(),
+        }
+    }
+}
+
+impl ProblemFilter {
+    fn matches(&self, problem: &Problem) -> bool {
+        let shown = match problem.severity {
+            DiagnosticSeverity::ERROR => self.errors,
+            DiagnosticSeverity::WARNING => self.warnings,
+            _ => self.infos,
+        };
+        if !shown {
+            return false;
+        }
+        let query = self.query.trim().to_lowercase();
+        query.is_empty()
+            || [Some(&problem.message), problem.source.as_ref(), problem.code.as_ref()]
+                .into_iter()
+                .flatten()
+                .any(|text| text.to_lowercase().contains(&query))
+    }
+}
+
+/// How problems are ordered within a file
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ProblemSort {
+    /// Errors first, then warnings, ..., each by position
+    #[default]
+    Severity,
+    Position,
+}
+
+/// A file's problems, as listed
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProblemGroup {
+    pub path: PathBuf,
+    pub problems: Vec<Problem>,
+}
+
+/// Problems passing `filter`, grouped by file in path order and sorted within each
+pub fn group_problems(problems: &[Problem], filter: &ProblemFilter, sort: ProblemSort) -> Vec<ProblemGroup> {
+    let mut groups: Vec<ProblemGroup> = Vec::new();
+    for problem in problems.iter().filter(|problem| filter.matches(problem)) {
+        match groups.iter_mut().find(|group| group.path == problem.path) {
+            Some(group) => group.problems.push(problem.clone()),
+            None => groups.push(ProblemGroup {
+                path: problem.path.clone(),
+                problems: vec![problem.clone()],
+            }),
+        }
+    }
+    groups.sort_by(|a, b| a.path.cmp(&b.path));
+    for group in &mut groups {
+        match sort {
+            // ERROR is 1, HINT 4
+            ProblemSort::Severity => group.problems.sort_by_key(|problem| (problem.severity, problem.line, problem.character)),
+            ProblemSort::Position => group.problems.sort_by_key(|problem| (problem.line, problem.character)),
+        }
+    }
+    groups
+}
+
+/// One visible row of the list: a file header or a problem beneath it
+#[derive(Clone, Copy)]
+enum ProblemRow {
+    File(usize),
+    Entry(usize, usize),
+}
+
+pub enum ProblemsPanelEvent {
+    OpenLocation(Location),
+}
+
+pub struct ProblemsPanel {
+    focus_handle: FocusHandle,
+    problems: Vec<Problem>,
+    filter: ProblemFilter,
+    sort: ProblemSort,
+    groups: Vec<ProblemGroup>,
+    rows: Vec<ProblemRow>,
+    /// Files whose problems are hidden, kept as the problems change
+    collapsed: HashSet<PathBuf>,
+    project_root: Option<PathBuf>,
+    search_input: Entity<InputState>,
+    scroll_handle: UniformListScrollHandle,
+    _search_subscription: Subscription,
+}
+
+impl EventEmitter<ProblemsPanelEvent> for ProblemsPanel {}
+
+impl ProblemsPanel {
+    pub fn new(window: &mut Window, cx: &mut Context<Self>) -> Self {
+        let search_input = cx.new(|cx| InputState::new(window, cx).placeholder("Filter problems"));
+        let search_subscription = cx.subscribe_in(&search_input, window, |this, input, event: &InputEvent, _window, cx| {
+            if let InputEvent::Change = event {
+                this.filter.query = input.read(cx).value().to_string();
+                this.rebuild(cx);
+            }
+        });
+        Self {
+            focus_handle: cx.focus_handle(),
+            problems: Vec::new(),
+            filter: ProblemFilter::default(),
+            sort: ProblemSort::default(),
+            groups: Vec::new(),
+            rows: Vec::new(),
+            collapsed: HashSet::new(),
+            project_root: None,
+            search_input,
+            scroll_handle: UniformListScrollHandle::new(),
+            _search_subscription: search_subscription,
+        }
+    }
+
+    /// Used to display paths relative to the project
+    pub fn set_project_root(&mut self, root: Option<PathBuf>) {
+        self.project_root = root;
+    }
+
+    pub fn set_problems(&mut self, problems: Vec<Problem>, cx: &mut Context<Self>) {
+        if problems != self.problems {
+            self.problems = problems;
+            self.rebuild(cx);
+        }
+    }
+
+    fn rebuild(&mut self, cx: &mut Context<Self>) {
+        self.groups = group_problems(&self.problems, &self.filter, self.sort);
+        self.rows.clear();
+        for (group_ix, group) in self.groups.iter().enumerate() {
+            self.rows.push(ProblemRow::File(group_ix));
+            if !self.collapsed.contains(&group.path) {
+                self.rows
+                    .extend((0..group.problems.len()).map(|entry_ix| ProblemRow::Entry(group_ix, entry_ix)));
+            }
+        }
+        cx.notify();
+    }
+
+    fn toggle_group(&mut self, group_ix: usize, cx: &mut Context<Self>) {
+        let path = self.groups[group_ix].path.clone();
+        if !self.collapsed.remove(&path) {
+            self.collapsed.insert(path);
+        }
+        self.rebuild(cx);
+    }
+
+    /// Errors, warnings, and the rest
+    fn counts(&self) -> (usize, usize, usize) {
+        let count = |severity| self.problems.iter().filter(|problem| problem.severity == severity).count();
+        let (errors, warnings) = (count(DiagnosticSeverity::ERROR), count(DiagnosticSeverity::WARNING));
+        (errors, warnings, self.problems.len() - errors - warnings)
+    }
+
+    fn display_path<'a>(&self, path: &'a Path) -> std::borrow::Cow<'a, str> {
+        self.project_root
+            .as_ref()
+            .and_then(|root| path.strip_prefix(root).ok())
+            .unwrap_or(path)
+            .to_string_lossy()
+    }
+
+    pub fn severity_glyph(severity: DiagnosticSeverity, cx: &App) -> (&'static str, Hsla) {
+        match severity {
+            DiagnosticSeverity::ERROR => ("⊗", cx.theme().danger),
+            DiagnosticSeverity::WARNING => ("⚠", cx.theme().warning),
+            DiagnosticSeverity::INFORMATION => ("ⓘ", cx.theme().info),
+            _ => ("…", cx.theme().muted_foreground),
+        }
+    }
+
+    fn render_rows(&mut self, range: Range<usize>, cx: &mut Context<Self>) -> Vec<AnyElement> {
+        range
+            .filter_map(|row_ix| {
+                let row = *self.rows.get(row_ix)?;
+                Some(match row {
+                    ProblemRow::File(group_ix) => self.render_file_row(row_ix, group_ix, cx),
+                    ProblemRow::Entry(group_ix, entry_ix) => self.render_entry_row(row_ix, group_ix, entry_ix, cx),
+                })
+            })
+            .collect()
+    }
+
+    fn render_file_row(&self, row_ix: usize, group_ix: usize, cx: &mut Context<Self>) -> AnyElement {
+        let group = &self.groups[group_ix];
+        let collapsed = self.collapsed.contains(&group.path);
+
+        h_flex()
+            .id(("problem-file", row_ix))
+            .h(px(24.))
+            .px_2()
+            .gap_1()
+            .cursor_pointer()
+            .hover(|style| style.bg(cx.theme().accent.opacity(0.1)))
+            .child(
+                Icon::new(if collapsed { IconName::ChevronRight } else { IconName::ChevronDown })
+                    .size_3()
+                    .text_color(cx.theme().muted_foreground),
+            )
+            .child(
+                div()
+                    .flex_1()
+                    .overflow_hidden()
+                    .text_ellipsis()
+                    .text_xs()
+                    .font_semibold()
+                    .text_color(cx.theme().foreground)
+                    .child(self.display_path(&group.path).to_string()),
+            )
+            .child(
+                div()
+                    .text_xs()
+                    .text_color(cx.theme().muted_foreground)
+                    .child(group.problems.len().to_string()),
+            )
+            .on_click(cx.listener(move |this, _, _window, cx| {
+                this.toggle_group(group_ix, cx);
+            }))
+            .into_any_element()
+    }
+
+    fn render_entry_row(&self, row_ix: usize, group_ix: usize, entry_ix: usize, cx: &mut Context<Self>) -> AnyElement {
+        let problem = &self.groups[group_ix].problems[entry_ix];
+        let path = problem.path.clone();
+        let (line, character) = (problem.line, problem.character);
+        let (glyph, color) = Self::severity_glyph(problem.severity, cx);
+        let origin = match (&problem.source, &problem.code) {
+            (Some(source), Some(code)) => format!("{}({}) ", source, code),
+            (Some(source), None) => format!("{} ", source),
+            (None, Some(code)) => format!("{} ", code),
+            (None, None) => String::new(),
+        };
+
+        h_flex()
+            .id(("problem-entry", row_ix))
+            .h(px(24.))
+            .pl_6()
+            .pr_2()
+            .gap_2()
+            .cursor_pointer()
+            .hover(|style| style.bg(cx.theme().accent.opacity(0.1)))
+            .child(div().text_xs().text_color(color).child(glyph))
+            .child(
+                div()
+                    .flex_1()
+                    .overflow_hidden()
+                    .whitespace_nowrap()
+                    .text_ellipsis()
+                    .text_xs()
+                    .text_color(cx.theme().foreground)
+                    .child(problem.message.lines().next().unwrap_or_default().to_string()),
+            )
+            .child(
+                div()
+                    .text_xs()
+                    .whitespace_nowrap()
+                    .text_color(cx.theme().muted_foreground)
+                    .child(format!("{}[{}:{}]", origin, line + 1, character + 1)),
+            )
+            .on_click(cx.listener(move |_this, _, _window, cx| {
+                cx.emit(ProblemsPanelEvent::OpenLocation(Location {
+                    path: path.clone(),
+                    line,
+                    character,
+                }));
+            }))
+            .into_any_element()
+    }
+
+    fn render_toggle(
+        &self,
+        id: &'static str,
+        label: String,
+        selected: bool,
+        toggle: fn(&mut ProblemFilter),
+        cx: &mut Context<Self>,
+    ) -> impl IntoElement {
+        Button::new(id)
+            .label(label)
+            .ghost()
+            .xsmall()
+            .selected(selected)
+            .on_click(cx.listener(move |this, _, _window, cx| {
+                toggle(&mut this.filter);
+                this.rebuild(cx);
+            }))
+    }
+}
+
+impl Focusable for ProblemsPanel {
+    fn focus_handle(&self, _cx: &App) -> FocusHandle {
+        self.focus_handle.clone()
+    }
+}
+
+impl Render for ProblemsPanel {
+    fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let (errors, warnings, infos) = self.counts();
+        let sort = self.sort;
+
+        v_flex()
+            .size_full()
+            .track_focus(&self.focus_handle)
+            .child(
+                // Header
+                v_flex()
+                    .w_full()
+                    .px_2()
+                    .py_2()
+                    .gap_1()
+                    .border_b_1()
+                    .border_color(cx.theme().border)
+                    .child(TextInput::new(&self.search_input).small())
+                    .child(
+                        h_flex()
+                            .gap_1()
+                            .child(self.render_toggle("problems_errors", format!("⊗ {}", errors), self.filter.errors, |filter| filter.errors = !filter.errors, cx))
+                            .child(self.render_toggle("problems_warnings", format!("⚠ {}", warnings), self.filter.warnings, |filter| filter.warnings = !filter.warnings, cx))
+                            .child(self.render_toggle("problems_infos", format!("ⓘ {}", infos), self.filter.infos, |filter| filter.infos = !filter.infos, cx))
+                            .child(div().flex_1())
+                            .child(
+                                Button::new("problems_sort")
+                                    .label(match sort {
+                                        ProblemSort::Severity => "By severity",
+                                        ProblemSort::Position => "By position",
+                                    })
+                                    .tooltip("Change Sort Order")
+                                    .ghost()
+                                    .xsmall()
+                                    .on_click(cx.listener(|this, _, _window, cx| {
+                                        this.sort = match this.sort {
+                                            ProblemSort::Severity => ProblemSort::Position,
+                                            ProblemSort::Position => ProblemSort::Severity,
+                                        };
+                                        this.rebuild(cx);
+                                    })),
+                            ),
+                    ),
+            )
+            .child(
+                div()
+                    .flex_1()
+                    .min_h_0()
+                    .when(self.rows.is_empty(), |content| {
+                        content.child(
+                            div()
+                                .p_4()
+                                .text_xs()
+                                .text_color(cx.theme().muted_foreground)
+                                .child(if self.problems.is_empty() {
+                                    "No problems in open files"
+                                } else {
+                                    "No problems match the filter"
+                                }),
+                        )
+                    })
+                    .when(!self.rows.is_empty(), |content| {
+                        content.child(
+                            uniform_list(
+                                "problems-list",
+                                self.rows.len(),
+                                cx.processor(|this, range: Range<usize>, _window, cx| this.render_rows(range, cx)),
+                            )
+                            .track_scroll(self.scroll_handle.clone())
+                            .size_full(),
+                        )
+                    }),
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn problem(path: &str, line: u32, severity: DiagnosticSeverity, message: &str) -> Problem {
+        Problem {
+            path: PathBuf::from(path),
+            line,
+            character: 0,
+            severity,
+            message: message.to_string(),
+            source: Some("rustc".to_string()),
+            code: None,
+        }
+    }
+
+    #[test]
+    fn test_group_and_sort() {
+        let problems = vec![
+            problem("/p/src/main.rs", 9, DiagnosticSeverity::WARNING, "unused variable"),
+            problem("/p/src/lib.rs", 3, DiagnosticSeverity::HINT, "consider"),
+            problem("/p/src/main.rs", 2, DiagnosticSeverity::ERROR, "mismatched types"),
+            problem("/p/src/main.rs", 5, DiagnosticSeverity::WARNING, "unused import"),
+        ];
+        let groups = group_problems(&problems, &ProblemFilter::default(), ProblemSort::Severity);
+        assert_eq!(groups[0].path, PathBuf::from("/p/src/lib.rs"));
+        let lines: Vec<u32> = groups[1].problems.iter().map(|problem| problem.line).collect();
+        assert_eq!(lines, [2, 5, 9]);
+
+        let groups = group_problems(&problems[..2], &ProblemFilter::default(), ProblemSort::Position);
+        assert_eq!(groups.len(), 2);
+    }
+
+    #[test]
+    fn test_filter_by_severity_and_text() {
+        let problems = vec![
+            problem("/p/a.py", 0, DiagnosticSeverity::ERROR, "Undefined name `x`"),
+            problem("/p/a.py", 1, DiagnosticSeverity::WARNING, "Unused import os"),
+            problem("/p/a.py", 2, DiagnosticSeverity::INFORMATION, "Line too long"),
+        ];
+        let filter = ProblemFilter {
+            infos: false,
+            query: "UNUSED".to_string(),
+            ..ProblemFilter::default()
+        };
+        let groups = group_problems(&problems, &filter, ProblemSort::Severity);
+        assert_eq!(groups[0].problems.len(), 1);
+        assert_eq!(groups[0].problems[0].message, "Unused import os");
+
+        // The source is searched too
+        let filter = ProblemFilter { query: "rustc".to_string(), ..ProblemFilter::default() };
+        assert_eq!(group_problems(&problems, &filter, ProblemSort::Severity)[0].problems.len(), 3);
+    }
+}
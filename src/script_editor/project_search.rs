@@ -0,0 +1,805 @@
+//! Search and replace across the files of the project
+//!
+//! Files are walked with `.gitignore` (and `.ignore`) rules applied, off the
+//! UI thread, and their matches stream into the panel as each file is done.
+//! Open files are searched as they are in the editor, unsaved edits included.
+
+use futures::channel::mpsc::{unbounded, UnboundedSender};
+use gpui::{prelude::FluentBuilder, *};
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use ui::{
+    button::{Button, ButtonVariants as _},
+    h_flex,
+    input::{InputEvent, InputState, TextInput},
+    v_flex, ActiveTheme as _, Icon, IconName, Sizable as _, StyledExt,
+};
+
+use super::line_endings;
+use super::search::{SearchQuery, MAX_MATCHES};
+use super::location::Location;
+
+/// How long typing must pause before the project is searched again
+const SEARCH_DELAY: Duration = Duration::from_millis(300);
+
+/// Lines shown around each match
+const CONTEXT_LINES: u32 = 1;
+
+/// Files larger than this are not searched
+const MAX_FILE_SIZE: u64 = 2 * 1024 * 1024;
+
+/// A match within one line
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchMatch {
+    /// Zero-based line
+    pub line: u32,
+    /// Bytes of the match in the line
+    pub range: Range<usize>,
+}
+
+/// The matches in one file
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileResult {
+    pub path: PathBuf,
+    pub matches: Vec<SearchMatch>,
+    /// Text of the lines with matches and the context lines around them
+    pub lines: BTreeMap<u32, String>,
+}
+
+/// Matches of `regex` in each line of `text`, at most `limit` of them, and
+/// the lines to show for them with `context` lines either side.
+///
+/// Matches don't span lines, as in ripgrep; empty ones are skipped.
+pub fn search_lines(regex: &Regex, text: &str, context: u32, limit: usize) -> (Vec<SearchMatch>, BTreeMap<u32, String>) {
+    let lines: Vec<&str> = text.split('\n').map(|line| line.strip_suffix('\r').unwrap_or(line)).collect();
+    let mut matches = Vec::new();
+    'lines: for (line, text) in lines.iter().enumerate() {
+        for found in regex.find_iter(text).filter(|found| !found.is_empty()) {
+            if matches.len() == limit {
+                break 'lines;
+            }
+            matches.push(SearchMatch {
+                line: line as u32,
+                range: found.range(),
+            });
+        }
+    }
+
+    let last_line = lines.len().saturating_sub(1) as u32;
+    let mut shown = BTreeMap::new();
+    for found in &matches {
+        for line in found.line.saturating_sub(context)..=(found.line + context).min(last_line) {
+            shown.entry(line).or_insert_with(|| lines[line as usize].to_string());
+        }
+    }
+    (matches, shown)
+}
+
+/// Edits replacing every match not in `excluded` (file index, match index),
+/// and how many there are
+pub fn replacement_edit(
+    query: &SearchQuery,
+    regex: &Regex,
+    results: &[FileResult],
+    replace: &str,
+    excluded: &HashSet<(usize, usize)>,
+) -> (lsp_types::WorkspaceEdit, usize) {
+    let mut changes = HashMap::new();
+    let mut count = 0;
+    for (file_ix, file) in results.iter().enumerate() {
+        let Some(uri) = super::lsp::path_to_uri(&file.path) else {
+            continue;
+        };
+        let edits: Vec<lsp_types::TextEdit> = file
+            .matches
+            .iter()
+            .enumerate()
+            .filter(|(match_ix, _)| !excluded.contains(&(file_ix, *match_ix)))
+            .filter_map(|(_, found)| {
+                let text = file.lines.get(&found.line)?;
+                let column = |offset: usize| text[..offset].encode_utf16().count() as u32;
+                Some(lsp_types::TextEdit {
+                    range: lsp_types::Range::new(
+                        lsp_types::Position::new(found.line, column(found.range.start)),
+                        lsp_types::Position::new(found.line, column(found.range.end)),
+                    ),
+                    new_text: query.replacement(regex, text, found.range.clone(), replace),
+                })
+            })
+            .collect();
+        if !edits.is_empty() {
+            count += edits.len();
+            changes.insert(uri, edits);
+        }
+    }
+    let edit = lsp_types::WorkspaceEdit {
+        changes: Some(changes),
+        ..Default::default()
+    };
+    (edit, count)
+}
+
+enum SearchUpdate {
+    File(FileResult),
+    /// `MAX_MATCHES` were found; the rest of the project isn't searched
+    Truncated,
+}
+
+/// Walk `root` and send the matches of each file, stopping at `MAX_MATCHES`
+/// or once nobody is listening
+fn walk_project(root: PathBuf, regex: Regex, buffers: HashMap<PathBuf, String>, sender: UnboundedSender<SearchUpdate>) {
+    let mut remaining = MAX_MATCHES;
+    let walker = ignore::WalkBuilder::new(&root).require_git(false).build();
+    for entry in walker.flatten() {
+        if !entry.file_type().is_some_and(|kind| kind.is_file()) {
+            continue;
+        }
+        let path = entry.into_path();
+        let text = match buffers.get(&path) {
+            Some(text) => text.clone(),
+            None => {
+                if std::fs::metadata(&path).is_ok_and(|metadata| metadata.len() > MAX_FILE_SIZE) {
+                    continue;
+                }
+                // Binary files either aren't UTF-8 or have NULs
+                match std::fs::read_to_string(&path) {
+                    // Buffers only have `\n`, and matches should line up with them
+                    Ok(text) if !text.contains('\0') => line_endings::into_normalized(text),
+                    _ => continue,
+                }
+            }
+        };
+        let (matches, lines) = search_lines(&regex, &text, CONTEXT_LINES, remaining);
+        if matches.is_empty() {
+            continue;
+        }
+        remaining -= matches.len();
+        if sender.unbounded_send(SearchUpdate::File(FileResult { path, matches, lines })).is_err() {
+            return;
+        }
+        if remaining == 0 {
+            let _ = sender.unbounded_send(SearchUpdate::Truncated);
+            return;
+        }
+    }
+}
+
+/// One visible row of the results: a file, one of its matches, or a line around them
+#[derive(Clone, Copy)]
+enum SearchRow {
+    File(usize),
+    Match(usize, usize),
+    Context(usize, u32),
+}
+
+pub enum ProjectSearchPanelEvent {
+    /// The query changed: search again, through `ProjectSearchPanel::search`
+    SearchRequested,
+    OpenLocation(Location),
+    /// Apply the selected replacements
+    Replace(lsp_types::WorkspaceEdit),
+}
+
+pub struct ProjectSearchPanel {
+    focus_handle: FocusHandle,
+    search_input: Entity<InputState>,
+    replace_input: Entity<InputState>,
+    query: SearchQuery,
+    replace_mode: bool,
+    project_root: Option<PathBuf>,
+    /// Compiled query the results were found with
+    regex: Option<Regex>,
+    error: Option<String>,
+    results: Vec<FileResult>,
+    rows: Vec<SearchRow>,
+    collapsed: HashSet<PathBuf>,
+    /// Matches (file index, match index) left out of Replace All
+    excluded: HashSet<(usize, usize)>,
+    searching: bool,
+    truncated: bool,
+    scroll_handle: UniformListScrollHandle,
+    search_task: Option<Task<()>>,
+    _subscriptions: Vec<Subscription>,
+}
+
+impl EventEmitter<ProjectSearchPanelEvent> for ProjectSearchPanel {}
+
+impl ProjectSearchPanel {
+    pub fn new(window: &mut Window, cx: &mut Context<Self>) -> Self {
+        let search_input = cx.new(|cx| InputState::new(window, cx).placeholder("Search"));
+        let replace_input = cx.new(|cx| InputState::new(window, cx).placeholder("Replace"));
+        let subscriptions = vec![
+            cx.subscribe_in(&search_input, window, |this, input, event: &InputEvent, _window, cx| match event {
+                InputEvent::Change => {
+                    this.query.pattern = input.read(cx).value().to_string();
+                    this.schedule_search(cx);
+                }
+                InputEvent::PressEnter { .. } => cx.emit(ProjectSearchPanelEvent::SearchRequested),
+                _ => {}
+            }),
+            // The preview follows the replacement as it's typed
+            cx.subscribe_in(&replace_input, window, |_this, _input, event: &InputEvent, _window, cx| {
+                if let InputEvent::Change = event {
+                    cx.notify();
+                }
+            }),
+        ];
+        Self {
+            focus_handle: cx.focus_handle(),
+            search_input,
+            replace_input,
+            query: SearchQuery::default(),
+            replace_mode: false,
+            project_root: None,
+            regex: None,
+            error: None,
+            results: Vec::new(),
+            rows: Vec::new(),
+            collapsed: HashSet::new(),
+            excluded: HashSet::new(),
+            searching: false,
+            truncated: false,
+            scroll_handle: UniformListScrollHandle::new(),
+            search_task: None,
+            _subscriptions: subscriptions,
+        }
+    }
+
+    pub fn set_project_root(&mut self, root: Option<PathBuf>) {
+        self.project_root = root;
+    }
+
+    /// Focus the search field, starting from `text` when given (e.g. the editor's selection)
+    pub fn focus_search(&mut self, text: Option<String>, window: &mut Window, cx: &mut Context<Self>) {
+        self.search_input.update(cx, |input, cx| {
+            if let Some(text) = text.filter(|text| !text.is_empty() && !text.contains('\n')) {
+                input.set_value(&text, window, cx);
+            }
+            input.focus(window, cx);
+        });
+    }
+
+    /// Search the project for the query, with `buffers` (the text of open
+    /// files) in place of what's on disk. Results stream in as files are searched.
+    pub fn search(&mut self, buffers: HashMap<PathBuf, String>, cx: &mut Context<Self>) {
+        self.results.clear();
+        self.excluded.clear();
+        self.error = None;
+        self.truncated = false;
+        self.regex = None;
+        self.search_task = None;
+        self.searching = false;
+
+        let root = self.project_root.clone();
+        if let (Some(root), false) = (root, self.query.pattern.is_empty()) {
+            match self.query.build() {
+                Ok(regex) => {
+                    self.regex = Some(regex.clone());
+                    self.searching = true;
+                    let (sender, mut updates) = unbounded();
+                    std::thread::spawn(move || walk_project(root, regex, buffers, sender));
+                    self.search_task = Some(cx.spawn(async move |this, cx| {
+                        use futures::StreamExt as _;
+                        while let Some(update) = updates.next().await {
+                            // Take whatever else is ready too, to rebuild the rows once
+                            let mut batch = vec![update];
+                            while let Ok(Some(update)) = updates.try_next() {
+                                batch.push(update);
+                            }
+                            let applied = this.update(cx, |this, cx| {
+                                for update in batch {
+                                    match update {
+                                        SearchUpdate::File(file) => this.results.push(file),
+                                        SearchUpdate::Truncated => this.truncated = true,
+                                    }
+                                }
+                                this.rebuild(cx);
+                            });
+                            if applied.is_err() {
+                                return;
+                            }
+                        }
+                        this.update(cx, |this, cx| {
+                            this.searching = false;
+                            cx.notify();
+                        })
+                        .ok();
+                    }));
+                }
+                Err(e) => self.error = Some(e.to_string()),
+            }
+        }
+        self.rebuild(cx);
+    }
+
+    fn schedule_search(&mut self, cx: &mut Context<Self>) {
+        self.search_task = Some(cx.spawn(async move |this, cx| {
+            cx.background_executor().timer(SEARCH_DELAY).await;
+            this.update(cx, |_this, cx| cx.emit(ProjectSearchPanelEvent::SearchRequested)).ok();
+        }));
+    }
+
+    fn rebuild(&mut self, cx: &mut Context<Self>) {
+        self.rows.clear();
+        for (file_ix, file) in self.results.iter().enumerate() {
+            self.rows.push(SearchRow::File(file_ix));
+            if self.collapsed.contains(&file.path) {
+                continue;
+            }
+            let mut matches = file.matches.iter().enumerate().peekable();
+            for &line in file.lines.keys() {
+                let mut matched = false;
+                while let Some((match_ix, _)) = matches.next_if(|(_, found)| found.line == line) {
+                    self.rows.push(SearchRow::Match(file_ix, match_ix));
+                    matched = true;
+                }
+                if !matched {
+                    self.rows.push(SearchRow::Context(file_ix, line));
+                }
+            }
+        }
+        cx.notify();
+    }
+
+    fn toggle_option(&mut self, toggle: fn(&mut SearchQuery), cx: &mut Context<Self>) {
+        toggle(&mut self.query);
+        cx.emit(ProjectSearchPanelEvent::SearchRequested);
+    }
+
+    fn toggle_file(&mut self, file_ix: usize, cx: &mut Context<Self>) {
+        let path = self.results[file_ix].path.clone();
+        if !self.collapsed.remove(&path) {
+            self.collapsed.insert(path);
+        }
+        self.rebuild(cx);
+    }
+
+    /// Include or leave out every match of a file
+    fn toggle_file_included(&mut self, file_ix: usize, cx: &mut Context<Self>) {
+        let count = self.results[file_ix].matches.len();
+        let all_included = (0..count).all(|match_ix| !self.excluded.contains(&(file_ix, match_ix)));
+        for match_ix in 0..count {
+            if all_included {
+                self.excluded.insert((file_ix, match_ix));
+            } else {
+                self.excluded.remove(&(file_ix, match_ix));
+            }
+        }
+        cx.notify();
+    }
+
+    fn toggle_match_included(&mut self, file_ix: usize, match_ix: usize, cx: &mut Context<Self>) {
+        if !self.excluded.remove(&(file_ix, match_ix)) {
+            self.excluded.insert((file_ix, match_ix));
+        }
+        cx.notify();
+    }
+
+    fn replace_all(&mut self, cx: &mut Context<Self>) {
+        let Some(regex) = &self.regex else {
+            return;
+        };
+        let replace = self.replace_input.read(cx).value().to_string();
+        let (edit, count) = replacement_edit(&self.query, regex, &self.results, &replace, &self.excluded);
+        if count > 0 {
+            cx.emit(ProjectSearchPanelEvent::Replace(edit));
+        }
+    }
+
+    fn match_count(&self) -> usize {
+        self.results.iter().map(|file| file.matches.len()).sum()
+    }
+
+    fn display_path<'a>(&self, path: &'a Path) -> std::borrow::Cow<'a, str> {
+        self.project_root
+            .as_ref()
+            .and_then(|root| path.strip_prefix(root).ok())
+            .unwrap_or(path)
+            .to_string_lossy()
+    }
+
+    fn render_rows(&mut self, range: Range<usize>, cx: &mut Context<Self>) -> Vec<AnyElement> {
+        range
+            .filter_map(|row_ix| {
+                let row = *self.rows.get(row_ix)?;
+                Some(match row {
+                    SearchRow::File(file_ix) => self.render_file_row(row_ix, file_ix, cx),
+                    SearchRow::Match(file_ix, match_ix) => self.render_match_row(row_ix, file_ix, match_ix, cx),
+                    SearchRow::Context(file_ix, line) => self.render_context_row(row_ix, file_ix, line, cx),
+                })
+            })
+            .collect()
+    }
+
+    fn render_check(&self, id: (&'static str, usize), included: bool, cx: &mut Context<Self>) -> Stateful<Div> {
+        div()
+            .id(id)
+            .flex_none()
+            .w(px(14.))
+            .text_xs()
+            .text_color(if included { cx.theme().accent } else { cx.theme().muted_foreground })
+            .child(if included { "☑" } else { "☐" })
+    }
+
+    fn render_file_row(&self, row_ix: usize, file_ix: usize, cx: &mut Context<Self>) -> AnyElement {
+        let file = &self.results[file_ix];
+        let collapsed = self.collapsed.contains(&file.path);
+        let included = (0..file.matches.len()).any(|match_ix| !self.excluded.contains(&(file_ix, match_ix)));
+
+        h_flex()
+            .id(("search-file", row_ix))
+            .h(px(24.))
+            .px_2()
+            .gap_1()
+            .cursor_pointer()
+            .hover(|style| style.bg(cx.theme().accent.opacity(0.1)))
+            .child(
+                Icon::new(if collapsed { IconName::ChevronRight } else { IconName::ChevronDown })
+                    .size_3()
+                    .text_color(cx.theme().muted_foreground),
+            )
+            .when(self.replace_mode, |row| {
+                row.child(self.render_check(("search-file-check", row_ix), included, cx).on_click(cx.listener(
+                    move |this, _, _window, cx| {
+                        cx.stop_propagation();
+                        this.toggle_file_included(file_ix, cx);
+                    },
+                )))
+            })
+            .child(
+                div()
+                    .flex_1()
+                    .overflow_hidden()
+                    .text_ellipsis()
+                    .text_xs()
+                    .font_semibold()
+                    .text_color(cx.theme().foreground)
+                    .child(self.display_path(&file.path).to_string()),
+            )
+            .child(
+                div()
+                    .text_xs()
+                    .text_color(cx.theme().muted_foreground)
+                    .child(file.matches.len().to_string()),
+            )
+            .on_click(cx.listener(move |this, _, _window, cx| this.toggle_file(file_ix, cx)))
+            .into_any_element()
+    }
+
+    fn render_line_number(line: u32, cx: &App) -> Div {
+        div()
+            .flex_none()
+            .w(px(32.))
+            .text_right()
+            .text_xs()
+            .text_color(cx.theme().muted_foreground)
+            .child((line + 1).to_string())
+    }
+
+    fn render_context_row(&self, row_ix: usize, file_ix: usize, line: u32, cx: &mut Context<Self>) -> AnyElement {
+        let text = self.results[file_ix].lines.get(&line).map(String::as_str).unwrap_or_default();
+        h_flex()
+            .id(("search-context", row_ix))
+            .h(px(20.))
+            .pl_4()
+            .pr_2()
+            .gap_2()
+            .child(Self::render_line_number(line, cx))
+            .child(
+                div()
+                    .flex_1()
+                    .overflow_hidden()
+                    .whitespace_nowrap()
+                    .text_xs()
+                    .text_color(cx.theme().muted_foreground)
+                    .child(text.trim_start().to_string()),
+            )
+            .into_any_element()
+    }
+
+    fn render_match_row(&self, row_ix: usize, file_ix: usize, match_ix: usize, cx: &mut Context<Self>) -> AnyElement {
+        let file = &self.results[file_ix];
+        let found = &file.matches[match_ix];
+        let text = file.lines.get(&found.line).map(String::as_str).unwrap_or_default();
+        let included = !self.excluded.contains(&(file_ix, match_ix));
+
+        // Leading indentation is dropped, and long lines start shortly before the match
+        let indent = text.len() - text.trim_start().len();
+        let mut start = indent.min(found.range.start);
+        if found.range.start - start > 60 {
+            start = found.range.start - 30;
+            while !text.is_char_boundary(start) {
+                start -= 1;
+            }
+        }
+        let before = &text[start..found.range.start];
+        let matched = &text[found.range.clone()];
+        let after = &text[found.range.end..];
+        let replacement = (self.replace_mode && included)
+            .then(|| {
+                let regex = self.regex.as_ref()?;
+                let replace = self.replace_input.read(cx).value().to_string();
+                Some(self.query.replacement(regex, text, found.range.clone(), &replace))
+            })
+            .flatten();
+
+        let mut shown = format!("{}{}", before, matched);
+        let matched_range = before.len()..shown.len();
+        let mut highlights = vec![(
+            matched_range,
+            HighlightStyle {
+                background_color: Some(if replacement.is_some() {
+                    cx.theme().danger.opacity(0.25)
+                } else {
+                    cx.theme().warning.opacity(0.35)
+                }),
+                strikethrough: replacement.is_some().then(|| StrikethroughStyle {
+                    thickness: px(1.),
+                    color: Some(cx.theme().danger),
+                }),
+                ..Default::default()
+            },
+        )];
+        if let Some(replacement) = &replacement {
+            let inserted = shown.len()..shown.len() + replacement.len();
+            shown.push_str(replacement);
+            highlights.push((
+                inserted,
+                HighlightStyle {
+                    background_color: Some(cx.theme().success.opacity(0.25)),
+                    ..Default::default()
+                },
+            ));
+        }
+        shown.push_str(after);
+
+        let path = file.path.clone();
+        let line = found.line;
+        let character = text[..found.range.start].encode_utf16().count() as u32;
+
+        h_flex()
+            .id(("search-match", row_ix))
+            .h(px(20.))
+            .pl_4()
+            .pr_2()
+            .gap_2()
+            .cursor_pointer()
+            .hover(|style| style.bg(cx.theme().accent.opacity(0.1)))
+            .when(self.replace_mode, |row| {
+                row.child(self.render_check(("search-match-check", row_ix), included, cx).on_click(cx.listener(
+                    move |this, _, _window, cx| {
+                        cx.stop_propagation();
+                        this.toggle_match_included(file_ix, match_ix, cx);
+                    },
+                )))
+            })
+            .child(Self::render_line_number(line, cx))
+            .child(
+                div()
+                    .flex_1()
+                    .overflow_hidden()
+                    .whitespace_nowrap()
+                    .text_xs()
+                    .text_color(cx.theme().foreground)
+                    .child(StyledText::new(shown).with_highlights(highlights)),
+            )
+            .on_click(cx.listener(move |_this, _, _window, cx| {
+                cx.emit(ProjectSearchPanelEvent::OpenLocation(Location {
+                    path: path.clone(),
+                    line,
+                    character,
+                }));
+            }))
+            .into_any_element()
+    }
+
+    fn render_option(
+        &self,
+        id: &'static str,
+        label: &'static str,
+        tooltip: &'static str,
+        selected: bool,
+        toggle: fn(&mut SearchQuery),
+        cx: &mut Context<Self>,
+    ) -> impl IntoElement {
+        Button::new(id)
+            .label(label)
+            .tooltip(tooltip)
+            .ghost()
+            .xsmall()
+            .selected(selected)
+            .on_click(cx.listener(move |this, _, _window, cx| this.toggle_option(toggle, cx)))
+    }
+
+    fn summary(&self) -> String {
+        if let Some(error) = &self.error {
+            return error.lines().last().unwrap_or(error).to_string();
+        }
+        let (matches, files) = (self.match_count(), self.results.len());
+        let mut summary = match (matches, self.searching) {
+            (0, true) => "Searching…".to_string(),
+            (0, false) if self.regex.is_some() => "No results".to_string(),
+            (0, false) => String::new(),
+            _ => format!(
+                "{} result{} in {} file{}",
+                matches,
+                if matches == 1 { "" } else { "s" },
+                files,
+                if files == 1 { "" } else { "s" }
+            ),
+        };
+        if self.truncated {
+            summary.push_str(" (stopped at the limit)");
+        }
+        summary
+    }
+}
+
+impl Focusable for ProjectSearchPanel {
+    fn focus_handle(&self, _cx: &App) -> FocusHandle {
+        self.focus_handle.clone()
+    }
+}
+
+impl Render for ProjectSearchPanel {
+    fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let query = &self.query;
+        let can_replace = self.regex.is_some()
+            && self
+                .results
+                .iter()
+                .enumerate()
+                .any(|(file_ix, file)| (0..file.matches.len()).any(|match_ix| !self.excluded.contains(&(file_ix, match_ix))));
+
+        v_flex()
+            .size_full()
+            .track_focus(&self.focus_handle)
+            .child(
+                // Header
+                v_flex()
+                    .w_full()
+                    .px_2()
+                    .py_2()
+                    .gap_1()
+                    .border_b_1()
+                    .border_color(cx.theme().border)
+                    .child(
+                        h_flex()
+                            .gap_1()
+                            .child(
+                                Button::new("search_replace_mode")
+                                    .icon(if self.replace_mode { IconName::ChevronDown } else { IconName::ChevronRight })
+                                    .tooltip("Toggle Replace")
+                                    .ghost()
+                                    .xsmall()
+                                    .on_click(cx.listener(|this, _, _window, cx| {
+                                        this.replace_mode = !this.replace_mode;
+                                        cx.notify();
+                                    })),
+                            )
+                            .child(div().flex_1().child(TextInput::new(&self.search_input).small())),
+                    )
+                    .when(self.replace_mode, |header| {
+                        header.child(
+                            h_flex()
+                                .gap_1()
+                                .pl_6()
+                                .child(div().flex_1().child(TextInput::new(&self.replace_input).small()))
+                                .child(
+                                    Button::new("search_replace_all")
+                                        .label("Replace All")
+                                        .ghost()
+                                        .xsmall()
+                                        .disabled(!can_replace)
+                                        .on_click(cx.listener(|this, _, _window, cx| this.replace_all(cx))),
+                                ),
+                        )
+                    })
+                    .child(
+                        h_flex()
+                            .gap_1()
+                            .pl_6()
+                            .child(self.render_option("search_case", "Aa", "Match Case", query.case_sensitive, |query| query.case_sensitive = !query.case_sensitive, cx))
+                            .child(self.render_option("search_word", "ab", "Match Whole Word", query.whole_word, |query| query.whole_word = !query.whole_word, cx))
+                            .child(self.render_option("search_regex", ".*", "Use Regular Expression", query.regex, |query| query.regex = !query.regex, cx))
+                            .child(
+                                div()
+                                    .flex_1()
+                                    .overflow_hidden()
+                                    .text_ellipsis()
+                                    .text_right()
+                                    .text_xs()
+                                    .text_color(if self.error.is_some() {
+                                        cx.theme().danger
+                                    } else {
+                                        cx.theme().muted_foreground
+                                    })
+                                    .child(self.summary()),
+                            ),
+                    ),
+            )
+            .child(
+                div()
+                    .flex_1()
+                    .min_h_0()
+                    .when(self.project_root.is_none(), |content| {
+                        content.child(
+                            div()
+                                .p_4()
+                                .text_xs()
+                                .text_color(cx.theme().muted_foreground)
+                                .child("Open a project to search its files"),
+                        )
+                    })
+                    .when(!self.rows.is_empty(), |content| {
+                        content.child(
+                            uniform_list(
+                                "project-search-results",
+                                self.rows.len(),
+                                cx.processor(|this, range: Range<usize>, _window, cx| this.render_rows(range, cx)),
+                            )
+                            .track_scroll(self.scroll_handle.clone())
+                            .size_full(),
+                        )
+                    }),
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(pattern: &str) -> SearchQuery {
+        SearchQuery {
+            pattern: pattern.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_search_lines_with_context() {
+        let text = "fn main() {\r\n    let total = 1;\r\n    println!(\"{}\", total);\r\n}\r\n";
+        let regex = query("total").build().unwrap();
+        let (matches, lines) = search_lines(&regex, text, 1, 100);
+        assert_eq!(
+            matches,
+            [
+                SearchMatch { line: 1, range: 8..13 },
+                SearchMatch { line: 2, range: 19..24 },
+            ]
+        );
+        // Line endings are dropped and context lines are shared
+        assert_eq!(lines.keys().copied().collect::<Vec<_>>(), [0, 1, 2, 3]);
+        assert_eq!(lines[&3], "}");
+
+        let (matches, _) = search_lines(&regex, text, 1, 1);
+        assert_eq!(matches.len(), 1);
+    }
+
+    #[test]
+    fn test_replacement_edit_skips_excluded() {
+        let search = SearchQuery {
+            regex: true,
+            ..query(r"(\w+)\.len\(\)")
+        };
+        let regex = search.build().unwrap();
+        let text = "let n = items.len() + names.len();";
+        let (matches, lines) = search_lines(&regex, text, 0, 100);
+        let results = vec![FileResult {
+            path: PathBuf::from("/p/src/main.rs"),
+            matches,
+            lines,
+        }];
+        let excluded = HashSet::from([(0, 1)]);
+        let (edit, count) = replacement_edit(&search, &regex, &results, "len(&$1)", &excluded);
+        assert_eq!(count, 1);
+        let edits = edit.changes.unwrap().into_values().next().unwrap();
+        assert_eq!(edits[0].new_text, "len(&items)");
+        assert_eq!(edits[0].range.start, lsp_types::Position::new(0, 8));
+        assert_eq!(edits[0].range.end, lsp_types::Position::new(0, 19));
+    }
+}
@@ -0,0 +1,223 @@
+//! Python interpreters a project may run with, and telling its language
+//! server which one is in use
+//!
+//! Environments are looked for in the project (`.venv`, `venv`, a conda
+//! prefix), where Poetry and conda keep theirs, and on `PATH`.
+
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+use super::lsp::find_in_path;
+
+/// Virtual environment folders looked for in the project root
+const VENV_FOLDERS: [&str; 3] = [".venv", "venv", "env"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EnvironmentKind {
+    Venv,
+    Poetry,
+    Conda,
+    System,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PythonInterpreter {
+    /// The `python` executable
+    pub path: PathBuf,
+    pub kind: EnvironmentKind,
+    /// The environment's folder name, e.g. `.venv` or a conda env's name
+    pub name: String,
+}
+
+impl PythonInterpreter {
+    /// How the status bar and the picker show it, e.g. `Python (.venv)`
+    pub fn label(&self) -> String {
+        match self.kind {
+            EnvironmentKind::Venv => format!("Python ({})", self.name),
+            EnvironmentKind::Poetry => format!("Python (poetry: {})", self.name),
+            EnvironmentKind::Conda => format!("Python (conda: {})", self.name),
+            EnvironmentKind::System => format!("Python ({})", self.name),
+        }
+    }
+}
+
+/// Interpreters for a project in `root`, the project's own first; the first
+/// one is what the project uses until another is picked
+pub fn find_interpreters(root: &Path) -> Vec<PythonInterpreter> {
+    let mut found = Vec::new();
+    let mut add = |env: &Path, kind, name: String| {
+        if let Some(path) = interpreter_in(env) {
+            if !found.iter().any(|interpreter: &PythonInterpreter| interpreter.path == path) {
+                found.push(PythonInterpreter { path, kind, name });
+            }
+        }
+    };
+
+    for folder in VENV_FOLDERS {
+        let env = root.join(folder);
+        // A conda prefix made with `conda create -p` looks much the same
+        let kind = if env.join("conda-meta").is_dir() { EnvironmentKind::Conda } else { EnvironmentKind::Venv };
+        add(&env, kind, folder.to_string());
+    }
+
+    let pyproject = std::fs::read_to_string(root.join("pyproject.toml")).unwrap_or_default();
+    if let (Some(project), Some(envs)) = (poetry_project_name(&pyproject), poetry_virtualenvs()) {
+        // Poetry's environments are named `<project>-<hash>-py<version>`
+        let prefix = format!("{}-", project.to_lowercase().replace([' ', '_', '.'], "-"));
+        for env in sorted_folders(&envs) {
+            let name = folder_name(&env);
+            if name.to_lowercase().starts_with(&prefix) {
+                add(&env, EnvironmentKind::Poetry, name);
+            }
+        }
+    }
+
+    let environment_yml = ["environment.yml", "environment.yaml"]
+        .iter()
+        .find_map(|file| std::fs::read_to_string(root.join(file)).ok())
+        .unwrap_or_default();
+    let conda_envs = conda_environments();
+    if let Some(name) = conda_environment_name(&environment_yml) {
+        if let Some(env) = conda_envs.iter().find(|env| folder_name(env) == name) {
+            add(env, EnvironmentKind::Conda, name);
+        }
+    }
+    if let Some(active) = std::env::var_os("CONDA_PREFIX").map(PathBuf::from) {
+        add(&active, EnvironmentKind::Conda, folder_name(&active));
+    }
+    for env in &conda_envs {
+        add(env, EnvironmentKind::Conda, folder_name(env));
+    }
+
+    for command in ["python3", "python"] {
+        if let Some(path) = find_in_path(command) {
+            if !found.iter().any(|interpreter| interpreter.path == path) {
+                found.push(PythonInterpreter {
+                    path,
+                    kind: EnvironmentKind::System,
+                    name: command.to_string(),
+                });
+            }
+        }
+    }
+    found
+}
+
+/// `workspace/didChangeConfiguration` settings pointing the Python servers
+/// at `interpreter`: pylsp's Jedi environment, and pyright's `pythonPath`
+pub fn python_language_settings(interpreter: &Path) -> Value {
+    let path = interpreter.to_string_lossy();
+    json!({
+        "pylsp": { "plugins": { "jedi": { "environment": path } } },
+        "python": { "pythonPath": path, "defaultInterpreterPath": path },
+    })
+}
+
+/// The `python` executable of an environment folder
+fn interpreter_in(env: &Path) -> Option<PathBuf> {
+    [
+        env.join("bin").join("python"),
+        env.join("Scripts").join("python.exe"),
+        // conda on Windows keeps it at the top
+        env.join("python.exe"),
+    ]
+    .into_iter()
+    .find(|path| path.is_file())
+}
+
+/// The project name of a `pyproject.toml` managed by Poetry
+fn poetry_project_name(pyproject: &str) -> Option<String> {
+    let manifest: toml::Table = pyproject.parse().ok()?;
+    let tool = manifest.get("tool")?.get("poetry")?;
+    let name = tool
+        .get("name")
+        .or_else(|| manifest.get("project")?.get("name"))?;
+    name.as_str().map(str::to_string)
+}
+
+/// Where Poetry creates environments outside of projects
+fn poetry_virtualenvs() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("POETRY_VIRTUALENVS_PATH") {
+        return Some(PathBuf::from(path));
+    }
+    let cache = if cfg!(target_os = "windows") {
+        PathBuf::from(std::env::var_os("LOCALAPPDATA")?).join("pypoetry").join("Cache")
+    } else if cfg!(target_os = "macos") {
+        home()?.join("Library").join("Caches").join("pypoetry")
+    } else {
+        std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| Some(home()?.join(".cache")))?
+            .join("pypoetry")
+    };
+    Some(cache.join("virtualenvs"))
+}
+
+/// The `name:` an `environment.yml` creates its conda environment under
+fn conda_environment_name(environment_yml: &str) -> Option<String> {
+    environment_yml.lines().find_map(|line| {
+        let name = line.strip_prefix("name:")?.trim().trim_matches(['"', '\'']);
+        (!name.is_empty()).then(|| name.to_string())
+    })
+}
+
+/// Named conda environments in the usual install folders
+fn conda_environments() -> Vec<PathBuf> {
+    let Some(home) = home() else {
+        return Vec::new();
+    };
+    [".conda", "miniconda3", "anaconda3", "miniforge3", "mambaforge"]
+        .iter()
+        .flat_map(|install| sorted_folders(&home.join(install).join("envs")))
+        .collect()
+}
+
+fn sorted_folders(path: &Path) -> Vec<PathBuf> {
+    let mut folders: Vec<PathBuf> = std::fs::read_dir(path)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect();
+    folders.sort();
+    folders
+}
+
+fn folder_name(path: &Path) -> String {
+    path.file_name().map_or_else(|| path.display().to_string(), |name| name.to_string_lossy().to_string())
+}
+
+fn home() -> Option<PathBuf> {
+    std::env::var_os(if cfg!(target_os = "windows") { "USERPROFILE" } else { "HOME" }).map(PathBuf::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_project_environment_names() {
+        let pyproject = "[tool.poetry]\nname = \"my-app\"\nversion = \"0.1.0\"\n";
+        assert_eq!(poetry_project_name(pyproject).as_deref(), Some("my-app"));
+        // Not managed by Poetry
+        assert_eq!(poetry_project_name("[project]\nname = \"other\"\n"), None);
+
+        let environment = "# Data tools\nname: analysis\nchannels:\n  - conda-forge\n";
+        assert_eq!(conda_environment_name(environment).as_deref(), Some("analysis"));
+        assert_eq!(conda_environment_name("channels: []\n"), None);
+    }
+
+    #[test]
+    fn test_finds_project_venv_first() {
+        let root = std::env::temp_dir().join(format!("python-env-test-{}", std::process::id()));
+        let bin = root.join(".venv").join("bin");
+        std::fs::create_dir_all(&bin).unwrap();
+        std::fs::write(bin.join("python"), "").unwrap();
+
+        let interpreters = find_interpreters(&root);
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(interpreters[0].path, bin.join("python"));
+        assert_eq!(interpreters[0].label(), "Python (.venv)");
+    }
+}
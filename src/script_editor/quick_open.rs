@@ -0,0 +1,525 @@
+//! "Go to File": fuzzy-find any file in the project by its path
+//!
+//! The project's files are listed on a background thread, respecting
+//! `.gitignore`, and listed again once files stop being created, deleted or
+//! renamed, leaving out ones the listing ignores, such as a build's output.
+//! Recently opened files rank above others that match as well, and a
+//! `:line` or `:line:column` after the name jumps there once the file opens.
+
+use futures::channel::mpsc::unbounded;
+use gpui::{prelude::FluentBuilder, *};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use notify::event::ModifyKind;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use ui::{
+    h_flex,
+    input::{InputEvent, InputState, TextInput},
+    v_flex, ActiveTheme as _, Sizable as _,
+};
+
+use super::command_palette::{fuzzy_match, FuzzyMatch};
+use super::file_watcher::WATCH_DEBOUNCE;
+use super::go_to_line::{parse_line_target, LineTarget};
+
+/// How many recently opened files get a bonus, and how big the most recent one's is
+pub const MAX_RECENT: usize = 20;
+
+/// Bonus for a query matching within the file name rather than across folders
+const NAME_BONUS: i32 = 10;
+
+/// Split a trailing `:line` or `:line:column` off the query, leaving what to match files against
+pub fn split_line_suffix(query: &str) -> (&str, Option<LineTarget>) {
+    for (colon, _) in query.match_indices(':') {
+        match parse_line_target(&query[colon + 1..]) {
+            Ok(target) => return (&query[..colon], target),
+            Err(_) => continue,
+        }
+    }
+    (query, None)
+}
+
+/// Index and match of every file matching `query`, best first
+///
+/// `files` and `recent` are project-relative paths with `/`, `recent` most recent first.
+pub fn rank_files(files: &[String], recent: &[String], query: &str) -> Vec<(usize, FuzzyMatch)> {
+    let mut matches: Vec<(usize, FuzzyMatch)> = files
+        .iter()
+        .enumerate()
+        .filter_map(|(ix, path)| {
+            let mut fuzzy = match_path(query, path)?;
+            if let Some(recency) = recent.iter().take(MAX_RECENT).position(|recent| recent == path) {
+                fuzzy.score += (MAX_RECENT - recency) as i32;
+            }
+            Some((ix, fuzzy))
+        })
+        .collect();
+    // Stable, so ties keep the shorter path, then the walk's order
+    matches.sort_by_key(|(ix, fuzzy)| (std::cmp::Reverse(fuzzy.score), files[*ix].len()));
+    matches
+}
+
+/// The better of matching the whole path and matching only its file name
+fn match_path(query: &str, path: &str) -> Option<FuzzyMatch> {
+    let across = fuzzy_match(query, path)?;
+    let name_start = path.rfind('/').map_or(0, |slash| slash + 1);
+    let within_name = fuzzy_match(query, &path[name_start..]).map(|fuzzy| FuzzyMatch {
+        score: fuzzy.score + NAME_BONUS,
+        positions: fuzzy.positions.iter().map(|offset| offset + name_start).collect(),
+    });
+    match within_name {
+        Some(within_name) if within_name.score >= across.score => Some(within_name),
+        _ => Some(across),
+    }
+}
+
+/// Every file under `root` that `.gitignore` doesn't exclude, relative and with `/`
+fn list_files(root: &Path) -> Vec<String> {
+    ignore::WalkBuilder::new(root)
+        .require_git(false)
+        .build()
+        .flatten()
+        .filter(|entry| entry.file_type().is_some_and(|kind| kind.is_file()))
+        .filter_map(|entry| {
+            let relative = entry.path().strip_prefix(root).ok()?;
+            let parts: Vec<_> = relative.components().map(|part| part.as_os_str().to_string_lossy()).collect();
+            Some(parts.join("/"))
+        })
+        .collect()
+}
+
+/// The rules `list_files` walks by, for telling whether a path it would list
+/// changed: hidden paths are left out, and the `.gitignore` and `.ignore`
+/// files from the path's folder up to the root decide for the rest
+struct IgnoreRules {
+    root: PathBuf,
+    /// Rules of each folder read so far
+    folders: HashMap<PathBuf, Gitignore>,
+}
+
+impl IgnoreRules {
+    fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            folders: HashMap::new(),
+        }
+    }
+
+    /// Whether the listing leaves out `path`, or the folder it's in
+    fn ignores(&mut self, path: &Path) -> bool {
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return true;
+        };
+        if relative.components().any(|part| part.as_os_str().to_string_lossy().starts_with('.')) {
+            return true;
+        }
+        let is_dir = path.is_dir();
+        // The nearest folder with a rule for the path decides
+        for folder in path.ancestors().skip(1).take_while(|folder| folder.starts_with(&self.root)) {
+            let rules = self
+                .folders
+                .entry(folder.to_path_buf())
+                .or_insert_with(|| folder_rules(folder, folder == self.root));
+            match rules.matched_path_or_any_parents(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => {}
+            }
+        }
+        false
+    }
+
+    /// Forget the rules read so far, once an ignore file changed
+    fn clear(&mut self) {
+        self.folders.clear();
+    }
+}
+
+/// A folder's `.gitignore` and `.ignore`, the latter winning, and at the
+/// root what `.git/info/exclude` leaves out too
+fn folder_rules(folder: &Path, is_root: bool) -> Gitignore {
+    let mut builder = GitignoreBuilder::new(folder);
+    if is_root {
+        builder.add(folder.join(".git/info/exclude"));
+    }
+    // Missing files add nothing
+    builder.add(folder.join(".gitignore"));
+    builder.add(folder.join(".ignore"));
+    builder.build().unwrap_or_else(|_| Gitignore::empty())
+}
+
+/// Whether `event` can change which files are listed: edits to contents
+/// can't, except to an ignore file, and paths the listing ignores don't count
+fn changes_listing(event: &Event, rules: &mut IgnoreRules) -> bool {
+    let is_ignore_file = |path: &PathBuf| path.file_name().is_some_and(|name| name == ".gitignore" || name == ".ignore");
+    let changes_rules = event
+        .paths
+        .iter()
+        .any(|path| is_ignore_file(path) && path.parent().is_some_and(|folder| !rules.ignores(folder)));
+    let listed = match event.kind {
+        EventKind::Create(_) | EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(_)) => {
+            changes_rules || event.paths.iter().any(|path| !rules.ignores(path))
+        }
+        EventKind::Modify(_) => changes_rules,
+        _ => false,
+    };
+    if changes_rules {
+        rules.clear();
+    }
+    listed
+}
+
+/// The files of one project, kept up to date with the disk
+pub struct FileIndex {
+    root: PathBuf,
+    files: Arc<Vec<String>>,
+    /// Whether the first listing is still running
+    indexing: bool,
+    _watcher: Option<RecommendedWatcher>,
+    _task: Task<()>,
+}
+
+impl FileIndex {
+    pub fn new(root: PathBuf, cx: &mut Context<Self>) -> Self {
+        let (changes, mut receiver) = unbounded();
+        let mut rules = IgnoreRules::new(root.clone());
+        let watcher = notify::recommended_watcher(move |result: notify::Result<Event>| {
+            if result.is_ok_and(|event| changes_listing(&event, &mut rules)) {
+                changes.unbounded_send(()).ok();
+            }
+        })
+        .and_then(|mut watcher| {
+            watcher.watch(&root, RecursiveMode::Recursive)?;
+            Ok(watcher)
+        });
+        let watcher = match watcher {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                tracing::warn!("⚠️  Not watching {:?} for new files: {}", root, e);
+                None
+            }
+        };
+
+        let walk_root = root.clone();
+        let task = cx.spawn(async move |this, cx| {
+            use futures::StreamExt as _;
+            loop {
+                let root = walk_root.clone();
+                let files = cx.background_spawn(async move { list_files(&root) }).await;
+                let applied = this.update(cx, |this, cx| {
+                    this.files = Arc::new(files);
+                    this.indexing = false;
+                    cx.notify();
+                });
+                if applied.is_err() || receiver.next().await.is_none() {
+                    break;
+                }
+                // A checkout creates files by the hundred; list them once they stop coming
+                loop {
+                    cx.background_executor().timer(WATCH_DEBOUNCE).await;
+                    let mut more = false;
+                    while let Ok(Some(())) = receiver.try_next() {
+                        more = true;
+                    }
+                    if !more {
+                        break;
+                    }
+                }
+            }
+        });
+
+        Self {
+            root,
+            files: Arc::new(Vec::new()),
+            indexing: true,
+            _watcher: watcher,
+            _task: task,
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+}
+
+pub enum QuickOpenEvent {
+    /// Open a file, at a 1-based line and column if one was typed
+    Open { path: PathBuf, target: Option<LineTarget> },
+    Dismissed,
+}
+
+pub struct QuickOpen {
+    focus_handle: FocusHandle,
+    query_input: Entity<InputState>,
+    index: Entity<FileIndex>,
+    /// Project-relative paths of recently opened files, most recent first
+    recent: Vec<String>,
+    /// The files `matches` index into, so a new listing can't shift them
+    files: Arc<Vec<String>>,
+    matches: Vec<(usize, FuzzyMatch)>,
+    selected: usize,
+    scroll_handle: UniformListScrollHandle,
+    _subscriptions: Vec<Subscription>,
+}
+
+impl EventEmitter<QuickOpenEvent> for QuickOpen {}
+
+impl QuickOpen {
+    pub fn new(index: Entity<FileIndex>, recent: Vec<String>, window: &mut Window, cx: &mut Context<Self>) -> Self {
+        let query_input = cx.new(|cx| InputState::new(window, cx).placeholder("Search files by name (append :line to jump)"));
+        let subscriptions = vec![
+            cx.subscribe_in(&query_input, window, |this, _input, event: &InputEvent, _window, cx| match event {
+                InputEvent::Change => this.update_matches(cx),
+                InputEvent::PressEnter { .. } => this.confirm(cx),
+                InputEvent::Blur => cx.emit(QuickOpenEvent::Dismissed),
+                _ => {}
+            }),
+            // Files found after the picker opened show up as they come
+            cx.observe(&index, |this, _index, cx| this.update_matches(cx)),
+        ];
+        query_input.update(cx, |input, cx| input.focus(window, cx));
+
+        let mut quick_open = Self {
+            focus_handle: cx.focus_handle(),
+            query_input,
+            index,
+            recent,
+            files: Arc::new(Vec::new()),
+            matches: Vec::new(),
+            selected: 0,
+            scroll_handle: UniformListScrollHandle::new(),
+            _subscriptions: subscriptions,
+        };
+        quick_open.update_matches(cx);
+        quick_open
+    }
+
+    fn update_matches(&mut self, cx: &mut Context<Self>) {
+        let query = self.query_input.read(cx).value();
+        let (name, _) = split_line_suffix(&query);
+        self.files = self.index.read(cx).files.clone();
+        self.matches = rank_files(&self.files, &self.recent, name);
+        self.selected = 0;
+        self.scroll_handle.scroll_to_item(0, ScrollStrategy::Top);
+        cx.notify();
+    }
+
+    fn move_selection(&mut self, forward: bool, cx: &mut Context<Self>) {
+        if self.matches.is_empty() {
+            return;
+        }
+        let count = self.matches.len();
+        self.selected = if forward {
+            (self.selected + 1) % count
+        } else {
+            (self.selected + count - 1) % count
+        };
+        self.scroll_handle.scroll_to_item(self.selected, ScrollStrategy::Top);
+        cx.notify();
+    }
+
+    fn confirm(&mut self, cx: &mut Context<Self>) {
+        let Some((file_ix, _)) = self.matches.get(self.selected) else {
+            return;
+        };
+        let query = self.query_input.read(cx).value();
+        let (_, target) = split_line_suffix(&query);
+        let path = self.index.read(cx).root.join(&self.files[*file_ix]);
+        cx.emit(QuickOpenEvent::Open { path, target });
+    }
+
+    fn render_rows(&mut self, range: Range<usize>, cx: &mut Context<Self>) -> Vec<AnyElement> {
+        range
+            .filter_map(|row_ix| {
+                let (file_ix, fuzzy) = self.matches.get(row_ix)?;
+                let path = &self.files[*file_ix];
+                let name_start = path.rfind('/').map_or(0, |slash| slash + 1);
+                let highlight = HighlightStyle {
+                    color: Some(cx.theme().accent),
+                    font_weight: Some(FontWeight::BOLD),
+                    ..Default::default()
+                };
+                // Match positions are in the whole path; split them between the name and its folder
+                let highlights = |range: Range<usize>| {
+                    fuzzy
+                        .positions
+                        .iter()
+                        .filter(|&&offset| range.contains(&offset))
+                        .filter_map(|&offset| {
+                            let len = path[offset..].chars().next()?.len_utf8();
+                            Some((offset - range.start..offset - range.start + len, highlight))
+                        })
+                        .collect::<Vec<_>>()
+                };
+                let name = StyledText::new(path[name_start..].to_string()).with_highlights(highlights(name_start..path.len()));
+                let folder = (name_start > 0).then(|| {
+                    StyledText::new(path[..name_start - 1].to_string()).with_highlights(highlights(0..name_start - 1))
+                });
+
+                Some(
+                    h_flex()
+                        .id(("quick-open-file", row_ix))
+                        .h(px(28.))
+                        .px_3()
+                        .gap_2()
+                        .cursor_pointer()
+                        .rounded(cx.theme().radius)
+                        .when(row_ix == self.selected, |row| row.bg(cx.theme().accent.opacity(0.2)))
+                        .hover(|style| style.bg(cx.theme().accent.opacity(0.1)))
+                        .child(
+                            div()
+                                .text_sm()
+                                .whitespace_nowrap()
+                                .text_color(cx.theme().foreground)
+                                .child(name),
+                        )
+                        .children(folder.map(|folder| {
+                            div()
+                                .flex_1()
+                                .overflow_hidden()
+                                .whitespace_nowrap()
+                                .text_ellipsis()
+                                .text_xs()
+                                .text_color(cx.theme().muted_foreground)
+                                .child(folder)
+                        }))
+                        .on_click(cx.listener(move |this, _, _window, cx| {
+                            this.selected = row_ix;
+                            this.confirm(cx);
+                        }))
+                        .into_any_element(),
+                )
+            })
+            .collect()
+    }
+}
+
+impl Focusable for QuickOpen {
+    fn focus_handle(&self, _cx: &App) -> FocusHandle {
+        self.focus_handle.clone()
+    }
+}
+
+impl Render for QuickOpen {
+    fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let status = if self.index.read(cx).indexing {
+            "Indexing project files…"
+        } else {
+            "No matching files"
+        };
+
+        v_flex()
+            .w(px(620.))
+            .p_1()
+            .gap_1()
+            .bg(cx.theme().popover)
+            .border_1()
+            .border_color(cx.theme().border)
+            .rounded(cx.theme().radius)
+            .shadow_lg()
+            .track_focus(&self.focus_handle)
+            // Arrow keys move through the list instead of the input's cursor
+            .capture_key_down(cx.listener(|this, event: &KeyDownEvent, _window, cx| {
+                match event.keystroke.key.as_str() {
+                    "up" => this.move_selection(false, cx),
+                    "down" => this.move_selection(true, cx),
+                    "escape" => cx.emit(QuickOpenEvent::Dismissed),
+                    _ => return,
+                }
+                cx.stop_propagation();
+            }))
+            .child(TextInput::new(&self.query_input).small())
+            .child(
+                div()
+                    .h(px(28. * self.matches.len().clamp(1, 12) as f32))
+                    .when(self.matches.is_empty(), |content| {
+                        content.child(
+                            div()
+                                .px_3()
+                                .py_1()
+                                .text_xs()
+                                .text_color(cx.theme().muted_foreground)
+                                .child(status),
+                        )
+                    })
+                    .when(!self.matches.is_empty(), |content| {
+                        content.child(
+                            uniform_list(
+                                "quick-open-list",
+                                self.matches.len(),
+                                cx.processor(|this, range: Range<usize>, _window, cx| this.render_rows(range, cx)),
+                            )
+                            .track_scroll(self.scroll_handle.clone())
+                            .size_full(),
+                        )
+                    }),
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_line_suffix() {
+        assert_eq!(split_line_suffix("main"), ("main", None));
+        assert_eq!(split_line_suffix("main.rs:12"), ("main.rs", Some(LineTarget { line: 12, column: None })));
+        assert_eq!(split_line_suffix("main:12:4"), ("main", Some(LineTarget { line: 12, column: Some(4) })));
+        // Still typing the line
+        assert_eq!(split_line_suffix("main:"), ("main", None));
+        assert_eq!(split_line_suffix("c:main"), ("c:main", None));
+    }
+
+    #[test]
+    fn test_file_names_and_recent_files_rank_higher() {
+        let files: Vec<String> = ["domain/entity.rs", "src/main.rs", "src/menu/main.rs", "README.md"]
+            .map(String::from)
+            .to_vec();
+        let ranked = |recent: &[String], query: &str| -> Vec<&str> {
+            rank_files(&files, recent, query).iter().map(|(ix, _)| files[*ix].as_str()).collect()
+        };
+        assert_eq!(ranked(&[], "main"), ["src/main.rs", "src/menu/main.rs", "domain/entity.rs"]);
+        assert_eq!(ranked(&["src/menu/main.rs".to_string()], "main"), ["src/menu/main.rs", "src/main.rs", "domain/entity.rs"]);
+        // With nothing typed, recent files come first, then the shortest paths
+        assert_eq!(ranked(&["README.md".to_string()], "")[..2], ["README.md", "src/main.rs"]);
+    }
+
+    #[test]
+    fn test_changes_to_ignored_paths_are_skipped() {
+        let root = std::env::temp_dir().join(format!("quick-open-ignore-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("target/debug")).unwrap();
+        std::fs::create_dir_all(root.join("src/generated")).unwrap();
+        std::fs::write(root.join(".gitignore"), "target/\n*.log\n").unwrap();
+        std::fs::write(root.join("src/.gitignore"), "generated/\n!keep.log\n").unwrap();
+
+        let mut rules = IgnoreRules::new(root.clone());
+        let created = |path: &str| Event::new(EventKind::Create(notify::event::CreateKind::File)).add_path(root.join(path));
+        let mut listed = |event: Event| changes_listing(&event, &mut rules);
+        assert!(listed(created("src/main.rs")));
+        assert!(!listed(created("target/debug/main.o")));
+        assert!(!listed(created("build.log")));
+        assert!(!listed(created("src/generated/schema.rs")));
+        // A nearer rule wins
+        assert!(listed(created("src/keep.log")));
+        assert!(!listed(created(".git/index.lock")));
+        // Edits to contents only count for ignore files
+        let modified = |path: &str| {
+            Event::new(EventKind::Modify(ModifyKind::Data(notify::event::DataChange::Content))).add_path(root.join(path))
+        };
+        assert!(!listed(modified("src/main.rs")));
+        assert!(listed(modified("src/.gitignore")));
+        assert!(!listed(modified("target/.gitignore")));
+
+        // New rules apply once the ignore file changed
+        std::fs::write(root.join(".gitignore"), "*.log\n").unwrap();
+        assert!(listed(modified(".gitignore")));
+        assert!(listed(created("target/debug/main.o")));
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
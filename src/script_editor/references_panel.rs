@@ -0,0 +1,351 @@
+//! Find All References results, grouped by file
+
+use gpui::{prelude::FluentBuilder, *};
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use ui::{h_flex, v_flex, ActiveTheme as _, Icon, IconName, StyledExt};
+use super::location::Location;
+
+/// A single reference location
+#[derive(Clone, Debug)]
+pub struct ReferenceEntry {
+    /// Zero-based line
+    pub line: u32,
+    /// Zero-based UTF-16 column
+    pub character: u32,
+    /// Trimmed text of the referencing line
+    pub snippet: String,
+}
+
+/// All references found in one file
+#[derive(Clone, Debug)]
+pub struct ReferenceGroup {
+    pub path: PathBuf,
+    pub entries: Vec<ReferenceEntry>,
+}
+
+/// Group raw `(path, line, character)` locations by file and attach line snippets.
+///
+/// `buffers` holds the text of files open in the editor so unsaved edits are
+/// reflected; other files are read from disk. Call this off the UI thread.
+pub fn group_references(
+    locations: Vec<(PathBuf, u32, u32)>,
+    buffers: &HashMap<PathBuf, String>,
+) -> Vec<ReferenceGroup> {
+    let mut groups: Vec<ReferenceGroup> = Vec::new();
+    let mut group_index: HashMap<PathBuf, usize> = HashMap::new();
+    for (path, line, character) in locations {
+        let index = *group_index.entry(path.clone()).or_insert_with(|| {
+            groups.push(ReferenceGroup {
+                path,
+                entries: Vec::new(),
+            });
+            groups.len() - 1
+        });
+        groups[index].entries.push(ReferenceEntry {
+            line,
+            character,
+            snippet: String::new(),
+        });
+    }
+
+    for group in &mut groups {
+        group.entries.sort_by_key(|entry| (entry.line, entry.character));
+        group.entries.dedup_by_key(|entry| (entry.line, entry.character));
+
+        let disk_text;
+        let text = match buffers.get(&group.path) {
+            Some(text) => text.as_str(),
+            None => {
+                disk_text = std::fs::read_to_string(&group.path).unwrap_or_default();
+                disk_text.as_str()
+            }
+        };
+        let lines: Vec<&str> = text.lines().collect();
+        for entry in &mut group.entries {
+            entry.snippet = lines
+                .get(entry.line as usize)
+                .map(|line| line.trim().chars().take(200).collect())
+                .unwrap_or_default();
+        }
+    }
+
+    groups
+}
+
+/// One visible row of the list: a file header or a reference beneath it
+#[derive(Clone, Copy)]
+enum ReferenceRow {
+    File(usize),
+    Entry(usize, usize),
+}
+
+pub enum ReferencesPanelEvent {
+    OpenLocation(Location),
+}
+
+pub struct ReferencesPanel {
+    focus_handle: FocusHandle,
+    /// Symbol the results were requested for, shown in the header
+    query: Option<String>,
+    groups: Vec<ReferenceGroup>,
+    rows: Vec<ReferenceRow>,
+    collapsed: HashSet<usize>,
+    project_root: Option<PathBuf>,
+    is_loading: bool,
+    scroll_handle: UniformListScrollHandle,
+}
+
+impl EventEmitter<ReferencesPanelEvent> for ReferencesPanel {}
+
+impl ReferencesPanel {
+    pub fn new(cx: &mut Context<Self>) -> Self {
+        Self {
+            focus_handle: cx.focus_handle(),
+            query: None,
+            groups: Vec::new(),
+            rows: Vec::new(),
+            collapsed: HashSet::new(),
+            project_root: None,
+            is_loading: false,
+            scroll_handle: UniformListScrollHandle::new(),
+        }
+    }
+
+    /// Used to display paths relative to the project
+    pub fn set_project_root(&mut self, root: Option<PathBuf>) {
+        self.project_root = root;
+    }
+
+    /// Clear previous results and show a loading state for a new search
+    pub fn begin_search(&mut self, query: Option<String>, cx: &mut Context<Self>) {
+        self.query = query;
+        self.groups.clear();
+        self.collapsed.clear();
+        self.rebuild_rows();
+        self.is_loading = true;
+        cx.notify();
+    }
+
+    pub fn set_results(&mut self, groups: Vec<ReferenceGroup>, cx: &mut Context<Self>) {
+        self.groups = groups;
+        self.collapsed.clear();
+        self.is_loading = false;
+        self.rebuild_rows();
+        self.scroll_handle.scroll_to_item(0, ScrollStrategy::Top);
+        cx.notify();
+    }
+
+    pub fn reference_count(&self) -> usize {
+        self.groups.iter().map(|group| group.entries.len()).sum()
+    }
+
+    fn rebuild_rows(&mut self) {
+        self.rows.clear();
+        for (group_ix, group) in self.groups.iter().enumerate() {
+            self.rows.push(ReferenceRow::File(group_ix));
+            if !self.collapsed.contains(&group_ix) {
+                self.rows
+                    .extend((0..group.entries.len()).map(|entry_ix| ReferenceRow::Entry(group_ix, entry_ix)));
+            }
+        }
+    }
+
+    fn toggle_group(&mut self, group_ix: usize, cx: &mut Context<Self>) {
+        if !self.collapsed.remove(&group_ix) {
+            self.collapsed.insert(group_ix);
+        }
+        self.rebuild_rows();
+        cx.notify();
+    }
+
+    fn display_path<'a>(&self, path: &'a Path) -> std::borrow::Cow<'a, str> {
+        self.project_root
+            .as_ref()
+            .and_then(|root| path.strip_prefix(root).ok())
+            .unwrap_or(path)
+            .to_string_lossy()
+    }
+
+    fn render_rows(&mut self, range: Range<usize>, cx: &mut Context<Self>) -> Vec<AnyElement> {
+        range
+            .filter_map(|row_ix| {
+                let row = *self.rows.get(row_ix)?;
+                Some(match row {
+                    ReferenceRow::File(group_ix) => self.render_file_row(row_ix, group_ix, cx),
+                    ReferenceRow::Entry(group_ix, entry_ix) => {
+                        self.render_entry_row(row_ix, group_ix, entry_ix, cx)
+                    }
+                })
+            })
+            .collect()
+    }
+
+    fn render_file_row(&self, row_ix: usize, group_ix: usize, cx: &mut Context<Self>) -> AnyElement {
+        let group = &self.groups[group_ix];
+        let collapsed = self.collapsed.contains(&group_ix);
+
+        h_flex()
+            .id(("reference-file", row_ix))
+            .h(px(24.))
+            .px_2()
+            .gap_1()
+            .cursor_pointer()
+            .hover(|style| style.bg(cx.theme().accent.opacity(0.1)))
+            .child(
+                Icon::new(if collapsed { IconName::ChevronRight } else { IconName::ChevronDown })
+                    .size_3()
+                    .text_color(cx.theme().muted_foreground),
+            )
+            .child(
+                div()
+                    .flex_1()
+                    .overflow_hidden()
+                    .text_ellipsis()
+                    .text_xs()
+                    .font_semibold()
+                    .text_color(cx.theme().foreground)
+                    .child(self.display_path(&group.path).to_string()),
+            )
+            .child(
+                div()
+                    .text_xs()
+                    .text_color(cx.theme().muted_foreground)
+                    .child(group.entries.len().to_string()),
+            )
+            .on_click(cx.listener(move |this, _, _window, cx| {
+                this.toggle_group(group_ix, cx);
+            }))
+            .into_any_element()
+    }
+
+    fn render_entry_row(
+        &self,
+        row_ix: usize,
+        group_ix: usize,
+        entry_ix: usize,
+        cx: &mut Context<Self>,
+    ) -> AnyElement {
+        let group = &self.groups[group_ix];
+        let entry = &group.entries[entry_ix];
+        let path = group.path.clone();
+        let (line, character) = (entry.line, entry.character);
+
+        h_flex()
+            .id(("reference-entry", row_ix))
+            .h(px(24.))
+            .pl_6()
+            .pr_2()
+            .gap_2()
+            .cursor_pointer()
+            .hover(|style| style.bg(cx.theme().accent.opacity(0.1)))
+            .child(
+                div()
+                    .min_w(px(36.))
+                    .text_xs()
+                    .text_color(cx.theme().muted_foreground)
+                    .child(format!("{}", line + 1)),
+            )
+            .child(
+                div()
+                    .flex_1()
+                    .overflow_hidden()
+                    .whitespace_nowrap()
+                    .text_ellipsis()
+                    .text_xs()
+                    .font_family("JetBrains Mono")
+                    .text_color(cx.theme().foreground)
+                    .child(entry.snippet.clone()),
+            )
+            .on_click(cx.listener(move |_this, _, _window, cx| {
+                cx.emit(ReferencesPanelEvent::OpenLocation(Location {
+                    path: path.clone(),
+                    line,
+                    character,
+                }));
+            }))
+            .into_any_element()
+    }
+}
+
+impl Focusable for ReferencesPanel {
+    fn focus_handle(&self, _cx: &App) -> FocusHandle {
+        self.focus_handle.clone()
+    }
+}
+
+impl Render for ReferencesPanel {
+    fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let count = self.reference_count();
+        let summary = if self.is_loading {
+            "Searching...".to_string()
+        } else {
+            format!(
+                "{} reference{} in {} file{}",
+                count,
+                if count == 1 { "" } else { "s" },
+                self.groups.len(),
+                if self.groups.len() == 1 { "" } else { "s" }
+            )
+        };
+
+        v_flex()
+            .size_full()
+            .track_focus(&self.focus_handle)
+            .child(
+                // Header
+                v_flex()
+                    .w_full()
+                    .px_4()
+                    .py_3()
+                    .gap_1()
+                    .border_b_1()
+                    .border_color(cx.theme().border)
+                    .child(
+                        div()
+                            .text_sm()
+                            .font_semibold()
+                            .text_color(cx.theme().foreground)
+                            .child(match &self.query {
+                                Some(query) => format!("References: {}", query),
+                                None => "References".to_string(),
+                            }),
+                    )
+                    .child(
+                        div()
+                            .text_xs()
+                            .text_color(cx.theme().muted_foreground)
+                            .child(summary),
+                    ),
+            )
+            .child(
+                div()
+                    .flex_1()
+                    .min_h_0()
+                    .when(self.rows.is_empty() && !self.is_loading, |content| {
+                        content.child(
+                            div()
+                                .p_4()
+                                .text_xs()
+                                .text_color(cx.theme().muted_foreground)
+                                .child("Press Shift+F12 on a symbol to find its references"),
+                        )
+                    })
+                    .when(!self.rows.is_empty(), |content| {
+                        // Only the rows in view are built, so hundreds of results stay cheap
+                        content.child(
+                            uniform_list(
+                                "references-list",
+                                self.rows.len(),
+                                cx.processor(|this, range: Range<usize>, _window, cx| {
+                                    this.render_rows(range, cx)
+                                }),
+                            )
+                            .track_scroll(self.scroll_handle.clone())
+                            .size_full(),
+                        )
+                    }),
+            )
+    }
+}
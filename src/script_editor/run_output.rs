@@ -0,0 +1,473 @@
+//! Running the current script: the command for its file type, and the
+//! output panel its stdout and stderr stream into
+//!
+//! Commands run through the shell in the script's folder, so a setting like
+//! `"runCommands": { "py": "uv run ${file}" }` can use pipes and `&&`.
+
+use gpui::{prelude::FluentBuilder, *};
+use parking_lot::Mutex;
+use std::io::{BufRead, BufReader, Read};
+use std::ops::Range;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use ui::{
+    button::{Button, ButtonVariants as _},
+    h_flex, v_flex, ActiveTheme as _, IconName, Sizable as _, StyledExt,
+};
+
+use super::ansi::{AnsiParser, StyledText};
+
+/// Lines kept in the panel; older ones are dropped as more arrive
+const MAX_OUTPUT_LINES: usize = 10_000;
+
+/// How often a script whose output has ended is checked for having exited
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How a script is run when the settings don't say, by file extension
+///
+/// `text` is the script itself: a Rust file with a cargo-script manifest or
+/// shebang runs through `cargo`, others are compiled with `rustc`.
+pub fn default_run_command(extension: &str, text: &str) -> Option<&'static str> {
+    Some(match extension {
+        "rs" if text.starts_with("---") || text.lines().next().is_some_and(|line| line.starts_with("#!") && line.contains("cargo")) => {
+            "cargo +nightly -Zscript ${file}"
+        }
+        "rs" => "rustc ${file} -o ${fileDirname}/${fileBasenameNoExtension} && ${fileDirname}/${fileBasenameNoExtension}",
+        "js" | "mjs" | "cjs" | "ts" => "node ${file}",
+        "py" => "${python} ${file}",
+        "lua" => "lua ${file}",
+        "go" => "go run ${file}",
+        "zig" => "zig run ${file}",
+        "sh" => "bash ${file}",
+        _ => return None,
+    })
+}
+
+/// `template` with the script's `${file}`, `${fileDirname}`,
+/// `${fileBasename}` and `${fileBasenameNoExtension}` filled in, quoted for
+/// the shell, and `${python}` with the interpreter Python files use
+pub fn expand_run_command(template: &str, path: &Path, python: &str) -> String {
+    let file_name = |name: Option<&std::ffi::OsStr>| name.map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    let directory = path.parent().map(|dir| dir.display().to_string()).unwrap_or_default();
+    // Longest names first, which the shorter ones are the start of
+    [
+        ("${fileBasenameNoExtension}", file_name(path.file_stem())),
+        ("${fileBasename}", file_name(path.file_name())),
+        ("${fileDirname}", directory),
+        ("${file}", path.display().to_string()),
+        ("${python}", python.to_string()),
+    ]
+    .iter()
+    .fold(template.to_string(), |command, (variable, value)| command.replace(variable, &shell_quote(value)))
+}
+
+/// `value` as one shell word, quoted only when it needs to be
+fn shell_quote(value: &str) -> String {
+    let plain = |ch: char| ch.is_alphanumeric() || "/\\._-+:=@%,".contains(ch);
+    if !value.is_empty() && value.chars().all(plain) {
+        value.to_string()
+    } else if cfg!(target_os = "windows") {
+        format!("\"{}\"", value)
+    } else {
+        format!("'{}'", value.replace('\'', r"'\''"))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+enum RunEvent {
+    Line(OutputStream, String),
+    /// The exit code, `None` if a signal ended it
+    Exited(Option<i32>),
+}
+
+struct OutputLine {
+    stream: OutputStream,
+    runs: Vec<StyledText>,
+}
+
+/// How the last run ended
+#[derive(Clone, Debug, PartialEq)]
+enum RunState {
+    Running,
+    Exited(Option<i32>),
+    Stopped,
+    FailedToStart(String),
+}
+
+struct RunningScript {
+    child: Arc<Mutex<Child>>,
+    _task: Task<()>,
+}
+
+impl Drop for RunningScript {
+    // Closing the editor shouldn't leave scripts running behind it
+    fn drop(&mut self) {
+        let mut child = self.child.lock();
+        if let Ok(None) = child.try_wait() {
+            kill_process_tree(&mut child);
+        }
+    }
+}
+
+pub enum OutputPanelEvent {
+    Close,
+}
+
+pub struct OutputPanel {
+    focus_handle: FocusHandle,
+    /// The command of the last run, shown in the header
+    command: Option<String>,
+    lines: Vec<OutputLine>,
+    /// Kept apart so one stream's colors don't leak into the other's lines
+    stdout_parser: AnsiParser,
+    stderr_parser: AnsiParser,
+    state: Option<RunState>,
+    started_at: Option<Instant>,
+    elapsed: Option<Duration>,
+    running: Option<RunningScript>,
+    scroll_handle: UniformListScrollHandle,
+}
+
+impl EventEmitter<OutputPanelEvent> for OutputPanel {}
+
+impl OutputPanel {
+    pub fn new(cx: &mut Context<Self>) -> Self {
+        Self {
+            focus_handle: cx.focus_handle(),
+            command: None,
+            lines: Vec::new(),
+            stdout_parser: AnsiParser::default(),
+            stderr_parser: AnsiParser::default(),
+            state: None,
+            started_at: None,
+            elapsed: None,
+            running: None,
+            scroll_handle: UniformListScrollHandle::new(),
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.is_some()
+    }
+
+    /// Run `command` through the shell in `cwd`, stopping the script still
+    /// running from before, if any, and clearing its output
+    pub fn run(&mut self, command: String, cwd: &Path, cx: &mut Context<Self>) {
+        self.stop(cx);
+        self.lines.clear();
+        self.stdout_parser = AnsiParser::default();
+        self.stderr_parser = AnsiParser::default();
+        self.elapsed = None;
+        self.started_at = Some(Instant::now());
+        self.command = Some(command.clone());
+
+        let (child, mut events) = match spawn_script(&command, cwd) {
+            Ok(spawned) => spawned,
+            Err(e) => {
+                self.state = Some(RunState::FailedToStart(e.to_string()));
+                cx.notify();
+                return;
+            }
+        };
+        self.state = Some(RunState::Running);
+        let task = cx.spawn(async move |this, cx| {
+            use futures::StreamExt as _;
+            while let Some(event) = events.next().await {
+                let done = this
+                    .update(cx, |this, cx| {
+                        let done = this.handle_event(event);
+                        cx.notify();
+                        done
+                    })
+                    .unwrap_or(true);
+                if done {
+                    break;
+                }
+            }
+        });
+        self.running = Some(RunningScript { child, _task: task });
+        cx.notify();
+    }
+
+    /// Kill the running script and whatever it started
+    pub fn stop(&mut self, cx: &mut Context<Self>) {
+        if self.running.take().is_none() {
+            return;
+        }
+        self.state = Some(RunState::Stopped);
+        self.elapsed = self.started_at.map(|started| started.elapsed());
+        cx.notify();
+    }
+
+    pub fn clear(&mut self, cx: &mut Context<Self>) {
+        self.lines.clear();
+        if !self.is_running() {
+            self.command = None;
+            self.state = None;
+        }
+        cx.notify();
+    }
+
+    /// Take in an event of the running script, returning whether it has exited
+    fn handle_event(&mut self, event: RunEvent) -> bool {
+        match event {
+            RunEvent::Line(stream, line) => {
+                let parser = match stream {
+                    OutputStream::Stdout => &mut self.stdout_parser,
+                    OutputStream::Stderr => &mut self.stderr_parser,
+                };
+                let runs = parser.parse_line(&line);
+                if self.lines.len() == MAX_OUTPUT_LINES {
+                    self.lines.remove(0);
+                }
+                self.lines.push(OutputLine { stream, runs });
+                self.scroll_handle.scroll_to_item(self.lines.len() - 1, ScrollStrategy::Top);
+                false
+            }
+            RunEvent::Exited(code) => {
+                self.running = None;
+                self.state = Some(RunState::Exited(code));
+                self.elapsed = self.started_at.map(|started| started.elapsed());
+                true
+            }
+        }
+    }
+
+    fn status(&self) -> Option<(String, Hsla)> {
+        let elapsed = self.elapsed.map_or_else(String::new, |elapsed| format!(" in {:.1}s", elapsed.as_secs_f32()));
+        self.state.as_ref().map(|state| match state {
+            RunState::Running => ("Running…".to_string(), gpui::blue()),
+            RunState::Exited(Some(0)) => (format!("Exited with code 0{}", elapsed), gpui::green()),
+            RunState::Exited(Some(code)) => (format!("Exited with code {}{}", code, elapsed), gpui::red()),
+            RunState::Exited(None) => (format!("Terminated by a signal{}", elapsed), gpui::red()),
+            RunState::Stopped => (format!("Stopped{}", elapsed), gpui::yellow()),
+            RunState::FailedToStart(e) => (format!("Failed to start: {}", e), gpui::red()),
+        })
+    }
+
+    fn render_lines(&self, range: Range<usize>, cx: &mut Context<Self>) -> Vec<AnyElement> {
+        range
+            .filter_map(|ix| {
+                let line = self.lines.get(ix)?;
+                let plain = match line.stream {
+                    OutputStream::Stdout => cx.theme().foreground,
+                    OutputStream::Stderr => cx.theme().danger,
+                };
+                Some(
+                    h_flex()
+                        .h(px(18.))
+                        .px_2()
+                        .whitespace_nowrap()
+                        .children(line.runs.iter().map(|run| {
+                            let style = run.style;
+                            div()
+                                .text_color(style.foreground.map_or(plain, |color| rgb(color).into()))
+                                .when_some(style.background, |span, color| span.bg(rgb(color)))
+                                .when(style.bold, |span| span.font_weight(FontWeight::BOLD))
+                                .when(style.dim, |span| span.opacity(0.6))
+                                .when(style.italic, |span| span.italic())
+                                .when(style.underline, |span| span.underline())
+                                .child(run.text.clone())
+                        }))
+                        .into_any_element(),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Start `command` in the platform's shell. Events come from a thread per
+/// output stream and one telling when the script has exited.
+fn spawn_script(command: &str, cwd: &Path) -> std::io::Result<(Arc<Mutex<Child>>, futures::channel::mpsc::UnboundedReceiver<RunEvent>)> {
+    let mut shell = if cfg!(target_os = "windows") {
+        let mut shell = Command::new("cmd");
+        shell.args(["/C", command]);
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.args(["-c", command]);
+        shell
+    };
+    shell.current_dir(cwd).stdin(Stdio::null()).stdout(Stdio::piped()).stderr(Stdio::piped());
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        // Its own process group, so stopping it also stops what it started
+        shell.process_group(0);
+    }
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+        shell.creation_flags(CREATE_NO_WINDOW);
+    }
+
+    let mut child = shell.spawn()?;
+    let (sender, receiver) = futures::channel::mpsc::unbounded();
+    let readers: Vec<_> = [
+        child.stdout.take().map(|out| (OutputStream::Stdout, Box::new(out) as Box<dyn Read + Send>)),
+        child.stderr.take().map(|err| (OutputStream::Stderr, Box::new(err) as Box<dyn Read + Send>)),
+    ]
+    .into_iter()
+    .flatten()
+    .map(|(stream, output)| {
+        let sender = sender.clone();
+        std::thread::spawn(move || {
+            let mut output = BufReader::new(output);
+            let mut line = Vec::new();
+            while output.read_until(b'\n', &mut line).is_ok_and(|read| read > 0) {
+                let text = String::from_utf8_lossy(&line);
+                let text = text.strip_suffix('\n').unwrap_or(&text);
+                if sender.unbounded_send(RunEvent::Line(stream, text.to_string())).is_err() {
+                    break;
+                }
+                line.clear();
+            }
+        })
+    })
+    .collect();
+
+    let pid = child.id();
+    let child = Arc::new(Mutex::new(child));
+    let waiter = child.clone();
+    std::thread::spawn(move || {
+        for reader in readers {
+            let _ = reader.join();
+        }
+        // A script can close its output and carry on; the lock is only held
+        // for a moment at a time so it can still be stopped meanwhile
+        let code = loop {
+            match waiter.lock().try_wait() {
+                Ok(Some(status)) => break status.code(),
+                Ok(None) => {}
+                Err(e) => {
+                    tracing::error!("⚠️  Lost track of script {}: {}", pid, e);
+                    break None;
+                }
+            }
+            std::thread::sleep(EXIT_POLL_INTERVAL);
+        };
+        let _ = sender.unbounded_send(RunEvent::Exited(code));
+    });
+    Ok((child, receiver))
+}
+
+fn kill_process_tree(child: &mut Child) {
+    let pid = child.id().to_string();
+    #[cfg(unix)]
+    let _ = Command::new("kill").args(["-TERM", &format!("-{}", pid)]).status();
+    #[cfg(target_os = "windows")]
+    let _ = Command::new("taskkill").args(["/T", "/F", "/PID", &pid]).status();
+    let _ = child.kill();
+}
+
+impl Focusable for OutputPanel {
+    fn focus_handle(&self, _cx: &App) -> FocusHandle {
+        self.focus_handle.clone()
+    }
+}
+
+impl Render for OutputPanel {
+    fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let status = self.status();
+        v_flex()
+            .size_full()
+            .bg(cx.theme().background)
+            .border_t_1()
+            .border_color(cx.theme().border)
+            .child(
+                h_flex()
+                    .w_full()
+                    .px_3()
+                    .py_1()
+                    .gap_3()
+                    .border_b_1()
+                    .border_color(cx.theme().border)
+                    .child(div().text_xs().font_semibold().text_color(cx.theme().foreground).child("Output"))
+                    .child(
+                        div()
+                            .flex_1()
+                            .overflow_hidden()
+                            .whitespace_nowrap()
+                            .text_ellipsis()
+                            .text_xs()
+                            .font_family("JetBrains Mono")
+                            .text_color(cx.theme().muted_foreground)
+                            .children(self.command.clone()),
+                    )
+                    .children(status.map(|(text, color)| div().text_xs().text_color(color).child(text)))
+                    .when(self.is_running(), |header| {
+                        header.child(
+                            Button::new("stop_script")
+                                .label("Stop")
+                                .ghost()
+                                .xsmall()
+                                .on_click(cx.listener(|this, _, _window, cx| this.stop(cx))),
+                        )
+                    })
+                    .child(
+                        Button::new("clear_output")
+                            .label("Clear")
+                            .ghost()
+                            .xsmall()
+                            .on_click(cx.listener(|this, _, _window, cx| this.clear(cx))),
+                    )
+                    .child(
+                        Button::new("close_output")
+                            .icon(IconName::Close)
+                            .tooltip("Close Output")
+                            .ghost()
+                            .xsmall()
+                            .on_click(cx.listener(|_this, _, _window, cx| cx.emit(OutputPanelEvent::Close))),
+                    ),
+            )
+            .child(
+                div()
+                    .flex_1()
+                    .min_h_0()
+                    .text_xs()
+                    .font_family("JetBrains Mono")
+                    .child(
+                        uniform_list(
+                            "output-lines",
+                            self.lines.len(),
+                            cx.processor(|this, range: Range<usize>, _window, cx| this.render_lines(range, cx)),
+                        )
+                        .track_scroll(self.scroll_handle.clone())
+                        .size_full(),
+                    ),
+            )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_run_command() {
+        let path = Path::new("/work/my scripts/tool.rs");
+        let template = default_run_command("rs", "fn main() {}").unwrap();
+        assert_eq!(
+            expand_run_command(template, path, "python"),
+            if cfg!(target_os = "windows") {
+                r#"rustc "/work/my scripts/tool.rs" -o "/work/my scripts"/tool && "/work/my scripts"/tool"#
+            } else {
+                "rustc '/work/my scripts/tool.rs' -o '/work/my scripts'/tool && '/work/my scripts'/tool"
+            }
+        );
+        assert_eq!(
+            expand_run_command(default_run_command("py", "").unwrap(), Path::new("/p/main.py"), "/p/.venv/bin/python"),
+            "/p/.venv/bin/python /p/main.py"
+        );
+        assert_eq!(default_run_command("rs", "---\n[dependencies]\n---\nfn main() {}"), Some("cargo +nightly -Zscript ${file}"));
+        assert_eq!(default_run_command("txt", ""), None);
+    }
+}
@@ -0,0 +1,137 @@
+//! Text search used by the find/replace bar
+
+use regex::{Regex, RegexBuilder};
+use std::ops::Range;
+
+/// Matches beyond this are not collected, keeping huge files responsive
+pub const MAX_MATCHES: usize = 10_000;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SearchQuery {
+    pub pattern: String,
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+    /// Treat `pattern` as a regular expression, and `$1`-style groups in the replacement
+    pub regex: bool,
+}
+
+impl SearchQuery {
+    /// Compile the query; literal patterns are escaped
+    pub fn build(&self) -> Result<Regex, regex::Error> {
+        let mut pattern = if self.regex {
+            self.pattern.clone()
+        } else {
+            regex::escape(&self.pattern)
+        };
+        if self.whole_word {
+            pattern = format!(r"\b(?:{})\b", pattern);
+        }
+        RegexBuilder::new(&pattern)
+            .case_insensitive(!self.case_sensitive)
+            .multi_line(true)
+            .build()
+    }
+
+    /// Text that replaces `matched` (a range found with `regex` in `text`)
+    pub fn replacement(&self, regex: &Regex, text: &str, matched: Range<usize>, replace: &str) -> String {
+        if !self.regex {
+            return replace.to_string();
+        }
+        match regex.captures_at(text, matched.start) {
+            Some(captures) if captures.get(0).map(|m| m.range()) == Some(matched) => {
+                let mut expanded = String::new();
+                captures.expand(replace, &mut expanded);
+                expanded
+            }
+            _ => replace.to_string(),
+        }
+    }
+
+    /// Replace every match, returning the new text and the number of replacements
+    pub fn replace_all(&self, regex: &Regex, text: &str, replace: &str) -> (String, usize) {
+        let count = regex.find_iter(text).count();
+        let replaced = if self.regex {
+            regex.replace_all(text, replace)
+        } else {
+            regex.replace_all(text, regex::NoExpand(replace))
+        };
+        (replaced.into_owned(), count)
+    }
+}
+
+/// Byte ranges of matches in `text`, at most `limit` of them.
+///
+/// Empty matches (e.g. `^` or `\b`) are reported once per position; the
+/// iterator steps past them so the search always makes progress.
+pub fn find_matches(regex: &Regex, text: &str, limit: usize) -> Vec<Range<usize>> {
+    regex.find_iter(text).take(limit).map(|m| m.range()).collect()
+}
+
+/// Index of the first match at or after `offset`, wrapping to the first match
+pub fn match_at_or_after(matches: &[Range<usize>], offset: usize) -> Option<usize> {
+    if matches.is_empty() {
+        return None;
+    }
+    Some(matches.partition_point(|m| m.start < offset) % matches.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(pattern: &str) -> SearchQuery {
+        SearchQuery {
+            pattern: pattern.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_literal_and_whole_word() {
+        let text = "let a.b = ab; let A.B";
+        let regex = query("a.b").build().unwrap();
+        assert_eq!(find_matches(&regex, text, MAX_MATCHES), vec![4..7, 18..21]);
+
+        let regex = SearchQuery {
+            whole_word: true,
+            case_sensitive: true,
+            ..query("ab")
+        }
+        .build()
+        .unwrap();
+        assert_eq!(find_matches(&regex, "ab abc ab", MAX_MATCHES), vec![0..2, 7..9]);
+    }
+
+    #[test]
+    fn test_zero_width_matches() {
+        let regex = SearchQuery { regex: true, ..query("^") }.build().unwrap();
+        assert_eq!(find_matches(&regex, "a\nb\n", MAX_MATCHES), vec![0..0, 2..2, 4..4]);
+        assert_eq!(find_matches(&regex, "a\nb\n", 2).len(), 2);
+    }
+
+    #[test]
+    fn test_replacement_with_groups() {
+        let search = SearchQuery {
+            regex: true,
+            ..query(r"(\w+)=(\d+)")
+        };
+        let regex = search.build().unwrap();
+        let text = "x=1, y=2";
+        assert_eq!(search.replacement(&regex, text, 0..3, "$2=$1"), "1=x");
+        assert_eq!(search.replace_all(&regex, text, "${1}: $2"), ("x: 1, y: 2".to_string(), 2));
+
+        // Literal mode never expands `$`
+        let literal = query("x");
+        let regex = literal.build().unwrap();
+        assert_eq!(literal.replace_all(&regex, "x", "$0"), ("$0".to_string(), 1));
+    }
+
+    #[test]
+    fn test_match_at_or_after() {
+        let matches = vec![2..3, 8..9];
+        assert_eq!(match_at_or_after(&matches, 0), Some(0));
+        assert_eq!(match_at_or_after(&matches, 5), Some(1));
+        assert_eq!(match_at_or_after(&matches, 9), Some(0));
+        assert_eq!(match_at_or_after(&[], 0), None);
+    }
+}
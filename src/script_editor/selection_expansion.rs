@@ -0,0 +1,144 @@
+//! Growing and shrinking the selection one syntactic step at a time
+//!
+//! Language servers with `textDocument/selectionRange` say what the steps
+//! are. Without one, the steps are the word, the inside of each enclosing
+//! bracket pair, the pair itself, and the lines these span, which in most
+//! languages goes word → expression → statement → block → function.
+
+use std::ops::Range;
+
+use super::brackets::BracketPairs;
+
+/// Selections grown from, so shrinking retraces the same steps
+#[derive(Clone, Debug, Default)]
+pub struct SelectionHistory {
+    /// Text version the selections belong to
+    version: i32,
+    smaller: Vec<Range<usize>>,
+    /// The selection last grown to
+    current: Option<Range<usize>>,
+}
+
+impl SelectionHistory {
+    /// Record growing from `from` to `to`. Growing from anything but the last
+    /// selection grown to, or after an edit, starts a new history.
+    pub fn grow(&mut self, version: i32, from: Range<usize>, to: Range<usize>) {
+        if self.version != version || self.current.as_ref() != Some(&from) {
+            self.smaller.clear();
+            self.version = version;
+        }
+        self.smaller.push(from);
+        self.current = Some(to);
+    }
+
+    /// The selection `current` was grown from, if it is the last one grown to
+    pub fn shrink(&mut self, version: i32, current: &Range<usize>) -> Option<Range<usize>> {
+        if self.version != version || self.current.as_ref() != Some(current) {
+            return None;
+        }
+        let previous = self.smaller.pop()?;
+        self.current = Some(previous.clone());
+        Some(previous)
+    }
+}
+
+/// The smallest of `candidates` that contains `selection` and is larger than it
+pub fn smallest_enclosing(
+    candidates: impl IntoIterator<Item = Range<usize>>,
+    selection: &Range<usize>,
+) -> Option<Range<usize>> {
+    candidates
+        .into_iter()
+        .filter(|candidate| {
+            candidate.start <= selection.start && selection.end <= candidate.end && candidate.len() > selection.len()
+        })
+        .min_by_key(|candidate| candidate.len())
+}
+
+/// The next step out from `selection` by words, brackets and lines
+pub fn expand_heuristically(text: &str, pairs: &BracketPairs, selection: &Range<usize>) -> Option<Range<usize>> {
+    let mut candidates = Vec::new();
+    candidates.extend(word_range(text, selection.start));
+    for (open, close) in pairs.surrounding(selection.clone()) {
+        candidates.push(trimmed(text, open + 1..close));
+        candidates.push(open..close + 1);
+        // The block with what leads up to it, e.g. a function's signature
+        candidates.push(trimmed_lines(text, open..close + 1));
+    }
+    candidates.push(trimmed_lines(text, selection.clone()));
+    candidates.push(0..text.len());
+    smallest_enclosing(candidates, selection)
+}
+
+fn word_range(text: &str, offset: usize) -> Option<Range<usize>> {
+    let is_word = |ch: char| ch.is_alphanumeric() || ch == '_';
+    let start = text[..offset]
+        .char_indices()
+        .rev()
+        .take_while(|(_, ch)| is_word(*ch))
+        .last()
+        .map_or(offset, |(ix, _)| ix);
+    let end = text[offset..]
+        .char_indices()
+        .find(|(_, ch)| !is_word(*ch))
+        .map_or(text.len(), |(ix, _)| offset + ix);
+    (start < end).then_some(start..end)
+}
+
+/// `range` without the whitespace it starts and ends with
+pub(super) fn trimmed(text: &str, range: Range<usize>) -> Range<usize> {
+    let inner = &text[range.clone()];
+    let start = range.start + (inner.len() - inner.trim_start().len());
+    let end = (range.start + inner.trim_end().len()).max(start);
+    start..end
+}
+
+/// The lines `range` touches, without their indentation and line break
+fn trimmed_lines(text: &str, range: Range<usize>) -> Range<usize> {
+    let start = text[..range.start].rfind('\n').map_or(0, |ix| ix + 1);
+    let end = text[range.end..].find('\n').map_or(text.len(), |ix| range.end + ix);
+    trimmed(text, start..end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_heuristically() {
+        let text = "fn f() {\n    foo(a, bar);\n}\n";
+        let pairs = BracketPairs::new(text);
+        let mut selection = 21..21;
+        let mut steps = Vec::new();
+        while let Some(next) = expand_heuristically(text, &pairs, &selection) {
+            steps.push(&text[next.clone()]);
+            selection = next;
+        }
+        assert_eq!(
+            steps,
+            [
+                "bar",
+                "a, bar",
+                "(a, bar)",
+                "foo(a, bar);",
+                "{\n    foo(a, bar);\n}",
+                "fn f() {\n    foo(a, bar);\n}",
+                text,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_shrink_retraces_growth() {
+        let mut history = SelectionHistory::default();
+        history.grow(1, 5..5, 4..7);
+        history.grow(1, 4..7, 0..9);
+        assert_eq!(history.shrink(1, &(0..9)), Some(4..7));
+        assert_eq!(history.shrink(1, &(4..7)), Some(5..5));
+        assert_eq!(history.shrink(1, &(5..5)), None);
+
+        // After an edit the old selections mean nothing
+        history.grow(1, 5..5, 4..7);
+        assert_eq!(history.shrink(2, &(4..7)), None);
+    }
+}
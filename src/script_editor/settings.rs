@@ -0,0 +1,406 @@
+//! User settings of the script editor
+//!
+//! Stored as JSON in the user's config directory, e.g.
+//! `{ "autoSave": "afterDelay", "autoSaveDelay": 1000 }`.
+//!
+//! JSON schemas can be associated with files by name, e.g.
+//! `"jsonSchemas": [{ "fileMatch": ["package.json"], "url": "https://json.schemastore.org/package" }]`.
+//!
+//! Language servers can be chosen per language id, e.g.
+//! `"languageServers": { "python": { "command": "pyright-langserver", "args": ["--stdio"] } }`.
+//!
+//! Run Script's command can be replaced per file extension, e.g.
+//! `"runCommands": { "ts": "deno run ${file}" }`.
+//!
+//! What saving does to whitespace can differ per language id, e.g.
+//! `"saveCleanup": { "markdown": { "trimFinalNewlines": false } }`.
+//!
+//! Keybindings follow a preset, e.g. `"keymap": "vimLite"`, and then the
+//! user's `keymap.json` next to this file.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use super::keymap::KeymapPreset;
+use super::large_files::DEFAULT_LARGE_FILE_THRESHOLD_MB;
+use super::sticky_scroll::DEFAULT_STICKY_SCROLL_DEPTH;
+use super::whitespace::SaveCleanup;
+
+/// When open files are saved without being asked to
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AutoSaveMode {
+    #[default]
+    Off,
+    /// `autoSaveDelay` milliseconds after the last edit
+    AfterDelay,
+    /// When the editor loses focus
+    OnFocusChange,
+    /// When the window loses focus
+    OnWindowBlur,
+}
+
+impl AutoSaveMode {
+    pub const ALL: [AutoSaveMode; 4] = [
+        AutoSaveMode::Off,
+        AutoSaveMode::AfterDelay,
+        AutoSaveMode::OnFocusChange,
+        AutoSaveMode::OnWindowBlur,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            AutoSaveMode::Off => "Off",
+            AutoSaveMode::AfterDelay => "After Delay",
+            AutoSaveMode::OnFocusChange => "On Focus Change",
+            AutoSaveMode::OnWindowBlur => "On Window Blur",
+        }
+    }
+}
+
+/// A JSON schema applied to files whose path matches one of `file_match`
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JsonSchemaAssociation {
+    /// Glob patterns, e.g. `package.json` or `*.schema.json`
+    pub file_match: Vec<String>,
+    pub url: String,
+}
+
+/// How to launch the language server for one language in place of the
+/// built-in choice; what's left out stays as the built-in server has it.
+/// `${VAR}` in the command, arguments and folder is replaced by the
+/// environment variable's value.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LanguageServerSettings {
+    /// Executable name or path
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<String>,
+    /// Arguments; those of the built-in server are kept only for its own command
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub args: Option<Vec<String>>,
+    /// Environment variables set for the server, on top of the editor's own
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Folder the server runs in instead of the workspace root, relative to it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<PathBuf>,
+    /// Sent as `initializationOptions` with `initialize`, in place of the
+    /// built-in server's own
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub initialization_options: Option<serde_json::Value>,
+}
+
+/// What saving does to one language's files, over the editor-wide choices;
+/// anything left out stays as those have it
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LanguageSaveCleanup {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trim_trailing_whitespace: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub insert_final_newline: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trim_final_newlines: Option<bool>,
+    /// Have the language server format the file before it's written
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format_on_save: Option<bool>,
+    /// Have the language server sort and prune imports before formatting; off unless set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub organize_imports_on_save: Option<bool>,
+}
+
+impl LanguageSaveCleanup {
+    pub fn apply(&self, cleanup: SaveCleanup) -> SaveCleanup {
+        SaveCleanup {
+            trim_trailing_whitespace: self.trim_trailing_whitespace.unwrap_or(cleanup.trim_trailing_whitespace),
+            insert_final_newline: self.insert_final_newline.unwrap_or(cleanup.insert_final_newline),
+            trim_final_newlines: self.trim_final_newlines.unwrap_or(cleanup.trim_final_newlines),
+        }
+    }
+}
+
+/// Whether files in `language` are formatted on save when their save cleanup
+/// doesn't say: those whose formatter everyone runs anyway
+pub fn formats_on_save_by_default(language: &str) -> bool {
+    matches!(language, "rust" | "go")
+}
+
+/// How long lines are wrapped onto the following visual lines
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum WrapMode {
+    #[default]
+    Off,
+    /// At the right edge of the editor
+    Viewport,
+    /// At a fixed column, or the right edge when the editor is narrower
+    Column(u32),
+}
+
+impl WrapMode {
+    pub fn is_off(&self) -> bool {
+        *self == WrapMode::Off
+    }
+
+    pub fn label(self) -> String {
+        match self {
+            WrapMode::Off => "Off".to_string(),
+            WrapMode::Viewport => "Viewport Width".to_string(),
+            WrapMode::Column(column) => format!("Column {}", column),
+        }
+    }
+}
+
+/// Which spaces and tabs are drawn as dots and arrows
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RenderWhitespace {
+    #[default]
+    None,
+    /// All but single spaces between words
+    Boundary,
+    All,
+    /// Only whitespace at the end of lines
+    Trailing,
+}
+
+impl RenderWhitespace {
+    pub const ALL: [RenderWhitespace; 4] = [
+        RenderWhitespace::None,
+        RenderWhitespace::Boundary,
+        RenderWhitespace::All,
+        RenderWhitespace::Trailing,
+    ];
+
+    pub fn is_none(&self) -> bool {
+        *self == RenderWhitespace::None
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            RenderWhitespace::None => "None",
+            RenderWhitespace::Boundary => "Boundary",
+            RenderWhitespace::All => "All",
+            RenderWhitespace::Trailing => "Trailing",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct EditorSettings {
+    pub auto_save: AutoSaveMode,
+    /// Milliseconds after the last edit, for `AutoSaveMode::AfterDelay`
+    pub auto_save_delay: u64,
+    /// Schemas for JSON files without a `$schema` field of their own
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub json_schemas: Vec<JsonSchemaAssociation>,
+    /// Name of a built-in syntax theme, or path of a theme file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub syntax_theme: Option<String>,
+    #[serde(skip_serializing_if = "WrapMode::is_off")]
+    pub word_wrap: WrapMode,
+    #[serde(skip_serializing_if = "RenderWhitespace::is_none")]
+    pub render_whitespace: RenderWhitespace,
+    /// Highlight the cursor's line and bold its number
+    #[serde(skip_serializing_if = "is_true")]
+    pub highlight_current_line: bool,
+    /// Remove whitespace at the end of lines when saving (except in Markdown)
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub trim_trailing_whitespace: bool,
+    /// End saved files with a line break, as rustfmt leaves them
+    #[serde(skip_serializing_if = "is_true")]
+    pub insert_final_newline: bool,
+    /// Drop the blank lines after the last one when saving
+    #[serde(skip_serializing_if = "is_true")]
+    pub trim_final_newlines: bool,
+    /// Save cleanup choices by language id, over the ones above
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub save_cleanup: BTreeMap<String, LanguageSaveCleanup>,
+    /// Save a file that formats on save as it is when formatting fails,
+    /// rather than not at all
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub save_when_formatting_fails: bool,
+    /// Show types and parameter names from the language server between the code
+    #[serde(skip_serializing_if = "is_true")]
+    pub inlay_hints: bool,
+    /// Servers to launch instead of the built-in ones, by language id
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub language_servers: BTreeMap<String, LanguageServerSettings>,
+    /// Shell commands that run scripts, by file extension
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub run_commands: BTreeMap<String, String>,
+    /// What dropping a file from the explorer inserts, by language id:
+    /// `{path}`, `{name}`, `{stem}` and `{module}` stand for the file
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub drop_templates: BTreeMap<String, String>,
+    /// Enclosing scopes' first lines pinned above the view at most, `0` for none
+    #[serde(skip_serializing_if = "is_default_sticky_scroll_depth")]
+    pub sticky_scroll_max_depth: usize,
+    #[serde(skip_serializing_if = "KeymapPreset::is_default")]
+    pub keymap: KeymapPreset,
+    /// Vim's normal, insert and visual modes and `:` commands
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub vim_mode: bool,
+    /// Files larger than this many megabytes ask before opening, and can be opened read-only
+    #[serde(skip_serializing_if = "is_default_large_file_threshold")]
+    pub large_file_threshold_mb: u64,
+}
+
+impl Default for EditorSettings {
+    fn default() -> Self {
+        Self {
+            auto_save: AutoSaveMode::Off,
+            auto_save_delay: 1000,
+            json_schemas: Vec::new(),
+            syntax_theme: None,
+            word_wrap: WrapMode::Off,
+            render_whitespace: RenderWhitespace::None,
+            highlight_current_line: true,
+            trim_trailing_whitespace: false,
+            insert_final_newline: true,
+            trim_final_newlines: true,
+            save_cleanup: BTreeMap::new(),
+            save_when_formatting_fails: false,
+            inlay_hints: true,
+            language_servers: BTreeMap::new(),
+            run_commands: BTreeMap::new(),
+            drop_templates: BTreeMap::new(),
+            sticky_scroll_max_depth: DEFAULT_STICKY_SCROLL_DEPTH,
+            keymap: KeymapPreset::Default,
+            vim_mode: false,
+            large_file_threshold_mb: DEFAULT_LARGE_FILE_THRESHOLD_MB,
+        }
+    }
+}
+
+impl EditorSettings {
+    /// Settings from the user's config directory (defaults if missing or unreadable)
+    pub fn load() -> Self {
+        default_settings_path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) {
+        let Some(path) = default_settings_path() else {
+            return;
+        };
+        let result = serde_json::to_string_pretty(self)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(&path, json)?;
+                Ok(())
+            });
+        if let Err(e) = result {
+            tracing::error!("⚠️  Failed to save editor settings to {:?}: {}", path, e);
+        }
+    }
+}
+
+fn is_true(value: &bool) -> bool {
+    *value
+}
+
+fn is_default_large_file_threshold(threshold_mb: &u64) -> bool {
+    *threshold_mb == DEFAULT_LARGE_FILE_THRESHOLD_MB
+}
+
+fn is_default_sticky_scroll_depth(depth: &usize) -> bool {
+    *depth == DEFAULT_STICKY_SCROLL_DEPTH
+}
+
+/// `<config dir>/pulsar/script_editor/settings.json`
+fn default_settings_path() -> Option<PathBuf> {
+    config_file("settings.json")
+}
+
+/// `<config dir>/pulsar/script_editor/<name>`
+pub(super) fn config_file(name: &str) -> Option<PathBuf> {
+    let base = if cfg!(target_os = "windows") {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        std::env::var_os("HOME").map(|home| PathBuf::from(home).join("Library/Application Support"))
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+    }?;
+    Some(base.join("pulsar").join("script_editor").join(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_json() {
+        let settings: EditorSettings = serde_json::from_str(r#"{ "autoSave": "onWindowBlur" }"#).unwrap();
+        assert_eq!(settings.auto_save, AutoSaveMode::OnWindowBlur);
+        // Missing fields keep their defaults
+        assert_eq!(settings.auto_save_delay, 1000);
+
+        let json = serde_json::to_string(&EditorSettings {
+            auto_save: AutoSaveMode::AfterDelay,
+            auto_save_delay: 500,
+            json_schemas: Vec::new(),
+            syntax_theme: None,
+            word_wrap: WrapMode::Off,
+            render_whitespace: RenderWhitespace::None,
+            highlight_current_line: true,
+            trim_trailing_whitespace: false,
+            insert_final_newline: true,
+            trim_final_newlines: true,
+            save_cleanup: BTreeMap::new(),
+            save_when_formatting_fails: false,
+            inlay_hints: true,
+            language_servers: BTreeMap::new(),
+            run_commands: BTreeMap::new(),
+            drop_templates: BTreeMap::new(),
+            sticky_scroll_max_depth: DEFAULT_STICKY_SCROLL_DEPTH,
+            keymap: KeymapPreset::Default,
+            vim_mode: false,
+            large_file_threshold_mb: DEFAULT_LARGE_FILE_THRESHOLD_MB,
+        })
+        .unwrap();
+        assert_eq!(json, r#"{"autoSave":"afterDelay","autoSaveDelay":500}"#);
+    }
+
+    #[test]
+    fn test_save_cleanup_json() {
+        let settings: EditorSettings = serde_json::from_str(
+            r#"{ "trimTrailingWhitespace": true, "saveCleanup": { "text": { "insertFinalNewline": false, "formatOnSave": true } } }"#,
+        )
+        .unwrap();
+        let defaults = SaveCleanup {
+            trim_trailing_whitespace: settings.trim_trailing_whitespace,
+            insert_final_newline: settings.insert_final_newline,
+            trim_final_newlines: settings.trim_final_newlines,
+        };
+        assert_eq!(
+            settings.save_cleanup["text"].apply(defaults),
+            SaveCleanup {
+                trim_trailing_whitespace: true,
+                insert_final_newline: false,
+                trim_final_newlines: true,
+            }
+        );
+        assert_eq!(settings.save_cleanup["text"].format_on_save, Some(true));
+        assert!(!settings.save_when_formatting_fails);
+    }
+
+    #[test]
+    fn test_word_wrap_json() {
+        let settings: EditorSettings = serde_json::from_str(r#"{ "wordWrap": { "column": 100 } }"#).unwrap();
+        assert_eq!(settings.word_wrap, WrapMode::Column(100));
+        let settings: EditorSettings = serde_json::from_str(r#"{ "wordWrap": "viewport" }"#).unwrap();
+        assert_eq!(settings.word_wrap, WrapMode::Viewport);
+    }
+}
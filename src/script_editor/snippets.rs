@@ -0,0 +1,499 @@
+//! Snippets: templates expanded from a short prefix with Tab
+//!
+//! Bodies use the LSP snippet syntax: `$1`, `${2:placeholder}`,
+//! `${3|one,two|}` and `$0` for where the cursor ends up. A tab stop used
+//! more than once is mirrored, so typing into it updates every copy.
+//!
+//! Besides the built-in ones, snippets are read from `snippets.json` beside
+//! the settings, by language id, e.g.
+//! `{ "rust": { "Test": { "prefix": "test", "body": ["#[test]", "fn ${1:name}() {", "\t$0", "}"] } } }`.
+
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+use std::path::PathBuf;
+
+/// A snippet that can be expanded in files of one language
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snippet {
+    pub prefix: String,
+    /// LSP snippet syntax, lines separated by `\n` and indented with `\t`
+    pub body: String,
+    pub description: String,
+}
+
+/// Text of an expanded snippet and its tab stops in Tab order, `$0` last
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParsedSnippet {
+    pub text: String,
+    /// Byte ranges of `text` for each stop: the first one, then its mirrors
+    pub stops: Vec<Vec<Range<usize>>>,
+}
+
+/// Expand `body`, putting `indent` after each of its line breaks and `unit`
+/// for each `\t`, like the line the snippet is inserted on.
+///
+/// Placeholders and the first choice of a choice stop become the stop's text,
+/// which its mirrors repeat. Variables (`$TM_FILENAME`, `${VAR:default}`)
+/// resolve to their default and transforms are left out.
+pub fn parse_snippet(body: &str, indent: &str, unit: &str) -> ParsedSnippet {
+    let mut parser = Parser {
+        chars: body.chars().collect(),
+        ix: 0,
+        text: String::with_capacity(body.len()),
+        stops: BTreeMap::new(),
+        indent,
+        unit,
+    };
+    parser.parse(false);
+
+    let final_stop = parser.stops.remove(&0);
+    let end = parser.text.len();
+    let mut stops: Vec<Vec<Range<usize>>> = parser.stops.into_values().collect();
+    stops.push(final_stop.unwrap_or_else(|| vec![end..end]));
+    ParsedSnippet {
+        text: parser.text,
+        stops,
+    }
+}
+
+struct Parser<'a> {
+    chars: Vec<char>,
+    ix: usize,
+    text: String,
+    stops: BTreeMap<u32, Vec<Range<usize>>>,
+    indent: &'a str,
+    unit: &'a str,
+}
+
+impl Parser<'_> {
+    fn parse(&mut self, nested: bool) {
+        while let Some(&ch) = self.chars.get(self.ix) {
+            match ch {
+                '\\' if self.ix + 1 < self.chars.len() => {
+                    self.push(self.chars[self.ix + 1]);
+                    self.ix += 2;
+                }
+                '}' if nested => return,
+                '$' => {
+                    self.ix += 1;
+                    if self.chars.get(self.ix) == Some(&'{') {
+                        self.ix += 1;
+                        self.group();
+                    } else {
+                        let name = self.name();
+                        if let Ok(index) = name.parse() {
+                            self.mirror(index);
+                        } else if name.is_empty() {
+                            self.text.push('$');
+                        }
+                    }
+                }
+                _ => {
+                    self.push(ch);
+                    self.ix += 1;
+                }
+            }
+        }
+    }
+
+    fn push(&mut self, ch: char) {
+        match ch {
+            '\n' => {
+                self.text.push('\n');
+                self.text.push_str(self.indent);
+            }
+            '\t' => self.text.push_str(self.unit),
+            _ => self.text.push(ch),
+        }
+    }
+
+    fn name(&mut self) -> String {
+        let start = self.ix;
+        while self.chars.get(self.ix).is_some_and(|ch| ch.is_alphanumeric() || *ch == '_') {
+            self.ix += 1;
+        }
+        self.chars[start..self.ix].iter().collect()
+    }
+
+    /// A stop without text of its own: a copy of the stop's text so far
+    fn mirror(&mut self, index: u32) {
+        let start = self.text.len();
+        if let Some(first) = self.stops.get(&index).and_then(|ranges| ranges.first()).cloned() {
+            let copy = self.text[first].to_string();
+            self.text.push_str(&copy);
+        }
+        self.stops.entry(index).or_default().push(start..self.text.len());
+    }
+
+    /// The body of a `${...}` group, leaving `ix` after its closing brace
+    fn group(&mut self) {
+        let index = self.name().parse::<u32>().ok();
+        let start = self.text.len();
+        match self.chars.get(self.ix) {
+            Some(':') => {
+                self.ix += 1;
+                self.parse(true);
+            }
+            Some('|') => {
+                self.ix += 1;
+                self.first_choice();
+            }
+            // A transform: not a place to type
+            Some('/') => {
+                self.skip_group();
+                return;
+            }
+            // `${1}` is `$1`
+            _ => {
+                self.skip_group();
+                if let Some(index) = index {
+                    self.mirror(index);
+                }
+                return;
+            }
+        }
+        self.skip_group();
+        if let Some(index) = index {
+            // The placeholder goes first, as the text its mirrors copy
+            self.stops.entry(index).or_default().insert(0, start..self.text.len());
+        }
+    }
+
+    fn first_choice(&mut self) {
+        let mut first_choice = true;
+        while self.ix < self.chars.len() && !(self.chars[self.ix] == '|' && self.chars.get(self.ix + 1) == Some(&'}')) {
+            match self.chars[self.ix] {
+                ',' => first_choice = false,
+                '\\' if self.ix + 1 < self.chars.len() => {
+                    self.ix += 1;
+                    if first_choice {
+                        self.text.push(self.chars[self.ix]);
+                    }
+                }
+                ch if first_choice => self.text.push(ch),
+                _ => {}
+            }
+            self.ix += 1;
+        }
+        // Skip the closing `|`
+        self.ix += 1;
+    }
+
+    /// Skip anything left in the group (e.g. transforms) up to its closing brace
+    fn skip_group(&mut self) {
+        let mut depth = 0;
+        while let Some(&ch) = self.chars.get(self.ix) {
+            self.ix += 1;
+            match ch {
+                '\\' => self.ix += 1,
+                '{' => depth += 1,
+                '}' if depth == 0 => return,
+                '}' => depth -= 1,
+                _ => {}
+            }
+        }
+    }
+}
+
+/// An expanded snippet being filled in, its stops kept on their text as it's edited
+#[derive(Clone, Debug)]
+pub struct SnippetSession {
+    stops: Vec<Vec<Range<usize>>>,
+    current: usize,
+}
+
+impl SnippetSession {
+    /// Start at the first stop of `snippet`, inserted at `offset`
+    pub fn new(snippet: &ParsedSnippet, offset: usize) -> Self {
+        let stops = snippet
+            .stops
+            .iter()
+            .map(|ranges| ranges.iter().map(|range| range.start + offset..range.end + offset).collect())
+            .collect();
+        Self { stops, current: 0 }
+    }
+
+    /// Range of the current stop to select
+    pub fn current(&self) -> Range<usize> {
+        self.stops[self.current][0].clone()
+    }
+
+    /// Whether the current stop is the final one, where the session ends
+    pub fn at_end(&self) -> bool {
+        self.current + 1 == self.stops.len()
+    }
+
+    /// Move to the next or previous stop; returns its range to select
+    pub fn step(&mut self, forward: bool) -> Range<usize> {
+        self.current = if forward {
+            (self.current + 1).min(self.stops.len() - 1)
+        } else {
+            self.current.saturating_sub(1)
+        };
+        self.current()
+    }
+
+    /// Whether a cursor at `offset` is still within the snippet
+    pub fn contains(&self, offset: usize) -> bool {
+        let ranges = self.stops.iter().flatten();
+        let start = ranges.clone().map(|range| range.start).min().unwrap_or(0);
+        let end = ranges.map(|range| range.end).max().unwrap_or(0);
+        (start..=end).contains(&offset)
+    }
+
+    /// Keep the stops on their text after `start..old_end` was replaced by
+    /// `start..new_end`. Returns false when the edit cut across a stop, which
+    /// ends the session.
+    pub fn edit(&mut self, start: usize, old_end: usize, new_end: usize) -> bool {
+        // The current stop's copy being typed into, if any
+        let edited = self.stops[self.current]
+            .iter()
+            .find(|range| range.start <= start && old_end <= range.end)
+            .cloned();
+        self.remap(edited, start, old_end, new_end)
+    }
+
+    fn remap(&mut self, edited: Option<Range<usize>>, start: usize, old_end: usize, new_end: usize) -> bool {
+        let shift = |offset: usize| offset + new_end - old_end;
+        for (index, ranges) in self.stops.iter_mut().enumerate() {
+            for range in ranges {
+                // The edited copy and the placeholders around it grow with it
+                let encloses_edited = edited.as_ref().is_some_and(|edited| {
+                    let encloses = range.start <= edited.start && edited.end <= range.end;
+                    encloses && (index == self.current || range != edited)
+                });
+                if encloses_edited {
+                    range.end = shift(range.end);
+                } else if range.start >= old_end {
+                    *range = shift(range.start)..shift(range.end);
+                } else if range.end > start {
+                    if range.start <= start && old_end <= range.end {
+                        range.end = shift(range.end);
+                    } else {
+                        return false;
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    /// Edits of `text` (sorted) that copy the current stop's text into its
+    /// mirrors, with the stops already moved to where the edits leave them
+    pub fn mirror_edits(&mut self, text: &str) -> Vec<(Range<usize>, String)> {
+        let Some(source) = text.get(self.current()) else {
+            return Vec::new();
+        };
+        let source = source.to_string();
+        let mut edits: Vec<(Range<usize>, String)> = self.stops[self.current][1..]
+            .iter()
+            .filter(|range| text.get((*range).clone()) != Some(source.as_str()))
+            .map(|range| (range.clone(), source.clone()))
+            .collect();
+        edits.sort_by_key(|(range, _)| range.start);
+        // Applied from the last, so each one only moves what comes after it
+        for (range, new_text) in edits.iter().rev() {
+            self.remap(Some(range.clone()), range.start, range.end, range.start + new_text.len());
+        }
+        edits
+    }
+}
+
+/// The built-in snippets and the user's, by language id
+#[derive(Clone, Debug, Default)]
+pub struct SnippetLibrary {
+    by_language: HashMap<String, Vec<Snippet>>,
+}
+
+/// A snippet as written in `snippets.json`
+#[derive(Deserialize)]
+struct SnippetDefinition {
+    prefix: OneOrMany,
+    body: OneOrMany,
+    #[serde(default)]
+    description: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl SnippetLibrary {
+    /// The built-in snippets, with the user's from their config directory
+    /// taking precedence (defaults alone if missing or unreadable)
+    pub fn load() -> Self {
+        let mut library = Self::builtin();
+        if let Some(json) = user_snippets_path().and_then(|path| std::fs::read_to_string(path).ok()) {
+            if let Err(e) = library.add_json(&json) {
+                tracing::error!("⚠️  Failed to read user snippets: {}", e);
+            }
+        }
+        library
+    }
+
+    pub fn builtin() -> Self {
+        let mut library = Self::default();
+        for (language, prefix, body, description) in BUILTIN_SNIPPETS {
+            library.add(language, Snippet {
+                prefix: prefix.to_string(),
+                body: body.to_string(),
+                description: description.to_string(),
+            });
+        }
+        library
+    }
+
+    /// Add snippets in the `snippets.json` format, replacing built-in ones with the same prefix
+    pub fn add_json(&mut self, json: &str) -> Result<(), serde_json::Error> {
+        let languages: BTreeMap<String, BTreeMap<String, SnippetDefinition>> = serde_json::from_str(json)?;
+        for (language, snippets) in languages {
+            for (name, definition) in snippets {
+                let body = match definition.body {
+                    OneOrMany::One(body) => body,
+                    OneOrMany::Many(lines) => lines.join("\n"),
+                };
+                let prefixes = match definition.prefix {
+                    OneOrMany::One(prefix) => vec![prefix],
+                    OneOrMany::Many(prefixes) => prefixes,
+                };
+                let description = if definition.description.is_empty() { name } else { definition.description };
+                for prefix in prefixes {
+                    self.add(&language, Snippet {
+                        prefix,
+                        body: body.clone(),
+                        description: description.clone(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn add(&mut self, language: &str, snippet: Snippet) {
+        let snippets = self.by_language.entry(language.to_string()).or_default();
+        snippets.retain(|existing| existing.prefix != snippet.prefix);
+        snippets.push(snippet);
+    }
+
+    /// The snippet of `language` whose prefix ends `before_cursor`, with the
+    /// longest such prefix that starts a word
+    pub fn find(&self, language: &str, before_cursor: &str) -> Option<&Snippet> {
+        self.by_language
+            .get(language)?
+            .iter()
+            .filter(|snippet| !snippet.prefix.is_empty() && before_cursor.ends_with(&snippet.prefix))
+            .filter(|snippet| {
+                let rest = &before_cursor[..before_cursor.len() - snippet.prefix.len()];
+                let word_char = |ch: char| ch.is_alphanumeric() || ch == '_';
+                // `fn` mustn't expand at the end of `myfn`
+                !(rest.ends_with(word_char) && snippet.prefix.starts_with(word_char))
+            })
+            .max_by_key(|snippet| snippet.prefix.len())
+    }
+}
+
+/// `<config dir>/pulsar/script_editor/snippets.json`
+pub fn user_snippets_path() -> Option<PathBuf> {
+    super::settings::config_file("snippets.json")
+}
+
+/// Language id, prefix, body and description
+const BUILTIN_SNIPPETS: &[(&str, &str, &str, &str)] = &[
+    ("rust", "fn", "fn ${1:name}(${2}) {\n\t$0\n}", "Function"),
+    ("rust", "pfn", "pub fn ${1:name}(${2}) -> ${3:()} {\n\t$0\n}", "Public function"),
+    ("rust", "struct", "struct ${1:Name} {\n\t$0\n}", "Struct"),
+    ("rust", "enum", "enum ${1:Name} {\n\t$0\n}", "Enum"),
+    ("rust", "impl", "impl ${1:Type} {\n\t$0\n}", "Impl block"),
+    ("rust", "match", "match ${1:value} {\n\t${2:_} => $0,\n}", "Match"),
+    ("rust", "iflet", "if let ${1:Some(value)} = ${2:option} {\n\t$0\n}", "If let"),
+    ("rust", "for", "for ${1:item} in ${2:iter} {\n\t$0\n}", "For loop"),
+    ("rust", "test", "#[test]\nfn ${1:name}() {\n\t$0\n}", "Test function"),
+    (
+        "rust",
+        "tests",
+        "#[cfg(test)]\nmod tests {\n\tuse super::*;\n\n\t#[test]\n\tfn ${1:name}() {\n\t\t$0\n\t}\n}",
+        "Test module",
+    ),
+    ("python", "def", "def ${1:name}(${2}):\n\t${0:pass}", "Function"),
+    ("python", "class", "class ${1:Name}:\n\tdef __init__(self${2}):\n\t\t${0:pass}", "Class"),
+    ("python", "for", "for ${1:item} in ${2:items}:\n\t${0:pass}", "For loop"),
+    ("python", "try", "try:\n\t${1:pass}\nexcept ${2:Exception} as ${3:e}:\n\t${0:raise}", "Try/except"),
+    ("python", "ifmain", "if __name__ == \"__main__\":\n\t${0:main()}", "Main guard"),
+    ("javascript", "function", "function ${1:name}(${2}) {\n\t$0\n}", "Function"),
+    ("javascript", "arrow", "const ${1:name} = (${2}) => {\n\t$0\n};", "Arrow function"),
+    ("javascript", "for", "for (let ${1:i} = 0; $1 < ${2:count}; $1++) {\n\t$0\n}", "For loop"),
+    ("javascript", "log", "console.log($0);", "Log to the console"),
+    ("typescript", "function", "function ${1:name}(${2}): ${3:void} {\n\t$0\n}", "Function"),
+    ("typescript", "interface", "interface ${1:Name} {\n\t$0\n}", "Interface"),
+    ("typescript", "for", "for (let ${1:i} = 0; $1 < ${2:count}; $1++) {\n\t$0\n}", "For loop"),
+    ("typescript", "log", "console.log($0);", "Log to the console"),
+    ("lua", "function", "function ${1:name}(${2})\n\t$0\nend", "Function"),
+    ("lua", "for", "for ${1:i} = ${2:1}, ${3:n} do\n\t$0\nend", "Numeric for loop"),
+    ("lua", "forp", "for ${1:key}, ${2:value} in pairs(${3:table}) do\n\t$0\nend", "For over pairs"),
+    ("go", "func", "func ${1:name}(${2}) ${3:error} {\n\t$0\n}", "Function"),
+    ("go", "iferr", "if err != nil {\n\treturn ${1:err}\n}", "Return on error"),
+    ("go", "for", "for ${1:i} := 0; $1 < ${2:n}; $1++ {\n\t$0\n}", "For loop"),
+    ("c", "main", "int main(int argc, char *argv[]) {\n\t$0\n\treturn 0;\n}", "Main function"),
+    ("c", "for", "for (int ${1:i} = 0; $1 < ${2:n}; $1++) {\n\t$0\n}", "For loop"),
+    ("c", "inc", "#include <${1:stdio.h}>", "Include"),
+    ("cpp", "main", "int main(int argc, char *argv[]) {\n\t$0\n\treturn 0;\n}", "Main function"),
+    ("cpp", "for", "for (int ${1:i} = 0; $1 < ${2:n}; $1++) {\n\t$0\n}", "For loop"),
+    ("cpp", "class", "class ${1:Name} {\npublic:\n\t$1();\n\t$0\n};", "Class"),
+    ("zig", "fn", "fn ${1:name}(${2}) ${3:void} {\n\t$0\n}", "Function"),
+    ("zig", "test", "test \"${1:name}\" {\n\t$0\n}", "Test"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_snippet() {
+        let snippet = parse_snippet("for ${1:i} in ${2:0..n} {\n\t$0\n}", "    ", "    ");
+        assert_eq!(snippet.text, "for i in 0..n {\n        \n    }");
+        assert_eq!(snippet.stops, vec![vec![4..5], vec![9..13], vec![24..24]]);
+
+        // Mirrors copy the placeholder; without `$0` the snippet ends at its end
+        let snippet = parse_snippet("${1:x} = $1 + ${2|a,b|} \\$", "", "\t");
+        assert_eq!(snippet.text, "x = x + a $");
+        assert_eq!(snippet.stops, vec![vec![0..1, 4..5], vec![8..9], vec![11..11]]);
+    }
+
+    #[test]
+    fn test_snippet_plain_text() {
+        let plain = |body| parse_snippet(body, "", "\t").text;
+        assert_eq!(plain("println!(\"$1\")$0"), "println!(\"\")");
+        assert_eq!(plain("fn ${1:name}(${2:args}) {}"), "fn name(args) {}");
+        assert_eq!(plain("${1:outer ${2:inner}}"), "outer inner");
+        assert_eq!(plain("${1|one,two|}"), "one");
+        assert_eq!(plain("cost: \\$5"), "cost: $5");
+        assert_eq!(plain("${TM_FILENAME/(.*)/$1/}"), "");
+    }
+
+    #[test]
+    fn test_session_mirrors_typing() {
+        let snippet = parse_snippet("let ${1:a} = $1; $0", "", "\t");
+        assert_eq!(snippet.text, "let a = a; ");
+        let mut session = SnippetSession::new(&snippet, 2);
+        assert_eq!(session.current(), 6..7);
+
+        // "a" typed over with "abc"
+        assert!(session.edit(6, 7, 9));
+        let mut text = "  let abc = a; ".to_string();
+        let edits = session.mirror_edits(&text);
+        assert_eq!(edits, vec![(12..13, "abc".to_string())]);
+        for (range, new_text) in edits.iter().rev() {
+            text.replace_range(range.clone(), new_text);
+        }
+        assert_eq!(text, "  let abc = abc; ");
+        assert_eq!(session.step(true), 17..17);
+        assert!(session.at_end());
+
+        // An edit across the stops ends the session
+        assert!(!session.edit(5, 13, 5));
+    }
+}
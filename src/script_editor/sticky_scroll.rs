@@ -0,0 +1,93 @@
+//! Sticky scroll: the first lines of the scopes around the top of the view,
+//! pinned above it
+//!
+//! Scopes are document symbols that have a body (functions, classes, impls,
+//! ...), or the file's foldable ranges when there are no symbols.
+
+use lsp_types::SymbolKind;
+
+use super::folding::FoldRange;
+use super::lsp::SymbolNode;
+
+/// Sticky lines shown at most when the settings don't say
+pub const DEFAULT_STICKY_SCROLL_DEPTH: usize = 5;
+
+/// Line ranges of the symbols that enclose code, outer ones before the ones inside them
+pub fn symbol_scopes(symbols: &[SymbolNode]) -> Vec<FoldRange> {
+    fn collect(symbols: &[SymbolNode], scopes: &mut Vec<FoldRange>) {
+        for symbol in symbols {
+            let encloses = matches!(
+                symbol.kind,
+                SymbolKind::MODULE
+                    | SymbolKind::NAMESPACE
+                    | SymbolKind::CLASS
+                    | SymbolKind::STRUCT
+                    | SymbolKind::ENUM
+                    | SymbolKind::INTERFACE
+                    | SymbolKind::FUNCTION
+                    | SymbolKind::METHOD
+                    | SymbolKind::CONSTRUCTOR
+                    | SymbolKind::OBJECT
+            );
+            if encloses && symbol.range.end.line > symbol.range.start.line {
+                scopes.push(FoldRange {
+                    start: symbol.range.start.line,
+                    end: symbol.range.end.line,
+                });
+            }
+            collect(&symbol.children, scopes);
+        }
+    }
+
+    let mut scopes = Vec::new();
+    collect(symbols, &mut scopes);
+    scopes
+}
+
+/// Header lines to pin, outermost first, with `top_line` the first line in view.
+///
+/// Each pinned line covers a row of the view, so the next header is looked
+/// for around the line below it. A scope is pinned while it continues below
+/// the rows pinned so far.
+pub fn sticky_lines(scopes: &[FoldRange], top_line: u32, max_depth: usize) -> Vec<u32> {
+    let mut lines: Vec<u32> = Vec::new();
+    while lines.len() < max_depth {
+        let covered = top_line + lines.len() as u32;
+        let next = scopes
+            .iter()
+            .filter(|scope| scope.start < covered && scope.end > covered)
+            .filter(|scope| lines.last().is_none_or(|&last| scope.start > last))
+            .min_by_key(|scope| scope.start);
+        match next {
+            Some(scope) => lines.push(scope.start),
+            None => break,
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nested_scopes() {
+        // impl 0..=20 { fn 2..=10 { loop 4..=8 } fn 12..=19 }
+        let scopes = [
+            FoldRange { start: 0, end: 20 },
+            FoldRange { start: 2, end: 10 },
+            FoldRange { start: 4, end: 8 },
+            FoldRange { start: 12, end: 19 },
+        ];
+        assert_eq!(sticky_lines(&scopes, 0, 5), Vec::<u32>::new());
+        assert_eq!(sticky_lines(&scopes, 1, 5), vec![0]);
+        // Line 2 is under the row pinning line 0, so its scope is pinned too
+        assert_eq!(sticky_lines(&scopes, 2, 5), vec![0, 2]);
+        assert_eq!(sticky_lines(&scopes, 5, 5), vec![0, 2, 4]);
+        assert_eq!(sticky_lines(&scopes, 5, 2), vec![0, 2]);
+        // The loop's end would sit under the third pinned row, so it isn't pinned
+        assert_eq!(sticky_lines(&scopes, 6, 5), vec![0, 2]);
+        assert_eq!(sticky_lines(&scopes, 14, 5), vec![0, 12]);
+        assert_eq!(sticky_lines(&scopes, 25, 5), Vec::<u32>::new());
+    }
+}
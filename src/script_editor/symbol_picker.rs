@@ -0,0 +1,309 @@
+//! "Go to Symbol in Workspace": symbols from every running language server
+//!
+//! The query goes to the servers after a short pause in typing; whatever they
+//! returned last is fuzzy-filtered locally in the meantime so the list keeps
+//! up with every keystroke.
+
+use futures::future::join_all;
+use gpui::{prelude::FluentBuilder, *};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use ui::{
+    h_flex,
+    input::{InputEvent, InputState, TextInput},
+    v_flex, ActiveTheme as _, Sizable as _, StyledExt,
+};
+
+use super::command_palette::{fuzzy_match, FuzzyMatch};
+use super::lsp::{merge_workspace_symbols, workspace_symbol_items, LanguageServer, WorkspaceSymbolItem};
+use super::outline_panel::kind_glyph;
+use super::location::Location;
+
+/// How long typing must pause before the query is sent to the servers
+const QUERY_DELAY: Duration = Duration::from_millis(200);
+
+pub enum SymbolPickerEvent {
+    OpenLocation(Location),
+    Dismissed,
+}
+
+pub struct SymbolPicker {
+    focus_handle: FocusHandle,
+    query_input: Entity<InputState>,
+    servers: Vec<Arc<LanguageServer>>,
+    root: PathBuf,
+    items: Vec<WorkspaceSymbolItem>,
+    /// Item index and match for every item matching the query, best first
+    matches: Vec<(usize, FuzzyMatch)>,
+    selected: usize,
+    /// Whether a query is waiting for (or out at) the servers
+    searching: bool,
+    query_task: Option<Task<()>>,
+    scroll_handle: UniformListScrollHandle,
+    _subscription: Subscription,
+}
+
+impl EventEmitter<SymbolPickerEvent> for SymbolPicker {}
+
+impl SymbolPicker {
+    pub fn new(servers: Vec<Arc<LanguageServer>>, root: PathBuf, window: &mut Window, cx: &mut Context<Self>) -> Self {
+        let query_input = cx.new(|cx| InputState::new(window, cx).placeholder("Search symbols in workspace"));
+        let subscription = cx.subscribe_in(&query_input, window, |this, _input, event: &InputEvent, _window, cx| {
+            match event {
+                InputEvent::Change => {
+                    this.update_matches(cx);
+                    this.schedule_query(cx);
+                }
+                InputEvent::PressEnter { .. } => this.confirm(cx),
+                InputEvent::Blur => cx.emit(SymbolPickerEvent::Dismissed),
+                _ => {}
+            }
+        });
+        query_input.update(cx, |input, cx| input.focus(window, cx));
+
+        let mut picker = Self {
+            focus_handle: cx.focus_handle(),
+            query_input,
+            servers,
+            root,
+            items: Vec::new(),
+            matches: Vec::new(),
+            selected: 0,
+            searching: false,
+            query_task: None,
+            scroll_handle: UniformListScrollHandle::new(),
+            _subscription: subscription,
+        };
+        // Servers answer an empty query with whatever they consider notable
+        picker.schedule_query(cx);
+        picker
+    }
+
+    /// Send the current query to every server once typing pauses, merging the answers
+    fn schedule_query(&mut self, cx: &mut Context<Self>) {
+        if self.servers.is_empty() {
+            return;
+        }
+        let query = self.query_input.read(cx).value().trim().to_string();
+        let servers = self.servers.clone();
+        let root = self.root.clone();
+        self.searching = true;
+        self.query_task = Some(cx.spawn(async move |this, cx| {
+            cx.background_executor().timer(QUERY_DELAY).await;
+            let requests = servers.iter().map(|server| {
+                let root = root.clone();
+                let request = server.request::<lsp_types::request::WorkspaceSymbolRequest>(
+                    lsp_types::WorkspaceSymbolParams {
+                        query: query.clone(),
+                        work_done_progress_params: Default::default(),
+                        partial_result_params: Default::default(),
+                    },
+                );
+                async move {
+                    match request.response().await {
+                        Ok(response) => response
+                            .map(|response| workspace_symbol_items(response, &root))
+                            .unwrap_or_default(),
+                        Err(e) => {
+                            tracing::debug!("⚠️  Workspace symbols unavailable: {:#}", e);
+                            Vec::new()
+                        }
+                    }
+                }
+            });
+            let items = merge_workspace_symbols(join_all(requests).await);
+
+            this.update(cx, |this, cx| {
+                this.items = items;
+                this.searching = false;
+                this.update_matches(cx);
+            })
+            .ok();
+        }));
+        cx.notify();
+    }
+
+    fn update_matches(&mut self, cx: &mut Context<Self>) {
+        let query = self.query_input.read(cx).value();
+        self.matches = self
+            .items
+            .iter()
+            .enumerate()
+            .filter_map(|(ix, item)| Some((ix, fuzzy_match(&query, &item.name)?)))
+            .collect();
+        // Stable, so equally good matches keep the servers' order
+        self.matches.sort_by_key(|(_, fuzzy)| std::cmp::Reverse(fuzzy.score));
+        self.selected = 0;
+        self.scroll_handle.scroll_to_item(0, ScrollStrategy::Top);
+        cx.notify();
+    }
+
+    fn move_selection(&mut self, forward: bool, cx: &mut Context<Self>) {
+        if self.matches.is_empty() {
+            return;
+        }
+        let count = self.matches.len();
+        self.selected = if forward {
+            (self.selected + 1) % count
+        } else {
+            (self.selected + count - 1) % count
+        };
+        self.scroll_handle.scroll_to_item(self.selected, ScrollStrategy::Top);
+        cx.notify();
+    }
+
+    fn confirm(&mut self, cx: &mut Context<Self>) {
+        if let Some((item_ix, _)) = self.matches.get(self.selected) {
+            let item = &self.items[*item_ix];
+            cx.emit(SymbolPickerEvent::OpenLocation(Location {
+                path: item.path.clone(),
+                line: item.position.line,
+                character: item.position.character,
+            }));
+        }
+    }
+
+    /// `src/main.rs:12`, relative to the project root
+    fn location_label(&self, item: &WorkspaceSymbolItem) -> String {
+        let path = item.path.strip_prefix(&self.root).unwrap_or(&item.path);
+        format!("{}:{}", path.display(), item.position.line + 1)
+    }
+
+    fn render_rows(&mut self, range: Range<usize>, cx: &mut Context<Self>) -> Vec<AnyElement> {
+        range
+            .filter_map(|row_ix| {
+                let (item_ix, fuzzy) = self.matches.get(row_ix)?;
+                let item = &self.items[*item_ix];
+                let (glyph, is_type) = kind_glyph(item.kind);
+                let highlight = HighlightStyle {
+                    color: Some(cx.theme().accent),
+                    font_weight: Some(FontWeight::BOLD),
+                    ..Default::default()
+                };
+                let highlights = fuzzy.positions.iter().filter_map(|&offset| {
+                    let len = item.name[offset..].chars().next()?.len_utf8();
+                    Some((offset..offset + len, highlight))
+                });
+                let name = StyledText::new(item.name.clone()).with_highlights(highlights);
+
+                Some(
+                    h_flex()
+                        .id(("workspace-symbol", row_ix))
+                        .h(px(28.))
+                        .px_3()
+                        .gap_2()
+                        .cursor_pointer()
+                        .rounded(cx.theme().radius)
+                        .when(row_ix == self.selected, |row| row.bg(cx.theme().accent.opacity(0.2)))
+                        .hover(|style| style.bg(cx.theme().accent.opacity(0.1)))
+                        .child(
+                            div()
+                                .w(px(14.))
+                                .text_xs()
+                                .font_semibold()
+                                .text_color(if is_type { cx.theme().warning } else { cx.theme().info })
+                                .child(glyph),
+                        )
+                        .child(
+                            div()
+                                .text_sm()
+                                .font_family("JetBrains Mono")
+                                .text_color(cx.theme().foreground)
+                                .child(name),
+                        )
+                        .children(item.container.clone().map(|container| {
+                            div()
+                                .text_xs()
+                                .text_color(cx.theme().muted_foreground)
+                                .child(container)
+                        }))
+                        .child(
+                            div()
+                                .flex_1()
+                                .overflow_hidden()
+                                .whitespace_nowrap()
+                                .text_ellipsis()
+                                .text_right()
+                                .text_xs()
+                                .text_color(cx.theme().muted_foreground)
+                                .child(self.location_label(item)),
+                        )
+                        .on_click(cx.listener(move |this, _, _window, cx| {
+                            this.selected = row_ix;
+                            this.confirm(cx);
+                        }))
+                        .into_any_element(),
+                )
+            })
+            .collect()
+    }
+}
+
+impl Focusable for SymbolPicker {
+    fn focus_handle(&self, _cx: &App) -> FocusHandle {
+        self.focus_handle.clone()
+    }
+}
+
+impl Render for SymbolPicker {
+    fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let status = if self.servers.is_empty() {
+            "No running language server supports workspace symbols"
+        } else if self.searching && self.matches.is_empty() {
+            "Searching…"
+        } else {
+            "No matching symbols"
+        };
+
+        v_flex()
+            .w(px(620.))
+            .p_1()
+            .gap_1()
+            .bg(cx.theme().popover)
+            .border_1()
+            .border_color(cx.theme().border)
+            .rounded(cx.theme().radius)
+            .shadow_lg()
+            .track_focus(&self.focus_handle)
+            // Arrow keys move through the list instead of the input's cursor
+            .capture_key_down(cx.listener(|this, event: &KeyDownEvent, _window, cx| {
+                match event.keystroke.key.as_str() {
+                    "up" => this.move_selection(false, cx),
+                    "down" => this.move_selection(true, cx),
+                    "escape" => cx.emit(SymbolPickerEvent::Dismissed),
+                    _ => return,
+                }
+                cx.stop_propagation();
+            }))
+            .child(TextInput::new(&self.query_input).small())
+            .child(
+                div()
+                    .h(px(28. * self.matches.len().clamp(1, 12) as f32))
+                    .when(self.matches.is_empty(), |content| {
+                        content.child(
+                            div()
+                                .px_3()
+                                .py_1()
+                                .text_xs()
+                                .text_color(cx.theme().muted_foreground)
+                                .child(status),
+                        )
+                    })
+                    .when(!self.matches.is_empty(), |content| {
+                        content.child(
+                            uniform_list(
+                                "workspace-symbol-list",
+                                self.matches.len(),
+                                cx.processor(|this, range: Range<usize>, _window, cx| {
+                                    this.render_rows(range, cx)
+                                }),
+                            )
+                            .track_scroll(self.scroll_handle.clone())
+                            .size_full(),
+                        )
+                    }),
+            )
+    }
+}
@@ -0,0 +1,377 @@
+//! Colors of highlighted code and the editor around it
+//!
+//! A theme maps token kinds (`keyword`, `string`, `comment.doc`, ...) to
+//! styles and also colors the gutter, selection, current line and
+//! diagnostics. Besides the built-in presets, themes load from VS Code theme
+//! files or from JSON or TOML in this layout:
+//!
+//! ```toml
+//! name = "My Theme"
+//! dark = true
+//!
+//! [editor]
+//! background = "#1e1e1e"
+//! foreground = "#d4d4d4"
+//! gutter = "#858585"
+//! activeGutter = "#c6c6c6"
+//! currentLine = "#282828"
+//! selection = "#264f78"
+//!
+//! [tokens]
+//! keyword = { color = "#569cd6" }
+//! comment = { color = "#6a9955", italic = true }
+//! ```
+
+use anyhow::{anyhow, Context as _};
+use gpui::{App, Hsla};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use super::vscode_theme::from_vscode_theme;
+
+/// Kinds only semantic tokens color, each styled like a broader kind when a
+/// theme leaves it out: mutable variables, macros and lifetimes or labels
+const SEMANTIC_KINDS: &[(&str, &str)] = &[("variable.special", "variable"), ("preproc", "function"), ("label", "keyword")];
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EditorColors {
+    pub background: String,
+    pub foreground: String,
+    /// Line numbers
+    pub gutter: String,
+    /// The line number of the cursor's line
+    pub active_gutter: String,
+    pub current_line: String,
+    pub selection: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DiagnosticColors {
+    pub error: String,
+    pub warning: String,
+    pub info: String,
+    pub hint: String,
+}
+
+impl Default for DiagnosticColors {
+    fn default() -> Self {
+        Self {
+            error: "#f14c4c".to_string(),
+            warning: "#cca700".to_string(),
+            info: "#3794ff".to_string(),
+            hint: "#a0a0a0".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TokenStyle {
+    pub color: String,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub italic: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub bold: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SyntaxTheme {
+    pub name: String,
+    #[serde(default)]
+    pub dark: bool,
+    pub editor: EditorColors,
+    #[serde(default)]
+    pub diagnostics: DiagnosticColors,
+    /// Token kind to its style; kinds left out use the editor foreground
+    pub tokens: BTreeMap<String, TokenStyle>,
+}
+
+/// Token styles of a preset: kind, color, italic, bold
+type PresetTokens<'a> = &'a [(&'a str, &'a str, bool, bool)];
+
+impl SyntaxTheme {
+    pub fn dark_plus() -> Self {
+        Self::preset(
+            "Dark+",
+            true,
+            ["#1e1e1e", "#d4d4d4", "#858585", "#c6c6c6", "#282828", "#264f78"],
+            ["#f14c4c", "#cca700", "#3794ff", "#a0a0a0"],
+            &[
+                ("attribute", "#9cdcfe", false, false),
+                ("boolean", "#569cd6", false, false),
+                ("comment", "#6a9955", true, false),
+                ("comment.doc", "#6a9955", true, false),
+                ("constant", "#4fc1ff", false, false),
+                ("constructor", "#4ec9b0", false, false),
+                ("function", "#dcdcaa", false, false),
+                ("keyword", "#569cd6", false, false),
+                ("link_uri", "#3794ff", false, false),
+                ("number", "#b5cea8", false, false),
+                ("operator", "#d4d4d4", false, false),
+                ("property", "#9cdcfe", false, false),
+                ("punctuation", "#d4d4d4", false, false),
+                ("string", "#ce9178", false, false),
+                ("string.escape", "#d7ba7d", false, false),
+                ("tag", "#569cd6", false, false),
+                ("title", "#569cd6", false, true),
+                ("type", "#4ec9b0", false, false),
+                ("variable", "#9cdcfe", false, false),
+            ],
+        )
+    }
+
+    pub fn light_plus() -> Self {
+        Self::preset(
+            "Light+",
+            false,
+            ["#ffffff", "#000000", "#237893", "#0b216f", "#f5f5f5", "#add6ff"],
+            ["#e51400", "#bf8803", "#1a85ff", "#6c6c6c"],
+            &[
+                ("attribute", "#001080", false, false),
+                ("boolean", "#0000ff", false, false),
+                ("comment", "#008000", true, false),
+                ("comment.doc", "#008000", true, false),
+                ("constant", "#0070c1", false, false),
+                ("constructor", "#267f99", false, false),
+                ("function", "#795e26", false, false),
+                ("keyword", "#0000ff", false, false),
+                ("link_uri", "#0000ff", false, false),
+                ("number", "#098658", false, false),
+                ("operator", "#000000", false, false),
+                ("property", "#001080", false, false),
+                ("punctuation", "#000000", false, false),
+                ("string", "#a31515", false, false),
+                ("string.escape", "#ee0000", false, false),
+                ("tag", "#800000", false, false),
+                ("title", "#800000", false, true),
+                ("type", "#267f99", false, false),
+                ("variable", "#001080", false, false),
+            ],
+        )
+    }
+
+    pub fn solarized() -> Self {
+        Self::preset(
+            "Solarized",
+            true,
+            ["#002b36", "#839496", "#586e75", "#93a1a1", "#073642", "#274642"],
+            ["#dc322f", "#b58900", "#268bd2", "#2aa198"],
+            &[
+                ("attribute", "#6c71c4", false, false),
+                ("boolean", "#b58900", false, false),
+                ("comment", "#586e75", true, false),
+                ("comment.doc", "#586e75", true, false),
+                ("constant", "#cb4b16", false, false),
+                ("constructor", "#b58900", false, false),
+                ("function", "#268bd2", false, false),
+                ("keyword", "#859900", false, false),
+                ("link_uri", "#6c71c4", false, false),
+                ("number", "#d33682", false, false),
+                ("operator", "#859900", false, false),
+                ("property", "#268bd2", false, false),
+                ("punctuation", "#839496", false, false),
+                ("string", "#2aa198", false, false),
+                ("string.escape", "#dc322f", false, false),
+                ("tag", "#268bd2", false, false),
+                ("title", "#cb4b16", false, true),
+                ("type", "#b58900", false, false),
+                ("variable", "#839496", false, false),
+            ],
+        )
+    }
+
+    pub fn presets() -> Vec<SyntaxTheme> {
+        vec![Self::dark_plus(), Self::light_plus(), Self::solarized()]
+    }
+
+    fn preset(name: &str, dark: bool, editor: [&str; 6], diagnostics: [&str; 4], tokens: PresetTokens) -> Self {
+        let [background, foreground, gutter, active_gutter, current_line, selection] = editor.map(str::to_string);
+        let [error, warning, info, hint] = diagnostics.map(str::to_string);
+        Self {
+            name: name.to_string(),
+            dark,
+            editor: EditorColors {
+                background,
+                foreground,
+                gutter,
+                active_gutter,
+                current_line,
+                selection,
+            },
+            diagnostics: DiagnosticColors { error, warning, info, hint },
+            tokens: tokens
+                .iter()
+                .map(|&(kind, color, italic, bold)| {
+                    let style = TokenStyle {
+                        color: color.to_string(),
+                        italic,
+                        bold,
+                    };
+                    (kind.to_string(), style)
+                })
+                .collect(),
+        }
+    }
+
+    /// A preset by name (ignoring case), or else a theme file at that path
+    pub fn resolve(name_or_path: &str) -> anyhow::Result<Self> {
+        Self::presets()
+            .into_iter()
+            .find(|preset| preset.name.eq_ignore_ascii_case(name_or_path))
+            .map_or_else(|| Self::load(Path::new(name_or_path)), Ok)
+    }
+
+    /// A theme from a `.json` or `.toml` file; a JSON file with `tokenColors` is read as a VS Code theme
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        let theme: Self = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => toml::from_str(&text)?,
+            Some("json") if text.contains("\"tokenColors\"") => from_vscode_theme(&text)?,
+            Some("json") => serde_json::from_str(&text)?,
+            _ => return Err(anyhow!("{} is not a .json or .toml theme", path.display())),
+        };
+        theme.validate()?;
+        Ok(theme)
+    }
+
+    /// Check every color is a `#rrggbb` or `#rrggbbaa` hex color
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let editor = &self.editor;
+        let diagnostics = &self.diagnostics;
+        let named = [
+            ("editor.background", &editor.background),
+            ("editor.foreground", &editor.foreground),
+            ("editor.gutter", &editor.gutter),
+            ("editor.activeGutter", &editor.active_gutter),
+            ("editor.currentLine", &editor.current_line),
+            ("editor.selection", &editor.selection),
+            ("diagnostics.error", &diagnostics.error),
+            ("diagnostics.warning", &diagnostics.warning),
+            ("diagnostics.info", &diagnostics.info),
+            ("diagnostics.hint", &diagnostics.hint),
+        ];
+        let tokens = self.tokens.iter().map(|(kind, style)| (kind.as_str(), &style.color));
+        for (name, color) in named.into_iter().chain(tokens) {
+            if parse_hex(color).is_none() {
+                return Err(anyhow!("{}: {:?} is not a #rrggbb color", name, color));
+            }
+        }
+        Ok(())
+    }
+
+    /// Make this the theme of every editor, repainting them
+    pub fn apply(&self, cx: &mut App) -> anyhow::Result<()> {
+        let highlight_theme: ui::highlighter::HighlightTheme = serde_json::from_value(self.highlight_theme_json())?;
+        let selection = parse_hex(&self.editor.selection).map(hex_to_hsla);
+
+        let theme = ui::Theme::global_mut(cx);
+        theme.highlight_theme = Arc::new(highlight_theme);
+        if let Some(selection) = selection {
+            theme.selection = selection;
+        }
+        cx.refresh_windows();
+        Ok(())
+    }
+
+    /// The theme in the highlighter's own format
+    fn highlight_theme_json(&self) -> serde_json::Value {
+        let mut syntax: serde_json::Map<String, serde_json::Value> = self
+            .tokens
+            .iter()
+            .map(|(kind, style)| {
+                let mut value = json!({ "color": style.color });
+                if style.italic {
+                    value["font_style"] = json!("italic");
+                }
+                if style.bold {
+                    value["font_weight"] = json!(700);
+                }
+                (kind.clone(), value)
+            })
+            .collect();
+        for (kind, base) in SEMANTIC_KINDS {
+            if !syntax.contains_key(*kind) {
+                if let Some(style) = syntax.get(*base).cloned() {
+                    syntax.insert(kind.to_string(), style);
+                }
+            }
+        }
+        json!({
+            "name": self.name,
+            "appearance": if self.dark { "dark" } else { "light" },
+            "style": {
+                "editor.background": self.editor.background,
+                "editor.foreground": self.editor.foreground,
+                "editor.active_line.background": self.editor.current_line,
+                "editor.line_number": self.editor.gutter,
+                "editor.active_line_number": self.editor.active_gutter,
+                "error": self.diagnostics.error,
+                "warning": self.diagnostics.warning,
+                "info": self.diagnostics.info,
+                "hint": self.diagnostics.hint,
+                "syntax": syntax,
+            },
+        })
+    }
+}
+
+/// `#rrggbb` or `#rrggbbaa` as `0xrrggbbaa`
+fn parse_hex(color: &str) -> Option<u32> {
+    let digits = color.strip_prefix('#')?;
+    let value = u32::from_str_radix(digits, 16).ok()?;
+    match digits.len() {
+        6 => Some(value << 8 | 0xff),
+        8 => Some(value),
+        _ => None,
+    }
+}
+
+fn hex_to_hsla(rgba: u32) -> Hsla {
+    gpui::rgba(rgba).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presets_are_valid() {
+        for preset in SyntaxTheme::presets() {
+            preset.validate().unwrap();
+            assert!(preset.tokens.contains_key("keyword"), "{}", preset.name);
+        }
+        assert_eq!(SyntaxTheme::resolve("light+").unwrap().name, "Light+");
+        assert_eq!(parse_hex("#ff000080"), Some(0xff000080));
+        assert_eq!(parse_hex("#abc"), None);
+    }
+
+    #[test]
+    fn test_theme_from_toml() {
+        let theme: SyntaxTheme = toml::from_str(
+            r##"
+            name = "Custom"
+
+            [editor]
+            background = "#101010"
+            foreground = "#eeeeee"
+            gutter = "#555555"
+            activeGutter = "#aaaaaa"
+            currentLine = "#202020"
+            selection = "#303060"
+
+            [tokens]
+            keyword = { color = "#ff8800", bold = true }
+            comment = { color = "nope" }
+            "##,
+        )
+        .unwrap();
+        assert!(!theme.dark);
+        assert_eq!(theme.diagnostics, DiagnosticColors::default());
+        assert!(theme.tokens["keyword"].bold);
+        // Colors are checked once the file is read
+        assert!(theme.validate().is_err());
+    }
+}
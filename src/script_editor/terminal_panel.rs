@@ -0,0 +1,476 @@
+//! Terminal tabs docked below the editor, each a shell in a pseudo-terminal
+
+use gpui::{prelude::FluentBuilder, *};
+use portable_pty::{native_pty_system, Child, ChildKiller as _, CommandBuilder, MasterPty, PtySize};
+use std::cell::Cell;
+use std::io::{Read, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use ui::{
+    button::{Button, ButtonVariants as _},
+    h_flex, v_flex, ActiveTheme as _, IconName, Sizable as _,
+};
+
+use super::ansi::StyledText;
+use super::terminal_screen::TerminalScreen;
+
+const TERMINAL_TEXT_SIZE: Pixels = px(13.0);
+const TERMINAL_LINE_HEIGHT: Pixels = px(18.0);
+
+fn terminal_font() -> Font {
+    Font {
+        family: "JetBrains Mono".to_string().into(),
+        weight: FontWeight::NORMAL,
+        style: FontStyle::Normal,
+        features: FontFeatures::default(),
+        fallbacks: Some(FontFallbacks::from_fonts(vec!["monospace".to_string()])),
+    }
+}
+
+/// The bytes a terminal sends for a key, `None` for keys it doesn't send
+fn keystroke_bytes(keystroke: &Keystroke) -> Option<Vec<u8>> {
+    let modifiers = &keystroke.modifiers;
+    let special: Option<&[u8]> = match keystroke.key.as_str() {
+        "enter" => Some(b"\r"),
+        "backspace" => Some(b"\x7f"),
+        "tab" if modifiers.shift => Some(b"\x1b[Z"),
+        "tab" => Some(b"\t"),
+        "escape" => Some(b"\x1b"),
+        "up" => Some(b"\x1b[A"),
+        "down" => Some(b"\x1b[B"),
+        "right" => Some(b"\x1b[C"),
+        "left" => Some(b"\x1b[D"),
+        "home" => Some(b"\x1b[H"),
+        "end" => Some(b"\x1b[F"),
+        "delete" => Some(b"\x1b[3~"),
+        "pageup" => Some(b"\x1b[5~"),
+        "pagedown" => Some(b"\x1b[6~"),
+        _ => None,
+    };
+    if let Some(bytes) = special {
+        return Some(bytes.to_vec());
+    }
+    if modifiers.control {
+        // Ctrl+letter is the letter's control character, e.g. Ctrl+C is ETX
+        let key = keystroke.key.as_bytes();
+        return match key {
+            [letter] if letter.is_ascii_alphabetic() => Some(vec![letter.to_ascii_lowercase() & 0x1f]),
+            [b'['] => Some(vec![0x1b]),
+            [b'\\'] => Some(vec![0x1c]),
+            [b']'] => Some(vec![0x1d]),
+            _ => None,
+        };
+    }
+    let text = keystroke.key_char.as_ref()?;
+    let mut bytes = Vec::new();
+    // Alt sends the character after an escape, as readline expects
+    if modifiers.alt {
+        bytes.push(0x1b);
+    }
+    bytes.extend_from_slice(text.as_bytes());
+    Some(bytes)
+}
+
+struct TerminalSession {
+    id: usize,
+    title: String,
+    screen: TerminalScreen,
+    master: Box<dyn MasterPty + Send>,
+    writer: Box<dyn Write + Send>,
+    child: Box<dyn Child + Send + Sync>,
+    exited: bool,
+    _reader_task: Task<()>,
+}
+
+impl TerminalSession {
+    fn write(&mut self, bytes: &[u8]) {
+        if self.exited {
+            return;
+        }
+        if let Err(e) = self.writer.write_all(bytes).and_then(|_| self.writer.flush()) {
+            tracing::error!("⚠️  Failed to write to terminal {}: {}", self.title, e);
+        }
+    }
+}
+
+impl Drop for TerminalSession {
+    fn drop(&mut self) {
+        if !self.exited {
+            let _ = self.child.kill();
+        }
+    }
+}
+
+pub enum TerminalPanelEvent {
+    Close,
+}
+
+pub struct TerminalPanel {
+    focus_handle: FocusHandle,
+    sessions: Vec<TerminalSession>,
+    active: usize,
+    next_id: usize,
+    /// Where new shells start
+    cwd: Option<PathBuf>,
+    /// Columns and rows the shells are sized to
+    size: (usize, usize),
+    /// Size of the terminal's body as last laid out
+    body_size: Rc<Cell<Size<Pixels>>>,
+    scroll_handle: UniformListScrollHandle,
+    error: Option<String>,
+}
+
+impl EventEmitter<TerminalPanelEvent> for TerminalPanel {}
+
+impl TerminalPanel {
+    pub fn new(cx: &mut Context<Self>) -> Self {
+        Self {
+            focus_handle: cx.focus_handle(),
+            sessions: Vec::new(),
+            active: 0,
+            next_id: 1,
+            cwd: None,
+            size: (80, 24),
+            body_size: Rc::new(Cell::new(Size::default())),
+            scroll_handle: UniformListScrollHandle::new(),
+            error: None,
+        }
+    }
+
+    pub fn has_sessions(&self) -> bool {
+        !self.sessions.is_empty()
+    }
+
+    pub fn focus(&self, window: &mut Window) {
+        window.focus(&self.focus_handle);
+    }
+
+    /// Start new shells in `cwd`, e.g. the project root
+    pub fn set_working_directory(&mut self, cwd: PathBuf) {
+        self.cwd = Some(cwd);
+    }
+
+    /// Start a shell in a new tab and switch to it
+    pub fn new_session(&mut self, cx: &mut Context<Self>) {
+        let cwd = self.cwd.clone().or_else(|| std::env::current_dir().ok()).unwrap_or_default();
+        match self.spawn_session(&cwd, cx) {
+            Ok(session) => {
+                self.sessions.push(session);
+                self.active = self.sessions.len() - 1;
+                self.error = None;
+            }
+            Err(e) => {
+                tracing::error!("⚠️  Failed to start a terminal in {:?}: {:#}", cwd, e);
+                self.error = Some(format!("Failed to start a shell: {:#}", e));
+            }
+        }
+        cx.notify();
+    }
+
+    /// Type `text` into the active terminal, starting one if there is none
+    pub fn send_text(&mut self, text: &str, cx: &mut Context<Self>) {
+        if self.sessions.is_empty() {
+            self.new_session(cx);
+        }
+        if let Some(session) = self.sessions.get_mut(self.active) {
+            session.write(text.as_bytes());
+        }
+    }
+
+    fn spawn_session(&mut self, cwd: &Path, cx: &mut Context<Self>) -> anyhow::Result<TerminalSession> {
+        let (cols, rows) = self.size;
+        let pair = native_pty_system().openpty(PtySize {
+            rows: rows as u16,
+            cols: cols as u16,
+            pixel_width: 0,
+            pixel_height: 0,
+        })?;
+        let mut command = CommandBuilder::new_default_prog();
+        command.cwd(cwd);
+        command.env("TERM", "xterm-256color");
+        command.env("COLORTERM", "truecolor");
+        let child = pair.slave.spawn_command(command)?;
+        // The shell holds the other end now; keeping it open here would keep
+        // the reader from ever seeing it exit
+        drop(pair.slave);
+        let reader = pair.master.try_clone_reader()?;
+        let writer = pair.master.take_writer()?;
+
+        let id = self.next_id;
+        self.next_id += 1;
+        let (sender, mut output) = futures::channel::mpsc::unbounded::<Vec<u8>>();
+        std::thread::spawn(move || {
+            let mut reader = reader;
+            let mut buffer = [0u8; 8192];
+            while let Ok(read) = reader.read(&mut buffer) {
+                if read == 0 || sender.unbounded_send(buffer[..read].to_vec()).is_err() {
+                    break;
+                }
+            }
+        });
+        let reader_task = cx.spawn(async move |this, cx| {
+            use futures::StreamExt as _;
+            while let Some(bytes) = output.next().await {
+                let open = this.update(cx, |this, cx| this.receive(id, &bytes, cx)).unwrap_or(false);
+                if !open {
+                    return;
+                }
+            }
+            let _ = this.update(cx, |this, cx| this.session_exited(id, cx));
+        });
+
+        Ok(TerminalSession {
+            id,
+            title: format!("Terminal {}", id),
+            screen: TerminalScreen::new(cols, rows),
+            master: pair.master,
+            writer,
+            child,
+            exited: false,
+            _reader_task: reader_task,
+        })
+    }
+
+    /// Take in output of session `id`, returning whether it's still open
+    fn receive(&mut self, id: usize, bytes: &[u8], cx: &mut Context<Self>) -> bool {
+        let Some(ix) = self.sessions.iter().position(|session| session.id == id) else {
+            return false;
+        };
+        self.sessions[ix].screen.feed(bytes);
+        if ix == self.active {
+            let (cursor_line, _) = self.sessions[ix].screen.cursor();
+            self.scroll_handle.scroll_to_item(cursor_line, ScrollStrategy::Top);
+        }
+        cx.notify();
+        true
+    }
+
+    fn session_exited(&mut self, id: usize, cx: &mut Context<Self>) {
+        if let Some(session) = self.sessions.iter_mut().find(|session| session.id == id) {
+            session.exited = true;
+            let message = match session.child.try_wait() {
+                Ok(Some(status)) => format!("[Process exited with code {}]", status.exit_code()),
+                _ => "[Process exited]".to_string(),
+            };
+            session.screen.feed(format!("\r\n\x1b[2m{}\x1b[0m", message).as_bytes());
+            cx.notify();
+        }
+    }
+
+    fn close_session(&mut self, ix: usize, cx: &mut Context<Self>) {
+        if ix >= self.sessions.len() {
+            return;
+        }
+        self.sessions.remove(ix);
+        if self.active >= ix && self.active > 0 {
+            self.active -= 1;
+        }
+        if self.sessions.is_empty() {
+            cx.emit(TerminalPanelEvent::Close);
+        }
+        cx.notify();
+    }
+
+    /// Size the shells to the body as last laid out, when that's changed
+    fn fit_to_body(&mut self, window: &Window) {
+        let body = self.body_size.get();
+        if body.width <= px(0.) || body.height <= px(0.) {
+            return;
+        }
+        let text_system = window.text_system();
+        let font_id = text_system.resolve_font(&terminal_font());
+        let cell_width = text_system
+            .advance(font_id, TERMINAL_TEXT_SIZE, 'm')
+            .map_or(TERMINAL_TEXT_SIZE * 0.6, |size| size.width);
+        let cols = ((body.width - px(16.)) / cell_width).floor().max(2.) as usize;
+        let rows = (body.height / TERMINAL_LINE_HEIGHT).floor().max(1.) as usize;
+        if (cols, rows) == self.size {
+            return;
+        }
+        self.size = (cols, rows);
+        for session in &mut self.sessions {
+            session.screen.resize(cols, rows);
+            let size = PtySize {
+                rows: rows as u16,
+                cols: cols as u16,
+                pixel_width: 0,
+                pixel_height: 0,
+            };
+            if let Err(e) = session.master.resize(size) {
+                tracing::error!("⚠️  Failed to resize terminal {}: {}", session.title, e);
+            }
+        }
+    }
+
+    fn on_key_down(&mut self, event: &KeyDownEvent, _window: &mut Window, cx: &mut Context<Self>) {
+        let keystroke = &event.keystroke;
+        let Some(session) = self.sessions.get_mut(self.active) else {
+            return;
+        };
+        if keystroke.modifiers.control && keystroke.modifiers.shift && keystroke.key == "v" {
+            if let Some(text) = cx.read_from_clipboard().and_then(|item| item.text()) {
+                session.write(text.as_bytes());
+            }
+            cx.stop_propagation();
+            return;
+        }
+        if keystroke.modifiers.platform {
+            return;
+        }
+        if let Some(bytes) = keystroke_bytes(keystroke) {
+            session.write(&bytes);
+            cx.stop_propagation();
+        }
+    }
+
+    fn render_lines(&self, range: Range<usize>, window: &Window, cx: &mut Context<Self>) -> Vec<AnyElement> {
+        let Some(session) = self.sessions.get(self.active) else {
+            return Vec::new();
+        };
+        let screen = &session.screen;
+        let (cursor_line, cursor_col) = screen.cursor();
+        let show_cursor = !session.exited && self.focus_handle.is_focused(window);
+        let foreground = cx.theme().foreground;
+        let span = |run: &StyledText| {
+            let style = run.style;
+            div()
+                .text_color(style.foreground.map_or(foreground, |color| rgb(color).into()))
+                .when_some(style.background, |span, color| span.bg(rgb(color)))
+                .when(style.bold, |span| span.font_weight(FontWeight::BOLD))
+                .when(style.dim, |span| span.opacity(0.6))
+                .when(style.italic, |span| span.italic())
+                .when(style.underline, |span| span.underline())
+                .child(run.text.clone())
+        };
+        range
+            .map(|ix| {
+                let line = h_flex().h(TERMINAL_LINE_HEIGHT).px_2().whitespace_nowrap();
+                if ix != cursor_line {
+                    return line.children(screen.line_runs(ix, 0..usize::MAX).iter().map(span)).into_any_element();
+                }
+                // Pad up to the cursor, which can sit past the line's end
+                let before: String = (0..cursor_col).map(|col| screen.char_at(ix, col)).collect();
+                line.child(div().child(before))
+                    .child(
+                        div()
+                            .when(show_cursor, |cursor| cursor.bg(foreground).text_color(cx.theme().background))
+                            .when(!show_cursor, |cursor| cursor.border_1().border_color(cx.theme().muted_foreground))
+                            .child(screen.char_at(ix, cursor_col).to_string()),
+                    )
+                    .children(screen.line_runs(ix, cursor_col + 1..usize::MAX).iter().map(span))
+                    .into_any_element()
+            })
+            .collect()
+    }
+
+    fn render_tabs(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        h_flex()
+            .w_full()
+            .px_2()
+            .py_1()
+            .gap_1()
+            .border_b_1()
+            .border_color(cx.theme().border)
+            .children(self.sessions.iter().enumerate().map(|(ix, session)| {
+                let active = ix == self.active;
+                h_flex()
+                    .id(("terminal-tab", session.id))
+                    .px_2()
+                    .gap_1()
+                    .rounded_sm()
+                    .text_xs()
+                    .cursor_pointer()
+                    .when(active, |tab| tab.bg(cx.theme().muted))
+                    .text_color(if active { cx.theme().foreground } else { cx.theme().muted_foreground })
+                    .on_click(cx.listener(move |this, _, window, cx| {
+                        this.active = ix;
+                        this.focus(window);
+                        cx.notify();
+                    }))
+                    .child(if session.exited {
+                        format!("{} (exited)", session.title)
+                    } else {
+                        session.title.clone()
+                    })
+                    .child(
+                        Button::new(("close_terminal", session.id))
+                            .icon(IconName::Close)
+                            .ghost()
+                            .xsmall()
+                            .on_click(cx.listener(move |this, _, _window, cx| this.close_session(ix, cx))),
+                    )
+            }))
+            .child(
+                Button::new("new_terminal")
+                    .icon(IconName::Plus)
+                    .tooltip("New Terminal")
+                    .ghost()
+                    .xsmall()
+                    .on_click(cx.listener(|this, _, window, cx| {
+                        this.new_session(cx);
+                        this.focus(window);
+                    })),
+            )
+            .child(div().flex_1())
+            .child(
+                Button::new("close_terminal_panel")
+                    .icon(IconName::ChevronDown)
+                    .tooltip("Hide Terminal")
+                    .ghost()
+                    .xsmall()
+                    .on_click(cx.listener(|_this, _, _window, cx| cx.emit(TerminalPanelEvent::Close))),
+            )
+    }
+}
+
+impl Focusable for TerminalPanel {
+    fn focus_handle(&self, _cx: &App) -> FocusHandle {
+        self.focus_handle.clone()
+    }
+}
+
+impl Render for TerminalPanel {
+    fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        self.fit_to_body(window);
+        let body_size = self.body_size.clone();
+        let line_count = self.sessions.get(self.active).map_or(0, |session| session.screen.line_count());
+
+        v_flex()
+            .size_full()
+            .bg(cx.theme().background)
+            .border_t_1()
+            .border_color(cx.theme().border)
+            .child(self.render_tabs(cx))
+            .child(
+                div()
+                    .id("terminal-body")
+                    .relative()
+                    .flex_1()
+                    .min_h_0()
+                    .key_context("Terminal")
+                    .track_focus(&self.focus_handle)
+                    .on_key_down(cx.listener(Self::on_key_down))
+                    .on_mouse_down(MouseButton::Left, cx.listener(|this, _, window, _cx| this.focus(window)))
+                    .font(terminal_font())
+                    .text_size(TERMINAL_TEXT_SIZE)
+                    .child(
+                        canvas(move |bounds, _window, _cx| body_size.set(bounds.size), |_, _, _, _| {})
+                            .absolute()
+                            .size_full(),
+                    )
+                    .when_some(self.error.clone(), |body, error| {
+                        body.child(div().p_2().text_xs().text_color(cx.theme().danger).child(error))
+                    })
+                    .child(
+                        uniform_list(
+                            "terminal-lines",
+                            line_count,
+                            cx.processor(|this, range: Range<usize>, window, cx| this.render_lines(range, window, cx)),
+                        )
+                        .track_scroll(self.scroll_handle.clone())
+                        .size_full(),
+                    ),
+            )
+    }
+}
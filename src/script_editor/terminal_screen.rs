@@ -0,0 +1,349 @@
+//! What a terminal shows: the screen and scrollback a shell's output draws,
+//! with enough of VT100 for prompts, colors, line editing and `clear`
+
+use std::ops::Range;
+
+use super::ansi::{AnsiStyle, StyledText};
+
+/// Lines kept above the screen once they scroll off it
+const MAX_SCROLLBACK: usize = 5_000;
+
+const TAB_WIDTH: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Cell {
+    ch: char,
+    style: AnsiStyle,
+}
+
+impl Cell {
+    const BLANK: Cell = Cell {
+        ch: ' ',
+        style: AnsiStyle {
+            foreground: None,
+            background: None,
+            bold: false,
+            dim: false,
+            italic: false,
+            underline: false,
+        },
+    };
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Parse {
+    Ground,
+    Escape,
+    /// ESC `(` or `)`, waiting for the character set it picks
+    Charset,
+    /// A control sequence's parameter and intermediate bytes so far
+    Csi(String),
+    /// An operating system command, e.g. setting the window title, which is skipped
+    Osc { after_escape: bool },
+}
+
+pub struct TerminalScreen {
+    cols: usize,
+    rows: usize,
+    /// Scrollback, then the screen's `rows` lines. Lines don't keep trailing blanks.
+    lines: Vec<Vec<Cell>>,
+    /// Row on the screen, not in `lines`
+    cursor_row: usize,
+    cursor_col: usize,
+    saved_cursor: (usize, usize),
+    style: AnsiStyle,
+    parse: Parse,
+    /// The start of a UTF-8 character the last output split
+    partial_char: Vec<u8>,
+}
+
+impl TerminalScreen {
+    pub fn new(cols: usize, rows: usize) -> Self {
+        let (cols, rows) = (cols.max(1), rows.max(1));
+        Self {
+            cols,
+            rows,
+            lines: vec![Vec::new(); rows],
+            cursor_row: 0,
+            cursor_col: 0,
+            saved_cursor: (0, 0),
+            style: AnsiStyle::default(),
+            parse: Parse::Ground,
+            partial_char: Vec::new(),
+        }
+    }
+
+    pub fn size(&self) -> (usize, usize) {
+        (self.cols, self.rows)
+    }
+
+    /// Lines in the scrollback and on the screen
+    pub fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// The cursor as a line of `line_runs` and a column
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.screen_top() + self.cursor_row, self.cursor_col.min(self.cols - 1))
+    }
+
+    /// Styled runs of line `ix`, between the columns of `cols`; the blanks
+    /// past its end are left out
+    pub fn line_runs(&self, ix: usize, cols: Range<usize>) -> Vec<StyledText> {
+        let Some(line) = self.lines.get(ix) else {
+            return Vec::new();
+        };
+        let cells = line.get(cols.start.min(line.len())..cols.end.min(line.len())).unwrap_or_default();
+        let mut runs: Vec<StyledText> = Vec::new();
+        for cell in cells {
+            match runs.last_mut() {
+                Some(last) if last.style == cell.style => last.text.push(cell.ch),
+                _ => runs.push(StyledText {
+                    text: cell.ch.to_string(),
+                    style: cell.style,
+                }),
+            }
+        }
+        runs
+    }
+
+    /// The character at a column of line `ix`, a space past its end
+    pub fn char_at(&self, ix: usize, col: usize) -> char {
+        self.lines.get(ix).and_then(|line| line.get(col)).map_or(' ', |cell| cell.ch)
+    }
+
+    /// Change the screen size, keeping the cursor's line on the screen
+    pub fn resize(&mut self, cols: usize, rows: usize) {
+        let (cols, rows) = (cols.max(1), rows.max(1));
+        let cursor_line = self.screen_top() + self.cursor_row;
+        // Empty lines below the cursor go first when the screen gets shorter
+        while self.lines.len() > cursor_line + 1 && self.lines.len() > rows && self.lines.last().is_some_and(Vec::is_empty) {
+            self.lines.pop();
+        }
+        while self.lines.len() < rows {
+            self.lines.push(Vec::new());
+        }
+        self.cols = cols;
+        self.rows = rows;
+        self.cursor_row = cursor_line.saturating_sub(self.screen_top()).min(rows - 1);
+        self.cursor_col = self.cursor_col.min(cols);
+    }
+
+    /// Take in output of the shell
+    pub fn feed(&mut self, bytes: &[u8]) {
+        let mut bytes = std::mem::take(&mut self.partial_char).into_iter().chain(bytes.iter().copied()).collect::<Vec<u8>>();
+        let text = match std::str::from_utf8(&bytes) {
+            Ok(text) => text.to_string(),
+            Err(e) if e.error_len().is_none() => {
+                // Cut off mid-character: the rest comes with the next output
+                self.partial_char = bytes.split_off(e.valid_up_to());
+                String::from_utf8_lossy(&bytes).to_string()
+            }
+            Err(_) => String::from_utf8_lossy(&bytes).to_string(),
+        };
+        for ch in text.chars() {
+            self.feed_char(ch);
+        }
+    }
+
+    fn feed_char(&mut self, ch: char) {
+        match std::mem::replace(&mut self.parse, Parse::Ground) {
+            Parse::Ground => match ch {
+                '\x1b' => self.parse = Parse::Escape,
+                '\n' | '\x0b' | '\x0c' => self.line_feed(),
+                '\r' => self.cursor_col = 0,
+                '\x08' => self.cursor_col = self.cursor_col.min(self.cols).saturating_sub(1),
+                '\t' => self.cursor_col = ((self.cursor_col / TAB_WIDTH + 1) * TAB_WIDTH).min(self.cols - 1),
+                ch if ch.is_control() => {}
+                ch => self.print(ch),
+            },
+            Parse::Escape => match ch {
+                '[' => self.parse = Parse::Csi(String::new()),
+                ']' => self.parse = Parse::Osc { after_escape: false },
+                '7' => self.saved_cursor = (self.cursor_row, self.cursor_col),
+                '8' => (self.cursor_row, self.cursor_col) = self.saved_cursor,
+                'c' => *self = Self::new(self.cols, self.rows),
+                // Character sets and keypad modes change nothing shown here
+                '(' | ')' => self.parse = Parse::Charset,
+                _ => {}
+            },
+            Parse::Charset => {}
+            Parse::Csi(mut params) => {
+                if ('@'..='~').contains(&ch) {
+                    self.control_sequence(&params, ch);
+                } else {
+                    params.push(ch);
+                    self.parse = Parse::Csi(params);
+                }
+            }
+            // Ended by BEL or ESC `\`
+            Parse::Osc { after_escape } => {
+                if !(ch == '\x07' || after_escape && ch == '\\') {
+                    self.parse = Parse::Osc { after_escape: ch == '\x1b' };
+                }
+            }
+        }
+    }
+
+    fn control_sequence(&mut self, params: &str, action: char) {
+        // `?` sequences set private modes, e.g. hiding the cursor
+        if params.starts_with(['?', '>', '=']) {
+            return;
+        }
+        let args: Vec<usize> = params.split(';').map(|arg| arg.parse().unwrap_or(0)).collect();
+        let arg = |ix: usize| args.get(ix).copied().unwrap_or(0);
+        // Counts and positions of 0 mean 1
+        let count = arg(0).max(1);
+        match action {
+            'm' => self.style.apply_sgr(params),
+            'A' => self.cursor_row = self.cursor_row.saturating_sub(count),
+            'B' => self.cursor_row = (self.cursor_row + count).min(self.rows - 1),
+            'C' => self.cursor_col = (self.cursor_col + count).min(self.cols - 1),
+            'D' => self.cursor_col = self.cursor_col.min(self.cols).saturating_sub(count),
+            'E' => (self.cursor_row, self.cursor_col) = ((self.cursor_row + count).min(self.rows - 1), 0),
+            'F' => (self.cursor_row, self.cursor_col) = (self.cursor_row.saturating_sub(count), 0),
+            'G' => self.cursor_col = (count - 1).min(self.cols - 1),
+            'd' => self.cursor_row = (count - 1).min(self.rows - 1),
+            'H' | 'f' => {
+                self.cursor_row = (arg(0).max(1) - 1).min(self.rows - 1);
+                self.cursor_col = (arg(1).max(1) - 1).min(self.cols - 1);
+            }
+            'J' => self.erase_display(arg(0)),
+            'K' => self.erase_line(arg(0)),
+            'P' => {
+                let col = self.cursor_col;
+                let line = self.current_line();
+                line.drain(col.min(line.len())..(col + count).min(line.len()));
+            }
+            '@' => {
+                let col = self.cursor_col;
+                let line = self.current_line();
+                if col < line.len() {
+                    line.splice(col..col, std::iter::repeat_n(Cell::BLANK, count));
+                }
+            }
+            'X' => {
+                let col = self.cursor_col;
+                let line = self.current_line();
+                let end = (col + count).min(line.len());
+                if col < end {
+                    line[col..end].fill(Cell::BLANK);
+                }
+            }
+            's' => self.saved_cursor = (self.cursor_row, self.cursor_col),
+            'u' => (self.cursor_row, self.cursor_col) = self.saved_cursor,
+            _ => {}
+        }
+    }
+
+    fn print(&mut self, ch: char) {
+        if self.cursor_col >= self.cols {
+            self.cursor_col = 0;
+            self.line_feed();
+        }
+        let (col, style) = (self.cursor_col, self.style);
+        let line = self.current_line();
+        if line.len() <= col {
+            line.resize(col + 1, Cell::BLANK);
+        }
+        line[col] = Cell { ch, style };
+        self.cursor_col += 1;
+    }
+
+    fn line_feed(&mut self) {
+        if self.cursor_row + 1 < self.rows {
+            self.cursor_row += 1;
+            return;
+        }
+        self.lines.push(Vec::new());
+        let scrollback = self.lines.len() - self.rows;
+        if scrollback > MAX_SCROLLBACK {
+            self.lines.drain(..scrollback - MAX_SCROLLBACK);
+        }
+    }
+
+    fn erase_display(&mut self, mode: usize) {
+        let top = self.screen_top();
+        let cursor_line = top + self.cursor_row;
+        match mode {
+            0 => {
+                self.erase_line(0);
+                self.lines[cursor_line + 1..].iter_mut().for_each(Vec::clear);
+            }
+            1 => {
+                self.erase_line(1);
+                self.lines[top..cursor_line].iter_mut().for_each(Vec::clear);
+            }
+            2 => self.lines[top..].iter_mut().for_each(Vec::clear),
+            // The scrollback too
+            3 => {
+                self.lines.drain(..top);
+            }
+            _ => {}
+        }
+    }
+
+    fn erase_line(&mut self, mode: usize) {
+        let col = self.cursor_col;
+        let line = self.current_line();
+        match mode {
+            0 => line.truncate(col),
+            1 => {
+                let end = (col + 1).min(line.len());
+                line[..end].fill(Cell::BLANK);
+            }
+            2 => line.clear(),
+            _ => {}
+        }
+    }
+
+    fn current_line(&mut self) -> &mut Vec<Cell> {
+        let ix = self.screen_top() + self.cursor_row;
+        &mut self.lines[ix]
+    }
+
+    fn screen_top(&self) -> usize {
+        self.lines.len() - self.rows
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(screen: &TerminalScreen, ix: usize) -> String {
+        screen.line_runs(ix, 0..usize::MAX).into_iter().map(|run| run.text).collect()
+    }
+
+    #[test]
+    fn test_prints_wraps_and_scrolls() {
+        let mut screen = TerminalScreen::new(5, 2);
+        screen.feed(b"\x1b[32m$\x1b[0m hello\r\n");
+        assert_eq!(text(&screen, 0), "$ hel");
+        assert_eq!(text(&screen, 1), "lo");
+        assert_eq!(screen.line_runs(0, 0..5)[0].style.foreground, Some(0x0dbc79));
+        // The third line scrolls the first into the scrollback
+        assert_eq!(screen.line_count(), 3);
+        assert_eq!(screen.cursor(), (2, 0));
+
+        // A character split across two reads
+        screen.feed(&"é".as_bytes()[..1]);
+        screen.feed(&"é".as_bytes()[1..]);
+        assert_eq!(text(&screen, 2), "é");
+    }
+
+    #[test]
+    fn test_line_editing_and_clear() {
+        let mut screen = TerminalScreen::new(20, 3);
+        // How a shell redraws the line after backspacing over `t`
+        screen.feed(b"$ cat\x08\x1b[K");
+        assert_eq!(text(&screen, 0), "$ ca");
+        assert_eq!(screen.cursor(), (0, 4));
+
+        screen.feed(b"\x1b]0;title\x07\x1b[2;3Hx\x1b[?25l");
+        assert_eq!(text(&screen, 1), "  x");
+        screen.feed(b"\x1b[H\x1b[2J");
+        assert_eq!((text(&screen, 0), text(&screen, 1)), (String::new(), String::new()));
+        assert_eq!(screen.cursor(), (0, 0));
+    }
+}
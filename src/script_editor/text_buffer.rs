@@ -0,0 +1,232 @@
+//! The editor's copy of a file's text as a rope, for the per-keystroke work
+//! on large files
+//!
+//! The input keeps the text it shows itself; this is the copy `OpenFile::synced`
+//! holds of what the language server was last sent. Edits and line lookups
+//! are O(log n) instead of a scan from the start, so syncing a change of a
+//! multi-megabyte file with its language server, or finding the lines an edit
+//! touched, costs about the same as in a small one.
+//! Offsets are UTF-8 bytes like everywhere else in the editor; LSP positions
+//! count UTF-16 units, and only `\n` breaks lines (a `\r` before it is part of
+//! the line, as `lsp::offset_to_position` has it).
+
+use lsp_types::Position;
+use ropey::Rope;
+use std::ops::Range;
+
+#[derive(Clone, Debug, Default)]
+pub struct TextBuffer {
+    rope: Rope,
+}
+
+impl TextBuffer {
+    pub fn new(text: &str) -> Self {
+        Self {
+            rope: Rope::from_str(text),
+        }
+    }
+
+    /// Length in bytes
+    pub fn len(&self) -> usize {
+        self.rope.len_bytes()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rope.len_bytes() == 0
+    }
+
+    /// Lines, counting the empty one after a trailing line break
+    pub fn line_count(&self) -> usize {
+        self.rope.len_lines()
+    }
+
+    pub fn byte(&self, offset: usize) -> u8 {
+        self.rope.byte(offset)
+    }
+
+    /// Whether the buffer holds exactly `text`
+    pub fn matches(&self, text: &str) -> bool {
+        self.rope == text
+    }
+
+    pub fn slice(&self, range: Range<usize>) -> String {
+        self.rope.byte_slice(range).to_string()
+    }
+
+    /// Line of a byte offset, clamped to the end
+    pub fn line_of(&self, offset: usize) -> u32 {
+        self.rope.byte_to_line(offset.min(self.len())) as u32
+    }
+
+    /// Bytes of a line without its line break, clamped to the last line
+    pub fn line_range(&self, line: u32) -> Range<usize> {
+        let line = (line as usize).min(self.rope.len_lines() - 1);
+        let start = self.rope.line_to_byte(line);
+        let end = if line + 1 < self.rope.len_lines() {
+            self.rope.line_to_byte(line + 1) - 1
+        } else {
+            self.len()
+        };
+        start..end
+    }
+
+    /// Line and column of a byte offset, both from zero, the column counting
+    /// characters as the status bar shows it
+    pub fn line_column(&self, offset: usize) -> (u32, u32) {
+        let char_ix = self.rope.byte_to_char(offset.min(self.len()));
+        let line = self.rope.char_to_line(char_ix);
+        (line as u32, (char_ix - self.rope.line_to_char(line)) as u32)
+    }
+
+    /// Byte offset of a line and column like `line_column` gives them, each
+    /// clamped to the buffer: a column past the end is the end of the line
+    pub fn line_column_to_offset(&self, line: u32, column: u32) -> usize {
+        let line = self.line_range(line);
+        let start_char = self.rope.byte_to_char(line.start);
+        let end_char = self.rope.byte_to_char(line.end);
+        self.rope.char_to_byte((start_char + column as usize).min(end_char))
+    }
+
+    /// Characters in a range of UTF-16 units, such as a selection the input reports
+    pub fn utf16_char_count(&self, range: Range<usize>) -> usize {
+        let len = self.rope.len_utf16_cu();
+        let start = self.rope.utf16_cu_to_char(range.start.min(len));
+        let end = self.rope.utf16_cu_to_char(range.end.min(len));
+        end.saturating_sub(start)
+    }
+
+    /// LSP position of a byte offset, clamped like `lsp::offset_to_position`
+    pub fn offset_to_position(&self, offset: usize) -> Position {
+        let char_ix = self.rope.byte_to_char(offset.min(self.len()));
+        let line = self.rope.char_to_line(char_ix);
+        let line_start = self.rope.line_to_char(line);
+        let character = self.rope.char_to_utf16_cu(char_ix) - self.rope.char_to_utf16_cu(line_start);
+        Position::new(line as u32, character as u32)
+    }
+
+    /// Byte offset of an LSP position, clamped like `lsp::position_to_offset`
+    pub fn position_to_offset(&self, position: Position) -> usize {
+        if position.line as usize >= self.rope.len_lines() {
+            return self.len();
+        }
+        let line = self.line_range(position.line);
+        let start_char = self.rope.byte_to_char(line.start);
+        let end_char = self.rope.byte_to_char(line.end);
+        let target = self.rope.char_to_utf16_cu(start_char) + position.character as usize;
+        let mut char_ix = self.rope.utf16_cu_to_char(target.min(self.rope.len_utf16_cu()));
+        // Inside a surrogate pair: the character it belongs to ends at the column
+        if self.rope.char_to_utf16_cu(char_ix) < target {
+            char_ix += 1;
+        }
+        self.rope.char_to_byte(char_ix.min(end_char))
+    }
+
+    pub fn offsets_to_range(&self, range: Range<usize>) -> lsp_types::Range {
+        lsp_types::Range {
+            start: self.offset_to_position(range.start),
+            end: self.offset_to_position(range.end),
+        }
+    }
+
+    /// Where `new` differs from the buffer, like `lsp::edit_bounds`: the
+    /// start, the end in the buffer and the end in `new`
+    pub fn diff(&self, new: &str) -> (usize, usize, usize) {
+        let new_bytes = new.as_bytes();
+        let mut start = 0;
+        'prefix: for chunk in self.rope.chunks() {
+            for &byte in chunk.as_bytes() {
+                if new_bytes.get(start) != Some(&byte) {
+                    break 'prefix;
+                }
+                start += 1;
+            }
+        }
+        while !self.is_char_boundary(start) || !new.is_char_boundary(start) {
+            start -= 1;
+        }
+
+        let max_suffix = (self.len() - start).min(new.len() - start);
+        let mut suffix = 0;
+        let chunks: Vec<&str> = self.rope.chunks().collect();
+        'suffix: for chunk in chunks.iter().rev() {
+            for &byte in chunk.as_bytes().iter().rev() {
+                if suffix == max_suffix || new_bytes[new.len() - suffix - 1] != byte {
+                    break 'suffix;
+                }
+                suffix += 1;
+            }
+        }
+        while !self.is_char_boundary(self.len() - suffix) || !new.is_char_boundary(new.len() - suffix) {
+            suffix -= 1;
+        }
+        (start, self.len() - suffix, new.len() - suffix)
+    }
+
+    /// Replace a byte range, which must fall on character boundaries
+    pub fn replace(&mut self, range: Range<usize>, text: &str) {
+        let start = self.rope.byte_to_char(range.start);
+        let end = self.rope.byte_to_char(range.end);
+        self.rope.remove(start..end);
+        self.rope.insert(start, text);
+    }
+
+    fn is_char_boundary(&self, offset: usize) -> bool {
+        offset == self.len() || self.rope.char_to_byte(self.rope.byte_to_char(offset)) == offset
+    }
+}
+
+impl std::fmt::Display for TextBuffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for chunk in self.rope.chunks() {
+            f.write_str(chunk)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::script_editor::lsp::{edit_bounds, offset_to_position, position_to_offset};
+
+    #[test]
+    fn test_positions_match_string_conversions() {
+        let text = "fn main() {\r\n    let π = \"😀\";\n}\n";
+        let buffer = TextBuffer::new(text);
+        for offset in (0..=text.len()).filter(|offset| text.is_char_boundary(*offset)) {
+            assert_eq!(buffer.offset_to_position(offset), offset_to_position(text, offset), "{}", offset);
+        }
+        for line in 0..5 {
+            for character in 0..20 {
+                let position = Position::new(line, character);
+                assert_eq!(buffer.position_to_offset(position), position_to_offset(text, position), "{:?}", position);
+            }
+        }
+        assert_eq!(buffer.line_range(1), 13..33);
+        assert_eq!(buffer.line_column(text.find('=').unwrap()), (1, 10));
+        assert_eq!(buffer.utf16_char_count(0..text.encode_utf16().count()), text.chars().count());
+    }
+
+    #[test]
+    fn test_line_column_to_offset() {
+        let buffer = TextBuffer::new("let π = 1;\nx\n");
+        for offset in [0, 4, 6, 12, 14] {
+            let (line, column) = buffer.line_column(offset);
+            assert_eq!(buffer.line_column_to_offset(line, column), offset);
+        }
+        assert_eq!(buffer.line_column_to_offset(0, 99), 11);
+        assert_eq!(buffer.line_column_to_offset(99, 5), 14);
+    }
+
+    #[test]
+    fn test_diff_and_replace() {
+        let mut buffer = TextBuffer::new("let π = 1;\nlet x = 2;\n");
+        for new in ["let τ = 1;\nlet x = 2;\n", "let τ = 1;\n", "", "a\nb"] {
+            let old = buffer.to_string();
+            let (start, old_end, new_end) = buffer.diff(new);
+            assert_eq!((start, old_end, new_end), edit_bounds(&old, new));
+            buffer.replace(start..old_end, &new[start..new_end]);
+            assert!(buffer.matches(new));
+        }
+    }
+}
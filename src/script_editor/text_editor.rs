@@ -1,6 +1,7 @@
 use gpui::*;
 use ui::{
     button::{Button, ButtonVariants as _},
+    context_menu::ContextMenuExt,
     h_flex,
     input::{InputEvent, InputState, TabSize, TextInput},
     resizable::{h_resizable, resizable_panel, ResizableState},
@@ -8,12 +9,119 @@ use ui::{
     v_flex, ActiveTheme as _, IconName, Sizable as _, StyledExt,
 };
 
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
-use std::path::PathBuf;
-use std::time::Instant;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use engine_backend::services::rust_analyzer_manager::RustAnalyzerManager;
 
+/// How long typing must pause before foldable ranges are recomputed
+const FOLD_REFRESH_DELAY: Duration = Duration::from_millis(300);
+
+/// How long typing must pause before document symbols are requested again
+const SYMBOL_REFRESH_DELAY: Duration = Duration::from_millis(500);
+
+/// Lines blamed per `git blame` run, so scrolling fetches a chunk at a time
+const BLAME_CHUNK_LINES: u32 = 200;
+
+/// How long edits must pause before blame is recomputed for the new text
+const BLAME_DELAY: Duration = Duration::from_millis(300);
+
+/// How long after a save or a change on disk the diff against `HEAD` is recomputed
+const GIT_DIFF_DELAY: Duration = Duration::from_millis(300);
+
+/// How often open files are checked for changes made outside the editor
+const GIT_DIFF_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How long the cursor must rest before the code actions at it are requested
+const CODE_ACTION_DELAY: Duration = Duration::from_millis(250);
+
+/// Pause in typing before a Markdown preview is rendered again
+const MARKDOWN_PREVIEW_DELAY: Duration = Duration::from_millis(300);
+
+/// How long the cursor rests on a symbol before its occurrences are highlighted
+const OCCURRENCE_HIGHLIGHT_DELAY: Duration = Duration::from_millis(150);
+
+/// How long scrolling or typing must pause before inlay hints are requested
+const INLAY_HINT_DELAY: Duration = Duration::from_millis(200);
+
+/// Lines above and below the view that inlay hints are requested for too,
+/// so scrolling a little doesn't ask again
+const INLAY_HINT_MARGIN: u32 = 50;
+
+/// How long typing must pause before semantic tokens are requested again
+const SEMANTIC_TOKENS_DELAY: Duration = Duration::from_millis(300);
+
+/// Lines past which a file isn't parsed for highlighting as a whole, but
+/// lexed a screenful at a time as it scrolls into view
+const LAZY_HIGHLIGHT_LINES: usize = 50_000;
+
+/// Lines of such a file lexed per background task, between which the UI
+/// thread takes in what's been lexed
+const HIGHLIGHT_CHUNK_LINES: u32 = 5_000;
+
+/// How long the line jumped to with Go to Line stays highlighted
+const LINE_FLASH_DURATION: Duration = Duration::from_millis(800);
+
+/// How long saving waits on the server to organize imports, and again to
+/// format, before going without
+const BEFORE_SAVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+use super::file_watcher::{FileWatcher, WATCH_DEBOUNCE};
+use super::workspace_root::workspace_root;
+use super::file_positions::{FilePosition, FilePositions};
+use super::find_bar::{FindBar, FindBarEvent};
+use super::workspace_panels::{FilePanelWrapper, WelcomePanelWrapper};
+use super::git_blame::{blame_lines, commit_message, head_commit, BlameCommit};
+use super::diff_view::{diff_view, DiffLine, DiffSide};
+use super::git_diff::{diff_hunks, head_text, revert_hunk, stage_hunk, DiffHunk};
+use super::go_to_line::{clamp_to_text, parse_line_target};
+use super::languages::language_for_path;
+use super::sticky_scroll::{sticky_lines, symbol_scopes, DEFAULT_STICKY_SCROLL_DEPTH};
+use super::folding::{fold_ranges_from_lsp, heuristic_fold_ranges, toml_fold_ranges, FoldRange, FoldState, FOLD_PLACEHOLDER};
+use super::auto_pairs;
+use super::comments::{toggle_block_comment, toggle_line_comments, CommentSyntax};
+use super::line_moves::{duplicate_lines, move_lines, LineEdit};
+use super::yaml;
+use super::json_validation::{json_language_settings, validate_json, JsonDialect};
+use super::run_output::{default_run_command, expand_run_command};
+use super::problems_panel::Problem;
+use super::bookmarks::{step_bookmark, Bookmarks};
+use super::navigation_history::{NavLocation, NavigationHistory, NEAR_LINES};
+use super::snippets::{parse_snippet, SnippetLibrary, SnippetSession};
+use super::emmet;
+use super::bookmarks_panel::Bookmark;
+use super::python_env::{find_interpreters, python_language_settings, PythonInterpreter};
+use super::toml_validation::{is_toml, toml_brackets, toml_outline, validate_toml};
+use super::settings::{
+    formats_on_save_by_default, JsonSchemaAssociation, LanguageSaveCleanup, LanguageServerSettings, RenderWhitespace,
+    WrapMode,
+};
+use super::editorconfig::EditorConfig;
+use super::encoding::{self, FileEncoding};
+use super::large_files;
+use super::line_endings::{self, LineEnding};
+use super::whitespace::{invisible_characters, save_cleanup_edits, trailing_whitespace, whitespace_markers, SaveCleanup};
+use super::brackets::BracketPairs;
+use super::indentation::{detect, indent_guides, indent_lines, outdent_lines, IndentStyle};
+use super::multi_cursor::{box_selections, next_occurrence, MultiCursor, Selection};
+use super::selection_expansion::{expand_heuristically, smallest_enclosing, SelectionHistory};
+use super::undo_history::UndoHistory;
+use super::vim::{self, ExCommand, Vim, VimAction};
+use super::text_buffer::TextBuffer;
+use super::lazy_highlight::{lex_chunk, LazyHighlighter};
+use super::markdown_preview::{self, PreviewBlock};
+use super::viewport;
+use super::lsp::{
+    CodeActionItem, DefinitionTarget, DocumentVersion, FileDiagnostic, InlayHintItem, LanguageServer,
+    offers_organize_imports, organize_imports_action, organize_imports_params, shut_down_servers, LanguageServerPool, MessageRequest, Occurrences, PendingRequest, SemanticTokenState, ServerMessages,
+    ServerProgress, ServerStatus, SharedInlayHints, Signatures, SharedCompletionExtras, SymbolNode,
+};
+
 #[derive(Clone)]
 pub enum TextEditorEvent {
     OpenFolderRequested(PathBuf),
@@ -30,6 +138,26 @@ pub enum TextEditorEvent {
     FileClosed {
         path: PathBuf,
     },
+    /// The editor's overall unsaved-changes state flipped
+    DirtyChanged(bool),
+    /// The text of an open file was edited
+    BufferChanged {
+        path: PathBuf,
+    },
+    /// Changes were staged in git from the editor
+    GitIndexChanged,
+    /// The problem counts in the status bar were clicked
+    ShowProblemsRequested,
+    /// A file was saved by auto-save rather than by the user
+    AutoSaved {
+        path: PathBuf,
+    },
+    /// The cursor of the current file moved, to a line and column from one
+    CursorMoved {
+        path: PathBuf,
+        line: usize,
+        column: usize,
+    },
     /// Request to navigate to a specific location (for go-to-definition)
     NavigateToLocation {
         path: PathBuf,
@@ -38,11 +166,68 @@ pub enum TextEditorEvent {
     },
 }
 
+/// Peek Definition or Peek References, open below a line of a file
+struct PeekSession {
+    /// File and zero-based line the peek opens below
+    path: PathBuf,
+    line: u32,
+    title: &'static str,
+    targets: Vec<DefinitionTarget>,
+    index: usize,
+    /// Text of each target's file when the peek opened
+    texts: HashMap<PathBuf, String>,
+    /// View of the current target's file; edits made in it aren't kept
+    input_state: Option<Entity<InputState>>,
+    /// Target the view was last loaded with, on a render
+    shown: Option<usize>,
+}
+
+/// What a file's panel shows of the peek open in it
+pub struct PeekView {
+    pub line: u32,
+    pub title: &'static str,
+    pub index: usize,
+    pub count: usize,
+    pub target: DefinitionTarget,
+    pub input_state: Entity<InputState>,
+}
+
+/// Outcome of saving several files at once
+#[derive(Clone, Debug, Default)]
+pub struct SaveAllSummary {
+    pub saved: Vec<PathBuf>,
+    /// Files that could not be saved, with the reason
+    pub errors: Vec<(PathBuf, String)>,
+}
+
+impl SaveAllSummary {
+    pub fn extend(&mut self, other: SaveAllSummary) {
+        self.saved.extend(other.saved);
+        self.errors.extend(other.errors);
+    }
+
+    /// One line for the status bar, e.g. `Saved 3 files, 1 failed`
+    pub fn message(&self) -> String {
+        let saved = match self.saved.len() {
+            0 => "No unsaved files".to_string(),
+            1 => "Saved 1 file".to_string(),
+            count => format!("Saved {} files", count),
+        };
+        if self.errors.is_empty() {
+            saved
+        } else {
+            format!("{}, {} failed", saved, self.errors.len())
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct OpenFile {
     pub path: PathBuf,
     pub input_state: Entity<InputState>,
     pub is_modified: bool,
+    /// Hash of the content as last loaded from or written to disk
+    pub saved_content_hash: u64,
     pub lines_count: usize,
     pub file_size: usize,
     /// Document version for LSP synchronization
@@ -53,8 +238,88 @@ pub struct OpenFile {
     pub markdown_preview_cache: String,
     /// Last time markdown was rendered
     pub last_markdown_render: Option<Instant>,
+    /// The preview's blocks, parsed from `markdown_preview_cache`
+    pub markdown_blocks: Rc<Vec<PreviewBlock>>,
     /// Pending scroll target (line, column) - will be applied after layout is ready
     pub pending_scroll_target: Option<(usize, usize)>,
+    /// Scroll offset to restore along with the pending scroll target
+    pub pending_scroll_top: Option<f32>,
+    /// Plugin-managed language server for this file, if one is running
+    pub language_server: Option<Arc<LanguageServer>>,
+    /// Text as of the last handled change, used to remap diagnostics and folds on edit
+    pub synced: TextBuffer,
+    /// Latest diagnostics for this file, remapped as the buffer changes
+    pub diagnostics: Vec<FileDiagnostic>,
+    /// Extra cursors that repeat what the input's own cursor does
+    pub multi_cursor: MultiCursor,
+    /// Primary cursor offset as last observed
+    pub cursor_offset: usize,
+    /// Whether the file on disk still matches what was last loaded or saved
+    pub disk_state: DiskState,
+    pub encoding: FileEncoding,
+    /// Opened without language features because of its size, and never saved
+    pub read_only: bool,
+    /// Line breaks the file gets on disk; the buffer only has `\n`
+    pub line_ending: LineEnding,
+    pub saved_line_ending: LineEnding,
+    /// Whether the file had both endings when it was read
+    pub mixed_line_endings: bool,
+    /// Buffer text as last observed while extra cursors are active
+    pub multi_cursor_text: String,
+    /// Text expected once queued extra-cursor edits land, so they aren't replayed again
+    pub pending_cursor_text: Option<String>,
+    /// Set during an Alt+Click so the click isn't replayed as cursor movement
+    pub placing_cursor: bool,
+    /// Foldable and folded line ranges
+    pub folds: FoldState,
+    /// Primary cursor line as last observed, to tell which way it moved into a fold
+    pub cursor_line: u32,
+    /// Indentation used by Tab, Shift+Tab and the indent guides
+    pub indent_style: IndentStyle,
+    /// What the project's `.editorconfig` files say about it
+    pub editorconfig: EditorConfig,
+    /// Bracket pairs and the text they were found in
+    pub bracket_pairs: Option<(SharedString, Rc<BracketPairs>)>,
+    /// Bracket ranges last highlighted: the pair at the cursor, then the unmatched ones
+    pub bracket_highlights: (Vec<Range<usize>>, Vec<Range<usize>>),
+    /// Lines last highlighted as current: the cursor's, or those a selection covers
+    pub current_lines: Option<Range<u32>>,
+    /// Occurrences of the symbol under the cursor, as last highlighted
+    pub occurrences: Occurrences,
+    /// The version and word range `occurrences` are (being) found for
+    pub occurrences_at: Option<(i32, Range<usize>)>,
+    /// Column selection being extended from the keyboard
+    pub box_selection: Option<BoxSelection>,
+    /// Selections Expand Selection grew from, for Shrink Selection
+    pub selection_history: SelectionHistory,
+    /// Undo and redo steps, with typing grouped into words and runs
+    pub undo_history: UndoHistory,
+    /// `version`, shared with the completion and hover providers to drop
+    /// answers about text that has since changed
+    pub document_version: DocumentVersion,
+    /// Inlay hints around the lines in view, shared with the hover provider for their tooltips
+    pub inlay_hints: SharedInlayHints,
+    /// Document version and lines inlay hints were last requested for
+    pub inlay_hint_request: Option<(i32, Range<u32>)>,
+    /// Semantic tokens over the lexer's highlighting, empty when the server has none
+    pub semantic_tokens: SemanticTokenState,
+    /// Document version semantic tokens were last requested for
+    pub semantic_tokens_version: Option<i32>,
+    /// Highlighting of the lines in view, for files too large to parse whole
+    pub lazy_highlighter: Option<LazyHighlighter>,
+    /// Document version and lines last highlighted by `lazy_highlighter`
+    pub lazy_highlight_request: Option<(i32, Range<u32>)>,
+    /// Document symbols from the language server, empty when it has none
+    pub symbols: Vec<SymbolNode>,
+    /// Index path of the symbols around the cursor, as shown in the breadcrumbs
+    pub breadcrumb_path: Vec<usize>,
+    pub bookmarks: Bookmarks,
+    /// Snippet whose tab stops are being filled in
+    pub snippet: Option<SnippetSession>,
+    /// Text expected once a snippet's own edits land, so they don't move its stops again
+    pub pending_snippet_text: Option<String>,
+    /// Snippets the language server offered as completions, shared with the completion provider
+    pub completion_extras: SharedCompletionExtras,
 }
 
 pub struct TextEditor {
@@ -65,6 +330,18 @@ pub struct TextEditor {
     /// Performance monitoring
     last_render_time: Option<Instant>,
     show_performance_stats: bool,
+    /// Show a document overview beside each file (drawn by its panel)
+    show_minimap: bool,
+    /// Scope headers pinned above the view at most, `0` to turn sticky scroll off
+    sticky_scroll_depth: usize,
+    /// Cursor and scroll positions remembered across reopening files
+    file_positions: FilePositions,
+    /// Indent styles set for languages, in place of `IndentStyle::for_language`
+    indent_styles: HashMap<String, IndentStyle>,
+    /// Comment markers set per language, overriding the built-in ones
+    comment_syntaxes: HashMap<String, CommentSyntax>,
+    /// Use the indentation found in a file's content when it has any
+    detect_indentation: bool,
     subscriptions: Vec<Subscription>,
     /// Global rust analyzer for LSP support
     rust_analyzer: Option<Entity<RustAnalyzerManager>>,
@@ -72,970 +349,7438 @@ pub struct TextEditor {
     markdown_split_state: Entity<ResizableState>,
     /// Pending navigation (path, line, character) to be handled when we have window access
     pending_navigation: Option<(PathBuf, u32, u32)>,
-    /// Internal workspace for draggable file tabs
-    workspace: Option<Entity<ui::workspace::Workspace>>,
-    /// Track if workspace has been initialized
-    workspace_initialized: bool,
-    /// Pending panels to add (file index, path, input_state)
-    pending_panels_to_add: Vec<(usize, PathBuf, Entity<InputState>)>,
+    /// Editor view of each open file (keyed by its input), created when first shown
+    file_views: HashMap<EntityId, Entity<FilePanelWrapper>>,
+    /// Shown when no files are open
+    welcome_view: Entity<WelcomePanelWrapper>,
+    /// Dirty state last reported through `TextEditorEvent::DirtyChanged`
+    last_reported_dirty: bool,
+    /// Language servers, shared with every other editor
+    language_servers: LanguageServerPool,
+    /// Who this editor is to the shared servers' documents
+    editor_id: u64,
+    /// Schemas the JSON language server applies by file path
+    json_schemas: Vec<JsonSchemaAssociation>,
+    /// How scripts are run, by file extension, over the built-in commands
+    run_commands: BTreeMap<String, String>,
+    /// What a file dropped from the explorer inserts, by language id
+    drop_templates: BTreeMap<String, String>,
+    wrap_mode: WrapMode,
+    render_whitespace: RenderWhitespace,
+    /// Files larger than this many megabytes ask before opening
+    large_file_threshold_mb: u64,
+    /// What saving does to whitespace, unless the file's language has its own choices
+    save_cleanup: SaveCleanup,
+    language_save_cleanup: BTreeMap<String, LanguageSaveCleanup>,
+    /// Save a file that formats on save anyway when formatting fails
+    save_when_formatting_fails: bool,
+    highlight_current_line: bool,
+    inlay_hints_enabled: bool,
+    /// Vim's modes and registers, while Vim mode is on
+    vim: Option<Vim>,
+    /// Debounced `textDocument/inlayHint` request per file
+    inlay_hint_tasks: HashMap<PathBuf, Task<()>>,
+    /// Debounced semantic tokens request per file
+    semantic_token_tasks: HashMap<PathBuf, Task<()>>,
+    /// Background lexing of each lazily highlighted file that isn't lexed to the end
+    lazy_highlight_tasks: HashMap<PathBuf, Task<()>>,
+    /// Debounced re-parses of Markdown previews, per file
+    markdown_preview_tasks: HashMap<PathBuf, Task<()>>,
+    /// Definitions to choose from when go-to-definition found more than one
+    definition_picker: Option<Vec<DefinitionTarget>>,
+    peek: Option<PeekSession>,
+    /// Places jumped away from, for Go Back and Go Forward
+    navigation: NavigationHistory,
+    /// Where a jump already recorded (or taken from the history) lands, so
+    /// the cursor arriving there isn't recorded again
+    expected_jump: Option<NavLocation>,
+    snippets: Arc<SnippetLibrary>,
+    /// Copies of a tab stop's text into its mirrors, applied on the next render
+    pending_snippet_mirrors: Vec<(Entity<InputState>, Vec<(Range<usize>, String)>)>,
+    /// Tab stops of accepted snippet completions to select on the next render
+    pending_snippet_selections: Vec<(Entity<InputState>, Range<usize>)>,
+    /// Imports and other edits that come with completions just accepted
+    pending_additional_edits: Vec<(Entity<InputState>, Vec<(Range<usize>, String)>)>,
+    /// The Python interpreter each workspace root's server is told to use
+    python_interpreters: HashMap<PathBuf, PythonInterpreter>,
+    /// Interpreters found for a root, to choose the one its Python files use
+    interpreter_picker: Option<(PathBuf, Vec<PythonInterpreter>)>,
+    /// Tasks receiving `publishDiagnostics` from each server in use, and
+    /// noticing when it exits
+    diagnostic_listeners: Vec<(Arc<LanguageServer>, Task<()>)>,
+    /// Restarts scheduled after a crash, per language and root
+    server_restart_tasks: HashMap<(String, PathBuf), Task<()>>,
+    /// `$/progress` each server has begun and not yet ended, per language and root
+    server_progress: HashMap<(String, PathBuf), ServerProgress>,
+    /// Toasts and prompts servers sent through `window/showMessage[Request]`
+    server_messages: ServerMessages,
+    /// Inline rename input, while a rename is being typed
+    rename_session: Option<RenameSession>,
+    /// Format and rename requests whose edits haven't been applied yet
+    edit_requests_in_flight: usize,
+    /// Go to Line input, while it is open
+    go_to_line: Option<GoToLineSession>,
+    /// Clears the highlight of the line jumped to
+    line_flash_task: Option<Task<()>>,
+    /// Parameter hints for the call being typed
+    signature_help: Option<SignatureHelpSession>,
+    signature_help_task: Option<Task<()>>,
+    /// Code actions at the cursor, shown as a lightbulb and listed by Ctrl+.
+    code_actions: Option<CodeActionsSession>,
+    code_actions_task: Option<Task<()>>,
+    /// Finds the occurrences of the symbol under the cursor once it rests there
+    occurrence_task: Option<Task<()>>,
+    /// Edits servers sent with `workspace/applyEdit`, applied on the next render
+    /// and answered once they are, with the server and id of the request
+    pending_workspace_edits: Vec<(Arc<LanguageServer>, serde_json::Value, lsp_types::WorkspaceEdit)>,
+    /// Short-lived message shown in the status bar (e.g. why a rename was rejected)
+    status_message: Option<SharedString>,
+    status_message_task: Option<Task<()>>,
+    /// Find/replace bar shown above the editor
+    find_bar: Entity<FindBar>,
+    find_bar_visible: bool,
+    /// Edits for extra cursors, applied on the next render where a window is available
+    pending_cursor_edits: Vec<(Entity<InputState>, Vec<lsp_types::TextEdit>)>,
+    /// Cursor moves out of folded lines, applied on the next render
+    pending_cursor_moves: Vec<(Entity<InputState>, ui::input::Position)>,
+    /// Debounced fold range recomputation per file
+    fold_refresh_tasks: HashMap<PathBuf, Task<()>>,
+    /// Debounced `textDocument/documentSymbol` request per file
+    symbol_refresh_tasks: HashMap<PathBuf, Task<()>>,
+    /// Breadcrumb segment (by depth) whose sibling symbols are listed
+    breadcrumb_menu: Option<usize>,
+    blame_mode: BlameMode,
+    /// Blame per file; `None` for files git doesn't track
+    file_blame: HashMap<PathBuf, Option<FileBlame>>,
+    /// `git blame` runs per file and chunk (`u32::MAX` while checking the file is tracked)
+    blame_tasks: HashMap<(PathBuf, u32), Task<()>>,
+    blame_popup: Option<BlamePopup>,
+    blame_popup_task: Option<Task<()>>,
+    /// Changes against `HEAD` per file, as of the last save or change on disk
+    git_diffs: HashMap<PathBuf, FileGitDiff>,
+    git_diff_tasks: HashMap<PathBuf, Task<()>>,
+    /// Hunk (by file and index) whose original lines are being peeked at
+    diff_peek: Option<(PathBuf, usize)>,
+    _git_diff_poll: Task<()>,
+    file_watcher: FileWatcher,
+    /// Clean files changed on disk, reloaded once a window is at hand
+    pending_reloads: Vec<PathBuf>,
+    _file_change_task: Task<()>,
 }
 
-impl TextEditor {
-    pub fn new(window: &mut Window, cx: &mut Context<Self>) -> Self {
-        let markdown_split_state = ResizableState::new(cx);
+struct FileGitDiff {
+    /// Empty for files that aren't committed
+    hunks: Vec<DiffHunk>,
+    /// Modification time of the file on disk when the diff was taken
+    modified: Option<std::time::SystemTime>,
+}
 
-        // Create internal workspace for file tabs
-        let workspace = cx.new(|cx| {
-            ui::workspace::Workspace::new_with_channel(
-                "text-editor-workspace",
-                ui::dock::DockChannel(4), // Unique channel for text editor internal workspace
-                window,
-                cx,
-            )
-        });
+/// Corners of a column selection, and where it left the primary cursor so a
+/// cursor moved since then starts a new box
+#[derive(Clone, Copy, Debug)]
+pub struct BoxSelection {
+    anchor: lsp_types::Position,
+    head: lsp_types::Position,
+    cursor: usize,
+}
 
-        Self {
-            focus_handle: cx.focus_handle(),
-            open_files: Vec::new(),
-            current_file_index: None,
-            last_render_time: None,
-            show_performance_stats: false,
-            subscriptions: Vec::new(),
-            rust_analyzer: None,
-            markdown_split_state,
-            pending_navigation: None,
-            workspace: Some(workspace),
-            workspace_initialized: false,
-            pending_panels_to_add: Vec::new(),
-        }
+/// How an open file relates to the file on disk
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum DiskState {
+    #[default]
+    InSync,
+    /// Changed outside the editor while the buffer had unsaved edits
+    Changed {
+        disk_content: String,
+        /// Whether the banner is showing how the two differ
+        show_diff: bool,
+    },
+    Deleted,
+}
+
+/// Which lines show git blame annotations
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BlameMode {
+    #[default]
+    Off,
+    CurrentLine,
+    AllLines,
+}
+
+/// Blame of one version of a file
+struct FileBlame {
+    version: i32,
+    lines: HashMap<u32, Arc<BlameCommit>>,
+    /// Chunks (of `BLAME_CHUNK_LINES`) fetched or being fetched
+    requested_chunks: HashSet<u32>,
+}
+
+/// Commit details opened from a blame annotation
+pub struct BlamePopup {
+    pub path: PathBuf,
+    pub line: u32,
+    pub commit: Arc<BlameCommit>,
+    /// Full commit message, once loaded
+    pub message: Option<String>,
+}
+
+/// A symbol rename waiting for the new name
+struct RenameSession {
+    path: PathBuf,
+    /// Byte offset of the symbol in the buffer
+    offset: usize,
+    /// Buffer version the rename was started on
+    version: i32,
+    input: Entity<InputState>,
+    _subscription: Subscription,
+}
+
+/// A Go to Line input and where the cursor was before it moved the cursor around
+struct GoToLineSession {
+    input_state: Entity<InputState>,
+    /// Cursor offset and scroll offset to go back to when cancelled
+    original_cursor: usize,
+    original_scroll: Point<Pixels>,
+    /// Zero-based line and UTF-16 column the input currently points at
+    target: Option<(u32, u32)>,
+    error: Option<&'static str>,
+    line_count: usize,
+    input: Entity<InputState>,
+    _subscription: Subscription,
+}
+
+/// Signature help open for a call in one file
+struct SignatureHelpSession {
+    path: PathBuf,
+    /// Cursor offset the last request was made at
+    cursor: usize,
+    /// `None` until the first response arrives
+    signatures: Option<Signatures>,
+}
+
+/// Code actions available at a cursor position in one file
+struct CodeActionsSession {
+    path: PathBuf,
+    version: i32,
+    /// Cursor offset they were requested at
+    cursor: usize,
+    line: u32,
+    /// Empty until the response arrives
+    items: Vec<CodeActionItem>,
+    /// Highlighted entry while the menu is open
+    selected: Option<usize>,
+}
+
+/// Whether a server answers `textDocument/codeAction`
+fn provides_code_actions(server: &LanguageServer) -> bool {
+    server.capabilities().is_some_and(|caps| match caps.code_action_provider {
+        Some(lsp_types::CodeActionProviderCapability::Simple(enabled)) => enabled,
+        Some(lsp_types::CodeActionProviderCapability::Options(_)) => true,
+        None => false,
+    })
+}
+
+/// Whether a server answers `textDocument/documentHighlight`
+fn provides_document_highlights(server: &LanguageServer) -> bool {
+    server.capabilities().is_some_and(|caps| match caps.document_highlight_provider {
+        Some(lsp_types::OneOf::Left(enabled)) => enabled,
+        Some(lsp_types::OneOf::Right(_)) => true,
+        None => false,
+    })
+}
+
+/// Whether a server answers `textDocument/prepareTypeHierarchy`
+fn provides_type_hierarchy(server: &LanguageServer) -> bool {
+    // Only an explicit `false` turns an advertised provider off
+    server.capabilities().is_some_and(|caps| {
+        caps.type_hierarchy_provider
+            .is_some_and(|provider| serde_json::to_value(provider).is_ok_and(|value| value != false))
+    })
+}
+
+/// Marks a server message's severity, like the diagnostic counts in the status bar
+fn message_type_symbol(kind: lsp_types::MessageType) -> &'static str {
+    match kind {
+        lsp_types::MessageType::ERROR => "⊗",
+        lsp_types::MessageType::WARNING => "⚠",
+        _ => "ℹ",
     }
+}
 
-    /// Manually refresh the markdown preview for the current file
-    pub fn refresh_markdown_preview(&mut self, cx: &mut Context<Self>) {
-        if let Some(index) = self.current_file_index {
-            if let Some(file) = self.open_files.get_mut(index) {
-                if file.render_as_markdown {
-                    let content = file.input_state.read(cx).value().to_string();
-                    file.markdown_preview_cache = content;
-                    file.last_markdown_render = Some(Instant::now());
-                    cx.notify();
-                }
-            }
+/// A symbol's kind and name, as listed in the breadcrumbs
+fn symbol_label(symbol: &SymbolNode) -> String {
+    match super::lsp::symbol_kind_label(symbol.kind) {
+        "" => symbol.name.clone(),
+        kind => format!("{} {}", kind, symbol.name),
+    }
+}
+
+/// Undo or redo the last edit of `file` from its own history
+fn step_undo_history(file: &mut OpenFile, redo: bool, window: &mut Window, cx: &mut App) {
+    let history = &mut file.undo_history;
+    file.input_state.update(cx, |state, cx| {
+        let text = state.value().to_string();
+        let step = if redo { history.redo(&text) } else { history.undo(&text) };
+        if let Some((new_text, selection)) = step {
+            replace_input_text(state, &text, &new_text, window, cx);
+            state.set_selected_range(selection, window, cx);
         }
+    });
+}
+
+impl OpenFile {
+    /// Whether saving would write something other than what was last loaded or saved
+    fn differs_from_saved(&self, cx: &App) -> bool {
+        self.line_ending != self.saved_line_ending || content_hash(&self.input_state.read(cx).value()) != self.saved_content_hash
     }
+}
 
-    /// Set the global rust analyzer manager.
-    ///
-    /// Also retroactively wires LSP providers for files already open so that
-    /// files opened before the host analyzer was injected still get full
-    /// completion / hover / go-to-definition support.
-    pub fn set_rust_analyzer(
-        &mut self,
-        analyzer: Entity<RustAnalyzerManager>,
-        cx: &mut Context<Self>,
-    ) {
-        tracing::debug!(
-            "[LSP] TextEditor::set_rust_analyzer called, open_files={}",
-            self.open_files.len()
-        );
-        self.rust_analyzer = Some(analyzer.clone());
+/// Warning for a file that had bytes `encoding` couldn't read
+fn invalid_bytes_message(path: &Path, encoding: FileEncoding) -> String {
+    format!(
+        "{} isn't valid {}; unreadable bytes show as \u{fffd} and saving replaces them",
+        path.file_name().unwrap_or_default().to_string_lossy(),
+        encoding.label()
+    )
+}
 
-        if self.open_files.is_empty() {
-            tracing::debug!("[LSP] No open files to back-fill LSP providers for.");
-            return;
-        }
+/// Hash buffer content so modification state can be compared against the saved state
+fn content_hash(content: &str) -> u64 {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    hasher.finish()
+}
 
-        // Back-fill LSP providers for files that were already open before the
-        // analyzer arrived (e.g., files opened in ScriptEditor::new before the
-        // host calls set_rust_analyzer).
-        let files: Vec<(PathBuf, Entity<InputState>)> = self
-            .open_files
-            .iter()
-            .map(|f| (f.path.clone(), f.input_state.clone()))
-            .collect();
+/// Identifier-like word surrounding a byte offset
+fn word_at(text: &str, offset: usize) -> Option<String> {
+    word_range_at(text, offset).map(|range| text[range].to_string())
+}
 
-        for (path, input_state) in files {
-            let ext = path
-                .extension()
-                .and_then(|e| e.to_str())
-                .unwrap_or("")
-                .to_owned();
-            if ext != "rs" {
-                continue; // Only Rust files use rust-analyzer
+/// Byte range of the identifier-like word surrounding a byte offset
+fn word_range_at(text: &str, offset: usize) -> Option<Range<usize>> {
+    let offset = offset.min(text.len());
+    let is_word = |ch: char| ch.is_alphanumeric() || ch == '_';
+    let start = text[..offset]
+        .char_indices()
+        .rev()
+        .take_while(|(_, ch)| is_word(*ch))
+        .last()
+        .map(|(ix, _)| ix)
+        .unwrap_or(offset);
+    let end = text[offset..]
+        .char_indices()
+        .find(|(_, ch)| !is_word(*ch))
+        .map(|(ix, _)| offset + ix)
+        .unwrap_or(text.len());
+    (start < end).then_some(start..end)
+}
+
+/// Replace `edits` (sorted ranges of `text`) in one go, keeping `selection` on
+/// the same text: selected again when `select`, otherwise as the cursor
+fn replace_ranges_keeping_selection(
+    state: &mut InputState,
+    text: &str,
+    edits: &[(Range<usize>, String)],
+    selection: Range<usize>,
+    select: bool,
+    window: &mut Window,
+    cx: &mut Context<InputState>,
+) {
+    let shifted = |offset: usize| {
+        let mut shifted = offset;
+        for (range, new_text) in edits {
+            if range.start > offset {
+                break;
             }
-            tracing::debug!(
-                "[LSP] Back-filling providers for already-open file {:?}",
-                path.file_name()
-            );
-            let a2 = analyzer.clone();
-            let ws = self
-                .resolve_workspace_root_for_file(&path)
-                .unwrap_or_else(|| path.parent().map(|p| p.to_path_buf()).unwrap_or_default());
-            let p2 = path.clone();
-            input_state.update(cx, |state, _cx| {
-                let provider = std::rc::Rc::new(
-                    engine_backend::services::lsp_completion_provider::GlobalRustAnalyzerCompletionProvider::new(
-                        a2, p2, ws,
-                    ),
-                );
-                state.lsp.completion_provider = Some(provider.clone());
-                state.lsp.definition_provider = Some(provider.clone());
-                state.lsp.hover_provider = Some(provider);
-                tracing::debug!("[LSP] Back-fill done for {:?}", path.file_name());
-            });
+            let removed = range.end.min(offset) - range.start;
+            shifted = shifted - removed + new_text.len();
         }
+        shifted
+    };
+    let new_selection = shifted(selection.start)..shifted(selection.end);
+    let mut new_text = text.to_string();
+    for (range, new_text_part) in edits.iter().rev() {
+        new_text.replace_range(range.clone(), new_text_part);
     }
+    replace_input_text(state, text, &new_text, window, cx);
+    if select {
+        state.set_selected_range(new_selection, window, cx);
+    } else {
+        let position = super::lsp::offset_to_position(&new_text, new_selection.start);
+        state.set_cursor_position(
+            ui::input::Position::new(position.line, position.character),
+            window,
+            cx,
+        );
+    }
+}
 
-    /// Add pending file panels to the first available TabPanel
-    fn add_pending_panels_to_workspace(&mut self, window: &mut Window, cx: &mut Context<Self>) {
-        if self.pending_panels_to_add.is_empty() || !self.workspace_initialized {
-            return;
-        }
+/// Lines a selection covers; one ending at the start of a line leaves that line out
+fn selected_lines(text: &str, selection: Range<usize>) -> Range<u32> {
+    let start = super::lsp::offset_to_position(text, selection.start);
+    let end = super::lsp::offset_to_position(text, selection.end);
+    let last = if end.line > start.line && end.character == 0 { end.line - 1 } else { end.line };
+    start.line..last + 1
+}
 
-        if let Some(workspace) = self.workspace.clone() {
-            let text_editor_weak = cx.entity().downgrade();
-            let panels_to_add = std::mem::take(&mut self.pending_panels_to_add);
+/// Edits deleting the whitespace at the end of each line
+fn trailing_whitespace_edits(text: &str) -> Vec<lsp_types::TextEdit> {
+    byte_range_edits(text, trailing_whitespace(text).into_iter().map(|range| (range, String::new())))
+}
 
-            // Defer adding panels to avoid reentrant updates
-            window.defer(cx, move |window, cx| {
-                _ = workspace.update(cx, |workspace, cx| {
-                    let dock_area = workspace.dock_area();
+fn byte_range_edits(text: &str, edits: impl IntoIterator<Item = (Range<usize>, String)>) -> Vec<lsp_types::TextEdit> {
+    edits
+        .into_iter()
+        .map(|(range, new_text)| lsp_types::TextEdit {
+            range: lsp_types::Range::new(
+                super::lsp::offset_to_position(text, range.start),
+                super::lsp::offset_to_position(text, range.end),
+            ),
+            new_text,
+        })
+        .collect()
+}
 
-                    // Get the first TabPanel from the center items
-                    if let Some(tab_panel) = dock_area.read(cx).items().left_top_tab_panel(cx) {
-                        for (index, path, input_state) in panels_to_add {
-                            let panel = cx.new(|cx| {
-                                use crate::script_editor::FilePanelWrapper;
-                                FilePanelWrapper::new(
-                                    text_editor_weak.clone(),
-                                    index,
-                                    path,
-                                    input_state,
-                                    cx,
-                                )
-                            });
+/// Whether a file is small enough to wrap without slowing the editor down
+fn wraps_well(lines_count: usize, file_size: usize) -> bool {
+    lines_count < 5_000 && file_size < 500_000
+}
 
-                            _ = tab_panel.update(cx, |tab_panel, cx| {
-                                tab_panel.add_panel(
-                                    std::sync::Arc::new(panel)
-                                        as std::sync::Arc<dyn ui::dock::PanelView>,
-                                    window,
-                                    cx,
-                                );
-                            });
-                        }
-                    }
-                });
-            });
-        }
+fn is_yaml(path: &Path) -> bool {
+    matches!(path.extension().and_then(|ext| ext.to_str()), Some("yaml" | "yml"))
+}
+
+/// Problems the editor finds in a file itself: JSON and TOML errors when no
+/// language server checks it, and tabs in YAML indentation always
+fn local_diagnostics(path: &Path, text: &str, has_server: bool) -> Vec<FileDiagnostic> {
+    if is_yaml(path) {
+        return yaml::tab_indentation_warnings(text);
     }
+    if is_toml(path) {
+        return if has_server { Vec::new() } else { validate_toml(text) };
+    }
+    match JsonDialect::for_path(path) {
+        Some(dialect) if !has_server => validate_json(text, dialect),
+        _ => Vec::new(),
+    }
+}
 
-    /// Initialize/reinitialize workspace with current files
-    fn initialize_workspace_once(&mut self, window: &mut Window, cx: &mut Context<Self>) {
-        // Process pending panels by adding them efficiently
-        if !self.pending_panels_to_add.is_empty() && self.workspace_initialized {
-            self.add_pending_panels_to_workspace(window, cx);
-            return;
-        }
+/// A buffer's change as `track_edit` found it
+pub(crate) struct TrackedEdit {
+    /// Start, and end before and after, as from `TextBuffer::diff`
+    pub edit: (usize, usize, usize),
+    /// The text the edit replaced
+    pub removed: String,
+    /// First line edited, and its last line before and after
+    pub lines: (u32, u32, u32),
+    /// Whole lines inserted at the start of a line, which push it and its bookmark down
+    pub pushes_start_line: bool,
+    /// The change to send a server that syncs incrementally
+    pub change: Option<lsp_types::TextDocumentContentChangeEvent>,
+}
+
+/// Bring a file's copy of its text, and its lazy highlighting, up to date
+/// with what the input now holds: the part of syncing a keystroke that
+/// grows with the file. `None` when nothing changed.
+pub(crate) fn track_edit(
+    synced: &mut TextBuffer,
+    highlighter: Option<&mut LazyHighlighter>,
+    content: &str,
+    incremental: bool,
+) -> Option<TrackedEdit> {
+    let edit = synced.diff(content);
+    let (start, old_end, new_end) = edit;
+    if start == old_end && start == new_end {
+        return None;
+    }
+    let removed = synced.slice(start..old_end);
+    let (first, old_last) = (synced.line_of(start), synced.line_of(old_end));
+    let pushes_start_line = start == old_end
+        && (start == 0 || synced.byte(start - 1) == b'\n')
+        && content[start..new_end].ends_with('\n');
+    let change = incremental.then(|| super::lsp::incremental_change(synced, edit, content));
+    synced.replace(start..old_end, &content[start..new_end]);
+    let new_last = synced.line_of(new_end);
+    if let Some(highlighter) = highlighter {
+        highlighter.edit(synced, first, old_last, new_last);
+    }
+    Some(TrackedEdit {
+        edit,
+        removed,
+        lines: (first, old_last, new_last),
+        pushes_start_line,
+        change,
+    })
+}
 
-        if self.workspace_initialized {
+/// Replace the diagnostics an input renders with `diagnostics`
+fn show_diagnostics(
+    input_state: &Entity<InputState>,
+    text: &str,
+    diagnostics: &[FileDiagnostic],
+    cx: &mut App,
+) {
+    input_state.update(cx, |state, cx| {
+        let Some(set) = state.diagnostics_mut() else {
             return;
-        }
+        };
+        set.clear();
+        set.extend(
+            diagnostics
+                .iter()
+                .map(|diagnostic| super::lsp::to_input_diagnostic(text, diagnostic)),
+        );
+        cx.notify();
+    });
+}
 
-        if let Some(ref workspace) = self.workspace {
-            let text_editor_weak = cx.entity().downgrade();
+/// Give an input its fold arrows and collapsed ranges
+fn show_folds(input_state: &Entity<InputState>, folds: &FoldState, cx: &mut App) {
+    let to_lines = |ranges: &[FoldRange]| -> Vec<Range<u32>> {
+        ranges.iter().map(|range| range.start..range.end + 1).collect()
+    };
+    let foldable = to_lines(folds.foldable());
+    let folded = to_lines(&folds.outermost_folds());
+    input_state.update(cx, |state, cx| {
+        state.set_folds(foldable, folded, FOLD_PLACEHOLDER);
+        cx.notify();
+    });
+}
 
-            workspace.update(cx, |workspace, cx| {
-                let dock_area = workspace.dock_area().downgrade();
+/// Draw inlay hints between characters of an input, leaving its text alone
+fn show_inlay_hints(input_state: &Entity<InputState>, hints: &[InlayHintItem], cx: &mut App) {
+    let hints = hints
+        .iter()
+        .map(|hint| (hint.offset, SharedString::from(hint.label.clone())))
+        .collect();
+    input_state.update(cx, |state, cx| {
+        state.set_inlay_hints(hints);
+        cx.notify();
+    });
+}
 
-                if self.open_files.is_empty() {
-                    // Show welcome panel when no files
-                    let welcome_panel = cx.new(|cx| {
-                        use crate::script_editor::WelcomePanelWrapper;
-                        WelcomePanelWrapper::new(cx)
-                    });
+/// Mark the occurrences of the symbol under an input's cursor, writes apart from reads
+fn show_occurrences(input_state: &Entity<InputState>, occurrences: &Occurrences, cx: &mut App) {
+    let (reads, writes) = (occurrences.reads.clone(), occurrences.writes.clone());
+    input_state.update(cx, |state, cx| {
+        state.set_occurrence_highlights(reads, writes);
+        cx.notify();
+    });
+}
 
-                    workspace.initialize(
-                        ui::dock::DockItem::tabs(
-                            vec![std::sync::Arc::new(welcome_panel)
-                                as std::sync::Arc<dyn ui::dock::PanelView>],
-                            Some(0),
-                            &dock_area,
-                            window,
-                            cx,
-                        ),
-                        None,
-                        None,
-                        None,
-                        window,
-                        cx,
-                    );
-                } else {
-                    // Create panels for all open files
-                    let file_panels: Vec<std::sync::Arc<dyn ui::dock::PanelView>> = self
-                        .open_files
-                        .iter()
-                        .enumerate()
-                        .map(|(index, open_file)| {
-                            let panel = cx.new(|cx| {
-                                use crate::script_editor::FilePanelWrapper;
-                                FilePanelWrapper::new(
-                                    text_editor_weak.clone(),
-                                    index,
-                                    open_file.path.clone(),
-                                    open_file.input_state.clone(),
-                                    cx,
-                                )
-                            });
-                            std::sync::Arc::new(panel) as std::sync::Arc<dyn ui::dock::PanelView>
-                        })
-                        .collect();
+/// Color token ranges of an input by their theme kinds, over its lexer highlighting
+fn show_semantic_highlights(input_state: &Entity<InputState>, highlights: &[(Range<usize>, &'static str)], cx: &mut App) {
+    let highlights = highlights
+        .iter()
+        .map(|(range, kind)| (range.clone(), SharedString::from(*kind)))
+        .collect();
+    input_state.update(cx, |state, cx| {
+        state.set_semantic_highlights(highlights);
+        cx.notify();
+    });
+}
 
-                    workspace.initialize(
-                        ui::dock::DockItem::tabs(
-                            file_panels,
-                            self.current_file_index,
-                            &dock_area,
-                            window,
-                            cx,
-                        ),
-                        None,
-                        None,
-                        None,
-                        window,
-                        cx,
-                    );
-                }
-            });
+/// Color ranges of an input by their theme kinds in place of its own
+/// highlighter, which is off for files lexed lazily
+fn show_lazy_highlights(input_state: &Entity<InputState>, highlights: Vec<(Range<usize>, &'static str)>, cx: &mut App) {
+    let highlights = highlights
+        .into_iter()
+        .map(|(range, kind)| (range, SharedString::from(kind)))
+        .collect();
+    input_state.update(cx, |state, cx| {
+        state.set_syntax_highlights(highlights);
+        cx.notify();
+    });
+}
 
-            self.workspace_initialized = true;
+/// Bracket pairs of a file's current text, found again only after it changes
+fn file_bracket_pairs(file: &mut OpenFile, text: SharedString) -> Rc<BracketPairs> {
+    match &file.bracket_pairs {
+        Some((built, pairs)) if *built == text => pairs.clone(),
+        _ => {
+            let pairs = Rc::new(if is_toml(&file.path) {
+                BracketPairs::from_brackets(toml_brackets(&text))
+            } else {
+                BracketPairs::new(&text)
+            });
+            file.bracket_pairs = Some((text, pairs.clone()));
+            pairs
         }
     }
+}
 
-    /// Mark that files have changed (workspace will re-render)
-    fn mark_files_changed(&mut self, cx: &mut Context<Self>) {
-        // Just notify - the workspace will pick up changes on next render
+/// Render the extra cursors (and their selections) in an input
+fn show_extra_cursors(input_state: &Entity<InputState>, multi_cursor: &MultiCursor, cx: &mut App) {
+    let ranges = multi_cursor
+        .selections()
+        .iter()
+        .map(|selection| selection.range())
+        .collect();
+    input_state.update(cx, |state, cx| {
+        state.set_secondary_selections(ranges);
         cx.notify();
+    });
+}
+
+/// Formatting for `file`'s `text`, or only its byte `range` when the server formats ranges
+fn formatting_request(
+    file: &OpenFile,
+    server: &LanguageServer,
+    text: &str,
+    range: Option<Range<usize>>,
+) -> Option<PendingRequest<Option<Vec<lsp_types::TextEdit>>>> {
+    let uri = super::lsp::path_to_uri(&file.path)?;
+    let options = lsp_types::FormattingOptions {
+        tab_size: file.indent_style.width,
+        insert_spaces: !file.indent_style.hard_tabs,
+        trim_trailing_whitespace: Some(file.editorconfig.trim_trailing_whitespace.unwrap_or(true)),
+        insert_final_newline: Some(file.editorconfig.insert_final_newline.unwrap_or(true)),
+        ..Default::default()
+    };
+    let supports_range = server
+        .capabilities()
+        .is_some_and(|caps| caps.document_range_formatting_provider.is_some());
+    Some(match range {
+        Some(range) if supports_range => {
+            server.request::<lsp_types::request::RangeFormatting>(lsp_types::DocumentRangeFormattingParams {
+                text_document: lsp_types::TextDocumentIdentifier { uri },
+                range: super::lsp::offsets_to_range(text, range),
+                options,
+                work_done_progress_params: Default::default(),
+            })
+        }
+        _ => server.request::<lsp_types::request::Formatting>(lsp_types::DocumentFormattingParams {
+            text_document: lsp_types::TextDocumentIdentifier { uri },
+            options,
+            work_done_progress_params: Default::default(),
+        }),
+    })
+}
+
+/// `response`, or an error once the server has taken `BEFORE_SAVE_TIMEOUT` over it
+async fn before_save<T>(
+    response: impl Future<Output = anyhow::Result<T>>,
+    executor: &BackgroundExecutor,
+) -> anyhow::Result<T> {
+    let timeout = executor.timer(BEFORE_SAVE_TIMEOUT);
+    match futures::future::select(Box::pin(response), timeout).await {
+        futures::future::Either::Left((response, _)) => response,
+        futures::future::Either::Right(_) => Err(anyhow::anyhow!("no answer in {}s", BEFORE_SAVE_TIMEOUT.as_secs())),
+    }
+}
+
+/// Apply LSP edits to an input as one replacement so a single undo reverts them
+///
+/// The cursor stays on the same code; inside the rewritten region it keeps its
+/// line and column as closely as the new text allows.
+pub(super) fn apply_edits_to_input(
+    input_state: &Entity<InputState>,
+    edits: Vec<lsp_types::TextEdit>,
+    window: &mut Window,
+    cx: &mut App,
+) {
+    input_state.update(cx, |state, cx| {
+        let old_text = state.value().to_string();
+        let new_text = super::lsp::apply_text_edits(&old_text, edits);
+        replace_input_text(state, &old_text, &new_text, window, cx);
+    });
+}
+
+/// Replace only the part of an input's text that differs from `new_text`,
+/// keeping the cursor on the same code
+fn replace_input_text(
+    state: &mut InputState,
+    old_text: &str,
+    new_text: &str,
+    window: &mut Window,
+    cx: &mut Context<InputState>,
+) {
+    if new_text == old_text {
+        return;
     }
 
-    /// Create a new empty file
-    pub fn create_new_file(&mut self, window: &mut Window, cx: &mut Context<Self>) {
-        // Generate a unique untitled file name
-        let mut counter = 1;
-        let mut new_path = PathBuf::from(format!("untitled-{}.txt", counter));
-        while self.open_files.iter().any(|f| f.path == new_path) {
-            counter += 1;
-            new_path = PathBuf::from(format!("untitled-{}.txt", counter));
+    let cursor = state.cursor();
+    let edit = super::lsp::edit_bounds(old_text, new_text);
+    let (start, old_end, new_end) = edit;
+    let utf16_range = super::lsp::offset_to_utf16(old_text, start)
+        ..super::lsp::offset_to_utf16(old_text, old_end);
+    state.replace_text_in_range(Some(utf16_range), &new_text[start..new_end], window, cx);
+
+    let position = match super::lsp::remap_range(cursor..cursor, edit) {
+        Some(range) => super::lsp::offset_to_position(new_text, range.start),
+        None => {
+            let old_position = super::lsp::offset_to_position(old_text, cursor);
+            let offset = super::lsp::position_to_offset(new_text, old_position);
+            super::lsp::offset_to_position(new_text, offset)
         }
+    };
+    state.set_cursor_position(
+        ui::input::Position::new(position.line, position.character),
+        window,
+        cx,
+    );
+}
 
-        // Create an empty file in memory
-        let language = "text";
-        let input_state = cx.new(|cx| {
-            let mut state = InputState::new(window, cx)
-                .code_editor(language)
-                .line_number(true)
-                .minimap(true) // Enable VSCode-style minimap
-                .tab_size(TabSize {
-                    tab_size: 4,
-                    hard_tabs: false,
-                })
-                .soft_wrap(true);
+impl TextEditor {
+    pub fn new(window: &mut Window, cx: &mut Context<Self>) -> Self {
+        let markdown_split_state = ResizableState::new(cx);
 
-            state.set_value("", window, cx);
-            state
+        let welcome_view = cx.new(|cx| WelcomePanelWrapper::new(cx));
+
+        let find_bar = cx.new(|cx| FindBar::new(window, cx));
+        let find_bar_subscription = cx.subscribe(&find_bar, |this, _find_bar, event: &FindBarEvent, cx| {
+            match event {
+                FindBarEvent::Dismissed => {
+                    this.find_bar_visible = false;
+                    cx.notify();
+                }
+            }
         });
 
-        let open_file = OpenFile {
-            path: new_path.clone(),
-            input_state: input_state.clone(),
-            is_modified: false,
-            lines_count: 1,
-            file_size: 0,
-            version: 1,
-            render_as_markdown: false,
-            markdown_preview_cache: String::new(),
-            last_markdown_render: None,
-            pending_scroll_target: None,
-        };
+        let (file_watcher, mut file_changes) = FileWatcher::new();
+        let file_change_task = cx.spawn(async move |this, cx| {
+            use futures::StreamExt as _;
+            while let Some(path) = file_changes.next().await {
+                // Saves and checkouts arrive as bursts of events
+                cx.background_executor().timer(WATCH_DEBOUNCE).await;
+                let mut paths = HashSet::from([path]);
+                while let Ok(Some(path)) = file_changes.try_next() {
+                    paths.insert(path);
+                }
+                let handled = this.update(cx, |this, cx| {
+                    for path in paths {
+                        this.handle_disk_change(path, cx);
+                    }
+                });
+                if handled.is_err() {
+                    break;
+                }
+            }
+        });
 
-        self.open_files.push(open_file);
-        let new_index = self.open_files.len() - 1;
-        self.current_file_index = Some(new_index);
+        Self {
+            focus_handle: cx.focus_handle(),
+            open_files: Vec::new(),
+            current_file_index: None,
+            last_render_time: None,
+            show_performance_stats: false,
+            show_minimap: true,
+            sticky_scroll_depth: DEFAULT_STICKY_SCROLL_DEPTH,
+            file_positions: FilePositions::load(),
+            indent_styles: HashMap::new(),
+            comment_syntaxes: HashMap::new(),
+            detect_indentation: true,
+            subscriptions: vec![find_bar_subscription],
+            rust_analyzer: None,
+            markdown_split_state,
+            pending_navigation: None,
+            definition_picker: None,
+            peek: None,
+            navigation: NavigationHistory::default(),
+            expected_jump: None,
+            snippets: Arc::new(SnippetLibrary::builtin()),
+            pending_snippet_mirrors: Vec::new(),
+            pending_snippet_selections: Vec::new(),
+            pending_additional_edits: Vec::new(),
+            python_interpreters: HashMap::new(),
+            interpreter_picker: None,
+            diagnostic_listeners: Vec::new(),
+            server_restart_tasks: HashMap::new(),
+            server_progress: HashMap::new(),
+            server_messages: ServerMessages::default(),
+            rename_session: None,
+            edit_requests_in_flight: 0,
+            go_to_line: None,
+            line_flash_task: None,
+            signature_help: None,
+            signature_help_task: None,
+            code_actions: None,
+            code_actions_task: None,
+            occurrence_task: None,
+            pending_workspace_edits: Vec::new(),
+            status_message: None,
+            status_message_task: None,
+            find_bar,
+            find_bar_visible: false,
+            pending_cursor_edits: Vec::new(),
+            pending_cursor_moves: Vec::new(),
+            fold_refresh_tasks: HashMap::new(),
+            symbol_refresh_tasks: HashMap::new(),
+            breadcrumb_menu: None,
+            blame_mode: BlameMode::Off,
+            file_blame: HashMap::new(),
+            blame_tasks: HashMap::new(),
+            blame_popup: None,
+            blame_popup_task: None,
+            git_diffs: HashMap::new(),
+            git_diff_tasks: HashMap::new(),
+            diff_peek: None,
+            _git_diff_poll: cx.spawn(async move |this, cx| loop {
+                cx.background_executor().timer(GIT_DIFF_POLL_INTERVAL).await;
+                if this.update(cx, |this, cx| this.recheck_git_diffs(cx)).is_err() {
+                    break;
+                }
+            }),
+            file_watcher,
+            pending_reloads: Vec::new(),
+            _file_change_task: file_change_task,
+            file_views: HashMap::new(),
+            welcome_view,
+            last_reported_dirty: false,
+            language_servers: LanguageServerPool::global(cx),
+            editor_id: cx.entity_id().as_u64(),
+            json_schemas: Vec::new(),
+            run_commands: BTreeMap::new(),
+            drop_templates: BTreeMap::new(),
+            wrap_mode: WrapMode::Off,
+            render_whitespace: RenderWhitespace::None,
+            large_file_threshold_mb: large_files::DEFAULT_LARGE_FILE_THRESHOLD_MB,
+            save_cleanup: SaveCleanup {
+                trim_trailing_whitespace: false,
+                insert_final_newline: true,
+                trim_final_newlines: true,
+            },
+            language_save_cleanup: BTreeMap::new(),
+            save_when_formatting_fails: false,
+            highlight_current_line: true,
+            inlay_hints_enabled: true,
+            vim: None,
+            inlay_hint_tasks: HashMap::new(),
+            semantic_token_tasks: HashMap::new(),
+            lazy_highlight_tasks: HashMap::new(),
+            markdown_preview_tasks: HashMap::new(),
+        }
+    }
 
-        // Queue panel to be added on next render
-        self.pending_panels_to_add
-            .push((new_index, new_path.clone(), input_state.clone()));
+    /// Whether any open file has changes that differ from its saved state
+    pub fn is_dirty(&self) -> bool {
+        self.open_files.iter().any(|f| f.is_modified)
+    }
 
-        // Create subscription for this file
-        let analyzer = self.rust_analyzer.clone();
-        tracing::debug!(
-            "📝 Creating change subscription for new file, rust_analyzer present: {}",
-            analyzer.is_some()
-        );
-        let subscription = cx.subscribe(
-            &input_state,
-            move |this: &mut TextEditor,
-                  input_state_entity: Entity<InputState>,
-                  event: &InputEvent,
-                  cx: &mut Context<TextEditor>| {
-                if let InputEvent::Change = event {
-                    if let Some(index) = this
-                        .open_files
-                        .iter()
-                        .position(|f| f.input_state == input_state_entity)
-                    {
-                        if let Some(file) = this.open_files.get_mut(index) {
-                            file.is_modified = true;
-                            file.version += 1;
+    /// Whether the open file at `path` has unsaved changes
+    pub fn is_file_modified(&self, path: &PathBuf) -> bool {
+        self.open_files
+            .iter()
+            .any(|f| &f.path == path && f.is_modified)
+    }
 
-                            // Notify rust-analyzer of the change
-                            if let Some(ref analyzer) = analyzer {
-                                let path = file.path.clone();
-                                let version = file.version;
-                                let content = file.input_state.read(cx).value().to_string();
+    /// Save an open file's cursor and scroll position for when it is reopened
+    fn remember_position(&mut self, index: usize, cx: &App) {
+        let Some(file) = self.open_files.get(index) else {
+            return;
+        };
+        // Untitled buffers have nothing on disk to come back to
+        if !file.path.exists() {
+            return;
+        }
+        let state = file.input_state.read(cx);
+        let position = FilePosition::new(state.cursor(), -f32::from(state.get_scroll_offset().y));
+        self.file_positions.set(&file.path, position);
+    }
 
-                                tracing::debug!(
-                                    "📝 File changed: {:?} (version {}), notifying rust-analyzer",
-                                    path.file_name(),
-                                    version
-                                );
-                                analyzer.update(cx, |analyzer, _cx| {
-                                    if let Err(e) =
-                                        analyzer.did_change_file(&path, &content, version)
-                                    {
-                                        tracing::error!(
-                                            "⚠️  Failed to notify rust-analyzer of file change: {}",
-                                            e
-                                        );
-                                    } else {
-                                        if version % 10 == 0 {
-                                            // Log every 10th change to avoid spam
-                                            tracing::debug!(
-                                                "✓ Notified rust-analyzer of change (version {})",
-                                                version
-                                            );
-                                        }
-                                    }
-                                });
-                            } else {
-                                if file.version == 2 {
-                                    // Only log once to avoid spam
-                                    tracing::debug!("⚠️  No rust-analyzer available for didChange");
-                                }
-                            }
+    fn remember_current_position(&mut self, cx: &App) {
+        if let Some(index) = self.current_file_index {
+            self.remember_position(index, cx);
+        }
+    }
 
-                            cx.notify();
-                        }
-                    }
-                }
-            },
-        );
+    /// Document version of an open file, bumped on every edit
+    pub fn file_version(&self, path: &PathBuf) -> Option<i32> {
+        self.open_files
+            .iter()
+            .find(|f| &f.path == path)
+            .map(|f| f.version)
+    }
 
-        self.subscriptions.push(subscription);
+    pub fn minimap_enabled(&self) -> bool {
+        self.show_minimap
+    }
 
-        tracing::debug!("✓ Created new file: {:?}", new_path);
-        cx.notify();
+    pub fn set_sticky_scroll_depth(&mut self, depth: usize) {
+        self.sticky_scroll_depth = depth;
     }
 
-    /// Open a file picker dialog (platform-specific)
-    pub fn open_folder_dialog(&mut self, _window: &mut Window, cx: &mut Context<Self>) {
-        // For now, open the current working directory
-        // In a real implementation, this would show a platform file picker
-        if let Ok(cwd) = std::env::current_dir() {
-            tracing::debug!("✓ Opening folder: {:?}", cwd);
-            // Emit an event or call a method to open this folder in the file explorer
-            cx.emit(TextEditorEvent::OpenFolderRequested(cwd));
+    /// Lines and text of the scope headers to pin above the view of `path`
+    /// when `top_line` is the first line in it
+    pub fn sticky_lines(&self, path: &Path, top_line: u32) -> Vec<(u32, String)> {
+        if self.sticky_scroll_depth == 0 {
+            return Vec::new();
         }
+        let Some(file) = self.open_files.iter().find(|file| file.path == path) else {
+            return Vec::new();
+        };
+        let scopes = if file.symbols.is_empty() {
+            file.folds.foldable().to_vec()
+        } else {
+            symbol_scopes(&file.symbols)
+        };
+        sticky_lines(&scopes, top_line, self.sticky_scroll_depth)
+            .into_iter()
+            .map(|line| {
+                let text = file.synced.slice(file.synced.line_range(line));
+                (line, text.trim_end().to_string())
+            })
+            .collect()
+    }
+
+    pub fn toggle_minimap(&mut self, cx: &mut Context<Self>) {
+        self.show_minimap = !self.show_minimap;
         cx.notify();
     }
 
-    /// Show search/find dialog
-    pub fn show_find_dialog(&mut self, _window: &mut Window, cx: &mut Context<Self>) {
-        if let Some(index) = self.current_file_index {
-            if let Some(file) = self.open_files.get(index) {
-                // Trigger search on the input state
-                file.input_state.update(cx, |state, cx| {
-                    // The InputState has a search panel that can be shown
-                    // Emit focus event to potentially show search
-                    tracing::debug!("✓ Opening search panel for current file");
-                    cx.emit(ui::input::InputEvent::Focus);
-                });
-            }
+    pub fn blame_mode(&self) -> BlameMode {
+        self.blame_mode
+    }
+
+    /// Cycle blame annotations: off, the cursor's line, every line
+    pub fn cycle_blame_mode(&mut self, cx: &mut Context<Self>) {
+        let (mode, message) = match self.blame_mode {
+            BlameMode::Off => (BlameMode::CurrentLine, "Git blame: current line"),
+            BlameMode::CurrentLine => (BlameMode::AllLines, "Git blame: all lines"),
+            BlameMode::AllLines => (BlameMode::Off, "Git blame off"),
+        };
+        self.blame_mode = mode;
+        if mode == BlameMode::Off {
+            self.blame_popup = None;
+            self.blame_tasks.clear();
         }
+        self.set_status_message(message, cx);
         cx.notify();
     }
 
-    /// Show find and replace dialog
-    pub fn show_replace_dialog(&mut self, _window: &mut Window, cx: &mut Context<Self>) {
-        if let Some(index) = self.current_file_index {
-            if let Some(file) = self.open_files.get(index) {
-                // Trigger search/replace on the input state
-                file.input_state.update(cx, |state, cx| {
-                    tracing::debug!("✓ Opening find/replace panel for current file");
-                    // The search panel supports replace functionality
-                    cx.emit(ui::input::InputEvent::Focus);
+    /// Zero-based line the cursor of an open file is on
+    pub fn cursor_line(&self, path: &Path) -> Option<u32> {
+        self.open_files.iter().find(|file| file.path == path).map(|file| file.cursor_line)
+    }
+
+    /// Whether `path` turned out not to be under version control
+    pub fn blame_untracked(&self, path: &Path) -> bool {
+        matches!(self.file_blame.get(path), Some(None))
+    }
+
+    /// Blame of the current text of `path`, for the lines fetched so far
+    pub fn blame_for(&self, path: &Path) -> Option<&HashMap<u32, Arc<BlameCommit>>> {
+        let version = self.open_files.iter().find(|file| file.path == path)?.version;
+        let blame = self.file_blame.get(path)?.as_ref()?;
+        (blame.version == version).then_some(&blame.lines)
+    }
+
+    /// Make sure blame is known (or being fetched) for `lines` of `path`.
+    ///
+    /// Blame is fetched per chunk of lines as they scroll into view and kept
+    /// until the buffer changes or the file is saved (which may precede a commit).
+    pub fn ensure_blame(&mut self, path: &Path, lines: Range<u32>, cx: &mut Context<Self>) {
+        if self.blame_mode == BlameMode::Off {
+            return;
+        }
+        let Some(file) = self.open_files.iter().find(|file| file.path == path) else {
+            return;
+        };
+        let version = file.version;
+        let path = path.to_path_buf();
+        match self.file_blame.get_mut(&path) {
+            // Not under version control
+            Some(None) => {}
+            Some(Some(blame)) if blame.version == version => {
+                let chunks = lines.start / BLAME_CHUNK_LINES..=lines.end.saturating_sub(1) / BLAME_CHUNK_LINES;
+                let missing: Vec<u32> = chunks.filter(|chunk| blame.requested_chunks.insert(*chunk)).collect();
+                for chunk in missing {
+                    self.fetch_blame_chunk(path.clone(), chunk, cx);
+                }
+            }
+            _ => {
+                let key = (path.clone(), u32::MAX);
+                if self.blame_tasks.contains_key(&key) {
+                    return;
+                }
+                // Typing bumps the version on every keystroke; wait for a pause
+                let debounce = self.file_blame.contains_key(&path);
+                let task = cx.spawn({
+                    let path = path.clone();
+                    async move |this, cx| {
+                        if debounce {
+                            cx.background_executor().timer(BLAME_DELAY).await;
+                        }
+                        let head = cx
+                            .background_executor()
+                            .spawn({
+                                let path = path.clone();
+                                async move { head_commit(&path) }
+                            })
+                            .await;
+                        this.update(cx, |this, cx| {
+                            this.blame_tasks.remove(&(path.clone(), u32::MAX));
+                            let Some(version) = this.file_version(&path) else {
+                                return;
+                            };
+                            match head {
+                                Some(_) => {
+                                    this.file_blame.insert(
+                                        path.clone(),
+                                        Some(FileBlame {
+                                            version,
+                                            lines: HashMap::new(),
+                                            requested_chunks: HashSet::new(),
+                                        }),
+                                    );
+                                    this.ensure_blame(&path, lines, cx);
+                                }
+                                None => {
+                                    this.file_blame.insert(path, None);
+                                }
+                            }
+                            cx.notify();
+                        })
+                        .ok();
+                    }
                 });
+                self.blame_tasks.insert(key, task);
             }
         }
-        cx.notify();
     }
 
-    /// Run the current script file
-    pub fn run_current_file(&mut self, _window: &mut Window, cx: &mut Context<Self>) {
-        if let Some(index) = self.current_file_index {
-            if let Some(file) = self.open_files.get(index) {
-                let path = file.path.clone();
-                let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
-
-                tracing::debug!("🚀 Running file: {:?}", path);
-
-                // Determine how to run based on file extension
-                let command = match extension {
-                    "rs" => format!(
-                        "rustc {} && ./{}",
-                        path.display(),
-                        path.with_extension("").display()
-                    ),
-                    "py" => format!("python {}", path.display()),
-                    "js" | "ts" => format!("node {}", path.display()),
-                    "sh" => format!("bash {}", path.display()),
-                    _ => {
-                        tracing::debug!("⚠️  Don't know how to run .{} files", extension);
-                        cx.emit(TextEditorEvent::RunScriptRequested(
-                            path,
-                            "unknown".to_string(),
-                        ));
+    fn fetch_blame_chunk(&mut self, path: PathBuf, chunk: u32, cx: &mut Context<Self>) {
+        let Some(file) = self.open_files.iter().find(|file| file.path == path) else {
+            return;
+        };
+        let text = file.input_state.read(cx).value().to_string();
+        let version = file.version;
+        let line_count = text.split('\n').count() as u32;
+        let start = chunk * BLAME_CHUNK_LINES;
+        if start >= line_count {
+            return;
+        }
+        let lines = start..(start + BLAME_CHUNK_LINES).min(line_count);
+
+        let task = cx.spawn({
+            let path = path.clone();
+            async move |this, cx| {
+                let blamed = cx
+                    .background_executor()
+                    .spawn({
+                        let path = path.clone();
+                        async move { blame_lines(&path, &text, lines) }
+                    })
+                    .await;
+                this.update(cx, |this, cx| {
+                    this.blame_tasks.remove(&(path.clone(), chunk));
+                    let Some(Some(blame)) = this.file_blame.get_mut(&path) else {
+                        return;
+                    };
+                    if blame.version != version {
                         return;
                     }
+                    match blamed {
+                        Some(blamed) => blame.lines.extend(blamed),
+                        // New files git hasn't seen yet can't be blamed
+                        None => {
+                            this.file_blame.insert(path, None);
+                        }
+                    }
+                    cx.notify();
+                })
+                .ok();
+            }
+        });
+        self.blame_tasks.insert((path, chunk), task);
+    }
+
+    /// Changes of `path` against `HEAD`; `None` until first computed
+    pub fn git_diff_hunks(&self, path: &Path) -> Option<&[DiffHunk]> {
+        self.git_diffs.get(path).map(|diff| diff.hunks.as_slice())
+    }
+
+    /// Compute the changes of `path` against `HEAD` if they never were
+    pub fn ensure_git_diff(&mut self, path: &Path, cx: &mut Context<Self>) {
+        if !self.git_diffs.contains_key(path) && !self.git_diff_tasks.contains_key(path) {
+            self.schedule_git_diff(path.to_path_buf(), Duration::ZERO, cx);
+        }
+    }
+
+    /// Recompute the diff of `path` against `HEAD` after `delay`, replacing a pending recompute
+    fn schedule_git_diff(&mut self, path: PathBuf, delay: Duration, cx: &mut Context<Self>) {
+        let Some(file) = self.open_files.iter().find(|file| file.path == path) else {
+            return;
+        };
+        let input_state = file.input_state.clone();
+        let task = cx.spawn({
+            let path = path.clone();
+            async move |this, cx| {
+                cx.background_executor().timer(delay).await;
+                let Ok(text) = input_state.read_with(cx, |state, _cx| state.value().to_string()) else {
+                    return;
                 };
+                let (hunks, modified) = cx
+                    .background_executor()
+                    .spawn({
+                        let path = path.clone();
+                        async move {
+                            let modified = fs::metadata(&path).and_then(|metadata| metadata.modified()).ok();
+                            let hunks = head_text(&path).map(|head| diff_hunks(&head, &text)).unwrap_or_default();
+                            (hunks, modified)
+                        }
+                    })
+                    .await;
+                this.update(cx, |this, cx| {
+                    this.git_diff_tasks.remove(&path);
+                    if this.diff_peek.as_ref().is_some_and(|(peeked, _)| *peeked == path) {
+                        this.diff_peek = None;
+                    }
+                    this.git_diffs.insert(path, FileGitDiff { hunks, modified });
+                    cx.notify();
+                })
+                .ok();
+            }
+        });
+        self.git_diff_tasks.insert(path, task);
+    }
 
-                cx.emit(TextEditorEvent::RunScriptRequested(path, command));
+    /// Recompute diffs of files changed on disk since their diff was taken
+    fn recheck_git_diffs(&mut self, cx: &mut Context<Self>) {
+        let changed: Vec<PathBuf> = self
+            .git_diffs
+            .iter()
+            .filter(|(path, diff)| fs::metadata(path).and_then(|metadata| metadata.modified()).ok() != diff.modified)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in changed {
+            if !self.git_diff_tasks.contains_key(&path) {
+                self.schedule_git_diff(path, GIT_DIFF_DELAY, cx);
             }
         }
+    }
+
+    /// Show the `HEAD` version of the lines of a hunk, or hide it if already shown
+    pub fn toggle_diff_peek(&mut self, path: &Path, hunk_index: usize, cx: &mut Context<Self>) {
+        let peek = (path.to_path_buf(), hunk_index);
+        self.diff_peek = if self.diff_peek.as_ref() == Some(&peek) { None } else { Some(peek) };
         cx.notify();
     }
 
-    /// Debug the current script file
-    pub fn debug_current_file(&mut self, _window: &mut Window, cx: &mut Context<Self>) {
-        if let Some(index) = self.current_file_index {
-            if let Some(file) = self.open_files.get(index) {
-                let path = file.path.clone();
-                tracing::debug!("🐛 Debugging file: {:?}", path);
-                cx.emit(TextEditorEvent::DebugScriptRequested(path));
-            }
+    /// Hunk of `path` being peeked at, with its index
+    pub fn diff_peek(&self, path: &Path) -> Option<(usize, &DiffHunk)> {
+        let (peeked, index) = self.diff_peek.as_ref()?;
+        if peeked != path {
+            return None;
         }
-        cx.notify();
+        Some((*index, self.git_diffs.get(path)?.hunks.get(*index)?))
     }
 
-    pub fn open_file(&mut self, path: PathBuf, window: &mut Window, cx: &mut Context<Self>) {
-        // Check if file is already open
-        if let Some(index) = self.open_files.iter().position(|f| f.path == path) {
-            self.current_file_index = Some(index);
+    pub fn dismiss_diff_peek(&mut self, cx: &mut Context<Self>) {
+        if self.diff_peek.take().is_some() {
             cx.notify();
-            return;
         }
+    }
 
-        // Read file content with timing
-        let read_start = Instant::now();
-        let content = match fs::read_to_string(&path) {
-            Ok(content) => {
-                let read_time = read_start.elapsed();
-                tracing::debug!(
-                    "✓ Read file {:?} - {} bytes in {:.2}ms",
-                    path.file_name().unwrap_or_default(),
-                    content.len(),
-                    read_time.as_secs_f64() * 1000.0
-                );
-                content
-            }
-            Err(err) => {
-                tracing::error!("✗ Failed to read file: {:?}, error: {}", path, err);
-                return;
-            }
+    /// Put a hunk's lines back the way they are in `HEAD`, as an undoable edit
+    pub fn revert_diff_hunk(&mut self, path: &Path, hunk_index: usize, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(hunk) = self.git_diffs.get(path).and_then(|diff| diff.hunks.get(hunk_index)).cloned() else {
+            return;
         };
+        let Some(file) = self.open_files.iter().find(|file| file.path == path) else {
+            return;
+        };
+        let input_state = file.input_state.clone();
+        let reverted = input_state.update(cx, |state, cx| {
+            let text = state.value().to_string();
+            let reverted = revert_hunk(&text, &hunk)?;
+            replace_input_text(state, &text, &reverted, window, cx);
+            Some(())
+        });
+        self.diff_peek = None;
+        match reverted {
+            Some(()) => {
+                // The hunk is gone from the buffer; drop it until the next save
+                if let Some(diff) = self.git_diffs.get_mut(path) {
+                    diff.hunks.remove(hunk_index);
+                }
+            }
+            None => self.set_status_message("The changed lines were edited; save to refresh the diff", cx),
+        }
+        cx.notify();
+    }
 
-        let file_size = content.len();
-        let lines_count = content.lines().count();
-
-        // Determine syntax highlighting based on file extension
-        let language = self.get_language_from_extension(&path);
-
-        // Check if this is a markdown file
-        let is_markdown = path
-            .extension()
-            .and_then(|ext| ext.to_str())
-            .map(|ext| ext == "md")
-            .unwrap_or(false);
+    /// Stage just one hunk of a saved file
+    pub fn stage_diff_hunk(&mut self, path: &Path, hunk_index: usize, cx: &mut Context<Self>) {
+        if self.open_files.iter().any(|file| file.path == path && file.is_modified) {
+            self.set_status_message("Save the file before staging changes", cx);
+            return;
+        }
+        let Some(hunk) = self.git_diffs.get(path).and_then(|diff| diff.hunks.get(hunk_index)).cloned() else {
+            return;
+        };
+        self.diff_peek = None;
+        let path = path.to_path_buf();
+        cx.spawn(async move |this, cx| {
+            let result = cx.background_executor().spawn(async move { stage_hunk(&path, &hunk) }).await;
+            this.update(cx, |this, cx| match result {
+                Ok(()) => {
+                    this.set_status_message("Staged change", cx);
+                    cx.emit(TextEditorEvent::GitIndexChanged);
+                }
+                Err(e) => {
+                    tracing::error!("⚠️  Failed to stage change: {}", e);
+                    this.set_status_message(format!("Failed to stage change: {}", e), cx);
+                }
+            })
+            .ok();
+        })
+        .detach();
+        cx.notify();
+    }
 
-        tracing::debug!(
-            "📄 Opening file: {} lines, {} KB, language: {}{}",
-            lines_count,
-            file_size / 1024,
-            language,
-            if is_markdown {
-                " (markdown preview mode)"
+    /// Open the commit behind the blame annotation of `line`, loading its full message
+    pub fn show_blame_commit(&mut self, path: &Path, line: u32, cx: &mut Context<Self>) {
+        let Some(commit) = self.blame_for(path).and_then(|lines| lines.get(&line)).cloned() else {
+            return;
+        };
+        if self
+            .blame_popup
+            .as_ref()
+            .is_some_and(|popup| popup.path == path && popup.line == line)
+        {
+            self.dismiss_blame_popup(cx);
+            return;
+        }
+        self.blame_popup = Some(BlamePopup {
+            path: path.to_path_buf(),
+            line,
+            commit: commit.clone(),
+            message: None,
+        });
+        self.blame_popup_task = (!commit.is_uncommitted()).then(|| {
+            let path = path.to_path_buf();
+            cx.spawn(async move |this, cx| {
+                let message = cx
+                    .background_executor()
+                    .spawn(async move { commit_message(&path, &commit.sha) })
+                    .await;
+                this.update(cx, |this, cx| {
+                    if let Some(popup) = this.blame_popup.as_mut() {
+                        popup.message = message;
+                        cx.notify();
+                    }
+                })
+                .ok();
+            })
+        });
+        cx.notify();
+    }
+
+    pub fn blame_popup(&self, path: &Path) -> Option<&BlamePopup> {
+        self.blame_popup.as_ref().filter(|popup| popup.path == path)
+    }
+
+    pub fn dismiss_blame_popup(&mut self, cx: &mut Context<Self>) {
+        if self.blame_popup.take().is_some() {
+            self.blame_popup_task = None;
+            cx.notify();
+        }
+    }
+
+    /// Send a buffer's change to its language server, moving what is drawn
+    /// over the text along with the edit
+    fn sync_document_change(&mut self, index: usize, cx: &mut App) {
+        let Some(file) = self.open_files.get_mut(index) else {
+            return;
+        };
+        file.document_version.set(file.version);
+        let content = file.input_state.read(cx).value().clone();
+        let incremental = file.language_server.as_ref().is_some_and(|server| server.syncs_incrementally());
+        let Some(tracked) = track_edit(&mut file.synced, file.lazy_highlighter.as_mut(), &content, incremental) else {
+            return;
+        };
+        let TrackedEdit {
+            edit,
+            removed,
+            lines: edited_lines,
+            pushes_start_line,
+            change,
+        } = tracked;
+        let (start, old_end, new_end) = edit;
+        // A completion accepted along with an import: the import goes in once the
+        // edit is through, in the same undo step. The server placed it in the text before.
+        let mut joins_next = false;
+        if new_end > start + 1 {
+            let mut extras = file.completion_extras.lock();
+            let accepted = extras
+                .additional_edits
+                .iter()
+                .find(|(plain, _)| content[..new_end].ends_with(plain.as_str()) && new_end - plain.len() <= start);
+            if let Some((_, edits)) = accepted {
+                let old_text = format!("{}{}{}", &content[..start], removed, &content[new_end..]);
+                let edits = super::lsp::accepted_additional_edits(&old_text, edits, edit);
+                if !edits.is_empty() {
+                    joins_next = true;
+                    self.pending_additional_edits.push((file.input_state.clone(), edits));
+                }
+                extras.additional_edits.clear();
+            }
+        }
+        file.undo_history.record_edit(
+            &content,
+            start,
+            removed,
+            content[start..new_end].to_string(),
+            file.cursor_offset,
+            Instant::now(),
+        );
+        if joins_next {
+            file.undo_history.join_next();
+        }
+
+        // Keep diagnostics next to the code they describe until the server republishes;
+        // ones whose range was edited are dropped rather than shown in the wrong place
+        if !file.diagnostics.is_empty() {
+            file.diagnostics.retain_mut(|diagnostic| {
+                match super::lsp::remap_range(diagnostic.range.clone(), edit) {
+                    Some(range) => {
+                        diagnostic.range = range;
+                        true
+                    }
+                    None => false,
+                }
+            });
+            show_diagnostics(&file.input_state, &content, &file.diagnostics, cx);
+        }
+
+        if !file.bookmarks.is_empty() {
+            file.bookmarks
+                .remap(edited_lines.0, edited_lines.1, edited_lines.2, pushes_start_line);
+        }
+
+        // Tab stops follow the typing; their mirrors catch up on the next render
+        if file.pending_snippet_text.as_deref() == Some(&*content) {
+            file.pending_snippet_text = None;
+        } else if let Some(session) = &mut file.snippet {
+            if session.edit(start, old_end, new_end) {
+                let edits = session.mirror_edits(&content);
+                if !edits.is_empty() {
+                    let mut mirrored = content.to_string();
+                    for (range, new_text) in edits.iter().rev() {
+                        mirrored.replace_range(range.clone(), new_text);
+                    }
+                    file.pending_snippet_text = Some(mirrored);
+                    self.pending_snippet_mirrors.push((file.input_state.clone(), edits));
+                }
             } else {
-                ""
+                file.snippet = None;
+            }
+        } else if new_end > start + 1 {
+            // A snippet accepted from the completion menu: fill in its stops
+            let mut completions = file.completion_extras.lock();
+            let accepted = completions
+                .snippets
+                .iter()
+                .find(|(plain, _)| content[..new_end].ends_with(plain.as_str()) && new_end - plain.len() <= start);
+            if let Some((plain, snippet)) = accepted {
+                let session = SnippetSession::new(snippet, new_end - plain.len());
+                self.pending_snippet_selections.push((file.input_state.clone(), session.current()));
+                file.snippet = Some(session);
+                completions.snippets.clear();
+            }
+        }
+
+        // Folds outside the edited lines stay folded until the ranges are recomputed
+        if !file.folds.foldable().is_empty() {
+            file.folds.remap(edited_lines.0, edited_lines.1, edited_lines.2);
+            show_folds(&file.input_state, &file.folds, cx);
+        }
+
+        // Hints outside the edit stay beside their code until new ones arrive
+        {
+            let mut hints = file.inlay_hints.lock();
+            if !hints.is_empty() {
+                hints.retain_mut(|hint| match super::lsp::remap_range(hint.offset..hint.offset, edit) {
+                    Some(range) => {
+                        hint.offset = range.start;
+                        true
+                    }
+                    None => false,
+                });
+                show_inlay_hints(&file.input_state, &hints, cx);
+            }
+        }
+
+        // Likewise token colors, so typing doesn't flash back to the lexer's
+        let highlights = &mut file.semantic_tokens.highlights;
+        if !highlights.is_empty() {
+            highlights.retain_mut(|(range, _)| match super::lsp::remap_range(range.clone(), edit) {
+                Some(remapped) if !remapped.is_empty() => {
+                    *range = remapped;
+                    true
+                }
+                _ => false,
+            });
+            show_semantic_highlights(&file.input_state, highlights, cx);
+        }
+
+        if let Some(server) = &file.language_server {
+            let changes = match change {
+                Some(change) => vec![change],
+                None => super::lsp::full_change(&content),
+            };
+            self.language_servers
+                .borrow_mut()
+                .did_change(server, &file.path, self.editor_id, changes, &content);
+        } else if JsonDialect::for_path(&file.path).is_some() || is_yaml(&file.path) || is_toml(&file.path) {
+            file.diagnostics = local_diagnostics(&file.path, &content, false);
+            show_diagnostics(&file.input_state, &content, &file.diagnostics, cx);
+        }
+    }
+
+    /// Start forwarding a server's `publishDiagnostics` to the open files, and
+    /// its `workspace/applyEdit` requests to the next render, once per server
+    fn listen_for_diagnostics(&mut self, server: &Arc<LanguageServer>, cx: &mut Context<Self>) {
+        if self
+            .diagnostic_listeners
+            .iter()
+            .any(|(listening, _)| Arc::ptr_eq(listening, server))
+        {
+            return;
+        }
+
+        let mut notifications = server.subscribe();
+        let source = server.clone();
+        let key = (server.language_id().to_string(), server.root().to_path_buf());
+        let task = cx.spawn(async move |this, cx| {
+            use futures::StreamExt as _;
+            use lsp_types::notification::{
+                Initialized, LogMessage, Notification as _, Progress, PublishDiagnostics, ShowMessage,
+            };
+            use lsp_types::request::{ApplyWorkspaceEdit, Request as _, ShowMessageRequest};
+
+            while let Some(notification) = notifications.next().await {
+                if notification.method == LogMessage::METHOD {
+                    if let Ok(params) = serde_json::from_value::<lsp_types::LogMessageParams>(notification.params) {
+                        super::lsp::log_message(source.name(), &params);
+                    }
+                    continue;
+                }
+                if notification.method == ShowMessage::METHOD {
+                    let Ok(params) = serde_json::from_value::<lsp_types::ShowMessageParams>(notification.params) else {
+                        continue;
+                    };
+                    let name = source.name().to_string();
+                    if this.update(cx, |this, cx| this.show_server_message(&name, params, cx)).is_err() {
+                        break;
+                    }
+                    continue;
+                }
+                if notification.method == ShowMessageRequest::METHOD {
+                    let (Some(id), Ok(params)) = (
+                        notification.id,
+                        serde_json::from_value::<lsp_types::ShowMessageRequestParams>(notification.params),
+                    ) else {
+                        continue;
+                    };
+                    let request = MessageRequest {
+                        server: source.clone(),
+                        id,
+                        params,
+                    };
+                    let queued = this.update(cx, |this, cx| {
+                        this.server_messages.push_request(request);
+                        cx.notify();
+                    });
+                    if queued.is_err() {
+                        break;
+                    }
+                    continue;
+                }
+                if notification.method == Initialized::METHOD {
+                    // The status bar stops showing it as starting
+                    if this.update(cx, |_, cx| cx.notify()).is_err() {
+                        break;
+                    }
+                    continue;
+                }
+                if notification.method == Progress::METHOD {
+                    let Ok(params) = serde_json::from_value::<lsp_types::ProgressParams>(notification.params) else {
+                        continue;
+                    };
+                    let updated = this.update(cx, |this, cx| {
+                        this.server_progress.entry(key.clone()).or_default().apply(params);
+                        cx.notify();
+                    });
+                    if updated.is_err() {
+                        break;
+                    }
+                    continue;
+                }
+                if notification.method == ApplyWorkspaceEdit::METHOD {
+                    let Some(id) = notification.id else {
+                        continue;
+                    };
+                    let params = match serde_json::from_value::<lsp_types::ApplyWorkspaceEditParams>(notification.params) {
+                        Ok(params) => params,
+                        Err(e) => {
+                            let failure = super::lsp::EditFailure {
+                                reason: format!("Malformed edit: {}", e),
+                                failed_change: None,
+                            };
+                            let response = super::lsp::apply_edit_response::<()>(&Err(failure));
+                            source.respond(id, serde_json::to_value(response).unwrap_or_default());
+                            continue;
+                        }
+                    };
+                    let server = source.clone();
+                    let queued = this.update(cx, |this, cx| {
+                        this.pending_workspace_edits.push((server, id.clone(), params.edit));
+                        cx.notify();
+                    });
+                    if queued.is_err() {
+                        let failure = super::lsp::EditFailure {
+                            reason: "The editor was closed".into(),
+                            failed_change: None,
+                        };
+                        let response = super::lsp::apply_edit_response::<()>(&Err(failure));
+                        source.respond(id, serde_json::to_value(response).unwrap_or_default());
+                        break;
+                    }
+                    continue;
+                }
+                if notification.method != PublishDiagnostics::METHOD {
+                    continue;
+                }
+                let Ok(params) =
+                    serde_json::from_value::<lsp_types::PublishDiagnosticsParams>(notification.params)
+                else {
+                    continue;
+                };
+                let Some(path) = super::lsp::uri_to_path(&params.uri) else {
+                    continue;
+                };
+                let updated = this.update(cx, |this, cx| {
+                    this.apply_published_diagnostics(&path, params.version, params.diagnostics, cx);
+                });
+                if updated.is_err() {
+                    break;
+                }
+            }
+            // The server's notifications end with its process
+            this.update(cx, |this, cx| this.server_exited(&source, cx)).ok();
+        });
+        self.diagnostic_listeners.push((server.clone(), task));
+    }
+
+    /// A server's process has ended: unless the editor stopped it, report the
+    /// crash and schedule a restart, waiting longer each time it crashes again
+    fn server_exited(&mut self, server: &Arc<LanguageServer>, cx: &mut Context<Self>) {
+        self.diagnostic_listeners.retain(|(listening, _)| !Arc::ptr_eq(listening, server));
+        // Nothing a dead process began is still in progress
+        self.server_progress
+            .remove(&(server.language_id().to_string(), server.root().to_path_buf()));
+        self.server_messages.server_exited(server);
+        cx.notify();
+        if server.was_stopped() {
+            // Restarted from another editor: follow it to the new process
+            let replacement = self
+                .language_servers
+                .borrow()
+                .servers()
+                .find(|running| {
+                    running.language_id() == server.language_id() && running.root() == server.root()
+                })
+                .cloned();
+            if let Some(replacement) = replacement.filter(|replacement| !Arc::ptr_eq(replacement, server)) {
+                self.adopt_language_server(replacement, cx);
+            }
+            return;
+        }
+        let status = server
+            .exit_status()
+            .map_or_else(|| "still shutting down".to_string(), |status| status.to_string());
+        tracing::error!(
+            "⚠️  {} for {} exited unexpectedly ({}) after {:.0?}",
+            server.name(),
+            server.language_id(),
+            status,
+            server.uptime()
+        );
+        let Some(delay) = self.language_servers.borrow_mut().record_crash(server) else {
+            self.set_status_message(
+                format!("{} keeps crashing; run Restart Language Server to try again", server.name()),
+                cx,
+            );
+            return;
+        };
+        self.set_status_message(format!("{} crashed; restarting in {}s", server.name(), delay.as_secs()), cx);
+
+        let key = (server.language_id().to_string(), server.root().to_path_buf());
+        let task = cx.spawn({
+            let (language, root) = key.clone();
+            async move |this, cx| {
+                cx.background_executor().timer(delay).await;
+                this.update(cx, |this, cx| {
+                    this.server_restart_tasks.remove(&(language.clone(), root.clone()));
+                    // Opening a file may have started it again meanwhile, which is just as good
+                    let server = this.language_servers.borrow_mut().get_or_start(&language, &root);
+                    if let Some(server) = server {
+                        this.adopt_language_server(server, cx);
+                    }
+                })
+                .ok();
+            }
+        });
+        self.server_restart_tasks.insert(key, task);
+    }
+
+    /// Restart the current file's language server, also once it has crashed
+    /// too often to be restarted by itself
+    pub fn restart_language_server(&mut self, cx: &mut Context<Self>) {
+        let Some(server) = self
+            .current_file_index
+            .and_then(|index| self.open_files.get(index))
+            .and_then(|file| file.language_server.clone())
+        else {
+            self.set_status_message("No language server for this file", cx);
+            return;
+        };
+        let key = (server.language_id().to_string(), server.root().to_path_buf());
+        self.server_restart_tasks.remove(&key);
+        let restarted = self.language_servers.borrow_mut().restart(&key.0, &key.1);
+        match restarted {
+            Some(restarted) => {
+                self.set_status_message(format!("Restarted {}", restarted.name()), cx);
+                self.adopt_language_server(restarted, cx);
+            }
+            None => self.set_status_message(format!("Failed to restart {}", server.name()), cx),
+        }
+    }
+
+    /// Hand the open files of a restarted server's language and root over to
+    /// it: each is opened with it again and gets completion and hover from it.
+    /// Its `initialize` handshake was sent as it started.
+    fn adopt_language_server(&mut self, server: Arc<LanguageServer>, cx: &mut Context<Self>) {
+        self.listen_for_diagnostics(&server, cx);
+        if server.language_id() == "python" {
+            self.configure_python_server(&server);
+        }
+        let provides_completions = !(server.language_id() == "rust" && self.rust_analyzer.is_some());
+        let mut configured = false;
+        for file in &mut self.open_files {
+            let Some(previous) = &file.language_server else {
+                continue;
+            };
+            if Arc::ptr_eq(previous, &server)
+                || previous.language_id() != server.language_id()
+                || previous.root() != server.root()
+            {
+                continue;
+            }
+            if !configured && JsonDialect::for_path(&file.path).is_some() {
+                server.notify_raw("workspace/didChangeConfiguration", serde_json::json!({
+                    "settings": json_language_settings(&self.json_schemas),
+                }));
+                configured = true;
+            }
+            // The old server may have taken this file's project on as another folder
+            let folder = workspace_root(&file.path);
+            let mut servers = self.language_servers.borrow_mut();
+            if folder != server.root() {
+                servers.add_folder(&server, &folder);
+            }
+            servers.open_document(&server, &file.path, &file.synced.to_string(), self.editor_id);
+            drop(servers);
+            file.language_server = Some(server.clone());
+            // Result ids and requests belong to the old process
+            file.semantic_tokens = SemanticTokenState::default();
+            file.semantic_tokens_version = None;
+            file.inlay_hint_request = None;
+            if provides_completions {
+                let (path, version, hints, snippets) = (
+                    file.path.clone(),
+                    file.document_version.clone(),
+                    file.inlay_hints.clone(),
+                    file.completion_extras.clone(),
+                );
+                let server = server.clone();
+                file.input_state.update(cx, |state, cx| {
+                    super::setup_lsp_providers(state, server, path, version, hints, snippets, cx);
+                });
+            }
+        }
+        cx.notify();
+    }
+
+    /// Point a Python server at its root's interpreter, the first one found
+    /// there until another is picked
+    fn configure_python_server(&mut self, server: &Arc<LanguageServer>) {
+        let root = server.root().to_path_buf();
+        if !self.python_interpreters.contains_key(&root) {
+            let Some(interpreter) = find_interpreters(&root).into_iter().next() else {
+                return;
+            };
+            self.python_interpreters.insert(root.clone(), interpreter);
+        }
+        let interpreter = &self.python_interpreters[&root];
+        tracing::debug!("🐍 {} uses {}", server.name(), interpreter.path.display());
+        server.set_configuration(python_language_settings(&interpreter.path));
+    }
+
+    /// List the interpreters found for the current Python file's project to pick from
+    pub fn select_python_interpreter(&mut self, cx: &mut Context<Self>) {
+        let Some(root) = self
+            .current_file_path()
+            .filter(|path| self.get_language_from_extension(path) == "python")
+            .and_then(|path| self.resolve_workspace_root_for_file(&path))
+        else {
+            self.set_status_message("Open a Python file to select its interpreter", cx);
+            return;
+        };
+        let interpreters = find_interpreters(&root);
+        if interpreters.is_empty() {
+            self.set_status_message("No Python interpreters found", cx);
+            return;
+        }
+        self.interpreter_picker = Some((root, interpreters));
+        cx.notify();
+    }
+
+    /// Use `interpreter` for the Python files under `root`, telling their
+    /// running server about it
+    fn pick_python_interpreter(&mut self, root: PathBuf, interpreter: PythonInterpreter, cx: &mut Context<Self>) {
+        self.interpreter_picker = None;
+        let settings = python_language_settings(&interpreter.path);
+        self.set_status_message(format!("Using {}", interpreter.path.display()), cx);
+        self.python_interpreters.insert(root.clone(), interpreter);
+
+        let mut configured: Vec<Arc<LanguageServer>> = Vec::new();
+        for server in self.open_files.iter().filter_map(|file| file.language_server.as_ref()) {
+            if server.language_id() == "python"
+                && server.root() == root
+                && !configured.iter().any(|done| Arc::ptr_eq(done, server))
+            {
+                server.set_configuration(settings.clone());
+                configured.push(server.clone());
+            }
+        }
+        cx.notify();
+    }
+
+    /// The interpreter the current Python file's server was told to use
+    fn current_python_interpreter(&self) -> Option<&PythonInterpreter> {
+        let file = self.current_file_index.and_then(|index| self.open_files.get(index))?;
+        let server = file.language_server.as_ref().filter(|server| server.language_id() == "python")?;
+        self.python_interpreters.get(server.root())
+    }
+
+    fn render_interpreter_picker(&self, cx: &mut Context<Self>) -> Option<impl IntoElement> {
+        let (root, interpreters) = self.interpreter_picker.as_ref()?;
+        let selected = self.python_interpreters.get(root).map(|interpreter| &interpreter.path);
+
+        Some(
+            div().absolute().top_10().left_0().right_0().flex().justify_center().child(
+                v_flex()
+                    .w(px(480.))
+                    .max_h(px(320.))
+                    .overflow_y_scroll()
+                    .p_1()
+                    .gap_0p5()
+                    .bg(cx.theme().popover)
+                    .border_1()
+                    .border_color(cx.theme().border)
+                    .rounded(cx.theme().radius)
+                    .shadow_lg()
+                    .child(
+                        h_flex()
+                            .px_2()
+                            .py_1()
+                            .justify_between()
+                            .child(
+                                div()
+                                    .text_xs()
+                                    .font_semibold()
+                                    .text_color(cx.theme().muted_foreground)
+                                    .child("Select Python interpreter"),
+                            )
+                            .child(
+                                Button::new("close_interpreter_picker")
+                                    .icon(IconName::Close)
+                                    .ghost()
+                                    .xsmall()
+                                    .on_click(cx.listener(|this, _, _window, cx| {
+                                        this.interpreter_picker = None;
+                                        cx.notify();
+                                    })),
+                            ),
+                    )
+                    .children(interpreters.iter().enumerate().map(|(ix, interpreter)| {
+                        let current = if selected == Some(&interpreter.path) { "✓ " } else { "" };
+                        let (root, interpreter) = (root.clone(), interpreter.clone());
+                        Button::new(("python_interpreter", ix))
+                            .label(format!("{}{}  {}", current, interpreter.label(), interpreter.path.display()))
+                            .ghost()
+                            .small()
+                            .w_full()
+                            .on_click(cx.listener(move |this, _, _window, cx| {
+                                this.pick_python_interpreter(root.clone(), interpreter.clone(), cx);
+                            }))
+                    })),
+            ),
+        )
+    }
+
+    /// Toast a server's `window/showMessage` until its time is up
+    fn show_server_message(&mut self, server: &str, params: lsp_types::ShowMessageParams, cx: &mut Context<Self>) {
+        let (id, count) = self.server_messages.show(server, params);
+        let duration = self
+            .server_messages
+            .toasts()
+            .iter()
+            .find(|toast| toast.id == id)
+            .map_or(Duration::ZERO, |toast| toast.duration());
+        cx.spawn(async move |this, cx| {
+            cx.background_executor().timer(duration).await;
+            this.update(cx, |this, cx| {
+                if this.server_messages.expire(id, count) {
+                    cx.notify();
+                }
+            })
+            .ok();
+        })
+        .detach();
+        cx.notify();
+    }
+
+    /// Send the choice made in a server's prompt, `None` if it was dismissed
+    fn answer_server_message(&mut self, action: Option<usize>, cx: &mut Context<Self>) {
+        self.server_messages.answer(action);
+        cx.notify();
+    }
+
+    /// The current file's language server as the status bar shows it: its
+    /// name and state, or what it's busy with
+    fn language_server_status(&self) -> Option<String> {
+        let file = self.open_files.get(self.current_file_index?)?;
+        let server = file.language_server.as_ref()?;
+        let key = (server.language_id().to_string(), server.root().to_path_buf());
+        let name = server.name();
+        Some(match self.language_servers.borrow().status(&key.0, &key.1) {
+            ServerStatus::Starting => format!("{} starting…", name),
+            ServerStatus::Ready => match self.server_progress.get(&key).and_then(ServerProgress::label) {
+                Some(progress) => format!("{}: {}", name, progress),
+                None => name.to_string(),
+            },
+            ServerStatus::Restarting => format!("⟳ {} crashed, restarting", name),
+            ServerStatus::Stopped => format!("⊘ {} stopped", name),
+        })
+    }
+
+    fn apply_published_diagnostics(
+        &mut self,
+        path: &PathBuf,
+        version: Option<i32>,
+        diagnostics: Vec<lsp_types::Diagnostic>,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(file) = self.open_files.iter_mut().find(|file| &file.path == path) else {
+            return;
+        };
+        // Results for an older version would land on the wrong text; a newer publish follows
+        let sent_version = file
+            .language_server
+            .as_ref()
+            .and_then(|server| self.language_servers.borrow().document_version(server, path));
+        if version.is_some_and(|version| Some(version) != sent_version) {
+            return;
+        }
+
+        let text = file.synced.to_string();
+        file.diagnostics = diagnostics
+            .into_iter()
+            .map(|diagnostic| FileDiagnostic::from_lsp(&text, diagnostic))
+            .collect();
+        file.diagnostics.extend(local_diagnostics(path, &text, true));
+        show_diagnostics(&file.input_state, &text, &file.diagnostics, cx);
+        cx.notify();
+    }
+
+    /// Worst diagnostic severity per line of an open file, and its line count
+    pub fn line_severities(
+        &self,
+        path: &PathBuf,
+    ) -> Option<(std::collections::BTreeMap<u32, lsp_types::DiagnosticSeverity>, usize)> {
+        let file = self.open_files.iter().find(|file| &file.path == path)?;
+        if file.diagnostics.is_empty() {
+            return None;
+        }
+        // Like `str::lines`, which doesn't count an empty last line
+        let ends_with_newline = !file.synced.is_empty() && file.synced.byte(file.synced.len() - 1) == b'\n';
+        let lines_count = (file.synced.line_count() - ends_with_newline as usize).max(1);
+        Some((
+            super::lsp::line_severities(|offset| file.synced.line_of(offset), &file.diagnostics),
+            lines_count,
+        ))
+    }
+
+    /// Error and warning counts across the open files
+    fn diagnostic_counts(&self) -> (usize, usize) {
+        let count = |severity| {
+            self.open_files
+                .iter()
+                .flat_map(|file| &file.diagnostics)
+                .filter(|diagnostic| diagnostic.severity == severity)
+                .count()
+        };
+        (count(lsp_types::DiagnosticSeverity::ERROR), count(lsp_types::DiagnosticSeverity::WARNING))
+    }
+
+    /// Diagnostics of every open file, for the problems panel
+    pub fn problems(&self) -> Vec<Problem> {
+        self.open_files
+            .iter()
+            .flat_map(|file| {
+                file.diagnostics.iter().map(|diagnostic| {
+                    let position = file.synced.offset_to_position(diagnostic.range.start.min(file.synced.len()));
+                    Problem {
+                        path: file.path.clone(),
+                        line: position.line,
+                        character: position.character,
+                        severity: diagnostic.severity,
+                        message: diagnostic.message.clone(),
+                        source: diagnostic.source.clone(),
+                        code: diagnostic.code.clone(),
+                    }
+                })
+            })
+            .collect()
+    }
+
+    /// Add a bookmark on the cursor's line of the current file, or remove the one there
+    pub fn toggle_bookmark(&mut self, cx: &mut Context<Self>) {
+        let Some(file) = self.current_file_index.and_then(|index| self.open_files.get_mut(index)) else {
+            return;
+        };
+        let line = file.synced.line_of(file.input_state.read(cx).cursor());
+        file.bookmarks.toggle(line);
+        cx.notify();
+    }
+
+    /// Move to the next (or previous) bookmark, through the open files in tab order
+    pub fn step_bookmark(&mut self, forward: bool, cx: &mut Context<Self>) {
+        let files: Vec<(PathBuf, Vec<u32>)> = self
+            .open_files
+            .iter()
+            .map(|file| (file.path.clone(), file.bookmarks.lines().iter().copied().collect()))
+            .collect();
+        let (path, line) = match self.current_file_index.and_then(|index| self.open_files.get(index)) {
+            Some(file) => (file.path.clone(), file.synced.line_of(file.input_state.read(cx).cursor())),
+            None => (PathBuf::new(), 0),
+        };
+        match step_bookmark(&files, &path, line, forward) {
+            Some((path, line)) => self.navigate_to(path.to_path_buf(), line, 0, cx),
+            None => self.set_status_message("No bookmarks", cx),
+        }
+    }
+
+    pub fn remove_bookmark(&mut self, path: &Path, line: u32, cx: &mut Context<Self>) {
+        if let Some(file) = self.open_files.iter_mut().find(|file| file.path == path) {
+            file.bookmarks.remove(line);
+            cx.notify();
+        }
+    }
+
+    pub fn clear_bookmarks(&mut self, cx: &mut Context<Self>) {
+        for file in &mut self.open_files {
+            file.bookmarks.clear();
+        }
+        cx.notify();
+    }
+
+    /// Bookmarked lines of `path`, for its gutter
+    pub fn bookmark_lines(&self, path: &Path) -> Option<&std::collections::BTreeSet<u32>> {
+        let file = self.open_files.iter().find(|file| file.path == path)?;
+        (!file.bookmarks.is_empty()).then(|| file.bookmarks.lines())
+    }
+
+    /// Bookmarks of every open file, with their lines' text
+    pub fn bookmarks(&self) -> Vec<Bookmark> {
+        self.open_files
+            .iter()
+            .flat_map(|file| {
+                file.bookmarks.lines().iter().map(|&line| Bookmark {
+                    path: file.path.clone(),
+                    line,
+                    text: file.synced.slice(file.synced.line_range(line)).trim().to_string(),
+                })
+            })
+            .collect()
+    }
+
+    /// Emit `DirtyChanged` if the overall dirty state differs from the last reported one
+    fn notify_dirty_state(&mut self, cx: &mut Context<Self>) {
+        let dirty = self.is_dirty();
+        if dirty != self.last_reported_dirty {
+            self.last_reported_dirty = dirty;
+            cx.emit(TextEditorEvent::DirtyChanged(dirty));
+        }
+    }
+
+    /// Manually refresh the markdown preview for the current file
+    pub fn refresh_markdown_preview(&mut self, cx: &mut Context<Self>) {
+        if let Some(index) = self.current_file_index {
+            if let Some(file) = self.open_files.get_mut(index) {
+                if file.render_as_markdown {
+                    let content = file.input_state.read(cx).value().to_string();
+                    file.markdown_blocks = Rc::new(markdown_preview::parse(&content));
+                    file.markdown_preview_cache = content;
+                    file.last_markdown_render = Some(Instant::now());
+                    self.markdown_preview_tasks.remove(&file.path);
+                    cx.notify();
+                }
+            }
+        }
+    }
+
+    /// Re-render a Markdown file's preview once typing pauses, parsing off
+    /// the UI thread
+    fn schedule_markdown_preview(&mut self, index: usize, cx: &mut Context<Self>) {
+        let Some(file) = self.open_files.get(index).filter(|file| file.render_as_markdown) else {
+            return;
+        };
+        let path = file.path.clone();
+        let version = file.version;
+        let content = file.input_state.read(cx).value();
+        let task = cx.spawn({
+            let path = path.clone();
+            async move |this, cx| {
+                cx.background_executor().timer(MARKDOWN_PREVIEW_DELAY).await;
+                let blocks = cx
+                    .background_spawn({
+                        let content = content.clone();
+                        async move { markdown_preview::parse(&content) }
+                    })
+                    .await;
+                this.update(cx, |this, cx| {
+                    this.markdown_preview_tasks.remove(&path);
+                    let Some(file) = this.open_files.iter_mut().find(|file| file.path == path) else {
+                        return;
+                    };
+                    if file.version != version {
+                        return;
+                    }
+                    file.markdown_blocks = Rc::new(blocks);
+                    file.markdown_preview_cache = content.to_string();
+                    file.last_markdown_render = Some(Instant::now());
+                    cx.notify();
+                })
+                .ok();
+            }
+        });
+        self.markdown_preview_tasks.insert(path, task);
+    }
+
+    /// Blocks to preview beside a Markdown file
+    pub fn markdown_preview(&self, path: &Path) -> Option<Rc<Vec<PreviewBlock>>> {
+        self.open_files
+            .iter()
+            .find(|file| file.path == path && file.render_as_markdown)
+            .map(|file| file.markdown_blocks.clone())
+    }
+
+    /// Set the global rust analyzer manager.
+    ///
+    /// Also retroactively wires LSP providers for files already open so that
+    /// files opened before the host analyzer was injected still get full
+    /// completion / hover / go-to-definition support.
+    pub fn set_rust_analyzer(
+        &mut self,
+        analyzer: Entity<RustAnalyzerManager>,
+        cx: &mut Context<Self>,
+    ) {
+        tracing::debug!(
+            "[LSP] TextEditor::set_rust_analyzer called, open_files={}",
+            self.open_files.len()
+        );
+        self.rust_analyzer = Some(analyzer.clone());
+
+        if self.open_files.is_empty() {
+            tracing::debug!("[LSP] No open files to back-fill LSP providers for.");
+            return;
+        }
+
+        // Back-fill LSP providers for files that were already open before the
+        // analyzer arrived (e.g., files opened in ScriptEditor::new before the
+        // host calls set_rust_analyzer).
+        let files: Vec<(PathBuf, Entity<InputState>)> = self
+            .open_files
+            .iter()
+            .map(|f| (f.path.clone(), f.input_state.clone()))
+            .collect();
+
+        for (path, input_state) in files {
+            let ext = path
+                .extension()
+                .and_then(|e| e.to_str())
+                .unwrap_or("")
+                .to_owned();
+            if ext != "rs" {
+                continue; // Only Rust files use rust-analyzer
+            }
+            tracing::debug!(
+                "[LSP] Back-filling providers for already-open file {:?}",
+                path.file_name()
+            );
+            let a2 = analyzer.clone();
+            let ws = self
+                .resolve_workspace_root_for_file(&path)
+                .unwrap_or_else(|| path.parent().map(|p| p.to_path_buf()).unwrap_or_default());
+            let p2 = path.clone();
+            input_state.update(cx, |state, _cx| {
+                let provider = std::rc::Rc::new(
+                    engine_backend::services::lsp_completion_provider::GlobalRustAnalyzerCompletionProvider::new(
+                        a2, p2, ws,
+                    ),
+                );
+                state.lsp.completion_provider = Some(provider.clone());
+                state.lsp.definition_provider = Some(provider.clone());
+                state.lsp.hover_provider = Some(provider);
+                tracing::debug!("[LSP] Back-fill done for {:?}", path.file_name());
+            });
+        }
+    }
+
+    /// Create a new empty file
+    pub fn create_new_file(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        // Generate a unique untitled file name
+        let mut counter = 1;
+        let mut new_path = PathBuf::from(format!("untitled-{}.txt", counter));
+        while self.open_files.iter().any(|f| f.path == new_path) {
+            counter += 1;
+            new_path = PathBuf::from(format!("untitled-{}.txt", counter));
+        }
+
+        // Create an empty file in memory
+        let language = "text";
+        let indent_style = self.indent_style_for(language, "", &EditorConfig::default());
+        let input_state = cx.new(|cx| {
+            let mut state = InputState::new(window, cx)
+                .code_editor(language)
+                .line_number(true)
+                .tab_size(TabSize {
+                    tab_size: indent_style.width as usize,
+                    hard_tabs: indent_style.hard_tabs,
+                })
+                .soft_wrap(!self.wrap_mode.is_off());
+
+            state.set_value("", window, cx);
+            state
+        });
+
+        let open_file = OpenFile {
+            path: new_path.clone(),
+            input_state: input_state.clone(),
+            is_modified: false,
+            saved_content_hash: content_hash(""),
+            lines_count: 1,
+            file_size: 0,
+            version: 1,
+            render_as_markdown: false,
+            markdown_preview_cache: String::new(),
+            last_markdown_render: None,
+            markdown_blocks: Rc::default(),
+            pending_scroll_target: None,
+            pending_scroll_top: None,
+            language_server: None,
+            synced: TextBuffer::default(),
+            diagnostics: Vec::new(),
+            multi_cursor: MultiCursor::default(),
+            cursor_offset: 0,
+            multi_cursor_text: String::new(),
+            pending_cursor_text: None,
+            placing_cursor: false,
+            folds: FoldState::default(),
+            cursor_line: 0,
+            indent_style,
+            editorconfig: EditorConfig::default(),
+            bracket_pairs: None,
+            bracket_highlights: Default::default(),
+            occurrences: Occurrences::default(),
+            occurrences_at: None,
+            current_lines: None,
+            box_selection: None,
+            selection_history: SelectionHistory::default(),
+            undo_history: UndoHistory::default(),
+            document_version: DocumentVersion::default(),
+            inlay_hints: SharedInlayHints::default(),
+            inlay_hint_request: None,
+            semantic_tokens: SemanticTokenState::default(),
+            semantic_tokens_version: None,
+            lazy_highlighter: None,
+            lazy_highlight_request: None,
+            symbols: Vec::new(),
+            breadcrumb_path: Vec::new(),
+            bookmarks: Bookmarks::default(),
+            snippet: None,
+            pending_snippet_text: None,
+            completion_extras: SharedCompletionExtras::default(),
+            disk_state: DiskState::InSync,
+            encoding: FileEncoding::default(),
+            read_only: false,
+            line_ending: LineEnding::Lf,
+            saved_line_ending: LineEnding::Lf,
+            mixed_line_endings: false,
+        };
+
+        self.open_files.push(open_file);
+        let new_index = self.open_files.len() - 1;
+        self.current_file_index = Some(new_index);
+
+        // Create subscription for this file
+        let analyzer = self.rust_analyzer.clone();
+        tracing::debug!(
+            "📝 Creating change subscription for new file, rust_analyzer present: {}",
+            analyzer.is_some()
+        );
+        let subscription = cx.subscribe(
+            &input_state,
+            move |this: &mut TextEditor,
+                  input_state_entity: Entity<InputState>,
+                  event: &InputEvent,
+                  cx: &mut Context<TextEditor>| {
+                if let InputEvent::Change = event {
+                    if let Some(index) = this
+                        .open_files
+                        .iter()
+                        .position(|f| f.input_state == input_state_entity)
+                    {
+                        if let Some(file) = this.open_files.get_mut(index) {
+                            file.is_modified = file.differs_from_saved(cx);
+                            file.version += 1;
+
+                            // Notify rust-analyzer of the change
+                            if let Some(ref analyzer) = analyzer {
+                                let path = file.path.clone();
+                                let version = file.version;
+                                let content = file.input_state.read(cx).value().to_string();
+
+                                tracing::debug!(
+                                    "📝 File changed: {:?} (version {}), notifying rust-analyzer",
+                                    path.file_name(),
+                                    version
+                                );
+                                analyzer.update(cx, |analyzer, _cx| {
+                                    if let Err(e) =
+                                        analyzer.did_change_file(&path, &content, version)
+                                    {
+                                        tracing::error!(
+                                            "⚠️  Failed to notify rust-analyzer of file change: {}",
+                                            e
+                                        );
+                                    } else {
+                                        if version % 10 == 0 {
+                                            // Log every 10th change to avoid spam
+                                            tracing::debug!(
+                                                "✓ Notified rust-analyzer of change (version {})",
+                                                version
+                                            );
+                                        }
+                                    }
+                                });
+                            } else {
+                                if file.version == 2 {
+                                    // Only log once to avoid spam
+                                    tracing::debug!("⚠️  No rust-analyzer available for didChange");
+                                }
+                            }
+
+                            cx.notify();
+                        }
+                        this.sync_document_change(index, cx);
+                        this.update_signature_help(index, true, cx);
+                        this.schedule_fold_refresh(index, cx);
+                        this.schedule_symbol_refresh(index, cx);
+                        this.schedule_markdown_preview(index, cx);
+                        this.refresh_whitespace(index, cx);
+                        this.notify_dirty_state(cx);
+                        cx.emit(TextEditorEvent::BufferChanged {
+                            path: this.open_files[index].path.clone(),
+                        });
+                    }
+                }
+            },
+        );
+
+        self.subscriptions.push(subscription);
+        self.watch_cursor_and_folds(&input_state, window, cx);
+
+        tracing::debug!("✓ Created new file: {:?}", new_path);
+        cx.notify();
+    }
+
+    /// Open a file picker dialog (platform-specific)
+    pub fn open_folder_dialog(&mut self, _window: &mut Window, cx: &mut Context<Self>) {
+        // For now, open the current working directory
+        // In a real implementation, this would show a platform file picker
+        if let Ok(cwd) = std::env::current_dir() {
+            tracing::debug!("✓ Opening folder: {:?}", cwd);
+            // Emit an event or call a method to open this folder in the file explorer
+            cx.emit(TextEditorEvent::OpenFolderRequested(cwd));
+        }
+        cx.notify();
+    }
+
+    /// Show search/find dialog
+    pub fn show_find_dialog(&mut self, _window: &mut Window, cx: &mut Context<Self>) {
+        if let Some(index) = self.current_file_index {
+            if let Some(file) = self.open_files.get(index) {
+                // Trigger search on the input state
+                file.input_state.update(cx, |state, cx| {
+                    // The InputState has a search panel that can be shown
+                    // Emit focus event to potentially show search
+                    tracing::debug!("✓ Opening search panel for current file");
+                    cx.emit(ui::input::InputEvent::Focus);
+                });
+            }
+        }
+        cx.notify();
+    }
+
+    /// Show find and replace dialog
+    pub fn show_replace_dialog(&mut self, _window: &mut Window, cx: &mut Context<Self>) {
+        if let Some(index) = self.current_file_index {
+            if let Some(file) = self.open_files.get(index) {
+                // Trigger search/replace on the input state
+                file.input_state.update(cx, |state, cx| {
+                    tracing::debug!("✓ Opening find/replace panel for current file");
+                    // The search panel supports replace functionality
+                    cx.emit(ui::input::InputEvent::Focus);
+                });
+            }
+        }
+        cx.notify();
+    }
+
+    /// Run the current script file
+    pub fn run_current_file(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(file) = self.current_file_index.and_then(|index| self.open_files.get(index)) else {
+            return;
+        };
+        let path = file.path.clone();
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+        let template = match self.run_commands.get(&extension) {
+            Some(command) => command.clone(),
+            None => match default_run_command(&extension, &file.input_state.read(cx).value()) {
+                Some(command) => command.to_string(),
+                None => {
+                    self.set_status_message(format!("Don't know how to run .{} files; set one in runCommands", extension), cx);
+                    return;
+                }
+            },
+        };
+        // The script runs from disk, so it has to be there as it's shown
+        if file.is_modified && !self.save_current_file(window, cx) {
+            return;
+        }
+
+        let python = self
+            .current_python_interpreter()
+            .map_or_else(|| "python".to_string(), |interpreter| interpreter.path.display().to_string());
+        let command = expand_run_command(&template, &path, &python);
+        tracing::debug!("🚀 Running {:?}: {}", path, command);
+        cx.emit(TextEditorEvent::RunScriptRequested(path, command));
+    }
+
+    /// The current file's whole text
+    pub fn current_text(&self, cx: &App) -> Option<String> {
+        let file = self.current_file_index.and_then(|index| self.open_files.get(index))?;
+        Some(file.input_state.read(cx).value().to_string())
+    }
+
+    /// Replace a byte range of the current file's text, keeping the cursor on
+    /// the same code. Like typing, the edit can be undone and marks the file
+    /// modified.
+    pub fn replace_range(
+        &mut self,
+        range: Range<usize>,
+        new_text: &str,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Result<(), String> {
+        let file = self
+            .current_file_index
+            .and_then(|index| self.open_files.get(index))
+            .ok_or("No file currently open")?;
+        if file.read_only {
+            return Err(format!("{} is open read-only", file.path.display()));
+        }
+        file.input_state.update(cx, |state, cx| {
+            let text = state.value().to_string();
+            if range.start > range.end
+                || range.end > text.len()
+                || !text.is_char_boundary(range.start)
+                || !text.is_char_boundary(range.end)
+            {
+                return Err(format!("{:?} is not a range of the text ({} bytes)", range, text.len()));
+            }
+            let mut replaced = text.clone();
+            replaced.replace_range(range, new_text);
+            replace_input_text(state, &text, &replaced, window, cx);
+            Ok(())
+        })
+    }
+
+    /// The current file's selected text, if any
+    pub fn selected_text(&self, window: &mut Window, cx: &mut Context<Self>) -> Option<String> {
+        let file = self.current_file_index.and_then(|index| self.open_files.get(index))?;
+        file.input_state.update(cx, |state, cx| {
+            let text = state.value().to_string();
+            let range = state.selected_text_range(true, window, cx)?.range;
+            (!range.is_empty())
+                .then(|| text[super::lsp::utf16_to_offset(&text, range.start)..super::lsp::utf16_to_offset(&text, range.end)].to_string())
+        })
+    }
+
+    /// The current file's selected text, or the cursor's line without a selection
+    pub fn selection_or_line(&self, window: &mut Window, cx: &mut Context<Self>) -> Option<String> {
+        let file = self.current_file_index.and_then(|index| self.open_files.get(index))?;
+        file.input_state.update(cx, |state, cx| {
+            let text = state.value().to_string();
+            let selection = state.selected_text_range(true, window, cx).map(|selection| selection.range);
+            let selected = selection
+                .filter(|range| !range.is_empty())
+                .map(|range| super::lsp::utf16_to_offset(&text, range.start)..super::lsp::utf16_to_offset(&text, range.end));
+            let range = selected.unwrap_or_else(|| {
+                let cursor = state.cursor().min(text.len());
+                let start = text[..cursor].rfind('\n').map_or(0, |ix| ix + 1);
+                let end = text[cursor..].find('\n').map_or(text.len(), |ix| cursor + ix);
+                start..end
+            });
+            let chosen = text[range].to_string();
+            (!chosen.trim().is_empty()).then_some(chosen)
+        })
+    }
+
+    /// Give the keyboard back to the current file
+    pub fn focus_current_file(&self, window: &mut Window, cx: &mut Context<Self>) {
+        if let Some(file) = self.current_file_index.and_then(|index| self.open_files.get(index)) {
+            file.input_state.update(cx, |state, cx| state.focus(window, cx));
+        }
+    }
+
+    /// Debug the current script file
+    pub fn debug_current_file(&mut self, _window: &mut Window, cx: &mut Context<Self>) {
+        if let Some(index) = self.current_file_index {
+            if let Some(file) = self.open_files.get(index) {
+                let path = file.path.clone();
+                tracing::debug!("🐛 Debugging file: {:?}", path);
+                cx.emit(TextEditorEvent::DebugScriptRequested(path));
+            }
+        }
+        cx.notify();
+    }
+
+    pub fn open_file(&mut self, path: PathBuf, window: &mut Window, cx: &mut Context<Self>) {
+        self.remember_current_position(cx);
+
+        // Check if file is already open
+        if let Some(index) = self.open_files.iter().position(|f| f.path == path) {
+            self.current_file_index = Some(index);
+            cx.notify();
+            return;
+        }
+
+        let filename = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        match large_files::is_binary_file(&path) {
+            Ok(false) => {}
+            Ok(true) => {
+                self.set_status_message(format!("{} is a binary file; view it in a hex editor instead", filename), cx);
+                return;
+            }
+            Err(err) => {
+                tracing::error!("✗ Failed to read file: {:?}, error: {}", path, err);
+                return;
+            }
+        }
+        let size = fs::metadata(&path).map(|metadata| metadata.len()).unwrap_or_default();
+        if !large_files::is_large(size, self.large_file_threshold_mb) {
+            self.load_file(path, false, window, cx);
+            return;
+        }
+
+        let answer = window.prompt(
+            PromptLevel::Warning,
+            &format!("{filename} is {} MB", size / (1024 * 1024)),
+            Some("Editing a file this large may make the editor hang. Read-only mode opens it without language servers, highlighting or folding."),
+            &["Open Read-Only", "Open Anyway", "Cancel"],
+            cx,
+        );
+        cx.spawn_in(window, async move |this, cx| {
+            let Ok(answer) = answer.await else {
+                return;
+            };
+            this.update_in(cx, |this, window, cx| match answer {
+                0 => this.load_file(path, true, window, cx),
+                1 => this.load_file(path, false, window, cx),
+                _ => {}
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    /// Read a file into a new tab; read-only tabs skip everything but showing the text
+    fn load_file(&mut self, path: PathBuf, read_only: bool, window: &mut Window, cx: &mut Context<Self>) {
+        let editorconfig = EditorConfig::for_file(&path);
+        let charset = editorconfig.charset.as_deref().and_then(FileEncoding::for_charset);
+        // Read file content with timing
+        let read_start = Instant::now();
+        let mut decoded = match fs::read(&path) {
+            Ok(bytes) => {
+                let read_time = read_start.elapsed();
+                tracing::debug!(
+                    "✓ Read file {:?} - {} bytes in {:.2}ms",
+                    path.file_name().unwrap_or_default(),
+                    bytes.len(),
+                    read_time.as_secs_f64() * 1000.0
+                );
+                match charset {
+                    Some(charset) => encoding::decode_preferring(&bytes, charset),
+                    None => encoding::decode(&bytes),
+                }
+            }
+            Err(err) => {
+                tracing::error!("✗ Failed to read file: {:?}, error: {}", path, err);
+                return;
+            }
+        };
+        if decoded.had_errors {
+            self.set_status_message(invalid_bytes_message(&path, decoded.encoding), cx);
+        }
+        let (detected_line_ending, mixed_line_endings) = LineEnding::detect(&decoded.text);
+        let content = line_endings::into_normalized(std::mem::take(&mut decoded.text));
+        let line_ending = match editorconfig.end_of_line {
+            Some(ending) if !read_only => ending,
+            _ => detected_line_ending,
+        };
+        // A file without line breaks has none to convert
+        let saved_line_ending = if content.contains('\n') { detected_line_ending } else { line_ending };
+        if line_ending != saved_line_ending {
+            self.set_status_message(
+                format!(".editorconfig asks for {} line endings; saving converts them", line_ending.label()),
+                cx,
+            );
+        } else if mixed_line_endings {
+            self.set_status_message(
+                format!("Mixed line endings; saving makes them all {}", line_ending.label()),
+                cx,
+            );
+        }
+
+        let file_size = content.len();
+        let lines_count = content.lines().count();
+
+        // Determine syntax highlighting based on file extension
+        let language = self.get_language_from_extension(&path);
+
+        // Check if this is a markdown file
+        let is_markdown = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext == "md")
+            .unwrap_or(false);
+
+        tracing::debug!(
+            "📄 Opening file: {} lines, {} KB, language: {}{}",
+            lines_count,
+            file_size / 1024,
+            language,
+            if is_markdown {
+                " (markdown preview mode)"
+            } else {
+                ""
+            }
+        );
+
+        // Warn user about very large files
+        if lines_count > LAZY_HIGHLIGHT_LINES {
+            tracing::debug!(
+                "⚠️  Large file detected ({} lines). Some features may be disabled for performance:",
+                lines_count
+            );
+            tracing::debug!("   - Highlighting by keyword, the lines in view first");
+            tracing::debug!("   - Soft wrap disabled");
+        } else if lines_count > 10_000 {
+            tracing::debug!(
+                "ℹ️  Large file ({} lines). Performance optimizations enabled:",
+                lines_count
+            );
+            tracing::debug!("   - Soft wrap disabled");
+        }
+
+        // Create editor state with optimal settings for large files
+        let setup_start = Instant::now();
+        let indent_style = self.indent_style_for(&language, &content, &editorconfig);
+        let input_state = cx.new(|cx| {
+            let mut state = InputState::new(window, cx)
+                // JSONC is highlighted as JSON, which takes comments in its stride;
+                // files too large to parse get the lazy highlighter instead
+                .code_editor(if read_only || lines_count > LAZY_HIGHLIGHT_LINES {
+                    "text".to_string()
+                } else if language == "jsonc" {
+                    "json".to_string()
+                } else {
+                    language.clone()
+                })
+                .line_number(true)
+                .tab_size(TabSize {
+                    tab_size: indent_style.width as usize,
+                    hard_tabs: indent_style.hard_tabs,
+                })
+                // Disable soft wrap for large files for better performance
+                // Files with more than 5k lines or 500KB get no wrapping
+                .soft_wrap(!read_only && !self.wrap_mode.is_off() && wraps_well(lines_count, file_size));
+
+            // Set the content after creating the state
+            state.set_value(&content, window, cx);
+            state
+        });
+
+        // Set up autocomplete for the file with rust-analyzer support
+        let workspace_root = self.resolve_workspace_root_for_file(&path);
+        if read_only {
+            tracing::debug!("[LSP] open_file: {:?} is read-only, no language features", path.file_name());
+        } else if let Some(analyzer) = self.rust_analyzer.clone() {
+            tracing::debug!("[LSP] open_file: rust_analyzer present, calling setup_autocomplete_for_file for {:?}",
+                path.file_name());
+            input_state.update(cx, |state, cx| {
+                super::setup_autocomplete_for_file(
+                    state,
+                    path.clone(),
+                    workspace_root,
+                    analyzer,
+                    window,
+                    cx,
+                );
+            });
+        } else {
+            tracing::debug!(
+                "[LSP] open_file: rust_analyzer is NONE – LSP features will NOT work for {:?}",
+                path.file_name()
+            );
+            tracing::debug!("⚠️  rust-analyzer not available, completions will be limited");
+        }
+
+        // Start (or reuse) a plugin-managed language server for this language
+        let document_version = DocumentVersion::default();
+        let inlay_hints = SharedInlayHints::default();
+        let completion_extras = SharedCompletionExtras::default();
+        let language_server = self
+            .resolve_workspace_root_for_file(&path)
+            .filter(|_| !read_only)
+            .and_then(|root| self.language_servers.borrow_mut().get_or_start(&language, &root));
+        let notices = self.language_servers.borrow_mut().take_notices();
+        for notice in notices {
+            self.set_status_message(notice, cx);
+        }
+        if let Some(server) = language_server.clone() {
+            if JsonDialect::for_path(&path).is_some() {
+                server.notify_raw("workspace/didChangeConfiguration", serde_json::json!({
+                    "settings": json_language_settings(&self.json_schemas),
+                }));
+            }
+            if language == "python" {
+                self.configure_python_server(&server);
+            }
+            self.language_servers
+                .borrow_mut()
+                .open_document(&server, &path, &content, self.editor_id);
+            self.listen_for_diagnostics(&server, cx);
+
+            // The host rust-analyzer keeps serving Rust completions and hover when it is injected
+            if !(language == "rust" && self.rust_analyzer.is_some()) {
+                input_state.update(cx, |state, cx| {
+                    super::setup_lsp_providers(
+                        state,
+                        server,
+                        path.clone(),
+                        document_version.clone(),
+                        inlay_hints.clone(),
+                        completion_extras.clone(),
+                        cx,
+                    );
+                });
+            }
+        }
+
+        let setup_time = setup_start.elapsed();
+        tracing::debug!(
+            "⚡ Editor setup completed in {:.2}ms",
+            setup_time.as_secs_f64() * 1000.0
+        );
+
+        let mut open_file = OpenFile {
+            path: path.clone(),
+            input_state: input_state.clone(),
+            is_modified: line_ending != saved_line_ending,
+            saved_content_hash: content_hash(&content),
+            lines_count,
+            file_size,
+            version: 1,
+            render_as_markdown: is_markdown,
+            markdown_preview_cache: if is_markdown {
+                content.clone()
+            } else {
+                String::new()
+            },
+            last_markdown_render: if is_markdown {
+                Some(Instant::now())
+            } else {
+                None
+            },
+            markdown_blocks: if is_markdown {
+                Rc::new(markdown_preview::parse(&content))
+            } else {
+                Rc::default()
+            },
+            pending_scroll_target: None,
+            pending_scroll_top: None,
+            language_server: language_server.clone(),
+            synced: TextBuffer::new(&content),
+            diagnostics: Vec::new(),
+            multi_cursor: MultiCursor::default(),
+            cursor_offset: 0,
+            multi_cursor_text: String::new(),
+            pending_cursor_text: None,
+            placing_cursor: false,
+            folds: FoldState::default(),
+            cursor_line: 0,
+            indent_style,
+            editorconfig,
+            bracket_pairs: None,
+            bracket_highlights: Default::default(),
+            occurrences: Occurrences::default(),
+            occurrences_at: None,
+            current_lines: None,
+            box_selection: None,
+            selection_history: SelectionHistory::default(),
+            undo_history: UndoHistory::default(),
+            document_version,
+            inlay_hints,
+            inlay_hint_request: None,
+            semantic_tokens: SemanticTokenState::default(),
+            semantic_tokens_version: None,
+            lazy_highlighter: (!read_only && lines_count > LAZY_HIGHLIGHT_LINES).then(LazyHighlighter::default),
+            lazy_highlight_request: None,
+            symbols: Vec::new(),
+            breadcrumb_path: Vec::new(),
+            bookmarks: Bookmarks::default(),
+            snippet: None,
+            pending_snippet_text: None,
+            completion_extras,
+            disk_state: DiskState::InSync,
+            encoding: decoded.encoding,
+            read_only,
+            line_ending,
+            saved_line_ending,
+            mixed_line_endings,
+        };
+
+        // Back to where the file was left; it may have changed on disk since
+        if let Some(position) = self.file_positions.get(&path) {
+            let cursor = super::lsp::offset_to_position(&content, position.clamped_cursor(&content));
+            open_file.pending_scroll_target = Some((cursor.line as usize + 1, cursor.character as usize + 1));
+            open_file.pending_scroll_top = Some(position.scroll_top);
+        }
+
+        if !read_only {
+            open_file.diagnostics = local_diagnostics(&path, &content, open_file.language_server.is_some());
+        }
+        if !open_file.diagnostics.is_empty() {
+            show_diagnostics(&input_state, &content, &open_file.diagnostics, cx);
+        }
+
+        self.file_watcher.watch(&path);
+        self.open_files.push(open_file);
+        let new_index = self.open_files.len() - 1;
+        self.current_file_index = Some(new_index);
+
+        // Create subscription for this file
+        let analyzer = self.rust_analyzer.clone();
+        tracing::debug!(
+            "📝 Creating change subscription for {:?}, rust_analyzer present: {}",
+            path.file_name(),
+            analyzer.is_some()
+        );
+        let subscription = cx.subscribe(&input_state, move |this: &mut TextEditor, input_state_entity: Entity<InputState>, event: &InputEvent, cx: &mut Context<TextEditor>| {
+            match event {
+                InputEvent::Change => {
+                    // Find which file this corresponds to
+                    if let Some(index) = this.open_files.iter().position(|f| f.input_state == input_state_entity) {
+                        if let Some(file) = this.open_files.get_mut(index) {
+                            file.is_modified = file.differs_from_saved(cx);
+                            file.version += 1;
+
+                            // The markdown preview follows below, once typing pauses
+
+                            // Notify rust-analyzer of the change
+                            if let Some(ref analyzer) = analyzer {
+                                let path = file.path.clone();
+                                let version = file.version;
+                                let content = file.input_state.read(cx).value().to_string();
+
+                                tracing::debug!("📝 File changed: {:?} (version {}), notifying rust-analyzer", path.file_name(), version);
+                                analyzer.update(cx, |analyzer, _cx| {
+                                    if let Err(e) = analyzer.did_change_file(&path, &content, version) {
+                                        tracing::error!("⚠️  Failed to notify rust-analyzer of file change: {}", e);
+                                    } else {
+                                        if version % 10 == 0 {  // Log every 10th change to avoid spam
+                                            tracing::debug!("✓ Notified rust-analyzer of change (version {})", version);
+                                        }
+                                    }
+                                });
+                            } else {
+                                if file.version == 2 {  // Only log once to avoid spam
+                                    tracing::debug!("⚠️  No rust-analyzer available for didChange");
+                                }
+                            }
+
+                            cx.notify();
+                        }
+                        this.sync_document_change(index, cx);
+                        this.update_signature_help(index, true, cx);
+                        this.schedule_fold_refresh(index, cx);
+                        this.schedule_symbol_refresh(index, cx);
+                        this.schedule_markdown_preview(index, cx);
+                        this.refresh_whitespace(index, cx);
+                        this.notify_dirty_state(cx);
+                        cx.emit(TextEditorEvent::BufferChanged {
+                            path: this.open_files[index].path.clone(),
+                        });
+                    }
+                },
+                InputEvent::GoToDefinition { path, line, character } => {
+                    // Navigate to the definition - emit an event so it can be handled
+                    // by the parent where we have window access
+                    tracing::debug!("🎯 Received GoToDefinition event: {:?} at {}:{}", path, line, character);
+
+                    // Emit the navigation event so it can be handled by parent components
+                    // that have window access
+                    let target_path = path.clone();
+                    let target_line = *line;
+                    let target_character = *character;
+
+                    // Remembered in the navigation history like other jumps
+                    this.navigate_to(target_path, target_line, target_character, cx);
+                },
+                _ => {}
+            }
+        });
+
+        self.subscriptions.push(subscription);
+        self.watch_cursor_and_folds(&input_state, window, cx);
+
+        // Emit event so rust-analyzer can be notified
+        cx.emit(TextEditorEvent::FileOpened {
+            path: path.clone(),
+            content: content.clone(),
+        });
+
+        // Read-only tabs skip the language features
+        if read_only {
+            let filename = path.file_name().unwrap_or_default().to_string_lossy();
+            self.set_status_message(format!("{} is open read-only", filename), cx);
+            cx.notify();
+            return;
+        }
+        self.schedule_fold_refresh(new_index, cx);
+        self.schedule_symbol_refresh(new_index, cx);
+        self.refresh_whitespace(new_index, cx);
+
+        // Log cache stats after opening
+        if let Some(index) = self.current_file_index {
+            if let Some(file) = self.open_files.get(index) {
+                let state = file.input_state.read(cx);
+                tracing::debug!(
+                    "📊 Line cache initialized - capacity: {} lines",
+                    state.line_cache().len()
+                );
+
+                // Log autocomplete configuration
+                if state.lsp.completion_provider.is_some() {
+                    tracing::debug!("✓ Autocomplete enabled with comprehensive provider");
+                } else {
+                    tracing::debug!("ℹ️  No autocomplete provider configured");
+                }
+            }
+        }
+
+        cx.notify();
+    }
+
+    fn resolve_workspace_root_for_file(&self, path: &PathBuf) -> Option<PathBuf> {
+        Some(workspace_root(path))
+    }
+
+    pub fn get_language_from_extension(&self, path: &PathBuf) -> String {
+        language_for_path(path).map_or("text", |language| language.name).to_string()
+    }
+
+    /// Get performance info about the current file
+    fn get_current_file_performance(&self, cx: &App) -> Option<String> {
+        if !self.show_performance_stats {
+            return None;
+        }
+
+        let index = self.current_file_index?;
+        let open_file = self.open_files.get(index)?;
+
+        let state = open_file.input_state.read(cx);
+        let cache_stats = state.line_cache().stats();
+
+        Some(format!(
+            "📊 Performance: {} lines | Cache: {:.1}% hit rate | {} cached lines | Memory: ~{} MB",
+            open_file.lines_count,
+            cache_stats.hit_rate() * 100.0,
+            state.line_cache().len(),
+            (state.line_cache().len() * 1024) / (1024 * 1024) // Rough estimate
+        ))
+    }
+
+    pub fn close_file(&mut self, index: usize, _window: &mut Window, cx: &mut Context<Self>) {
+        if index < self.open_files.len() {
+            self.remember_position(index, cx);
+            let file_path = self.open_files[index].path.clone();
+            if let Some(server) = &self.open_files[index].language_server {
+                let unused = self.language_servers.borrow_mut().close_document(server, &file_path, self.editor_id);
+                // Its last file closed; other editors would have started it again anyway
+                if let Some(server) = unused {
+                    cx.background_spawn(async move { shut_down_servers(vec![server]) }).detach();
+                }
+            }
+            let closed = self.open_files.remove(index);
+            self.file_watcher.unwatch(&file_path);
+            self.fold_refresh_tasks.remove(&file_path);
+            self.symbol_refresh_tasks.remove(&file_path);
+            self.inlay_hint_tasks.remove(&file_path);
+            self.semantic_token_tasks.remove(&file_path);
+            self.lazy_highlight_tasks.remove(&file_path);
+            self.markdown_preview_tasks.remove(&file_path);
+            self.file_views.remove(&closed.input_state.entity_id());
+
+            // Emit event so rust-analyzer can be notified
+            cx.emit(TextEditorEvent::FileClosed { path: file_path });
+            self.notify_dirty_state(cx);
+
+            // Adjust current file index
+            if let Some(current) = self.current_file_index {
+                if current == index {
+                    // Closed the current file
+                    if self.open_files.is_empty() {
+                        self.current_file_index = None;
+                    } else if index == self.open_files.len() {
+                        // Closed the last file, select the previous one
+                        self.current_file_index = Some(index.saturating_sub(1));
+                    } else {
+                        // Keep the same index (which now points to the next file)
+                        self.current_file_index = Some(index);
+                    }
+                } else if current > index {
+                    // Closed a file before the current one
+                    self.current_file_index = Some(current - 1);
+                }
+            }
+
+            cx.notify();
+        }
+    }
+
+    /// Close every file without asking, as the editor itself closes: language
+    /// servers hear the files are closed, and nothing stays watched.
+    ///
+    /// Returns the files that were open, and the servers none of the editors
+    /// have documents open with any more, for the caller to stop.
+    pub fn shut_down(&mut self, cx: &mut Context<Self>) -> (Vec<PathBuf>, Vec<Arc<LanguageServer>>) {
+        for index in 0..self.open_files.len() {
+            self.remember_position(index, cx);
+        }
+        let mut closed = Vec::with_capacity(self.open_files.len());
+        let mut unused = Vec::new();
+        for file in self.open_files.drain(..) {
+            if let Some(server) = &file.language_server {
+                unused.extend(self.language_servers.borrow_mut().close_document(server, &file.path, self.editor_id));
+            }
+            self.file_watcher.unwatch(&file.path);
+            closed.push(file.path);
+        }
+        self.current_file_index = None;
+        self.diagnostic_listeners.clear();
+        self.fold_refresh_tasks.clear();
+        self.symbol_refresh_tasks.clear();
+        self.inlay_hint_tasks.clear();
+        self.semantic_token_tasks.clear();
+        self.lazy_highlight_tasks.clear();
+        self.markdown_preview_tasks.clear();
+        self.file_views.clear();
+        cx.notify();
+        (closed, unused)
+    }
+
+    /// Close a tab, asking whether to save it first if it has unsaved changes
+    pub fn request_close_file(&mut self, index: usize, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(file) = self.open_files.get(index) else {
+            return;
+        };
+        if !file.is_modified {
+            self.close_file(index, window, cx);
+            return;
+        }
+
+        let path = file.path.clone();
+        let filename = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("untitled")
+            .to_string();
+        let answer = window.prompt(
+            PromptLevel::Warning,
+            &format!("Save changes to {filename}?"),
+            Some("Your changes will be lost if you don't save them."),
+            &["Save", "Don't Save", "Cancel"],
+            cx,
+        );
+        cx.spawn_in(window, async move |this, cx| {
+            let Ok(answer) = answer.await else {
+                return;
+            };
+            this.update_in(cx, |this, window, cx| {
+                // The tab may have moved while the prompt was open
+                let Some(index) = this.open_files.iter().position(|f| f.path == path) else {
+                    return;
+                };
+                match answer {
+                    0 => {
+                        this.current_file_index = Some(index);
+                        if this.save_current_file(window, cx) {
+                            this.close_file(index, window, cx);
+                        }
+                    }
+                    1 => this.close_file(index, window, cx),
+                    _ => {}
+                }
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    /// Switch to the next (or previous) tab, wrapping around
+    pub fn cycle_tabs(&mut self, forward: bool, window: &mut Window, cx: &mut Context<Self>) {
+        let count = self.open_files.len();
+        if count < 2 {
+            return;
+        }
+        let current = self.current_file_index.unwrap_or(0);
+        let next = if forward {
+            (current + 1) % count
+        } else {
+            (current + count - 1) % count
+        };
+        self.set_active_file(next, window, cx);
+    }
+
+    /// The active file's editor view, created the first time the file is shown
+    fn active_file_view(&mut self, cx: &mut Context<Self>) -> Option<Entity<FilePanelWrapper>> {
+        let index = self.current_file_index?;
+        let file = self.open_files.get(index)?;
+        let key = file.input_state.entity_id();
+        if let Some(view) = self.file_views.get(&key) {
+            // Renamed files get a fresh view carrying the new path
+            if view.read(cx).file_path() == &file.path {
+                return Some(view.clone());
+            }
+        }
+
+        let text_editor = cx.entity().downgrade();
+        let path = file.path.clone();
+        let input_state = file.input_state.clone();
+        let view = cx.new(|cx| FilePanelWrapper::new(text_editor, index, path, input_state, cx));
+        self.file_views.insert(key, view.clone());
+        Some(view)
+    }
+
+    fn set_active_file(&mut self, index: usize, _window: &mut Window, cx: &mut Context<Self>) {
+        if index < self.open_files.len() {
+            self.remember_current_position(cx);
+            self.current_file_index = Some(index);
+            cx.notify();
+        }
+    }
+
+    pub fn save_current_file(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> bool {
+        self.remember_current_position(cx);
+        let Some(index) = self.current_file_index else {
+            return false;
+        };
+        match self.save_file(index, cx) {
+            Ok(()) => true,
+            Err(e) => {
+                self.set_status_message(e, cx);
+                false
+            }
+        }
+    }
+
+    /// Save every open file with unsaved changes, carrying on past files that fail
+    pub fn save_all_files(&mut self, cx: &mut Context<Self>) -> SaveAllSummary {
+        self.remember_current_position(cx);
+        let mut summary = SaveAllSummary::default();
+        for index in 0..self.open_files.len() {
+            if !self.open_files[index].is_modified {
+                continue;
+            }
+            let path = self.open_files[index].path.clone();
+            match self.save_file(index, cx) {
+                Ok(()) => summary.saved.push(path),
+                Err(e) => summary.errors.push((path, e)),
+            }
+        }
+        summary
+    }
+
+    /// What saving `file` does to its whitespace; its `.editorconfig` has the last word
+    fn save_cleanup_for(&self, file: &OpenFile) -> SaveCleanup {
+        let language = self.get_language_from_extension(&file.path);
+        let mut cleanup = self.save_cleanup;
+        // Two trailing spaces break a Markdown line
+        if language == "markdown" {
+            cleanup.trim_trailing_whitespace = false;
+        }
+        let mut cleanup = self
+            .language_save_cleanup
+            .get(&language)
+            .map_or(cleanup, |language_cleanup| language_cleanup.apply(cleanup));
+        let editorconfig = &file.editorconfig;
+        cleanup.trim_trailing_whitespace = editorconfig.trim_trailing_whitespace.unwrap_or(cleanup.trim_trailing_whitespace);
+        cleanup.insert_final_newline = editorconfig.insert_final_newline.unwrap_or(cleanup.insert_final_newline);
+        cleanup
+    }
+
+    fn save_file(&mut self, index: usize, cx: &mut Context<Self>) -> Result<(), String> {
+        let cleanup = self.open_files.get(index).map(|file| self.save_cleanup_for(file)).unwrap_or_default();
+        let open_file = self.open_files.get_mut(index).ok_or_else(|| "No file open".to_string())?;
+        if open_file.read_only {
+            return Err(format!("{} is open read-only", open_file.path.display()));
+        }
+        // Saving would quietly bring back a file deleted elsewhere
+        if open_file.disk_state == DiskState::Deleted {
+            return Err("File was deleted on disk; use Save Anyway to recreate it".to_string());
+        }
+
+        // Get content from input state
+        let mut content = open_file.input_state.read(cx).value();
+        let edits = byte_range_edits(&content, save_cleanup_edits(&content, cleanup));
+        if !edits.is_empty() {
+            // The buffer catches up on the next render, keeping the cursor, and then matches what was saved
+            content = super::lsp::apply_text_edits(&content, edits.clone()).into();
+            self.pending_cursor_edits.push((open_file.input_state.clone(), edits));
+        }
+
+        // Write to file, in the encoding and line endings it was read with
+        let Some(bytes) = encoding::encode(&open_file.line_ending.apply(&content), open_file.encoding) else {
+            return Err(format!(
+                "{} can't hold every character of {}; save it with another encoding",
+                open_file.encoding.label(),
+                open_file.path.display()
+            ));
+        };
+        if let Err(e) = fs::write(&open_file.path, bytes) {
+            tracing::error!("✗ Failed to save file: {:?}, error: {}", open_file.path, e);
+            return Err(format!("Failed to save {}: {}", open_file.path.display(), e));
+        }
+        open_file.is_modified = false;
+        open_file.saved_content_hash = content_hash(&content);
+        open_file.saved_line_ending = open_file.line_ending;
+        open_file.mixed_line_endings = false;
+        open_file.disk_state = DiskState::InSync;
+        // Saving may be followed by a commit; check the file again next time
+        self.file_blame.remove(&open_file.path);
+        let path = open_file.path.clone();
+        tracing::debug!("💾 File saved: {:?}", open_file.path);
+        if let Some(server) = &open_file.language_server {
+            if self.language_servers.borrow().syncs_document(server, &open_file.path, self.editor_id) {
+                server.did_save(&open_file.path, &content);
+            }
+        }
+
+        // Emit event so rust-analyzer can be notified
+        cx.emit(TextEditorEvent::FileSaved {
+            path: open_file.path.clone(),
+            content: content.to_string(),
+        });
+        self.notify_dirty_state(cx);
+        self.schedule_git_diff(path, GIT_DIFF_DELAY, cx);
+
+        cx.notify();
+        Ok(())
+    }
+
+    /// Re-read the current file from disk, discarding unsaved changes
+    pub fn reload_current_file(&mut self, window: &mut Window, cx: &mut Context<Self>) -> bool {
+        match self.current_file_index {
+            Some(index) => self.reload_file(index, window, cx),
+            None => false,
+        }
+    }
+
+    /// Re-read an open file from disk, discarding unsaved changes
+    fn reload_file(&mut self, index: usize, window: &mut Window, cx: &mut Context<Self>) -> bool {
+        let Some(open_file) = self.open_files.get_mut(index) else {
+            return false;
+        };
+
+        let decoded = match fs::read(&open_file.path) {
+            Ok(bytes) => encoding::decode_as(&bytes, open_file.encoding.encoding),
+            Err(err) => {
+                tracing::error!("✗ Failed to reload file: {:?}, error: {}", open_file.path, err);
+                return false;
+            }
+        };
+        open_file.encoding = decoded.encoding;
+        (open_file.line_ending, open_file.mixed_line_endings) = LineEnding::detect(&decoded.text);
+        open_file.saved_line_ending = open_file.line_ending;
+        let content = line_endings::into_normalized(decoded.text);
+        let invalid_bytes = decoded.had_errors.then(|| invalid_bytes_message(&open_file.path, open_file.encoding));
+
+        open_file.input_state.update(cx, |state, cx| {
+            state.set_value(&content, window, cx);
+        });
+        open_file.is_modified = false;
+        open_file.saved_content_hash = content_hash(&content);
+        open_file.disk_state = DiskState::InSync;
+        open_file.lines_count = content.lines().count();
+        open_file.file_size = content.len();
+        tracing::debug!("🔄 File reloaded: {:?}", open_file.path);
+        let path = open_file.path.clone();
+        self.schedule_git_diff(path, Duration::ZERO, cx);
+        if let Some(message) = invalid_bytes {
+            self.set_status_message(message, cx);
+        }
+
+        self.notify_dirty_state(cx);
+        cx.notify();
+        true
+    }
+
+    /// React to an open file changing on disk: reload it when it has no
+    /// unsaved edits, otherwise leave it to the user in a banner
+    fn handle_disk_change(&mut self, path: PathBuf, cx: &mut Context<Self>) {
+        let Some(file) = self.open_files.iter_mut().find(|file| file.path == path) else {
+            return;
+        };
+        let on_disk = fs::read(&path).map(|bytes| {
+            line_endings::into_normalized(encoding::decode_as(&bytes, file.encoding.encoding).text)
+        });
+        let disk_state = match on_disk {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => DiskState::Deleted,
+            Err(e) => {
+                tracing::warn!("⚠️  Failed to read changed file {:?}: {}", path, e);
+                return;
+            }
+            // Our own save, or changed back to what was saved
+            Ok(content) if content_hash(&content) == file.saved_content_hash => DiskState::InSync,
+            Ok(_) if !file.is_modified => {
+                self.pending_reloads.push(path);
+                cx.notify();
+                return;
+            }
+            Ok(disk_content) => DiskState::Changed {
+                disk_content,
+                show_diff: false,
+            },
+        };
+        if file.disk_state != disk_state {
+            if disk_state == DiskState::Deleted {
+                // It may come back, e.g. when a checkout replaces it
+                self.file_watcher.unwatch(&path);
+                self.file_watcher.watch(&path);
+            }
+            file.disk_state = disk_state;
+            cx.notify();
+        }
+    }
+
+    fn process_pending_reloads(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        for path in std::mem::take(&mut self.pending_reloads) {
+            if let Some(index) = self.open_files.iter().position(|file| file.path == path) {
+                if !self.open_files[index].is_modified {
+                    self.reload_file(index, window, cx);
+                }
+            }
+        }
+    }
+
+    /// Whether a format or rename is waiting to apply its edits, which saving
+    /// now would split across two versions on disk
+    pub fn is_applying_lsp_edits(&self) -> bool {
+        self.edit_requests_in_flight > 0
+    }
+
+    /// Whether an open file has been deleted on disk since it was opened
+    pub fn is_deleted_on_disk(&self, path: &Path) -> bool {
+        self.open_files
+            .iter()
+            .any(|file| file.path == path && file.disk_state == DiskState::Deleted)
+    }
+
+    /// Leave the buffer as it is, ignoring the change on disk until the next one
+    fn keep_buffer_over_disk(&mut self, index: usize, cx: &mut Context<Self>) {
+        if let Some(file) = self.open_files.get_mut(index) {
+            file.disk_state = DiskState::InSync;
+            cx.notify();
+        }
+    }
+
+    /// Save a file deleted on disk, recreating it
+    fn save_deleted_file_anyway(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        if let Some(file) = self.current_file_index.and_then(|index| self.open_files.get_mut(index)) {
+            file.disk_state = DiskState::InSync;
+        }
+        if self.save_current_file(window, cx) {
+            if let Some(path) = self.current_file_path() {
+                self.file_watcher.unwatch(&path);
+                self.file_watcher.watch(&path);
+            }
+        }
+    }
+
+    /// Banner above the current file when it changed or vanished on disk
+    fn render_disk_change_banner(&self, cx: &mut Context<Self>) -> Option<impl IntoElement> {
+        let index = self.current_file_index?;
+        let file = self.open_files.get(index)?;
+        let file_name = file
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let banner = h_flex()
+            .w_full()
+            .px_3()
+            .py_1()
+            .gap_2()
+            .items_center()
+            .text_xs()
+            .bg(cx.theme().warning.opacity(0.15))
+            .border_b_1()
+            .border_color(cx.theme().warning);
+
+        match &file.disk_state {
+            DiskState::InSync => None,
+            DiskState::Deleted => Some(
+                v_flex().w_full().child(
+                    banner
+                        .child(
+                            div()
+                                .flex_1()
+                                .text_color(cx.theme().foreground)
+                                .child(format!("{} was deleted on disk.", file_name)),
+                        )
+                        .child(
+                            Button::new("save_anyway")
+                                .label("Save Anyway")
+                                .ghost()
+                                .xsmall()
+                                .on_click(cx.listener(|this, _, window, cx| this.save_deleted_file_anyway(window, cx))),
+                        )
+                        .child(
+                            Button::new("close_deleted")
+                                .label("Close")
+                                .ghost()
+                                .xsmall()
+                                .on_click(cx.listener(move |this, _, window, cx| this.request_close_file(index, window, cx))),
+                        ),
+                ),
+            ),
+            DiskState::Changed { disk_content, show_diff } => {
+                let diff = show_diff.then(|| {
+                    let buffer = file.input_state.read(cx).value();
+                    let view = diff_view(disk_content, &buffer, DiffSide::Inline);
+                    v_flex()
+                        .id("disk_change_diff")
+                        .w_full()
+                        .max_h(px(240.))
+                        .overflow_y_scroll()
+                        .py_1()
+                        .font_family("JetBrains Mono")
+                        .text_xs()
+                        .border_b_1()
+                        .border_color(cx.theme().border)
+                        .children(view.text.lines().zip(&view.lines).filter_map(|(line, kind)| {
+                            let (prefix, color) = match kind {
+                                DiffLine::Removed => ("-", cx.theme().danger),
+                                DiffLine::Added => ("+", cx.theme().success),
+                                DiffLine::Unchanged | DiffLine::Padding => return None,
+                            };
+                            Some(
+                                div()
+                                    .px_3()
+                                    .whitespace_nowrap()
+                                    .text_color(color)
+                                    .bg(color.opacity(0.08))
+                                    .child(format!("{} {}", prefix, line)),
+                            )
+                        }))
+                });
+                Some(
+                    v_flex()
+                        .w_full()
+                        .child(
+                            banner
+                                .child(
+                                    div()
+                                        .flex_1()
+                                        .text_color(cx.theme().foreground)
+                                        .child(format!("{} changed on disk and has unsaved changes here.", file_name)),
+                                )
+                                .child(
+                                    Button::new("reload_from_disk")
+                                        .label("Reload")
+                                        .ghost()
+                                        .xsmall()
+                                        .on_click(cx.listener(move |this, _, window, cx| {
+                                            this.reload_file(index, window, cx);
+                                        })),
+                                )
+                                .child(
+                                    Button::new("keep_buffer")
+                                        .label("Keep")
+                                        .ghost()
+                                        .xsmall()
+                                        .on_click(cx.listener(move |this, _, _window, cx| this.keep_buffer_over_disk(index, cx))),
+                                )
+                                .child(
+                                    Button::new("diff_with_disk")
+                                        .label("Diff")
+                                        .ghost()
+                                        .xsmall()
+                                        .selected(*show_diff)
+                                        .on_click(cx.listener(move |this, _, _window, cx| {
+                                            if let Some(DiskState::Changed { show_diff, .. }) =
+                                                this.open_files.get_mut(index).map(|file| &mut file.disk_state)
+                                            {
+                                                *show_diff = !*show_diff;
+                                                cx.notify();
+                                            }
+                                        })),
+                                ),
+                        )
+                        .children(diff),
+                )
+            }
+        }
+    }
+
+    pub fn close_current_file(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        if let Some(index) = self.current_file_index {
+            self.request_close_file(index, window, cx);
+        }
+    }
+
+    /// Navigate to a specific line and column in the current file
+    pub fn go_to_line(
+        &mut self,
+        line: usize,
+        column: usize,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if let Some(index) = self.current_file_index {
+            if let Some(open_file) = self.open_files.get_mut(index) {
+                // Store the pending scroll target in case layout isn't ready yet
+                open_file.pending_scroll_target = Some((line, column));
+
+                open_file.input_state.update(cx, |state, cx| {
+                    // LSP Position uses 'line' and 'character' fields (0-based)
+                    // Our UI uses 1-based line numbers
+                    use ui::input::Position;
+                    state.set_cursor_position(
+                        Position {
+                            line: (line.saturating_sub(1)) as u32,
+                            character: (column.saturating_sub(1)) as u32,
+                        },
+                        window,
+                        cx,
+                    );
+
+                    tracing::debug!("📍 Navigated to line {}, column {}", line, column);
+
+                    // Force an additional notify to ensure scroll is processed
+                    cx.notify();
+                });
+
+                // Notify at the TextEditor level as well to ensure render is triggered
+                cx.notify();
+            }
+        }
+    }
+
+    /// Cursor line and column in the current file, both from one and the
+    /// column in characters, as the status bar shows them
+    pub fn cursor_position(&self, cx: &App) -> Option<(usize, usize)> {
+        let file = self.open_files.get(self.current_file_index?)?;
+        let (line, column) = file.synced.line_column(file.input_state.read(cx).cursor());
+        Some((line as usize + 1, column as usize + 1))
+    }
+
+    /// Move the cursor to a line and column like `cursor_position` gives
+    /// them, clamped to the current file, and focus the editor
+    ///
+    /// Returns false when no file is open.
+    pub fn goto_position(&mut self, line: usize, column: usize, window: &mut Window, cx: &mut Context<Self>) -> bool {
+        let Some(file) = self.current_file_index.and_then(|index| self.open_files.get(index)) else {
+            return false;
+        };
+        let to_u32 = |n: usize| u32::try_from(n.saturating_sub(1)).unwrap_or(u32::MAX);
+        let offset = file.synced.line_column_to_offset(to_u32(line), to_u32(column));
+        let position = file.synced.offset_to_position(offset);
+        let input_state = file.input_state.clone();
+        self.go_to_line(position.line as usize + 1, position.character as usize + 1, window, cx);
+        input_state.update(cx, |state, cx| state.focus(window, cx));
+        true
+    }
+
+    /// Follow a file or folder renamed on disk: open files at or under `from` move to `to`
+    pub fn rename_path(&mut self, from: &Path, to: &Path, cx: &mut Context<Self>) {
+        let mut renamed = false;
+        for file in self.open_files.iter_mut() {
+            let Ok(rest) = file.path.strip_prefix(from) else {
+                continue;
+            };
+            let old_path = std::mem::replace(&mut file.path, to.join(rest));
+            self.file_watcher.unwatch(&old_path);
+            self.file_watcher.watch(&file.path);
+            if let Some(server) = &file.language_server {
+                let mut servers = self.language_servers.borrow_mut();
+                // Opened under the new name first, so the server isn't left without documents
+                servers.open_document(server, &file.path, &file.synced.to_string(), self.editor_id);
+                servers.close_document(server, &old_path, self.editor_id);
+            }
+            renamed = true;
+        }
+        if renamed {
+            cx.notify();
+        }
+    }
+
+    /// Running language servers of this pane that answer `workspace/symbol`
+    pub fn workspace_symbol_servers(&self) -> Vec<Arc<LanguageServer>> {
+        self.language_servers
+            .borrow()
+            .servers()
+            .filter(|server| {
+                server.is_running()
+                    && server
+                        .capabilities()
+                        .is_some_and(|caps| caps.workspace_symbol_provider.is_some())
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Open the Go to Line input for the current file.
+    ///
+    /// The cursor follows the input as it is typed; Escape goes back to where it was.
+    pub fn show_go_to_line(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(input_state) = self.current_input_state() else {
+            return;
+        };
+        let (original_cursor, original_scroll, line_count) = {
+            let state = input_state.read(cx);
+            (state.cursor(), state.get_scroll_offset(), state.value().split('\n').count())
+        };
+
+        let input = cx.new(|cx| {
+            InputState::new(window, cx).placeholder(format!("Line or line:column (1-{})", line_count))
+        });
+        let subscription = cx.subscribe_in(&input, window, |this, _input, event: &InputEvent, window, cx| {
+            match event {
+                InputEvent::Change => this.preview_go_to_line(window, cx),
+                InputEvent::PressEnter { .. } => this.confirm_go_to_line(window, cx),
+                InputEvent::Blur => this.cancel_go_to_line(window, cx),
+                _ => {}
+            }
+        });
+        input.update(cx, |state, cx| state.focus(window, cx));
+
+        self.go_to_line = Some(GoToLineSession {
+            input_state,
+            original_cursor,
+            original_scroll,
+            target: None,
+            error: None,
+            line_count,
+            input,
+            _subscription: subscription,
+        });
+        cx.notify();
+    }
+
+    /// Move the cursor to the typed location so the editor scrolls there while typing
+    fn preview_go_to_line(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(session) = self.go_to_line.as_mut() else {
+            return;
+        };
+        let query = session.input.read(cx).value();
+        match parse_line_target(&query) {
+            Ok(Some(target)) => {
+                let text = session.input_state.read(cx).value();
+                let (line, column) = clamp_to_text(target, &text);
+                session.target = Some((line, column));
+                session.error = None;
+                session.input_state.update(cx, |state, cx| {
+                    state.set_cursor_position(ui::input::Position::new(line, column), window, cx);
+                });
+            }
+            Ok(None) => {
+                session.target = None;
+                session.error = None;
+            }
+            Err(error) => {
+                session.target = None;
+                session.error = Some(error);
+            }
+        }
+        cx.notify();
+    }
+
+    fn confirm_go_to_line(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(session) = self.go_to_line.as_ref() else {
+            return;
+        };
+        // Keep the input open so the error can be corrected
+        if session.error.is_some() {
+            return;
+        }
+        let Some(session) = self.go_to_line.take() else {
+            return;
+        };
+        let input_state = session.input_state;
+        input_state.update(cx, |state, cx| state.focus(window, cx));
+        match session.target {
+            Some((line, _)) => self.flash_line(&input_state, line, cx),
+            None => self.restore_cursor(&input_state, session.original_cursor, session.original_scroll, window, cx),
+        }
+        cx.notify();
+    }
+
+    fn cancel_go_to_line(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(session) = self.go_to_line.take() else {
+            return;
+        };
+        let input_state = session.input_state;
+        self.restore_cursor(&input_state, session.original_cursor, session.original_scroll, window, cx);
+        input_state.update(cx, |state, cx| state.focus(window, cx));
+        cx.notify();
+    }
+
+    fn restore_cursor(
+        &mut self,
+        input_state: &Entity<InputState>,
+        cursor: usize,
+        scroll: Point<Pixels>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        input_state.update(cx, |state, cx| {
+            let position = super::lsp::offset_to_position(&state.value(), cursor);
+            state.set_cursor_position(ui::input::Position::new(position.line, position.character), window, cx);
+            state.set_scroll_offset(scroll);
+        });
+    }
+
+    /// Briefly highlight `line`, unless the find bar is using the search highlights
+    fn flash_line(&mut self, input_state: &Entity<InputState>, line: u32, cx: &mut Context<Self>) {
+        if self.find_bar_visible {
+            return;
+        }
+        let text = input_state.read(cx).value();
+        let start = super::lsp::position_to_offset(&text, lsp_types::Position::new(line, 0));
+        let end = text[start..].find('\n').map_or(text.len(), |len| start + len);
+        input_state.update(cx, |state, cx| {
+            state.set_search_highlights(vec![start..end], Some(0));
+            cx.notify();
+        });
+
+        let input_state = input_state.clone();
+        self.line_flash_task = Some(cx.spawn(async move |this, cx| {
+            cx.background_executor().timer(LINE_FLASH_DURATION).await;
+            this.update(cx, |this, cx| {
+                if !this.find_bar_visible {
+                    input_state.update(cx, |state, cx| {
+                        state.set_search_highlights(Vec::new(), None);
+                        cx.notify();
+                    });
+                }
+            })
+            .ok();
+        }));
+    }
+
+    fn render_go_to_line(&self, cx: &mut Context<Self>) -> Option<impl IntoElement> {
+        let session = self.go_to_line.as_ref()?;
+        let hint = match (session.error, session.target) {
+            (Some(error), _) => error.to_string(),
+            (None, Some((line, column))) => format!("Go to line {}, column {}", line + 1, column + 1),
+            (None, None) => format!("Type a line between 1 and {}", session.line_count),
+        };
+        Some(
+            div().absolute().top_10().left_0().right_0().flex().justify_center().child(
+                v_flex()
+                    .w(px(360.))
+                    .p_2()
+                    .gap_1()
+                    .bg(cx.theme().popover)
+                    .border_1()
+                    .border_color(cx.theme().border)
+                    .rounded(cx.theme().radius)
+                    .shadow_lg()
+                    .capture_key_down(cx.listener(|this, event: &KeyDownEvent, window, cx| {
+                        if event.keystroke.key == "escape" {
+                            this.cancel_go_to_line(window, cx);
+                            cx.stop_propagation();
+                        }
+                    }))
+                    .child(TextInput::new(&session.input).small())
+                    .child(
+                        div()
+                            .text_xs()
+                            .text_color(if session.error.is_some() {
+                                cx.theme().danger
+                            } else {
+                                cx.theme().muted_foreground
+                            })
+                            .child(hint),
+                    ),
+            ),
+        )
+    }
+
+    /// Get the current file path if any
+    pub fn current_file_path(&self) -> Option<PathBuf> {
+        self.current_file_index
+            .and_then(|index| self.open_files.get(index))
+            .map(|file| file.path.clone())
+    }
+
+    /// Get the current scroll offset of the active file's input state
+    pub fn get_current_scroll_offset(&self, cx: &mut Context<Self>) -> Option<Point<Pixels>> {
+        self.current_file_index
+            .and_then(|index| self.open_files.get(index))
+            .map(|file| file.input_state.read(cx).get_scroll_offset())
+    }
+
+    /// Set the scroll offset for the active file's input state
+    pub fn set_scroll_offset(&mut self, offset: Point<Pixels>, cx: &mut Context<Self>) {
+        if let Some(index) = self.current_file_index {
+            if let Some(file) = self.open_files.get(index) {
+                file.input_state.update(cx, |state, _cx| {
+                    state.set_scroll_offset(offset);
+                });
+            }
+        }
+    }
+
+    /// Process pending navigation request (called from render where we have window access)
+    fn process_pending_navigation(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        if let Some((path, line, character)) = self.pending_navigation.take() {
+            tracing::debug!(
+                "🎯 Processing pending navigation to {:?} at {}:{}",
+                path,
+                line,
+                character
+            );
+
+            // Check if file is already open
+            let file_index = self.open_files.iter().position(|f| f.path == path);
+
+            if let Some(index) = file_index {
+                // File is already open, switch to it
+                self.current_file_index = Some(index);
+                tracing::debug!("✓ Switched to already-open file {:?}", path);
+            } else {
+                // Need to open the file first
+                tracing::debug!("📂 Opening file {:?}", path);
+                self.open_file(path.clone(), window, cx);
+                tracing::debug!(
+                    "✓ File opened, current_file_index: {:?}",
+                    self.current_file_index
+                );
+            }
+
+            // Now navigate to the specific position
+            // LSP positions are 0-based, go_to_line expects 1-based line numbers
+            // and will convert back to 0-based internally
+            let target_line = (line + 1) as usize; // Convert 0-based to 1-based
+            let target_col = (character + 1) as usize; // Convert 0-based to 1-based
+
+            tracing::debug!(
+                "🎯 Calling go_to_line with line {} (LSP: {}), column {} (LSP: {})",
+                target_line,
+                line,
+                target_col,
+                character
+            );
+
+            self.go_to_line(target_line, target_col, window, cx);
+
+            cx.notify();
+        }
+    }
+
+    /// Ask the file's language server for the definition of the symbol under the cursor
+    ///
+    /// A single result is navigated to directly; several results open a picker.
+    pub fn go_to_definition(&mut self, cx: &mut Context<Self>) {
+        if let Some(path) = self.current_file_path() {
+            self.go_to_definition_in(&path, cx);
+        }
+    }
+
+    /// Go to the definition of the symbol under the cursor of a specific open file
+    pub fn go_to_definition_in(&mut self, path: &PathBuf, cx: &mut Context<Self>) {
+        let Some(request) = self.definition_request(path, cx) else {
+            return;
+        };
+
+        cx.spawn(async move |this, cx| {
+            let targets = match request.response().await {
+                Ok(response) => response.map(super::lsp::definition_targets).unwrap_or_default(),
+                Err(e) => {
+                    tracing::error!("⚠️  Definition request failed: {:#}", e);
+                    return;
+                }
+            };
+            this.update(cx, |this, cx| this.show_definition_targets(targets, cx)).ok();
+        })
+        .detach();
+    }
+
+    /// Start a `textDocument/definition` request for the symbol under the cursor of `path`
+    fn definition_request(
+        &self,
+        path: &PathBuf,
+        cx: &App,
+    ) -> Option<PendingRequest<Option<lsp_types::GotoDefinitionResponse>>> {
+        let open_file = self.open_files.iter().find(|file| &file.path == path)?;
+        let Some(server) = open_file.language_server.clone() else {
+            tracing::debug!("ℹ️  No language server for {:?}, cannot go to definition", open_file.path.file_name());
+            return None;
+        };
+
+        let state = open_file.input_state.read(cx);
+        let text = state.value().to_string();
+        let position = super::lsp::text_document_position(&open_file.path, &text, state.cursor())?;
+
+        Some(server.request::<lsp_types::request::GotoDefinition>(lsp_types::GotoDefinitionParams {
+            text_document_position_params: position,
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        }))
+    }
+
+    /// Show the definition of the symbol under the cursor below its line, without leaving the file
+    pub fn peek_definition(&mut self, cx: &mut Context<Self>) {
+        let Some(path) = self.current_file_path() else {
+            return;
+        };
+        let Some(request) = self.definition_request(&path, cx) else {
+            return;
+        };
+        cx.spawn(async move |this, cx| {
+            let targets = match request.response().await {
+                Ok(response) => response.map(super::lsp::definition_targets).unwrap_or_default(),
+                Err(e) => {
+                    tracing::error!("⚠️  Definition request failed: {:#}", e);
+                    return;
+                }
+            };
+            Self::open_peek(this, "Definition", targets, cx).await;
+        })
+        .detach();
+    }
+
+    /// Step through the references to the symbol under the cursor below its line
+    pub fn peek_references(&mut self, cx: &mut Context<Self>) {
+        let Some((request, _)) = self.find_references(cx) else {
+            return;
+        };
+        cx.spawn(async move |this, cx| {
+            let targets = match request.response().await {
+                Ok(locations) => super::lsp::location_targets(locations.unwrap_or_default()),
+                Err(e) => {
+                    tracing::error!("⚠️  References request failed: {:#}", e);
+                    return;
+                }
+            };
+            Self::open_peek(this, "References", targets, cx).await;
+        })
+        .detach();
+    }
+
+    /// Open a peek at `targets` below the cursor's line, with their files'
+    /// text taken from open buffers or read off the UI thread
+    async fn open_peek(this: WeakEntity<Self>, title: &'static str, targets: Vec<DefinitionTarget>, cx: &mut AsyncApp) {
+        let Ok(Some((buffers, path, line))) = this.update(cx, |this, cx| {
+            if targets.is_empty() {
+                this.set_status_message(format!("No {} found", title.to_lowercase()), cx);
+                return None;
+            }
+            let file = this.current_file_index.and_then(|index| this.open_files.get(index))?;
+            let line = file.synced.line_of(file.input_state.read(cx).cursor());
+            Some((this.open_buffers(cx), file.path.clone(), line))
+        }) else {
+            return;
+        };
+        let paths: Vec<PathBuf> = targets.iter().map(|target| target.path.clone()).collect();
+        let texts = cx
+            .background_executor()
+            .spawn(async move {
+                let mut texts = HashMap::new();
+                for path in paths {
+                    if texts.contains_key(&path) {
+                        continue;
+                    }
+                    let text = buffers
+                        .get(&path)
+                        .cloned()
+                        .or_else(|| std::fs::read_to_string(&path).ok().map(line_endings::into_normalized));
+                    texts.insert(path, text.unwrap_or_default());
+                }
+                texts
+            })
+            .await;
+        this.update(cx, |this, cx| {
+            this.peek = Some(PeekSession {
+                path,
+                line,
+                title,
+                targets,
+                index: 0,
+                texts,
+                input_state: None,
+                shown: None,
+            });
+            cx.notify();
+        })
+        .ok();
+    }
+
+    /// Load the peek's view with its current target, which needs the window
+    fn process_pending_peek(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(peek) = self.peek.as_ref().filter(|peek| peek.shown != Some(peek.index)) else {
+            return;
+        };
+        let target = peek.targets[peek.index].clone();
+        let text = peek.texts.get(&target.path).cloned().unwrap_or_default();
+        let language = self.get_language_from_extension(&target.path);
+        let input_state = cx.new(|cx| {
+            let mut state = InputState::new(window, cx).code_editor(language).line_number(true);
+            state.set_value(&text, window, cx);
+            state.set_cursor_position(
+                ui::input::Position::new(target.position.line, target.position.character),
+                window,
+                cx,
+            );
+            state
+        });
+        if let Some(peek) = self.peek.as_mut() {
+            peek.input_state = Some(input_state);
+            peek.shown = Some(peek.index);
+        }
+    }
+
+    /// The peek open below a line of `path`, once its view is loaded
+    pub fn peek_view(&self, path: &Path) -> Option<PeekView> {
+        let peek = self.peek.as_ref().filter(|peek| peek.path == path)?;
+        Some(PeekView {
+            line: peek.line,
+            title: peek.title,
+            index: peek.index,
+            count: peek.targets.len(),
+            target: peek.targets[peek.index].clone(),
+            input_state: peek.input_state.clone()?,
+        })
+    }
+
+    /// Show the next (`1`) or previous (`-1`) peeked result, wrapping around
+    pub fn step_peek(&mut self, delta: isize, cx: &mut Context<Self>) {
+        if let Some(peek) = self.peek.as_mut() {
+            let count = peek.targets.len() as isize;
+            peek.index = (peek.index as isize + delta).rem_euclid(count) as usize;
+            cx.notify();
+        }
+    }
+
+    pub fn close_peek(&mut self, cx: &mut Context<Self>) {
+        if self.peek.take().is_some() {
+            cx.notify();
+        }
+    }
+
+    /// Jump to the peeked result, closing the peek
+    pub fn open_peeked(&mut self, cx: &mut Context<Self>) {
+        if let Some(peek) = self.peek.take() {
+            let target = &peek.targets[peek.index];
+            self.navigate_to(target.path.clone(), target.position.line, target.position.character, cx);
+        }
+    }
+
+    /// Escape closes a peek open in `path`
+    pub fn handle_peek_key(&mut self, path: &Path, keystroke: &Keystroke, cx: &mut Context<Self>) -> bool {
+        if keystroke.key != "escape" || !self.peek.as_ref().is_some_and(|peek| peek.path == path) {
+            return false;
+        }
+        self.close_peek(cx);
+        true
+    }
+
+    /// Start a `textDocument/references` request for the symbol under the cursor
+    ///
+    /// Returns the pending request together with the symbol's text, or `None`
+    /// when the current file has no language server.
+    pub fn find_references(
+        &self,
+        cx: &App,
+    ) -> Option<(PendingRequest<Option<Vec<lsp_types::Location>>>, Option<String>)> {
+        let open_file = self.current_file_index.and_then(|index| self.open_files.get(index))?;
+        let server = open_file.language_server.clone()?;
+
+        let state = open_file.input_state.read(cx);
+        let text = state.value().to_string();
+        let offset = state.cursor();
+        let position = super::lsp::text_document_position(&open_file.path, &text, offset)?;
+
+        let request = server.request::<lsp_types::request::References>(lsp_types::ReferenceParams {
+            text_document_position: position,
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            context: lsp_types::ReferenceContext {
+                include_declaration: true,
+            },
+        });
+        Some((request, word_at(&text, offset)))
+    }
+
+    /// The function under the cursor, for its call hierarchy, and the server
+    /// to ask about its calls
+    pub fn prepare_call_hierarchy(
+        &self,
+        cx: &App,
+    ) -> Option<(PendingRequest<Option<Vec<lsp_types::CallHierarchyItem>>>, Arc<LanguageServer>)> {
+        let open_file = self.current_file_index.and_then(|index| self.open_files.get(index))?;
+        let server = open_file.language_server.clone()?;
+
+        let state = open_file.input_state.read(cx);
+        let position = super::lsp::text_document_position(&open_file.path, &state.value().to_string(), state.cursor())?;
+
+        let request = server.request::<lsp_types::request::CallHierarchyPrepare>(lsp_types::CallHierarchyPrepareParams {
+            text_document_position_params: position,
+            work_done_progress_params: Default::default(),
+        });
+        Some((request, server))
+    }
+
+    /// Whether the current file's language server answers `textDocument/prepareTypeHierarchy`
+    pub fn provides_type_hierarchy(&self) -> bool {
+        self.current_file_index
+            .and_then(|index| self.open_files.get(index)?.language_server.as_deref())
+            .is_some_and(provides_type_hierarchy)
+    }
+
+    /// The type under the cursor, for its type hierarchy, and the server to
+    /// ask about its supertypes and subtypes
+    pub fn prepare_type_hierarchy(
+        &self,
+        cx: &App,
+    ) -> Option<(PendingRequest<Option<Vec<lsp_types::TypeHierarchyItem>>>, Arc<LanguageServer>)> {
+        let open_file = self.current_file_index.and_then(|index| self.open_files.get(index))?;
+        let server = open_file.language_server.clone().filter(|server| provides_type_hierarchy(server))?;
+
+        let state = open_file.input_state.read(cx);
+        let position = super::lsp::text_document_position(&open_file.path, &state.value().to_string(), state.cursor())?;
+
+        let request = server.request::<lsp_types::request::TypeHierarchyPrepare>(lsp_types::TypeHierarchyPrepareParams {
+            text_document_position_params: position,
+            work_done_progress_params: Default::default(),
+        });
+        Some((request, server))
+    }
+
+    /// Current text of every open file, keyed by path
+    pub fn open_buffers(&self, cx: &App) -> HashMap<PathBuf, String> {
+        self.open_files
+            .iter()
+            .map(|file| (file.path.clone(), file.input_state.read(cx).value().to_string()))
+            .collect()
+    }
+
+    /// Current text of the open file at `path`
+    pub fn buffer_text(&self, path: &PathBuf, cx: &App) -> Option<String> {
+        self.open_files
+            .iter()
+            .find(|file| &file.path == path)
+            .map(|file| file.input_state.read(cx).value().to_string())
+    }
+
+    /// Bring the open file at `path` up to date with `text` edited elsewhere,
+    /// e.g. in another split pane showing the same file.
+    ///
+    /// Only the changed part is replaced so this pane's cursor and scroll stay put.
+    pub fn sync_buffer(&mut self, path: &PathBuf, text: &str, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(file) = self.open_files.iter().find(|file| &file.path == path) else {
+            return;
+        };
+        file.input_state.update(cx, |state, cx| {
+            let old_text = state.value().to_string();
+            replace_input_text(state, &old_text, text, window, cx);
+        });
+    }
+
+    /// Record that the file at `path` was saved with `content` by another pane
+    pub fn mark_saved(&mut self, path: &PathBuf, content: &str, cx: &mut Context<Self>) {
+        let Some(file) = self.open_files.iter_mut().find(|file| &file.path == path) else {
+            return;
+        };
+        file.saved_content_hash = content_hash(content);
+        file.is_modified = file.differs_from_saved(cx);
+        self.notify_dirty_state(cx);
+        cx.notify();
+    }
+
+    /// Open a file if needed and jump to a zero-based line and UTF-16 column
+    pub fn navigate_to(&mut self, path: PathBuf, line: u32, character: u32, cx: &mut Context<Self>) {
+        let target = NavLocation { path, line, character };
+        if let Some(current) = self.cursor_location(cx).filter(|current| !current.is_near(&target)) {
+            self.navigation.record(current);
+        }
+        self.jump_to(target, cx);
+    }
+
+    /// Move to `target` without recording where the cursor was
+    fn jump_to(&mut self, target: NavLocation, cx: &mut Context<Self>) {
+        self.pending_navigation = Some((target.path.clone(), target.line, target.character));
+        self.expected_jump = Some(target);
+        cx.notify();
+    }
+
+    /// The current file's cursor position
+    fn cursor_location(&self, cx: &App) -> Option<NavLocation> {
+        let file = self.current_file_index.and_then(|index| self.open_files.get(index))?;
+        let cursor = file.input_state.read(cx).cursor().min(file.synced.len());
+        let position = file.synced.offset_to_position(cursor);
+        Some(NavLocation {
+            path: file.path.clone(),
+            line: position.line,
+            character: position.character,
+        })
+    }
+
+    /// Return to where the cursor was before the last jump
+    pub fn go_back(&mut self, cx: &mut Context<Self>) {
+        let Some(current) = self.cursor_location(cx) else {
+            return;
+        };
+        if let Some(target) = self.navigation.go_back(current) {
+            self.jump_to(target, cx);
+        }
+    }
+
+    /// Redo a jump undone with Go Back
+    pub fn go_forward(&mut self, cx: &mut Context<Self>) {
+        let Some(current) = self.cursor_location(cx) else {
+            return;
+        };
+        if let Some(target) = self.navigation.go_forward(current) {
+            self.jump_to(target, cx);
+        }
+    }
+
+    /// Remember where the cursor was when it moves many lines at once
+    /// (a click far away, Ctrl+End, a find result) without the text changing
+    fn record_cursor_jump(&mut self, input_state: &Entity<InputState>, cx: &mut Context<Self>) {
+        let Some(file) = self.open_files.iter().find(|file| &file.input_state == input_state) else {
+            return;
+        };
+        let state = input_state.read(cx);
+        let cursor = state.cursor();
+        if cursor == file.cursor_offset || !file.synced.matches(&state.value()) {
+            return;
+        }
+        let from = file.synced.offset_to_position(file.cursor_offset.min(file.synced.len()));
+        let to = file.synced.offset_to_position(cursor.min(file.synced.len()));
+        let landed = NavLocation {
+            path: file.path.clone(),
+            line: to.line,
+            character: to.character,
+        };
+        if self.expected_jump.take_if(|expected| expected.is_near(&landed)).is_some() {
+            return;
+        }
+        if from.line.abs_diff(to.line) >= NEAR_LINES {
+            self.navigation.record(NavLocation {
+                path: file.path.clone(),
+                line: from.line,
+                character: from.character,
+            });
+        }
+    }
+
+    /// Format the current file, or just its selection, through the language server
+    ///
+    /// Files without a server (TOML, Markdown, ...) are left untouched.
+    pub fn format_document(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(open_file) = self.current_file_index.and_then(|index| self.open_files.get(index)) else {
+            return;
+        };
+        let Some(server) = open_file.language_server.clone() else {
+            tracing::debug!("ℹ️  No formatter for {:?}", open_file.path.file_name());
+            return;
+        };
+        let path = open_file.path.clone();
+        let version = open_file.version;
+        let input_state = open_file.input_state.clone();
+
+        let (text, selection) = input_state.update(cx, |state, cx| {
+            let selection = state
+                .selected_text_range(true, window, cx)
+                .map(|selection| selection.range)
+                .filter(|range| !range.is_empty());
+            (state.value().to_string(), selection)
+        });
+        let selection = selection
+            .map(|range| super::lsp::utf16_to_offset(&text, range.start)..super::lsp::utf16_to_offset(&text, range.end));
+        let Some(request) = formatting_request(open_file, &server, &text, selection) else {
+            return;
+        };
+
+        self.edit_requests_in_flight += 1;
+        cx.spawn_in(window, async move |this, cx| {
+            let response = request.response().await;
+            this.update_in(cx, |this, window, cx| {
+                this.edit_requests_in_flight -= 1;
+                match response {
+                    Ok(edits) => {
+                        this.apply_formatting_edits(&path, version, edits.unwrap_or_default(), false, window, cx)
+                    }
+                    Err(e) => tracing::error!("⚠️  Formatting failed: {:#}", e),
+                }
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    /// Whether saving `path` formats it first
+    pub fn formats_on_save(&self, path: &PathBuf) -> bool {
+        let language = self.get_language_from_extension(path);
+        self.language_save_cleanup
+            .get(&language)
+            .and_then(|cleanup| cleanup.format_on_save)
+            .unwrap_or_else(|| formats_on_save_by_default(&language))
+    }
+
+    /// Whether saving `path` has its server organize its imports first
+    pub fn organizes_imports_on_save(&self, path: &PathBuf) -> bool {
+        let language = self.get_language_from_extension(path);
+        self.language_save_cleanup
+            .get(&language)
+            .and_then(|cleanup| cleanup.organize_imports_on_save)
+            .unwrap_or(false)
+    }
+
+    /// Save the current file, first having its server organize its imports
+    /// and then format it where it's set to and the server can; false when
+    /// it isn't saved and won't be.
+    ///
+    /// The two are one undo step, and the file is written once they're in
+    /// the buffer, so what's saved is what's shown.
+    pub fn format_and_save_current_file(&mut self, window: &mut Window, cx: &mut Context<Self>) -> bool {
+        let Some(open_file) = self.current_file_index.and_then(|index| self.open_files.get(index)) else {
+            return false;
+        };
+        let server = open_file.language_server.clone().filter(|_| !open_file.read_only);
+        let organize = server.clone().filter(|server| {
+            self.organizes_imports_on_save(&open_file.path) && offers_organize_imports(server.capabilities().as_ref())
+        });
+        let format = server.filter(|server| {
+            self.formats_on_save(&open_file.path)
+                && server.capabilities().is_some_and(|caps| caps.document_formatting_provider.is_some())
+        });
+        // Without either there's nothing to wait for
+        if organize.is_none() && format.is_none() {
+            return self.save_current_file(window, cx);
+        }
+        let path = open_file.path.clone();
+
+        self.remember_current_position(cx);
+        self.edit_requests_in_flight += 1;
+        cx.spawn_in(window, async move |this, cx| {
+            let executor = cx.background_executor().clone();
+            // Organizing imports failing is no reason not to format and save
+            let mut organized = false;
+            let request = organize.as_ref().and_then(|server| {
+                this.update(cx, |this, cx| this.organize_imports_request(&path, server, cx)).ok().flatten()
+            });
+            if let (Some(server), Some((request, version))) = (organize, request) {
+                let action = match before_save(request.response(), &executor).await {
+                    Ok(response) => organize_imports_action(response.unwrap_or_default()),
+                    Err(e) => {
+                        tracing::debug!("ℹ️  Imports not organized: {:#}", e);
+                        None
+                    }
+                };
+                let action = match action {
+                    Some(action) if super::lsp::needs_resolve(&action, server.capabilities().as_ref()) => {
+                        let request = server.request::<lsp_types::request::CodeActionResolveRequest>(action);
+                        before_save(request.response(), &executor)
+                            .await
+                            .inspect_err(|e| tracing::debug!("ℹ️  Imports not organized: {:#}", e))
+                            .ok()
+                    }
+                    action => action,
+                };
+                if let Some(edit) = action.and_then(|action| action.edit) {
+                    organized = this
+                        .update_in(cx, |this, window, cx| this.apply_organized_imports(&path, version, edit, window, cx))
+                        .unwrap_or(false);
+                }
+            }
+
+            // Asked for once the server has the organized text
+            let request = format.as_ref().and_then(|server| {
+                this.update(cx, |this, cx| {
+                    let file = this.open_files.iter().find(|file| file.path == path)?;
+                    let text = file.input_state.read(cx).value().to_string();
+                    Some((formatting_request(file, server, &text, None)?, file.version))
+                })
+                .ok()
+                .flatten()
+            });
+            let formatted = match request {
+                Some((request, version)) => Some((before_save(request.response(), &executor).await, version)),
+                None => None,
+            };
+            this.update_in(cx, |this, window, cx| {
+                this.edit_requests_in_flight -= 1;
+                this.finish_format_on_save(&path, formatted, organized, window, cx);
+            })
+            .ok();
+        })
+        .detach();
+        true
+    }
+
+    /// The organize imports request for the file at `path`, with the
+    /// version of the file it's for
+    fn organize_imports_request(
+        &self,
+        path: &Path,
+        server: &LanguageServer,
+        cx: &App,
+    ) -> Option<(PendingRequest<Option<lsp_types::CodeActionResponse>>, i32)> {
+        let file = self.open_files.iter().find(|file| file.path == path)?;
+        let params = organize_imports_params(path, &file.input_state.read(cx).value())?;
+        Some((server.request::<lsp_types::request::CodeActionRequest>(params), file.version))
+    }
+
+    /// Apply the file's own edits of an organize imports action as an undo
+    /// step of their own; false when they changed nothing
+    fn apply_organized_imports(
+        &mut self,
+        path: &PathBuf,
+        version: i32,
+        edit: lsp_types::WorkspaceEdit,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> bool {
+        use super::lsp::WorkspaceChange;
+
+        let Some(file) = self.open_files.iter_mut().find(|file| &file.path == path) else {
+            return false;
+        };
+        if file.version != version {
+            return false;
+        }
+        // Organizing imports has no business in other files
+        let edits: Vec<lsp_types::TextEdit> = super::lsp::workspace_changes(edit)
+            .into_iter()
+            .filter_map(|change| match change {
+                WorkspaceChange::Edit(edited, edits) if &edited == path => Some(edits),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+        let text = file.input_state.read(cx).value().to_string();
+        if super::lsp::apply_text_edits(&text, edits.clone()) == text {
+            return false;
+        }
+        file.undo_history.start_atomic();
+        let input_state = file.input_state.clone();
+        apply_edits_to_input(&input_state, edits, window, cx);
+        true
+    }
+
+    /// Apply the formatter's answer, if there was one, and save the file
+    /// unless formatting failed and that isn't to be saved
+    fn finish_format_on_save(
+        &mut self,
+        path: &PathBuf,
+        formatted: Option<(anyhow::Result<Option<Vec<lsp_types::TextEdit>>>, i32)>,
+        organized: bool,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(index) = self.open_files.iter().position(|file| &file.path == path) else {
+            return;
+        };
+        let failure = match formatted {
+            Some((Ok(_), version)) if self.open_files[index].version != version => {
+                Some("the file changed meanwhile".to_string())
+            }
+            Some((Ok(edits), version)) => {
+                self.apply_formatting_edits(path, version, edits.unwrap_or_default(), organized, window, cx);
+                None
+            }
+            Some((Err(e), _)) => Some(format!("{:#}", e)),
+            None => None,
+        };
+        if let Some(e) = failure {
+            tracing::error!("⚠️  Format on save failed for {:?}: {}", path.file_name(), e);
+            if !self.save_when_formatting_fails {
+                self.set_status_message(format!("Not saved: formatting failed ({})", e), cx);
+                return;
+            }
+            self.set_status_message(format!("Formatting failed ({}); saved unformatted", e), cx);
+        }
+        if let Err(e) = self.save_file(index, cx) {
+            self.set_status_message(e, cx);
+        }
+    }
+
+    /// Apply formatting edits as one replacement so a single undo reverts
+    /// them, with the last step too when `join` says
+    fn apply_formatting_edits(
+        &mut self,
+        path: &PathBuf,
+        version: i32,
+        edits: Vec<lsp_types::TextEdit>,
+        join: bool,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(open_file) = self.open_files.iter_mut().find(|file| &file.path == path) else {
+            return;
+        };
+        // The user kept typing while the server worked; the edits no longer fit
+        if open_file.version != version || edits.is_empty() {
+            return;
+        }
+
+        if !join {
+            open_file.undo_history.start_atomic();
+        } else {
+            // An edit that changes nothing isn't recorded, and would leave the join for the next one
+            let text = open_file.input_state.read(cx).value().to_string();
+            if super::lsp::apply_text_edits(&text, edits.clone()) == text {
+                return;
+            }
+            open_file.undo_history.join_next();
+        }
+        let input_state = open_file.input_state.clone();
+        apply_edits_to_input(&input_state, edits, window, cx);
+        tracing::debug!("✓ Formatted {:?}", path.file_name());
+    }
+
+    /// Show a message in the status bar for a few seconds
+    pub fn set_status_message(&mut self, message: impl Into<SharedString>, cx: &mut Context<Self>) {
+        self.status_message = Some(message.into());
+        self.status_message_task = Some(cx.spawn(async move |this, cx| {
+            cx.background_executor().timer(Duration::from_secs(5)).await;
+            this.update(cx, |this, cx| {
+                this.status_message = None;
+                cx.notify();
+            })
+            .ok();
+        }));
+        cx.notify();
+    }
+
+    /// Start renaming the symbol under the cursor
+    ///
+    /// When the server supports `prepareRename` the position is validated first
+    /// and the server's placeholder pre-fills the input.
+    pub fn start_rename(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(open_file) = self.current_file_index.and_then(|index| self.open_files.get(index)) else {
+            return;
+        };
+        let Some(server) = open_file.language_server.clone() else {
+            self.set_status_message("Rename is not available for this file", cx);
+            return;
+        };
+
+        let path = open_file.path.clone();
+        let version = open_file.version;
+        let state = open_file.input_state.read(cx);
+        let text = state.value().to_string();
+        let offset = state.cursor();
+        let Some(position) = super::lsp::text_document_position(&path, &text, offset) else {
+            return;
+        };
+
+        let supports_prepare = matches!(
+            server.capabilities().and_then(|caps| caps.rename_provider),
+            Some(lsp_types::OneOf::Right(lsp_types::RenameOptions {
+                prepare_provider: Some(true),
+                ..
+            }))
+        );
+        if !supports_prepare {
+            match word_at(&text, offset) {
+                Some(word) => self.show_rename_input(path, offset, version, word, window, cx),
+                None => self.set_status_message("Place the cursor on a symbol to rename it", cx),
+            }
+            return;
+        }
+
+        let request = server.request::<lsp_types::request::PrepareRenameRequest>(position);
+        cx.spawn_in(window, async move |this, cx| {
+            let response = request.response().await;
+            this.update_in(cx, |this, window, cx| {
+                use lsp_types::PrepareRenameResponse;
+                let placeholder = match response {
+                    Ok(Some(PrepareRenameResponse::Range(range))) => {
+                        let range = super::lsp::range_to_offsets(&text, range);
+                        Some(text[range].to_string())
+                    }
+                    Ok(Some(PrepareRenameResponse::RangeWithPlaceholder { placeholder, .. })) => {
+                        Some(placeholder)
+                    }
+                    Ok(Some(PrepareRenameResponse::DefaultBehavior { .. })) => word_at(&text, offset),
+                    Ok(None) => None,
+                    Err(e) => {
+                        this.set_status_message(format!("Can't rename: {}", e), cx);
+                        return;
+                    }
+                };
+                match placeholder {
+                    Some(placeholder) => {
+                        this.show_rename_input(path, offset, version, placeholder, window, cx)
+                    }
+                    None => this.set_status_message("This element can't be renamed", cx),
+                }
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    fn show_rename_input(
+        &mut self,
+        path: PathBuf,
+        offset: usize,
+        version: i32,
+        placeholder: String,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let input = cx.new(|cx| InputState::new(window, cx).default_value(placeholder));
+        let subscription = cx.subscribe_in(&input, window, |this, _input, event: &InputEvent, window, cx| {
+            match event {
+                InputEvent::PressEnter { .. } => this.confirm_rename(window, cx),
+                InputEvent::Blur => this.cancel_rename(cx),
+                _ => {}
+            }
+        });
+        input.update(cx, |state, cx| state.focus(window, cx));
+
+        self.rename_session = Some(RenameSession {
+            path,
+            offset,
+            version,
+            input,
+            _subscription: subscription,
+        });
+        cx.notify();
+    }
+
+    fn cancel_rename(&mut self, cx: &mut Context<Self>) {
+        if self.rename_session.take().is_some() {
+            cx.notify();
+        }
+    }
+
+    fn confirm_rename(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(session) = self.rename_session.take() else {
+            return;
+        };
+        cx.notify();
+
+        let new_name = session.input.read(cx).value().trim().to_string();
+        if new_name.is_empty() {
+            return;
+        }
+        let Some(open_file) = self.open_files.iter().find(|file| file.path == session.path) else {
+            return;
+        };
+        if open_file.version != session.version {
+            self.set_status_message("The file changed, rename cancelled", cx);
+            return;
+        }
+        let Some(server) = open_file.language_server.clone() else {
+            return;
+        };
+        let text = open_file.input_state.read(cx).value().to_string();
+        let Some(position) = super::lsp::text_document_position(&session.path, &text, session.offset) else {
+            return;
+        };
+
+        let request = server.request::<lsp_types::request::Rename>(lsp_types::RenameParams {
+            text_document_position: position,
+            new_name,
+            work_done_progress_params: Default::default(),
+        });
+        self.edit_requests_in_flight += 1;
+        cx.spawn_in(window, async move |this, cx| {
+            let response = request.response().await;
+            this.update_in(cx, |this, window, cx| {
+                this.edit_requests_in_flight -= 1;
+                match response {
+                    Ok(Some(edit)) => {
+                        if let Ok(files_changed) = this.apply_workspace_edit(edit, window, cx) {
+                            this.set_status_message(
+                                format!(
+                                    "Renamed in {} file{}",
+                                    files_changed,
+                                    if files_changed == 1 { "" } else { "s" }
+                                ),
+                                cx,
+                            );
+                        }
+                    }
+                    Ok(None) => this.set_status_message("Nothing to rename", cx),
+                    Err(e) => this.set_status_message(format!("Rename failed: {}", e), cx),
+                }
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    /// Apply a workspace edit to open buffers and, for files that are not open, on disk
+    ///
+    /// Open files receive each file's edits as one undoable change and are left
+    /// unsaved for review; files that are not open are rewritten in place.
+    /// Every change is checked before any is made, so an edit that can't be
+    /// applied in full isn't applied at all; should the disk still refuse one
+    /// partway through, the rest are left undone and the failure names it.
+    /// Returns the number of files edited.
+    pub fn apply_workspace_edit(
+        &mut self,
+        edit: lsp_types::WorkspaceEdit,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> Result<usize, super::lsp::EditFailure> {
+        use super::lsp::{EditFailure, WorkspaceChange};
+
+        let changes = super::lsp::workspace_changes(edit);
+        let planned = self.plan_workspace_edit(changes).map_err(|reason| EditFailure {
+            reason,
+            failed_change: None,
+        });
+        let planned = match planned {
+            Ok(planned) => planned,
+            Err(failure) => {
+                tracing::error!("⚠️  Workspace edit not applied: {}", failure);
+                self.set_status_message(format!("Edit not applied: {}", failure), cx);
+                return Err(failure);
+            }
+        };
+
+        let mut files_changed = 0;
+        for (index, change, disk_text) in planned {
+            let result = match change {
+                WorkspaceChange::Edit(path, edits) => {
+                    let result = match disk_text {
+                        Some(text) => fs::write(&path, text)
+                            .map_err(|e| format!("Failed to write {}: {}", path.display(), e)),
+                        None => {
+                            if let Some(file) = self.open_files.iter_mut().find(|file| file.path == path) {
+                                file.undo_history.start_atomic();
+                                let input_state = file.input_state.clone();
+                                apply_edits_to_input(&input_state, edits, window, cx);
+                            }
+                            Ok(())
+                        }
+                    };
+                    if result.is_ok() {
+                        files_changed += 1;
+                    }
+                    result
+                }
+                // Checked already: the path is free, or `overwrite` asks for it to be emptied
+                WorkspaceChange::Create(path, _) => {
+                    if let Some(parent) = path.parent() {
+                        let _ = fs::create_dir_all(parent);
+                    }
+                    fs::write(&path, "").map_err(|e| format!("Failed to create {}: {}", path.display(), e))
+                }
+                WorkspaceChange::Rename(from, to, options) => {
+                    // Only renaming onto something there already with `overwrite`
+                    let replaced = if options.overwrite && to.exists() {
+                        let removed = if to.is_dir() { fs::remove_dir_all(&to) } else { fs::remove_file(&to) };
+                        removed.map_err(|e| format!("Failed to replace {}: {}", to.display(), e))
+                    } else {
+                        Ok(())
+                    };
+                    let result = replaced.and_then(|()| {
+                        fs::rename(&from, &to)
+                            .map_err(|e| format!("Failed to rename {} to {}: {}", from.display(), to.display(), e))
+                    });
+                    if result.is_ok() {
+                        self.rename_path(&from, &to, cx);
+                    }
+                    result
+                }
+                WorkspaceChange::Delete(path, options) => {
+                    let result = match (path.is_dir(), options.recursive) {
+                        (true, true) => fs::remove_dir_all(&path),
+                        // Fails rather than take anything still inside
+                        (true, false) => fs::remove_dir(&path),
+                        (false, _) => fs::remove_file(&path),
+                    };
+                    result.map_err(|e| format!("Failed to delete {}: {}", path.display(), e))
+                }
+            };
+            if let Err(reason) = result {
+                let failure = EditFailure {
+                    reason,
+                    failed_change: Some(index),
+                };
+                tracing::error!("⚠️  Workspace edit partly applied: {}", failure);
+                self.set_status_message(format!("Edit partly applied: {}", failure), cx);
+                return Err(failure);
+            }
+        }
+        Ok(files_changed)
+    }
+
+    /// Walk through a workspace edit without changing anything, leaving out
+    /// the changes whose options skip them: each change that goes ahead, by
+    /// its index in the edit and with the new text of an edit to a file that
+    /// isn't open, or why the edit can't be applied
+    fn plan_workspace_edit(
+        &self,
+        changes: Vec<super::lsp::WorkspaceChange>,
+    ) -> Result<Vec<(usize, super::lsp::WorkspaceChange, Option<String>)>, String> {
+        use super::lsp::WorkspaceChange;
+
+        let mut open: HashSet<PathBuf> = self.open_files.iter().map(|file| file.path.clone()).collect();
+        // Files as they will be once the changes so far are made; `None` once deleted
+        let mut disk: HashMap<PathBuf, Option<String>> = HashMap::new();
+        let read = |disk: &HashMap<PathBuf, Option<String>>, path: &PathBuf| -> Result<String, String> {
+            match disk.get(path) {
+                Some(Some(text)) => Ok(text.clone()),
+                Some(None) => Err(format!("{} is deleted by an earlier change", path.display())),
+                None => fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e)),
+            }
+        };
+
+        let mut planned = Vec::with_capacity(changes.len());
+        for (index, change) in changes.into_iter().enumerate() {
+            let exists = |path: &Path| match disk.get(path) {
+                Some(text) => text.is_some(),
+                // Nothing is left inside a folder deleted or renamed away
+                None => !path.ancestors().skip(1).any(|dir| matches!(disk.get(dir), Some(None))) && path.exists(),
+            };
+            let has_entries = |path: &Path| fs::read_dir(path).is_ok_and(|mut entries| entries.next().is_some());
+            if !change.check(exists, has_entries)? {
+                continue;
+            }
+
+            let mut text = None;
+            match &change {
+                WorkspaceChange::Edit(path, edits) => {
+                    if !open.contains(path) {
+                        let new_text = super::lsp::apply_text_edits(&read(&disk, path)?, edits.clone());
+                        disk.insert(path.clone(), Some(new_text.clone()));
+                        text = Some(new_text);
+                    }
+                }
+                WorkspaceChange::Create(path, _) => {
+                    disk.insert(path.clone(), Some(String::new()));
+                }
+                WorkspaceChange::Rename(from, to, _) => {
+                    if open.remove(from) {
+                        open.insert(to.clone());
+                    }
+                    let moved = disk.get(from).cloned().flatten();
+                    disk.insert(from.clone(), None);
+                    match moved {
+                        Some(moved) => disk.insert(to.clone(), Some(moved)),
+                        None => disk.remove(to),
+                    };
+                }
+                WorkspaceChange::Delete(path, _) => {
+                    disk.insert(path.clone(), None);
+                }
+            }
+            planned.push((index, change, text));
+        }
+        Ok(planned)
+    }
+
+    /// Apply edits servers asked for with `workspace/applyEdit` and tell each
+    /// server how it went (called from render where we have window access)
+    fn process_pending_workspace_edits(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        for (server, id, edit) in std::mem::take(&mut self.pending_workspace_edits) {
+            let result = self.apply_workspace_edit(edit, window, cx);
+            let response = super::lsp::apply_edit_response(&result);
+            server.respond(id, serde_json::to_value(response).unwrap_or_default());
+        }
+    }
+
+    /// Ask for the code actions at the cursor of a file once the cursor rests,
+    /// to show the lightbulb when there are any
+    fn schedule_code_actions(&mut self, input_state: &Entity<InputState>, cx: &mut Context<Self>) {
+        let Some(file) = self.open_files.iter().find(|file| &file.input_state == input_state) else {
+            return;
+        };
+        if file.language_server.as_deref().is_none_or(|server| !provides_code_actions(server)) {
+            return;
+        }
+        let state = input_state.read(cx);
+        let cursor = state.cursor();
+        // Edits are asked about once the server has the new text
+        if !file.synced.matches(&state.value()) {
+            return;
+        }
+        let unchanged = self.code_actions.as_ref().is_some_and(|session| {
+            session.selected.is_some()
+                || (session.path == file.path && session.version == file.version && session.cursor == cursor)
+        });
+        if unchanged {
+            return;
+        }
+
+        let path = file.path.clone();
+        let version = file.version;
+        self.code_actions = Some(CodeActionsSession {
+            path: path.clone(),
+            version,
+            cursor,
+            line: file.synced.line_of(cursor),
+            items: Vec::new(),
+            selected: None,
+        });
+        self.code_actions_task = Some(cx.spawn(async move |this, cx| {
+            cx.background_executor().timer(CODE_ACTION_DELAY).await;
+            let Ok(Some(request)) = this.update(cx, |this, cx| this.request_code_actions(&path, cursor..cursor, cx)) else {
+                return;
+            };
+            let response = request.response().await;
+            this.update(cx, |this, cx| this.show_code_action_response(&path, version, cursor, response, false, cx))
+                .ok();
+        }));
+        cx.notify();
+    }
+
+    /// Highlight the other occurrences of the symbol the cursor rests on,
+    /// as the file's server finds them or, without one, by word
+    fn schedule_occurrence_highlights(&mut self, input_state: &Entity<InputState>, cx: &mut Context<Self>) {
+        let Some(file) = self.open_files.iter_mut().find(|file| &file.input_state == input_state) else {
+            return;
+        };
+        let state = input_state.read(cx);
+        let text = state.value();
+        let cursor = state.cursor();
+        let word = word_range_at(&text, cursor);
+        let at = word.clone().map(|word| (file.version, word));
+        if file.occurrences_at == at {
+            return;
+        }
+        file.occurrences_at = at.clone();
+        // Nothing stays highlighted once the cursor leaves the symbol or the text changes
+        if !file.occurrences.is_empty() {
+            file.occurrences = Occurrences::default();
+            show_occurrences(input_state, &file.occurrences, cx);
+        }
+        let (Some(word), Some(at)) = (word, at) else {
+            self.occurrence_task = None;
+            return;
+        };
+
+        let path = file.path.clone();
+        let server = file
+            .language_server
+            .clone()
+            .filter(|server| provides_document_highlights(server));
+        self.occurrence_task = Some(cx.spawn(async move |this, cx| {
+            cx.background_executor().timer(OCCURRENCE_HIGHLIGHT_DELAY).await;
+            let response = match server
+                .zip(super::lsp::text_document_position(&path, &text, cursor))
+            {
+                Some((server, position)) => Some(
+                    server
+                        .request::<lsp_types::request::DocumentHighlightRequest>(lsp_types::DocumentHighlightParams {
+                            text_document_position_params: position,
+                            work_done_progress_params: Default::default(),
+                            partial_result_params: Default::default(),
+                        })
+                        .response()
+                        .await,
+                ),
+                None => None,
+            };
+            let occurrences = match response {
+                Some(Ok(highlights)) => super::lsp::occurrences(&text, &highlights.unwrap_or_default()),
+                failed => {
+                    if let Some(Err(e)) = failed {
+                        tracing::debug!("⚠️  Document highlights unavailable: {:#}", e);
+                    }
+                    cx.background_spawn(async move { super::lsp::word_occurrences(&text, &text[word]) })
+                        .await
+                }
+            };
+            this.update(cx, |this, cx| {
+                let Some(file) = this.open_files.iter_mut().find(|file| file.path == path) else {
+                    return;
+                };
+                // The cursor moved on, or the text changed, while they were found
+                if file.occurrences_at.as_ref() != Some(&at) {
+                    return;
+                }
+                file.occurrences = occurrences;
+                show_occurrences(&file.input_state, &file.occurrences, cx);
+            })
+            .ok();
+        }));
+    }
+
+    /// Request the code actions for `range` of an open file, with the
+    /// diagnostics on the lines it spans
+    fn request_code_actions(
+        &self,
+        path: &Path,
+        range: Range<usize>,
+        cx: &App,
+    ) -> Option<PendingRequest<Option<lsp_types::CodeActionResponse>>> {
+        let file = self.open_files.iter().find(|file| file.path == path)?;
+        let server = file.language_server.as_ref()?;
+        let text = file.input_state.read(cx).value();
+        let lines = selected_lines(&text, range.clone());
+        let diagnostics = file
+            .diagnostics
+            .iter()
+            .filter(|diagnostic| {
+                let start = super::lsp::offset_to_position(&text, diagnostic.range.start).line;
+                let end = super::lsp::offset_to_position(&text, diagnostic.range.end).line;
+                start < lines.end && end >= lines.start
+            })
+            .map(|diagnostic| diagnostic.to_lsp(&text))
+            .collect();
+        let params = super::lsp::code_action_params(path, &text, range, diagnostics)?;
+        Some(server.request::<lsp_types::request::CodeActionRequest>(params))
+    }
+
+    fn show_code_action_response(
+        &mut self,
+        path: &Path,
+        version: i32,
+        cursor: usize,
+        response: anyhow::Result<Option<lsp_types::CodeActionResponse>>,
+        open_menu: bool,
+        cx: &mut Context<Self>,
+    ) {
+        let items = match response {
+            Ok(response) => super::lsp::code_action_items(response.unwrap_or_default()),
+            Err(e) => {
+                tracing::error!("⚠️  Code action request failed: {:#}", e);
+                Vec::new()
+            }
+        };
+        let Some(session) = self.code_actions.as_mut().filter(|session| {
+            session.path == path && session.version == version && session.cursor == cursor
+        }) else {
+            return;
+        };
+        let empty = items.is_empty();
+        session.items = items;
+        session.selected = (open_menu && !empty).then_some(0);
+        if open_menu && empty {
+            self.set_status_message("No code actions available here", cx);
+        }
+        cx.notify();
+    }
+
+    /// Open the menu of code actions for the current file's selection (or cursor)
+    pub fn show_code_actions(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(file) = self.current_file_index.and_then(|index| self.open_files.get(index)) else {
+            return;
+        };
+        let Some(server) = file.language_server.clone() else {
+            self.set_status_message("No language server for this file", cx);
+            return;
+        };
+        if !provides_code_actions(&server) {
+            self.set_status_message(format!("{} has no code actions", server.name()), cx);
+            return;
+        }
+        let path = file.path.clone();
+        let version = file.version;
+        let (text, cursor, selection) = file.input_state.update(cx, |state, cx| {
+            let text = state.value().to_string();
+            let cursor = state.cursor();
+            let selection = state
+                .selected_text_range(true, window, cx)
+                .map(|selection| {
+                    super::lsp::utf16_to_offset(&text, selection.range.start)
+                        ..super::lsp::utf16_to_offset(&text, selection.range.end)
+                })
+                .unwrap_or(cursor..cursor);
+            (text, cursor, selection)
+        });
+
+        // The lightbulb's actions are the ones for the cursor already
+        if let Some(session) = self.code_actions.as_mut().filter(|session| {
+            selection.is_empty()
+                && session.path == path
+                && session.version == version
+                && session.cursor == cursor
+                && !session.items.is_empty()
+        }) {
+            session.selected = Some(0);
+            cx.notify();
+            return;
+        }
+
+        self.code_actions = Some(CodeActionsSession {
+            path: path.clone(),
+            version,
+            cursor,
+            line: super::lsp::offset_to_position(&text, cursor).line,
+            items: Vec::new(),
+            selected: None,
+        });
+        let Some(request) = self.request_code_actions(&path, selection, cx) else {
+            return;
+        };
+        self.code_actions_task = Some(cx.spawn(async move |this, cx| {
+            let response = request.response().await;
+            this.update(cx, |this, cx| this.show_code_action_response(&path, version, cursor, response, true, cx))
+                .ok();
+        }));
+    }
+
+    /// Whether files at `path` get code actions, so their panel has room for the lightbulb
+    pub fn code_actions_supported(&self, path: &Path) -> bool {
+        self.open_files
+            .iter()
+            .find(|file| file.path == path)
+            .and_then(|file| file.language_server.as_deref())
+            .is_some_and(provides_code_actions)
+    }
+
+    /// The line with the lightbulb in a file, its actions, and the entry
+    /// highlighted when the menu is open
+    pub fn code_actions(&self, path: &Path) -> Option<(u32, &[CodeActionItem], Option<usize>)> {
+        let session = self.code_actions.as_ref().filter(|session| session.path == path)?;
+        let file = self.open_files.iter().find(|file| file.path == path)?;
+        if session.items.is_empty() || session.version != file.version {
+            return None;
+        }
+        Some((session.line, &session.items, session.selected))
+    }
+
+    /// Open or close the code action menu at the lightbulb in a file
+    pub fn toggle_code_action_menu(&mut self, path: &Path, cx: &mut Context<Self>) {
+        if let Some(session) = self.code_actions.as_mut().filter(|session| session.path == path) {
+            session.selected = match session.selected {
+                Some(_) => None,
+                None => (!session.items.is_empty()).then_some(0),
+            };
+            cx.notify();
+        }
+    }
+
+    pub fn dismiss_code_action_menu(&mut self, cx: &mut Context<Self>) {
+        if let Some(session) = self.code_actions.as_mut().filter(|session| session.selected.is_some()) {
+            session.selected = None;
+            cx.notify();
+        }
+    }
+
+    /// Up, Down, Enter and Escape while the code action menu is open in the file at `path`.
+    ///
+    /// Returns true when the keystroke was handled here and must not reach the input.
+    pub fn handle_code_action_key(
+        &mut self,
+        path: &PathBuf,
+        keystroke: &Keystroke,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> bool {
+        let modifiers = &keystroke.modifiers;
+        if modifiers.control || modifiers.platform || modifiers.alt || modifiers.shift {
+            return false;
+        }
+        let Some(session) = self.code_actions.as_mut().filter(|session| &session.path == path) else {
+            return false;
+        };
+        let Some(selected) = session.selected else {
+            return false;
+        };
+        let count = session.items.len();
+        match keystroke.key.as_str() {
+            "up" => session.selected = Some((selected + count - 1) % count),
+            "down" => session.selected = Some((selected + 1) % count),
+            "escape" => session.selected = None,
+            "enter" => {
+                self.apply_code_action(selected, window, cx);
+                return true;
+            }
+            _ => return false,
+        }
+        cx.notify();
+        true
+    }
+
+    /// Apply entry `index` of the code action menu: its edit, then its command,
+    /// resolving the action first when the server left the edit out
+    pub fn apply_code_action(&mut self, index: usize, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(session) = self.code_actions.as_mut() else {
+            return;
+        };
+        session.selected = None;
+        let Some(item) = session.items.get(index).cloned() else {
+            return;
+        };
+        let (path, version) = (session.path.clone(), session.version);
+        let title = item.title;
+        cx.notify();
+        if let Some(reason) = item.disabled {
+            self.set_status_message(format!("{}: {}", title, reason), cx);
+            return;
+        }
+        let Some(server) = self
+            .open_files
+            .iter()
+            .find(|file| file.path == path)
+            .and_then(|file| file.language_server.clone())
+        else {
+            return;
+        };
+
+        match item.action {
+            lsp_types::CodeActionOrCommand::Command(command) => self.execute_command(&server, command, cx),
+            lsp_types::CodeActionOrCommand::CodeAction(action)
+                if super::lsp::needs_resolve(&action, server.capabilities().as_ref()) =>
+            {
+                let request = server.request::<lsp_types::request::CodeActionResolveRequest>(action);
+                self.edit_requests_in_flight += 1;
+                cx.spawn_in(window, async move |this, cx| {
+                    let response = request.response().await;
+                    this.update_in(cx, |this, window, cx| {
+                        this.edit_requests_in_flight -= 1;
+                        // The edit was made for the text as it was when the menu opened
+                        let current = this.open_files.iter().find(|file| file.path == path);
+                        if current.is_none_or(|file| file.version != version) {
+                            this.set_status_message(format!("{}: the file changed, try again", title), cx);
+                            return;
+                        }
+                        match response {
+                            Ok(action) => this.run_code_action(&server, action, window, cx),
+                            Err(e) => this.set_status_message(format!("{} failed: {}", title, e), cx),
+                        }
+                    })
+                    .ok();
+                })
+                .detach();
+            }
+            lsp_types::CodeActionOrCommand::CodeAction(action) => self.run_code_action(&server, action, window, cx),
+        }
+    }
+
+    fn run_code_action(
+        &mut self,
+        server: &Arc<LanguageServer>,
+        action: lsp_types::CodeAction,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        if let Some(edit) = action.edit {
+            if self.apply_workspace_edit(edit, window, cx).is_err() {
+                return;
+            }
+        }
+        if let Some(command) = action.command {
+            self.execute_command(server, command, cx);
+        }
+    }
+
+    /// Run a server command; edits it makes come back as `workspace/applyEdit`
+    fn execute_command(&mut self, server: &Arc<LanguageServer>, command: lsp_types::Command, cx: &mut Context<Self>) {
+        let title = command.title;
+        let request = server.request::<lsp_types::request::ExecuteCommand>(lsp_types::ExecuteCommandParams {
+            command: command.command,
+            arguments: command.arguments.unwrap_or_default(),
+            work_done_progress_params: Default::default(),
+        });
+        cx.spawn(async move |this, cx| {
+            if let Err(e) = request.response().await {
+                this.update(cx, |this, cx| this.set_status_message(format!("{} failed: {}", title, e), cx))
+                    .ok();
+            }
+        })
+        .detach();
+    }
+
+    fn render_rename_input(&self, cx: &mut Context<Self>) -> Option<impl IntoElement> {
+        let session = self.rename_session.as_ref()?;
+        Some(
+            div().absolute().top_10().left_0().right_0().flex().justify_center().child(
+                v_flex()
+                    .w(px(360.))
+                    .p_2()
+                    .gap_1()
+                    .bg(cx.theme().popover)
+                    .border_1()
+                    .border_color(cx.theme().border)
+                    .rounded(cx.theme().radius)
+                    .shadow_lg()
+                    .child(
+                        div()
+                            .text_xs()
+                            .text_color(cx.theme().muted_foreground)
+                            .child("Rename symbol (Enter to confirm)"),
+                    )
+                    .child(TextInput::new(&session.input).small()),
+            ),
+        )
+    }
+
+    /// Request signature help for the call around the cursor of the file at `index`.
+    ///
+    /// After an edit (`typed`) a server trigger character before the cursor
+    /// opens the popup and `)` closes it; while open, edits and cursor moves
+    /// re-request so the active parameter follows the cursor.
+    fn update_signature_help(&mut self, index: usize, typed: bool, cx: &mut Context<Self>) {
+        let Some(file) = self.open_files.get(index) else {
+            return;
+        };
+        let session_cursor = self
+            .signature_help
+            .as_ref()
+            .filter(|session| session.path == file.path)
+            .map(|session| session.cursor);
+        let state = file.input_state.read(cx);
+        let cursor = state.cursor();
+        let text = state.value();
+        // Cursor moves only: edits are requested once the server has the new text
+        if !typed && (session_cursor.is_none_or(|previous| previous == cursor) || !file.synced.matches(&text)) {
+            return;
+        }
+        let Some(server) = file.language_server.clone() else {
+            return;
+        };
+        let Some(provider) = server.capabilities().and_then(|caps| caps.signature_help_provider) else {
+            return;
+        };
+        let Some(position) = super::lsp::text_document_position(&file.path, &text, cursor) else {
+            return;
+        };
+        let path = file.path.clone();
+
+        let previous = text[..cursor].chars().next_back();
+        if typed && previous == Some(')') {
+            self.dismiss_signature_help(cx);
+            return;
+        }
+        if session_cursor.is_none() {
+            let triggers = provider
+                .trigger_characters
+                .unwrap_or_else(|| vec!["(".to_string(), ",".to_string()]);
+            let triggered = typed && previous.is_some_and(|ch| triggers.iter().any(|trigger| trigger.starts_with(ch)));
+            if !triggered {
+                return;
+            }
+        }
+
+        let request = server.request::<lsp_types::request::SignatureHelpRequest>(lsp_types::SignatureHelpParams {
+            context: None,
+            text_document_position_params: position,
+            work_done_progress_params: Default::default(),
+        });
+        let signatures = self
+            .signature_help
+            .take()
+            .filter(|session| session.path == path)
+            .and_then(|session| session.signatures);
+        self.signature_help = Some(SignatureHelpSession { path, cursor, signatures });
+        self.signature_help_task = Some(cx.spawn(async move |this, cx| {
+            let response = request.response().await;
+            this.update(cx, |this, cx| {
+                let signatures = match response {
+                    Ok(help) => help.and_then(Signatures::from_lsp),
+                    Err(e) => {
+                        tracing::error!("⚠️  Signature help request failed: {:#}", e);
+                        None
+                    }
+                };
+                // No signatures means the cursor has left the call
+                let Some(mut signatures) = signatures else {
+                    this.dismiss_signature_help(cx);
+                    return;
+                };
+                let Some(session) = this.signature_help.as_mut() else {
+                    return;
+                };
+                // Keep the overload picked with up/down while typing its arguments
+                if let Some(previous) = &session.signatures {
+                    if previous.signatures.len() == signatures.signatures.len() {
+                        signatures.active_signature = previous.active_signature;
+                    }
+                }
+                session.signatures = Some(signatures);
+                cx.notify();
+            })
+            .ok();
+        }));
+    }
+
+    /// Close the signature help popup and drop its pending request
+    pub fn dismiss_signature_help(&mut self, cx: &mut Context<Self>) {
+        self.signature_help_task = None;
+        if self.signature_help.take().is_some() {
+            cx.notify();
+        }
+    }
+
+    /// Escape closes signature help and up/down cycle its overloads, for a
+    /// keystroke in the file at `path`.
+    ///
+    /// Returns true when the keystroke was handled here and must not reach the input.
+    pub fn handle_signature_help_key(&mut self, path: &PathBuf, keystroke: &Keystroke, cx: &mut Context<Self>) -> bool {
+        let modifiers = &keystroke.modifiers;
+        if modifiers.control || modifiers.platform || modifiers.alt || modifiers.shift {
+            return false;
+        }
+        let Some(signatures) = self
+            .signature_help
+            .as_mut()
+            .filter(|session| &session.path == path)
+            .and_then(|session| session.signatures.as_mut())
+        else {
+            return false;
+        };
+        match keystroke.key.as_str() {
+            "up" | "down" if signatures.signatures.len() > 1 => {
+                signatures.cycle(keystroke.key == "down");
+                cx.notify();
+            }
+            "escape" => self.dismiss_signature_help(cx),
+            _ => return false,
+        }
+        true
+    }
+
+    fn render_signature_help(&self, cx: &mut Context<Self>) -> Option<impl IntoElement> {
+        let current_path = self.current_file_path()?;
+        let session = self.signature_help.as_ref().filter(|session| session.path == current_path)?;
+        let signatures = session.signatures.as_ref()?;
+        let signature = signatures.active();
+        let count = signatures.signatures.len();
+
+        let bold = HighlightStyle {
+            font_weight: Some(FontWeight::BOLD),
+            ..Default::default()
+        };
+        let label = StyledText::new(signature.label.clone())
+            .with_highlights(signatures.active_parameter_range().map(|range| (range, bold)));
+
+        Some(
+            div().absolute().bottom_10().left_4().right_4().child(
+                v_flex()
+                    .max_w(px(640.))
+                    .p_2()
+                    .gap_1()
+                    .bg(cx.theme().popover)
+                    .border_1()
+                    .border_color(cx.theme().border)
+                    .rounded(cx.theme().radius)
+                    .shadow_lg()
+                    .child(
+                        h_flex()
+                            .gap_2()
+                            .when(count > 1, |this| {
+                                this.child(
+                                    div()
+                                        .text_xs()
+                                        .text_color(cx.theme().muted_foreground)
+                                        .child(format!("{}/{} ↑↓", signatures.active_signature + 1, count)),
+                                )
+                            })
+                            .child(div().text_sm().font_family("JetBrains Mono").child(label)),
+                    )
+                    .children(signature.documentation.clone().map(|documentation| {
+                        div()
+                            .text_xs()
+                            .text_color(cx.theme().muted_foreground)
+                            .child(documentation)
+                    })),
+            ),
+        )
+    }
+
+    /// Open the find bar on the current file, with the replace row when `replace` is set
+    pub fn show_find_bar(&mut self, replace: bool, window: &mut Window, cx: &mut Context<Self>) {
+        self.find_bar_visible = true;
+        let target = self.current_input_state();
+        self.find_bar.update(cx, |find_bar, cx| {
+            find_bar.set_target(target, cx);
+            find_bar.show(replace, window, cx);
+        });
+        cx.notify();
+    }
+
+    fn current_input_state(&self) -> Option<Entity<InputState>> {
+        self.current_file_index
+            .and_then(|index| self.open_files.get(index))
+            .map(|file| file.input_state.clone())
+    }
+
+    /// Follow an input's cursor (for extra cursors and folds) and its fold arrow clicks
+    fn watch_cursor_and_folds(&mut self, input_state: &Entity<InputState>, window: &mut Window, cx: &mut Context<Self>) {
+        self.subscriptions
+            .push(cx.observe_in(input_state, window, |this, input_state, window, cx| {
+                this.highlight_current_lines(&input_state, window, cx);
+            }));
+        self.subscriptions
+            .push(cx.observe(input_state, |this, input_state, cx| {
+                this.end_undo_step_on_cursor_move(&input_state, cx);
+                this.record_cursor_jump(&input_state, cx);
+                this.report_cursor_move(&input_state, cx);
+                this.replay_on_extra_cursors(&input_state, cx);
+                this.keep_cursor_out_of_folds(&input_state, cx);
+                this.highlight_brackets(&input_state, cx);
+                this.update_breadcrumb(&input_state, cx);
+                this.schedule_code_actions(&input_state, cx);
+                this.schedule_occurrence_highlights(&input_state, cx);
+                if let Some(index) = this.open_files.iter().position(|file| file.input_state == input_state) {
+                    this.update_signature_help(index, false, cx);
+                }
+            }));
+        self.subscriptions.push(cx.subscribe(
+            input_state,
+            |this, input_state, event: &InputEvent, cx| {
+                if let InputEvent::ToggleFold { line } = event {
+                    this.toggle_fold(&input_state, *line, cx);
+                }
+            },
+        ));
+    }
+
+    /// Recompute a file's foldable ranges and indent guides once typing pauses
+    ///
+    /// Uses `textDocument/foldingRange` when the file's server supports it and
+    /// the brace/indent heuristics otherwise (or when the request fails).
+    fn schedule_fold_refresh(&mut self, index: usize, cx: &mut Context<Self>) {
+        let Some(file) = self.open_files.get(index) else {
+            return;
+        };
+        let path = file.path.clone();
+        let indent_style = file.indent_style;
+        let toml = is_toml(&path);
+        let server = file.language_server.clone().filter(|server| {
+            server
+                .capabilities()
+                .is_some_and(|caps| caps.folding_range_provider.is_some())
+        });
+
+        let task = cx.spawn({
+            let path = path.clone();
+            async move |this, cx| {
+                cx.background_executor().timer(FOLD_REFRESH_DELAY).await;
+                let Ok(Some((text, version))) = this.update(cx, |this, cx| {
+                    this.open_files
+                        .iter()
+                        .find(|file| file.path == path)
+                        .map(|file| (file.input_state.read(cx).value().to_string(), file.version))
+                }) else {
+                    return;
+                };
+                let guides = cx
+                    .background_spawn({
+                        let text = text.clone();
+                        async move { indent_guides(&text, indent_style) }
+                    })
+                    .await;
+
+                let mut ranges = None;
+                if let Some((server, uri)) =
+                    server.and_then(|server| Some((server, super::lsp::path_to_uri(&path)?)))
+                {
+                    let request = server.request::<lsp_types::request::FoldingRangeRequest>(
+                        lsp_types::FoldingRangeParams {
+                            text_document: lsp_types::TextDocumentIdentifier { uri },
+                            work_done_progress_params: Default::default(),
+                            partial_result_params: Default::default(),
+                        },
+                    );
+                    match request.response().await {
+                        Ok(result) => ranges = Some(fold_ranges_from_lsp(result.unwrap_or_default())),
+                        Err(e) => tracing::debug!("⚠️  Folding ranges unavailable, using heuristics: {:#}", e),
+                    }
+                }
+                let ranges = match ranges {
+                    Some(ranges) => ranges,
+                    None if toml => cx.background_spawn(async move { toml_fold_ranges(&text) }).await,
+                    None => {
+                        cx.background_spawn(async move { heuristic_fold_ranges(&text) })
+                            .await
+                    }
+                };
+
+                this.update(cx, |this, cx| {
+                    this.fold_refresh_tasks.remove(&path);
+                    let Some(file) = this.open_files.iter_mut().find(|file| file.path == path) else {
+                        return;
+                    };
+                    // A newer edit has scheduled another refresh
+                    if file.version != version {
+                        return;
+                    }
+                    file.folds.set_foldable(ranges);
+                    show_folds(&file.input_state, &file.folds, cx);
+                    file.input_state.update(cx, |state, cx| {
+                        state.set_indent_guides(guides);
+                        cx.notify();
+                    });
+                })
+                .ok();
+            }
+        });
+        self.fold_refresh_tasks.insert(path, task);
+    }
+
+    /// Request a file's document symbols for the breadcrumbs once typing pauses
+    ///
+    /// TOML files without a server get their tables and keys from the
+    /// built-in scan instead.
+    fn schedule_symbol_refresh(&mut self, index: usize, cx: &mut Context<Self>) {
+        let Some(file) = self.open_files.get(index) else {
+            return;
+        };
+        let path = file.path.clone();
+        let server = file.language_server.clone().filter(|server| {
+            server
+                .capabilities()
+                .is_some_and(|caps| caps.document_symbol_provider.is_some())
+        });
+        let server = match server.and_then(|server| Some((server, super::lsp::path_to_uri(&path)?))) {
+            Some(server) => Some(server),
+            None if is_toml(&path) && file.language_server.is_none() => None,
+            None => return,
+        };
+
+        let task = cx.spawn({
+            let path = path.clone();
+            async move |this, cx| {
+                cx.background_executor().timer(SYMBOL_REFRESH_DELAY).await;
+                let Ok(Some((version, text))) = this.update(cx, |this, cx| {
+                    this.open_files
+                        .iter()
+                        .find(|file| file.path == path)
+                        .map(|file| (file.version, file.input_state.read(cx).value()))
+                }) else {
+                    return;
+                };
+                let symbols = match server {
+                    Some((server, uri)) => {
+                        let request = server.request::<lsp_types::request::DocumentSymbolRequest>(
+                            lsp_types::DocumentSymbolParams {
+                                text_document: lsp_types::TextDocumentIdentifier { uri },
+                                work_done_progress_params: Default::default(),
+                                partial_result_params: Default::default(),
+                            },
+                        );
+                        match request.response().await {
+                            Ok(response) => response.map(super::lsp::symbol_tree).unwrap_or_default(),
+                            Err(e) => {
+                                tracing::debug!("⚠️  Document symbols unavailable: {:#}", e);
+                                return;
+                            }
+                        }
+                    }
+                    None => cx.background_spawn(async move { toml_outline(&text) }).await,
+                };
+
+                this.update(cx, |this, cx| {
+                    this.symbol_refresh_tasks.remove(&path);
+                    let Some(file) = this.open_files.iter_mut().find(|file| file.path == path) else {
+                        return;
+                    };
+                    // A newer edit has scheduled another refresh
+                    if file.version != version {
+                        return;
+                    }
+                    file.symbols = symbols;
+                    let input_state = file.input_state.clone();
+                    this.update_breadcrumb(&input_state, cx);
+                    cx.notify();
+                })
+                .ok();
+            }
+        });
+        self.symbol_refresh_tasks.insert(path, task);
+    }
+
+    /// Current file with its document symbols and the index path of the ones around the cursor
+    pub fn current_outline(&self) -> Option<(&PathBuf, &[SymbolNode], &[usize])> {
+        let file = self.current_file_index.and_then(|index| self.open_files.get(index))?;
+        Some((&file.path, &file.symbols, &file.breadcrumb_path))
+    }
+
+    /// Follow the cursor with the breadcrumbs' symbol path
+    fn update_breadcrumb(&mut self, input_state: &Entity<InputState>, cx: &mut Context<Self>) {
+        let Some(file) = self.open_files.iter_mut().find(|file| &file.input_state == input_state) else {
+            return;
+        };
+        let state = input_state.read(cx);
+        let position = super::lsp::offset_to_position(&state.value(), state.cursor());
+        let path = super::lsp::symbol_path(&file.symbols, position);
+        if file.breadcrumb_path != path {
+            file.breadcrumb_path = path;
+            self.breadcrumb_menu = None;
+            cx.notify();
+        }
+    }
+
+    /// Move the cursor to a symbol picked from a breadcrumb's dropdown
+    fn pick_breadcrumb_symbol(&mut self, depth: usize, ix: usize, cx: &mut Context<Self>) {
+        self.breadcrumb_menu = None;
+        let Some(file) = self.current_file_index.and_then(|index| self.open_files.get(index)) else {
+            return;
+        };
+        let siblings = super::lsp::symbols_at(&file.symbols, &file.breadcrumb_path[..depth.min(file.breadcrumb_path.len())]);
+        if let Some(symbol) = siblings.get(ix) {
+            let position = symbol.selection_start;
+            self.navigate_to(file.path.clone(), position.line, position.character, cx);
+        }
+        cx.notify();
+    }
+
+    /// File path and the symbols around the cursor, each symbol opening a
+    /// list of its siblings
+    fn render_breadcrumbs(&self, cx: &mut Context<Self>) -> Option<impl IntoElement> {
+        let file = self.current_file_index.and_then(|index| self.open_files.get(index))?;
+        // Relative to the workspace the file's server was started for
+        let display_path = file
+            .language_server
+            .as_ref()
+            .and_then(|server| file.path.strip_prefix(server.root()).ok())
+            .unwrap_or(&file.path);
+        let separator = || div().text_color(cx.theme().muted_foreground).child("›");
+
+        let mut segments: Vec<AnyElement> = Vec::new();
+        for (ix, component) in display_path.components().enumerate() {
+            if ix > 0 {
+                segments.push(separator().into_any_element());
+            }
+            segments.push(
+                div()
+                    .text_color(cx.theme().muted_foreground)
+                    .child(component.as_os_str().to_string_lossy().to_string())
+                    .into_any_element(),
+            );
+        }
+
+        let mut level: &[SymbolNode] = &file.symbols;
+        for (depth, &ix) in file.breadcrumb_path.iter().enumerate() {
+            let Some(symbol) = level.get(ix) else {
+                break;
+            };
+            segments.push(separator().into_any_element());
+            segments.push(
+                Button::new(("breadcrumb", depth))
+                    .label(symbol_label(symbol))
+                    .ghost()
+                    .xsmall()
+                    .selected(self.breadcrumb_menu == Some(depth))
+                    .on_click(cx.listener(move |this, _, _window, cx| {
+                        this.breadcrumb_menu = if this.breadcrumb_menu == Some(depth) { None } else { Some(depth) };
+                        cx.notify();
+                    }))
+                    .into_any_element(),
+            );
+            level = &symbol.children;
+        }
+
+        let dropdown = self.breadcrumb_menu.map(|depth| {
+            let siblings = super::lsp::symbols_at(&file.symbols, &file.breadcrumb_path[..depth.min(file.breadcrumb_path.len())]);
+            let current = file.breadcrumb_path.get(depth).copied();
+            // Deferred so the list paints over the editor below
+            deferred(div().absolute().top(relative(1.)).left_2().child(
+                v_flex()
+                    .w(px(320.))
+                    .max_h(px(320.))
+                    .overflow_y_scroll()
+                    .p_1()
+                    .gap_0p5()
+                    .bg(cx.theme().popover)
+                    .border_1()
+                    .border_color(cx.theme().border)
+                    .rounded(cx.theme().radius)
+                    .shadow_lg()
+                    .children(siblings.iter().enumerate().map(|(ix, symbol)| {
+                        Button::new(("breadcrumb_symbol", ix))
+                            .label(symbol_label(symbol))
+                            .ghost()
+                            .small()
+                            .w_full()
+                            .selected(current == Some(ix))
+                            .on_click(cx.listener(move |this, _, _window, cx| {
+                                this.pick_breadcrumb_symbol(depth, ix, cx);
+                            }))
+                    })),
+            ))
+        });
+
+        Some(
+            h_flex()
+                .id("breadcrumbs")
+                .relative()
+                .w_full()
+                .px_2()
+                .py_0p5()
+                .gap_1()
+                .items_center()
+                .text_xs()
+                .font_family("JetBrains Mono")
+                .bg(cx.theme().background)
+                .border_b_1()
+                .border_color(cx.theme().border)
+                .children(segments)
+                .children(dropdown),
+        )
+    }
+
+    /// Fold or unfold the range starting at `line` (a fold arrow click)
+    fn toggle_fold(&mut self, input_state: &Entity<InputState>, line: u32, cx: &mut Context<Self>) {
+        let Some(file) = self.open_files.iter_mut().find(|file| &file.input_state == input_state) else {
+            return;
+        };
+        if file.folds.toggle(line) {
+            show_folds(input_state, &file.folds, cx);
+            cx.notify();
+        }
+    }
+
+    /// Fold or unfold every range in the current file
+    pub fn set_all_folded(&mut self, folded: bool, cx: &mut Context<Self>) {
+        let Some(file) = self.current_file_index.and_then(|index| self.open_files.get_mut(index)) else {
+            return;
+        };
+        if folded {
+            file.folds.fold_all();
+        } else {
+            file.folds.unfold_all();
+        }
+        show_folds(&file.input_state, &file.folds, cx);
+        cx.notify();
+    }
+
+    /// Move a cursor that landed on a hidden line to the nearest visible one
+    fn keep_cursor_out_of_folds(&mut self, input_state: &Entity<InputState>, cx: &mut Context<Self>) {
+        let Some(file) = self.open_files.iter_mut().find(|file| &file.input_state == input_state) else {
+            return;
+        };
+        let state = input_state.read(cx);
+        let position = super::lsp::offset_to_position(&state.value(), state.cursor());
+        let from = std::mem::replace(&mut file.cursor_line, position.line);
+        if !file.folds.is_hidden(position.line) {
+            return;
+        }
+
+        let line = file.folds.visible_line(from, position.line);
+        file.cursor_line = line;
+        self.pending_cursor_moves
+            .push((input_state.clone(), ui::input::Position::new(line, position.character)));
+        cx.notify();
+    }
+
+    pub fn inlay_hints_enabled(&self) -> bool {
+        self.inlay_hints_enabled
+    }
+
+    /// Show or hide inlay hints in every open file
+    pub fn set_inlay_hints_enabled(&mut self, enabled: bool, cx: &mut Context<Self>) {
+        self.inlay_hints_enabled = enabled;
+        self.inlay_hint_tasks.clear();
+        for file in &mut self.open_files {
+            // Panels ask for hints again as they render
+            file.inlay_hint_request = None;
+            if !enabled {
+                file.inlay_hints.lock().clear();
+                show_inlay_hints(&file.input_state, &[], cx);
+            }
+        }
+        cx.notify();
+    }
+
+    /// Highlight the lines around `visible` in a file too large to parse
+    /// whole, unless they already are for its current text, and lex the rest
+    /// of it in the background
+    pub fn ensure_visible_highlights(&mut self, path: &Path, visible: Range<u32>, cx: &mut Context<Self>) {
+        let Some(file) = self.open_files.iter_mut().find(|file| file.path == path) else {
+            return;
+        };
+        let Some(highlighter) = &mut file.lazy_highlighter else {
+            return;
+        };
+        let version = file.version;
+        let covered = file.lazy_highlight_request.as_ref().is_some_and(|(highlighted, lines)| {
+            *highlighted == version && lines.start <= visible.start && visible.end <= lines.end
+        });
+        if covered || visible.is_empty() {
+            return;
+        }
+        let lines = viewport::overscanned(visible, file.synced.line_count());
+        let highlights = highlighter.highlights(&file.synced, lines.clone());
+        let complete = highlighter.is_complete(&file.synced);
+        file.lazy_highlight_request = Some((version, lines));
+        show_lazy_highlights(&file.input_state, highlights, cx);
+        if !complete {
+            self.highlight_in_background(path, cx);
+        }
+    }
+
+    /// Lex a lazily highlighted file down to its end on the background
+    /// executor, a chunk at a time, recoloring the lines in view once the
+    /// lines above them are known
+    fn highlight_in_background(&mut self, path: &Path, cx: &mut Context<Self>) {
+        if self.lazy_highlight_tasks.contains_key(path) {
+            return;
+        }
+        let path = path.to_path_buf();
+        let task = cx.spawn({
+            let path = path.clone();
+            async move |this, cx| loop {
+                let Ok(Some((buffer, version, (start, in_block_comment)))) = this.update(cx, |this, _cx| {
+                    let file = this.open_files.iter().find(|file| file.path == path)?;
+                    let highlighter = file.lazy_highlighter.as_ref()?;
+                    (!highlighter.is_complete(&file.synced))
+                        .then(|| (file.synced.clone(), file.version, highlighter.resume_point()))
+                }) else {
+                    this.update(cx, |this, _cx| this.lazy_highlight_tasks.remove(&path)).ok();
+                    return;
+                };
+                let chunk = cx
+                    .background_spawn(async move { lex_chunk(&buffer, start, in_block_comment, HIGHLIGHT_CHUNK_LINES) })
+                    .await;
+                this.update(cx, |this, cx| {
+                    // Lexed from text that has changed since; the next chunk starts over from the edit
+                    let Some(file) = this.open_files.iter_mut().find(|file| file.path == path && file.version == version)
+                    else {
+                        return;
+                    };
+                    let Some(highlighter) = &mut file.lazy_highlighter else {
+                        return;
+                    };
+                    let merged = highlighter.merge(chunk);
+                    if let Some((_, lines)) = &file.lazy_highlight_request {
+                        if lines.start < merged.end && merged.start < lines.end {
+                            let highlights = highlighter.highlights(&file.synced, lines.clone());
+                            show_lazy_highlights(&file.input_state, highlights, cx);
+                        }
+                    }
+                })
+                .ok();
+            }
+        });
+        self.lazy_highlight_tasks.insert(path, task);
+    }
+
+    /// Request inlay hints for the lines around `visible` in a file unless they
+    /// are known for its current text (called by its panel as it renders, so
+    /// scrolling and typing both lead here)
+    pub fn ensure_inlay_hints(&mut self, path: &Path, visible: Range<u32>, cx: &mut Context<Self>) {
+        if !self.inlay_hints_enabled || visible.is_empty() {
+            return;
+        }
+        let Some(file) = self.open_files.iter_mut().find(|file| file.path == path) else {
+            return;
+        };
+        let Some(server) = file
+            .language_server
+            .clone()
+            .filter(|server| server.capabilities().is_some_and(|caps| super::lsp::provides_inlay_hints(&caps)))
+        else {
+            return;
+        };
+        let version = file.version;
+        let covered = file.inlay_hint_request.as_ref().is_some_and(|(requested, lines)| {
+            *requested == version && lines.start <= visible.start && visible.end <= lines.end
+        });
+        if covered {
+            return;
+        }
+        let lines = visible.start.saturating_sub(INLAY_HINT_MARGIN)..visible.end + INLAY_HINT_MARGIN;
+        file.inlay_hint_request = Some((version, lines.clone()));
+
+        let path = path.to_path_buf();
+        let task = cx.spawn({
+            let path = path.clone();
+            async move |this, cx| {
+                cx.background_executor().timer(INLAY_HINT_DELAY).await;
+                let Ok(Some((request, text))) = this.update(cx, |this, cx| {
+                    let file = this.open_files.iter().find(|file| file.path == path && file.version == version)?;
+                    let text = file.input_state.read(cx).value().to_string();
+                    let start = super::lsp::position_to_offset(&text, lsp_types::Position::new(lines.start, 0));
+                    let end = super::lsp::position_to_offset(&text, lsp_types::Position::new(lines.end, 0));
+                    let params = super::lsp::inlay_hint_params(&path, &text, start..end)?;
+                    Some((server.request::<lsp_types::request::InlayHintRequest>(params), text))
+                }) else {
+                    return;
+                };
+                let response = request.response().await;
+                this.update(cx, |this, cx| {
+                    let Some(file) = this.open_files.iter_mut().find(|file| file.path == path) else {
+                        return;
+                    };
+                    // The text changed meanwhile; hints for the new text are on their way
+                    if file.version != version {
+                        return;
+                    }
+                    let hints = match response {
+                        Ok(hints) => super::lsp::inlay_hint_items(&text, hints.unwrap_or_default()),
+                        Err(e) => {
+                            tracing::error!("⚠️  Inlay hint request failed: {:#}", e);
+                            return;
+                        }
+                    };
+                    show_inlay_hints(&file.input_state, &hints, cx);
+                    *file.inlay_hints.lock() = hints;
+                })
+                .ok();
+            }
+        });
+        self.inlay_hint_tasks.insert(path, task);
+    }
+
+    /// Request semantic tokens for a file unless they are known for its current
+    /// text, as a delta against the last result when the server offers one.
+    /// Files whose server has no semantic tokens keep the lexer's colors alone.
+    pub fn ensure_semantic_tokens(&mut self, path: &Path, cx: &mut Context<Self>) {
+        let Some(file) = self.open_files.iter_mut().find(|file| file.path == path) else {
+            return;
+        };
+        let Some((server, support)) = file.language_server.clone().and_then(|server| {
+            let support = super::lsp::semantic_tokens_support(&server.capabilities()?)?;
+            Some((server, support))
+        }) else {
+            return;
+        };
+        let version = file.version;
+        if file.semantic_tokens_version == Some(version) {
+            return;
+        }
+        file.semantic_tokens_version = Some(version);
+        // Right away for a file just opened, after a pause while typing
+        let delay = if file.semantic_tokens.data.is_empty() { Duration::ZERO } else { SEMANTIC_TOKENS_DELAY };
+
+        let path = path.to_path_buf();
+        let task = cx.spawn({
+            let path = path.clone();
+            async move |this, cx| {
+                cx.background_executor().timer(delay).await;
+                let Ok(Some((request, text))) = this.update(cx, |this, cx| {
+                    let file = this.open_files.iter().find(|file| file.path == path && file.version == version)?;
+                    let text = file.input_state.read(cx).value().to_string();
+                    let request = match file.semantic_tokens.result_id.clone().filter(|_| support.delta) {
+                        Some(previous) => {
+                            let params = super::lsp::semantic_tokens_delta_params(&path, previous)?;
+                            server.request::<lsp_types::request::SemanticTokensFullDeltaRequest>(params)
+                        }
+                        // Read like a delta response, whose `Tokens` case is a full result
+                        None => {
+                            let params = super::lsp::semantic_tokens_params(&path)?;
+                            server.request_raw(
+                                <lsp_types::request::SemanticTokensFullRequest as lsp_types::request::Request>::METHOD,
+                                serde_json::to_value(params).ok()?,
+                            )
+                        }
+                    };
+                    Some((request, text))
+                }) else {
+                    return;
+                };
+                let response = request.response().await;
+                this.update(cx, |this, cx| {
+                    let Some(file) = this.open_files.iter_mut().find(|file| file.path == path) else {
+                        return;
+                    };
+                    // The text changed meanwhile; tokens for the new text are on their way
+                    if file.version != version {
+                        return;
+                    }
+                    let tokens = &mut file.semantic_tokens;
+                    match response {
+                        Ok(Some(lsp_types::SemanticTokensFullDeltaResult::Tokens(result))) => {
+                            tokens.result_id = result.result_id;
+                            tokens.data = result.data;
+                        }
+                        Ok(Some(lsp_types::SemanticTokensFullDeltaResult::TokensDelta(delta))) => {
+                            tokens.result_id = delta.result_id;
+                            tokens.data = super::lsp::apply_semantic_token_edits(&tokens.data, delta.edits);
+                        }
+                        Ok(Some(lsp_types::SemanticTokensFullDeltaResult::PartialTokensDelta { edits })) => {
+                            tokens.result_id = None;
+                            tokens.data = super::lsp::apply_semantic_token_edits(&tokens.data, edits);
+                        }
+                        Ok(None) => *tokens = SemanticTokenState::default(),
+                        Err(e) => {
+                            // Start over with a full request next time
+                            tracing::error!("⚠️  Semantic tokens request failed: {:#}", e);
+                            tokens.result_id = None;
+                            return;
+                        }
+                    }
+                    tokens.highlights = super::lsp::semantic_highlights(&text, &tokens.data, &support.legend);
+                    show_semantic_highlights(&file.input_state, &tokens.highlights, cx);
+                })
+                .ok();
             }
-        );
+        });
+        self.semantic_token_tasks.insert(path, task);
+    }
 
-        // Warn user about very large files
-        if lines_count > 50_000 {
-            tracing::debug!(
-                "⚠️  Large file detected ({} lines). Some features may be disabled for performance:",
-                lines_count
-            );
-            tracing::debug!("   - Syntax highlighting disabled");
-            tracing::debug!("   - Soft wrap disabled");
-        } else if lines_count > 10_000 {
-            tracing::debug!(
-                "ℹ️  Large file ({} lines). Performance optimizations enabled:",
-                lines_count
+    pub fn highlight_current_line(&self) -> bool {
+        self.highlight_current_line
+    }
+
+    /// Turn the current line's highlight and bold line number on or off in every open file
+    pub fn set_highlight_current_line(&mut self, enabled: bool, window: &mut Window, cx: &mut Context<Self>) {
+        self.highlight_current_line = enabled;
+        let inputs: Vec<_> = self.open_files.iter().map(|file| file.input_state.clone()).collect();
+        for input_state in inputs {
+            self.highlight_current_lines(&input_state, window, cx);
+        }
+    }
+
+    /// Highlight the cursor's line, or every line a selection covers.
+    ///
+    /// The input paints the theme's active-line background beneath selections,
+    /// search matches and diagnostics, so those stay visible on top of it, and
+    /// draws the numbers of the lines in bold.
+    fn highlight_current_lines(&mut self, input_state: &Entity<InputState>, window: &mut Window, cx: &mut Context<Self>) {
+        let highlight_current_line = self.highlight_current_line;
+        let Some(file) = self.open_files.iter_mut().find(|file| &file.input_state == input_state) else {
+            return;
+        };
+        let lines = highlight_current_line.then(|| {
+            input_state.update(cx, |state, cx| {
+                let text = state.value();
+                let selection = state
+                    .selected_text_range(true, window, cx)
+                    .map(|selection| {
+                        super::lsp::utf16_to_offset(&text, selection.range.start)
+                            ..super::lsp::utf16_to_offset(&text, selection.range.end)
+                    })
+                    .unwrap_or_else(|| state.cursor()..state.cursor());
+                selected_lines(&text, selection)
+            })
+        });
+        if file.current_lines == lines {
+            return;
+        }
+        file.current_lines = lines.clone();
+        input_state.update(cx, |state, cx| {
+            state.set_current_lines(lines);
+            cx.notify();
+        });
+    }
+
+    /// Highlight the bracket pair next to the cursor and every unmatched bracket
+    fn highlight_brackets(&mut self, input_state: &Entity<InputState>, cx: &mut Context<Self>) {
+        let Some(file) = self.open_files.iter_mut().find(|file| &file.input_state == input_state) else {
+            return;
+        };
+        let state = input_state.read(cx);
+        let cursor = state.cursor();
+        let pairs = file_bracket_pairs(file, state.value());
+
+        let matched = pairs
+            .adjacent(cursor)
+            .map(|(bracket, partner)| vec![bracket..bracket + 1, partner..partner + 1])
+            .unwrap_or_default();
+        let unmatched = pairs.unmatched().iter().map(|&offset| offset..offset + 1).collect();
+        let highlights = (matched, unmatched);
+        if file.bracket_highlights == highlights {
+            return;
+        }
+        file.bracket_highlights = highlights.clone();
+        input_state.update(cx, |state, cx| {
+            state.set_bracket_highlights(highlights.0, highlights.1);
+            cx.notify();
+        });
+    }
+
+    /// Move the cursor to the bracket matching the one next to it, or else to
+    /// the closing bracket of the innermost pair around it
+    pub fn jump_to_matching_bracket(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(file) = self.current_file_index.and_then(|index| self.open_files.get_mut(index)) else {
+            return;
+        };
+        let input_state = file.input_state.clone();
+        let text = input_state.read(cx).value();
+        let cursor = input_state.read(cx).cursor();
+        let pairs = file_bracket_pairs(file, text.clone());
+
+        let target = match pairs.adjacent(cursor).or_else(|| pairs.enclosing(cursor).map(|(_, close)| (close, close))) {
+            Some((_, target)) => target,
+            None => return,
+        };
+        let position = super::lsp::offset_to_position(&text, target);
+        input_state.update(cx, |state, cx| {
+            state.set_cursor_position(
+                ui::input::Position::new(position.line, position.character),
+                window,
+                cx,
             );
-            tracing::debug!("   - Soft wrap disabled");
-            tracing::debug!("   - Virtual scrolling enabled");
+        });
+    }
+
+    /// Apply queued cursor moves (called from render where we have window access)
+    fn process_pending_cursor_moves(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        for (input_state, position) in std::mem::take(&mut self.pending_cursor_moves) {
+            input_state.update(cx, |state, cx| state.set_cursor_position(position, window, cx));
         }
+    }
 
-        // Create editor state with optimal settings for large files
-        let setup_start = Instant::now();
-        let input_state = cx.new(|cx| {
-            let mut state = InputState::new(window, cx)
-                .code_editor(language)
-                .line_number(true)
-                .minimap(true) // Enable VSCode-style minimap scrollbar
-                .tab_size(TabSize {
-                    tab_size: 4,
-                    hard_tabs: false,
-                })
-                // Disable soft wrap for large files for better performance
-                // Files with more than 5k lines or 500KB get no wrapping
-                .soft_wrap(lines_count < 5_000 && file_size < 500_000)
-                .soft_wrap(false);
+    /// Stop grouping typing into the last undo step once the cursor moves away from it
+    fn end_undo_step_on_cursor_move(&mut self, input_state: &Entity<InputState>, cx: &mut Context<Self>) {
+        let Some(file) = self.open_files.iter_mut().find(|file| &file.input_state == input_state) else {
+            return;
+        };
+        let state = input_state.read(cx);
+        // Edits are recorded when their change event is handled; only plain moves count here
+        if file.synced.matches(&state.value()) {
+            file.undo_history.cursor_moved(state.cursor());
+        }
+    }
 
-            // Set the content after creating the state
-            state.set_value(&content, window, cx);
-            state
+    /// Tell listeners where the current file's cursor went, before
+    /// `replay_on_extra_cursors` takes in the move
+    fn report_cursor_move(&mut self, input_state: &Entity<InputState>, cx: &mut Context<Self>) {
+        let Some(file) = self.current_file_index.and_then(|index| self.open_files.get(index)) else {
+            return;
+        };
+        let state = input_state.read(cx);
+        let cursor = state.cursor();
+        if &file.input_state != input_state || cursor == file.cursor_offset {
+            return;
+        }
+        let (line, column) = if file.synced.matches(&state.value()) {
+            let (line, column) = file.synced.line_column(cursor);
+            (line as usize, column as usize)
+        } else {
+            // Typed: the buffer catches up when the change is handled
+            let text = state.value();
+            let before = &text[..cursor.min(text.len())];
+            let line_start = before.rfind('\n').map_or(0, |ix| ix + 1);
+            (before.matches('\n').count(), before[line_start..].chars().count())
+        };
+        cx.emit(TextEditorEvent::CursorMoved {
+            path: file.path.clone(),
+            line: line + 1,
+            column: column + 1,
         });
+    }
 
-        // Set up autocomplete for the file with rust-analyzer support
-        let workspace_root = self.resolve_workspace_root_for_file(&path);
-        if let Some(analyzer) = self.rust_analyzer.clone() {
-            tracing::debug!("[LSP] open_file: rust_analyzer present, calling setup_autocomplete_for_file for {:?}",
-                path.file_name());
+    /// Mirror the input's latest edit or cursor movement at the extra cursors
+    fn replay_on_extra_cursors(&mut self, input_state: &Entity<InputState>, cx: &mut Context<Self>) {
+        let Some(file) = self.open_files.iter_mut().find(|file| &file.input_state == input_state) else {
+            return;
+        };
+        let state = input_state.read(cx);
+        let cursor = state.cursor();
+        if !file.multi_cursor.is_active() || file.placing_cursor {
+            file.cursor_offset = cursor;
+            return;
+        }
+        let text = state.value().to_string();
+
+        if text == file.multi_cursor_text {
+            if cursor != file.cursor_offset {
+                file.multi_cursor.replay_movement(&text, file.cursor_offset, cursor);
+                file.multi_cursor.remove_at(cursor);
+                show_extra_cursors(input_state, &file.multi_cursor, cx);
+            }
+        } else if file.pending_cursor_text.as_deref() == Some(text.as_str()) {
+            // Our own replayed edits landing
+            file.pending_cursor_text = None;
+            file.multi_cursor_text = text;
+        } else {
+            let old_text = std::mem::replace(&mut file.multi_cursor_text, text.clone());
+            let (start, old_end, _) = super::lsp::edit_bounds(&old_text, &text);
+            if file.cursor_offset < start || file.cursor_offset > old_end {
+                // Not typed at the cursor (undo, paste elsewhere, formatting): drop the extra cursors
+                file.multi_cursor.clear();
+            } else {
+                let replay = file.multi_cursor.replay_edit(&old_text, &text, file.cursor_offset);
+                let edits: Vec<lsp_types::TextEdit> = replay
+                    .edits
+                    .into_iter()
+                    .map(|(range, new_text)| lsp_types::TextEdit {
+                        range: super::lsp::offsets_to_range(&text, range),
+                        new_text,
+                    })
+                    .collect();
+                file.pending_cursor_text = Some(super::lsp::apply_text_edits(&text, edits.clone()));
+                file.multi_cursor.set(replay.selections);
+                self.pending_cursor_edits.push((input_state.clone(), edits));
+            }
+            show_extra_cursors(input_state, &file.multi_cursor, cx);
+            cx.notify();
+        }
+        file.cursor_offset = cursor;
+    }
+
+    /// Apply queued extra-cursor edits (called from render where we have window access)
+    fn process_pending_cursor_edits(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        for (input_state, edits) in std::mem::take(&mut self.pending_cursor_edits) {
+            apply_edits_to_input(&input_state, edits, window, cx);
+        }
+    }
+
+    /// Copy typed tab stops into their mirrors and select the first stop of
+    /// accepted snippet completions (called from render where we have window access)
+    fn process_pending_snippet_edits(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        for (input_state, edits) in std::mem::take(&mut self.pending_snippet_mirrors) {
+            let Some(file) = self.open_files.iter_mut().find(|file| file.input_state == input_state) else {
+                continue;
+            };
+            let Some(expected) = file.pending_snippet_text.clone() else {
+                continue;
+            };
+            let applied = input_state.update(cx, |state, cx| {
+                let text = state.value().to_string();
+                let mut new_text = text.clone();
+                for (range, new_text_part) in edits.iter().rev() {
+                    new_text.replace_range(range.clone(), new_text_part);
+                }
+                // Edited again before the mirrors caught up: the stops are stale
+                if new_text != expected {
+                    return false;
+                }
+                let cursor = state.cursor();
+                replace_ranges_keeping_selection(state, &text, &edits, cursor..cursor, false, window, cx);
+                true
+            });
+            if !applied {
+                file.snippet = None;
+                file.pending_snippet_text = None;
+            }
+        }
+        for (input_state, stop) in std::mem::take(&mut self.pending_snippet_selections) {
+            input_state.update(cx, |state, cx| state.set_selected_range(stop, window, cx));
+        }
+    }
+
+    /// Apply the edits accepted completions came with, keeping the cursor,
+    /// or a snippet's selected stop, on the completion
+    fn process_pending_additional_edits(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        for (input_state, edits) in std::mem::take(&mut self.pending_additional_edits) {
             input_state.update(cx, |state, cx| {
-                super::setup_autocomplete_for_file(
-                    state,
-                    path.clone(),
-                    workspace_root,
-                    analyzer,
-                    window,
-                    cx,
-                );
+                let text = state.value().to_string();
+                // Edited again in between: the edits no longer fit
+                if edits.iter().any(|(range, _)| text.get(range.clone()).is_none()) {
+                    return;
+                }
+                let cursor = state.cursor();
+                let selection = state
+                    .selected_text_range(true, window, cx)
+                    .map(|selection| {
+                        super::lsp::utf16_to_offset(&text, selection.range.start)
+                            ..super::lsp::utf16_to_offset(&text, selection.range.end)
+                    })
+                    .unwrap_or(cursor..cursor);
+                let select = !selection.is_empty();
+                replace_ranges_keeping_selection(state, &text, &edits, selection, select, window, cx);
             });
-        } else {
-            tracing::debug!(
-                "[LSP] open_file: rust_analyzer is NONE – LSP features will NOT work for {:?}",
-                path.file_name()
-            );
-            tracing::debug!("⚠️  rust-analyzer not available, completions will be limited");
         }
+    }
 
-        let setup_time = setup_start.elapsed();
-        tracing::debug!(
-            "⚡ Editor setup completed in {:.2}ms",
-            setup_time.as_secs_f64() * 1000.0
+    /// Add a cursor where the primary cursor was before an Alt+Click moves it
+    pub fn begin_add_cursor(&mut self, path: &PathBuf, cx: &mut Context<Self>) {
+        let Some(file) = self.open_files.iter_mut().find(|file| &file.path == path) else {
+            return;
+        };
+        if !file.multi_cursor.is_active() {
+            file.multi_cursor_text = file.input_state.read(cx).value().to_string();
+        }
+        file.multi_cursor.add(Selection::cursor(file.cursor_offset));
+        file.placing_cursor = true;
+    }
+
+    /// Finish an Alt+Click once the input has moved its cursor to the click,
+    /// or turn an Alt+drag's selection into a box
+    pub fn end_add_cursor(&mut self, path: &PathBuf, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(index) = self.open_files.iter().position(|file| &file.path == path) else {
+            return;
+        };
+        let file = &mut self.open_files[index];
+        if !file.placing_cursor {
+            return;
+        }
+        file.placing_cursor = false;
+        let dragged = file.input_state.update(cx, |state, cx| {
+            let text = state.value();
+            let selection = state.selected_text_range(true, window, cx)?;
+            let start = super::lsp::utf16_to_offset(&text, selection.range.start);
+            let end = super::lsp::utf16_to_offset(&text, selection.range.end);
+            let (anchor, head) = if selection.reversed { (end, start) } else { (start, end) };
+            (anchor != head).then(|| {
+                (super::lsp::offset_to_position(&text, anchor), super::lsp::offset_to_position(&text, head))
+            })
+        });
+        if let Some((anchor, head)) = dragged {
+            self.select_box(index, anchor, head, window, cx);
+            return;
+        }
+        file.cursor_offset = file.input_state.read(cx).cursor();
+        file.multi_cursor.remove_at(file.cursor_offset);
+        let input_state = file.input_state.clone();
+        show_extra_cursors(&input_state, &file.multi_cursor, cx);
+        cx.notify();
+    }
+
+    /// Grow the current file's column selection by `lines` and `columns` from its
+    /// moving corner, starting one at the cursor when there is none
+    pub fn extend_box_selection(&mut self, lines: i32, columns: i32, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(index) = self.current_file_index.filter(|&index| index < self.open_files.len()) else {
+            return;
+        };
+        let file = &self.open_files[index];
+        let state = file.input_state.read(cx);
+        let text = state.value();
+        let cursor = state.cursor();
+        let (anchor, head) = match file.box_selection {
+            Some(selection) if selection.cursor == cursor => (selection.anchor, selection.head),
+            _ => {
+                let position = super::lsp::offset_to_position(&text, cursor);
+                (position, position)
+            }
+        };
+        let last_line = text.matches('\n').count() as i64;
+        let head = lsp_types::Position::new(
+            (head.line as i64 + lines as i64).clamp(0, last_line) as u32,
+            (head.character as i64 + columns as i64).max(0) as u32,
         );
+        self.select_box(index, anchor, head, window, cx);
+    }
 
-        let open_file = OpenFile {
-            path: path.clone(),
-            input_state: input_state.clone(),
-            is_modified: false,
-            lines_count,
-            file_size,
-            version: 1,
-            render_as_markdown: is_markdown,
-            markdown_preview_cache: if is_markdown {
-                content.clone()
-            } else {
-                String::new()
-            },
-            last_markdown_render: if is_markdown {
-                Some(Instant::now())
-            } else {
-                None
-            },
-            pending_scroll_target: None,
+    /// Select the box from `anchor` to `head` as one selection per line: the
+    /// input's own on `head`'s line and secondary ones on the others
+    fn select_box(
+        &mut self,
+        index: usize,
+        anchor: lsp_types::Position,
+        head: lsp_types::Position,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let file = &mut self.open_files[index];
+        let text = file.input_state.read(cx).value().to_string();
+        let mut selections = box_selections(&text, anchor, head);
+        let primary_ix = if head.line >= anchor.line { selections.len() - 1 } else { 0 };
+        let primary = selections.remove(primary_ix);
+
+        let cursor = file.input_state.update(cx, |state, cx| {
+            state.set_selected_range(primary.range(), window, cx);
+            state.cursor()
+        });
+        // Set after the input moved so the move isn't replayed at the new cursors
+        file.multi_cursor.set(selections);
+        file.multi_cursor_text = text;
+        file.cursor_offset = cursor;
+        file.box_selection = Some(BoxSelection { anchor, head, cursor });
+        let input_state = file.input_state.clone();
+        show_extra_cursors(&input_state, &file.multi_cursor, cx);
+        cx.notify();
+    }
+
+    /// Grow the selection to the next enclosing syntax node: from the file's
+    /// language server when it supports selection ranges, otherwise by brackets,
+    /// words and lines
+    pub fn expand_selection(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(file) = self.current_file_index.and_then(|index| self.open_files.get_mut(index)) else {
+            return;
         };
+        let path = file.path.clone();
+        let version = file.version;
+        let (text, selection) = file.input_state.update(cx, |state, cx| {
+            let text = state.value();
+            let cursor = state.cursor();
+            let selection = state
+                .selected_text_range(true, window, cx)
+                .map(|selection| {
+                    super::lsp::utf16_to_offset(&text, selection.range.start)
+                        ..super::lsp::utf16_to_offset(&text, selection.range.end)
+                })
+                .unwrap_or(cursor..cursor);
+            (text, selection)
+        });
+        let heuristic = expand_heuristically(&text, &file_bracket_pairs(file, text.clone()), &selection);
 
-        self.open_files.push(open_file);
-        let new_index = self.open_files.len() - 1;
-        self.current_file_index = Some(new_index);
+        let server = file.language_server.clone().filter(|server| {
+            server
+                .capabilities()
+                .is_some_and(|caps| caps.selection_range_provider.is_some())
+        });
+        let Some((server, uri)) = server.and_then(|server| Some((server, super::lsp::path_to_uri(&path)?))) else {
+            self.grow_selection(&path, version, selection, heuristic, window, cx);
+            return;
+        };
+        let request = server.request::<lsp_types::request::SelectionRangeRequest>(lsp_types::SelectionRangeParams {
+            text_document: lsp_types::TextDocumentIdentifier { uri },
+            positions: vec![super::lsp::offset_to_position(&text, selection.start)],
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        });
+        cx.spawn_in(window, async move |this, cx| {
+            let next = match request.response().await {
+                Ok(ranges) => ranges
+                    .unwrap_or_default()
+                    .first()
+                    .and_then(|range| smallest_enclosing(super::lsp::selection_range_chain(&text, range), &selection)),
+                Err(e) => {
+                    tracing::debug!("⚠️  Selection ranges unavailable, using heuristics: {:#}", e);
+                    None
+                }
+            };
+            this.update_in(cx, |this, window, cx| {
+                this.grow_selection(&path, version, selection, next.or(heuristic), window, cx);
+            })
+            .ok();
+        })
+        .detach();
+    }
+
+    /// Select `to`, remembering `from` for Shrink Selection, unless the file changed meanwhile
+    fn grow_selection(
+        &mut self,
+        path: &Path,
+        version: i32,
+        from: Range<usize>,
+        to: Option<Range<usize>>,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) {
+        let Some(file) = self.open_files.iter_mut().find(|file| file.path == path) else {
+            return;
+        };
+        let Some(to) = to.filter(|_| file.version == version) else {
+            return;
+        };
+        file.selection_history.grow(version, from, to.clone());
+        file.input_state.update(cx, |state, cx| state.set_selected_range(to, window, cx));
+    }
+
+    /// Go back to the selection the last Expand Selection grew from
+    pub fn shrink_selection(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(file) = self.current_file_index.and_then(|index| self.open_files.get_mut(index)) else {
+            return;
+        };
+        let version = file.version;
+        let history = &mut file.selection_history;
+        file.input_state.update(cx, |state, cx| {
+            let text = state.value();
+            let cursor = state.cursor();
+            let selection = state
+                .selected_text_range(true, window, cx)
+                .map(|selection| {
+                    super::lsp::utf16_to_offset(&text, selection.range.start)
+                        ..super::lsp::utf16_to_offset(&text, selection.range.end)
+                })
+                .unwrap_or(cursor..cursor);
+            if let Some(previous) = history.shrink(version, &selection) {
+                state.set_selected_range(previous, window, cx);
+            }
+        });
+    }
+
+    /// Add a cursor at the next occurrence of the word (or selection) under the cursor
+    pub fn select_next_occurrence(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(file) = self.current_file_index.and_then(|index| self.open_files.get_mut(index)) else {
+            return;
+        };
+        let input_state = file.input_state.clone();
+        let (text, cursor, selection) = input_state.update(cx, |state, cx| {
+            let selection = state
+                .selected_text_range(true, window, cx)
+                .map(|selection| selection.range)
+                .filter(|range| !range.is_empty());
+            (state.value().to_string(), state.cursor(), selection)
+        });
+
+        // With a selection every occurrence is selected; otherwise the cursor keeps
+        // its position inside the word at each occurrence
+        let (needle_range, cursor_in_needle) = match selection {
+            Some(range) => (
+                super::lsp::utf16_to_offset(&text, range.start)..super::lsp::utf16_to_offset(&text, range.end),
+                None,
+            ),
+            None => {
+                let Some(word) = word_range_at(&text, cursor) else {
+                    return;
+                };
+                let relative = cursor - word.start;
+                (word, Some(relative))
+            }
+        };
+        let needle = &text[needle_range.clone()];
+
+        let mut taken: Vec<Range<usize>> = vec![needle_range.clone()];
+        taken.extend(file.multi_cursor.selections().iter().map(|selection| {
+            let start = match cursor_in_needle {
+                Some(relative) => selection.head.saturating_sub(relative),
+                None => selection.range().start,
+            };
+            start..start + needle.len()
+        }));
+        let after = taken.iter().map(|range| range.end).max().unwrap_or(needle_range.end);
+        let Some(next) = next_occurrence(&text, needle, after, &taken) else {
+            self.set_status_message("No more occurrences", cx);
+            return;
+        };
+
+        if !file.multi_cursor.is_active() {
+            file.multi_cursor_text = text.clone();
+        }
+        file.multi_cursor.add(match cursor_in_needle {
+            Some(relative) => Selection::cursor(next.start + relative),
+            None => Selection {
+                anchor: next.start,
+                head: next.end,
+            },
+        });
+        file.cursor_offset = cursor;
+        show_extra_cursors(&input_state, &file.multi_cursor, cx);
+        cx.notify();
+    }
+
+    /// Indentation for a file in `language`: what its `.editorconfig` says,
+    /// else what its content already uses when detection is on, otherwise
+    /// the style set for the language
+    fn indent_style_for(&self, language: &str, content: &str, editorconfig: &EditorConfig) -> IndentStyle {
+        let configured = self
+            .indent_styles
+            .get(language)
+            .copied()
+            .unwrap_or_else(|| IndentStyle::for_language(language));
+        if editorconfig.has_indentation() {
+            return editorconfig.indent_style(configured);
+        }
+        if !self.detect_indentation {
+            return configured;
+        }
+        detect(content, configured.width).unwrap_or(configured)
+    }
 
-        // Queue panel to be added on next render
-        self.pending_panels_to_add
-            .push((new_index, path.clone(), input_state.clone()));
+    pub fn wrap_mode(&self) -> WrapMode {
+        self.wrap_mode
+    }
+
+    /// Wrap long lines of every open file (too large ones excepted) as `mode` says.
+    ///
+    /// Wrapping is the input's own, so line numbers, cursor movement and
+    /// clicks follow logical lines.
+    pub fn set_wrap_mode(&mut self, mode: WrapMode, window: &mut Window, cx: &mut Context<Self>) {
+        self.wrap_mode = mode;
+        for file in &self.open_files {
+            let wrap = !mode.is_off() && wraps_well(file.lines_count, file.file_size);
+            file.input_state.update(cx, |state, cx| state.set_soft_wrap(wrap, window, cx));
+        }
+        cx.notify();
+    }
+
+    pub fn render_whitespace(&self) -> RenderWhitespace {
+        self.render_whitespace
+    }
+
+    /// Draw the whitespace `mode` picks in every open file
+    pub fn set_render_whitespace(&mut self, mode: RenderWhitespace, cx: &mut Context<Self>) {
+        self.render_whitespace = mode;
+        for index in 0..self.open_files.len() {
+            self.refresh_whitespace(index, cx);
+        }
+        cx.notify();
+    }
+
+    /// Remove whitespace at the end of lines whenever a file (other than Markdown,
+    /// where two trailing spaces break the line) is saved
+    pub fn set_trim_trailing_whitespace(&mut self, trim: bool) {
+        self.save_cleanup.trim_trailing_whitespace = trim;
+    }
+
+    /// End saved files with one line break: adding it if missing, and
+    /// dropping blank lines after it
+    pub fn set_final_newlines(&mut self, insert: bool, trim: bool) {
+        self.save_cleanup.insert_final_newline = insert;
+        self.save_cleanup.trim_final_newlines = trim;
+    }
 
-        // Create subscription for this file
-        let analyzer = self.rust_analyzer.clone();
-        tracing::debug!(
-            "📝 Creating change subscription for {:?}, rust_analyzer present: {}",
-            path.file_name(),
-            analyzer.is_some()
-        );
-        let subscription = cx.subscribe(&input_state, move |this: &mut TextEditor, input_state_entity: Entity<InputState>, event: &InputEvent, cx: &mut Context<TextEditor>| {
-            match event {
-                InputEvent::Change => {
-                    // Find which file this corresponds to
-                    if let Some(index) = this.open_files.iter().position(|f| f.input_state == input_state_entity) {
-                        if let Some(file) = this.open_files.get_mut(index) {
-                            file.is_modified = true;
-                            file.version += 1;
+    /// Ask before opening files larger than `threshold_mb` megabytes
+    pub fn set_large_file_threshold(&mut self, threshold_mb: u64) {
+        self.large_file_threshold_mb = threshold_mb;
+    }
 
-                            // Note: We no longer auto-update markdown preview here
-                            // User must click the refresh button to update preview
+    /// Save cleanup choices by language id, over the editor-wide ones
+    pub fn set_language_save_cleanup(&mut self, cleanup: BTreeMap<String, LanguageSaveCleanup>) {
+        self.language_save_cleanup = cleanup;
+    }
 
-                            // Notify rust-analyzer of the change
-                            if let Some(ref analyzer) = analyzer {
-                                let path = file.path.clone();
-                                let version = file.version;
-                                let content = file.input_state.read(cx).value().to_string();
+    /// Save files that format on save unformatted when formatting fails
+    pub fn set_save_when_formatting_fails(&mut self, save: bool) {
+        self.save_when_formatting_fails = save;
+    }
 
-                                tracing::debug!("📝 File changed: {:?} (version {}), notifying rust-analyzer", path.file_name(), version);
-                                analyzer.update(cx, |analyzer, _cx| {
-                                    if let Err(e) = analyzer.did_change_file(&path, &content, version) {
-                                        tracing::error!("⚠️  Failed to notify rust-analyzer of file change: {}", e);
-                                    } else {
-                                        if version % 10 == 0 {  // Log every 10th change to avoid spam
-                                            tracing::debug!("✓ Notified rust-analyzer of change (version {})", version);
-                                        }
-                                    }
-                                });
-                            } else {
-                                if file.version == 2 {  // Only log once to avoid spam
-                                    tracing::debug!("⚠️  No rust-analyzer available for didChange");
-                                }
-                            }
+    /// Save the current file in `encoding`, and keep it in it from then on
+    pub fn save_with_encoding(&mut self, encoding: FileEncoding, window: &mut Window, cx: &mut Context<Self>) -> bool {
+        let Some(file) = self.current_file_index.and_then(|index| self.open_files.get_mut(index)) else {
+            return false;
+        };
+        let previous = std::mem::replace(&mut file.encoding, encoding);
+        if self.save_current_file(window, cx) {
+            return true;
+        }
+        // Not saved, e.g. because the encoding lacks some character
+        if let Some(file) = self.current_file_index.and_then(|index| self.open_files.get_mut(index)) {
+            file.encoding = previous;
+        }
+        false
+    }
 
-                            cx.notify();
-                        }
-                    }
-                },
-                InputEvent::GoToDefinition { path, line, character } => {
-                    // Navigate to the definition - emit an event so it can be handled
-                    // by the parent where we have window access
-                    tracing::debug!("🎯 Received GoToDefinition event: {:?} at {}:{}", path, line, character);
+    /// Read the current file again as `encoding`, discarding unsaved changes
+    pub fn reopen_with_encoding(&mut self, encoding: FileEncoding, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(index) = self.current_file_index.filter(|&index| index < self.open_files.len()) else {
+            return;
+        };
+        self.open_files[index].encoding = encoding;
+        // Reloading warns about bytes the encoding can't read, over this
+        self.set_status_message(format!("Reopened as {}", encoding.label()), cx);
+        if !self.reload_file(index, window, cx) {
+            self.set_status_message("Couldn't read the file again", cx);
+        }
+    }
 
-                    // Emit the navigation event so it can be handled by parent components
-                    // that have window access
-                    let target_path = path.clone();
-                    let target_line = *line;
-                    let target_character = *character;
+    /// Write the current file with `ending` from the next save on
+    pub fn set_line_ending(&mut self, ending: LineEnding, cx: &mut Context<Self>) {
+        let Some(file) = self.current_file_index.and_then(|index| self.open_files.get_mut(index)) else {
+            return;
+        };
+        let converted = file.line_ending != ending || file.mixed_line_endings;
+        file.line_ending = ending;
+        file.mixed_line_endings = false;
+        // Mixed endings only go away on disk once saved
+        file.is_modified = converted || file.differs_from_saved(cx);
+        self.notify_dirty_state(cx);
+        self.set_status_message(format!("Line endings: {}", ending.label()), cx);
+    }
 
-                    // Store pending navigation in TextEditor
-                    this.pending_navigation = Some((target_path.clone(), target_line, target_character));
+    /// Remove whitespace at the end of every line of the current file
+    pub fn trim_trailing_whitespace(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(file) = self.current_file_index.and_then(|index| self.open_files.get(index)) else {
+            return;
+        };
+        let edits = trailing_whitespace_edits(&file.input_state.read(cx).value());
+        let count = edits.len();
+        apply_edits_to_input(&file.input_state, edits, window, cx);
+        self.set_status_message(format!("Trimmed trailing whitespace from {} lines", count), cx);
+    }
 
-                    cx.notify();
-                },
-                _ => {}
-            }
+    /// Mark the shown whitespace, trailing whitespace and invisible characters of a file.
+    ///
+    /// Invisible characters are marked even with whitespace hidden, since they
+    /// can change what code means without showing.
+    fn refresh_whitespace(&mut self, index: usize, cx: &mut Context<Self>) {
+        let Some(file) = self.open_files.get(index) else {
+            return;
+        };
+        let text = file.input_state.read(cx).value();
+        let markers = whitespace_markers(&text, self.render_whitespace);
+        let trailing = if self.render_whitespace.is_none() {
+            Vec::new()
+        } else {
+            trailing_whitespace(&text)
+        };
+        let invisible = invisible_characters(&text);
+        file.input_state.update(cx, |state, cx| {
+            state.set_whitespace_markers(markers, trailing);
+            state.set_invisible_highlights(invisible);
+            cx.notify();
         });
+    }
 
-        self.subscriptions.push(subscription);
+    /// Which server launches for each language, as configured in the settings
+    pub fn set_language_server_settings(&mut self, settings: BTreeMap<String, LanguageServerSettings>) {
+        self.language_servers.borrow_mut().set_settings(settings);
+    }
 
-        // Emit event so rust-analyzer can be notified
-        cx.emit(TextEditorEvent::FileOpened {
-            path: path.clone(),
-            content: content.clone(),
-        });
+    /// Schemas the JSON language server applies to files opened from now on
+    pub fn set_json_schemas(&mut self, schemas: Vec<JsonSchemaAssociation>) {
+        self.json_schemas = schemas;
+    }
 
-        // Log cache stats after opening
-        if let Some(index) = self.current_file_index {
-            if let Some(file) = self.open_files.get(index) {
-                let state = file.input_state.read(cx);
-                tracing::debug!(
-                    "📊 Line cache initialized - capacity: {} lines",
-                    state.line_cache().len()
-                );
+    /// Commands that run scripts, by file extension, in place of the built-in ones
+    pub fn set_run_commands(&mut self, commands: BTreeMap<String, String>) {
+        self.run_commands = commands;
+    }
 
-                // Log autocomplete configuration
-                if state.lsp.completion_provider.is_some() {
-                    tracing::debug!("✓ Autocomplete enabled with comprehensive provider");
-                } else {
-                    tracing::debug!("ℹ️  No autocomplete provider configured");
-                }
-            }
+    pub fn set_drop_templates(&mut self, templates: BTreeMap<String, String>) {
+        self.drop_templates = templates;
+    }
+
+    /// Insert references to `targets`, dropped from the explorer, at the
+    /// cursor of the open file at `path`
+    pub fn insert_file_references(&mut self, path: &Path, targets: &[PathBuf], window: &mut Window, cx: &mut Context<Self>) {
+        let Some(open_file) = self.open_files.iter().find(|file| file.path == path) else {
+            return;
+        };
+        if targets.is_empty() || open_file.read_only {
+            return;
         }
+        let language = self.get_language_from_extension(&open_file.path);
+        let text = super::file_references::file_references(
+            &language,
+            path,
+            targets,
+            &workspace_root(path),
+            self.drop_templates.get(&language).map(String::as_str),
+        );
+        open_file.input_state.update(cx, |state, cx| {
+            state.focus(window, cx);
+            state.replace_text_in_range(None, &text, window, cx);
+        });
+    }
 
-        cx.notify();
+    /// Set the indentation for files in `language` opened from now on
+    pub fn set_indent_style(&mut self, language: impl Into<String>, style: IndentStyle) {
+        self.indent_styles.insert(language.into(), style);
     }
 
-    fn resolve_workspace_root_for_file(&self, path: &PathBuf) -> Option<PathBuf> {
-        let candidate = if path.is_file() {
-            path.parent()
-                .map(|p| p.to_path_buf())
-                .unwrap_or_else(|| path.clone())
-        } else {
-            path.clone()
+    /// Choose whether files opened from now on keep the indentation found in them
+    pub fn set_detect_indentation(&mut self, detect: bool) {
+        self.detect_indentation = detect;
+    }
+
+    /// Enter in a YAML file, keeping its indentation and continuing lists.
+    ///
+    /// Returns true when the keystroke was handled here and must not reach the input.
+    pub fn handle_enter_key(
+        &mut self,
+        path: &PathBuf,
+        keystroke: &Keystroke,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> bool {
+        let modifiers = &keystroke.modifiers;
+        if keystroke.key != "enter" || modifiers.modified() || !is_yaml(path) {
+            return false;
+        }
+        let Some(file) = self.open_files.iter().find(|file| &file.path == path) else {
+            return false;
         };
+        if file.multi_cursor.is_active() {
+            return false;
+        }
 
-        let mut current = candidate.as_path();
-        loop {
-            if current.join("Cargo.toml").exists() {
-                return Some(current.to_path_buf());
-            }
-            if let Some(parent) = current.parent() {
-                current = parent;
-            } else {
-                break;
+        file.input_state.update(cx, |state, cx| {
+            let text = state.value().to_string();
+            let cursor = state.cursor();
+            let has_selection = state
+                .selected_text_range(true, window, cx)
+                .is_some_and(|selection| !selection.range.is_empty());
+            if has_selection {
+                return false;
             }
+            let (range, insert) = yaml::newline_edit(&text, cursor);
+            let mut new_text = text.clone();
+            new_text.replace_range(range.clone(), &insert);
+            replace_input_text(state, &text, &new_text, window, cx);
+            let position = super::lsp::offset_to_position(&new_text, range.start + insert.len());
+            state.set_cursor_position(ui::input::Position::new(position.line, position.character), window, cx);
+            true
+        })
+    }
+
+    /// Undo (Ctrl+Z) and redo (Ctrl+Shift+Z or Ctrl+Y) in the file at `path`,
+    /// a typed word or run at a time, putting back the selection from before.
+    /// Returns true when the keystroke was handled here and must not reach the input.
+    pub fn handle_undo_key(
+        &mut self,
+        path: &PathBuf,
+        keystroke: &Keystroke,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> bool {
+        let modifiers = &keystroke.modifiers;
+        if !modifiers.secondary() || modifiers.alt {
+            return false;
         }
+        let redo = match keystroke.key.as_str() {
+            "z" => modifiers.shift,
+            "y" if !modifiers.shift && !cfg!(target_os = "macos") => true,
+            _ => return false,
+        };
+        let Some(file) = self.open_files.iter_mut().find(|file| &file.path == path) else {
+            return false;
+        };
+        step_undo_history(file, redo, window, cx);
+        // Handled even with nothing to undo, so the input's own history stays out of it
+        true
+    }
 
-        Some(candidate)
+    /// Swallow keys that would edit a read-only file
+    pub fn handle_read_only_key(&mut self, path: &PathBuf, keystroke: &Keystroke, cx: &mut Context<Self>) -> bool {
+        if !self.open_files.iter().any(|file| &file.path == path && file.read_only) {
+            return false;
+        }
+        let modifiers = &keystroke.modifiers;
+        let edits = if modifiers.secondary() {
+            matches!(keystroke.key.as_str(), "v" | "x" | "z" | "y")
+        } else {
+            matches!(keystroke.key.as_str(), "enter" | "backspace" | "delete" | "tab")
+                || (!modifiers.control && !modifiers.alt && keystroke.key_char.is_some())
+        };
+        if edits {
+            self.set_status_message("This file is open read-only", cx);
+        }
+        edits
     }
 
-    fn get_language_from_extension(&self, path: &PathBuf) -> String {
-        match path.extension().and_then(|ext| ext.to_str()) {
-            Some("rs") => "rust".to_string(),
-            Some("js") => "javascript".to_string(),
-            Some("ts") => "typescript".to_string(),
-            Some("py") => "python".to_string(),
-            Some("toml") => "toml".to_string(),
-            Some("json") => "json".to_string(),
-            Some("md") => "markdown".to_string(),
-            Some("html") => "html".to_string(),
-            Some("css") => "css".to_string(),
-            Some("go") => "go".to_string(),
-            Some("rb") => "ruby".to_string(),
-            Some("sql") => "sql".to_string(),
-            Some("log") => "text".to_string(),
-            Some("txt") => "text".to_string(),
-            Some("yaml") | Some("yml") => "yaml".to_string(),
-            Some("xml") => "xml".to_string(),
-            Some("c") | Some("h") => "c".to_string(),
-            Some("cpp") | Some("hpp") | Some("cc") => "cpp".to_string(),
-            _ => "text".to_string(),
+    /// Turn Vim's modal editing on or off in every open file
+    pub fn set_vim_mode(&mut self, enabled: bool, cx: &mut Context<Self>) {
+        if enabled != self.vim.is_some() {
+            self.vim = enabled.then(Vim::default);
+            cx.notify();
         }
     }
 
-    /// Get performance info about the current file
-    fn get_current_file_performance(&self, cx: &App) -> Option<String> {
-        if !self.show_performance_stats {
-            return None;
+    /// A key in the file at `path` while Vim mode is on. Returns true when
+    /// Vim took the keystroke and it must not reach the input.
+    pub fn handle_vim_key(
+        &mut self,
+        path: &PathBuf,
+        keystroke: &Keystroke,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> bool {
+        let Some(vim) = &mut self.vim else {
+            return false;
+        };
+        let modifiers = &keystroke.modifiers;
+        let key = if modifiers.alt || modifiers.platform || modifiers.function {
+            return false;
+        } else if modifiers.control {
+            match keystroke.key.as_str() {
+                "r" => "ctrl-r".to_string(),
+                "[" => "escape".to_string(),
+                _ => return false,
+            }
+        } else {
+            match keystroke.key.as_str() {
+                key @ ("escape" | "enter" | "backspace" | "delete" | "tab" | "space") => key.to_string(),
+                key => keystroke.key_char.clone().unwrap_or_else(|| key.to_string()),
+            }
+        };
+        let Some(file) = self.open_files.iter_mut().find(|file| &file.path == path) else {
+            return false;
+        };
+        if file.multi_cursor.is_active() {
+            return false;
         }
 
-        let index = self.current_file_index?;
-        let open_file = self.open_files.get(index)?;
+        let action = file.input_state.update(cx, |state, cx| {
+            let text = state.value().to_string();
+            let action = vim.handle_key(&key, &text, state.cursor());
+            let move_to = |state: &mut InputState, text: &str, offset: usize, window: &mut Window, cx: &mut Context<InputState>| {
+                let position = super::lsp::offset_to_position(text, offset);
+                state.set_cursor_position(ui::input::Position::new(position.line, position.character), window, cx);
+            };
+            match &action {
+                VimAction::MoveTo(offset) => move_to(state, &text, *offset, window, cx),
+                VimAction::Select(range) => state.set_selected_range(range.clone(), window, cx),
+                VimAction::Edit { range, text: new_part, cursor } => {
+                    let mut new_text = text.clone();
+                    new_text.replace_range(range.clone(), new_part);
+                    replace_input_text(state, &text, &new_text, window, cx);
+                    move_to(state, &new_text, *cursor, window, cx);
+                }
+                _ => {}
+            }
+            action
+        });
 
-        let state = open_file.input_state.read(cx);
-        let cache_stats = state.line_cache().stats();
+        match action {
+            VimAction::PassThrough => return false,
+            VimAction::Undo => step_undo_history(file, false, window, cx),
+            VimAction::Redo => step_undo_history(file, true, window, cx),
+            VimAction::Ex(ExCommand::Write) => window.dispatch_action(Box::new(super::SaveCurrentFile), cx),
+            VimAction::Ex(ExCommand::Quit) => window.dispatch_action(Box::new(super::CloseCurrentFile), cx),
+            VimAction::Ex(ExCommand::WriteQuit) => {
+                window.dispatch_action(Box::new(super::SaveCurrentFile), cx);
+                window.dispatch_action(Box::new(super::CloseCurrentFile), cx);
+            }
+            VimAction::Ex(ExCommand::Substitute(substitute)) => {
+                let message = file.input_state.update(cx, |state, cx| {
+                    let text = state.value().to_string();
+                    match vim::substitute(&text, &substitute) {
+                        Ok((_, 0)) => format!("Pattern not found: {}", substitute.pattern),
+                        Ok((new_text, count)) => {
+                            replace_input_text(state, &text, &new_text, window, cx);
+                            match count {
+                                1 => "1 substitution".to_string(),
+                                count => format!("{} substitutions", count),
+                            }
+                        }
+                        Err(e) => format!("Invalid pattern: {}", e),
+                    }
+                });
+                self.set_status_message(message, cx);
+            }
+            VimAction::Error(message) => self.set_status_message(message, cx),
+            _ => {}
+        }
+        // The mode and the keys typed so far are in the status bar
+        cx.notify();
+        true
+    }
 
-        Some(format!(
-            "📊 Performance: {} lines | Cache: {:.1}% hit rate | {} cached lines | Memory: ~{} MB",
-            open_file.lines_count,
-            cache_stats.hit_rate() * 100.0,
-            state.line_cache().len(),
-            (state.line_cache().len() * 1024) / (1024 * 1024) // Rough estimate
-        ))
+    /// Snippets expanded by Tab, built-in ones and the user's
+    pub fn set_snippets(&mut self, snippets: Arc<SnippetLibrary>) {
+        self.snippets = snippets;
     }
 
-    pub fn close_file(&mut self, index: usize, _window: &mut Window, cx: &mut Context<Self>) {
-        if index < self.open_files.len() {
-            let file_path = self.open_files[index].path.clone();
-            self.open_files.remove(index);
+    /// Tab in the file at `path`: go to the next tab stop of the snippet being
+    /// filled in (Shift+Tab, the previous one), or expand the snippet whose
+    /// prefix is before the cursor, or in HTML and JSX an Emmet abbreviation.
+    ///
+    /// Returns true when the keystroke was handled here and must not reach the input.
+    pub fn handle_snippet_key(
+        &mut self,
+        path: &PathBuf,
+        keystroke: &Keystroke,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> bool {
+        let modifiers = &keystroke.modifiers;
+        if modifiers.control || modifiers.platform || modifiers.alt {
+            return false;
+        }
+        let language = self.get_language_from_extension(path);
+        let Some(file) = self.open_files.iter_mut().find(|file| &file.path == path) else {
+            return false;
+        };
+        let cursor = file.input_state.read(cx).cursor();
+        // Escape or a cursor gone elsewhere ends the snippet; Escape still reaches the input
+        if keystroke.key == "escape" || file.snippet.as_ref().is_some_and(|session| !session.contains(cursor)) {
+            file.snippet = None;
+        }
+        if keystroke.key != "tab" || file.multi_cursor.is_active() {
+            return false;
+        }
 
-            // Emit event so rust-analyzer can be notified
-            cx.emit(TextEditorEvent::FileClosed { path: file_path });
+        if let Some(session) = &mut file.snippet {
+            let stop = session.step(!modifiers.shift);
+            if session.at_end() {
+                file.snippet = None;
+            }
+            file.input_state.update(cx, |state, cx| state.set_selected_range(stop, window, cx));
+            return true;
+        }
+        if modifiers.shift {
+            return false;
+        }
 
-            // Adjust current file index
-            if let Some(current) = self.current_file_index {
-                if current == index {
-                    // Closed the current file
-                    if self.open_files.is_empty() {
-                        self.current_file_index = None;
-                    } else if index == self.open_files.len() {
-                        // Closed the last file, select the previous one
-                        self.current_file_index = Some(index.saturating_sub(1));
-                    } else {
-                        // Keep the same index (which now points to the next file)
-                        self.current_file_index = Some(index);
-                    }
-                } else if current > index {
-                    // Closed a file before the current one
-                    self.current_file_index = Some(current - 1);
-                }
+        let unit = file.indent_style.unit();
+        let snippets = self.snippets.clone();
+        // Emmet, and whether it writes JSX
+        let emmet = emmet::supports(path).then(|| emmet::is_jsx(path));
+        let expanded = file.input_state.update(cx, |state, cx| {
+            let has_selection = state
+                .selected_text_range(true, window, cx)
+                .is_some_and(|selection| !selection.range.is_empty());
+            if has_selection {
+                return None;
             }
+            let text = state.value().to_string();
+            let line_start = text[..cursor].rfind('\n').map_or(0, |ix| ix + 1);
+            let before_cursor = &text[line_start..cursor];
+            let (prefix_len, body) = match (snippets.find(&language, before_cursor), emmet) {
+                (Some(snippet), _) => (snippet.prefix.len(), snippet.body.clone()),
+                (None, Some(jsx)) => {
+                    let len = emmet::abbreviation_len(before_cursor);
+                    (len, emmet::expand(&before_cursor[before_cursor.len() - len..], jsx)?)
+                }
+                (None, None) => return None,
+            };
+            let indent: String = text[line_start..]
+                .chars()
+                .take_while(|ch| *ch == ' ' || *ch == '\t')
+                .collect();
+            let parsed = parse_snippet(&body, &indent, &unit);
+            let start = cursor - prefix_len;
+            let mut new_text = text.clone();
+            new_text.replace_range(start..cursor, &parsed.text);
+            replace_input_text(state, &text, &new_text, window, cx);
+            let session = SnippetSession::new(&parsed, start);
+            state.set_selected_range(session.current(), window, cx);
+            Some((session, new_text))
+        });
+        let Some((session, new_text)) = expanded else {
+            return false;
+        };
+        // A snippet that's only text is done once inserted
+        file.snippet = (!session.at_end()).then_some(session);
+        file.pending_snippet_text = Some(new_text);
+        true
+    }
 
-            cx.notify();
+    /// Tab and Shift+Tab in the file at `path`.
+    ///
+    /// Tab inserts one indent unit, or indents every line of a multi-line
+    /// selection; Shift+Tab outdents the current or selected lines.
+    /// Returns true when the keystroke was handled here and must not reach the input.
+    pub fn handle_indent_key(
+        &mut self,
+        path: &PathBuf,
+        keystroke: &Keystroke,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+    ) -> bool {
+        let modifiers = &keystroke.modifiers;
+        if keystroke.key != "tab" || modifiers.control || modifiers.platform || modifiers.alt {
+            return false;
         }
+        let Some(file) = self.open_files.iter().find(|file| &file.path == path) else {
+            return false;
+        };
+        let style = file.indent_style;
+
+        file.input_state.update(cx, |state, cx| {
+            let text = state.value().to_string();
+            let cursor = state.cursor();
+            let selection = state
+                .selected_text_range(true, window, cx)
+                .map(|selection| {
+                    super::lsp::utf16_to_offset(&text, selection.range.start)
+                        ..super::lsp::utf16_to_offset(&text, selection.range.end)
+                })
+                .unwrap_or(cursor..cursor);
+            let start = super::lsp::offset_to_position(&text, selection.start);
+            let end = super::lsp::offset_to_position(&text, selection.end);
+            let multi_line = start.line != end.line;
+
+            if !modifiers.shift && !multi_line {
+                let line_start = text[..selection.start].rfind('\n').map_or(0, |ix| ix + 1);
+                let column = text[line_start..selection.start].chars().count() as u32;
+                let mut new_text = text.clone();
+                new_text.replace_range(selection.clone(), &style.unit_at(column));
+                replace_input_text(state, &text, &new_text, window, cx);
+                return true;
+            }
+
+            // A selection ending at the start of a line leaves that line alone
+            let last_line = if multi_line && end.character == 0 { end.line } else { end.line + 1 };
+            let edits = if modifiers.shift {
+                outdent_lines(&text, start.line..last_line, style)
+            } else {
+                indent_lines(&text, start.line..last_line, style)
+            };
+            if edits.is_empty() {
+                return true;
+            }
+
+            replace_ranges_keeping_selection(state, &text, &edits, selection, multi_line, window, cx);
+            true
+        })
     }
 
-    fn set_active_file(&mut self, index: usize, _window: &mut Window, cx: &mut Context<Self>) {
-        if index < self.open_files.len() {
-            self.current_file_index = Some(index);
-            cx.notify();
-        }
+    /// Move the current file's selected lines (or the cursor's) up or down a line
+    pub fn move_lines(&mut self, up: bool, window: &mut Window, cx: &mut Context<Self>) {
+        self.edit_selected_lines(window, cx, |text, lines| move_lines(text, lines, up));
     }
 
-    pub fn save_current_file(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> bool {
-        if let Some(index) = self.current_file_index {
-            if let Some(open_file) = self.open_files.get_mut(index) {
-                // Get content from input state
-                let content = open_file.input_state.read(cx).value();
-
-                // Write to file
-                if let Ok(_) = fs::write(&open_file.path, content.as_str()) {
-                    open_file.is_modified = false;
-                    tracing::debug!("💾 File saved: {:?}", open_file.path);
-
-                    // Emit event so rust-analyzer can be notified
-                    cx.emit(TextEditorEvent::FileSaved {
-                        path: open_file.path.clone(),
-                        content: content.to_string(),
-                    });
+    /// Copy the current file's selected lines (or the cursor's) below them,
+    /// selecting the copy when `down`
+    pub fn duplicate_lines(&mut self, down: bool, window: &mut Window, cx: &mut Context<Self>) {
+        self.edit_selected_lines(window, cx, |text, lines| duplicate_lines(text, lines, down));
+    }
 
-                    cx.notify();
-                    return true;
-                }
+    /// Apply a whole-line edit to the lines of the selection in one undo step,
+    /// moving the selection by the edit's shift
+    fn edit_selected_lines(
+        &mut self,
+        window: &mut Window,
+        cx: &mut Context<Self>,
+        line_edit: impl FnOnce(&str, Range<u32>) -> Option<LineEdit>,
+    ) {
+        let Some(file) = self.current_file_index.and_then(|index| self.open_files.get(index)) else {
+            return;
+        };
+        file.input_state.update(cx, |state, cx| {
+            let text = state.value().to_string();
+            let cursor = state.cursor();
+            let selection = state
+                .selected_text_range(true, window, cx)
+                .map(|selection| {
+                    super::lsp::utf16_to_offset(&text, selection.range.start)
+                        ..super::lsp::utf16_to_offset(&text, selection.range.end)
+                })
+                .unwrap_or(cursor..cursor);
+            let Some(edit) = line_edit(&text, selected_lines(&text, selection.clone())) else {
+                return;
+            };
+            let mut new_text = text.clone();
+            new_text.replace_range(edit.range, &edit.new_text);
+            replace_input_text(state, &text, &new_text, window, cx);
+
+            let shifted = |offset: usize| (offset as isize + edit.shift) as usize;
+            if selection.is_empty() {
+                let position = super::lsp::offset_to_position(&new_text, shifted(cursor));
+                state.set_cursor_position(ui::input::Position::new(position.line, position.character), window, cx);
+            } else {
+                state.set_selected_range(shifted(selection.start)..shifted(selection.end), window, cx);
             }
-        }
-        false
+        });
     }
 
-    pub fn close_current_file(&mut self, _window: &mut Window, cx: &mut Context<Self>) {
-        if let Some(index) = self.current_file_index {
-            if let Some(open_file) = self.open_files.get(index) {
-                let path = open_file.path.clone();
-
-                // Emit event so rust-analyzer can be notified
-                cx.emit(TextEditorEvent::FileClosed { path: path.clone() });
+    /// Set the comment markers for files in `language`
+    pub fn set_comment_syntax(&mut self, language: impl Into<String>, syntax: CommentSyntax) {
+        self.comment_syntaxes.insert(language.into(), syntax);
+    }
 
-                tracing::debug!("❌ File closed: {:?}", path.file_name());
+    fn comment_syntax_for(&self, path: &PathBuf) -> CommentSyntax {
+        let language = self.get_language_from_extension(path);
+        self.comment_syntaxes
+            .get(&language)
+            .cloned()
+            .unwrap_or_else(|| CommentSyntax::for_language(&language))
+    }
 
-                // Remove the file from open files
-                self.open_files.remove(index);
+    /// Comment out the lines of the current file's selection (or the cursor's
+    /// line) with the language's line comment, or uncomment them
+    pub fn toggle_line_comment(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.toggle_comment(false, window, cx);
+    }
 
-                // Update current file index
-                if self.open_files.is_empty() {
-                    self.current_file_index = None;
-                } else if index >= self.open_files.len() {
-                    // If we removed the last file, select the new last file
-                    self.current_file_index = Some(self.open_files.len() - 1);
-                }
-                // else: keep current index (will now point to the next file)
+    /// Wrap the current file's selection (or the cursor's line) in a block
+    /// comment, or unwrap it
+    pub fn toggle_block_comment(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        self.toggle_comment(true, window, cx);
+    }
 
-                cx.notify();
+    fn toggle_comment(&mut self, block: bool, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(file) = self.current_file_index.and_then(|index| self.open_files.get(index)) else {
+            return;
+        };
+        let syntax = self.comment_syntax_for(&file.path);
+        let edited = file.input_state.update(cx, |state, cx| {
+            let text = state.value().to_string();
+            let cursor = state.cursor();
+            let selection = state
+                .selected_text_range(true, window, cx)
+                .map(|selection| {
+                    super::lsp::utf16_to_offset(&text, selection.range.start)
+                        ..super::lsp::utf16_to_offset(&text, selection.range.end)
+                })
+                .unwrap_or(cursor..cursor);
+            let edits = if block {
+                toggle_block_comment(&text, selection.clone(), &syntax)
+            } else {
+                toggle_line_comments(&text, selected_lines(&text, selection.clone()), &syntax)
+            };
+            if edits.is_empty() {
+                return false;
             }
+            let select = !selection.is_empty();
+            replace_ranges_keeping_selection(state, &text, &edits, selection, select, window, cx);
+            true
+        });
+        if !edited {
+            let message = if block {
+                "This language has no block comments"
+            } else {
+                "This language has no comments"
+            };
+            self.set_status_message(message, cx);
         }
     }
 
-    /// Navigate to a specific line and column in the current file
-    pub fn go_to_line(
+    /// Auto-close brackets and quotes for a keystroke in the file at `path`.
+    ///
+    /// Returns true when the keystroke was handled here and must not reach the input.
+    pub fn handle_auto_pair(
         &mut self,
-        line: usize,
-        column: usize,
+        path: &PathBuf,
+        keystroke: &Keystroke,
         window: &mut Window,
         cx: &mut Context<Self>,
-    ) {
-        if let Some(index) = self.current_file_index {
-            if let Some(open_file) = self.open_files.get_mut(index) {
-                // Store the pending scroll target in case layout isn't ready yet
-                open_file.pending_scroll_target = Some((line, column));
-
-                open_file.input_state.update(cx, |state, cx| {
-                    // LSP Position uses 'line' and 'character' fields (0-based)
-                    // Our UI uses 1-based line numbers
-                    use ui::input::Position;
-                    state.set_cursor_position(
-                        Position {
-                            line: (line.saturating_sub(1)) as u32,
-                            character: (column.saturating_sub(1)) as u32,
-                        },
-                        window,
-                        cx,
-                    );
-
-                    tracing::debug!("📍 Navigated to line {}, column {}", line, column);
+    ) -> bool {
+        let modifiers = &keystroke.modifiers;
+        if modifiers.control || modifiers.platform || modifiers.alt {
+            return false;
+        }
+        let Some(file) = self.open_files.iter().find(|file| &file.path == path) else {
+            return false;
+        };
+        let language = self.get_language_from_extension(path);
+
+        file.input_state.update(cx, |state, cx| {
+            let text = state.value().to_string();
+            let cursor = state.cursor();
+            let selection = state
+                .selected_text_range(true, window, cx)
+                .map(|selection| {
+                    super::lsp::utf16_to_offset(&text, selection.range.start)
+                        ..super::lsp::utf16_to_offset(&text, selection.range.end)
+                })
+                .unwrap_or(cursor..cursor);
 
-                    // Force an additional notify to ensure scroll is processed
-                    cx.notify();
-                });
+            let edit = if keystroke.key == "backspace" {
+                if !selection.is_empty() || modifiers.shift {
+                    return false;
+                }
+                auto_pairs::on_backspace(&text, cursor)
+            } else {
+                let mut chars = keystroke.key_char.as_deref().unwrap_or_default().chars();
+                match (chars.next(), chars.next()) {
+                    (Some(typed), None) => auto_pairs::on_char_typed(&text, selection, typed, &language),
+                    _ => None,
+                }
+            };
+            let Some(edit) = edit else {
+                return false;
+            };
 
-                // Notify at the TextEditor level as well to ensure render is triggered
-                cx.notify();
+            let mut new_text = text.clone();
+            if !(edit.range.is_empty() && edit.text.is_empty()) {
+                let utf16_range = super::lsp::offset_to_utf16(&text, edit.range.start)
+                    ..super::lsp::offset_to_utf16(&text, edit.range.end);
+                state.replace_text_in_range(Some(utf16_range), &edit.text, window, cx);
+                new_text.replace_range(edit.range, &edit.text);
             }
-        }
-    }
-
-    /// Get the current file path if any
-    pub fn current_file_path(&self) -> Option<PathBuf> {
-        self.current_file_index
-            .and_then(|index| self.open_files.get(index))
-            .map(|file| file.path.clone())
+            let position = super::lsp::offset_to_position(&new_text, edit.cursor);
+            state.set_cursor_position(
+                ui::input::Position::new(position.line, position.character),
+                window,
+                cx,
+            );
+            true
+        })
     }
 
-    /// Get the current scroll offset of the active file's input state
-    pub fn get_current_scroll_offset(&self, cx: &mut Context<Self>) -> Option<Point<Pixels>> {
-        self.current_file_index
-            .and_then(|index| self.open_files.get(index))
-            .map(|file| file.input_state.read(cx).get_scroll_offset())
+    /// Remove the extra cursors, keeping only the input's own cursor
+    pub fn collapse_cursors(&mut self, cx: &mut Context<Self>) {
+        let Some(file) = self.current_file_index.and_then(|index| self.open_files.get_mut(index)) else {
+            return;
+        };
+        file.box_selection = None;
+        if !file.multi_cursor.is_active() {
+            return;
+        }
+        file.multi_cursor.clear();
+        file.pending_cursor_text = None;
+        let input_state = file.input_state.clone();
+        show_extra_cursors(&input_state, &file.multi_cursor, cx);
+        cx.notify();
     }
 
-    /// Set the scroll offset for the active file's input state
-    pub fn set_scroll_offset(&mut self, offset: Point<Pixels>, cx: &mut Context<Self>) {
-        if let Some(index) = self.current_file_index {
-            if let Some(file) = self.open_files.get(index) {
-                file.input_state.update(cx, |state, _cx| {
-                    state.set_scroll_offset(offset);
-                });
+    fn show_definition_targets(&mut self, mut targets: Vec<DefinitionTarget>, cx: &mut Context<Self>) {
+        match targets.len() {
+            0 => tracing::debug!("ℹ️  No definition found"),
+            1 => {
+                let target = targets.remove(0);
+                self.navigate_to(target.path, target.position.line, target.position.character, cx);
             }
+            _ => self.definition_picker = Some(targets),
         }
+        cx.notify();
     }
 
-    /// Process pending navigation request (called from render where we have window access)
-    fn process_pending_navigation(&mut self, window: &mut Window, cx: &mut Context<Self>) {
-        if let Some((path, line, character)) = self.pending_navigation.take() {
-            tracing::debug!(
-                "🎯 Processing pending navigation to {:?} at {}:{}",
-                path,
-                line,
-                character
-            );
-
-            // Check if file is already open
-            let file_index = self.open_files.iter().position(|f| f.path == path);
+    fn pick_definition(&mut self, target: DefinitionTarget, cx: &mut Context<Self>) {
+        self.definition_picker = None;
+        self.navigate_to(target.path, target.position.line, target.position.character, cx);
+    }
 
-            if let Some(index) = file_index {
-                // File is already open, switch to it
-                self.current_file_index = Some(index);
-                tracing::debug!("✓ Switched to already-open file {:?}", path);
-            } else {
-                // Need to open the file first
-                tracing::debug!("📂 Opening file {:?}", path);
-                self.open_file(path.clone(), window, cx);
-                tracing::debug!(
-                    "✓ File opened, current_file_index: {:?}",
-                    self.current_file_index
-                );
-            }
+    fn render_definition_picker(&self, cx: &mut Context<Self>) -> Option<impl IntoElement> {
+        let targets = self.definition_picker.as_ref()?;
+        let workspace_root = self
+            .current_file_path()
+            .and_then(|path| self.resolve_workspace_root_for_file(&path));
 
-            // Now navigate to the specific position
-            // LSP positions are 0-based, go_to_line expects 1-based line numbers
-            // and will convert back to 0-based internally
-            let target_line = (line + 1) as usize; // Convert 0-based to 1-based
-            let target_col = (character + 1) as usize; // Convert 0-based to 1-based
+        Some(
+            div().absolute().top_10().left_0().right_0().flex().justify_center().child(
+                v_flex()
+                    .w(px(480.))
+                    .max_h(px(320.))
+                    .overflow_y_scroll()
+                    .p_1()
+                    .gap_0p5()
+                    .bg(cx.theme().popover)
+                    .border_1()
+                    .border_color(cx.theme().border)
+                    .rounded(cx.theme().radius)
+                    .shadow_lg()
+                    .child(
+                        h_flex()
+                            .px_2()
+                            .py_1()
+                            .justify_between()
+                            .child(
+                                div()
+                                    .text_xs()
+                                    .font_semibold()
+                                    .text_color(cx.theme().muted_foreground)
+                                    .child(format!("{} definitions", targets.len())),
+                            )
+                            .child(
+                                Button::new("close_definition_picker")
+                                    .icon(IconName::Close)
+                                    .ghost()
+                                    .xsmall()
+                                    .on_click(cx.listener(|this, _, _window, cx| {
+                                        this.definition_picker = None;
+                                        cx.notify();
+                                    })),
+                            ),
+                    )
+                    .children(targets.iter().enumerate().map(|(ix, target)| {
+                        let display_path = workspace_root
+                            .as_ref()
+                            .and_then(|root| target.path.strip_prefix(root).ok())
+                            .unwrap_or(&target.path)
+                            .display()
+                            .to_string();
+                        let target = target.clone();
+                        Button::new(("definition_target", ix))
+                            .label(format!("{}:{}", display_path, target.position.line + 1))
+                            .ghost()
+                            .small()
+                            .w_full()
+                            .on_click(cx.listener(move |this, _, _window, cx| {
+                                this.pick_definition(target.clone(), cx);
+                            }))
+                    })),
+            ),
+        )
+    }
 
-            tracing::debug!(
-                "🎯 Calling go_to_line with line {} (LSP: {}), column {} (LSP: {})",
-                target_line,
-                line,
-                target_col,
-                character
-            );
+    /// The oldest `showMessageRequest` still unanswered, with a button per action
+    fn render_message_request(&self, cx: &mut Context<Self>) -> Option<impl IntoElement> {
+        let request = self.server_messages.request()?;
+        let actions = request.params.actions.clone().unwrap_or_default();
 
-            self.go_to_line(target_line, target_col, window, cx);
+        Some(
+            div().absolute().top_10().left_0().right_0().flex().justify_center().child(
+                v_flex()
+                    .w(px(480.))
+                    .p_3()
+                    .gap_2()
+                    .bg(cx.theme().popover)
+                    .border_1()
+                    .border_color(cx.theme().border)
+                    .rounded(cx.theme().radius)
+                    .shadow_lg()
+                    .child(
+                        h_flex()
+                            .justify_between()
+                            .child(
+                                div()
+                                    .text_xs()
+                                    .font_semibold()
+                                    .text_color(cx.theme().muted_foreground)
+                                    .child(format!(
+                                        "{} {}",
+                                        message_type_symbol(request.params.typ),
+                                        request.server.name()
+                                    )),
+                            )
+                            .child(
+                                Button::new("dismiss_message_request")
+                                    .icon(IconName::Close)
+                                    .ghost()
+                                    .xsmall()
+                                    .on_click(cx.listener(|this, _, _window, cx| {
+                                        this.answer_server_message(None, cx);
+                                    })),
+                            ),
+                    )
+                    .child(div().text_sm().child(request.params.message.clone()))
+                    .child(h_flex().gap_1().justify_end().children(actions.into_iter().enumerate().map(
+                        |(ix, action)| {
+                            Button::new(("message_action", ix))
+                                .label(action.title)
+                                .small()
+                                .on_click(cx.listener(move |this, _, _window, cx| {
+                                    this.answer_server_message(Some(ix), cx);
+                                }))
+                        },
+                    ))),
+            ),
+        )
+    }
 
-            cx.notify();
+    /// Toasts from `window/showMessage`, stacked above the status bar
+    fn render_server_toasts(&self, cx: &mut Context<Self>) -> Option<impl IntoElement> {
+        let toasts = self.server_messages.toasts();
+        if toasts.is_empty() {
+            return None;
         }
+
+        Some(
+            v_flex()
+                .absolute()
+                .bottom_8()
+                .right_2()
+                .w(px(360.))
+                .gap_1()
+                .children(toasts.iter().map(|toast| {
+                    let id = toast.id;
+                    let mut text = format!("{} {}: {}", message_type_symbol(toast.kind), toast.server, toast.text);
+                    if toast.count > 1 {
+                        text.push_str(&format!(" (×{})", toast.count));
+                    }
+                    h_flex()
+                        .p_2()
+                        .gap_2()
+                        .justify_between()
+                        .bg(cx.theme().popover)
+                        .border_1()
+                        .border_color(cx.theme().border)
+                        .rounded(cx.theme().radius)
+                        .shadow_lg()
+                        .text_xs()
+                        .child(div().flex_1().child(text))
+                        .child(
+                            Button::new(("dismiss_toast", id))
+                                .icon(IconName::Close)
+                                .ghost()
+                                .xsmall()
+                                .on_click(cx.listener(move |this, _, _window, cx| {
+                                    this.server_messages.dismiss(id);
+                                    cx.notify();
+                                })),
+                        )
+                })),
+        )
     }
 
     /// Process any pending scroll targets (called from render after layout is ready)
@@ -1066,6 +7811,14 @@ impl TextEditor {
                     });
 
                     if scroll_attempted {
+                        // Restore the remembered scroll position on top of the cursor's
+                        if let Some(scroll_top) = open_file.pending_scroll_top.take() {
+                            open_file.input_state.update(cx, |state, _cx| {
+                                let offset = state.get_scroll_offset();
+                                state.set_scroll_offset(point(offset.x, px(-scroll_top)));
+                            });
+                        }
+
                         // Clear the pending scroll target - even if it didn't fully work,
                         // set_cursor_position was called which should set deferred scroll
                         open_file.pending_scroll_target = None;
@@ -1091,10 +7844,10 @@ impl TextEditor {
                     .file_name()
                     .and_then(|n| n.to_str())
                     .unwrap_or("untitled");
-                let display = if f.is_modified {
-                    format!("● {filename}")
-                } else {
-                    filename.to_string()
+                let display = match (f.is_modified, f.disk_state == DiskState::Deleted) {
+                    (_, true) => format!("{filename} (deleted)"),
+                    (true, false) => format!("● {filename}"),
+                    (false, false) => filename.to_string(),
                 };
                 (display.into(), false)
             })
@@ -1103,7 +7856,7 @@ impl TextEditor {
             tab_data.iter().map(|(l, d)| (Some(l.clone()), *d)).collect();
         let entity = cx.entity().downgrade();
 
-        TabBar::new("editor-tabs")
+        let tab_bar = TabBar::new("editor-tabs")
             .w_full()
             .bg(cx.theme().secondary)
             .border_b_1()
@@ -1128,22 +7881,51 @@ impl TextEditor {
                 move |ix, _, cx| {
                     let (label, _) = &data[ix];
                     let entity_c = entity.clone();
-                    Tab::new(label.clone()).child(
-                        h_flex().items_center().gap_2().child(
-                            Button::new(("close", ix))
-                                .icon(IconName::Close)
-                                .ghost()
-                                .xsmall()
-                                .on_click(move |_, window, cx| {
-                                    if let Some(entity) = entity_c.upgrade() {
-                                        entity.update(cx, |this, cx| {
-                                            this.close_file(ix, window, cx);
-                                        });
-                                    }
-                                }),
-                        ),
-                    )
+                    let entity_m = entity.clone();
+                    let entity_r = entity.clone();
+                    Tab::new(label.clone())
+                        // Middle-click closes the tab, like in browsers
+                        .on_mouse_up(MouseButton::Middle, move |_, window, cx| {
+                            if let Some(entity) = entity_m.upgrade() {
+                                entity.update(cx, |this, cx| {
+                                    this.request_close_file(ix, window, cx);
+                                });
+                            }
+                        })
+                        // The tab's menu acts on the current file
+                        .on_mouse_down(MouseButton::Right, move |_, window, cx| {
+                            if let Some(entity) = entity_r.upgrade() {
+                                entity.update(cx, |this, cx| this.set_active_file(ix, window, cx));
+                            }
+                        })
+                        .child(
+                            h_flex().items_center().gap_2().child(
+                                Button::new(("close", ix))
+                                    .icon(IconName::Close)
+                                    .ghost()
+                                    .xsmall()
+                                    .on_click(move |_, window, cx| {
+                                        if let Some(entity) = entity_c.upgrade() {
+                                            entity.update(cx, |this, cx| {
+                                                this.request_close_file(ix, window, cx);
+                                            });
+                                        }
+                                    }),
+                            ),
+                        )
                 }
+            });
+
+        div()
+            .id("editor-tab-bar")
+            .w_full()
+            .child(tab_bar)
+            .context_menu(|menu, _window, _cx| {
+                menu.menu("Reveal in File Explorer", Box::new(super::RevealInFileExplorer))
+                    .menu("Open Containing Folder", Box::new(super::OpenContainingFolder))
+                    .separator()
+                    .menu("Copy Absolute Path", Box::new(super::CopyAbsolutePath))
+                    .menu("Copy Relative Path", Box::new(super::CopyRelativePath))
             })
             .into_any_element()
     }
@@ -1223,6 +8005,37 @@ impl TextEditor {
                                 this.show_replace_dialog(window, cx);
                             })),
                     )
+                    .child(
+                        Button::new("fold_all")
+                            .icon(IconName::ChevronUp)
+                            .tooltip("Fold All (Ctrl+K Ctrl+0)")
+                            .ghost()
+                            .small()
+                            .on_click(cx.listener(|this, _, _window, cx| {
+                                this.set_all_folded(true, cx);
+                            })),
+                    )
+                    .child(
+                        Button::new("unfold_all")
+                            .icon(IconName::ChevronDown)
+                            .tooltip("Unfold All (Ctrl+K Ctrl+J)")
+                            .ghost()
+                            .small()
+                            .on_click(cx.listener(|this, _, _window, cx| {
+                                this.set_all_folded(false, cx);
+                            })),
+                    )
+                    .child(
+                        Button::new("toggle_minimap")
+                            .icon(IconName::Map)
+                            .tooltip("Toggle Minimap")
+                            .ghost()
+                            .small()
+                            .selected(self.show_minimap)
+                            .on_click(cx.listener(|this, _, _window, cx| {
+                                this.toggle_minimap(cx);
+                            })),
+                    )
                     .child(if self.show_performance_stats {
                         Button::new("toggle_stats")
                             .icon(IconName::Search)
@@ -1451,7 +8264,29 @@ impl TextEditor {
             .into_any_element()
     }
 
-    fn render_status_bar(&self, cx: &mut Context<Self>) -> impl IntoElement {
+    fn render_status_bar(&self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        // Where the cursor is and how the file is written
+        let editing_info = self.current_file_index.and_then(|index| self.open_files.get(index)).map(|file| {
+            let (cursor, selection) = file.input_state.update(cx, |state, cx| {
+                let selection = state
+                    .selected_text_range(true, window, cx)
+                    .map(|selection| selection.range)
+                    .filter(|range| !range.is_empty());
+                (state.cursor(), selection)
+            });
+            let (line, column) = file.synced.line_column(cursor);
+            let mut position = format!("Ln {}, Col {}", line + 1, column + 1);
+            if let Some(selection) = selection {
+                position.push_str(&format!(" ({} selected)", file.synced.utf16_char_count(selection)));
+            }
+            let indentation = if file.indent_style.hard_tabs {
+                format!("Tab Size: {}", file.indent_style.width)
+            } else {
+                format!("Spaces: {}", file.indent_style.width)
+            };
+            (position, indentation, file.encoding.label(), file.line_ending, file.mixed_line_endings)
+        });
+
         let (file_info, cache_info) = if let Some(index) = self.current_file_index {
             if let Some(open_file) = self.open_files.get(index) {
                 let filename = open_file
@@ -1518,9 +8353,32 @@ impl TextEditor {
             .child(
                 h_flex()
                     .gap_4()
+                    .children(self.vim.as_ref().map(|vim| vim.status()))
                     .child(file_info.0)
-                    .child("UTF-8")
-                    .child("LF"),
+                    .children(self.status_message.clone())
+                    .children(
+                        self.current_file_index
+                            .and_then(|index| self.open_files.get(index))
+                            .filter(|file| file.read_only)
+                            .map(|_| "Read-only"),
+                    )
+                    .children(editing_info.as_ref().map(|(_, _, encoding, _, _)| encoding.clone()))
+                    .children(editing_info.as_ref().map(|&(_, _, _, line_ending, mixed)| {
+                        let other = match line_ending {
+                            LineEnding::Lf => LineEnding::Crlf,
+                            LineEnding::Crlf => LineEnding::Lf,
+                        };
+                        div()
+                            .id("line_ending")
+                            .cursor_pointer()
+                            .when(mixed, |this| this.text_color(cx.theme().warning))
+                            .child(if mixed {
+                                format!("{} (mixed)", line_ending.label())
+                            } else {
+                                line_ending.label().to_string()
+                            })
+                            .on_click(cx.listener(move |this, _, _window, cx| this.set_line_ending(other, cx)))
+                    })),
             )
             .child({
                 let mut flex = h_flex().gap_4();
@@ -1529,9 +8387,36 @@ impl TextEditor {
                     flex = flex.child(cache_info.clone());
                 }
 
-                flex.child("Ln 1, Col 1")
-                    .child("Spaces: 4")
-                    .child(file_info.1)
+                if let Some(server) = self.language_server_status() {
+                    flex = flex.child(server);
+                }
+
+                if let Some(interpreter) = self.current_python_interpreter() {
+                    flex = flex.child(
+                        div()
+                            .id("python_interpreter")
+                            .cursor_pointer()
+                            .child(interpreter.label())
+                            .on_click(cx.listener(|this, _, _window, cx| this.select_python_interpreter(cx))),
+                    );
+                }
+
+                let (errors, warnings) = self.diagnostic_counts();
+                if errors > 0 || warnings > 0 {
+                    flex = flex.child(
+                        div()
+                            .id("problem_counts")
+                            .cursor_pointer()
+                            .child(format!("⊗ {}  ⚠ {}", errors, warnings))
+                            .on_click(cx.listener(|_this, _, _window, cx| cx.emit(TextEditorEvent::ShowProblemsRequested))),
+                    );
+                }
+
+                if let Some((position, indentation, ..)) = editing_info {
+                    flex = flex.child(position).child(indentation);
+                }
+
+                flex.child(file_info.1)
             })
     }
 
@@ -1553,7 +8438,7 @@ impl TextEditor {
         &mut self,
         path: PathBuf,
         content: String,
-        other_content: Option<(String, bool)>, // (other_content, is_before)
+        other_content: Option<(String, DiffSide)>,
         window: &mut Window,
         cx: &mut Context<Self>,
     ) {
@@ -1567,29 +8452,7 @@ impl TextEditor {
                 file.path = path.clone();
 
                 // Update language for syntax highlighting
-                let language = path
-                    .extension()
-                    .and_then(|ext| ext.to_str())
-                    .map(|ext| match ext {
-                        "rs" => "rust",
-                        "js" | "jsx" => "javascript",
-                        "ts" | "tsx" => "typescript",
-                        "py" => "python",
-                        "go" => "go",
-                        "c" | "h" => "c",
-                        "cpp" | "cc" | "cxx" | "hpp" => "cpp",
-                        "java" => "java",
-                        "json" => "json",
-                        "toml" => "toml",
-                        "yaml" | "yml" => "yaml",
-                        "md" => "markdown",
-                        "html" | "htm" => "html",
-                        "css" => "css",
-                        "xml" => "xml",
-                        "sh" | "bash" => "bash",
-                        _ => "plaintext",
-                    })
-                    .unwrap_or("plaintext");
+                let language = language_for_path(&path).map_or("plaintext", |language| language.grammar);
 
                 file.input_state.update(cx, |state, cx| {
                     // Update language/highlighter for the new file
@@ -1597,13 +8460,12 @@ impl TextEditor {
                     state.set_value(&content, window, cx);
 
                     // Apply diff highlighting if other_content is provided
-                    if let Some((other, is_before)) = &other_content {
-                        Self::apply_diff_highlighting(
-                            state, &content, other, *is_before, window, cx,
-                        );
+                    if let Some((other, side)) = &other_content {
+                        Self::apply_diff_highlighting(state, &content, other, *side, window, cx);
                     }
                 });
                 file.is_modified = false;
+                file.saved_content_hash = content_hash(&file.input_state.read(cx).value());
                 file.lines_count = content.lines().count();
                 file.file_size = content.len();
                 self.current_file_index = Some(0);
@@ -1617,156 +8479,130 @@ impl TextEditor {
                     state.set_value(&content, window, cx);
 
                     // Apply diff highlighting if other_content is provided
-                    if let Some((other, is_before)) = &other_content {
-                        Self::apply_diff_highlighting(
-                            state, &content, other, *is_before, window, cx,
-                        );
+                    if let Some((other, side)) = &other_content {
+                        Self::apply_diff_highlighting(state, &content, other, *side, window, cx);
                     }
                 });
                 file.is_modified = false;
+                file.saved_content_hash = content_hash(&file.input_state.read(cx).value());
             }
         } else {
             // Detect language from file extension for syntax highlighting
-            let language = path
-                .extension()
-                .and_then(|ext| ext.to_str())
-                .map(|ext| match ext {
-                    "rs" => "rust",
-                    "js" | "jsx" => "javascript",
-                    "ts" | "tsx" => "typescript",
-                    "py" => "python",
-                    "go" => "go",
-                    "c" | "h" => "c",
-                    "cpp" | "cc" | "cxx" | "hpp" => "cpp",
-                    "java" => "java",
-                    "json" => "json",
-                    "toml" => "toml",
-                    "yaml" | "yml" => "yaml",
-                    "md" => "markdown",
-                    "html" | "htm" => "html",
-                    "css" => "css",
-                    "xml" => "xml",
-                    "sh" | "bash" => "bash",
-                    _ => "plaintext",
-                })
-                .unwrap_or("plaintext");
+            let language = language_for_path(&path).map_or("plaintext", |language| language.grammar);
 
             // Create new file entry with provided content
+            let indent_style = self.indent_style_for(language, &content, &EditorConfig::default());
             let input_state = cx.new(|cx| {
                 let mut state = InputState::new(window, cx)
                     .multi_line()
                     .code_editor(language)
                     .line_number(true)
                     .tab_size(TabSize {
-                        tab_size: 4,
-                        hard_tabs: false,
+                        tab_size: indent_style.width as usize,
+                        hard_tabs: indent_style.hard_tabs,
                     });
                 state.set_value(&content, window, cx);
 
                 // Apply diff highlighting if other_content is provided
-                if let Some((other, is_before)) = &other_content {
-                    Self::apply_diff_highlighting(
-                        &mut state, &content, other, *is_before, window, cx,
-                    );
+                if let Some((other, side)) = &other_content {
+                    Self::apply_diff_highlighting(&mut state, &content, other, *side, window, cx);
                 }
 
                 state
             });
 
+            let saved_content_hash = content_hash(&input_state.read(cx).value());
             let file = OpenFile {
                 path: path.clone(),
                 input_state: input_state.clone(),
                 is_modified: false,
+                saved_content_hash,
                 lines_count: content.lines().count(),
                 file_size: content.len(),
                 version: 1,
                 render_as_markdown: false,
                 markdown_preview_cache: String::new(),
                 last_markdown_render: None,
+                markdown_blocks: Rc::default(),
                 pending_scroll_target: None,
+                pending_scroll_top: None,
+                language_server: None,
+                synced: TextBuffer::default(),
+                diagnostics: Vec::new(),
+                multi_cursor: MultiCursor::default(),
+                cursor_offset: 0,
+                multi_cursor_text: String::new(),
+                pending_cursor_text: None,
+                placing_cursor: false,
+                folds: FoldState::default(),
+                cursor_line: 0,
+                indent_style,
+                editorconfig: EditorConfig::default(),
+                bracket_pairs: None,
+                bracket_highlights: Default::default(),
+                occurrences: Occurrences::default(),
+                occurrences_at: None,
+                current_lines: None,
+                box_selection: None,
+                selection_history: SelectionHistory::default(),
+                undo_history: UndoHistory::default(),
+                document_version: DocumentVersion::default(),
+                inlay_hints: SharedInlayHints::default(),
+                inlay_hint_request: None,
+                semantic_tokens: SemanticTokenState::default(),
+                semantic_tokens_version: None,
+                lazy_highlighter: None,
+                lazy_highlight_request: None,
+                symbols: Vec::new(),
+                breadcrumb_path: Vec::new(),
+                bookmarks: Bookmarks::default(),
+                snippet: None,
+                pending_snippet_text: None,
+                completion_extras: SharedCompletionExtras::default(),
+                disk_state: DiskState::InSync,
+            encoding: FileEncoding::default(),
+            read_only: false,
+            line_ending: LineEnding::Lf,
+            saved_line_ending: LineEnding::Lf,
+            mixed_line_endings: false,
             };
 
             self.open_files.push(file);
             self.current_file_index = Some(self.open_files.len() - 1);
         }
 
+        self.notify_dirty_state(cx);
         cx.notify();
     }
 
-    /// Apply diff highlighting to an InputState with proper alignment
+    /// Show one side of a diff in an InputState: aligned with the other side or
+    /// merged with it, with changed lines and the changes within them highlighted
     fn apply_diff_highlighting(
         state: &mut InputState,
         this_content: &str,
         other_content: &str,
-        is_before: bool,
+        side: DiffSide,
         window: &mut Window,
         cx: &mut Context<InputState>,
     ) {
-        use similar::{ChangeTag, TextDiff};
-
-        // Use the 'similar' crate's Myers diff algorithm
-        let diff = TextDiff::from_lines(
-            if is_before {
-                this_content
-            } else {
-                other_content
-            },
-            if is_before {
-                other_content
-            } else {
-                this_content
-            },
-        );
-
-        let mut aligned_content = String::new();
-        let mut line_highlights = Vec::new();
-
-        for change in diff.iter_all_changes() {
-            let line = change.to_string_lossy();
-
-            match change.tag() {
-                ChangeTag::Equal => {
-                    // Line exists in both - no highlight
-                    aligned_content.push_str(&line);
-                    if !line.ends_with('\n') {
-                        aligned_content.push('\n');
-                    }
-                    line_highlights.push(ui::input::LineHighlight::None);
-                }
-                ChangeTag::Delete => {
-                    if is_before {
-                        // This is the "before" view, show the deleted line
-                        aligned_content.push_str(&line);
-                        if !line.ends_with('\n') {
-                            aligned_content.push('\n');
-                        }
-                        line_highlights.push(ui::input::LineHighlight::Removed);
-                    } else {
-                        // This is the "after" view, insert blank line for alignment
-                        aligned_content.push('\n');
-                        line_highlights.push(ui::input::LineHighlight::None);
-                    }
-                }
-                ChangeTag::Insert => {
-                    if !is_before {
-                        // This is the "after" view, show the inserted line
-                        aligned_content.push_str(&line);
-                        if !line.ends_with('\n') {
-                            aligned_content.push('\n');
-                        }
-                        line_highlights.push(ui::input::LineHighlight::Added);
-                    } else {
-                        // This is the "before" view, insert blank line for alignment
-                        aligned_content.push('\n');
-                        line_highlights.push(ui::input::LineHighlight::None);
-                    }
-                }
-            }
-        }
+        let (before, after) = match side {
+            DiffSide::Before | DiffSide::Inline => (this_content, other_content),
+            DiffSide::After => (other_content, this_content),
+        };
+        let view = diff_view(before, after, side);
+        let line_highlights = view
+            .lines
+            .iter()
+            .map(|line| match line {
+                DiffLine::Added => ui::input::LineHighlight::Added,
+                DiffLine::Removed => ui::input::LineHighlight::Removed,
+                DiffLine::Unchanged | DiffLine::Padding => ui::input::LineHighlight::None,
+            })
+            .collect();
 
-        // Update the content with aligned version
-        state.set_value(&aligned_content, window, cx);
+        state.set_value(&view.text, window, cx);
         state.set_line_highlights(line_highlights);
+        state.set_search_highlights(view.changed_ranges, None);
     }
 }
 
@@ -1780,11 +8616,27 @@ impl Focusable for TextEditor {
 
 impl Render for TextEditor {
     fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
-        // Initialize workspace on first render
-        self.initialize_workspace_once(window, cx);
-
         // Process any pending navigation requests (from go-to-definition)
         self.process_pending_navigation(window, cx);
+        self.process_pending_peek(window, cx);
+
+        // Apply edits typed at extra cursors
+        self.process_pending_cursor_edits(window, cx);
+
+        // Fill in snippet mirrors and tab stops
+        self.process_pending_snippet_edits(window, cx);
+
+        // Add the imports accepted completions came with
+        self.process_pending_additional_edits(window, cx);
+
+        // Apply edits servers asked for while running a command
+        self.process_pending_workspace_edits(window, cx);
+
+        // Move cursors off lines hidden by folds
+        self.process_pending_cursor_moves(window, cx);
+
+        // Reload clean files that changed on disk
+        self.process_pending_reloads(window, cx);
 
         // Process any pending scroll targets (after layout is ready)
         self.process_pending_scroll_targets(window, cx);
@@ -1792,18 +8644,43 @@ impl Render for TextEditor {
         // Track render time for performance monitoring
         let render_start = Instant::now();
 
+        let definition_picker = self.render_definition_picker(cx);
+        let interpreter_picker = self.render_interpreter_picker(cx);
+        let rename_input = self.render_rename_input(cx);
+        let signature_help = self.render_signature_help(cx);
+        let go_to_line = self.render_go_to_line(cx);
+        let message_request = self.render_message_request(cx);
+        let server_toasts = self.render_server_toasts(cx);
+
+        // Keep the find bar searching whichever file is active
+        if self.find_bar_visible {
+            let target = self.current_input_state();
+            self.find_bar.update(cx, |find_bar, cx| find_bar.set_target(target, cx));
+        }
+
         let result = v_flex()
+            .relative()
             .size_full()
             .bg(cx.theme().background)
             .child(self.render_toolbar(cx))
+            .child(self.render_tab_bar(cx))
+            .children(self.render_disk_change_banner(cx))
+            .children(self.render_breadcrumbs(cx))
+            .children(self.find_bar_visible.then(|| self.find_bar.clone()))
             .child(div().flex_1().min_h_0().overflow_hidden().child(
-                if let Some(ref workspace) = self.workspace {
-                    workspace.clone().into_any_element()
-                } else {
-                    div().child("Loading...").into_any_element()
+                match self.active_file_view(cx) {
+                    Some(view) => view.into_any_element(),
+                    None => self.welcome_view.clone().into_any_element(),
                 },
             ))
-            .child(self.render_status_bar(cx));
+            .child(self.render_status_bar(window, cx))
+            .children(signature_help)
+            .children(definition_picker)
+            .children(interpreter_picker)
+            .children(rename_input)
+            .children(go_to_line)
+            .children(server_toasts)
+            .children(message_request);
 
         // Log render time if performance stats are enabled
         if self.show_performance_stats {
@@ -0,0 +1,404 @@
+//! Built-in support for TOML files, which have no common language server
+//!
+//! Syntax errors come from the `toml` parser, which stops at the first one;
+//! a repeated key or `[table]` is found by a scan of the file's statements
+//! instead, so every one of them is reported. The same scan gives the
+//! outline, the `[table]` sections to fold and brackets that know TOML's
+//! strings and `#` comments.
+
+use lsp_types::{DiagnosticSeverity, Range as LspRange, SymbolKind};
+use std::collections::HashSet;
+use std::ops::Range;
+use std::path::Path;
+
+use super::brackets::Bracket;
+use super::folding::FoldRange;
+use super::lsp::{offset_to_position, FileDiagnostic, SymbolNode};
+
+pub fn is_toml(path: &Path) -> bool {
+    path.extension().and_then(|ext| ext.to_str()) == Some("toml")
+}
+
+/// Syntax errors and repeated keys and tables in `text`
+pub fn validate_toml(text: &str) -> Vec<FileDiagnostic> {
+    let mut diagnostics = duplicate_keys(text);
+    if let Err(e) = text.parse::<toml::Table>() {
+        let range = e.span().map_or(0..0, |span| error_range(text, span));
+        // A repeated key the parser stopped at is reported already
+        if !diagnostics.iter().any(|diagnostic| overlaps(&diagnostic.range, &range)) {
+            diagnostics.push(diagnostic(range, e.message()));
+        }
+    }
+    diagnostics.sort_by_key(|diagnostic| diagnostic.range.start);
+    diagnostics
+}
+
+/// Tables with their keys, in the order they appear
+pub fn toml_outline(text: &str) -> Vec<SymbolNode> {
+    let entries = entries(text);
+    let position = |offset| offset_to_position(text, offset);
+
+    let mut nodes: Vec<Option<SymbolNode>> = entries
+        .iter()
+        .map(|entry| {
+            Some(SymbolNode {
+                name: text[entry.name.clone()].to_string(),
+                kind: match entry.kind {
+                    EntryKind::Table => SymbolKind::NAMESPACE,
+                    EntryKind::ArrayTable => SymbolKind::ARRAY,
+                    EntryKind::Key => SymbolKind::KEY,
+                },
+                range: LspRange::new(position(entry.statement), position(entry.end)),
+                selection_start: position(entry.name.start),
+                children: Vec::new(),
+            })
+        })
+        .collect();
+    // Parents come before their children, so children are complete when
+    // they're moved into them back to front
+    let mut roots = Vec::new();
+    for (ix, entry) in entries.iter().enumerate().rev() {
+        let node = nodes[ix].take().expect("each node is moved once");
+        match entry.parent {
+            Some(parent) => nodes[parent].as_mut().expect("parent not moved yet").children.insert(0, node),
+            None => roots.insert(0, node),
+        }
+    }
+    roots
+}
+
+/// `[table]` and `[[array]]` sections, from their header to their last non-blank line
+pub fn section_fold_ranges(text: &str) -> Vec<FoldRange> {
+    let line = |offset: usize| text[..offset].matches('\n').count() as u32;
+    entries(text)
+        .into_iter()
+        .filter(|entry| entry.kind != EntryKind::Key)
+        .map(|entry| FoldRange {
+            start: line(entry.statement),
+            end: line(entry.end),
+        })
+        .collect()
+}
+
+/// Every bracket in `text` outside strings and comments, in order
+pub fn toml_brackets(text: &str) -> Vec<Bracket> {
+    scan(text).brackets
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum EntryKind {
+    Table,
+    ArrayTable,
+    Key,
+}
+
+/// A table header or a key
+#[derive(Debug)]
+struct Entry {
+    kind: EntryKind,
+    /// Start of the line's content
+    statement: usize,
+    /// The key or table name, as written
+    name: Range<usize>,
+    /// End of its last non-blank line: a key's value, or a table's last key
+    end: usize,
+    /// The table it's in, as an index into the entries
+    parent: Option<usize>,
+    /// The full key, with a number for each `[[array]]` entry it's under, to
+    /// tell repeats from keys of the same name elsewhere
+    scope: String,
+}
+
+/// The file's tables and keys. A table goes under the last header whose name
+/// starts its own, so `[package.metadata]` follows `[package]`.
+fn entries(text: &str) -> Vec<Entry> {
+    let statements = scan(text).statements;
+    let mut entries: Vec<Entry> = Vec::new();
+    // Open tables, outermost first, with their name's segments
+    let mut tables: Vec<(usize, Vec<String>)> = Vec::new();
+    let mut array_entries = 0;
+
+    for (ix, &statement) in statements.iter().enumerate() {
+        let Some((kind, name)) = parse_statement(text, statement) else {
+            continue;
+        };
+        let next = statements.get(ix + 1).copied().unwrap_or(text.len());
+        let end = statement + text[statement..next].trim_end().len();
+        let segments = key_segments(&text[name.clone()]);
+
+        if kind == EntryKind::Key {
+            let parent = tables.last().map(|(table, _)| *table);
+            let scope = scoped(parent.map_or("", |table| entries[table].scope.as_str()), &segments);
+            if let Some(table) = parent {
+                entries[table].end = end;
+            }
+            entries.push(Entry { kind, statement, name, end, parent, scope });
+            continue;
+        }
+
+        while tables
+            .last()
+            .is_some_and(|(_, open)| open.len() >= segments.len() || !segments.starts_with(open))
+        {
+            tables.pop();
+        }
+        let (parent, relative) = match tables.last() {
+            Some((table, open)) => (Some(*table), &segments[open.len()..]),
+            None => (None, &segments[..]),
+        };
+        let mut scope = scoped(parent.map_or("", |table| entries[table].scope.as_str()), relative);
+        if kind == EntryKind::ArrayTable {
+            array_entries += 1;
+            scope.push_str(&format!("#{}", array_entries));
+        }
+        let header_end = statement + text[statement..].find('\n').unwrap_or(text.len() - statement);
+        let end = statement + text[statement..header_end].trim_end().len();
+        tables.push((entries.len(), segments));
+        entries.push(Entry { kind, statement, name, end, parent, scope });
+    }
+    entries
+}
+
+fn scoped(parent: &str, segments: &[String]) -> String {
+    let mut scope = parent.to_string();
+    for segment in segments {
+        scope.push('\0');
+        scope.push_str(segment);
+    }
+    scope
+}
+
+/// Keys and tables defined a second time
+fn duplicate_keys(text: &str) -> Vec<FileDiagnostic> {
+    let mut defined = HashSet::new();
+    let mut diagnostics = Vec::new();
+    for entry in entries(text) {
+        // Each `[[array]]` header adds an entry rather than repeating it
+        if entry.kind == EntryKind::ArrayTable || defined.insert(entry.scope) {
+            continue;
+        }
+        let name = &text[entry.name.clone()];
+        let message = match entry.kind {
+            EntryKind::Key => format!("Duplicate key `{}`", name),
+            _ => format!("Duplicate table `[{}]`", name),
+        };
+        diagnostics.push(diagnostic(entry.name, &message));
+    }
+    diagnostics
+}
+
+/// A table header's kind and name, or a key, at the start of a statement
+fn parse_statement(text: &str, start: usize) -> Option<(EntryKind, Range<usize>)> {
+    let (kind, name_start, stop) = if text[start..].starts_with("[[") {
+        (EntryKind::ArrayTable, start + 2, b']')
+    } else if text[start..].starts_with('[') {
+        (EntryKind::Table, start + 1, b']')
+    } else {
+        (EntryKind::Key, start, b'=')
+    };
+    let name_end = key_end(text, name_start, stop)?;
+    let raw = &text[name_start..name_end];
+    let trimmed = raw.trim_start();
+    let name_start = name_start + raw.len() - trimmed.len();
+    let name = name_start..name_start + trimmed.trim_end().len();
+    (!name.is_empty()).then_some((kind, name))
+}
+
+/// Offset of `stop` after a key starting at `start`, skipping quoted parts;
+/// `None` if the line ends first
+fn key_end(text: &str, start: usize, stop: u8) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let mut ix = start;
+    while ix < bytes.len() {
+        match bytes[ix] {
+            byte if byte == stop => return Some(ix),
+            b'\n' => return None,
+            quote @ (b'"' | b'\'') => ix = string_end(text, ix, quote, false),
+            _ => ix += 1,
+        }
+    }
+    None
+}
+
+/// A dotted key's parts, unquoted: `a."b.c"` is `a` and `b.c`
+fn key_segments(key: &str) -> Vec<String> {
+    let mut segments = Vec::new();
+    let mut segment = String::new();
+    let mut quote = None;
+    for ch in key.chars() {
+        match (quote, ch) {
+            (None, '"' | '\'') => quote = Some(ch),
+            (Some(open), _) if ch == open => quote = None,
+            (None, '.') => segments.push(std::mem::take(&mut segment).trim().to_string()),
+            _ => segment.push(ch),
+        }
+    }
+    segments.push(segment.trim().to_string());
+    segments
+}
+
+struct Scan {
+    brackets: Vec<Bracket>,
+    /// Where each line that starts a table header or a key has its content
+    statements: Vec<usize>,
+}
+
+/// Brackets and statements outside strings and comments. Lines inside a
+/// multi-line array or string don't start statements.
+fn scan(text: &str) -> Scan {
+    let bytes = text.as_bytes();
+    let mut brackets = Vec::new();
+    let mut statements = Vec::new();
+    // Open `[`s of arrays, which may go on for several lines
+    let mut array_depth = 0usize;
+    let mut in_header = false;
+    let mut ix = 0;
+    let mut line_start = true;
+
+    while ix < bytes.len() {
+        if line_start {
+            line_start = false;
+            in_header = false;
+            let content = ix + text[ix..].len() - text[ix..].trim_start_matches([' ', '\t']).len();
+            if array_depth == 0 && !matches!(bytes.get(content), None | Some(b'#' | b'\r' | b'\n')) {
+                statements.push(content);
+                in_header = bytes[content] == b'[';
+            }
+        }
+        match bytes[ix] {
+            b'\n' => line_start = true,
+            b'#' => {
+                ix = text[ix..].find('\n').map_or(text.len(), |len| ix + len);
+                continue;
+            }
+            quote @ (b'"' | b'\'') => {
+                let multiline = bytes[ix..].starts_with(&[quote; 3]);
+                ix = string_end(text, ix, quote, multiline);
+                continue;
+            }
+            ch @ (b'[' | b'{') => {
+                if ch == b'[' && !in_header {
+                    array_depth += 1;
+                }
+                brackets.push(Bracket { offset: ix, ch: ch as char });
+            }
+            ch @ (b']' | b'}') => {
+                if ch == b']' && !in_header {
+                    array_depth = array_depth.saturating_sub(1);
+                }
+                brackets.push(Bracket { offset: ix, ch: ch as char });
+            }
+            _ => {}
+        }
+        ix += 1;
+    }
+    Scan { brackets, statements }
+}
+
+/// Index just past the string starting with the quote at `start`. A
+/// single-line string stops before the end of its line even if unclosed.
+fn string_end(text: &str, start: usize, quote: u8, multiline: bool) -> usize {
+    let bytes = text.as_bytes();
+    let delimiter = if multiline { 3 } else { 1 };
+    let mut ix = start + delimiter;
+    while ix < bytes.len() {
+        match bytes[ix] {
+            // Literal strings (in single quotes) have no escapes
+            b'\\' if quote == b'"' && (multiline || bytes.get(ix + 1) != Some(&b'\n')) => ix += 1,
+            b'\n' if !multiline => return ix,
+            byte if byte == quote && (!multiline || bytes[ix..].starts_with(&[quote; 3])) => {
+                // Up to two quotes before the closing ones are part of the string
+                let mut end = ix + delimiter;
+                while multiline && end < ix + 5 && bytes.get(end) == Some(&quote) {
+                    end += 1;
+                }
+                return end;
+            }
+            _ => {}
+        }
+        ix += 1;
+    }
+    bytes.len()
+}
+
+fn diagnostic(range: Range<usize>, message: &str) -> FileDiagnostic {
+    FileDiagnostic {
+        range,
+        severity: DiagnosticSeverity::ERROR,
+        message: message.to_string(),
+        source: Some("toml".to_string()),
+        code: None,
+    }
+}
+
+/// The parser's span, widened to a character when it's empty so it can be seen
+fn error_range(text: &str, span: Range<usize>) -> Range<usize> {
+    if !span.is_empty() {
+        return span;
+    }
+    let start = span.start.min(text.len());
+    match text[start..].chars().next() {
+        Some(ch) if ch != '\n' => start..start + ch.len_utf8(),
+        _ => match text[..start].char_indices().next_back() {
+            Some((ix, _)) => ix..start,
+            None => start..start,
+        },
+    }
+}
+
+fn overlaps(a: &Range<usize>, b: &Range<usize>) -> bool {
+    a.start < b.end.max(b.start + 1) && b.start < a.end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = "\
+name = \"demo\" # [not a table]
+[package]
+version = \"0.1\"
+authors = [
+  \"a\",
+]
+
+[package.metadata]
+docs = { all = true }
+
+[[bin]]
+name = \"a\"
+[[bin]]
+name = \"b\"
+";
+
+    #[test]
+    fn test_outline_and_sections() {
+        let outline = toml_outline(MANIFEST);
+        let names = |nodes: &[SymbolNode]| nodes.iter().map(|node| node.name.clone()).collect::<Vec<_>>();
+        assert_eq!(names(&outline), ["name", "package", "bin", "bin"]);
+        assert_eq!(names(&outline[1].children), ["version", "authors", "package.metadata"]);
+        assert_eq!(names(&outline[1].children[2].children), ["docs"]);
+        assert_eq!(outline[1].range.end.line, 5);
+
+        let sections: Vec<(u32, u32)> = section_fold_ranges(MANIFEST)
+            .iter()
+            .map(|range| (range.start, range.end))
+            .collect();
+        assert_eq!(sections, [(1, 5), (7, 8), (10, 11), (12, 13)]);
+        // The comment's brackets aren't matched
+        assert_eq!(toml_brackets(MANIFEST).len(), 16);
+        assert!(validate_toml(MANIFEST).is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_keys_and_syntax_errors() {
+        let text = "a = 1\n[t]\nb = 2\nb = 3\n[t]\n";
+        let diagnostics = validate_toml(text);
+        let found: Vec<_> = diagnostics.iter().map(|d| (d.range.clone(), d.message.as_str())).collect();
+        assert_eq!(found, [(16..17, "Duplicate key `b`"), (23..24, "Duplicate table `[t]`")]);
+
+        let diagnostics = validate_toml("a = \n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].source.as_deref(), Some("toml"));
+    }
+}
@@ -0,0 +1,356 @@
+//! Undo and redo in steps the way people think of them
+//!
+//! Every change to a buffer is recorded as one replacement. A run of typed
+//! characters (or of deleted ones) joins into one step until the cursor moves
+//! elsewhere, Enter is pressed or typing pauses; anything else, such as a
+//! paste, a format or a rename, is a step of its own. Undo puts back the
+//! cursor or selection from before the step.
+
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+/// A pause in typing this long starts a new undo step
+pub const UNDO_GROUP_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// `removed` at `start` replaced by `inserted`
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Edit {
+    start: usize,
+    removed: String,
+    inserted: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum EditKind {
+    /// One character typed, possibly over a selection
+    Typing,
+    /// One character deleted
+    Deleting,
+    Other,
+}
+
+#[derive(Clone, Debug)]
+struct Transaction {
+    edit: Edit,
+    kind: EditKind,
+    selection_before: Range<usize>,
+    /// When the step last grew, to end it after a pause
+    last_edit_at: Instant,
+}
+
+impl Transaction {
+    fn cursor_after(&self) -> usize {
+        self.edit.start + self.edit.inserted.len()
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct UndoHistory {
+    undo: Vec<Transaction>,
+    redo: Vec<Transaction>,
+    /// Whether the last undo step may still grow
+    group_open: bool,
+    /// Text the buffer will have once an undo or redo lands, which isn't recorded
+    expected: Option<String>,
+    /// Whether the next change is a step of its own whatever it looks like
+    next_atomic: bool,
+    /// Whether the next change joins the last step instead
+    next_joined: bool,
+}
+
+impl UndoHistory {
+    /// Record the change of `removed` at `start` to `inserted`, leaving
+    /// `new_text`, made with the cursor at `cursor` in the old text
+    pub fn record_edit(
+        &mut self,
+        new_text: &str,
+        start: usize,
+        removed: String,
+        inserted: String,
+        cursor: usize,
+        now: Instant,
+    ) {
+        if self.expected.as_deref() == Some(new_text) {
+            self.expected = None;
+            return;
+        }
+        if removed.is_empty() && inserted.is_empty() {
+            return;
+        }
+        let old_end = start + removed.len();
+        let edit = Edit { start, removed, inserted };
+        if std::mem::take(&mut self.next_joined) {
+            if let Some(last) = self.undo.last_mut() {
+                if let Some(joined) = join(&last.edit, &edit, new_text) {
+                    last.edit = joined;
+                    self.next_atomic = false;
+                    self.group_open = false;
+                    self.redo.clear();
+                    return;
+                }
+            }
+        }
+        let kind = if std::mem::take(&mut self.next_atomic) { EditKind::Other } else { edit_kind(&edit) };
+        self.redo.clear();
+
+        if self.group_open && kind != EditKind::Other {
+            if let Some(last) = self.undo.last_mut() {
+                if now.duration_since(last.last_edit_at) < UNDO_GROUP_TIMEOUT && merge(last, &edit, kind) {
+                    last.last_edit_at = now;
+                    return;
+                }
+            }
+        }
+
+        // A typed-over or deleted selection had the cursor at one of its ends
+        let selection_before = if kind != EditKind::Deleting && old_end > start && (cursor == start || cursor == old_end) {
+            start..old_end
+        } else {
+            cursor..cursor
+        };
+        self.undo.push(Transaction {
+            edit,
+            kind,
+            selection_before,
+            last_edit_at: now,
+        });
+        self.group_open = kind != EditKind::Other;
+    }
+
+    /// Make the next change a step of its own, e.g. a format or rename that
+    /// happens to change a single character
+    pub fn start_atomic(&mut self) {
+        self.group_open = false;
+        self.next_atomic = true;
+    }
+
+    /// Make the next change part of the last step, so one undo takes back
+    /// both: imports organized and then the file formatted, say
+    pub fn join_next(&mut self) {
+        self.next_joined = true;
+    }
+
+    /// Note where the cursor is when the text didn't change: moving it away
+    /// from the end of the last edit ends that step
+    pub fn cursor_moved(&mut self, cursor: usize) {
+        if self.undo.last().is_some_and(|last| last.cursor_after() != cursor) {
+            self.group_open = false;
+        }
+    }
+
+    /// The text with the last step undone and the selection to restore, or
+    /// `None` when there is nothing to undo (or the history no longer fits `text`)
+    pub fn undo(&mut self, text: &str) -> Option<(String, Range<usize>)> {
+        let transaction = self.undo.pop()?;
+        let edit = &transaction.edit;
+        let Some(new_text) = replace(text, edit.start, &edit.inserted, &edit.removed) else {
+            self.clear();
+            return None;
+        };
+        let selection = transaction.selection_before.clone();
+        self.redo.push(transaction);
+        self.group_open = false;
+        self.expected = Some(new_text.clone());
+        Some((new_text, selection))
+    }
+
+    /// The text with the last undone step made again and the cursor after it
+    pub fn redo(&mut self, text: &str) -> Option<(String, Range<usize>)> {
+        let transaction = self.redo.pop()?;
+        let edit = &transaction.edit;
+        let Some(new_text) = replace(text, edit.start, &edit.removed, &edit.inserted) else {
+            self.clear();
+            return None;
+        };
+        let cursor = transaction.cursor_after();
+        self.undo.push(transaction);
+        self.group_open = false;
+        self.expected = Some(new_text.clone());
+        Some((new_text, cursor..cursor))
+    }
+
+    fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+fn edit_kind(edit: &Edit) -> EditKind {
+    let single_char = |text: &str| {
+        let mut chars = text.chars();
+        chars.next().is_some() && chars.next().is_none()
+    };
+    if single_char(&edit.inserted) && edit.inserted != "\n" {
+        EditKind::Typing
+    } else if edit.inserted.is_empty() && single_char(&edit.removed) && edit.removed != "\n" {
+        EditKind::Deleting
+    } else {
+        EditKind::Other
+    }
+}
+
+/// Extend `last` with `edit` if it continues it: typing right after it, or
+/// deleting next to it
+fn merge(last: &mut Transaction, edit: &Edit, kind: EditKind) -> bool {
+    match (last.kind, kind) {
+        (EditKind::Typing, EditKind::Typing) if edit.removed.is_empty() && edit.start == last.cursor_after() => {
+            last.edit.inserted.push_str(&edit.inserted);
+            true
+        }
+        (EditKind::Deleting, EditKind::Deleting) => {
+            if edit.start + edit.removed.len() == last.edit.start {
+                // Backspace
+                last.edit.start = edit.start;
+                last.edit.removed.insert_str(0, &edit.removed);
+                true
+            } else if edit.start == last.edit.start {
+                // Delete
+                last.edit.removed.push_str(&edit.removed);
+                true
+            } else {
+                false
+            }
+        }
+        _ => false,
+    }
+}
+
+/// One edit making both `first` and then `second`, which left `new_text`
+fn join(first: &Edit, second: &Edit, new_text: &str) -> Option<Edit> {
+    let between = replace(new_text, second.start, &second.inserted, &second.removed)?;
+    let first_end = first.start + first.inserted.len();
+    let second_end = second.start + second.removed.len();
+    let (start, end) = (first.start.min(second.start), first_end.max(second_end));
+    let removed = format!(
+        "{}{}{}",
+        between.get(start..first.start)?,
+        first.removed,
+        between.get(first_end..end)?
+    );
+    let inserted = new_text.get(start..end + second.inserted.len() - second.removed.len())?.to_string();
+    Some(Edit { start, removed, inserted })
+}
+
+/// `text` with `old` at `start` replaced by `new`, if `old` is there
+fn replace(text: &str, start: usize, old: &str, new: &str) -> Option<String> {
+    let end = start + old.len();
+    if text.get(start..end)? != old {
+        return None;
+    }
+    Some(format!("{}{}{}", &text[..start], new, &text[end..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::script_editor::lsp::edit_bounds;
+
+    impl UndoHistory {
+        fn record(&mut self, old_text: &str, new_text: &str, cursor: usize, now: Instant) {
+            let (start, old_end, new_end) = edit_bounds(old_text, new_text);
+            let removed = old_text[start..old_end].to_string();
+            self.record_edit(new_text, start, removed, new_text[start..new_end].to_string(), cursor, now);
+        }
+    }
+
+    /// Type `typed` one character at a time at `at`, `delay` apart
+    fn type_text(history: &mut UndoHistory, text: &mut String, at: usize, typed: &str, now: &mut Instant, delay: Duration) {
+        let mut cursor = at;
+        for ch in typed.chars() {
+            let old = text.clone();
+            text.insert(cursor, ch);
+            history.record(&old, text, cursor, *now);
+            cursor += ch.len_utf8();
+            *now += delay;
+        }
+    }
+
+    #[test]
+    fn test_typing_then_undo() {
+        let mut history = UndoHistory::default();
+        let mut text = "fn a() {}".to_string();
+        let mut now = Instant::now();
+        type_text(&mut history, &mut text, 8, "let x", &mut now, Duration::from_millis(50));
+        assert_eq!(text, "fn a() {let x}");
+
+        // A pause ends the step
+        now += UNDO_GROUP_TIMEOUT;
+        type_text(&mut history, &mut text, 13, " = 1;", &mut now, Duration::from_millis(50));
+        // Enter is a step of its own
+        let old = text.clone();
+        text.insert(18, '\n');
+        history.record(&old, &text, 18, now);
+
+        let (undone, selection) = history.undo(&text).unwrap();
+        assert_eq!(undone, "fn a() {let x = 1;}");
+        assert_eq!(selection, 18..18);
+        // Recording the undo's own change is skipped
+        history.record(&text, &undone, 19, now);
+        text = undone;
+
+        let (undone, _) = history.undo(&text).unwrap();
+        assert_eq!(undone, "fn a() {let x}");
+        text = undone;
+        let (undone, selection) = history.undo(&text).unwrap();
+        assert_eq!(undone, "fn a() {}");
+        assert_eq!(selection, 8..8);
+        assert!(history.undo(&undone).is_none());
+
+        let (redone, cursor) = history.redo(&undone).unwrap();
+        assert_eq!(redone, "fn a() {let x}");
+        assert_eq!(cursor, 13..13);
+    }
+
+    #[test]
+    fn test_format_then_undo() {
+        let mut history = UndoHistory::default();
+        let now = Instant::now();
+        let mut text = "fn a(){x();}".to_string();
+
+        let old = text.clone();
+        text.insert(11, ';');
+        history.record(&old, &text, 11, now);
+        // Even a format that only adds a character right after the typing
+        history.start_atomic();
+        let old = text.clone();
+        text.insert(12, ' ');
+        history.record(&old, &text, 12, now);
+        assert_eq!(history.undo(&text).map(|(undone, _)| undone).as_deref(), Some(old.as_str()));
+        history.record(&text, &old, 12, now);
+        text = old;
+
+        history.cursor_moved(3);
+        let formatted = "fn a() {\n    x();;\n}\n";
+        history.record(&text, formatted, 3, now);
+        let (undone, selection) = history.undo(formatted).unwrap();
+        assert_eq!(undone, "fn a(){x();;}");
+        // The cursor goes back to where it was, not over the reformatted text
+        assert_eq!(selection, 3..3);
+
+        // A selection typed over comes back selected
+        let mut history = UndoHistory::default();
+        history.record("let name = 1;", "let x = 1;", 8, now);
+        assert_eq!(history.undo("let x = 1;"), Some(("let name = 1;".to_string(), 4..8)));
+    }
+
+    #[test]
+    fn test_joined_steps_undo_together() {
+        let mut history = UndoHistory::default();
+        let now = Instant::now();
+        let original = "use b;\nuse a;\nfn f(){}\n";
+        let organized = "use a;\nuse b;\nfn f(){}\n";
+        let formatted = "use a;\nuse b;\nfn f() {}\n";
+        history.start_atomic();
+        history.record(original, organized, 0, now);
+        history.join_next();
+        history.record(organized, formatted, 0, now);
+        assert_eq!(history.undo(formatted).map(|(undone, _)| undone).as_deref(), Some(original));
+        assert_eq!(history.redo(original).map(|(redone, _)| redone).as_deref(), Some(formatted));
+
+        // The second change before the first
+        let mut history = UndoHistory::default();
+        history.record("ab cd", "ab CD", 3, now);
+        history.join_next();
+        history.record("ab CD", "ABC CD", 0, now);
+        assert_eq!(history.undo("ABC CD").map(|(undone, _)| undone).as_deref(), Some("ab cd"));
+    }
+}
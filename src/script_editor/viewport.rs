@@ -0,0 +1,72 @@
+//! Which lines are in view, from the scroll offset and line height alone
+//!
+//! Every line is one line height tall, so nothing has to be laid out to know
+//! where a line is: line `n` starts `n * line_height` down the document and
+//! the document is `line_count * line_height` tall. Gutters draw, and the
+//! editor fetches data for, only the lines in view plus a few around them.
+
+use gpui::{px, Pixels};
+use std::ops::Range;
+
+/// Lines past either edge of the view that are prepared too, so scrolling a
+/// little shows them ready
+pub const OVERSCAN_LINES: u32 = 20;
+
+/// Height of a document of `line_count` lines
+pub fn content_height(line_count: usize, line_height: Pixels) -> Pixels {
+    line_height * line_count.max(1) as f32
+}
+
+/// Lines at least partly within a view `height` tall scrolled `scroll_top` down
+pub fn visible_lines(scroll_top: Pixels, height: Pixels, line_height: Pixels) -> Range<u32> {
+    if line_height <= px(0.0) {
+        return 0..0;
+    }
+    let first = (scroll_top / line_height).max(0.0) as u32;
+    first..first + (height / line_height).ceil() as u32 + 1
+}
+
+/// `lines` and `OVERSCAN_LINES` either side, within a document of `line_count` lines
+pub fn overscanned(lines: Range<u32>, line_count: usize) -> Range<u32> {
+    let line_count = line_count.max(1) as u32;
+    let start = lines.start.saturating_sub(OVERSCAN_LINES).min(line_count);
+    start..(lines.end + OVERSCAN_LINES).clamp(start, line_count)
+}
+
+/// Top of line `line` relative to the top of the view
+pub fn line_top(line: u32, scroll_top: Pixels, line_height: Pixels) -> Pixels {
+    line_height * line as f32 - scroll_top
+}
+
+/// Line under a point `y` below the top of the view, clamped to the document
+pub fn line_at(y: Pixels, scroll_top: Pixels, line_height: Pixels, line_count: usize) -> u32 {
+    if line_height <= px(0.0) {
+        return 0;
+    }
+    let line = ((y + scroll_top) / line_height).floor().max(0.0) as usize;
+    line.min(line_count.max(1) - 1) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_end_of_large_document() {
+        let line_count = 100_000;
+        let (height, line_height) = (px(600.0), px(20.0));
+        // Scrolled to the very end: the last screenful is in view
+        let scroll_top = content_height(line_count, line_height) - height;
+        let visible = visible_lines(scroll_top, height, line_height);
+        assert_eq!(visible, 99_970..100_001);
+        assert_eq!(overscanned(visible.clone(), line_count), 99_950..100_000);
+
+        // Clicks map back to the line drawn there, even past the last line
+        for line in 99_970..100_000 {
+            let top = line_top(line, scroll_top, line_height);
+            assert_eq!(line_at(top + px(1.0), scroll_top, line_height, line_count), line);
+            assert_eq!(line_at(top + line_height - px(1.0), scroll_top, line_height, line_count), line);
+        }
+        assert_eq!(line_at(height + px(50.0), scroll_top, line_height, line_count), 99_999);
+    }
+}
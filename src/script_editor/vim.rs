@@ -0,0 +1,1382 @@
+//! Vim emulation: normal, insert and visual modes, and the `:` command line
+//!
+//! `Vim` reads keys one at a time and says what they do to the text; the
+//! editor applies that to the file's input. Offsets are bytes of the text.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VimMode {
+    #[default]
+    Normal,
+    Insert,
+    Visual,
+    VisualLine,
+}
+
+/// Text yanked or deleted into a register
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Register {
+    pub text: String,
+    /// Whole lines, pasted as lines of their own
+    pub linewise: bool,
+}
+
+/// `:s/pattern/replacement/flags` over some lines
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Substitute {
+    /// Zero-based
+    pub lines: Range<u32>,
+    /// Vim's pattern syntax (`\(` groups, `\<` word starts)
+    pub pattern: String,
+    /// `&` and `\1` refer to the match and its groups
+    pub replacement: String,
+    /// Every match on a line rather than the first (`g`)
+    pub global: bool,
+    pub ignore_case: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExCommand {
+    Write,
+    Quit,
+    WriteQuit,
+    Substitute(Substitute),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VimAction {
+    /// Not for Vim: the input handles the key as usual
+    PassThrough,
+    /// Taken, with nothing to do (yet)
+    Nothing,
+    MoveTo(usize),
+    Select(Range<usize>),
+    /// Replace `range` with `text`, then put the cursor at `cursor` of the new text
+    Edit { range: Range<usize>, text: String, cursor: usize },
+    Undo,
+    Redo,
+    Ex(ExCommand),
+    /// Something typed on the command line that isn't a command
+    Error(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Motion {
+    Left,
+    Right,
+    Down,
+    Up,
+    WordStart { big: bool },
+    WordEnd { big: bool },
+    WordBack { big: bool },
+    LineStart,
+    FirstNonBlank,
+    LineEnd,
+    FileStart,
+    FileEnd,
+    /// `f`, `t`, `F` or `T` and the character to find
+    Find { kind: char, ch: char },
+    RepeatFind { reverse: bool },
+    MatchingBracket,
+    ParagraphForward,
+    ParagraphBack,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum MotionKind {
+    Exclusive,
+    Inclusive,
+    Linewise,
+}
+
+impl Motion {
+    fn kind(self) -> MotionKind {
+        match self {
+            Motion::Down | Motion::Up | Motion::FileStart | Motion::FileEnd => MotionKind::Linewise,
+            Motion::WordEnd { .. }
+            | Motion::LineEnd
+            | Motion::Find { .. }
+            | Motion::RepeatFind { .. }
+            | Motion::MatchingBracket => MotionKind::Inclusive,
+            _ => MotionKind::Exclusive,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operator {
+    Delete,
+    Change,
+    Yank,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Target {
+    Motion(Motion),
+    /// The operator typed twice: whole lines
+    Lines,
+    /// `iw`, `a(` and the like
+    Object { around: bool, kind: char },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Command {
+    Move(Motion),
+    Operate(Operator, Target),
+    /// A one-key command like `x`, `p` or `o`
+    Key(char),
+    Replace(char),
+    Redo,
+}
+
+/// What a key does in visual mode
+#[derive(Clone, Copy)]
+enum VisualStep {
+    Motion(Motion),
+    Object(Target),
+    Key(char),
+}
+
+/// A complete normal-mode command
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Parsed {
+    register: Option<char>,
+    count: Option<usize>,
+    command: Command,
+}
+
+/// The keys typed so far, to be read as one command
+struct Keys<'a> {
+    keys: &'a [String],
+    ix: usize,
+}
+
+impl<'a> Keys<'a> {
+    fn next(&mut self) -> Option<&'a str> {
+        let key = self.keys.get(self.ix)?;
+        self.ix += 1;
+        Some(key)
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.keys.get(self.ix).map(String::as_str)
+    }
+
+    /// A count like `12`; a lone `0` is a motion
+    fn count(&mut self) -> Option<usize> {
+        let mut count: Option<usize> = None;
+        while let Some(digit) = self.peek().and_then(|key| key.parse::<usize>().ok().filter(|_| key.len() == 1)) {
+            if count.is_none() && digit == 0 {
+                break;
+            }
+            count = Some(count.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+            self.ix += 1;
+        }
+        count
+    }
+
+    /// The next key as a character, for `f`, `r` and registers
+    fn char(&mut self) -> Option<Result<char, ()>> {
+        let key = self.next()?;
+        let mut chars = key.chars();
+        Some(match (chars.next(), chars.next()) {
+            (Some(ch), None) => Ok(ch),
+            _ if key == "space" => Ok(' '),
+            _ => Err(()),
+        })
+    }
+}
+
+/// `None` while more keys are needed, `Err` when the keys make no command
+fn parse_motion(key: &str, keys: &mut Keys) -> Option<Result<Motion, ()>> {
+    Some(Ok(match key {
+        "h" | "backspace" => Motion::Left,
+        "l" | "space" => Motion::Right,
+        "j" | "enter" => Motion::Down,
+        "k" => Motion::Up,
+        "w" | "W" => Motion::WordStart { big: key == "W" },
+        "e" | "E" => Motion::WordEnd { big: key == "E" },
+        "b" | "B" => Motion::WordBack { big: key == "B" },
+        "0" => Motion::LineStart,
+        "^" => Motion::FirstNonBlank,
+        "$" => Motion::LineEnd,
+        "G" => Motion::FileEnd,
+        "g" => match keys.next()? {
+            "g" => Motion::FileStart,
+            _ => return Some(Err(())),
+        },
+        "f" | "t" | "F" | "T" => match keys.char()? {
+            Ok(ch) => Motion::Find { kind: key.chars().next().unwrap_or('f'), ch },
+            Err(()) => return Some(Err(())),
+        },
+        ";" => Motion::RepeatFind { reverse: false },
+        "," => Motion::RepeatFind { reverse: true },
+        "%" => Motion::MatchingBracket,
+        "}" => Motion::ParagraphForward,
+        "{" => Motion::ParagraphBack,
+        _ => return Some(Err(())),
+    }))
+}
+
+fn parse_object(around: bool, keys: &mut Keys) -> Option<Result<Target, ()>> {
+    Some(match keys.char()? {
+        Ok(kind @ ('w' | 'W' | '"' | '\'' | '`' | '(' | ')' | 'b' | '[' | ']' | '{' | '}' | 'B' | '<' | '>')) => {
+            Ok(Target::Object { around, kind })
+        }
+        _ => Err(()),
+    })
+}
+
+fn parse(keys: &[String]) -> Option<Result<Parsed, ()>> {
+    let mut keys = Keys { keys, ix: 0 };
+    let register = if keys.peek() == Some("\"") {
+        keys.next();
+        match keys.char()? {
+            Ok(register) => Some(register),
+            Err(()) => return Some(Err(())),
+        }
+    } else {
+        None
+    };
+    let count = keys.count();
+    let key = keys.next()?;
+    let operator = match key {
+        "d" => Some(Operator::Delete),
+        "c" => Some(Operator::Change),
+        "y" => Some(Operator::Yank),
+        _ => None,
+    };
+    let command = if let Some(operator) = operator {
+        let motion_count = keys.count();
+        let target_key = keys.next()?;
+        let target = match target_key {
+            _ if target_key == key => Ok(Target::Lines),
+            "i" | "a" => parse_object(target_key == "a", &mut keys)?,
+            _ => parse_motion(target_key, &mut keys)?.map(Target::Motion),
+        };
+        // `2d3w` deletes six words
+        let count = match (count, motion_count) {
+            (None, None) => None,
+            (count, motion_count) => Some(count.unwrap_or(1) * motion_count.unwrap_or(1)),
+        };
+        return Some(target.map(|target| Parsed {
+            register,
+            count,
+            command: Command::Operate(operator, target),
+        }));
+    } else {
+        match key {
+            "r" => match keys.char()? {
+                Ok(ch) => Ok(Command::Replace(ch)),
+                Err(()) => Err(()),
+            },
+            "ctrl-r" => Ok(Command::Redo),
+            "delete" => Ok(Command::Key('x')),
+            _ if key.chars().count() == 1 && "iaIAoOxXDCsSpPuJvVY~:".contains(key) => {
+                Ok(Command::Key(key.chars().next().unwrap_or_default()))
+            }
+            _ => parse_motion(key, &mut keys)?.map(Command::Move),
+        }
+    };
+    Some(command.map(|command| Parsed { register, count, command }))
+}
+
+/// The editor's Vim state: mode, keys of an unfinished command, and registers
+#[derive(Debug, Default)]
+pub struct Vim {
+    mode: VimMode,
+    pending: Vec<String>,
+    /// Where the visual selection started, and its moving end
+    anchor: usize,
+    head: usize,
+    /// What's been typed after `:`, while the command line is open
+    command_line: Option<String>,
+    /// Lines selected when `:` was typed in visual mode, for `'<,'>`
+    visual_lines: Option<Range<u32>>,
+    registers: HashMap<char, Register>,
+    /// The last `f`, `t`, `F` or `T`, for `;` and `,`
+    last_find: Option<(char, char)>,
+}
+
+impl Vim {
+    pub fn mode(&self) -> VimMode {
+        self.mode
+    }
+
+    /// What the status bar shows: the mode and any keys typed so far, or the command line
+    pub fn status(&self) -> String {
+        if let Some(line) = &self.command_line {
+            return format!(":{}", line);
+        }
+        let mode = match self.mode {
+            VimMode::Normal => "NORMAL",
+            VimMode::Insert => "-- INSERT --",
+            VimMode::Visual => "-- VISUAL --",
+            VimMode::VisualLine => "-- VISUAL LINE --",
+        };
+        match self.pending.concat() {
+            pending if pending.is_empty() => mode.to_string(),
+            pending => format!("{} {}", mode, pending),
+        }
+    }
+
+    /// Handle `key`: a character (`"d"`, `"G"`, `"$"`) or a key name
+    /// (`"escape"`, `"enter"`, `"backspace"`, `"delete"`, `"tab"`, `"ctrl-r"`)
+    pub fn handle_key(&mut self, key: &str, text: &str, cursor: usize) -> VimAction {
+        // Arrows, Home and the like keep working as usual
+        let named = key.chars().count() > 1;
+        if named && !matches!(key, "escape" | "enter" | "backspace" | "delete" | "tab" | "space" | "ctrl-r") {
+            return VimAction::PassThrough;
+        }
+        if self.command_line.is_some() {
+            return self.command_line_key(key, text, cursor);
+        }
+        match self.mode {
+            VimMode::Insert => {
+                if key != "escape" {
+                    return VimAction::PassThrough;
+                }
+                self.mode = VimMode::Normal;
+                // The cursor goes back onto the last character typed
+                let line_start = line_start(text, cursor);
+                VimAction::MoveTo(if cursor > line_start { prev_char(text, cursor) } else { cursor })
+            }
+            VimMode::Normal => self.normal_key(key, text, cursor),
+            VimMode::Visual | VimMode::VisualLine => self.visual_key(key, text),
+        }
+    }
+
+    fn normal_key(&mut self, key: &str, text: &str, cursor: usize) -> VimAction {
+        if key == "escape" {
+            return if std::mem::take(&mut self.pending).is_empty() { VimAction::PassThrough } else { VimAction::Nothing };
+        }
+        if key == "tab" {
+            return VimAction::Nothing;
+        }
+        self.pending.push(key.to_string());
+        let parsed = match parse(&self.pending) {
+            None => return VimAction::Nothing,
+            Some(parsed) => parsed,
+        };
+        self.pending.clear();
+        let Ok(Parsed { register, count, command }) = parsed else {
+            return VimAction::Nothing;
+        };
+        let times = count.unwrap_or(1);
+
+        match command {
+            Command::Move(motion) => match self.motion_target(text, cursor, motion, count) {
+                Some(target) => VimAction::MoveTo(clamp_normal(text, target)),
+                None => VimAction::Nothing,
+            },
+            Command::Operate(Operator::Change, Target::Motion(Motion::WordStart { big }))
+                if text[cursor..].starts_with(|ch: char| !ch.is_whitespace()) =>
+            {
+                // `cw` changes to the end of the word, leaving the space after it
+                let end = (1..times).fold(word_end_at(text, cursor, big), |offset, _| word_end_forward(text, offset, big));
+                self.operate(Operator::Change, cursor..inclusive_end(text, end), false, register, text, cursor)
+            }
+            Command::Operate(operator, target) => match self.target_range(text, cursor, target, count) {
+                Some((range, linewise)) => self.operate(operator, range, linewise, register, text, cursor),
+                None => VimAction::Nothing,
+            },
+            Command::Replace(ch) => {
+                let end = (0..times).try_fold(cursor, |offset, _| {
+                    (offset < line_end(text, cursor)).then(|| next_char(text, offset))
+                });
+                match end {
+                    Some(end) => VimAction::Edit {
+                        range: cursor..end,
+                        text: ch.to_string().repeat(times),
+                        cursor: cursor + ch.len_utf8() * (times - 1),
+                    },
+                    None => VimAction::Nothing,
+                }
+            }
+            Command::Redo => VimAction::Redo,
+            Command::Key(key) => self.key_command(key, register, count, text, cursor),
+        }
+    }
+
+    fn key_command(&mut self, key: char, register: Option<char>, count: Option<usize>, text: &str, cursor: usize) -> VimAction {
+        let times = count.unwrap_or(1);
+        let operate = |this: &mut Self, operator, target| match this.target_range(text, cursor, target, count) {
+            Some((range, linewise)) => this.operate(operator, range, linewise, register, text, cursor),
+            None => VimAction::Nothing,
+        };
+        match key {
+            'i' => {
+                self.mode = VimMode::Insert;
+                VimAction::Nothing
+            }
+            'a' => {
+                self.mode = VimMode::Insert;
+                VimAction::MoveTo(if cursor < line_end(text, cursor) { next_char(text, cursor) } else { cursor })
+            }
+            'I' => {
+                self.mode = VimMode::Insert;
+                VimAction::MoveTo(first_non_blank(text, cursor))
+            }
+            'A' => {
+                self.mode = VimMode::Insert;
+                VimAction::MoveTo(line_end(text, cursor))
+            }
+            'o' | 'O' => {
+                self.mode = VimMode::Insert;
+                let indent = &text[line_start(text, cursor)..first_non_blank(text, cursor)];
+                if key == 'o' {
+                    let at = line_end(text, cursor);
+                    VimAction::Edit {
+                        range: at..at,
+                        text: format!("\n{}", indent),
+                        cursor: at + 1 + indent.len(),
+                    }
+                } else {
+                    let at = line_start(text, cursor);
+                    VimAction::Edit {
+                        range: at..at,
+                        text: format!("{}\n", indent),
+                        cursor: at + indent.len(),
+                    }
+                }
+            }
+            'x' if cursor < line_end(text, cursor) => operate(self, Operator::Delete, Target::Motion(Motion::Right)),
+            'X' => operate(self, Operator::Delete, Target::Motion(Motion::Left)),
+            'D' => operate(self, Operator::Delete, Target::Motion(Motion::LineEnd)),
+            'C' => operate(self, Operator::Change, Target::Motion(Motion::LineEnd)),
+            's' => operate(self, Operator::Change, Target::Motion(Motion::Right)),
+            'S' => operate(self, Operator::Change, Target::Lines),
+            'Y' => operate(self, Operator::Yank, Target::Lines),
+            'p' | 'P' => self.paste(key == 'p', register, times, text, cursor),
+            'u' => VimAction::Undo,
+            'J' => join_lines(text, cursor, times.max(2) - 1),
+            '~' => {
+                let end = (0..times).fold(cursor, |offset, _| {
+                    if offset < line_end(text, cursor) { next_char(text, offset) } else { offset }
+                });
+                let toggled: String = text[cursor..end]
+                    .chars()
+                    .map(|ch| {
+                        if ch.is_uppercase() {
+                            ch.to_lowercase().collect::<String>()
+                        } else {
+                            ch.to_uppercase().collect()
+                        }
+                    })
+                    .collect();
+                let new_end = cursor + toggled.len();
+                let mut new_text = text.to_string();
+                new_text.replace_range(cursor..end, &toggled);
+                VimAction::Edit {
+                    range: cursor..end,
+                    text: toggled,
+                    cursor: clamp_normal(&new_text, new_end),
+                }
+            }
+            'v' | 'V' => {
+                self.mode = if key == 'v' { VimMode::Visual } else { VimMode::VisualLine };
+                self.anchor = cursor;
+                self.head = cursor;
+                VimAction::Select(self.visual_range(text))
+            }
+            ':' => {
+                // `3:` works on three lines, like `:'<,'>` after selecting them
+                let line = if count.is_some() {
+                    let line = line_of(text, cursor);
+                    self.visual_lines = Some(line..line + times as u32);
+                    "'<,'>".to_string()
+                } else {
+                    String::new()
+                };
+                self.command_line = Some(line);
+                VimAction::Nothing
+            }
+            _ => VimAction::Nothing,
+        }
+    }
+
+    fn visual_key(&mut self, key: &str, text: &str) -> VimAction {
+        if key == "escape" {
+            self.pending.clear();
+            self.mode = VimMode::Normal;
+            return VimAction::MoveTo(clamp_normal(text, self.head));
+        }
+        self.pending.push(key.to_string());
+        let (count, step) = {
+            let mut keys = Keys { keys: &self.pending, ix: 0 };
+            let count = keys.count();
+            let step = match keys.next() {
+                None => None,
+                Some(first @ ("i" | "a")) => parse_object(first == "a", &mut keys).map(|object| object.map(VisualStep::Object)),
+                Some(first) if first.len() == 1 && "ovVdxXDcsSyYpP:".contains(first) => {
+                    Some(Ok(VisualStep::Key(first.chars().next().unwrap_or_default())))
+                }
+                Some(first) => parse_motion(first, &mut keys).map(|motion| motion.map(VisualStep::Motion)),
+            };
+            (count, step)
+        };
+        let Some(step) = step else {
+            return VimAction::Nothing;
+        };
+        self.pending.clear();
+        let Ok(step) = step else {
+            return VimAction::Nothing;
+        };
+
+        let linewise = self.mode == VimMode::VisualLine;
+        let (start, end) = (self.anchor.min(self.head), self.anchor.max(self.head));
+        let selection = self.visual_range(text);
+        let key = match step {
+            VisualStep::Motion(motion) => {
+                if let Some(head) = self.motion_target(text, self.head, motion, count) {
+                    self.head = clamp_normal(text, head);
+                }
+                return VimAction::Select(self.visual_range(text));
+            }
+            VisualStep::Object(target) => {
+                if let Some((range, _)) = self.target_range(text, self.head, target, count).filter(|(range, _)| !range.is_empty()) {
+                    self.anchor = range.start;
+                    self.head = prev_char(text, range.end);
+                }
+                return VimAction::Select(self.visual_range(text));
+            }
+            VisualStep::Key(key) => key,
+        };
+        match key {
+            'o' => {
+                std::mem::swap(&mut self.anchor, &mut self.head);
+                VimAction::Select(selection)
+            }
+            'v' | 'V' => {
+                let mode = if key == 'v' { VimMode::Visual } else { VimMode::VisualLine };
+                if self.mode == mode {
+                    self.mode = VimMode::Normal;
+                    return VimAction::MoveTo(clamp_normal(text, self.head));
+                }
+                self.mode = mode;
+                VimAction::Select(self.visual_range(text))
+            }
+            ':' => {
+                self.visual_lines = Some(line_of(text, start)..line_of(text, end) + 1);
+                self.mode = VimMode::Normal;
+                self.command_line = Some("'<,'>".to_string());
+                VimAction::MoveTo(clamp_normal(text, self.head))
+            }
+            'p' | 'P' => {
+                self.mode = VimMode::Normal;
+                let Some(pasted) = self.registers.get(&'"').cloned() else {
+                    return VimAction::MoveTo(clamp_normal(text, self.head));
+                };
+                self.store(None, text[selection.clone()].to_string(), linewise, false);
+                let cursor = clamp_normal(&format!("{}{}", &text[..selection.start], pasted.text), selection.start + pasted.text.len());
+                VimAction::Edit {
+                    range: selection,
+                    text: pasted.text,
+                    cursor,
+                }
+            }
+            _ => {
+                self.mode = VimMode::Normal;
+                let operator = match key {
+                    'c' | 's' | 'S' => Operator::Change,
+                    'y' | 'Y' => Operator::Yank,
+                    _ => Operator::Delete,
+                };
+                // The capital letters work on whole lines
+                let linewise = linewise || key.is_ascii_uppercase();
+                let range = if linewise { start..end } else { selection };
+                self.operate(operator, range.clone(), linewise, None, text, range.start)
+            }
+        }
+    }
+
+    fn command_line_key(&mut self, key: &str, text: &str, cursor: usize) -> VimAction {
+        let Some(line) = &mut self.command_line else {
+            return VimAction::PassThrough;
+        };
+        match key {
+            "escape" => self.command_line = None,
+            "backspace" if line.pop().is_none() => self.command_line = None,
+            "enter" => {
+                let line = self.command_line.take().unwrap_or_default();
+                let visual_lines = self.visual_lines.take();
+                return match parse_ex(&line, text, cursor, visual_lines) {
+                    Ok(Ex::Command(command)) => VimAction::Ex(command),
+                    Ok(Ex::GoToLine(line)) => VimAction::MoveTo(first_non_blank(text, line_offset(text, line))),
+                    Ok(Ex::Nothing) => VimAction::Nothing,
+                    Err(e) => VimAction::Error(e),
+                };
+            }
+            "space" => line.push(' '),
+            _ if key.chars().count() == 1 => line.push_str(key),
+            _ => {}
+        }
+        VimAction::Nothing
+    }
+
+    /// Where `motion` goes from `cursor`, `count` times; `None` when it can't
+    fn motion_target(&mut self, text: &str, cursor: usize, motion: Motion, count: Option<usize>) -> Option<usize> {
+        let times = count.unwrap_or(1);
+        let repeat = |step: &dyn Fn(usize) -> Option<usize>| (0..times).try_fold(cursor, |offset, _| step(offset));
+        match motion {
+            Motion::Left => {
+                let start = line_start(text, cursor);
+                (cursor > start).then(|| repeat(&|offset| Some(if offset > start { prev_char(text, offset) } else { offset })))?
+            }
+            Motion::Right => {
+                let end = line_end(text, cursor);
+                (cursor < end).then(|| repeat(&|offset| Some(if offset < end { next_char(text, offset) } else { offset })))?
+            }
+            Motion::Down | Motion::Up => {
+                let line = line_of(text, cursor) as i64;
+                let target = if motion == Motion::Down { line + times as i64 } else { line - times as i64 };
+                let last = last_line(text) as i64;
+                if !(0..=last).contains(&target) {
+                    return None;
+                }
+                let column = text[line_start(text, cursor)..cursor].chars().count();
+                let start = line_offset(text, target as u32);
+                let end = line_end(text, start);
+                Some(text[start..end].char_indices().nth(column).map_or(end, |(ix, _)| start + ix))
+            }
+            Motion::WordStart { big } => repeat(&|offset| Some(word_start_forward(text, offset, big))),
+            Motion::WordEnd { big } => repeat(&|offset| Some(word_end_forward(text, offset, big))),
+            Motion::WordBack { big } => repeat(&|offset| Some(word_start_back(text, offset, big))),
+            Motion::LineStart => Some(line_start(text, cursor)),
+            Motion::FirstNonBlank => Some(first_non_blank(text, cursor)),
+            Motion::LineEnd => {
+                let line = line_of(text, cursor) + times as u32 - 1;
+                let start = line_offset(text, line);
+                let end = line_end(text, start);
+                Some(if end > start { prev_char(text, end) } else { end })
+            }
+            Motion::FileStart | Motion::FileEnd => {
+                let last = last_line(text);
+                let line = match count {
+                    Some(line) => (line as u32).saturating_sub(1).min(last),
+                    None if motion == Motion::FileStart => 0,
+                    None => last,
+                };
+                Some(first_non_blank(text, line_offset(text, line)))
+            }
+            Motion::Find { kind, ch } => {
+                self.last_find = Some((kind, ch));
+                repeat(&|offset| find_in_line(text, offset, kind, ch))
+            }
+            Motion::RepeatFind { reverse } => {
+                let (kind, ch) = self.last_find?;
+                let kind = if !reverse {
+                    kind
+                } else {
+                    match kind {
+                        'f' => 'F',
+                        'F' => 'f',
+                        't' => 'T',
+                        _ => 't',
+                    }
+                };
+                repeat(&|offset| find_in_line(text, offset, kind, ch))
+            }
+            Motion::MatchingBracket => matching_bracket(text, cursor),
+            Motion::ParagraphForward => repeat(&|offset| {
+                let mut at = line_end(text, offset);
+                while at < text.len() {
+                    at += 1;
+                    if line_end(text, at) == at {
+                        return Some(at);
+                    }
+                    at = line_end(text, at);
+                }
+                Some(text.len())
+            }),
+            Motion::ParagraphBack => repeat(&|offset| {
+                let mut at = line_start(text, offset);
+                while at > 0 {
+                    at = line_start(text, at - 1);
+                    if line_end(text, at) == at {
+                        return Some(at);
+                    }
+                }
+                Some(0)
+            }),
+        }
+    }
+
+    /// What an operator applies to, and whether it's the whole lines of that
+    fn target_range(&mut self, text: &str, cursor: usize, target: Target, count: Option<usize>) -> Option<(Range<usize>, bool)> {
+        match target {
+            Target::Lines => {
+                let last = line_of(text, cursor) + count.unwrap_or(1) as u32 - 1;
+                let end = line_end(text, line_offset(text, last.min(last_line(text))));
+                Some((line_start(text, cursor)..end, true))
+            }
+            Target::Object { around, kind } => text_object(text, cursor, around, kind).map(|range| (range, false)),
+            Target::Motion(motion) => {
+                let mut target = self.motion_target(text, cursor, motion, count)?;
+                // `dw` on a line's last word stops at the end of the line
+                if matches!(motion, Motion::WordStart { .. }) {
+                    let line_end = line_end(text, cursor);
+                    if target > line_end && cursor < line_end {
+                        target = line_end;
+                    }
+                }
+                let (start, end) = (cursor.min(target), cursor.max(target));
+                Some(match motion.kind() {
+                    MotionKind::Linewise => (line_start(text, start)..line_end(text, end), true),
+                    MotionKind::Inclusive => (start..inclusive_end(text, end), false),
+                    MotionKind::Exclusive => (start..end, false),
+                })
+            }
+        }
+    }
+
+    /// Apply `operator` to `range`: characters, or for `linewise` the lines
+    /// from `range.start`'s to `range.end`'s
+    fn operate(
+        &mut self,
+        operator: Operator,
+        range: Range<usize>,
+        linewise: bool,
+        register: Option<char>,
+        text: &str,
+        cursor: usize,
+    ) -> VimAction {
+        let taken = if linewise { line_range(text, range.start, range.end) } else { range.clone() };
+        let yanked = if linewise {
+            format!("{}\n", &text[line_start(text, range.start)..line_end(text, range.end)])
+        } else {
+            text[taken.clone()].to_string()
+        };
+        self.store(register, yanked, linewise, operator == Operator::Yank);
+        match operator {
+            // Yanking lines leaves the cursor where it was
+            Operator::Yank => VimAction::MoveTo(if linewise { cursor } else { taken.start.min(cursor) }),
+            Operator::Delete => {
+                let mut new_text = text.to_string();
+                new_text.replace_range(taken.clone(), "");
+                let at = taken.start.min(new_text.len());
+                let cursor = if linewise { first_non_blank(&new_text, at) } else { clamp_normal(&new_text, at) };
+                VimAction::Edit {
+                    range: taken,
+                    text: String::new(),
+                    cursor,
+                }
+            }
+            Operator::Change => {
+                self.mode = VimMode::Insert;
+                // Changed lines keep their indentation and line breaks
+                let range = if linewise {
+                    first_non_blank(text, range.start)..line_end(text, range.end)
+                } else {
+                    taken
+                };
+                VimAction::Edit {
+                    range: range.clone(),
+                    text: String::new(),
+                    cursor: range.start,
+                }
+            }
+        }
+    }
+
+    /// Keep `text` in `register` (or only the unnamed one), and in `0` when yanked
+    fn store(&mut self, register: Option<char>, text: String, linewise: bool, yank: bool) {
+        let value = Register { text, linewise };
+        match register {
+            Some('_') => return,
+            Some(name) if name.is_ascii_uppercase() => {
+                let entry = self.registers.entry(name.to_ascii_lowercase()).or_insert(Register {
+                    text: String::new(),
+                    linewise,
+                });
+                entry.text.push_str(&value.text);
+            }
+            Some(name) if name != '"' => {
+                self.registers.insert(name, value.clone());
+            }
+            _ => {}
+        }
+        if yank {
+            self.registers.insert('0', value.clone());
+        }
+        self.registers.insert('"', value);
+    }
+
+    fn paste(&mut self, after: bool, register: Option<char>, times: usize, text: &str, cursor: usize) -> VimAction {
+        let name = register.map_or('"', |name| name.to_ascii_lowercase());
+        let Some(Register { text: pasted, linewise }) = self.registers.get(&name).cloned() else {
+            return VimAction::Nothing;
+        };
+        let pasted = pasted.repeat(times);
+        if linewise {
+            let end = line_end(text, cursor);
+            let (at, inserted, line) = match (after, end < text.len()) {
+                (true, true) => (end + 1, pasted, end + 1),
+                // After the last line, which has no line break to insert after
+                (true, false) => (end, format!("\n{}", pasted.strip_suffix('\n').unwrap_or(&pasted)), end + 1),
+                (false, _) => (line_start(text, cursor), pasted, line_start(text, cursor)),
+            };
+            let indent = inserted[line - at..].chars().take_while(|ch| *ch == ' ' || *ch == '\t').map(char::len_utf8).sum::<usize>();
+            return VimAction::Edit {
+                range: at..at,
+                text: inserted,
+                cursor: line + indent,
+            };
+        }
+        let at = if after && cursor < line_end(text, cursor) { next_char(text, cursor) } else { cursor };
+        let last = pasted.char_indices().last().map_or(0, |(ix, _)| ix);
+        VimAction::Edit {
+            range: at..at,
+            text: pasted,
+            cursor: at + last,
+        }
+    }
+
+    /// The input's selection for the visual selection
+    fn visual_range(&self, text: &str) -> Range<usize> {
+        let (start, end) = (self.anchor.min(self.head), self.anchor.max(self.head));
+        match self.mode {
+            VimMode::VisualLine => line_range(text, start, end),
+            _ => start..inclusive_end(text, end),
+        }
+    }
+}
+
+enum Ex {
+    Command(ExCommand),
+    /// Zero-based
+    GoToLine(u32),
+    Nothing,
+}
+
+/// Read a command line: an optional line range (`%`, `'<,'>`, `3,7`, `.`, `$`)
+/// and a command
+fn parse_ex(line: &str, text: &str, cursor: usize, visual_lines: Option<Range<u32>>) -> Result<Ex, String> {
+    let line = line.trim();
+    let current = line_of(text, cursor);
+    let last = last_line(text);
+    let address = |address: &str| -> Option<u32> {
+        match address {
+            "." => Some(current),
+            "$" => Some(last),
+            number => number.parse::<u32>().ok().map(|line| line.saturating_sub(1).min(last)),
+        }
+    };
+
+    let (range, command) = if let Some(rest) = line.strip_prefix('%') {
+        (Some(0..last + 1), rest)
+    } else if let Some(rest) = line.strip_prefix("'<,'>") {
+        (Some(visual_lines.unwrap_or(current..current + 1)), rest)
+    } else {
+        let address_len = line
+            .find(|ch: char| !(ch.is_ascii_digit() || ch == ',' || ch == '.' || ch == '$'))
+            .unwrap_or(line.len());
+        let (addresses, rest) = line.split_at(address_len);
+        let range = match addresses.split_once(',') {
+            _ if addresses.is_empty() => None,
+            Some((from, to)) => {
+                let (from, to) = (address(from).ok_or("Invalid range")?, address(to).ok_or("Invalid range")?);
+                Some(from.min(to)..from.max(to) + 1)
+            }
+            None => {
+                let line = address(addresses).ok_or("Invalid range")?;
+                Some(line..line + 1)
+            }
+        };
+        (range, rest)
+    };
+
+    let command = command.trim();
+    let (name, args) = command
+        .find(|ch: char| !ch.is_ascii_alphabetic())
+        .map_or((command, ""), |ix| command.split_at(ix));
+    Ok(match name {
+        "" if command.is_empty() => match range {
+            Some(range) => Ex::GoToLine(range.end - 1),
+            None => Ex::Nothing,
+        },
+        "w" | "write" if args.trim().is_empty() => Ex::Command(ExCommand::Write),
+        "q" | "quit" if matches!(args.trim(), "" | "!") => Ex::Command(ExCommand::Quit),
+        "wq" | "x" | "xit" if matches!(args.trim(), "" | "!") => Ex::Command(ExCommand::WriteQuit),
+        "s" | "substitute" if !args.is_empty() => {
+            let lines = range.unwrap_or(current..current + 1);
+            Ex::Command(ExCommand::Substitute(parse_substitute(args, lines)?))
+        }
+        "noh" | "nohlsearch" => Ex::Nothing,
+        _ => return Err(format!("Not an editor command: {}", command)),
+    })
+}
+
+/// `/pattern/replacement/flags`, with any punctuation as the delimiter
+fn parse_substitute(args: &str, lines: Range<u32>) -> Result<Substitute, String> {
+    let mut chars = args.chars();
+    let delimiter = chars.next().filter(|ch| !ch.is_alphanumeric() && !matches!(ch, '\\' | '"' | ' ' | '|'));
+    let Some(delimiter) = delimiter else {
+        return Err("Invalid substitute delimiter".to_string());
+    };
+    let mut parts: Vec<String> = Vec::new();
+    let mut part = String::new();
+    let mut escaped = false;
+    for ch in chars {
+        if escaped {
+            // `\/` is the delimiter itself; other escapes are the pattern's
+            if ch != delimiter {
+                part.push('\\');
+            }
+            part.push(ch);
+            escaped = false;
+        } else if ch == '\\' {
+            escaped = true;
+        } else if ch == delimiter && parts.len() < 2 {
+            parts.push(std::mem::take(&mut part));
+        } else {
+            part.push(ch);
+        }
+    }
+    if escaped {
+        part.push('\\');
+    }
+    parts.push(part);
+    let mut parts = parts.into_iter();
+    let pattern = parts.next().unwrap_or_default();
+    if pattern.is_empty() {
+        return Err("No previous search pattern".to_string());
+    }
+    let replacement = parts.next().unwrap_or_default();
+    let flags = parts.next().unwrap_or_default();
+    if let Some(flag) = flags.trim().chars().find(|ch| !matches!(ch, 'g' | 'i' | 'I')) {
+        return Err(format!("Unknown substitute flag: {}", flag));
+    }
+    Ok(Substitute {
+        lines,
+        pattern,
+        replacement,
+        global: flags.contains('g'),
+        ignore_case: flags.contains('i'),
+    })
+}
+
+/// Run `:s` on `text`: the new text and how many matches were replaced
+pub fn substitute(text: &str, substitute: &Substitute) -> Result<(String, usize), regex::Error> {
+    let regex = regex::RegexBuilder::new(&vim_pattern_to_regex(&substitute.pattern))
+        .case_insensitive(substitute.ignore_case)
+        .build()?;
+    let replacement = vim_replacement_to_regex(&substitute.replacement);
+    let mut count = 0;
+    let mut new_text = String::with_capacity(text.len());
+    for (line_ix, line) in text.split_inclusive('\n').enumerate() {
+        if !substitute.lines.contains(&(line_ix as u32)) {
+            new_text.push_str(line);
+            continue;
+        }
+        let (content, line_break) = line.strip_suffix('\n').map_or((line, ""), |content| (content, "\n"));
+        let limit = if substitute.global { 0 } else { 1 };
+        count += regex.find_iter(content).take(if limit == 0 { usize::MAX } else { limit }).count();
+        new_text.push_str(&regex.replacen(content, limit, replacement.as_str()));
+        new_text.push_str(line_break);
+    }
+    Ok((new_text, count))
+}
+
+/// Vim's "magic" patterns as `regex` syntax: `\(`, `\|`, `\+` and `\{` are
+/// special and `(`, `|`, `+` and `{` are not; `\<` and `\>` are word edges
+fn vim_pattern_to_regex(pattern: &str) -> String {
+    let mut regex = String::new();
+    let mut chars = pattern.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => match chars.next() {
+                Some(special @ ('(' | ')' | '|' | '+' | '?' | '{' | '}')) => regex.push(special),
+                Some('=') => regex.push('?'),
+                Some('<' | '>') => regex.push_str("\\b"),
+                Some(class @ ('d' | 'D' | 'w' | 'W' | 's' | 'S' | 'n' | 't')) => {
+                    regex.push('\\');
+                    regex.push(class);
+                }
+                Some(literal) => regex.push_str(&regex::escape(&literal.to_string())),
+                None => regex.push_str("\\\\"),
+            },
+            '(' | ')' | '|' | '+' | '?' | '{' | '}' => {
+                regex.push('\\');
+                regex.push(ch);
+            }
+            _ => regex.push(ch),
+        }
+    }
+    regex
+}
+
+/// `&` and `\1` as `${0}` and `${1}`
+fn vim_replacement_to_regex(replacement: &str) -> String {
+    let mut result = String::new();
+    let mut chars = replacement.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '&' => result.push_str("${0}"),
+            '$' => result.push_str("$$"),
+            '\\' => match chars.next() {
+                Some(digit @ '0'..='9') => result.push_str(&format!("${{{}}}", digit)),
+                Some('n' | 'r') => result.push('\n'),
+                Some('t') => result.push('\t'),
+                Some('$') => result.push_str("$$"),
+                Some(other) => result.push(other),
+                None => result.push('\\'),
+            },
+            _ => result.push(ch),
+        }
+    }
+    result
+}
+
+fn line_start(text: &str, offset: usize) -> usize {
+    text[..offset].rfind('\n').map_or(0, |ix| ix + 1)
+}
+
+fn line_end(text: &str, offset: usize) -> usize {
+    text[offset..].find('\n').map_or(text.len(), |ix| offset + ix)
+}
+
+fn first_non_blank(text: &str, offset: usize) -> usize {
+    let start = line_start(text, offset);
+    let line = &text[start..line_end(text, offset)];
+    start + (line.len() - line.trim_start_matches([' ', '\t']).len())
+}
+
+/// Zero-based line of an offset
+fn line_of(text: &str, offset: usize) -> u32 {
+    text[..offset].matches('\n').count() as u32
+}
+
+/// The last line, not counting the empty one after a final line break
+fn last_line(text: &str) -> u32 {
+    line_of(text, text.len()) - u32::from(text.ends_with('\n'))
+}
+
+/// Start of a zero-based line, or the end of the text past the last line
+fn line_offset(text: &str, line: u32) -> usize {
+    if line == 0 {
+        return 0;
+    }
+    text.match_indices('\n').nth(line as usize - 1).map_or(text.len(), |(ix, _)| ix + 1)
+}
+
+fn next_char(text: &str, offset: usize) -> usize {
+    text[offset..].chars().next().map_or(offset, |ch| offset + ch.len_utf8())
+}
+
+fn prev_char(text: &str, offset: usize) -> usize {
+    text[..offset].chars().next_back().map_or(offset, |ch| offset - ch.len_utf8())
+}
+
+/// In normal mode the cursor sits on a character, never past a line's last one
+fn clamp_normal(text: &str, offset: usize) -> usize {
+    let offset = offset.min(text.len());
+    if offset == line_end(text, offset) && offset > line_start(text, offset) {
+        prev_char(text, offset)
+    } else {
+        offset
+    }
+}
+
+/// The end of a range that includes the character at `offset`
+fn inclusive_end(text: &str, offset: usize) -> usize {
+    if offset < line_end(text, offset) { next_char(text, offset) } else { offset }
+}
+
+/// Whole lines from `start`'s to `end`'s, with a line break: the one after
+/// the lines, or before them for the last line of the text
+fn line_range(text: &str, start: usize, end: usize) -> Range<usize> {
+    let (start, end) = (line_start(text, start), line_end(text, end));
+    if end < text.len() {
+        start..end + 1
+    } else {
+        start.saturating_sub(1)..end
+    }
+}
+
+/// 0 for whitespace, 1 for word characters (any non-blank with `big`), 2 for punctuation
+fn char_class(ch: char, big: bool) -> u8 {
+    if ch.is_whitespace() {
+        0
+    } else if big || ch.is_alphanumeric() || ch == '_' {
+        1
+    } else {
+        2
+    }
+}
+
+fn word_start_forward(text: &str, offset: usize, big: bool) -> usize {
+    let mut chars = text[offset..].char_indices().map(|(ix, ch)| (offset + ix, ch));
+    let Some((_, first)) = chars.next() else {
+        return offset;
+    };
+    let class = char_class(first, big);
+    let (mut previous, mut crossed_space) = (first, false);
+    for (ix, ch) in chars {
+        // An empty line counts as a word
+        if ch == '\n' && previous == '\n' {
+            return ix;
+        }
+        match char_class(ch, big) {
+            0 => crossed_space = true,
+            other if crossed_space || other != class => return ix,
+            _ => {}
+        }
+        previous = ch;
+    }
+    text.len()
+}
+
+/// The last character of the word (or run of punctuation) at `offset`
+fn word_end_at(text: &str, offset: usize, big: bool) -> usize {
+    let class = text[offset..].chars().next().map_or(0, |ch| char_class(ch, big));
+    text[offset..]
+        .char_indices()
+        .take_while(|(_, ch)| char_class(*ch, big) == class)
+        .last()
+        .map_or(offset, |(ix, _)| offset + ix)
+}
+
+fn word_end_forward(text: &str, offset: usize, big: bool) -> usize {
+    let mut chars = text[offset..]
+        .char_indices()
+        .map(|(ix, ch)| (offset + ix, ch))
+        .skip(1)
+        .skip_while(|(_, ch)| ch.is_whitespace())
+        .peekable();
+    let Some((mut end, first)) = chars.next() else {
+        return offset;
+    };
+    let class = char_class(first, big);
+    while let Some((ix, _)) = chars.next_if(|(_, ch)| char_class(*ch, big) == class) {
+        end = ix;
+    }
+    end
+}
+
+fn word_start_back(text: &str, offset: usize, big: bool) -> usize {
+    let mut chars = text[..offset].char_indices().rev().skip_while(|(_, ch)| ch.is_whitespace()).peekable();
+    let Some((mut start, first)) = chars.next() else {
+        return 0;
+    };
+    let class = char_class(first, big);
+    while let Some((ix, _)) = chars.next_if(|(_, ch)| char_class(*ch, big) == class) {
+        start = ix;
+    }
+    start
+}
+
+/// `f`/`t` forward and `F`/`T` back, within the line
+fn find_in_line(text: &str, offset: usize, kind: char, ch: char) -> Option<usize> {
+    match kind {
+        'f' | 't' => {
+            // `t` from just before a match looks for the next one
+            let from = next_char(text, if kind == 't' { next_char(text, offset) } else { offset });
+            let found = from.min(line_end(text, offset));
+            let found = found + text[found..line_end(text, offset)].find(ch)?;
+            Some(if kind == 't' { prev_char(text, found) } else { found })
+        }
+        _ => {
+            let until = if kind == 'T' { prev_char(text, offset) } else { offset };
+            let start = line_start(text, offset);
+            let found = start + text[start..until.max(start)].rfind(ch)?;
+            Some(if kind == 'T' { next_char(text, found) } else { found })
+        }
+    }
+}
+
+/// `%`: the bracket matching the first one at or after the cursor on its line
+fn matching_bracket(text: &str, offset: usize) -> Option<usize> {
+    const PAIRS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+    let (at, bracket) = text[offset..line_end(text, offset)]
+        .char_indices()
+        .find(|(_, ch)| PAIRS.iter().any(|(open, close)| ch == open || ch == close))
+        .map(|(ix, ch)| (offset + ix, ch))?;
+    let &(open, close) = PAIRS.iter().find(|(open, close)| bracket == *open || bracket == *close)?;
+    let mut depth = 0usize;
+    if bracket == open {
+        for (ix, ch) in text[at..].char_indices() {
+            if ch == open {
+                depth += 1;
+            } else if ch == close {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(at + ix);
+                }
+            }
+        }
+    } else {
+        for (ix, ch) in text[..=at].char_indices().rev() {
+            if ch == close {
+                depth += 1;
+            } else if ch == open {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(ix);
+                }
+            }
+        }
+    }
+    None
+}
+
+/// `iw`, `a"`, `i(` and the like around the cursor
+fn text_object(text: &str, offset: usize, around: bool, kind: char) -> Option<Range<usize>> {
+    match kind {
+        'w' | 'W' => {
+            let big = kind == 'W';
+            let line = line_start(text, offset)..line_end(text, offset);
+            let class = text[offset..].chars().next().filter(|ch| *ch != '\n').map(|ch| char_class(ch, big))?;
+            let same = |ch: char| char_class(ch, big) == class;
+            let start = offset - text[line.start..offset].chars().rev().take_while(|ch| same(*ch)).map(char::len_utf8).sum::<usize>();
+            let end = offset + text[offset..line.end].chars().take_while(|ch| same(*ch)).map(char::len_utf8).sum::<usize>();
+            if !around {
+                return Some(start..end);
+            }
+            // Taking the spaces after the word, or else those before it
+            let trailing = text[end..line.end].chars().take_while(|ch| ch.is_whitespace()).map(char::len_utf8).sum::<usize>();
+            if trailing > 0 || class == 0 {
+                Some(start..end + trailing)
+            } else {
+                let leading = text[line.start..start].chars().rev().take_while(|ch| ch.is_whitespace()).map(char::len_utf8).sum::<usize>();
+                Some(start - leading..end)
+            }
+        }
+        '"' | '\'' | '`' => {
+            let start = line_start(text, offset);
+            let line = &text[start..line_end(text, offset)];
+            let quotes: Vec<usize> = line.match_indices(kind).map(|(ix, _)| start + ix).collect();
+            // The pair around the cursor, or the first after it
+            let pair = quotes
+                .chunks_exact(2)
+                .find(|pair| pair[1] >= offset)
+                .map(|pair| (pair[0], pair[1]))?;
+            Some(if around { pair.0..pair.1 + 1 } else { pair.0 + 1..pair.1 })
+        }
+        _ => {
+            let (open, close) = match kind {
+                '(' | ')' | 'b' => ('(', ')'),
+                '[' | ']' => ('[', ']'),
+                '{' | '}' | 'B' => ('{', '}'),
+                _ => ('<', '>'),
+            };
+            let mut depth = 0usize;
+            let mut start = None;
+            let from = if text[offset..].starts_with(close) { offset } else { next_char(text, offset) };
+            for (ix, ch) in text[..from].char_indices().rev() {
+                if ch == close {
+                    depth += 1;
+                } else if ch == open {
+                    if depth == 0 {
+                        start = Some(ix);
+                        break;
+                    }
+                    depth -= 1;
+                }
+            }
+            let start = start?;
+            let end = matching_bracket(text, start)?;
+            Some(if around { start..end + 1 } else { start + 1..end })
+        }
+    }
+}
+
+/// `J`: join `joins` following lines onto the cursor's, with a space between
+fn join_lines(text: &str, offset: usize, joins: usize) -> VimAction {
+    let start = line_end(text, offset);
+    let mut end = start;
+    let mut joined = text[..start].to_string();
+    let mut cursor = start;
+    for _ in 0..joins {
+        if end >= text.len() {
+            break;
+        }
+        let next_start = end + 1;
+        let next_end = line_end(text, next_start);
+        let next = text[next_start..next_end].trim_start_matches([' ', '\t']);
+        cursor = joined.len();
+        if !next.is_empty() && !joined.ends_with([' ', '\t']) && !next.starts_with(')') && cursor > 0 && !joined.ends_with('\n') {
+            joined.push(' ');
+        }
+        joined.push_str(next);
+        end = next_end;
+    }
+    if end == start {
+        return VimAction::Nothing;
+    }
+    VimAction::Edit {
+        range: start..end,
+        text: joined[start..].to_string(),
+        cursor,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Type `keys` in normal mode, applying edits and moves, and return the
+    /// text and the cursor
+    fn run(text: &str, cursor: usize, keys: &[&str]) -> (String, usize) {
+        let mut vim = Vim::default();
+        let (mut text, mut cursor) = (text.to_string(), cursor);
+        for key in keys {
+            match vim.handle_key(key, &text, cursor) {
+                VimAction::MoveTo(offset) => cursor = offset,
+                VimAction::Edit { range, text: new, cursor: at } => {
+                    text.replace_range(range, &new);
+                    cursor = at;
+                }
+                VimAction::PassThrough if vim.mode() == VimMode::Insert => {
+                    text.insert_str(cursor, key);
+                    cursor += key.len();
+                }
+                _ => {}
+            }
+        }
+        (text, cursor)
+    }
+
+    #[test]
+    fn test_motions_and_operators() {
+        let text = "fn main() {\n    let x = foo(a, b);\n}\n";
+        assert_eq!(run(text, 0, &["w"]).1, 3);
+        assert_eq!(run(text, 0, &["2", "w"]).1, 7);
+        assert_eq!(run(text, 0, &["$"]).1, 10);
+        assert_eq!(run(text, 0, &["j"]).1, 12);
+        assert_eq!(run(text, 0, &["G"]).1, text.len() - 2);
+        assert_eq!(run(text, 20, &["f", "("]).1, 27);
+
+        // `dw`, then `.`-less `cw` typing a new name
+        assert_eq!(run(text, 0, &["d", "w"]).0, "main() {\n    let x = foo(a, b);\n}\n");
+        assert_eq!(run(text, 3, &["c", "w", "r", "u", "n", "escape"]), ("fn run() {\n    let x = foo(a, b);\n}\n".to_string(), 5));
+        // `di(` inside the call, then `dd` a whole line and paste it back below
+        assert_eq!(run(text, 30, &["d", "i", "("]).0, "fn main() {\n    let x = foo();\n}\n");
+        assert_eq!(run(text, 14, &["d", "d"]).0, "fn main() {\n}\n");
+        assert_eq!(run(text, 0, &["y", "y", "j", "p"]).0, "fn main() {\n    let x = foo(a, b);\nfn main() {\n}\n");
+        assert_eq!(run("one two", 0, &["x", "$", "p"]).0, "ne twoo");
+    }
+
+    #[test]
+    fn test_command_line() {
+        let text = "a-a\nb-a\n";
+        let mut vim = Vim::default();
+        for key in [":", "%", "s", "/", "a", "/", "x", "/", "g"] {
+            assert_eq!(vim.handle_key(key, text, 0), VimAction::Nothing);
+        }
+        assert_eq!(vim.status(), ":%s/a/x/g");
+        let VimAction::Ex(ExCommand::Substitute(sub)) = vim.handle_key("enter", text, 0) else {
+            panic!("expected a substitution");
+        };
+        assert_eq!(sub.lines, 0..2);
+        assert_eq!(substitute(text, &sub).unwrap(), ("x-x\nb-x\n".to_string(), 3));
+
+        let sub = parse_substitute(r"/\(\w\+\)-\(\w\)/\2=\1/", 0..1).unwrap();
+        assert_eq!(substitute(text, &sub).unwrap(), ("a=a\nb-a\n".to_string(), 1));
+        assert!(matches!(parse_ex("wq", text, 0, None), Ok(Ex::Command(ExCommand::WriteQuit))));
+        assert!(matches!(parse_ex("2", text, 0, None), Ok(Ex::GoToLine(1))));
+        assert!(parse_ex("frobnicate", text, 0, None).is_err());
+    }
+}
@@ -0,0 +1,224 @@
+//! Reading VS Code color themes, whose token colors are TextMate scope rules
+//!
+//! Each token kind of `SyntaxTheme` is looked up with a few representative
+//! scopes, the way VS Code would style a token with that scope: the rule
+//! whose selector matches the most scope segments wins, later rules winning
+//! ties.
+
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+
+use super::json_validation::strip_comments;
+use super::syntax_theme::{SyntaxTheme, TokenStyle};
+
+/// Token kinds with the scopes tried for them, most specific first
+const KIND_SCOPES: &[(&str, &[&str])] = &[
+    ("attribute", &["entity.other.attribute-name", "meta.attribute"]),
+    ("boolean", &["constant.language.boolean", "constant.language"]),
+    ("comment", &["comment"]),
+    ("comment.doc", &["comment.block.documentation", "comment"]),
+    ("constant", &["variable.other.constant", "constant.other", "constant"]),
+    ("constructor", &["entity.name.function.constructor", "entity.name.type.class", "entity.name.type"]),
+    ("function", &["entity.name.function", "support.function"]),
+    ("keyword", &["keyword.control", "keyword", "storage.modifier", "storage"]),
+    ("link_uri", &["markup.underline.link"]),
+    ("number", &["constant.numeric"]),
+    ("operator", &["keyword.operator"]),
+    ("preproc", &["entity.name.function.macro", "entity.name.function.preprocessor", "entity.name.function"]),
+    ("property", &["variable.other.property", "support.type.property-name", "variable.other.object.property"]),
+    ("punctuation", &["punctuation"]),
+    ("string", &["string"]),
+    ("string.escape", &["constant.character.escape"]),
+    ("tag", &["entity.name.tag"]),
+    ("title", &["markup.heading", "entity.name.section"]),
+    ("type", &["entity.name.type", "support.type", "support.class"]),
+    ("variable", &["variable.other", "variable"]),
+];
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VsCodeTheme {
+    name: Option<String>,
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    #[serde(default)]
+    colors: HashMap<String, String>,
+    #[serde(default)]
+    token_colors: Vec<TokenColorRule>,
+}
+
+#[derive(Deserialize)]
+struct TokenColorRule {
+    scope: Option<Scopes>,
+    #[serde(default)]
+    settings: RuleSettings,
+}
+
+/// `"a.b, c"` or `["a.b", "c"]`
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Scopes {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RuleSettings {
+    foreground: Option<String>,
+    font_style: Option<String>,
+}
+
+/// A theme from the text of a VS Code color theme file (comments allowed)
+pub fn from_vscode_theme(text: &str) -> anyhow::Result<SyntaxTheme> {
+    let theme: VsCodeTheme = serde_json::from_str(&strip_comments(text))?;
+    let dark = theme.kind.as_deref() != Some("light") && theme.kind.as_deref() != Some("hc-light");
+    let mut result = if dark { SyntaxTheme::dark_plus() } else { SyntaxTheme::light_plus() };
+    result.name = theme.name.clone().unwrap_or_else(|| "VS Code Theme".to_string());
+    result.dark = dark;
+
+    let color = |key: &str| theme.colors.get(key).map(|color| normalize_color(color));
+    let editor = &mut result.editor;
+    for (key, field) in [
+        ("editor.background", &mut editor.background),
+        ("editor.foreground", &mut editor.foreground),
+        ("editorLineNumber.foreground", &mut editor.gutter),
+        ("editorLineNumber.activeForeground", &mut editor.active_gutter),
+        ("editor.lineHighlightBackground", &mut editor.current_line),
+        ("editor.selectionBackground", &mut editor.selection),
+    ] {
+        if let Some(color) = color(key) {
+            *field = color;
+        }
+    }
+    let diagnostics = &mut result.diagnostics;
+    for (key, field) in [
+        ("editorError.foreground", &mut diagnostics.error),
+        ("editorWarning.foreground", &mut diagnostics.warning),
+        ("editorInfo.foreground", &mut diagnostics.info),
+        ("editorHint.foreground", &mut diagnostics.hint),
+    ] {
+        if let Some(color) = color(key) {
+            *field = color;
+        }
+    }
+
+    // A rule without a scope sets the default text color
+    let mut rules: Vec<(Vec<String>, &RuleSettings)> = Vec::new();
+    for rule in &theme.token_colors {
+        match &rule.scope {
+            None => {
+                if let Some(foreground) = &rule.settings.foreground {
+                    result.editor.foreground = normalize_color(foreground);
+                }
+            }
+            Some(Scopes::One(scopes)) => {
+                rules.push((scopes.split(',').map(|scope| scope.trim().to_string()).collect(), &rule.settings))
+            }
+            Some(Scopes::Many(scopes)) => rules.push((scopes.clone(), &rule.settings)),
+        }
+    }
+
+    result.tokens = KIND_SCOPES
+        .iter()
+        .map(|(kind, scopes)| {
+            let style = scopes
+                .iter()
+                .find_map(|scope| style_for_scope(&rules, scope))
+                .unwrap_or_else(|| TokenStyle {
+                    color: result.editor.foreground.clone(),
+                    italic: false,
+                    bold: false,
+                });
+            (kind.to_string(), style)
+        })
+        .collect::<BTreeMap<_, _>>();
+    result.validate()?;
+    Ok(result)
+}
+
+/// Style of a token with `scope`: the best matching rule with a color
+fn style_for_scope(rules: &[(Vec<String>, &RuleSettings)], scope: &str) -> Option<TokenStyle> {
+    let mut best: Option<(usize, &RuleSettings)> = None;
+    for (selectors, settings) in rules {
+        if settings.foreground.is_none() {
+            continue;
+        }
+        for selector in selectors {
+            let Some(depth) = selector_depth(selector, scope) else {
+                continue;
+            };
+            if best.is_none_or(|(best_depth, _)| depth >= best_depth) {
+                best = Some((depth, settings));
+            }
+        }
+    }
+    let (_, settings) = best?;
+    let font_style = settings.font_style.as_deref().unwrap_or_default();
+    Some(TokenStyle {
+        color: normalize_color(settings.foreground.as_deref()?),
+        italic: font_style.contains("italic"),
+        bold: font_style.contains("bold"),
+    })
+}
+
+/// Segments of `scope` matched by a selector that is a prefix of it, e.g.
+/// `keyword` matches `keyword.control` with depth 1
+fn selector_depth(selector: &str, scope: &str) -> Option<usize> {
+    // Descendant selectors (`meta.class entity.name`) style by their last part
+    let selector = selector.split_whitespace().last()?;
+    let matches = scope == selector
+        || scope
+            .strip_prefix(selector)
+            .is_some_and(|rest| rest.starts_with('.'));
+    matches.then(|| selector.split('.').count())
+}
+
+/// `#rgb`, `#rgba`, `#rrggbb` or `#rrggbbaa` as `#rrggbb[aa]`, lowercase
+fn normalize_color(color: &str) -> String {
+    let digits = color.trim().trim_start_matches('#');
+    let digits = if digits.len() == 3 || digits.len() == 4 {
+        digits.chars().flat_map(|digit| [digit, digit]).collect()
+    } else {
+        digits.to_string()
+    };
+    format!("#{}", digits.to_ascii_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_theme() {
+        let theme = from_vscode_theme(
+            r##"{
+                // Comments are common in theme files
+                "name": "Sample",
+                "type": "light",
+                "colors": { "editor.background": "#FAFAFA", "editor.foreground": "#333" },
+                "tokenColors": [
+                    { "settings": { "foreground": "#222222" } },
+                    { "scope": "keyword", "settings": { "foreground": "#0000ff" } },
+                    { "scope": "keyword.control", "settings": { "foreground": "#af00db", "fontStyle": "bold" } },
+                    { "scope": ["comment", "punctuation.definition.comment"], "settings": { "foreground": "#008000", "fontStyle": "italic" } },
+                    { "scope": "entity.name.function, support.function", "settings": { "foreground": "#795e26" } },
+                    { "scope": "keyword.operator", "settings": { "fontStyle": "bold" } },
+                ],
+            }"##,
+        )
+        .unwrap();
+
+        assert_eq!(theme.name, "Sample");
+        assert!(!theme.dark);
+        assert_eq!(theme.editor.background, "#fafafa");
+        assert_eq!(theme.tokens["keyword"].color, "#af00db");
+        assert!(theme.tokens["keyword"].bold);
+        assert!(theme.tokens["comment"].italic);
+        assert_eq!(theme.tokens["function"].color, "#795e26");
+        // `keyword.operator` has no color of its own, so `keyword`'s applies
+        assert_eq!(theme.tokens["operator"].color, "#0000ff");
+        // Nothing styles strings: the default foreground
+        assert_eq!(theme.tokens["string"].color, "#222222");
+    }
+}
@@ -0,0 +1,225 @@
+//! Showing whitespace, trailing whitespace and characters that can't be seen
+//! or pass for others
+
+use std::ops::Range;
+
+use super::settings::RenderWhitespace;
+
+/// Drawn over a space
+pub const SPACE_MARKER: char = '·';
+/// Drawn over a tab
+pub const TAB_MARKER: char = '→';
+
+/// Letters of other scripts that look like Latin ones
+const LATIN_LOOKALIKES: &str = "аеорсухіјѕԁӏАВЕКМНОРСТХІЈЅονΑΒΕΖΗΙΚΜΝΟΡΤΥΧ";
+
+/// Markers for the spaces and tabs `mode` shows, by byte offset
+pub fn whitespace_markers(text: &str, mode: RenderWhitespace) -> Vec<(usize, char)> {
+    let mut markers = Vec::new();
+    if mode == RenderWhitespace::None {
+        return markers;
+    }
+    let mut line_start = 0;
+    for line in text.split_inclusive('\n') {
+        let content = line.trim_end_matches(['\n', '\r']);
+        let trailing_start = content.trim_end_matches([' ', '\t']).len();
+        let bytes = content.as_bytes();
+        for (ix, &byte) in bytes.iter().enumerate() {
+            if byte != b' ' && byte != b'\t' {
+                continue;
+            }
+            let shown = match mode {
+                RenderWhitespace::None => false,
+                RenderWhitespace::All => true,
+                RenderWhitespace::Trailing => ix >= trailing_start,
+                // Everything but single spaces between words
+                RenderWhitespace::Boundary => {
+                    let leading = bytes[..ix].iter().all(|&byte| byte == b' ' || byte == b'\t');
+                    let run = |byte: Option<&u8>| matches!(byte, Some(b' ' | b'\t'));
+                    byte == b'\t'
+                        || leading
+                        || ix >= trailing_start
+                        || run(ix.checked_sub(1).and_then(|prev| bytes.get(prev)))
+                        || run(bytes.get(ix + 1))
+                }
+            };
+            if shown {
+                markers.push((line_start + ix, if byte == b'\t' { TAB_MARKER } else { SPACE_MARKER }));
+            }
+        }
+        line_start += line.len();
+    }
+    markers
+}
+
+/// The spaces and tabs ending each line
+pub fn trailing_whitespace(text: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut line_start = 0;
+    for line in text.split_inclusive('\n') {
+        let content = line.trim_end_matches(['\n', '\r']);
+        let trimmed = content.trim_end_matches([' ', '\t']).len();
+        if trimmed < content.len() {
+            ranges.push(line_start + trimmed..line_start + content.len());
+        }
+        line_start += line.len();
+    }
+    ranges
+}
+
+/// What saving a file does to the whitespace at the end of its lines and of itself
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SaveCleanup {
+    pub trim_trailing_whitespace: bool,
+    /// End a file that isn't empty with a line break
+    pub insert_final_newline: bool,
+    /// Keep at most one line break after the last line
+    pub trim_final_newlines: bool,
+}
+
+/// Replacements by byte range that `cleanup` makes before `text` is saved,
+/// in order and without overlaps. Line breaks added are `\r\n` in files
+/// that use them.
+pub fn save_cleanup_edits(text: &str, cleanup: SaveCleanup) -> Vec<(Range<usize>, String)> {
+    let line_break = if text.contains("\r\n") { "\r\n" } else { "\n" };
+    // Where the run of line breaks (and blank lines, when trimming them) ends
+    // the file; trimming final newlines rewrites it whole
+    let tail_start = if !cleanup.trim_final_newlines {
+        text.len()
+    } else if cleanup.trim_trailing_whitespace {
+        text.trim_end_matches([' ', '\t', '\r', '\n']).len()
+    } else {
+        text.trim_end_matches(['\r', '\n']).len()
+    };
+
+    let mut edits: Vec<(Range<usize>, String)> = Vec::new();
+    if cleanup.trim_trailing_whitespace {
+        edits.extend(
+            trailing_whitespace(text)
+                .into_iter()
+                .filter(|range| range.start < tail_start)
+                .map(|range| (range, String::new())),
+        );
+    }
+    if cleanup.trim_final_newlines {
+        let tail = &text[tail_start..];
+        let keeps_break = tail_start > 0 && (cleanup.insert_final_newline || tail.contains('\n'));
+        let new_tail = if keeps_break { line_break } else { "" };
+        if tail != new_tail {
+            edits.push((tail_start..text.len(), new_tail.to_string()));
+        }
+    } else if cleanup.insert_final_newline && !text.is_empty() && !text.ends_with('\n') {
+        edits.push((text.len()..text.len(), line_break.to_string()));
+    }
+    edits
+}
+
+/// Characters that are invisible or unusual enough to be a mistake or a
+/// trick: zero-width and bidirectional control characters, odd spaces, other
+/// control characters, and lookalikes of Latin letters inside Latin words
+pub fn invisible_characters(text: &str) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for (ix, ch) in text.char_indices() {
+        let suspicious = match ch {
+            '\t' | '\n' | '\r' => false,
+            // A byte order mark only belongs at the start
+            '\u{feff}' => ix > 0,
+            _ => is_invisible(ch) || (LATIN_LOOKALIKES.contains(ch) && in_latin_word(text, ix, ch)),
+        };
+        if suspicious {
+            ranges.push(ix..ix + ch.len_utf8());
+        }
+    }
+    ranges
+}
+
+fn is_invisible(ch: char) -> bool {
+    matches!(
+        ch,
+        '\u{0}'..='\u{1f}'
+            | '\u{7f}'..='\u{a0}'
+            | '\u{ad}'
+            | '\u{34f}'
+            | '\u{61c}'
+            | '\u{115f}'
+            | '\u{1160}'
+            | '\u{180e}'
+            | '\u{2000}'..='\u{200f}'
+            | '\u{2028}'..='\u{202f}'
+            | '\u{205f}'..='\u{206f}'
+            | '\u{3000}'
+            | '\u{3164}'
+            | '\u{fe00}'..='\u{fe0f}'
+            | '\u{ffa0}'
+            | '\u{fff9}'..='\u{fffb}'
+    )
+}
+
+/// Whether the word around the character at `ix` has ASCII letters in it
+fn in_latin_word(text: &str, ix: usize, ch: char) -> bool {
+    let before = text[..ix].chars().rev().take_while(|c| c.is_alphanumeric());
+    let after = text[ix + ch.len_utf8()..].chars().take_while(|c| c.is_alphanumeric());
+    before.chain(after).any(|c| c.is_ascii_alphabetic())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_whitespace_markers() {
+        let text = "\tlet a  = b; \r\nc d\n";
+        let offsets = |mode| -> Vec<usize> {
+            whitespace_markers(text, mode).into_iter().map(|(offset, _)| offset).collect()
+        };
+        assert_eq!(offsets(RenderWhitespace::All), [0, 4, 6, 7, 9, 12, 16]);
+        // Single spaces between words are left out
+        assert_eq!(offsets(RenderWhitespace::Boundary), [0, 6, 7, 12]);
+        assert_eq!(offsets(RenderWhitespace::Trailing), [12]);
+        assert_eq!(whitespace_markers(text, RenderWhitespace::All)[0], (0, TAB_MARKER));
+        assert_eq!(trailing_whitespace(text), [12..13]);
+    }
+
+    #[test]
+    fn test_invisible_characters() {
+        // A zero-width space, and a Cyrillic `а` in a Latin word
+        let text = "\u{feff}let x\u{200b} = \"pаss\"; // привет\n";
+        let found: Vec<&str> = invisible_characters(text).into_iter().map(|range| &text[range]).collect();
+        assert_eq!(found, ["\u{200b}", "а"]);
+    }
+
+    #[test]
+    fn test_save_cleanup_edits() {
+        let cleanup = SaveCleanup {
+            trim_trailing_whitespace: true,
+            insert_final_newline: true,
+            trim_final_newlines: true,
+        };
+        let saved = |text: &str, cleanup: SaveCleanup| {
+            let mut text = text.to_string();
+            for (range, new_text) in save_cleanup_edits(&text, cleanup).into_iter().rev() {
+                text.replace_range(range, &new_text);
+            }
+            text
+        };
+        // No trailing newline, one, and many
+        assert_eq!(saved("fn main() {}", cleanup), "fn main() {}\n");
+        assert_eq!(saved("fn main() {} \n", cleanup), "fn main() {}\n");
+        assert!(save_cleanup_edits("fn main() {}\n", cleanup).is_empty());
+        assert_eq!(saved("a  \nb\n\n  \n\n", cleanup), "a\nb\n");
+        assert_eq!(saved("a\r\n\r\n", cleanup), "a\r\n");
+        assert_eq!(saved("\n\n", cleanup), "");
+        assert!(save_cleanup_edits("", cleanup).is_empty());
+
+        // Each option on its own
+        let only = |insert_final_newline, trim_final_newlines| SaveCleanup {
+            trim_trailing_whitespace: false,
+            insert_final_newline,
+            trim_final_newlines,
+        };
+        assert_eq!(saved("a \n\n", only(false, true)), "a \n");
+        assert_eq!(saved("a", only(false, true)), "a");
+        assert_eq!(saved("a", only(true, false)), "a\n");
+        assert_eq!(saved("a\n\n", only(true, false)), "a\n\n");
+    }
+}
@@ -1,10 +1,71 @@
 //! Workspace panels for Script Editor
 
-use gpui::*;
-use ui::{ActiveTheme, StyledExt, dock::{Panel, PanelEvent}, v_flex, input::InputState};
+use gpui::{prelude::FluentBuilder, *};
+use ui::{
+    button::{Button, ButtonVariants as _},
+    h_flex, ActiveTheme, Sizable as _, StyledExt, dock::{Panel, PanelEvent}, v_flex, input::InputState,
+};
+use std::cell::Cell;
 use std::path::PathBuf;
-use super::text_editor::TextEditor;
-use super::file_explorer::FileExplorer;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use super::git_blame::{relative_time, BlameCommit};
+use super::git_diff::HunkKind;
+use super::settings::WrapMode;
+use super::text_editor::{BlameMode, TextEditor};
+use super::file_explorer::{DraggedEntries, FileExplorer};
+use super::markdown_preview::{block_at_line, render_preview, PreviewBlock};
+use super::minimap::{minimap, MinimapColors, MinimapGeometry, MinimapRows};
+use super::problems_panel::ProblemsPanel;
+use super::viewport;
+
+/// Width of the minimap beside each file
+const MINIMAP_WIDTH: Pixels = px(96.0);
+
+/// Width of the git blame gutter left of each file
+const BLAME_WIDTH: Pixels = px(180.0);
+
+/// Width of the strip marking lines changed since `HEAD`
+const DIFF_GUTTER_WIDTH: Pixels = px(8.0);
+
+/// Width of the strip marking bookmarked lines
+const BOOKMARK_GUTTER_WIDTH: Pixels = px(10.0);
+
+/// Width of the strip holding the code action lightbulb
+const LIGHTBULB_GUTTER_WIDTH: Pixels = px(16.0);
+
+const EDITOR_TEXT_SIZE: Pixels = px(14.0);
+
+/// Characters of line numbers the input's gutter is assumed to take,
+/// padding included, when wrapping at a column
+const LINE_NUMBER_CHARS: usize = 7;
+
+fn editor_font() -> Font {
+    Font {
+        family: "JetBrains Mono".to_string().into(),
+        weight: FontWeight::NORMAL,
+        style: FontStyle::Normal,
+        features: FontFeatures::default(),
+        fallbacks: Some(FontFallbacks::from_fonts(vec!["monospace".to_string()])),
+    }
+}
+
+/// Width of the input that wraps its lines at `column`; the input wraps at
+/// its width when the panel is narrower. The input's wrapper breaks words
+/// longer than a line wherever the line ends, so nothing runs past it.
+fn column_wrap_width(column: u32, window: &Window) -> Pixels {
+    char_width(window) * (column as usize + LINE_NUMBER_CHARS) as f32
+}
+
+/// Advance of a character of the editor font
+fn char_width(window: &Window) -> Pixels {
+    let text_system = window.text_system();
+    let font_id = text_system.resolve_font(&editor_font());
+    text_system
+        .advance(font_id, EDITOR_TEXT_SIZE, 'm')
+        .map_or(EDITOR_TEXT_SIZE * 0.6, |size| size.width)
+}
 
 /// Individual File Panel - wraps a single file's editor
 pub struct FilePanelWrapper {
@@ -13,6 +74,16 @@ pub struct FilePanelWrapper {
     file_path: PathBuf,
     input_state: Entity<InputState>,
     focus_handle: FocusHandle,
+    /// Minimap rows and the document version they were built from
+    minimap_rows: Option<(i32, Rc<MinimapRows>)>,
+    minimap_geometry: Rc<Cell<MinimapGeometry>>,
+    minimap_dragging: bool,
+    /// Height and line height of the gutters as last laid out
+    gutter_geometry: Rc<Cell<(Pixels, Pixels)>>,
+    /// Scroll of the Markdown preview, and the editor line it was last
+    /// scrolled to follow
+    preview_scroll: ScrollHandle,
+    preview_line: Option<u32>,
 }
 
 impl FilePanelWrapper {
@@ -29,30 +100,998 @@ impl FilePanelWrapper {
             file_path,
             input_state,
             focus_handle: cx.focus_handle(),
+            minimap_rows: None,
+            minimap_geometry: Rc::default(),
+            minimap_dragging: false,
+            gutter_geometry: Rc::default(),
+            preview_scroll: ScrollHandle::new(),
+            preview_line: None,
         }
     }
+
+    pub fn file_path(&self) -> &PathBuf {
+        &self.file_path
+    }
+
+    /// Minimap rows for the current text, rebuilt only after an edit
+    fn minimap_rows(&mut self, version: i32, cx: &App) -> Rc<MinimapRows> {
+        match &self.minimap_rows {
+            Some((built, rows)) if *built == version => rows.clone(),
+            _ => {
+                let rows = Rc::new(MinimapRows::new(&self.input_state.read(cx).value()));
+                self.minimap_rows = Some((version, rows.clone()));
+                rows
+            }
+        }
+    }
+
+    /// Scroll so the line under `y` (in window coordinates) is centered in the editor
+    fn scroll_to_minimap_position(&mut self, y: Pixels, cx: &mut Context<Self>) {
+        let Some((_, rows)) = &self.minimap_rows else {
+            return;
+        };
+        let geometry = self.minimap_geometry.get();
+        if geometry.line_height <= px(0.0) {
+            return;
+        }
+        let height = geometry.bounds.size.height;
+        let line = rows.line_at(y - geometry.bounds.top(), height);
+        let visible_lines = height / geometry.line_height;
+        let first_line = (line as f32 - visible_lines / 2.0).max(0.0);
+
+        self.input_state.update(cx, |state, cx| {
+            let offset = state.get_scroll_offset();
+            state.set_scroll_offset(point(offset.x, -(geometry.line_height * first_line)));
+            cx.notify();
+        });
+    }
+
+    /// Scroll the preview to the block at the editor's top line, once the
+    /// preview has been laid out
+    fn follow_in_preview(&mut self, blocks: &[PreviewBlock], top_line: u32) {
+        if self.preview_line == Some(top_line) {
+            return;
+        }
+        let ix = block_at_line(blocks, top_line);
+        let Some(block_bounds) = self.preview_scroll.bounds_for_item(ix) else {
+            return;
+        };
+        let offset = self.preview_scroll.offset();
+        let top = block_bounds.top() - self.preview_scroll.bounds().top() - offset.y;
+        self.preview_scroll.set_offset(point(offset.x, -top));
+        self.preview_line = Some(top_line);
+    }
+
+    /// Lines in view, as of the last layout of the gutters
+    fn visible_lines(&self, scroll_top: Pixels) -> std::ops::Range<u32> {
+        let (height, line_height) = self.gutter_geometry.get();
+        viewport::visible_lines(scroll_top, height, line_height)
+    }
+
+    /// Blame gutter for the lines in view, fetching blame for them as needed
+    fn render_blame_gutter(&self, scroll_top: Pixels, cx: &mut Context<Self>) -> Option<AnyElement> {
+        let editor = self.text_editor.upgrade()?;
+        let editor_ref = editor.read(cx);
+        let mode = editor_ref.blame_mode();
+        if mode == BlameMode::Off || editor_ref.blame_untracked(&self.file_path) {
+            return None;
+        }
+
+        let (_, line_height) = self.gutter_geometry.get();
+        let visible = self.visible_lines(scroll_top);
+        let cursor_line = editor_ref.cursor_line(&self.file_path);
+        let blame = editor_ref.blame_for(&self.file_path);
+        let rows: Vec<(u32, Arc<BlameCommit>, bool)> = visible
+            .clone()
+            .filter(|line| mode == BlameMode::AllLines || Some(*line) == cursor_line)
+            .filter_map(|line| {
+                let blame = blame?;
+                let commit = blame.get(&line)?;
+                // Consecutive lines from one commit are labelled once
+                let continues = mode == BlameMode::AllLines
+                    && line > visible.start
+                    && blame.get(&(line - 1)).is_some_and(|previous| Arc::ptr_eq(previous, commit));
+                Some((line, commit.clone(), continues))
+            })
+            .collect();
+        let popup = editor_ref.blame_popup(&self.file_path).map(|popup| {
+            (popup.line, popup.commit.clone(), popup.message.clone())
+        });
+
+        if !visible.is_empty() {
+            let path = self.file_path.clone();
+            let editor = editor.downgrade();
+            cx.defer(move |cx| {
+                editor.update(cx, |editor, cx| editor.ensure_blame(&path, visible, cx)).ok();
+            });
+        }
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs() as i64)
+            .unwrap_or_default();
+        let geometry = self.gutter_geometry.clone();
+        let row_top = move |line: u32| viewport::line_top(line, scroll_top, line_height);
+
+        Some(
+            div()
+                .id("blame-gutter")
+                .relative()
+                .h_full()
+                .w(BLAME_WIDTH)
+                .flex_none()
+                .overflow_hidden()
+                // Same text size as the editor so rows line up with its lines
+                .text_size(px(14.0))
+                .border_r_1()
+                .border_color(cx.theme().border)
+                .child(
+                    canvas(
+                        move |bounds, window, _cx| geometry.set((bounds.size.height, window.line_height())),
+                        |_, _, _, _| {},
+                    )
+                    .absolute()
+                    .size_full(),
+                )
+                .children(rows.into_iter().map(|(line, commit, continues)| {
+                    let label = if commit.is_uncommitted() {
+                        "Not committed yet".to_string()
+                    } else {
+                        format!("{} • {}", commit.author, relative_time(commit.author_time, now))
+                    };
+                    div()
+                        .id(("blame-line", line as usize))
+                        .absolute()
+                        .left_0()
+                        .right_0()
+                        .top(row_top(line))
+                        .h(line_height)
+                        .px_2()
+                        .flex()
+                        .items_center()
+                        .overflow_hidden()
+                        .whitespace_nowrap()
+                        .text_ellipsis()
+                        .text_xs()
+                        .text_color(cx.theme().muted_foreground)
+                        .cursor_pointer()
+                        .hover(|style| style.bg(cx.theme().accent.opacity(0.1)).text_color(cx.theme().foreground))
+                        .when(!continues, |row| row.child(label))
+                        .on_click(cx.listener(move |this, _, _window, cx| {
+                            if let Some(editor) = this.text_editor.upgrade() {
+                                let path = this.file_path.clone();
+                                editor.update(cx, |editor, cx| editor.show_blame_commit(&path, line, cx));
+                            }
+                        }))
+                }))
+                .children(popup.map(|(line, commit, message)| {
+                    let date = if commit.is_uncommitted() {
+                        "Not committed yet".to_string()
+                    } else {
+                        relative_time(commit.author_time, now)
+                    };
+                    // Deferred so the popup paints over the editor beside the gutter
+                    deferred(
+                        v_flex()
+                            .id("blame-popup")
+                            .absolute()
+                            .left_1()
+                            .top(row_top(line) + line_height)
+                            .w(px(420.0))
+                            .max_h(px(280.0))
+                            .overflow_y_scroll()
+                            .p_2()
+                            .gap_1()
+                            .bg(cx.theme().popover)
+                            .border_1()
+                            .border_color(cx.theme().border)
+                            .rounded(cx.theme().radius)
+                            .shadow_lg()
+                            .text_xs()
+                            .on_mouse_down_out(cx.listener(|this, _, _window, cx| {
+                                if let Some(editor) = this.text_editor.upgrade() {
+                                    editor.update(cx, |editor, cx| editor.dismiss_blame_popup(cx));
+                                }
+                            }))
+                            .child(
+                                div()
+                                    .flex()
+                                    .gap_2()
+                                    .child(
+                                        div()
+                                            .font_family("JetBrains Mono")
+                                            .text_color(cx.theme().accent)
+                                            .child(commit.short_sha().to_string()),
+                                    )
+                                    .child(div().font_semibold().text_color(cx.theme().foreground).child(commit.author.clone()))
+                                    .when(!commit.author_mail.is_empty(), |header| {
+                                        header.child(
+                                            div()
+                                                .text_color(cx.theme().muted_foreground)
+                                                .child(format!("<{}>", commit.author_mail)),
+                                        )
+                                    }),
+                            )
+                            .child(div().text_color(cx.theme().muted_foreground).child(date))
+                            .child(
+                                div()
+                                    .pt_1()
+                                    .whitespace_normal()
+                                    .text_color(cx.theme().foreground)
+                                    .child(message.unwrap_or_else(|| commit.summary.clone())),
+                            ),
+                    )
+                    .with_priority(1)
+                }))
+                .into_any_element(),
+        )
+    }
+
+    /// Markers beside bookmarked lines, while the file has any; clicking one removes it
+    fn render_bookmark_gutter(&self, scroll_top: Pixels, cx: &mut Context<Self>) -> Option<AnyElement> {
+        let editor = self.text_editor.upgrade()?;
+        let visible = self.visible_lines(scroll_top);
+        let lines: Vec<u32> = editor
+            .read(cx)
+            .bookmark_lines(&self.file_path)?
+            .range(visible)
+            .copied()
+            .collect();
+        let (_, line_height) = self.gutter_geometry.get();
+        let row_top = move |line: u32| viewport::line_top(line, scroll_top, line_height);
+
+        Some(
+            div()
+                .id("bookmark-gutter")
+                .relative()
+                .h_full()
+                .w(BOOKMARK_GUTTER_WIDTH)
+                .flex_none()
+                .children(lines.into_iter().map(|line| {
+                    div()
+                        .id(("bookmark", line as usize))
+                        .absolute()
+                        .left_0()
+                        .w_full()
+                        .top(row_top(line))
+                        .h(line_height)
+                        .flex()
+                        .items_center()
+                        .justify_center()
+                        .cursor_pointer()
+                        .child(div().w(px(6.0)).h(px(10.0)).rounded_sm().bg(cx.theme().accent))
+                        .on_click(cx.listener(move |this, _, _window, cx| {
+                            if let Some(editor) = this.text_editor.upgrade() {
+                                let path = this.file_path.clone();
+                                editor.update(cx, |editor, cx| editor.remove_bookmark(&path, line, cx));
+                            }
+                        }))
+                }))
+                .into_any_element(),
+        )
+    }
+
+    /// Each line in view with diagnostics marked by its worst severity, at the
+    /// left of the input's line numbers; clicking a mark moves the cursor there
+    fn render_diagnostic_gutter(
+        &self,
+        markers: &std::collections::BTreeMap<u32, lsp_types::DiagnosticSeverity>,
+        scroll_top: Pixels,
+        window: &Window,
+        cx: &mut Context<Self>,
+    ) -> AnyElement {
+        let visible = self.visible_lines(scroll_top);
+        let (_, line_height) = self.gutter_geometry.get();
+        let row_top = move |line: u32| viewport::line_top(line, scroll_top, line_height);
+
+        div()
+            .absolute()
+            .top_0()
+            .left_0()
+            .h_full()
+            .w(char_width(window) * 2.)
+            .children(markers.range(visible).map(|(&line, &severity)| {
+                let (glyph, color) = ProblemsPanel::severity_glyph(severity, cx);
+                div()
+                    .id(("diagnostic-marker", line as usize))
+                    .absolute()
+                    .left_0()
+                    .w_full()
+                    .top(row_top(line))
+                    .h(line_height)
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .text_xs()
+                    .text_color(color)
+                    .cursor_pointer()
+                    .child(glyph)
+                    .on_click(cx.listener(move |this, _, _window, cx| {
+                        if let Some(editor) = this.text_editor.upgrade() {
+                            let path = this.file_path.clone();
+                            editor.update(cx, |editor, cx| editor.navigate_to(path, line, 0, cx));
+                        }
+                    }))
+            }))
+            .into_any_element()
+    }
+
+    /// Bars beside lines changed since `HEAD`, opening a peek at the original lines when clicked
+    fn render_diff_gutter(&self, scroll_top: Pixels, cx: &mut Context<Self>) -> AnyElement {
+        let (_, line_height) = self.gutter_geometry.get();
+        let visible = self.visible_lines(scroll_top);
+
+        let mut markers = Vec::new();
+        let mut peek = None;
+        if let Some(editor) = self.text_editor.upgrade() {
+            let editor_ref = editor.read(cx);
+            match editor_ref.git_diff_hunks(&self.file_path) {
+                Some(hunks) => {
+                    markers = hunks
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, hunk)| {
+                            // Deletions sit on the boundary above their line
+                            hunk.lines.start <= visible.end && hunk.lines.end.max(hunk.lines.start + 1) > visible.start
+                        })
+                        .map(|(ix, hunk)| (ix, hunk.kind, hunk.lines.clone()))
+                        .collect();
+                    peek = editor_ref
+                        .diff_peek(&self.file_path)
+                        .map(|(ix, hunk)| (ix, hunk.lines.end, hunk.head_text.clone()));
+                }
+                None => {
+                    let path = self.file_path.clone();
+                    let editor = editor.downgrade();
+                    cx.defer(move |cx| {
+                        editor.update(cx, |editor, cx| editor.ensure_git_diff(&path, cx)).ok();
+                    });
+                }
+            }
+        }
+
+        let geometry = self.gutter_geometry.clone();
+        let row_top = move |line: u32| viewport::line_top(line, scroll_top, line_height);
+        let toggle_peek = move |ix: usize| {
+            move |this: &mut Self, _: &ClickEvent, _window: &mut Window, cx: &mut Context<Self>| {
+                if let Some(editor) = this.text_editor.upgrade() {
+                    let path = this.file_path.clone();
+                    editor.update(cx, |editor, cx| editor.toggle_diff_peek(&path, ix, cx));
+                }
+            }
+        };
+
+        div()
+            .id("diff-gutter")
+            .relative()
+            .h_full()
+            .w(DIFF_GUTTER_WIDTH)
+            .flex_none()
+            .text_size(px(14.0))
+            .child(
+                canvas(
+                    move |bounds, window, _cx| geometry.set((bounds.size.height, window.line_height())),
+                    |_, _, _, _| {},
+                )
+                .absolute()
+                .size_full(),
+            )
+            .children(markers.into_iter().map(|(ix, kind, lines)| {
+                let marker = div()
+                    .id(("diff-hunk", ix))
+                    .absolute()
+                    .left_0()
+                    .w_full()
+                    .cursor_pointer()
+                    .on_click(cx.listener(toggle_peek(ix)));
+                match kind {
+                    HunkKind::Deleted => marker
+                        .top(row_top(lines.start) - px(5.0))
+                        .h(px(10.0))
+                        .flex()
+                        .items_center()
+                        .text_size(px(10.0))
+                        .line_height(px(10.0))
+                        .text_color(cx.theme().danger)
+                        .child("▸"),
+                    HunkKind::Added | HunkKind::Modified => {
+                        let color = if kind == HunkKind::Added { cx.theme().success } else { cx.theme().info };
+                        marker
+                            .top(row_top(lines.start))
+                            .h(line_height * lines.len() as f32)
+                            .child(div().ml(px(2.0)).w(px(3.0)).h_full().bg(color))
+                    }
+                }
+            }))
+            .children(peek.map(|(ix, end_line, head_text)| {
+                // Deferred so the peek paints over the editor text
+                deferred(
+                    v_flex()
+                        .id("diff-peek")
+                        .absolute()
+                        .left(DIFF_GUTTER_WIDTH)
+                        .top(row_top(end_line))
+                        .w(px(520.0))
+                        .max_h(px(320.0))
+                        .overflow_y_scroll()
+                        .p_1()
+                        .gap_1()
+                        .bg(cx.theme().popover)
+                        .border_1()
+                        .border_color(cx.theme().border)
+                        .rounded(cx.theme().radius)
+                        .shadow_lg()
+                        .on_mouse_down_out(cx.listener(|this, _, _window, cx| {
+                            if let Some(editor) = this.text_editor.upgrade() {
+                                editor.update(cx, |editor, cx| editor.dismiss_diff_peek(cx));
+                            }
+                        }))
+                        .child(
+                            h_flex()
+                                .gap_1()
+                                .px_1()
+                                .child(
+                                    div()
+                                        .flex_1()
+                                        .text_xs()
+                                        .text_color(cx.theme().muted_foreground)
+                                        .child("HEAD"),
+                                )
+                                .child(
+                                    Button::new("revert_hunk")
+                                        .label("Revert")
+                                        .ghost()
+                                        .xsmall()
+                                        .on_click(cx.listener(move |this, _, window, cx| {
+                                            if let Some(editor) = this.text_editor.upgrade() {
+                                                let path = this.file_path.clone();
+                                                editor.update(cx, |editor, cx| editor.revert_diff_hunk(&path, ix, window, cx));
+                                            }
+                                        })),
+                                )
+                                .child(
+                                    Button::new("stage_hunk")
+                                        .label("Stage")
+                                        .ghost()
+                                        .xsmall()
+                                        .on_click(cx.listener(move |this, _, _window, cx| {
+                                            if let Some(editor) = this.text_editor.upgrade() {
+                                                let path = this.file_path.clone();
+                                                editor.update(cx, |editor, cx| editor.stage_diff_hunk(&path, ix, cx));
+                                            }
+                                        })),
+                                ),
+                        )
+                        .when(head_text.is_empty(), |peek| {
+                            peek.child(
+                                div()
+                                    .px_1()
+                                    .text_xs()
+                                    .text_color(cx.theme().muted_foreground)
+                                    .child("Lines added since the last commit"),
+                            )
+                        })
+                        .children(head_text.lines().map(|line| {
+                            div()
+                                .px_1()
+                                .whitespace_nowrap()
+                                .font_family("JetBrains Mono")
+                                .text_sm()
+                                .text_color(cx.theme().foreground)
+                                .bg(cx.theme().danger.opacity(0.12))
+                                .child(line.to_string())
+                        })),
+                )
+                .with_priority(1)
+            }))
+            .into_any_element()
+    }
+
+    /// Lightbulb beside the cursor's line when it has code actions, opening
+    /// their menu when clicked; `None` for files whose server has none
+    fn render_code_action_gutter(&self, scroll_top: Pixels, cx: &mut Context<Self>) -> Option<AnyElement> {
+        let editor = self.text_editor.upgrade()?;
+        let editor_ref = editor.read(cx);
+        if !editor_ref.code_actions_supported(&self.file_path) {
+            return None;
+        }
+        let (_, line_height) = self.gutter_geometry.get();
+        let actions = editor_ref
+            .code_actions(&self.file_path)
+            .map(|(line, items, selected)| (line, items.to_vec(), selected));
+        let row_top = move |line: u32| viewport::line_top(line, scroll_top, line_height);
+
+        Some(
+            div()
+                .id("code-action-gutter")
+                .relative()
+                .h_full()
+                .w(LIGHTBULB_GUTTER_WIDTH)
+                .flex_none()
+                .text_size(px(14.0))
+                .children(actions.map(|(line, items, selected)| {
+                    let lightbulb = div()
+                        .id("code-action-lightbulb")
+                        .absolute()
+                        .left_0()
+                        .w_full()
+                        .top(row_top(line))
+                        .h(line_height)
+                        .flex()
+                        .items_center()
+                        .justify_center()
+                        .text_xs()
+                        .text_color(cx.theme().warning)
+                        .cursor_pointer()
+                        .child("💡")
+                        .on_click(cx.listener(|this, _, _window, cx| {
+                            if let Some(editor) = this.text_editor.upgrade() {
+                                let path = this.file_path.clone();
+                                editor.update(cx, |editor, cx| editor.toggle_code_action_menu(&path, cx));
+                            }
+                        }));
+                    let menu = selected.map(|selected| {
+                        let mut rows = Vec::new();
+                        for (ix, item) in items.iter().enumerate() {
+                            if ix == 0 || items[ix - 1].group != item.group {
+                                rows.push(
+                                    div()
+                                        .px_2()
+                                        .pt_1()
+                                        .text_xs()
+                                        .text_color(cx.theme().muted_foreground)
+                                        .child(item.group)
+                                        .into_any_element(),
+                                );
+                            }
+                            rows.push(
+                                div()
+                                    .id(("code-action", ix))
+                                    .px_2()
+                                    .py_0p5()
+                                    .rounded(cx.theme().radius)
+                                    .text_sm()
+                                    .whitespace_nowrap()
+                                    .cursor_pointer()
+                                    .text_color(if item.disabled.is_some() {
+                                        cx.theme().muted_foreground
+                                    } else {
+                                        cx.theme().foreground
+                                    })
+                                    .when(ix == selected, |row| row.bg(cx.theme().accent.opacity(0.2)))
+                                    .hover(|row| row.bg(cx.theme().accent.opacity(0.1)))
+                                    .when(item.preferred, |row| row.font_semibold())
+                                    .child(item.title.clone())
+                                    .on_click(cx.listener(move |this, _, window, cx| {
+                                        if let Some(editor) = this.text_editor.upgrade() {
+                                            editor.update(cx, |editor, cx| editor.apply_code_action(ix, window, cx));
+                                        }
+                                    }))
+                                    .into_any_element(),
+                            );
+                        }
+                        // Deferred so the menu paints over the editor text
+                        deferred(
+                            v_flex()
+                                .id("code-action-menu")
+                                .absolute()
+                                .left(LIGHTBULB_GUTTER_WIDTH)
+                                .top(row_top(line) + line_height)
+                                .min_w(px(240.0))
+                                .max_w(px(520.0))
+                                .max_h(px(320.0))
+                                .overflow_y_scroll()
+                                .p_1()
+                                .bg(cx.theme().popover)
+                                .border_1()
+                                .border_color(cx.theme().border)
+                                .rounded(cx.theme().radius)
+                                .shadow_lg()
+                                .on_mouse_down_out(cx.listener(|this, _, _window, cx| {
+                                    if let Some(editor) = this.text_editor.upgrade() {
+                                        editor.update(cx, |editor, cx| editor.dismiss_code_action_menu(cx));
+                                    }
+                                }))
+                                .children(rows),
+                        )
+                        .with_priority(1)
+                    });
+                    div().child(lightbulb).children(menu)
+                }))
+                .into_any_element(),
+        )
+    }
+}
+
+impl FilePanelWrapper {
+    /// Peek Definition or Peek References, open below its line
+    fn render_peek(&self, scroll_top: Pixels, cx: &mut Context<Self>) -> Option<AnyElement> {
+        let editor = self.text_editor.upgrade()?;
+        let peek = editor.read(cx).peek_view(&self.file_path)?;
+        let (_, line_height) = self.gutter_geometry.get();
+        let top = viewport::line_top(peek.line + 1, scroll_top, line_height);
+        let location = format!(
+            "{}:{}",
+            peek.target.path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default(),
+            peek.target.position.line + 1,
+        );
+        let on_editor = |action: fn(&mut TextEditor, &mut Context<TextEditor>)| {
+            cx.listener(move |this: &mut Self, _: &ClickEvent, _window, cx| {
+                if let Some(editor) = this.text_editor.upgrade() {
+                    editor.update(cx, action);
+                }
+            })
+        };
+
+        Some(
+            // Deferred so the peek paints over the editor text
+            deferred(
+                v_flex()
+                    .id("peek")
+                    .absolute()
+                    .left(px(48.0))
+                    .right(px(24.0))
+                    .top(top)
+                    .h(px(240.0))
+                    .bg(cx.theme().popover)
+                    .border_1()
+                    .border_color(cx.theme().accent)
+                    .rounded(cx.theme().radius)
+                    .shadow_lg()
+                    .child(
+                        h_flex()
+                            .gap_1()
+                            .px_2()
+                            .py_0p5()
+                            .border_b_1()
+                            .border_color(cx.theme().border)
+                            .child(div().text_xs().font_semibold().child(peek.title))
+                            .child(
+                                div()
+                                    .flex_1()
+                                    .text_xs()
+                                    .text_color(cx.theme().muted_foreground)
+                                    .child(location),
+                            )
+                            .when(peek.count > 1, |header| {
+                                header
+                                    .child(
+                                        div()
+                                            .text_xs()
+                                            .text_color(cx.theme().muted_foreground)
+                                            .child(format!("{} of {}", peek.index + 1, peek.count)),
+                                    )
+                                    .child(
+                                        Button::new("peek_previous")
+                                            .icon(ui::IconName::ChevronUp)
+                                            .ghost()
+                                            .xsmall()
+                                            .tooltip("Previous")
+                                            .on_click(on_editor(|editor, cx| editor.step_peek(-1, cx))),
+                                    )
+                                    .child(
+                                        Button::new("peek_next")
+                                            .icon(ui::IconName::ChevronDown)
+                                            .ghost()
+                                            .xsmall()
+                                            .tooltip("Next")
+                                            .on_click(on_editor(|editor, cx| editor.step_peek(1, cx))),
+                                    )
+                            })
+                            .child(
+                                Button::new("peek_open")
+                                    .label("Open")
+                                    .ghost()
+                                    .xsmall()
+                                    .on_click(on_editor(TextEditor::open_peeked)),
+                            )
+                            .child(
+                                Button::new("peek_close")
+                                    .icon(ui::IconName::Close)
+                                    .ghost()
+                                    .xsmall()
+                                    .on_click(on_editor(TextEditor::close_peek)),
+                            ),
+                    )
+                    .child(
+                        div().flex_1().min_h_0().child(
+                            ui::input::TextInput::new(&peek.input_state)
+                                .h_full()
+                                .font_family("JetBrains Mono")
+                                .font(editor_font())
+                                .text_size(EDITOR_TEXT_SIZE),
+                        ),
+                    ),
+            )
+            .with_priority(1)
+            .into_any_element(),
+        )
+    }
+}
+
+impl FilePanelWrapper {
+    /// First lines of the scopes around the top of the view, pinned over it;
+    /// clicking one scrolls to it
+    fn render_sticky_scroll(&self, scroll_top: Pixels, window: &Window, cx: &mut Context<Self>) -> Option<AnyElement> {
+        let editor = self.text_editor.upgrade()?;
+        let top_line = self.visible_lines(scroll_top).start;
+        let lines = editor.read(cx).sticky_lines(&self.file_path, top_line);
+        if lines.is_empty() {
+            return None;
+        }
+        let (_, line_height) = self.gutter_geometry.get();
+        let number_width = char_width(window) * LINE_NUMBER_CHARS as f32;
+
+        Some(
+            v_flex()
+                .id("sticky-scroll")
+                .absolute()
+                .top_0()
+                .left_0()
+                .right_0()
+                .overflow_hidden()
+                .bg(cx.theme().background)
+                .border_b_1()
+                .border_color(cx.theme().border)
+                .shadow_sm()
+                .font(editor_font())
+                .text_size(EDITOR_TEXT_SIZE)
+                .children(lines.into_iter().map(|(line, text)| {
+                    h_flex()
+                        .id(("sticky-line", line as usize))
+                        .h(line_height)
+                        .cursor_pointer()
+                        .hover(|style| style.bg(cx.theme().accent.opacity(0.1)))
+                        .on_click(cx.listener(move |this, _, _window, cx| {
+                            if let Some(editor) = this.text_editor.upgrade() {
+                                let path = this.file_path.clone();
+                                editor.update(cx, |editor, cx| editor.navigate_to(path, line, 0, cx));
+                            }
+                        }))
+                        .child(
+                            div()
+                                .w(number_width)
+                                .flex_none()
+                                .pr_3()
+                                .text_right()
+                                .text_color(cx.theme().muted_foreground)
+                                .child((line + 1).to_string()),
+                        )
+                        .child(div().whitespace_nowrap().child(text))
+                }))
+                .into_any_element(),
+        )
+    }
 }
 
 impl EventEmitter<PanelEvent> for FilePanelWrapper {}
 
 impl Render for FilePanelWrapper {
-    fn render(&mut self, _window: &mut Window, _cx: &mut Context<Self>) -> impl IntoElement {
+    fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let wrap_width = self
+            .text_editor
+            .upgrade()
+            .and_then(|editor| match editor.read(cx).wrap_mode() {
+                WrapMode::Column(column) => Some(column_wrap_width(column, window)),
+                _ => None,
+            });
+        let diagnostic_markers = self
+            .text_editor
+            .upgrade()
+            .and_then(|editor| editor.read(cx).line_severities(&self.file_path));
+        let minimap_version = self.text_editor.upgrade().and_then(|editor| {
+            let editor = editor.read(cx);
+            editor
+                .minimap_enabled()
+                .then(|| editor.file_version(&self.file_path))
+                .flatten()
+        });
+        let minimap_rows = minimap_version.map(|version| self.minimap_rows(version, cx));
+        let scroll_top = -self.input_state.read(cx).get_scroll_offset().y;
+        let blame_gutter = self.render_blame_gutter(scroll_top, cx);
+        let diff_gutter = self.render_diff_gutter(scroll_top, cx);
+        let bookmark_gutter = self.render_bookmark_gutter(scroll_top, cx);
+        let code_action_gutter = self.render_code_action_gutter(scroll_top, cx);
+        let diagnostic_gutter = diagnostic_markers
+            .as_ref()
+            .map(|(lines, _)| self.render_diagnostic_gutter(lines, scroll_top, window, cx));
+        let peek = self.render_peek(scroll_top, cx);
+        let sticky_scroll = self.render_sticky_scroll(scroll_top, window, cx);
+        let markdown_preview = self
+            .text_editor
+            .upgrade()
+            .and_then(|editor| editor.read(cx).markdown_preview(&self.file_path));
+        if let Some(blocks) = &markdown_preview {
+            let top_line = self.visible_lines(scroll_top).start;
+            self.follow_in_preview(blocks, top_line);
+        }
+        let markdown_preview_shown = markdown_preview.is_some();
+
+        // Inlay hints and the lexer's colors for large files are asked for as
+        // the view scrolls and the text changes, semantic tokens as it changes
+        let visible = self.visible_lines(scroll_top);
+        if let Some(editor) = self.text_editor.upgrade() {
+            let path = self.file_path.clone();
+            let editor = editor.downgrade();
+            cx.defer(move |cx| {
+                editor
+                    .update(cx, |editor, cx| {
+                        editor.ensure_visible_highlights(&path, visible.clone(), cx);
+                        editor.ensure_inlay_hints(&path, visible, cx);
+                        editor.ensure_semantic_tokens(&path, cx);
+                    })
+                    .ok();
+            });
+        }
+
         div()
+            .relative()
             .size_full()
+            // Files dragged from the explorer leave a reference to them at the cursor
+            .on_drop(cx.listener(|this, dragged: &DraggedEntries, window, cx| {
+                if let Some(editor) = this.text_editor.upgrade() {
+                    let path = this.file_path.clone();
+                    editor.update(cx, |editor, cx| editor.insert_file_references(&path, &dragged.paths, window, cx));
+                }
+            }))
+            // Alt+Click adds a cursor: the current cursor is kept as an extra one
+            // before the input moves it to the click. Alt+drag selects a box instead.
+            .capture_any_mouse_down(cx.listener(|this, event: &MouseDownEvent, _window, cx| {
+                if event.button != MouseButton::Left || !event.modifiers.alt {
+                    return;
+                }
+                if let Some(editor) = this.text_editor.upgrade() {
+                    let path = this.file_path.clone();
+                    editor.update(cx, |editor, cx| editor.begin_add_cursor(&path, cx));
+                }
+            }))
+            // Edits of read-only files, the code action menu, undo, indentation and
+            // auto-closed brackets and quotes are handled before the input sees the keystroke
+            .capture_key_down(cx.listener(|this, event: &KeyDownEvent, window, cx| {
+                let Some(editor) = this.text_editor.upgrade() else {
+                    return;
+                };
+                let path = this.file_path.clone();
+                // Keys typed in a peek only go to the file's own input when it has focus
+                let in_file = this.input_state.focus_handle(cx).is_focused(window);
+                let handled = editor.update(cx, |editor, cx| {
+                    if !in_file {
+                        return editor.handle_peek_key(&path, &event.keystroke, cx);
+                    }
+                    editor.handle_read_only_key(&path, &event.keystroke, cx)
+                        || editor.handle_code_action_key(&path, &event.keystroke, window, cx)
+                        || editor.handle_signature_help_key(&path, &event.keystroke, cx)
+                        || editor.handle_peek_key(&path, &event.keystroke, cx)
+                        || editor.handle_vim_key(&path, &event.keystroke, window, cx)
+                        || editor.handle_undo_key(&path, &event.keystroke, window, cx)
+                        || editor.handle_snippet_key(&path, &event.keystroke, window, cx)
+                        || editor.handle_indent_key(&path, &event.keystroke, window, cx)
+                        || editor.handle_enter_key(&path, &event.keystroke, window, cx)
+                        || editor.handle_auto_pair(&path, &event.keystroke, window, cx)
+                });
+                if handled {
+                    cx.stop_propagation();
+                }
+            }))
+            .on_mouse_up(MouseButton::Left, cx.listener(|this, _event: &MouseUpEvent, window, cx| {
+                this.minimap_dragging = false;
+                if let Some(editor) = this.text_editor.upgrade() {
+                    let path = this.file_path.clone();
+                    editor.update(cx, |editor, cx| editor.end_add_cursor(&path, window, cx));
+                }
+            }))
+            // Ctrl+Click (Cmd+Click on macOS): the click has already moved the
+            // cursor onto the symbol, so resolve the definition from there.
+            // Inputs with their own definition provider handle the click themselves.
+            .on_mouse_up(MouseButton::Left, cx.listener(|this, event: &MouseUpEvent, _window, cx| {
+                if !event.modifiers.secondary() || this.input_state.read(cx).lsp.definition_provider.is_some() {
+                    return;
+                }
+                if let Some(editor) = this.text_editor.upgrade() {
+                    let path = this.file_path.clone();
+                    editor.update(cx, |editor, cx| editor.go_to_definition_in(&path, cx));
+                }
+            }))
+            .flex()
+            .children(blame_gutter)
+            .children(bookmark_gutter)
+            .child(diff_gutter)
+            .children(code_action_gutter)
             .child(
-                ui::input::TextInput::new(&self.input_state)
+                div()
+                    .relative()
+                    .flex()
                     .h_full()
-                    .w_full()
-                    .font_family("JetBrains Mono")
-                    .font(gpui::Font {
-                        family: "JetBrains Mono".to_string().into(),
-                        weight: gpui::FontWeight::NORMAL,
-                        style: gpui::FontStyle::Normal,
-                        features: gpui::FontFeatures::default(),
-                        fallbacks: Some(gpui::FontFallbacks::from_fonts(vec!["monospace".to_string()])),
-                    })
-                    .text_size(px(14.0))
+                    .flex_1()
+                    .min_w_0()
+                    .child(
+                        ui::input::TextInput::new(&self.input_state)
+                            .h_full()
+                            .flex_1()
+                            .when_some(wrap_width, |input, width| input.max_w(width))
+                            .font_family("JetBrains Mono")
+                            .font(editor_font())
+                            .text_size(EDITOR_TEXT_SIZE)
+                    )
+                    .children(diagnostic_gutter)
+                    .children(sticky_scroll),
             )
+            .children(minimap_rows.map(|rows| {
+                let colors = MinimapColors {
+                    text: cx.theme().foreground.opacity(0.45),
+                    keyword: cx.theme().info.opacity(0.7),
+                    string: cx.theme().success.opacity(0.7),
+                    comment: cx.theme().muted_foreground.opacity(0.5),
+                    number: cx.theme().warning.opacity(0.7),
+                    viewport: cx.theme().accent.opacity(0.25),
+                };
+                // Same text size as the editor so the viewport uses its line height
+                div()
+                    .id("minimap")
+                    .h_full()
+                    .w(MINIMAP_WIDTH)
+                    .flex_none()
+                    .text_size(px(14.0))
+                    .border_l_1()
+                    .border_color(cx.theme().border)
+                    .cursor_pointer()
+                    .on_mouse_down(MouseButton::Left, cx.listener(|this, event: &MouseDownEvent, _window, cx| {
+                        this.minimap_dragging = true;
+                        this.scroll_to_minimap_position(event.position.y, cx);
+                        cx.stop_propagation();
+                    }))
+                    .on_mouse_move(cx.listener(|this, event: &MouseMoveEvent, _window, cx| {
+                        if this.minimap_dragging && event.pressed_button == Some(MouseButton::Left) {
+                            this.scroll_to_minimap_position(event.position.y, cx);
+                        }
+                    }))
+                    .child(minimap(rows, scroll_top, colors, self.minimap_geometry.clone()))
+            }))
+            .children(markdown_preview.map(|blocks| {
+                // Clicking a heading moves the editor to it
+                let editor = self.text_editor.clone();
+                let path = self.file_path.clone();
+                let on_heading = Rc::new(move |line: u32, _window: &mut Window, cx: &mut App| {
+                    editor
+                        .update(cx, |editor, cx| editor.navigate_to(path.clone(), line, 0, cx))
+                        .ok();
+                });
+                div()
+                    .h_full()
+                    .w(relative(0.5))
+                    .flex_none()
+                    .border_l_1()
+                    .border_color(cx.theme().border)
+                    .child(render_preview(&blocks, &self.preview_scroll, on_heading, cx))
+            }))
+            .children(diagnostic_markers.map(|(lines, lines_count)| {
+                // The same marks placed along the editor's height, like an overview ruler
+                div()
+                    .absolute()
+                    .top_0()
+                    .bottom_0()
+                    .right_0()
+                    // Beside the editor, not the preview
+                    .when(markdown_preview_shown, |ruler| ruler.right(relative(0.5)))
+                    .w(px(4.0))
+                    .children(lines.into_iter().map(|(line, severity)| {
+                        let color = if severity == lsp_types::DiagnosticSeverity::ERROR {
+                            cx.theme().danger
+                        } else if severity == lsp_types::DiagnosticSeverity::WARNING {
+                            cx.theme().warning
+                        } else {
+                            cx.theme().info
+                        };
+                        div()
+                            .absolute()
+                            .left_0()
+                            .w_full()
+                            .h(px(3.0))
+                            .top(relative(line as f32 / lines_count as f32))
+                            .bg(color)
+                    }))
+            }))
+            .children(peek)
     }
 }
 
@@ -67,12 +1106,25 @@ impl Panel for FilePanelWrapper {
         "file"
     }
 
-    fn title(&self, _window: &Window, _cx: &App) -> AnyElement {
+    fn title(&self, _window: &Window, cx: &App) -> AnyElement {
         let filename = self.file_path.file_name()
             .and_then(|n| n.to_str())
             .unwrap_or("untitled")
             .to_string();
-        filename.into_any_element()
+        let (is_modified, is_deleted) = self.text_editor
+            .upgrade()
+            .map(|editor| {
+                let editor = editor.read(cx);
+                (editor.is_file_modified(&self.file_path), editor.is_deleted_on_disk(&self.file_path))
+            })
+            .unwrap_or_default();
+        if is_deleted {
+            format!("{filename} (deleted)").into_any_element()
+        } else if is_modified {
+            format!("● {filename}").into_any_element()
+        } else {
+            filename.into_any_element()
+        }
     }
 }
 
@@ -0,0 +1,97 @@
+//! Where a file's project starts, which its language server is rooted at
+//!
+//! The nearest folder up from a file with a project manifest is its project,
+//! except that a Cargo package inside a Cargo workspace belongs to the
+//! workspace, so all of its crates share one rust-analyzer. A file with no
+//! manifest above it belongs to its repository, or else to its own folder.
+//! Nothing above a repository's root is looked at.
+
+use std::path::{Path, PathBuf};
+
+/// Files that make the folder they're in a project
+pub const PROJECT_MARKERS: [&str; 10] = [
+    "Cargo.toml",
+    "package.json",
+    "tsconfig.json",
+    "pyproject.toml",
+    "setup.py",
+    "go.mod",
+    "build.zig",
+    "compile_commands.json",
+    "CMakeLists.txt",
+    ".luarc.json",
+];
+
+pub fn workspace_root(path: &Path) -> PathBuf {
+    let start = if path.is_dir() { path } else { path.parent().unwrap_or(path) };
+    let repository = start.ancestors().position(|dir| dir.join(".git").exists());
+    let ancestors: Vec<&Path> = match repository {
+        Some(ix) => start.ancestors().take(ix + 1).collect(),
+        None => start.ancestors().collect(),
+    };
+    let project = ancestors
+        .iter()
+        .position(|dir| PROJECT_MARKERS.iter().any(|marker| dir.join(marker).is_file()));
+    let Some(project) = project else {
+        return match repository {
+            Some(ix) => ancestors[ix].to_path_buf(),
+            None => start.to_path_buf(),
+        };
+    };
+    if ancestors[project].join("Cargo.toml").is_file() {
+        if let Some(workspace) = ancestors[project..]
+            .iter()
+            .find(|dir| is_cargo_workspace(&dir.join("Cargo.toml")))
+        {
+            return workspace.to_path_buf();
+        }
+    }
+    ancestors[project].to_path_buf()
+}
+
+fn is_cargo_workspace(manifest: &Path) -> bool {
+    std::fs::read_to_string(manifest).is_ok_and(|text| text.lines().any(|line| line.trim() == "[workspace]"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("workspace-root-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_cargo_workspace_members_share_its_root() {
+        let workspace = scratch("cargo");
+        let member = workspace.join("crates/engine");
+        fs::create_dir_all(member.join("src")).unwrap();
+        fs::write(workspace.join("Cargo.toml"), "[workspace]\nmembers = [\"crates/*\"]\n").unwrap();
+        fs::write(member.join("Cargo.toml"), "[package]\nname = \"engine\"\n").unwrap();
+        fs::write(member.join("src/lib.rs"), "").unwrap();
+
+        assert_eq!(workspace_root(&member.join("src/lib.rs")), workspace);
+        // A package on its own is its own root
+        fs::write(workspace.join("Cargo.toml"), "[package]\nname = \"game\"\n").unwrap();
+        assert_eq!(workspace_root(&member.join("src/lib.rs")), member);
+        fs::remove_dir_all(&workspace).unwrap();
+    }
+
+    #[test]
+    fn test_repository_bounds_the_search() {
+        let outer = scratch("repo");
+        let repository = outer.join("repo");
+        fs::create_dir_all(repository.join(".git")).unwrap();
+        fs::create_dir_all(repository.join("scripts")).unwrap();
+        fs::create_dir_all(repository.join("web/src")).unwrap();
+        fs::write(outer.join("package.json"), "{}").unwrap();
+        fs::write(repository.join("web/package.json"), "{}").unwrap();
+
+        assert_eq!(workspace_root(&repository.join("scripts/build.py")), repository);
+        assert_eq!(workspace_root(&repository.join("web/src/main.ts")), repository.join("web"));
+        fs::remove_dir_all(&outer).unwrap();
+    }
+}
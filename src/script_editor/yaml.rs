@@ -0,0 +1,86 @@
+//! YAML editing: keeping indentation on Enter and warning about tabs
+
+use lsp_types::DiagnosticSeverity;
+use std::ops::Range;
+
+use super::lsp::FileDiagnostic;
+
+/// Spaces a mapping's values are indented by under their key
+const YAML_INDENT: usize = 2;
+
+/// Replacement for pressing Enter at `cursor`: the byte range to replace and its new text.
+///
+/// The new line keeps the indentation of the current one, goes one level
+/// deeper after a `key:`, and continues a list with `- `. Enter on an empty
+/// list item ends the list instead, removing the dash.
+pub fn newline_edit(text: &str, cursor: usize) -> (Range<usize>, String) {
+    let line_start = text[..cursor].rfind('\n').map_or(0, |ix| ix + 1);
+    let line = &text[line_start..cursor];
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    let content = line[indent..].trim_end();
+
+    let item = content == "-" || content.starts_with("- ");
+    if item && content == "-" && text[cursor..].split('\n').next().is_some_and(|rest| rest.trim().is_empty()) {
+        return (line_start + indent..cursor, String::new());
+    }
+
+    // What follows on the next line lines up with the item's content, not its dash
+    let content_indent = if item { indent + 2 } else { indent };
+    let next_line = if content.ends_with(':') {
+        " ".repeat(content_indent + YAML_INDENT)
+    } else if item {
+        format!("{}- ", " ".repeat(indent))
+    } else {
+        " ".repeat(indent)
+    };
+    (cursor..cursor, format!("\n{}", next_line))
+}
+
+/// Warnings for tabs in the indentation of lines, which YAML forbids
+pub fn tab_indentation_warnings(text: &str) -> Vec<FileDiagnostic> {
+    let mut warnings = Vec::new();
+    let mut line_start = 0;
+    for line in text.split_inclusive('\n') {
+        let indent = line.len() - line.trim_start_matches([' ', '\t']).len();
+        if let Some(tab) = line[..indent].find('\t') {
+            warnings.push(FileDiagnostic {
+                range: line_start + tab..line_start + indent,
+                severity: DiagnosticSeverity::WARNING,
+                message: "Tabs are not allowed as indentation in YAML".to_string(),
+                source: Some("yaml".to_string()),
+                code: None,
+            });
+        }
+        line_start += line.len();
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_newline_edit() {
+        let apply = |text: &str| {
+            let (range, new_text) = newline_edit(text, text.len());
+            let mut text = text.to_string();
+            text.replace_range(range, &new_text);
+            text
+        };
+        assert_eq!(apply("  name: x"), "  name: x\n  ");
+        assert_eq!(apply("server:"), "server:\n  ");
+        assert_eq!(apply("  - a"), "  - a\n  - ");
+        assert_eq!(apply("- item:"), "- item:\n    ");
+        // An empty item ends the list
+        assert_eq!(apply("  - a\n  - "), "  - a\n  ");
+    }
+
+    #[test]
+    fn test_tab_indentation_warnings() {
+        let warnings = tab_indentation_warnings("a:\n\tb: 1\n  c: \"\t\"\n");
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].range, 3..4);
+        assert_eq!(warnings[0].severity, DiagnosticSeverity::WARNING);
+    }
+}